@@ -0,0 +1,108 @@
+// `BytesReadExt` for a futures `AsyncRead`, e.g. a socket on an async runtime. A stream can't seek, so nothing is
+// checked against its length up front: running out of data part way through a value fails with NotEnoughData, and
+// `read_vec` grows its buffer as the bytes arrive rather than trusting the amount it's asked for.
+//
+// The methods are `async fn`s, so a generic caller can't require their futures to be `Send`. For concrete readers
+// they are whenever the reader is.
+
+use std::io::ErrorKind;
+
+use futures_util::io::{AsyncRead, AsyncReadExt};
+
+use crate::BytesError;
+
+macro_rules! define_integral_r_async {
+	($ty:ty, $bytes:expr) => {
+		paste::item! {
+		async fn [<read_$ty>](
+			&mut self,
+		) -> Result<$ty, BytesError> {
+			Ok($ty::from_be_bytes(self.read_n_bytes::<$bytes>().await?))
+		}
+		}
+	};
+}
+
+#[allow(async_fn_in_trait)]
+pub trait AsyncBytesReadExt: AsyncRead + Unpin {
+	define_integral_r_async!(i8, 1);
+	define_integral_r_async!(u8, 1);
+
+	define_integral_r_async!(i16, 2);
+	define_integral_r_async!(u16, 2);
+
+	define_integral_r_async!(i32, 4);
+	define_integral_r_async!(u32, 4);
+
+	define_integral_r_async!(i64, 8);
+	define_integral_r_async!(u64, 8);
+
+	/// Everything up to the end of the stream.
+	async fn read_to_vec(&mut self) -> Result<Vec<u8>, BytesError> {
+		let mut bytes = Vec::new();
+		self.read_to_end(&mut bytes).await?;
+		Ok(bytes)
+	}
+
+	async fn read_n_bytes<const N: usize>(&mut self) -> Result<[u8; N], BytesError> {
+		let mut bytes = [0u8; N];
+		self.read_exact(&mut bytes).await.map_err(|err| match err.kind() {
+			ErrorKind::UnexpectedEof => BytesError::NotEnoughData,
+			_ => BytesError::IO(err),
+		})?;
+		Ok(bytes)
+	}
+
+	async fn read_vec(&mut self, len: usize) -> Result<Vec<u8>, BytesError> {
+		let mut bytes = Vec::new();
+		AsyncReadExt::take(&mut *self, len as u64)
+			.read_to_end(&mut bytes)
+			.await?;
+		if bytes.len() < len {
+			return Err(BytesError::NotEnoughData);
+		}
+		Ok(bytes)
+	}
+
+	#[deprecated(note = "use `read_vec`")]
+	async fn read_n_bytes_vec(&mut self, amount: usize) -> Result<Vec<u8>, BytesError> {
+		self.read_vec(amount).await
+	}
+
+	async fn read_f32(&mut self) -> Result<f32, BytesError> {
+		Ok(f32::from_bits(self.read_u32().await?))
+	}
+
+	async fn read_f64(&mut self) -> Result<f64, BytesError> {
+		Ok(f64::from_bits(self.read_u64().await?))
+	}
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> AsyncBytesReadExt for R {}
+
+#[cfg(test)]
+mod tests {
+	use futures_executor::block_on;
+	use futures_util::io::Cursor;
+
+	use super::*;
+
+	#[test]
+	fn read_from_a_stream() {
+		block_on(async {
+			let mut buffer = Cursor::new([0xCA, 0xFE, 0xBA, 0xBE, 0xFF, 0x3F, 0xF0, 0, 0, 0, 0, 0, 0, 1, 2, 3]);
+			assert_eq!(buffer.read_u32().await.unwrap(), 0xCAFEBABE);
+			assert_eq!(buffer.read_i8().await.unwrap(), -1);
+			assert_eq!(buffer.read_f64().await.unwrap(), 1.0);
+			assert_eq!(buffer.read_vec(2).await.unwrap(), [1, 2]);
+			assert!(matches!(buffer.read_u16().await, Err(BytesError::NotEnoughData)));
+
+			// Asking for more than there is doesn't allocate for it.
+			let mut buffer = Cursor::new([1, 2, 3]);
+			assert!(matches!(
+				buffer.read_vec(usize::MAX).await,
+				Err(BytesError::NotEnoughData)
+			));
+		});
+	}
+}
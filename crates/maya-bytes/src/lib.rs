@@ -1,17 +1,27 @@
 #![feature(seek_stream_len)]
 
+#[cfg(feature = "async")]
+mod async_read;
 mod macros;
 
-use std::io::{Read, Seek, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
+use maya_mutf8::MUTFError;
 use thiserror::Error;
 
+#[cfg(feature = "async")]
+pub use crate::async_read::AsyncBytesReadExt;
+
 #[derive(Debug, Error)]
 pub enum BytesError {
 	#[error("Not enough data left in the buffer")]
 	NotEnoughData,
 	#[error("IO Error: {0}")]
 	IO(#[from] std::io::Error),
+	#[error("Modified UTF-8 Error: {0}")]
+	Mutf8(#[from] MUTFError),
+	#[error("A string of {0} bytes doesn't fit a u16 length")]
+	StringTooLong(usize),
 }
 
 pub trait BytesReadExt: Read + Seek {
@@ -28,18 +38,20 @@ pub trait BytesReadExt: Read + Seek {
 	define_integral_r!(u64, 8);
 
 	fn len_check(&mut self, needed: u64) -> Result<(), BytesError> {
-		let len = self.stream_len()?;
-		if len < self.stream_position()? + needed {
+		if self.remaining()? < needed {
 			return Err(BytesError::NotEnoughData);
 		}
 
 		Ok(())
 	}
 
+	fn remaining(&mut self) -> Result<u64, BytesError> {
+		Ok(self.stream_len()?.saturating_sub(self.stream_position()?))
+	}
+
 	fn read_to_vec(&mut self) -> Result<Vec<u8>, BytesError> {
-		let len = self.stream_len()? as usize;
-		let pos = self.stream_position()? as usize;
-		self.read_n_bytes_vec(len - pos - 1)
+		let remaining = self.remaining()? as usize;
+		self.read_vec(remaining)
 	}
 
 	fn read_n_bytes<const N: usize>(&mut self) -> Result<[u8; N], BytesError> {
@@ -50,14 +62,35 @@ pub trait BytesReadExt: Read + Seek {
 		Ok(bytes)
 	}
 
-	fn read_n_bytes_vec(&mut self, amount: usize) -> Result<Vec<u8>, BytesError> {
-		self.len_check(amount as u64)?;
+	/// Reads `len` bytes, checking there are that many left before allocating for them.
+	fn read_vec(&mut self, len: usize) -> Result<Vec<u8>, BytesError> {
+		self.len_check(len as u64)?;
 
-		let mut bytes = vec![0; amount];
+		let mut bytes = vec![0; len];
 		self.read_exact(&mut bytes)?;
 		Ok(bytes)
 	}
 
+	#[deprecated(note = "use `read_vec`")]
+	fn read_n_bytes_vec(&mut self, amount: usize) -> Result<Vec<u8>, BytesError> {
+		self.read_vec(amount)
+	}
+
+	/// The next `limit` bytes as a reader of their own, which ends there whatever is read from it. For handing a
+	/// length-prefixed part, like an attribute body, to a parser that mustn't read past it.
+	fn take_exact(&mut self, limit: u64) -> Result<Limited<'_, Self>, BytesError>
+	where
+		Self: Sized,
+	{
+		self.len_check(limit)?;
+		let start = self.stream_position()?;
+		Ok(Limited {
+			inner: self,
+			start,
+			limit,
+		})
+	}
+
 	fn read_f32(&mut self) -> Result<f32, BytesError> {
 		self.len_check(4)?;
 
@@ -71,6 +104,41 @@ pub trait BytesReadExt: Read + Seek {
 		let v = self.read_u64()?;
 		Ok(f64::from_bits(v))
 	}
+
+	/// Like `read_n_bytes`, leaving the position where it was.
+	fn peek_n_bytes<const N: usize>(&mut self) -> Result<[u8; N], BytesError> {
+		let position = self.stream_position()?;
+		let bytes = self.read_n_bytes::<N>();
+		self.seek(SeekFrom::Start(position))?;
+		bytes
+	}
+
+	fn peek_u8(&mut self) -> Result<u8, BytesError> {
+		Ok(u8::from_be_bytes(self.peek_n_bytes()?))
+	}
+
+	fn peek_u16(&mut self) -> Result<u16, BytesError> {
+		Ok(u16::from_be_bytes(self.peek_n_bytes()?))
+	}
+
+	fn peek_u32(&mut self) -> Result<u32, BytesError> {
+		Ok(u32::from_be_bytes(self.peek_n_bytes()?))
+	}
+
+	/// Up to `max` of the next bytes, fewer near the end, leaving the position where it was.
+	fn look_ahead(&mut self, max: usize) -> Result<Vec<u8>, BytesError> {
+		let len = (max as u64).min(self.remaining()?) as usize;
+		let position = self.stream_position()?;
+		let bytes = self.read_vec(len);
+		self.seek(SeekFrom::Start(position))?;
+		bytes
+	}
+
+	/// A string in modified UTF-8 behind its length as a u16, the way class files store them.
+	fn read_mutf8(&mut self) -> Result<String, BytesError> {
+		let len = self.read_u16()?;
+		Ok(maya_mutf8::decode(&self.read_vec(len.into())?)?)
+	}
 }
 
 pub trait BytesWriteExt: Write {
@@ -88,9 +156,62 @@ pub trait BytesWriteExt: Write {
 
 	define_write!(f32);
 	define_write!(f64);
+
+	/// Writes `value` in modified UTF-8 behind its length as a u16, the way class files store strings.
+	fn write_mutf8(&mut self, value: &str) -> Result<(), BytesError> {
+		let bytes = maya_mutf8::encode(value);
+		let len = u16::try_from(bytes.len()).map_err(|_| BytesError::StringTooLong(bytes.len()))?;
+		self.write_u16(len)?;
+		self.write_all(&bytes)?;
+		Ok(())
+	}
 }
 
 impl<R: Read + Seek> BytesReadExt for R {}
+
+/// A window of `limit` bytes of another reader, see `BytesReadExt::take_exact`. Positions are from the start of the
+/// window, and reading or seeking leaves the reader underneath where it would be without the window.
+#[derive(Debug)]
+pub struct Limited<'a, R> {
+	inner: &'a mut R,
+	start: u64,
+	limit: u64,
+}
+
+impl<R: Read + Seek> Limited<'_, R> {
+	/// Moves the reader underneath to the end of the window, past whatever wasn't read.
+	pub fn skip_rest(self) -> Result<(), BytesError> {
+		self.inner.seek(SeekFrom::Start(self.start + self.limit))?;
+		Ok(())
+	}
+}
+
+impl<R: Read + Seek> Read for Limited<'_, R> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let position = self.stream_position()?;
+		let left = self.limit.saturating_sub(position);
+		let len = buf.len().min(usize::try_from(left).unwrap_or(usize::MAX));
+		self.inner.read(&mut buf[..len])
+	}
+}
+
+impl<R: Read + Seek> Seek for Limited<'_, R> {
+	fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+		let target = match pos {
+			SeekFrom::Start(offset) => Some(offset),
+			SeekFrom::Current(offset) => self.stream_position()?.checked_add_signed(offset),
+			SeekFrom::End(offset) => self.limit.checked_add_signed(offset),
+		};
+		let target = target.ok_or_else(|| {
+			std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek before the start of the window")
+		})?;
+		Ok(self.inner.seek(SeekFrom::Start(self.start + target))? - self.start)
+	}
+
+	fn stream_position(&mut self) -> std::io::Result<u64> {
+		Ok(self.inner.stream_position()? - self.start)
+	}
+}
 impl<R: Write> BytesWriteExt for R {}
 
 #[cfg(test)]
@@ -131,4 +252,61 @@ mod tests {
 	define_test!(u64);
 	define_test!(f32);
 	define_test!(f64);
+
+	#[test]
+	fn peek() {
+		let mut buffer = Cursor::new(vec![0xAA, 0xBB, 0xCC]);
+		assert_eq!(buffer.peek_u8().unwrap(), 0xAA);
+		assert_eq!(buffer.peek_u16().unwrap(), 0xAABB);
+		assert!(matches!(buffer.peek_u32(), Err(BytesError::NotEnoughData)));
+		assert_eq!(buffer.look_ahead(8).unwrap(), [0xAA, 0xBB, 0xCC]);
+		assert_eq!(buffer.position(), 0);
+		buffer.read_u8().unwrap();
+		assert_eq!(buffer.look_ahead(1).unwrap(), [0xBB]);
+		assert_eq!(buffer.read_u16().unwrap(), 0xBBCC);
+	}
+
+	#[test]
+	fn bounded_reads() {
+		let mut buffer = Cursor::new(vec![0, 2, 0xAA, 0xBB, 0xCC]);
+		assert!(matches!(buffer.read_vec(usize::MAX), Err(BytesError::NotEnoughData)));
+		let len = buffer.read_u16().unwrap();
+
+		let mut body = buffer.take_exact(len.into()).unwrap();
+		assert_eq!(body.read_u8().unwrap(), 0xAA);
+		assert_eq!(body.remaining().unwrap(), 1);
+		assert!(matches!(body.read_u16(), Err(BytesError::NotEnoughData)));
+		assert!(matches!(body.read_to_vec(), Ok(rest) if rest == [0xBB]));
+		let mut body = buffer.take_exact(0).unwrap();
+		assert!(body.read_u8().is_err());
+		body.skip_rest().unwrap();
+		assert_eq!(buffer.read_u8().unwrap(), 0xCC);
+
+		buffer.set_position(2);
+		buffer.take_exact(2).unwrap().skip_rest().unwrap();
+		assert_eq!(buffer.position(), 4);
+		assert!(buffer.take_exact(2).is_err());
+	}
+
+	#[test]
+	fn mutf8() {
+		let mut buffer = Cursor::new(Vec::new());
+		buffer.write_mutf8("a\0\u{1F600}").unwrap();
+		buffer.write_mutf8("").unwrap();
+		// NUL takes two bytes and the emoji a surrogate pair of three each.
+		assert_eq!(buffer.get_ref()[..2], [0, 9]);
+		buffer.set_position(0);
+		assert_eq!(buffer.read_mutf8().unwrap(), "a\0\u{1F600}");
+		assert_eq!(buffer.read_mutf8().unwrap(), "");
+		assert!(matches!(buffer.read_mutf8(), Err(BytesError::NotEnoughData)));
+
+		assert!(matches!(
+			Vec::new().write_mutf8(&"x".repeat(70_000)),
+			Err(BytesError::StringTooLong(70_000))
+		));
+		assert!(matches!(
+			Cursor::new([0, 1, 0x80]).read_mutf8(),
+			Err(BytesError::Mutf8(MUTFError::InvalidEncoding))
+		));
+	}
 }
@@ -0,0 +1,72 @@
+/*
+ * C API of maya, a Java class file parser. See crates/maya-capi/src/lib.rs for the conventions:
+ * handles are freed with their _free function, returned strings with maya_string_free and bytes
+ * with maya_bytes_free, input strings are NUL-terminated UTF-8, and failing calls return NULL or -1
+ * with a message for maya_last_error.
+ */
+#ifndef MAYA_H
+#define MAYA_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define MAYA_ABI_VERSION 1
+
+typedef struct MayaClass MayaClass;
+typedef struct MayaRemapper MayaRemapper;
+
+typedef struct {
+	char *name;
+	char *descriptor;
+	uint16_t access_flags;
+} MayaMember;
+
+typedef struct {
+	uint8_t *data;
+	size_t len;
+} MayaBytes;
+
+uint32_t maya_abi_version(void);
+/* Owned by the library, valid until the next failing call on this thread. */
+const char *maya_last_error(void);
+
+void maya_string_free(char *s);
+void maya_bytes_free(MayaBytes bytes);
+void maya_member_free(MayaMember *member);
+
+MayaClass *maya_class_parse(const uint8_t *bytes, size_t len);
+void maya_class_free(MayaClass *class_);
+char *maya_class_name(const MayaClass *class_);
+/* NULL for java/lang/Object and module-info. */
+char *maya_class_super_name(const MayaClass *class_);
+uint16_t maya_class_access_flags(const MayaClass *class_);
+size_t maya_class_field_count(const MayaClass *class_);
+int maya_class_field(const MayaClass *class_, size_t index, MayaMember *out);
+size_t maya_class_method_count(const MayaClass *class_);
+int maya_class_method(const MayaClass *class_, size_t index, MayaMember *out);
+char *maya_class_disassemble(const MayaClass *class_);
+int maya_class_to_bytes(const MayaClass *class_, MayaBytes *out);
+int maya_class_remap(MayaClass *class_, MayaRemapper *remapper);
+
+MayaRemapper *maya_remapper_new(void);
+/* format is "proguard", "srg" or "tsrg". */
+MayaRemapper *maya_remapper_parse(const char *format, const char *mappings);
+MayaRemapper *maya_remapper_parse_tiny_v2(const char *mappings, const char *from, const char *to);
+void maya_remapper_free(MayaRemapper *remapper);
+int maya_remapper_add_class(MayaRemapper *remapper, const char *from, const char *to);
+int maya_remapper_add_package(MayaRemapper *remapper, const char *from, const char *to);
+/* descriptor may be NULL. */
+int maya_remapper_add_field(MayaRemapper *remapper, const char *owner, const char *name, const char *descriptor,
+                            const char *to);
+int maya_remapper_add_method(MayaRemapper *remapper, const char *owner, const char *name, const char *descriptor,
+                             const char *to);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,417 @@
+// A C API over maya-classfile-ir, for tools in C, C++ or Python (through ctypes) that embed the parser. The header is
+// include/maya.h, and what's there is kept stable: functions are only ever added, and `maya_abi_version` goes up when
+// they are.
+//
+// Classes and remappers are opaque handles, freed with their `_free` function. Strings and bytes handed out are owned
+// by the caller and freed with `maya_string_free` and `maya_bytes_free`, strings taken in are NUL-terminated UTF-8.
+// Functions that can fail return NULL or -1 and leave a message for `maya_last_error`, kept per thread until the next
+// failing call. Panics are caught at the boundary and reported the same way.
+//
+// Pointers must be valid as the header says, NULL only where it allows it, and handles are not thread-safe.
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+	cell::RefCell,
+	ffi::{c_char, c_int, CStr, CString},
+	fmt::Display,
+	panic::{catch_unwind, AssertUnwindSafe},
+	ptr, slice,
+};
+
+use maya_classfile_ir::{
+	disasm,
+	remap::Remapper,
+	transform::{ChangeLog, Transform},
+	IRClassFile,
+};
+
+/// Bumped whenever functions are added to the header.
+pub const MAYA_ABI_VERSION: u32 = 1;
+
+pub struct MayaClass(IRClassFile);
+
+pub struct MayaRemapper(Remapper);
+
+/// A field or method. The strings are owned, see `maya_member_free`.
+#[repr(C)]
+pub struct MayaMember {
+	pub name: *mut c_char,
+	pub descriptor: *mut c_char,
+	pub access_flags: u16,
+}
+
+#[repr(C)]
+pub struct MayaBytes {
+	pub data: *mut u8,
+	pub len: usize,
+}
+
+thread_local! {
+	static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: impl Display) {
+	// Messages come from Rust errors, which don't contain NULs, but a name in one might.
+	let message = message.to_string().replace('\0', "\\0");
+	LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+/// Runs `f`, turning its error or panic into the last error and `None`.
+fn guard<T, E: Display>(f: impl FnOnce() -> Result<T, E>) -> Option<T> {
+	match catch_unwind(AssertUnwindSafe(f)) {
+		Ok(Ok(value)) => Some(value),
+		Ok(Err(err)) => {
+			set_error(err);
+			None
+		}
+		Err(_) => {
+			set_error("maya panicked");
+			None
+		}
+	}
+}
+
+fn status<E: Display>(f: impl FnOnce() -> Result<(), E>) -> c_int {
+	match guard(f) {
+		Some(()) => 0,
+		None => -1,
+	}
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, what: &str) -> Result<&'a str, String> {
+	if s.is_null() {
+		return Err(format!("{what} is NULL"));
+	}
+	CStr::from_ptr(s)
+		.to_str()
+		.map_err(|_| format!("{what} isn't valid UTF-8"))
+}
+
+fn owned_string(s: &str) -> *mut c_char {
+	CString::new(s.replace('\0', "\\0"))
+		.expect("NULs are escaped")
+		.into_raw()
+}
+
+#[no_mangle]
+pub extern "C" fn maya_abi_version() -> u32 {
+	MAYA_ABI_VERSION
+}
+
+/// The message of the last failing call on this thread, or NULL. Owned by the library.
+#[no_mangle]
+pub extern "C" fn maya_last_error() -> *const c_char {
+	LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_string_free(s: *mut c_char) {
+	if !s.is_null() {
+		drop(CString::from_raw(s));
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_bytes_free(bytes: MayaBytes) {
+	if !bytes.data.is_null() {
+		drop(Vec::from_raw_parts(bytes.data, bytes.len, bytes.len));
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_member_free(member: *mut MayaMember) {
+	if let Some(member) = member.as_mut() {
+		maya_string_free(member.name);
+		maya_string_free(member.descriptor);
+		member.name = ptr::null_mut();
+		member.descriptor = ptr::null_mut();
+	}
+}
+
+/// Parses a class file, strictly and with the default limits.
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_parse(bytes: *const u8, len: usize) -> *mut MayaClass {
+	guard(|| {
+		if bytes.is_null() {
+			return Err("bytes is NULL".to_string());
+		}
+		let class = IRClassFile::read(slice::from_raw_parts(bytes, len)).map_err(|err| err.to_string())?;
+		Ok(Box::into_raw(Box::new(MayaClass(class))))
+	})
+	.unwrap_or(ptr::null_mut())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_free(class: *mut MayaClass) {
+	if !class.is_null() {
+		drop(Box::from_raw(class));
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_name(class: *const MayaClass) -> *mut c_char {
+	owned_string((*class).0.class_name())
+}
+
+/// NULL for java/lang/Object and module-info.
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_super_name(class: *const MayaClass) -> *mut c_char {
+	(*class).0.super_name().map_or(ptr::null_mut(), owned_string)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_access_flags(class: *const MayaClass) -> u16 {
+	(*class).0.access_flags.bits()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_field_count(class: *const MayaClass) -> usize {
+	(*class).0.fields.len()
+}
+
+/// Fills `out` with the field at `index`, -1 if there's none.
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_field(class: *const MayaClass, index: usize, out: *mut MayaMember) -> c_int {
+	status(|| {
+		let class = &(*class).0;
+		let field = class.fields.get(index).ok_or("field index out of range")?;
+		*out = MayaMember {
+			name: owned_string(field.name()),
+			descriptor: owned_string(field.descriptor()),
+			access_flags: field.access_flags.bits(),
+		};
+		Ok::<_, &str>(())
+	})
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_method_count(class: *const MayaClass) -> usize {
+	(*class).0.methods.len()
+}
+
+/// Fills `out` with the method at `index`, -1 if there's none.
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_method(class: *const MayaClass, index: usize, out: *mut MayaMember) -> c_int {
+	status(|| {
+		let class = &(*class).0;
+		let method = class.methods.get(index).ok_or("method index out of range")?;
+		*out = MayaMember {
+			name: owned_string(method.name()),
+			descriptor: owned_string(method.descriptor()),
+			access_flags: method.access_flags.bits(),
+		};
+		Ok::<_, &str>(())
+	})
+}
+
+/// The javap-style listing of the class.
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_disassemble(class: *const MayaClass) -> *mut c_char {
+	guard(|| disasm::disassemble(&(*class).0).map(|text| owned_string(&text))).unwrap_or(ptr::null_mut())
+}
+
+/// The class written back to bytes, -1 with `out` untouched if it can't be.
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_to_bytes(class: *const MayaClass, out: *mut MayaBytes) -> c_int {
+	status(|| {
+		let bytes = (*class).0.to_bytes()?.into_boxed_slice();
+		let len = bytes.len();
+		*out = MayaBytes {
+			data: Box::into_raw(bytes).cast(),
+			len,
+		};
+		Ok::<_, maya_classfile_ir::class_pool::IRClassfileError>(())
+	})
+}
+
+/// Renames throughout the class, see `Remapper`.
+#[no_mangle]
+pub unsafe extern "C" fn maya_class_remap(class: *mut MayaClass, remapper: *mut MayaRemapper) -> c_int {
+	status(|| (*remapper).0.apply(&mut (*class).0, &mut ChangeLog::disabled()))
+}
+
+#[no_mangle]
+pub extern "C" fn maya_remapper_new() -> *mut MayaRemapper {
+	Box::into_raw(Box::new(MayaRemapper(Remapper::new())))
+}
+
+/// Reads mappings in `format`, one of "proguard", "srg" or "tsrg".
+#[no_mangle]
+pub unsafe extern "C" fn maya_remapper_parse(format: *const c_char, mappings: *const c_char) -> *mut MayaRemapper {
+	guard(|| {
+		let mappings = str_arg(mappings, "mappings")?;
+		let remapper = match str_arg(format, "format")? {
+			"proguard" => Remapper::from_proguard(mappings),
+			"srg" => Remapper::from_srg(mappings),
+			"tsrg" => Remapper::from_tsrg(mappings),
+			other => return Err(format!("unknown mapping format {other}")),
+		};
+		remapper.map_err(|err| err.to_string())
+	})
+	.map_or(ptr::null_mut(), |remapper| {
+		Box::into_raw(Box::new(MayaRemapper(remapper)))
+	})
+}
+
+/// Reads Tiny v2 mappings from namespace `from` to `to`.
+#[no_mangle]
+pub unsafe extern "C" fn maya_remapper_parse_tiny_v2(
+	mappings: *const c_char,
+	from: *const c_char,
+	to: *const c_char,
+) -> *mut MayaRemapper {
+	guard(|| {
+		Remapper::from_tiny_v2(
+			str_arg(mappings, "mappings")?,
+			str_arg(from, "from")?,
+			str_arg(to, "to")?,
+		)
+		.map_err(|err| err.to_string())
+	})
+	.map_or(ptr::null_mut(), |remapper| {
+		Box::into_raw(Box::new(MayaRemapper(remapper)))
+	})
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_remapper_free(remapper: *mut MayaRemapper) {
+	if !remapper.is_null() {
+		drop(Box::from_raw(remapper));
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_remapper_add_class(
+	remapper: *mut MayaRemapper,
+	from: *const c_char,
+	to: *const c_char,
+) -> c_int {
+	status(|| {
+		(*remapper).0.add_class(str_arg(from, "from")?, str_arg(to, "to")?);
+		Ok::<_, String>(())
+	})
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_remapper_add_package(
+	remapper: *mut MayaRemapper,
+	from: *const c_char,
+	to: *const c_char,
+) -> c_int {
+	status(|| {
+		(*remapper).0.add_package(str_arg(from, "from")?, str_arg(to, "to")?);
+		Ok::<_, String>(())
+	})
+}
+
+/// `descriptor` may be NULL to rename the field whatever its type.
+#[no_mangle]
+pub unsafe extern "C" fn maya_remapper_add_field(
+	remapper: *mut MayaRemapper,
+	owner: *const c_char,
+	name: *const c_char,
+	descriptor: *const c_char,
+	to: *const c_char,
+) -> c_int {
+	status(|| {
+		let descriptor = match descriptor.is_null() {
+			true => None,
+			false => Some(str_arg(descriptor, "descriptor")?),
+		};
+		(*remapper).0.add_field(
+			str_arg(owner, "owner")?,
+			str_arg(name, "name")?,
+			descriptor,
+			str_arg(to, "to")?,
+		);
+		Ok::<_, String>(())
+	})
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn maya_remapper_add_method(
+	remapper: *mut MayaRemapper,
+	owner: *const c_char,
+	name: *const c_char,
+	descriptor: *const c_char,
+	to: *const c_char,
+) -> c_int {
+	status(|| {
+		(*remapper).0.add_method(
+			str_arg(owner, "owner")?,
+			str_arg(name, "name")?,
+			str_arg(descriptor, "descriptor")?,
+			str_arg(to, "to")?,
+		);
+		Ok::<_, String>(())
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use maya_classfile_ir::asm::assemble;
+
+	use super::*;
+
+	fn take(s: *mut c_char) -> String {
+		let owned = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+		unsafe { maya_string_free(s) };
+		owned
+	}
+
+	#[test]
+	fn parse_and_remap_through_the_c_api() {
+		let bytes = assemble(
+			".class public super a/A\n.field private b I\n.method public c()La/A;\naload_0\nareturn\n.end method",
+		)
+		.unwrap()
+		.to_bytes()
+		.unwrap();
+
+		unsafe {
+			let class = maya_class_parse(bytes.as_ptr(), bytes.len());
+			assert_eq!(take(maya_class_name(class)), "a/A");
+			assert_eq!(maya_class_method_count(class), 1);
+			let mut member = MayaMember {
+				name: ptr::null_mut(),
+				descriptor: ptr::null_mut(),
+				access_flags: 0,
+			};
+			assert_eq!(maya_class_method(class, 0, &mut member), 0);
+			assert_eq!(CStr::from_ptr(member.descriptor).to_str().unwrap(), "()La/A;");
+			maya_member_free(&mut member);
+			assert_eq!(maya_class_field(class, 1, &mut member), -1);
+			assert_eq!(
+				CStr::from_ptr(maya_last_error()).to_str().unwrap(),
+				"field index out of range"
+			);
+
+			let remapper = maya_remapper_parse(
+				c"proguard".as_ptr(),
+				c"p.Main -> a.A:\n    int count -> b\n    p.Main self() -> c\n".as_ptr(),
+			);
+			assert!(!remapper.is_null());
+			assert_eq!(maya_class_remap(class, remapper), 0);
+			maya_remapper_free(remapper);
+			assert_eq!(take(maya_class_name(class)), "p/Main");
+			assert!(take(maya_class_disassemble(class)).contains("p.Main self();"));
+			let mut out = MayaBytes {
+				data: ptr::null_mut(),
+				len: 0,
+			};
+			assert_eq!(maya_class_to_bytes(class, &mut out), 0);
+			let written = slice::from_raw_parts(out.data, out.len).to_vec();
+			maya_bytes_free(out);
+			maya_class_free(class);
+
+			let reparsed = maya_class_parse(written.as_ptr(), written.len());
+			assert_eq!(maya_class_field(reparsed, 0, &mut member), 0);
+			assert_eq!(CStr::from_ptr(member.name).to_str().unwrap(), "count");
+			maya_member_free(&mut member);
+			maya_class_free(reparsed);
+
+			assert!(maya_class_parse(bytes.as_ptr(), 3).is_null());
+			assert!(!maya_last_error().is_null());
+		}
+	}
+}
@@ -73,30 +73,17 @@ pub enum IOCpTag {
 	Package {
 		name_index: u16,
 	} = 20,
+	// The slot following a Long or Double. It has no representation in the classfile.
+	Unusable = 0,
 }
 
 impl IOCpTag {
 	pub fn read<B: BytesReadExt>(buffer: &mut B) -> Result<IOCpTag, IOClassfileError> {
 		let tag = buffer.read_u8()?;
-		println!("{tag} | {}", buffer.stream_position().expect("fak"));
 		match tag {
-			0 => {
-				let b = buffer.read_n_bytes::<50>()?;
-				let len = buffer.read_u16()?;
-				let b2 = buffer.read_n_bytes_vec(len as usize)?;
-				println!("MEW");
-				todo!()
-			}
 			1 => {
 				let len = buffer.read_u16()?;
-				dbg!(len);
-				let mut bytes = Vec::with_capacity(len as usize);
-				for _ in 0..len {
-					bytes.push(buffer.read_u8()?);
-				}
-
-				println!("{:?}", maya_mutf8::decode(&bytes).expect("mew"));
-
+				let bytes = buffer.read_vec(len as usize)?;
 				Ok(IOCpTag::Utf8 { length: len, bytes })
 			}
 			3 => Ok(IOCpTag::Integer {
@@ -154,7 +141,7 @@ impl IOCpTag {
 			20 => Ok(IOCpTag::Package {
 				name_index: buffer.read_u16()?,
 			}),
-			_ => unimplemented!("unimplemented tag: {tag}"),
+			_ => Err(IOClassfileError::InvalidCpTag(tag)),
 		}
 	}
 
@@ -201,11 +188,20 @@ impl IOCpTag {
 				name_and_ty_index: _,
 			} => 18,
 			Self::Module { name_index: _ } => 19,
-			Self::Package { name_index: _ } => 19,
+			Self::Package { name_index: _ } => 20,
+			Self::Unusable => 0,
 		}
 	}
 
+	pub fn is_wide(&self) -> bool {
+		matches!(self, IOCpTag::Long { .. } | IOCpTag::Double { .. })
+	}
+
 	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IOClassfileError> {
+		if let IOCpTag::Unusable = self {
+			return Ok(());
+		}
+
 		buffer.write_u8(self.id())?;
 		match self {
 			IOCpTag::Utf8 { length, bytes } => {
@@ -285,6 +281,7 @@ impl IOCpTag {
 			IOCpTag::Package { name_index } => {
 				buffer.write_u16(*name_index)?;
 			}
+			IOCpTag::Unusable => unreachable!(),
 		}
 		Ok(())
 	}
@@ -1,6 +1,9 @@
 pub mod class_pool;
+pub mod limits;
+pub mod stream;
 
 use class_pool::IOCpTag;
+use limits::{LimitExceeded, Limits};
 use maya_bytes::*;
 use thiserror::Error;
 
@@ -8,6 +11,10 @@ use thiserror::Error;
 pub enum IOClassfileError {
 	#[error("First 4 bytes were not 0xCAFEBABE")]
 	InvalidMagic,
+	#[error("Invalid constant pool tag: {0}")]
+	InvalidCpTag(u8),
+	#[error("{0}")]
+	LimitExceeded(#[from] LimitExceeded),
 	#[error("{0}")]
 	Bytes(#[from] BytesError),
 	#[error("IO Error: {0}")]
@@ -36,6 +43,10 @@ pub struct IOClassFile {
 
 impl IOClassFile {
 	pub fn read<B: BytesReadExt>(buffer: &mut B) -> Result<IOClassFile, IOClassfileError> {
+		Self::read_with(buffer, &Limits::default())
+	}
+
+	pub fn read_with<B: BytesReadExt>(buffer: &mut B, limits: &Limits) -> Result<IOClassFile, IOClassfileError> {
 		let magic = buffer.read_u32()?;
 		if magic != 0xCAFEBABE {
 			return Err(IOClassfileError::InvalidMagic);
@@ -44,10 +55,19 @@ impl IOClassFile {
 		let minor_version = buffer.read_u16()?;
 		let major_version = buffer.read_u16()?;
 		let cp_count = buffer.read_u16()?;
-		let mut cp = Vec::with_capacity(cp_count as usize - 1);
-		for i in 0..cp_count - 1 {
-			print!("{i} - ");
-			cp.push(IOCpTag::read(buffer)?);
+		Limits::check(
+			"constant pool entries",
+			limits.max_cp_entries.into(),
+			cp_count.saturating_sub(1).into(),
+		)?;
+		let mut cp = Vec::with_capacity((cp_count as usize).saturating_sub(1));
+		while cp.len() + 1 < cp_count as usize {
+			let tag = IOCpTag::read(buffer)?;
+			let wide = tag.is_wide();
+			cp.push(tag);
+			if wide {
+				cp.push(IOCpTag::Unusable);
+			}
 		}
 		let access_flags = buffer.read_u16()?;
 		let this_class = buffer.read_u16()?;
@@ -138,7 +158,7 @@ impl IOAttributeInfo {
 		Ok(IOAttributeInfo {
 			attribute_name_index,
 			attribute_length,
-			info: buffer.read_n_bytes_vec(attribute_length as usize)?,
+			info: buffer.read_vec(attribute_length as usize)?,
 		})
 	}
 
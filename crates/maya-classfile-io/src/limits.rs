@@ -0,0 +1,39 @@
+/// Caps on the sizes a class file can ask the parser to allocate, for reading untrusted input.
+/// The size defaults are the maximums the JVMS allows, so only the nesting depth can reject a valid class file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+	/// Constant pool entries, including the unusable slots after Long and Double.
+	pub max_cp_entries: u16,
+	/// Bytes of bytecode in a single Code attribute.
+	pub max_code_size: u32,
+	/// How deep attributes may nest, e.g. Record -> component attributes, or annotation values inside annotations.
+	pub max_attribute_depth: u16,
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Self {
+			max_cp_entries: u16::MAX - 1,
+			max_code_size: u16::MAX as u32,
+			max_attribute_depth: 64,
+		}
+	}
+}
+
+impl Limits {
+	pub fn check(what: &'static str, limit: u64, actual: u64) -> Result<(), LimitExceeded> {
+		if actual > limit {
+			return Err(LimitExceeded { what, limit, actual });
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("{what} exceeds the limit of {limit} (got {actual})")]
+pub struct LimitExceeded {
+	pub what: &'static str,
+	pub limit: u64,
+	pub actual: u64,
+}
@@ -0,0 +1,277 @@
+// Reading a class file from a `Read` that can't seek or tell how long it is, e.g. a socket or a jar entry being
+// inflated. The usual readers check lengths against the end of the buffer, which a stream doesn't have, so here the
+// structure is walked piece by piece instead: each constant and attribute is read into a buffer of its own and handed
+// to those readers. Nothing past the end of the class is read, so classes sent back to back can be read one by one.
+//
+// A declared length is only trusted as far as data arrives: buffers grow as it's read, and a length running past the
+// end of the stream fails with NotEnoughData instead of allocating for it up front.
+//
+// `read_class_async` does the same walk over an `AsyncRead`, gathering the bytes of the class as they arrive, and
+// parses them once they're all there.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.1
+
+use std::io::{Cursor, ErrorKind, Read};
+
+#[cfg(feature = "async")]
+use futures_util::io::AsyncRead;
+#[cfg(feature = "async")]
+use maya_bytes::AsyncBytesReadExt;
+use maya_bytes::BytesError;
+
+use crate::{
+	class_pool::IOCpTag, limits::Limits, IOAttributeInfo, IOClassFile, IOClassfileError, IOFieldInfo, IOMethodInfo,
+};
+
+#[derive(Debug)]
+pub struct ClassStream<R> {
+	reader: R,
+	position: u64,
+}
+
+impl<R: Read> ClassStream<R> {
+	pub fn new(reader: R) -> Self {
+		Self { reader, position: 0 }
+	}
+
+	/// Bytes read so far.
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	/// Reads the next class with the default limits.
+	pub fn read_class(&mut self) -> Result<IOClassFile, IOClassfileError> {
+		self.read_class_with(&Limits::default())
+	}
+
+	pub fn read_class_with(&mut self, limits: &Limits) -> Result<IOClassFile, IOClassfileError> {
+		let magic = self.read_u32()?;
+		if magic != 0xCAFEBABE {
+			return Err(IOClassfileError::InvalidMagic);
+		}
+
+		let minor_version = self.read_u16()?;
+		let major_version = self.read_u16()?;
+		let cp_count = self.read_u16()?;
+		Limits::check(
+			"constant pool entries",
+			limits.max_cp_entries.into(),
+			cp_count.saturating_sub(1).into(),
+		)?;
+		let mut cp = Vec::with_capacity((cp_count as usize).saturating_sub(1));
+		while cp.len() + 1 < cp_count as usize {
+			let tag = self.read_cp_tag()?;
+			let wide = tag.is_wide();
+			cp.push(tag);
+			if wide {
+				cp.push(IOCpTag::Unusable);
+			}
+		}
+		let access_flags = self.read_u16()?;
+		let this_class = self.read_u16()?;
+		let super_class = self.read_u16()?;
+		let interface_count = self.read_u16()?;
+		let mut interfaces = Vec::with_capacity(interface_count as usize);
+		for _ in 0..interface_count {
+			interfaces.push(self.read_u16()?);
+		}
+		let field_count = self.read_u16()?;
+		let mut fields = Vec::with_capacity(field_count as usize);
+		for _ in 0..field_count {
+			let (access_flags, name_index, descriptor_index) = (self.read_u16()?, self.read_u16()?, self.read_u16()?);
+			let (attributes_count, attributes) = self.read_attributes()?;
+			fields.push(IOFieldInfo {
+				access_flags,
+				name_index,
+				descriptor_index,
+				attributes_count,
+				attributes,
+			});
+		}
+		let method_count = self.read_u16()?;
+		let mut methods = Vec::with_capacity(method_count as usize);
+		for _ in 0..method_count {
+			let (access_flags, name_index, descriptor_index) = (self.read_u16()?, self.read_u16()?, self.read_u16()?);
+			let (attributes_count, attributes) = self.read_attributes()?;
+			methods.push(IOMethodInfo {
+				access_flags,
+				name_index,
+				descriptor_index,
+				attributes_count,
+				attributes,
+			});
+		}
+		let (attribute_count, attributes) = self.read_attributes()?;
+
+		Ok(IOClassFile {
+			magic,
+			minor_version,
+			major_version,
+			cp_count,
+			cp,
+			access_flags,
+			this_class,
+			super_class,
+			interface_count,
+			interfaces,
+			field_count,
+			fields,
+			method_count,
+			methods,
+			attribute_count,
+			attributes,
+		})
+	}
+
+	fn read_cp_tag(&mut self) -> Result<IOCpTag, IOClassfileError> {
+		let tag = self.read_bytes::<1>()?[0];
+		let mut entry = vec![tag];
+		let len = match tag {
+			1 => {
+				let len = self.read_bytes::<2>()?;
+				entry.extend(len);
+				u16::from_be_bytes(len) as u64
+			}
+			3 | 4 => 4,
+			5 | 6 => 8,
+			7 | 8 | 16 | 19 | 20 => 2,
+			9..=12 | 18 => 4,
+			15 => 3,
+			_ => return Err(IOClassfileError::InvalidCpTag(tag)),
+		};
+		self.read_into(&mut entry, len)?;
+		IOCpTag::read(&mut Cursor::new(entry))
+	}
+
+	fn read_attributes(&mut self) -> Result<(u16, Vec<IOAttributeInfo>), IOClassfileError> {
+		let count = self.read_u16()?;
+		let mut attributes = Vec::with_capacity(count as usize);
+		for _ in 0..count {
+			let attribute_name_index = self.read_u16()?;
+			let attribute_length = self.read_u32()?;
+			let mut info = Vec::new();
+			self.read_into(&mut info, attribute_length.into())?;
+			attributes.push(IOAttributeInfo {
+				attribute_name_index,
+				attribute_length,
+				info,
+			});
+		}
+		Ok((count, attributes))
+	}
+
+	fn read_u16(&mut self) -> Result<u16, IOClassfileError> {
+		Ok(u16::from_be_bytes(self.read_bytes()?))
+	}
+
+	fn read_u32(&mut self) -> Result<u32, IOClassfileError> {
+		Ok(u32::from_be_bytes(self.read_bytes()?))
+	}
+
+	fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], IOClassfileError> {
+		let mut bytes = [0; N];
+		self.reader.read_exact(&mut bytes).map_err(|err| match err.kind() {
+			ErrorKind::UnexpectedEof => IOClassfileError::Bytes(BytesError::NotEnoughData),
+			_ => IOClassfileError::IO(err),
+		})?;
+		self.position += N as u64;
+		Ok(bytes)
+	}
+
+	/// Appends `len` bytes to `out`, growing it as they arrive.
+	fn read_into(&mut self, out: &mut Vec<u8>, len: u64) -> Result<(), IOClassfileError> {
+		let read = self.reader.by_ref().take(len).read_to_end(out)? as u64;
+		self.position += read;
+		if read < len {
+			return Err(BytesError::NotEnoughData.into());
+		}
+		Ok(())
+	}
+}
+
+/// Reads the next class from `reader`, leaving anything after it unread.
+#[cfg(feature = "async")]
+pub async fn read_class_async<R: AsyncRead + Unpin>(
+	reader: &mut R,
+	limits: &Limits,
+) -> Result<IOClassFile, IOClassfileError> {
+	let mut class = AsyncFramer {
+		reader,
+		bytes: Vec::new(),
+	};
+	if class.u32().await? != 0xCAFEBABE {
+		return Err(IOClassfileError::InvalidMagic);
+	}
+	class.copy(4).await?;
+	let cp_count = class.u16().await?;
+	Limits::check(
+		"constant pool entries",
+		limits.max_cp_entries.into(),
+		cp_count.saturating_sub(1).into(),
+	)?;
+	let mut index = 1;
+	while index < cp_count {
+		let tag = class.copy(1).await?[0];
+		let len = match tag {
+			1 => class.u16().await?.into(),
+			3 | 4 => 4,
+			5 | 6 => 8,
+			7 | 8 | 16 | 19 | 20 => 2,
+			9..=12 | 18 => 4,
+			15 => 3,
+			_ => return Err(IOClassfileError::InvalidCpTag(tag)),
+		};
+		class.copy(len).await?;
+		index += if matches!(tag, 5 | 6) { 2 } else { 1 };
+	}
+	class.copy(6).await?;
+	let interface_count = class.u16().await?;
+	class.copy(interface_count as usize * 2).await?;
+	for _ in 0..2 {
+		let member_count = class.u16().await?;
+		for _ in 0..member_count {
+			class.copy(6).await?;
+			class.attributes().await?;
+		}
+	}
+	class.attributes().await?;
+	IOClassFile::read_with(&mut Cursor::new(class.bytes), limits)
+}
+
+#[cfg(feature = "async")]
+struct AsyncFramer<'a, R> {
+	reader: &'a mut R,
+	bytes: Vec<u8>,
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncFramer<'_, R> {
+	/// Reads `len` bytes onto the class, returning them.
+	async fn copy(&mut self, len: usize) -> Result<&[u8], IOClassfileError> {
+		let start = self.bytes.len();
+		let read = self.reader.read_vec(len).await?;
+		self.bytes.extend(read);
+		Ok(&self.bytes[start..])
+	}
+
+	async fn u16(&mut self) -> Result<u16, IOClassfileError> {
+		Ok(u16::from_be_bytes(self.copy(2).await?.try_into().expect("two bytes")))
+	}
+
+	async fn u32(&mut self) -> Result<u32, IOClassfileError> {
+		Ok(u32::from_be_bytes(self.copy(4).await?.try_into().expect("four bytes")))
+	}
+
+	async fn attributes(&mut self) -> Result<(), IOClassfileError> {
+		let count = self.u16().await?;
+		for _ in 0..count {
+			self.copy(2).await?;
+			let len = self.u32().await?;
+			self.copy(len as usize).await?;
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,420 @@
+// A call graph by class hierarchy analysis: every invoke instruction of the classes analyzed is a call site, and a
+// virtual or interface call reaches whatever the method would dispatch to on each concrete class of the program that
+// could be the receiver. That over-approximates, since not every subclass is ever instantiated, but it never misses a
+// call between the classes analyzed. Classes outside them, like the JDK's, are found through a `ClassResolver` to
+// resolve methods inherited from them, but their own code isn't walked and their subclasses aren't known, so a call
+// that can't be resolved keeps the method it names as its only callee. invokedynamic has no static target and isn't a
+// call site.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-5.html#jvms-5.4.3.3
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-5.html#jvms-5.4.6
+
+use std::{
+	cell::RefCell,
+	collections::{BTreeSet, HashMap, VecDeque},
+	rc::Rc,
+};
+
+use crate::{
+	code::Instructions,
+	flags::MethodAccessFlags,
+	names,
+	query::MemberRef,
+	resolver::ClassResolver,
+	symbols::{ClassId, MemberId, Symbol, SymbolTable},
+	IRClassFile,
+};
+
+const OBJECT: &str = "java/lang/Object";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CallKind {
+	Virtual,
+	Special,
+	Static,
+	Interface,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallSite {
+	pub caller: MemberRef,
+	// of the invoke instruction in the caller's code
+	pub offset: u32,
+	pub kind: CallKind,
+	// the method as the instruction names it
+	pub target: MemberRef,
+	// the methods the call can end up in
+	pub callees: BTreeSet<MemberRef>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+	// every class and method seen, the indexes below are keyed on their ids
+	symbols: SymbolTable,
+	// every method of the classes analyzed
+	methods: BTreeSet<MemberRef>,
+	sites: Vec<CallSite>,
+	// caller and callees to their call sites, by index in `sites`
+	by_caller: HashMap<MemberId, Vec<usize>>,
+	by_callee: HashMap<MemberId, Vec<usize>>,
+}
+
+impl CallGraph {
+	/// The call graph of `classes`, with classes they extend or call into looked up through `resolver`.
+	pub fn build<'a, R: ClassResolver>(classes: impl IntoIterator<Item = &'a IRClassFile>, resolver: &R) -> Self {
+		let classes = classes.into_iter().collect::<Vec<_>>();
+		let program = Program::new(&classes, resolver);
+		let mut graph = CallGraph::default();
+		let mut sites = Vec::new();
+		for class in &classes {
+			for method in &class.methods {
+				let caller = program.member(class.class_name(), method.name(), method.descriptor());
+				graph.methods.insert(program.member_ref(caller));
+				// Undecodable code has no call sites, `lint` is the place to report it.
+				let Some(Ok(instructions)) = method.code().map(|code| Instructions::read_all(&class.cp, &code.code))
+				else {
+					continue;
+				};
+				for (offset, instruction) in instructions {
+					let (kind, class, name_and_ty) = match &instruction {
+						Instructions::INVOKEVIRTUAL(method) => (CallKind::Virtual, &method.class, &method.name_and_ty),
+						Instructions::INVOKESPECIAL(method) => (CallKind::Special, &method.class, &method.name_and_ty),
+						Instructions::INVOKESTATIC(method) => (CallKind::Static, &method.class, &method.name_and_ty),
+						Instructions::INVOKEINTERFACE { method, .. } => {
+							(CallKind::Interface, &method.class, &method.name_and_ty)
+						}
+						_ => continue,
+					};
+					let target = program.member(&class.data.data, &name_and_ty.name.data, &name_and_ty.ty.data);
+					let callees = program.callees(kind, target);
+					sites.push((caller, offset, kind, target, callees));
+				}
+			}
+		}
+
+		graph.symbols = program.symbols.into_inner();
+		for (caller, offset, kind, target, callees) in sites {
+			let index = graph.sites.len();
+			graph.by_caller.entry(caller).or_default().push(index);
+			for &callee in &callees {
+				graph.by_callee.entry(callee).or_default().push(index);
+			}
+			graph.sites.push(CallSite {
+				caller: graph.symbols.member_ref(caller),
+				offset,
+				kind,
+				target: graph.symbols.member_ref(target),
+				callees: callees
+					.into_iter()
+					.map(|callee| graph.symbols.member_ref(callee))
+					.collect(),
+			});
+		}
+		graph
+	}
+
+	pub fn methods(&self) -> &BTreeSet<MemberRef> {
+		&self.methods
+	}
+
+	/// Every call site, in the order of the classes, their methods and their code.
+	pub fn sites(&self) -> &[CallSite] {
+		&self.sites
+	}
+
+	pub fn sites_in(&self, caller: &MemberRef) -> impl Iterator<Item = &CallSite> {
+		self.indexed(&self.by_caller, caller)
+	}
+
+	/// The methods `caller` may call directly.
+	pub fn callees(&self, caller: &MemberRef) -> BTreeSet<&MemberRef> {
+		self.sites_in(caller).flat_map(|site| &site.callees).collect()
+	}
+
+	/// The call sites that may end up in `callee`.
+	pub fn callers<'a>(&'a self, callee: &'a MemberRef) -> impl Iterator<Item = &'a CallSite> + 'a {
+		self.indexed(&self.by_callee, callee)
+	}
+
+	/// Every method reachable from the entry points through calls, the entry points included.
+	pub fn reachable<'a>(&self, entries: impl IntoIterator<Item = &'a MemberRef>) -> BTreeSet<MemberRef> {
+		let mut reached = BTreeSet::new();
+		let mut pending = entries.into_iter().cloned().collect::<VecDeque<_>>();
+		while let Some(method) = pending.pop_front() {
+			if reached.contains(&method) {
+				continue;
+			}
+			pending.extend(self.callees(&method).into_iter().cloned());
+			reached.insert(method);
+		}
+		reached
+	}
+
+	fn indexed<'a>(
+		&'a self,
+		index: &'a HashMap<MemberId, Vec<usize>>,
+		method: &MemberRef,
+	) -> impl Iterator<Item = &'a CallSite> + 'a {
+		let id = self
+			.symbols
+			.lookup_member(&method.owner, &method.name, &method.descriptor);
+		let sites = id.and_then(|id| index.get(&id)).into_iter().flatten();
+		sites.map(|&index| &self.sites[index])
+	}
+}
+
+/// What call resolution needs of a class.
+#[derive(Debug)]
+struct ClassInfo {
+	super_class: Option<ClassId>,
+	interfaces: Vec<ClassId>,
+	methods: Vec<(Symbol, Symbol, MethodAccessFlags)>,
+}
+
+impl ClassInfo {
+	fn of(class: &IRClassFile, symbols: &mut SymbolTable) -> Self {
+		Self {
+			super_class: class.super_name().map(|name| symbols.class(name)),
+			interfaces: class.interface_names().map(|name| symbols.class(name)).collect(),
+			methods: class
+				.methods
+				.iter()
+				.map(|method| {
+					(
+						symbols.intern(method.name()),
+						symbols.intern(method.descriptor()),
+						method.access_flags,
+					)
+				})
+				.collect(),
+		}
+	}
+
+	fn declared(&self, name: Symbol, descriptor: Symbol) -> Option<MethodAccessFlags> {
+		self.methods
+			.iter()
+			.find(|(candidate, candidate_descriptor, _)| *candidate == name && *candidate_descriptor == descriptor)
+			.map(|(_, _, flags)| *flags)
+	}
+}
+
+struct Program<'a, R> {
+	resolver: &'a R,
+	symbols: RefCell<SymbolTable>,
+	// the classes analyzed, and the others as the resolver finds them, `None` if it doesn't
+	classes: RefCell<HashMap<ClassId, Option<Rc<ClassInfo>>>>,
+	// class or interface to the concrete classes analyzed that are subtypes of it, itself included
+	implementors: HashMap<ClassId, Vec<ClassId>>,
+}
+
+impl<'a, R: ClassResolver> Program<'a, R> {
+	fn new(classes: &[&IRClassFile], resolver: &'a R) -> Self {
+		let mut symbols = SymbolTable::new();
+		let infos = classes
+			.iter()
+			.map(|class| {
+				let id = symbols.class(class.class_name());
+				(id, Some(Rc::new(ClassInfo::of(class, &mut symbols))))
+			})
+			.collect();
+		let mut program = Self {
+			resolver,
+			symbols: RefCell::new(symbols),
+			classes: RefCell::new(infos),
+			implementors: HashMap::new(),
+		};
+
+		let mut implementors = HashMap::<_, Vec<_>>::new();
+		for class in classes {
+			if class.access_flags.is_interface() || class.access_flags.is_abstract() {
+				continue;
+			}
+			let id = program.class(class.class_name());
+			let superclasses = program.superclasses(id);
+			for supertype in program.superinterfaces(&superclasses).into_iter().chain(superclasses) {
+				implementors.entry(supertype).or_default().push(id);
+			}
+		}
+		program.implementors = implementors;
+		program
+	}
+
+	fn class(&self, name: &str) -> ClassId {
+		self.symbols.borrow_mut().class(name)
+	}
+
+	fn member(&self, owner: &str, name: &str, descriptor: &str) -> MemberId {
+		self.symbols.borrow_mut().member(owner, name, descriptor)
+	}
+
+	fn member_ref(&self, member: MemberId) -> MemberRef {
+		self.symbols.borrow().member_ref(member)
+	}
+
+	// The member `name` and `descriptor` of `target`, declared on `class`.
+	fn member_on(&self, class: ClassId, target: MemberId) -> MemberId {
+		let mut symbols = self.symbols.borrow_mut();
+		let (name, descriptor) = symbols.signature(target);
+		symbols.member_of(class, name, descriptor)
+	}
+
+	fn owner(&self, member: MemberId) -> ClassId {
+		self.symbols.borrow().owner(member)
+	}
+
+	fn info(&self, class: ClassId) -> Option<Rc<ClassInfo>> {
+		if let Some(info) = self.classes.borrow().get(&class) {
+			return info.clone();
+		}
+		let name = self.symbols.borrow().class_name(class).to_string();
+		let info = self
+			.resolver
+			.resolve(&name)
+			.map(|resolved| Rc::new(ClassInfo::of(&resolved, &mut self.symbols.borrow_mut())));
+		self.classes.borrow_mut().insert(class, info.clone());
+		info
+	}
+
+	/// `class` and its superclasses, nearest first, as far as they can be found.
+	fn superclasses(&self, class: ClassId) -> Vec<ClassId> {
+		let mut chain = vec![class];
+		while let Some(next) = self
+			.info(*chain.last().expect("starts with the class"))
+			.and_then(|info| info.super_class)
+		{
+			// A cyclic hierarchy is broken input, don't loop on it.
+			if chain.contains(&next) {
+				break;
+			}
+			chain.push(next);
+		}
+		chain
+	}
+
+	/// The interfaces the classes implement, directly or through other interfaces, nearest first.
+	fn superinterfaces(&self, classes: &[ClassId]) -> Vec<ClassId> {
+		let mut interfaces = Vec::new();
+		let mut pending = VecDeque::new();
+		for &class in classes {
+			pending.extend(self.info(class).into_iter().flat_map(|info| info.interfaces.clone()));
+		}
+		while let Some(interface) = pending.pop_front() {
+			if interfaces.contains(&interface) {
+				continue;
+			}
+			pending.extend(
+				self.info(interface)
+					.into_iter()
+					.flat_map(|info| info.interfaces.clone()),
+			);
+			interfaces.push(interface);
+		}
+		interfaces
+	}
+
+	/// The method a reference to `target` resolves to: declared on its class or a superclass, or failing that on a
+	/// superinterface, preferring default methods.
+	fn resolve(&self, target: MemberId) -> Option<(MemberId, MethodAccessFlags)> {
+		let (name, descriptor) = self.symbols.borrow().signature(target);
+		let declared_on = |&class: &ClassId| {
+			let flags = self.info(class)?.declared(name, descriptor)?;
+			Some((self.member_on(class, target), flags))
+		};
+		let superclasses = self.superclasses(self.owner(target));
+		if let Some(found) = superclasses.iter().find_map(declared_on) {
+			return Some(found);
+		}
+		let interfaces = self.superinterfaces(&superclasses);
+		let mut declared = interfaces.iter().filter_map(declared_on);
+		let first = declared.next()?;
+		Some(declared.find(|(_, flags)| !flags.is_abstract()).unwrap_or(first))
+	}
+
+	/// The method a virtual call of `target` runs on an instance of `class`, `None` if there's none.
+	fn select(&self, class: ClassId, target: MemberId) -> Option<MemberId> {
+		let (name, descriptor) = self.symbols.borrow().signature(target);
+		let superclasses = self.superclasses(class);
+		for &class in &superclasses {
+			match self.info(class).and_then(|info| info.declared(name, descriptor)) {
+				Some(flags) if flags.is_static() => {}
+				Some(flags) if flags.is_abstract() => return None,
+				Some(_) => return Some(self.member_on(class, target)),
+				None => {}
+			}
+		}
+		self.superinterfaces(&superclasses).into_iter().find_map(|interface| {
+			let flags = self.info(interface)?.declared(name, descriptor)?;
+			let default = !flags.is_abstract() && !flags.is_static() && !flags.is_private();
+			default.then(|| self.member_on(interface, target))
+		})
+	}
+
+	fn callees(&self, kind: CallKind, target: MemberId) -> BTreeSet<MemberId> {
+		// Methods called on arrays are Object's.
+		let is_array = names::is_array(self.symbols.borrow().class_name(self.owner(target)));
+		let target = match is_array {
+			true => self.member_on(self.class(OBJECT), target),
+			false => target,
+		};
+		let owner = self.owner(target);
+		let resolved = self.resolve(target);
+		let mut callees = BTreeSet::new();
+		if matches!(kind, CallKind::Virtual | CallKind::Interface) {
+			let implementors = self.implementors.get(&owner).into_iter().flatten();
+			callees.extend(implementors.filter_map(|&class| self.select(class, target)));
+			// Classes that weren't analyzed may not override it.
+			if let Some((method, flags)) = resolved {
+				if !flags.is_abstract() && !self.implementors.contains_key(&self.owner(method)) {
+					callees.insert(method);
+				}
+			}
+		}
+		if callees.is_empty() {
+			callees.insert(resolved.map_or(target, |(method, _)| method));
+		}
+		callees
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{asm::assemble, classpath::MemoryProvider};
+
+	#[test]
+	fn class_hierarchy_analysis() {
+		let classes = [
+			".class public super p/Base\n.method public run()V\nreturn\n.end method",
+			".class public super p/Sub\n.super p/Base\n.method public run()V\naload_0\ninvokespecial p/Base/run()V\n\
+			return\n.end method",
+			".class public super p/Other\n.super p/Base",
+			".class public interface abstract p/I\n.method public greet()V\nreturn\n.end method",
+			".class public super p/Impl\n.implements p/I",
+			".class public super p/Main\n.method public static main([Ljava/lang/String;)V\naconst_null\n\
+			invokevirtual p/Base/run()V\naconst_null\ninvokeinterface p/I/greet()V\ninvokestatic p/Main/helper()V\n\
+			aconst_null\ninvokevirtual java/lang/Object/toString()Ljava/lang/String;\npop\nreturn\n.end method\n\
+			.method static helper()V\nreturn\n.end method\n.method static unused()V\nreturn\n.end method",
+		]
+		.map(|source| assemble(source).unwrap());
+		let graph = CallGraph::build(&classes, &MemoryProvider::new());
+
+		let method = |owner: &str, name: &str, descriptor: &str| MemberRef::new(owner, name, descriptor);
+		let main = method("p/Main", "main", "([Ljava/lang/String;)V");
+		let sites = graph.sites_in(&main).collect::<Vec<_>>();
+		assert_eq!(sites.len(), 4);
+		assert_eq!(
+			sites[0].callees,
+			BTreeSet::from([method("p/Base", "run", "()V"), method("p/Sub", "run", "()V")])
+		);
+		assert_eq!(sites[1].kind, CallKind::Interface);
+		assert_eq!(sites[1].callees, BTreeSet::from([method("p/I", "greet", "()V")]));
+		// Nothing analyzed overrides it and Object can't be found, so it stays as named.
+		assert_eq!(sites[3].callees, BTreeSet::from([sites[3].target.clone()]));
+		assert_eq!(graph.callers(&method("p/Base", "run", "()V")).count(), 2);
+
+		let reachable = graph.reachable([&main]);
+		assert_eq!(reachable.len(), 6);
+		assert!(reachable.contains(&method("p/Main", "helper", "()V")));
+		assert!(!reachable.contains(&method("p/Main", "unused", "()V")));
+		assert!(graph.methods().contains(&method("p/Main", "unused", "()V")));
+	}
+}
@@ -0,0 +1,396 @@
+// The control flow graph of a method body. Blocks start at the first instruction, at jump and switch targets, after
+// instructions that end control flow or branch, and where exception handlers and their protected ranges begin or end.
+// The last means a block is either entirely inside a try range or entirely outside, so each block gets exactly the
+// exception edges of the handlers covering it.
+//
+// For writing passes against, blocks can be visited in reverse post-order, which sees a block before its successors
+// except along back edges, and loops are found as the targets of back edges, edges to a block that dominates their
+// source. Loops that can be entered in more than one place have no such header and aren't reported.
+//
+// Subroutines aren't followed: `jsr` has an edge to the subroutine and falls through to the instruction after it,
+// `ret` has no successors, since where it returns to depends on the caller.
+
+use std::{collections::BTreeSet, ops::Range};
+
+use crate::{
+	attribute::{CodeAttribute, CodeAttributeException},
+	class_pool::{IRClassfileError, IRCpTag},
+	code::Instructions,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+	pub start_pc: u32,
+	// exclusive, the pc of the next block or the code length
+	pub end_pc: u32,
+	// indexes into `ControlFlowGraph::instructions`
+	pub instructions: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+	FallThrough,
+	// goto, a conditional branch taken, or jsr
+	Jump,
+	Switch,
+	/// To a handler of the exception table, `catch_type` being its class entry or 0 for any.
+	Exception {
+		catch_type: u16,
+	},
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+	pub from: usize,
+	pub to: usize,
+	pub kind: EdgeKind,
+}
+
+/// Blocks are numbered in code order, block 0 being the entry.
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+	instructions: Vec<(u32, Instructions)>,
+	blocks: Vec<BasicBlock>,
+	edges: Vec<Edge>,
+	// edge indexes by block
+	successors: Vec<Vec<usize>>,
+	predecessors: Vec<Vec<usize>>,
+}
+
+impl ControlFlowGraph {
+	pub fn new(cp: &[IRCpTag], code: &CodeAttribute) -> Result<Self, IRClassfileError> {
+		let instructions = Instructions::read_all(cp, &code.code)?;
+		Self::from_instructions(instructions, code.code.len() as u32, &code.exception_table)
+	}
+
+	/// Builds the graph of already decoded instructions, `code_length` being where the last one ends.
+	pub fn from_instructions(
+		instructions: Vec<(u32, Instructions)>,
+		code_length: u32,
+		exception_table: &[CodeAttributeException],
+	) -> Result<Self, IRClassfileError> {
+		let is_instruction = |pc: u32| instructions.binary_search_by_key(&pc, |(pc, _)| *pc).is_ok();
+
+		let mut leaders = BTreeSet::new();
+		if !instructions.is_empty() {
+			leaders.insert(0);
+		}
+		for (i, (pc, insn)) in instructions.iter().enumerate() {
+			let targets = insn.branch_targets(*pc);
+			for target in &targets {
+				if !is_instruction(*target) {
+					return Err(IRClassfileError::InvalidBranchTarget {
+						pc: *pc,
+						target: *target,
+					});
+				}
+			}
+			if !targets.is_empty() || !insn.falls_through() {
+				if let Some((next, _)) = instructions.get(i + 1) {
+					leaders.insert(*next);
+				}
+			}
+			leaders.extend(targets);
+		}
+		for entry in exception_table {
+			let (start, end, handler) = (entry.start_pc as u32, entry.end_pc as u32, entry.handler_pc as u32);
+			for target in [start, handler] {
+				if !is_instruction(target) {
+					return Err(IRClassfileError::InvalidBranchTarget { pc: start, target });
+				}
+			}
+			if end != code_length && !is_instruction(end) {
+				return Err(IRClassfileError::InvalidBranchTarget { pc: start, target: end });
+			}
+			leaders.extend([start, handler]);
+			if end != code_length {
+				leaders.insert(end);
+			}
+		}
+
+		let leaders = leaders.into_iter().collect::<Vec<_>>();
+		let mut blocks = Vec::with_capacity(leaders.len());
+		let mut first = 0;
+		for (n, start_pc) in leaders.iter().enumerate() {
+			let end_pc = leaders.get(n + 1).copied().unwrap_or(code_length);
+			let last = instructions[first..].partition_point(|(pc, _)| *pc < end_pc) + first;
+			blocks.push(BasicBlock {
+				start_pc: *start_pc,
+				end_pc,
+				instructions: first..last,
+			});
+			first = last;
+		}
+
+		let mut graph = Self {
+			successors: vec![Vec::new(); blocks.len()],
+			predecessors: vec![Vec::new(); blocks.len()],
+			instructions,
+			blocks,
+			edges: Vec::new(),
+		};
+		for from in 0..graph.blocks.len() {
+			let block = &graph.blocks[from];
+			let (pc, insn) = &graph.instructions[block.instructions.end - 1];
+			let kind = match insn {
+				Instructions::TABLESWITCH { .. } | Instructions::LOOKUPSWITCH { .. } => EdgeKind::Switch,
+				_ => EdgeKind::Jump,
+			};
+			let mut out = insn
+				.branch_targets(*pc)
+				.into_iter()
+				.map(|target| (graph.block_at(target).expect("targets were checked"), kind))
+				.collect::<Vec<_>>();
+			// Falling off the end of the code gets no edge, the verifier rejects it anyway.
+			if insn.falls_through() && from + 1 < graph.blocks.len() {
+				out.insert(0, (from + 1, EdgeKind::FallThrough));
+			}
+			let start_pc = block.start_pc;
+			for entry in exception_table {
+				if (entry.start_pc as u32..entry.end_pc as u32).contains(&start_pc) {
+					let handler = graph.block_at(entry.handler_pc as u32).expect("handlers were checked");
+					out.push((
+						handler,
+						EdgeKind::Exception {
+							catch_type: entry.catch_type,
+						},
+					));
+				}
+			}
+			for (to, kind) in out {
+				graph.add_edge(Edge { from, to, kind });
+			}
+		}
+		Ok(graph)
+	}
+
+	fn add_edge(&mut self, edge: Edge) {
+		// A switch can have several cases jumping to the same place.
+		if self.successors[edge.from].iter().any(|i| self.edges[*i] == edge) {
+			return;
+		}
+		self.successors[edge.from].push(self.edges.len());
+		self.predecessors[edge.to].push(self.edges.len());
+		self.edges.push(edge);
+	}
+
+	pub fn instructions(&self) -> &[(u32, Instructions)] {
+		&self.instructions
+	}
+
+	pub fn blocks(&self) -> &[BasicBlock] {
+		&self.blocks
+	}
+
+	pub fn block(&self, block: usize) -> &BasicBlock {
+		&self.blocks[block]
+	}
+
+	/// The block the instruction at `pc` belongs to.
+	pub fn block_at(&self, pc: u32) -> Option<usize> {
+		let block = self
+			.blocks
+			.partition_point(|block| block.start_pc <= pc)
+			.checked_sub(1)?;
+		(pc < self.blocks[block].end_pc).then_some(block)
+	}
+
+	pub fn edges(&self) -> &[Edge] {
+		&self.edges
+	}
+
+	/// Edges out of `block`: fall through first, then jumps in target order, then handlers in exception table order.
+	pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+		self.successors[block].iter().map(|i| &self.edges[*i])
+	}
+
+	pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+		self.predecessors[block].iter().map(|i| &self.edges[*i])
+	}
+
+	/// The instructions of `block` with their pcs.
+	pub fn block_instructions(&self, block: usize) -> &[(u32, Instructions)] {
+		&self.instructions[self.blocks[block].instructions.clone()]
+	}
+
+	/// The blocks reachable from the entry, exception edges included, each before its successors unless the edge to
+	/// it is a back edge. Successors are visited in `successors` order, so the order is the same on every run.
+	pub fn reverse_post_order(&self) -> Vec<usize> {
+		if self.blocks.is_empty() {
+			return Vec::new();
+		}
+
+		let mut visited = vec![false; self.blocks.len()];
+		let mut post_order = Vec::with_capacity(self.blocks.len());
+		// blocks being visited, with how many of their successors have been looked at
+		let mut stack = vec![(0, 0)];
+		visited[0] = true;
+		while let Some((block, next)) = stack.last().copied() {
+			match self.successors[block].get(next) {
+				Some(edge) => {
+					stack.last_mut().expect("isn't empty").1 += 1;
+					let to = self.edges[*edge].to;
+					if !visited[to] {
+						visited[to] = true;
+						stack.push((to, 0));
+					}
+				}
+				None => {
+					post_order.push(block);
+					stack.pop();
+				}
+			}
+		}
+		post_order.reverse();
+		post_order
+	}
+
+	pub fn dominators(&self) -> Dominators {
+		// https://www.cs.tufts.edu/comp/150FP/archive/keith-cooper/dom14.pdf
+		let order = self.reverse_post_order();
+		let mut position = vec![usize::MAX; self.blocks.len()];
+		for (i, block) in order.iter().enumerate() {
+			position[*block] = i;
+		}
+
+		let mut idom = vec![None; self.blocks.len()];
+		let Some(entry) = order.first().copied() else {
+			return Dominators { idom };
+		};
+		idom[entry] = Some(entry);
+		let mut changed = true;
+		while changed {
+			changed = false;
+			for block in &order[1..] {
+				let mut new_idom: Option<usize> = None;
+				for pred in self.predecessors(*block).map(|edge| edge.from) {
+					if idom[pred].is_none() {
+						continue;
+					}
+					new_idom = Some(match new_idom {
+						None => pred,
+						Some(mut other) => {
+							let mut pred = pred;
+							while pred != other {
+								while position[pred] > position[other] {
+									pred = idom[pred].expect("processed");
+								}
+								while position[other] > position[pred] {
+									other = idom[other].expect("processed");
+								}
+							}
+							pred
+						}
+					});
+				}
+				if new_idom.is_some() && idom[*block] != new_idom {
+					idom[*block] = new_idom;
+					changed = true;
+				}
+			}
+		}
+		idom[entry] = None;
+		Dominators { idom }
+	}
+
+	/// Blocks that start a loop, in code order.
+	pub fn loop_headers(&self) -> Vec<usize> {
+		let dominators = self.dominators();
+		let headers = self
+			.edges
+			.iter()
+			.filter(|edge| dominators.dominates(edge.to, edge.from))
+			.map(|edge| edge.to)
+			.collect::<BTreeSet<_>>();
+		headers.into_iter().collect()
+	}
+}
+
+/// Which blocks every path from the entry goes through first. Unreachable blocks are dominated by nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dominators {
+	idom: Vec<Option<usize>>,
+}
+
+impl Dominators {
+	/// The closest block that dominates `block`, `None` for the entry and unreachable blocks.
+	pub fn immediate(&self, block: usize) -> Option<usize> {
+		self.idom[block]
+	}
+
+	/// Whether every path from the entry to `b` goes through `a`. A block dominates itself.
+	pub fn dominates(&self, a: usize, b: usize) -> bool {
+		let mut block = b;
+		loop {
+			if block == a {
+				return true;
+			}
+			match self.idom[block] {
+				Some(idom) => block = idom,
+				None => return false,
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use smallvec::smallvec;
+
+	use super::*;
+
+	#[test]
+	fn blocks_and_edges() {
+		// 0: iload_0, 1: ifeq +9, 4: iinc 0 -1, 7: goto -7, 10: iconst_0, 11: tableswitch, 32: return, 33: athrow
+		let code = [
+			&[0x1A, 0x99, 0, 9, 0x84, 0, 0xFF, 0xA7, 0xFF, 0xF9, 0x03, 0xAA][..],
+			// default +21, low 0, high 1, +21, +22
+			&[0, 0, 0, 21, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 21, 0, 0, 0, 22][..],
+			&[0xB1, 0xBF][..],
+		]
+		.concat();
+		let graph = ControlFlowGraph::new(
+			&[],
+			&CodeAttribute {
+				max_stack: 1,
+				max_locals: 1,
+				code,
+				exception_table: smallvec![CodeAttributeException {
+					start_pc: 4,
+					end_pc: 7,
+					handler_pc: 33,
+					catch_type: 0,
+				}],
+				attributes: Default::default(),
+			},
+		)
+		.unwrap();
+
+		let starts = graph.blocks().iter().map(|block| block.start_pc).collect::<Vec<_>>();
+		assert_eq!(starts, [0, 4, 7, 10, 32, 33]);
+		assert_eq!(graph.block_at(12), Some(3));
+		let successors = |block| {
+			graph
+				.successors(block)
+				.map(|edge| (edge.to, edge.kind))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(successors(0), [(1, EdgeKind::FallThrough), (3, EdgeKind::Jump)]);
+		assert_eq!(
+			successors(1),
+			[(2, EdgeKind::FallThrough), (5, EdgeKind::Exception { catch_type: 0 })]
+		);
+		assert_eq!(successors(2), [(0, EdgeKind::Jump)]);
+		assert_eq!(successors(3), [(4, EdgeKind::Switch), (5, EdgeKind::Switch)]);
+		assert_eq!(successors(5), []);
+		let predecessors = graph.predecessors(0).map(|edge| edge.from).collect::<Vec<_>>();
+		assert_eq!(predecessors, [2]);
+
+		assert!(matches!(graph.block_instructions(2), [(7, Instructions::GOTO(-7))]));
+		assert_eq!(graph.reverse_post_order(), [0, 3, 4, 1, 5, 2]);
+		let dominators = graph.dominators();
+		assert_eq!(dominators.immediate(5), Some(0));
+		assert!(dominators.dominates(1, 2) && !dominators.dominates(3, 5));
+		assert_eq!(graph.loop_headers(), [0]);
+	}
+}
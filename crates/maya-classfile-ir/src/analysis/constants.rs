@@ -0,0 +1,475 @@
+// Static fields that end up holding a constant after class initialization, for mining configuration out of classes.
+// ConstantValue attributes only cover final fields initialized to a literal; this also follows the straight-line start
+// of <clinit>, where javac puts initializers like `static final long TIMEOUT = 30 * 1000L`, and evaluates simple
+// constant expressions on an abstract operand stack. Anything it can't follow is unknown, and a field assigned an
+// unknown value anywhere in <clinit> is left out rather than guessed.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+	class_pool::{CPConstValueRefKind, CPFieldRef, IRClassfileError, IRCpTag},
+	code::Instructions,
+	descriptor::MethodDescriptor,
+	IRClassFile,
+};
+
+/// Static field name to the constant it holds once the class is initialized. Only fields nothing else can change are
+/// included: finals, and private fields no method but <clinit> writes.
+pub fn static_constants(class: &IRClassFile) -> Result<BTreeMap<String, CPConstValueRefKind>, IRClassfileError> {
+	let mut candidates = class
+		.fields
+		.iter()
+		.filter(|field| {
+			field.access_flags.is_static() && (field.access_flags.is_final() || field.access_flags.is_private())
+		})
+		.map(|field| field.name())
+		.collect::<BTreeSet<_>>();
+
+	let mut clinit = None;
+	for method in &class.methods {
+		let Some(code) = method.code() else {
+			continue;
+		};
+		let instructions = Instructions::read_all(&class.cp, &code.code)?;
+		if method.name() == "<clinit>" {
+			clinit = Some((code, instructions));
+			continue;
+		}
+
+		// Final fields can only be written during initialization, so this is about private non-finals.
+		for (_, insn) in &instructions {
+			if let Instructions::PUTSTATIC(field) = insn {
+				if field.class.data.data.as_str() == class.class_name() {
+					candidates.remove(field.name_and_ty.name.data.as_str());
+				}
+			}
+		}
+	}
+
+	let mut constants = class
+		.fields
+		.iter()
+		.filter(|field| candidates.contains(field.name()))
+		.filter_map(|field| Some((field.name().to_string(), field.constant_value()?)))
+		.collect::<BTreeMap<_, _>>();
+
+	let Some((code, instructions)) = clinit else {
+		return Ok(constants);
+	};
+
+	// The candidate a field ref points at, by name.
+	let own_field = |field: &CPFieldRef| {
+		let name = field.name_and_ty.name.data.as_str();
+		candidates
+			.get(name)
+			.copied()
+			.filter(|_| field.class.data.data.as_str() == class.class_name())
+	};
+
+	// The straight-line prefix ends at the first instruction control can reach some other way.
+	let mut boundaries = instructions
+		.iter()
+		.flat_map(|(pc, insn)| insn.branch_targets(*pc))
+		.collect::<BTreeSet<_>>();
+	boundaries.extend(code.exception_table.iter().map(|entry| entry.handler_pc as u32));
+
+	let mut poisoned = BTreeSet::new();
+	let mut stack = Stack::default();
+	let mut straight = true;
+	for (pc, insn) in &instructions {
+		straight &= !boundaries.contains(pc);
+		if !straight {
+			// Past the prefix a field may or may not be assigned, whatever the value.
+			if let Instructions::PUTSTATIC(field) = insn {
+				if let Some(name) = own_field(field) {
+					poisoned.insert(name);
+				}
+			}
+			continue;
+		}
+
+		match insn {
+			Instructions::GETSTATIC(field) => {
+				let value = own_field(field).and_then(|name| constants.get(name).cloned());
+				stack.push(value);
+			}
+			Instructions::PUTSTATIC(field) => match (own_field(field), stack.pop()) {
+				(Some(name), Some(value)) => {
+					constants.insert(name.to_string(), value);
+				}
+				(Some(name), None) => {
+					poisoned.insert(name);
+				}
+				_ => {}
+			},
+			insn => stack.step(insn),
+		}
+
+		straight &= insn.falls_through() && insn.branch_offsets().is_empty();
+	}
+
+	constants.retain(|name, _| !poisoned.contains(name.as_str()));
+	Ok(constants)
+}
+
+/// The operand stack, one entry per value regardless of its size. `None` is a value that isn't a known constant.
+#[derive(Debug, Default)]
+struct Stack {
+	values: Vec<Option<CPConstValueRefKind>>,
+}
+
+impl Stack {
+	fn push(&mut self, value: Option<CPConstValueRefKind>) {
+		self.values.push(value);
+	}
+
+	fn push_unknown(&mut self) {
+		self.values.push(None);
+	}
+
+	// Popping past what's been tracked is fine, it just yields unknowns.
+	fn pop(&mut self) -> Option<CPConstValueRefKind> {
+		self.values.pop().flatten()
+	}
+
+	fn pop_n(&mut self, n: usize) {
+		let len = self.values.len().saturating_sub(n);
+		self.values.truncate(len);
+	}
+
+	fn unary(&mut self, f: impl FnOnce(CPConstValueRefKind) -> Option<CPConstValueRefKind>) {
+		let value = self.pop().and_then(f);
+		self.push(value);
+	}
+
+	fn binary(&mut self, f: impl FnOnce(CPConstValueRefKind, CPConstValueRefKind) -> Option<CPConstValueRefKind>) {
+		let b = self.pop();
+		let a = self.pop();
+		let value = a.zip(b).and_then(|(a, b)| f(a, b));
+		self.push(value);
+	}
+
+	fn int(&mut self, f: impl FnOnce(i32, i32) -> Option<i32>) {
+		self.binary(|a, b| match (a, b) {
+			(CPConstValueRefKind::Int(a), CPConstValueRefKind::Int(b)) => f(a, b).map(CPConstValueRefKind::Int),
+			_ => None,
+		});
+	}
+
+	fn long(&mut self, f: impl FnOnce(i64, i64) -> Option<i64>) {
+		self.binary(|a, b| match (a, b) {
+			(CPConstValueRefKind::Long(a), CPConstValueRefKind::Long(b)) => f(a, b).map(CPConstValueRefKind::Long),
+			_ => None,
+		});
+	}
+
+	// Long shifts take an int distance.
+	fn long_shift(&mut self, f: impl FnOnce(i64, u32) -> i64) {
+		self.binary(|a, b| match (a, b) {
+			(CPConstValueRefKind::Long(a), CPConstValueRefKind::Int(b)) => {
+				Some(CPConstValueRefKind::Long(f(a, b as u32 & 0x3F)))
+			}
+			_ => None,
+		});
+	}
+
+	fn float(&mut self, f: impl FnOnce(f32, f32) -> f32) {
+		self.binary(|a, b| match (a, b) {
+			(CPConstValueRefKind::Float(a), CPConstValueRefKind::Float(b)) => Some(CPConstValueRefKind::Float(f(a, b))),
+			_ => None,
+		});
+	}
+
+	fn double(&mut self, f: impl FnOnce(f64, f64) -> f64) {
+		self.binary(|a, b| match (a, b) {
+			(CPConstValueRefKind::Double(a), CPConstValueRefKind::Double(b)) => {
+				Some(CPConstValueRefKind::Double(f(a, b)))
+			}
+			_ => None,
+		});
+	}
+
+	/// `nan` is what the comparison yields when either side is NaN, -1 for fcmpl/dcmpl and 1 for fcmpg/dcmpg.
+	fn compare(&mut self, nan: i32) {
+		self.binary(|a, b| {
+			let ordering = match (a, b) {
+				(CPConstValueRefKind::Long(a), CPConstValueRefKind::Long(b)) => Some(a.cmp(&b)),
+				(CPConstValueRefKind::Float(a), CPConstValueRefKind::Float(b)) => a.partial_cmp(&b),
+				(CPConstValueRefKind::Double(a), CPConstValueRefKind::Double(b)) => a.partial_cmp(&b),
+				_ => return None,
+			};
+			Some(CPConstValueRefKind::Int(
+				ordering.map_or(nan, |ordering| ordering as i32),
+			))
+		});
+	}
+
+	fn invoke(&mut self, descriptor: &str, has_receiver: bool) {
+		let Ok(descriptor) = MethodDescriptor::parse(descriptor) else {
+			self.values.clear();
+			return;
+		};
+		self.pop_n(descriptor.params.len() + has_receiver as usize);
+		if descriptor.ret.is_some() {
+			self.push_unknown();
+		}
+	}
+
+	/// Everything but the static field accesses, which need the class.
+	fn step(&mut self, insn: &Instructions) {
+		use CPConstValueRefKind::{Double, Float, Int, Long};
+
+		match insn {
+			Instructions::NOP | Instructions::CHECKCAST(_) => {}
+			Instructions::ICONST_M1 => self.push(Some(Int(-1))),
+			Instructions::ICONST_0 => self.push(Some(Int(0))),
+			Instructions::ICONST_1 => self.push(Some(Int(1))),
+			Instructions::ICONST_2 => self.push(Some(Int(2))),
+			Instructions::ICONST_3 => self.push(Some(Int(3))),
+			Instructions::ICONST_4 => self.push(Some(Int(4))),
+			Instructions::ICONST_5 => self.push(Some(Int(5))),
+			Instructions::LCONST_0 => self.push(Some(Long(0))),
+			Instructions::LCONST_1 => self.push(Some(Long(1))),
+			Instructions::FCONST_0 => self.push(Some(Float(0.0))),
+			Instructions::FCONST_1 => self.push(Some(Float(1.0))),
+			Instructions::FCONST_2 => self.push(Some(Float(2.0))),
+			Instructions::DCONST_0 => self.push(Some(Double(0.0))),
+			Instructions::DCONST_1 => self.push(Some(Double(1.0))),
+			Instructions::BIPUSH(value) => self.push(Some(Int(*value as i32))),
+			Instructions::SIPUSH(value) => self.push(Some(Int(*value as i32))),
+			Instructions::LDC(tag) => self.push(match tag {
+				IRCpTag::Integer(value) => Some(Int(*value)),
+				IRCpTag::Float(value) => Some(Float(*value)),
+				IRCpTag::Long(value) => Some(Long(*value)),
+				IRCpTag::Double(value) => Some(Double(*value)),
+				IRCpTag::String(value) => Some(CPConstValueRefKind::String(value.data.clone())),
+				_ => None,
+			}),
+
+			Instructions::ACONST_NULL
+			| Instructions::ILOAD(_)
+			| Instructions::LLOAD(_)
+			| Instructions::FLOAD(_)
+			| Instructions::DLOAD(_)
+			| Instructions::ALOAD(_)
+			| Instructions::NEW(_) => self.push_unknown(),
+			Instructions::ISTORE(_)
+			| Instructions::LSTORE(_)
+			| Instructions::FSTORE(_)
+			| Instructions::DSTORE(_)
+			| Instructions::ASTORE(_)
+			| Instructions::POP
+			| Instructions::MONITORENTER
+			| Instructions::MONITOREXIT => self.pop_n(1),
+			Instructions::DUP => {
+				let value = self.pop();
+				self.push(value.clone());
+				self.push(value);
+			}
+
+			Instructions::IALOAD
+			| Instructions::LALOAD
+			| Instructions::FALOAD
+			| Instructions::DALOAD
+			| Instructions::AALOAD
+			| Instructions::BALOAD
+			| Instructions::CALOAD
+			| Instructions::SALOAD => {
+				self.pop_n(2);
+				self.push_unknown();
+			}
+			Instructions::IASTORE
+			| Instructions::LASTORE
+			| Instructions::FASTORE
+			| Instructions::DASTORE
+			| Instructions::AASTORE
+			| Instructions::BASTORE
+			| Instructions::CASTORE
+			| Instructions::SASTORE => self.pop_n(3),
+			Instructions::NEWARRAY(_)
+			| Instructions::ANEWARRAY(_)
+			| Instructions::ARRAYLENGTH
+			| Instructions::INSTANCEOF(_)
+			| Instructions::GETFIELD(_) => {
+				self.pop_n(1);
+				self.push_unknown();
+			}
+			Instructions::PUTFIELD(_) => self.pop_n(2),
+
+			Instructions::IADD => self.int(|a, b| Some(a.wrapping_add(b))),
+			Instructions::ISUB => self.int(|a, b| Some(a.wrapping_sub(b))),
+			Instructions::IMUL => self.int(|a, b| Some(a.wrapping_mul(b))),
+			// Division by zero throws, so nothing gets assigned.
+			Instructions::IDIV => self.int(|a, b| (b != 0).then(|| a.wrapping_div(b))),
+			Instructions::IREM => self.int(|a, b| (b != 0).then(|| a.wrapping_rem(b))),
+			Instructions::ISHL => self.int(|a, b| Some(a.wrapping_shl(b as u32))),
+			Instructions::ISHR => self.int(|a, b| Some(a.wrapping_shr(b as u32))),
+			Instructions::IUSHR => self.int(|a, b| Some((a as u32).wrapping_shr(b as u32) as i32)),
+			Instructions::IAND => self.int(|a, b| Some(a & b)),
+			Instructions::IOR => self.int(|a, b| Some(a | b)),
+			Instructions::IXOR => self.int(|a, b| Some(a ^ b)),
+			Instructions::LADD => self.long(|a, b| Some(a.wrapping_add(b))),
+			Instructions::LSUB => self.long(|a, b| Some(a.wrapping_sub(b))),
+			Instructions::LMUL => self.long(|a, b| Some(a.wrapping_mul(b))),
+			Instructions::LDIV => self.long(|a, b| (b != 0).then(|| a.wrapping_div(b))),
+			Instructions::LREM => self.long(|a, b| (b != 0).then(|| a.wrapping_rem(b))),
+			Instructions::LSHL => self.long_shift(|a, b| a << b),
+			Instructions::LSHR => self.long_shift(|a, b| a >> b),
+			Instructions::LUSHR => self.long_shift(|a, b| ((a as u64) >> b) as i64),
+			Instructions::LAND => self.long(|a, b| Some(a & b)),
+			Instructions::LOR => self.long(|a, b| Some(a | b)),
+			Instructions::LXOR => self.long(|a, b| Some(a ^ b)),
+			Instructions::FADD => self.float(|a, b| a + b),
+			Instructions::FSUB => self.float(|a, b| a - b),
+			Instructions::FMUL => self.float(|a, b| a * b),
+			Instructions::FDIV => self.float(|a, b| a / b),
+			Instructions::FREM => self.float(|a, b| a % b),
+			Instructions::DADD => self.double(|a, b| a + b),
+			Instructions::DSUB => self.double(|a, b| a - b),
+			Instructions::DMUL => self.double(|a, b| a * b),
+			Instructions::DDIV => self.double(|a, b| a / b),
+			Instructions::DREM => self.double(|a, b| a % b),
+			Instructions::LCMP | Instructions::FCMPL | Instructions::DCMPL => self.compare(-1),
+			Instructions::FCMPG | Instructions::DCMPG => self.compare(1),
+
+			Instructions::INEG => self.unary(|a| match a {
+				Int(a) => Some(Int(a.wrapping_neg())),
+				_ => None,
+			}),
+			Instructions::LNEG => self.unary(|a| match a {
+				Long(a) => Some(Long(a.wrapping_neg())),
+				_ => None,
+			}),
+			Instructions::FNEG => self.unary(|a| match a {
+				Float(a) => Some(Float(-a)),
+				_ => None,
+			}),
+			Instructions::DNEG => self.unary(|a| match a {
+				Double(a) => Some(Double(-a)),
+				_ => None,
+			}),
+			// Rust's `as` saturates and maps NaN to 0, like the JVM.
+			Instructions::I2L
+			| Instructions::I2F
+			| Instructions::I2D
+			| Instructions::L2I
+			| Instructions::L2F
+			| Instructions::L2D
+			| Instructions::F2I
+			| Instructions::F2L
+			| Instructions::F2D
+			| Instructions::D2I
+			| Instructions::D2L
+			| Instructions::D2F
+			| Instructions::I2B
+			| Instructions::I2C
+			| Instructions::I2S => self.unary(|a| convert(insn, a)),
+
+			Instructions::INVOKEVIRTUAL(method) | Instructions::INVOKESPECIAL(method) => {
+				self.invoke(method.name_and_ty.ty.data.as_str(), true)
+			}
+			Instructions::INVOKESTATIC(method) => self.invoke(method.name_and_ty.ty.data.as_str(), false),
+			Instructions::INVOKEINTERFACE { method, .. } => self.invoke(method.name_and_ty.ty.data.as_str(), true),
+			Instructions::INVOKEDYNAMIC(call_site) => self.invoke(call_site.name_and_ty.ty.data.as_str(), false),
+
+			// Not worth modelling: forget what's on the stack, which only loses constants.
+			_ => self.values.clear(),
+		}
+	}
+}
+
+fn convert(insn: &Instructions, value: CPConstValueRefKind) -> Option<CPConstValueRefKind> {
+	use CPConstValueRefKind::{Double, Float, Int, Long};
+
+	Some(match (insn, value) {
+		(Instructions::I2L, Int(a)) => Long(a as i64),
+		(Instructions::I2F, Int(a)) => Float(a as f32),
+		(Instructions::I2D, Int(a)) => Double(a as f64),
+		(Instructions::L2I, Long(a)) => Int(a as i32),
+		(Instructions::L2F, Long(a)) => Float(a as f32),
+		(Instructions::L2D, Long(a)) => Double(a as f64),
+		(Instructions::F2I, Float(a)) => Int(a as i32),
+		(Instructions::F2L, Float(a)) => Long(a as i64),
+		(Instructions::F2D, Float(a)) => Double(a as f64),
+		(Instructions::D2I, Double(a)) => Int(a as i32),
+		(Instructions::D2L, Double(a)) => Long(a as i64),
+		(Instructions::D2F, Double(a)) => Float(a as f32),
+		(Instructions::I2B, Int(a)) => Int(a as i8 as i32),
+		(Instructions::I2C, Int(a)) => Int(a as u16 as i32),
+		(Instructions::I2S, Int(a)) => Int(a as i16 as i32),
+		_ => return None,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use smallvec::SmallVec;
+
+	use super::*;
+	use crate::{
+		attribute::{Attributes, CodeAttribute, IRAttribute},
+		builder::ClassBuilder,
+		flags::{FieldAccessFlags, MethodAccessFlags},
+	};
+
+	#[test]
+	fn folds_clinit_prefix() {
+		let mut builder = ClassBuilder::new("p/Config").unwrap();
+		let field = |builder: &mut ClassBuilder, name: &str, descriptor: &str| {
+			let field = CPFieldRef::find_or_add(builder.cp(), "p/Config", name, descriptor).unwrap();
+			field.index.to_be_bytes()
+		};
+		let [size_hi, size_lo] = field(&mut builder, "SIZE", "I");
+		let [timeout_hi, timeout_lo] = field(&mut builder, "TIMEOUT", "J");
+		let [mutable_hi, mutable_lo] = field(&mut builder, "mutable", "I");
+		let [late_hi, late_lo] = field(&mut builder, "LATE", "I");
+		let code = [
+			// SIZE = 6 * 7
+			&[0x10, 6, 0x10, 7, 0x68, 0xB3, size_hi, size_lo][..],
+			// TIMEOUT = (long) SIZE << 2
+			&[0xB2, size_hi, size_lo, 0x85, 0x05, 0x79, 0xB3, timeout_hi, timeout_lo],
+			// mutable = 1, but it isn't final and another method writes it
+			&[0x04, 0xB3, mutable_hi, mutable_lo],
+			// if (SIZE == 0) goto end; LATE = 1; end: return
+			&[0xB2, size_hi, size_lo, 0x99, 0, 7, 0x04, 0xB3, late_hi, late_lo, 0xB1],
+		];
+		let code = CodeAttribute {
+			max_stack: 4,
+			max_locals: 0,
+			code: code.concat(),
+			exception_table: SmallVec::new(),
+			attributes: Attributes::new(),
+		};
+		let setter = CodeAttribute {
+			max_stack: 1,
+			max_locals: 0,
+			code: vec![0x03, 0xB3, mutable_hi, mutable_lo, 0xB1],
+			exception_table: SmallVec::new(),
+			attributes: Attributes::new(),
+		};
+
+		let constant = FieldAccessFlags::STATIC | FieldAccessFlags::FINAL;
+		builder
+			.field(constant, "SIZE", "I", [])
+			.unwrap()
+			.field(constant, "TIMEOUT", "J", [])
+			.unwrap()
+			.field(FieldAccessFlags::PRIVATE | FieldAccessFlags::STATIC, "mutable", "I", [])
+			.unwrap()
+			.field(constant, "LATE", "I", [])
+			.unwrap()
+			.method(MethodAccessFlags::STATIC, "<clinit>", "()V", [IRAttribute::Code(code)])
+			.unwrap()
+			.method(MethodAccessFlags::STATIC, "reset", "()V", [IRAttribute::Code(setter)])
+			.unwrap();
+
+		let constants = static_constants(&builder.build()).unwrap();
+		assert_eq!(
+			constants.into_iter().collect::<Vec<_>>(),
+			[
+				("SIZE".to_string(), CPConstValueRefKind::Int(42)),
+				("TIMEOUT".to_string(), CPConstValueRefKind::Long(168)),
+			]
+		);
+	}
+}
@@ -0,0 +1,388 @@
+// Data-flow analysis over the control flow graph: an `Analysis` says what a fact is, how facts from several paths
+// combine and how an instruction changes one, and `solve` finds what holds at every block by iterating to a fixed
+// point. Facts only grow, so for a finite lattice this terminates.
+//
+// Exceptions can be thrown from any instruction in a try range, not just the last one in its block. Going forward, a
+// handler sees what held before each instruction of the blocks it covers, and going backward what holds at the
+// handler has to hold before each of them.
+//
+// Liveness of locals and reaching definitions are here both for use and as examples of writing analyses. Locals are
+// tracked by index, a long or double by its first slot.
+
+use std::collections::BTreeSet;
+
+use crate::{
+	analysis::cfg::{ControlFlowGraph, EdgeKind},
+	class_pool::IRClassfileError,
+	code::Instructions,
+	descriptor::MethodDescriptor,
+	IRMethodInfo,
+};
+
+/// The facts an analysis computes. `join` combines the facts of two paths and may only ever add to `self`.
+pub trait Lattice: Clone + Eq {
+	fn join(&mut self, other: &Self);
+}
+
+/// Sets grow by union.
+impl<T: Ord + Clone> Lattice for BTreeSet<T> {
+	fn join(&mut self, other: &Self) {
+		self.extend(other.iter().cloned());
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+	Forward,
+	Backward,
+}
+
+pub trait Analysis {
+	type Fact: Lattice;
+
+	const DIRECTION: Direction;
+
+	/// What holds where the analysis starts: on entry to the method going forward, after its exits going backward.
+	fn boundary(&self) -> Self::Fact;
+
+	/// What holds before anything is known, joining it with a fact leaving that fact as it is.
+	fn bottom(&self) -> Self::Fact;
+
+	/// Applies the instruction at `pc` to `fact`. Going forward `fact` holds before the instruction and is turned into
+	/// what holds after it, going backward the other way around.
+	fn transfer(&self, fact: &mut Self::Fact, pc: u32, insn: &Instructions);
+}
+
+/// What holds at the start and end of each block, in code order whatever the direction of the analysis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataFlowResults<F> {
+	entry: Vec<F>,
+	exit: Vec<F>,
+}
+
+impl<F: Lattice> DataFlowResults<F> {
+	/// What holds before the first instruction of `block`.
+	pub fn entry(&self, block: usize) -> &F {
+		&self.entry[block]
+	}
+
+	/// What holds after the last instruction of `block`.
+	pub fn exit(&self, block: usize) -> &F {
+		&self.exit[block]
+	}
+
+	/// What holds before each instruction of `block`, in code order.
+	pub fn instruction_facts<A>(&self, cfg: &ControlFlowGraph, analysis: &A, block: usize) -> Vec<F>
+	where
+		A: Analysis<Fact = F>,
+	{
+		match A::DIRECTION {
+			Direction::Forward => forward_block(cfg, analysis, block, &self.entry[block]).0,
+			Direction::Backward => {
+				let handlers = handler_facts(cfg, analysis, &self.entry, block);
+				backward_block(cfg, analysis, block, &self.exit[block], &handlers).0
+			}
+		}
+	}
+}
+
+/// Runs `analysis` over the graph to a fixed point. Blocks that can't be reached from the entry keep `bottom`.
+pub fn solve<A: Analysis>(cfg: &ControlFlowGraph, analysis: &A) -> DataFlowResults<A::Fact> {
+	let blocks = cfg.blocks().len();
+	let mut entry = vec![analysis.bottom(); blocks];
+	let mut exit = vec![analysis.bottom(); blocks];
+	// going forward, the join of what held before each instruction, for the handlers covering the block
+	let mut thrown = vec![analysis.bottom(); blocks];
+
+	let mut order = cfg.reverse_post_order();
+	if A::DIRECTION == Direction::Backward {
+		order.reverse();
+	}
+	let mut position = vec![usize::MAX; blocks];
+	for (i, block) in order.iter().enumerate() {
+		position[*block] = i;
+	}
+	// positions in `order`, so blocks are taken in order and a block is only queued once
+	let mut worklist = (0..order.len()).collect::<BTreeSet<_>>();
+
+	while let Some(next) = worklist.pop_first() {
+		let block = order[next];
+		match A::DIRECTION {
+			Direction::Forward => {
+				let mut fact = match block {
+					0 => analysis.boundary(),
+					_ => analysis.bottom(),
+				};
+				for edge in cfg.predecessors(block) {
+					fact.join(match edge.kind {
+						EdgeKind::Exception { .. } => &thrown[edge.from],
+						_ => &exit[edge.from],
+					});
+				}
+				let (before, out) = forward_block(cfg, analysis, block, &fact);
+				let mut caught = analysis.bottom();
+				for fact in &before {
+					caught.join(fact);
+				}
+				entry[block] = fact;
+				if out != exit[block] || caught != thrown[block] {
+					exit[block] = out;
+					thrown[block] = caught;
+					worklist.extend(cfg.successors(block).map(|edge| position[edge.to]));
+				}
+			}
+			Direction::Backward => {
+				let mut normal = cfg
+					.successors(block)
+					.filter(|edge| !matches!(edge.kind, EdgeKind::Exception { .. }))
+					.peekable();
+				let mut fact = match normal.peek() {
+					None => analysis.boundary(),
+					Some(_) => analysis.bottom(),
+				};
+				for edge in normal {
+					fact.join(&entry[edge.to]);
+				}
+				let handlers = handler_facts(cfg, analysis, &entry, block);
+				let (_, out) = backward_block(cfg, analysis, block, &fact, &handlers);
+				exit[block] = fact;
+				if out != entry[block] {
+					entry[block] = out;
+					worklist.extend(cfg.predecessors(block).map(|edge| position[edge.from]));
+				}
+			}
+		}
+	}
+	DataFlowResults { entry, exit }
+}
+
+// What holds before each instruction of the block and after the last, given what holds on entry.
+fn forward_block<A: Analysis>(
+	cfg: &ControlFlowGraph,
+	analysis: &A,
+	block: usize,
+	entry: &A::Fact,
+) -> (Vec<A::Fact>, A::Fact) {
+	let mut fact = entry.clone();
+	let mut before = Vec::with_capacity(cfg.block(block).instructions.len());
+	for (pc, insn) in cfg.block_instructions(block) {
+		before.push(fact.clone());
+		analysis.transfer(&mut fact, *pc, insn);
+	}
+	(before, fact)
+}
+
+// What holds before each instruction of the block, in code order, and so on entry, given what holds after the last
+// one and at the handlers covering the block.
+fn backward_block<A: Analysis>(
+	cfg: &ControlFlowGraph,
+	analysis: &A,
+	block: usize,
+	exit: &A::Fact,
+	handlers: &A::Fact,
+) -> (Vec<A::Fact>, A::Fact) {
+	let mut fact = exit.clone();
+	let mut before = Vec::with_capacity(cfg.block(block).instructions.len());
+	for (pc, insn) in cfg.block_instructions(block).iter().rev() {
+		analysis.transfer(&mut fact, *pc, insn);
+		fact.join(handlers);
+		before.push(fact.clone());
+	}
+	before.reverse();
+	(before, fact)
+}
+
+fn handler_facts<A: Analysis>(cfg: &ControlFlowGraph, analysis: &A, entry: &[A::Fact], block: usize) -> A::Fact {
+	let mut fact = analysis.bottom();
+	for edge in cfg.successors(block) {
+		if let EdgeKind::Exception { .. } = edge.kind {
+			fact.join(&entry[edge.to]);
+		}
+	}
+	fact
+}
+
+// The local an instruction reads, and the one it writes with whether that's a long or double.
+fn local_access(insn: &Instructions) -> (Option<u16>, Option<(u16, bool)>) {
+	use Instructions as I;
+
+	match insn {
+		I::ILOAD(index) | I::LLOAD(index) | I::FLOAD(index) | I::DLOAD(index) | I::ALOAD(index) | I::RET(index) => {
+			(Some(*index), None)
+		}
+		I::ISTORE(index) | I::FSTORE(index) | I::ASTORE(index) => (None, Some((*index, false))),
+		I::LSTORE(index) | I::DSTORE(index) => (None, Some((*index, true))),
+		I::IINC { index, .. } => (Some(*index), Some((*index, false))),
+		_ => (None, None),
+	}
+}
+
+/// Locals whose current value may still be read, a backward analysis.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Liveness;
+
+impl Analysis for Liveness {
+	type Fact = BTreeSet<u16>;
+
+	const DIRECTION: Direction = Direction::Backward;
+
+	fn boundary(&self) -> Self::Fact {
+		BTreeSet::new()
+	}
+
+	fn bottom(&self) -> Self::Fact {
+		BTreeSet::new()
+	}
+
+	fn transfer(&self, fact: &mut Self::Fact, _pc: u32, insn: &Instructions) {
+		let (read, write) = local_access(insn);
+		if let Some((index, _)) = write {
+			fact.remove(&index);
+		}
+		if let Some(index) = read {
+			fact.insert(index);
+		}
+	}
+}
+
+/// A value stored to a local.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Definition {
+	pub local: u16,
+	// the pc of the store, None for the value the local had on entry, `this` or a parameter
+	pub pc: Option<u32>,
+}
+
+/// Which stores to locals may have last set them, a forward analysis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReachingDefinitions {
+	// first slots of `this` and the parameters
+	parameters: Vec<u16>,
+}
+
+impl ReachingDefinitions {
+	pub fn new(method: &IRMethodInfo) -> Result<Self, IRClassfileError> {
+		let mut parameters = Vec::new();
+		let mut slot = 0;
+		if !method.access_flags.is_static() {
+			parameters.push(0);
+			slot = 1;
+		}
+		for param in MethodDescriptor::parse(method.descriptor())?.params {
+			parameters.push(slot);
+			slot += param.slots();
+		}
+		Ok(Self { parameters })
+	}
+}
+
+impl Analysis for ReachingDefinitions {
+	type Fact = BTreeSet<Definition>;
+
+	const DIRECTION: Direction = Direction::Forward;
+
+	fn boundary(&self) -> Self::Fact {
+		self.parameters
+			.iter()
+			.map(|local| Definition {
+				local: *local,
+				pc: None,
+			})
+			.collect()
+	}
+
+	fn bottom(&self) -> Self::Fact {
+		BTreeSet::new()
+	}
+
+	fn transfer(&self, fact: &mut Self::Fact, pc: u32, insn: &Instructions) {
+		let (_, Some((index, wide))) = local_access(insn) else {
+			return;
+		};
+		// Code that passes verification never reads a slot a wide store clobbered before storing to it again, so
+		// killing the definitions of exactly the slots written is enough.
+		fact.retain(|def| def.local != index && !(wide && def.local == index + 1));
+		fact.insert(Definition {
+			local: index,
+			pc: Some(pc),
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use smallvec::smallvec;
+
+	use super::*;
+	use crate::{
+		attribute::{CodeAttribute, CodeAttributeException, IRAttribute},
+		builder::ClassBuilder,
+		flags::MethodAccessFlags,
+	};
+
+	#[test]
+	fn liveness_and_reaching_definitions() {
+		// static int m(int a) { int x = 0; try { while (a > 0) { x += a; a--; } } catch (Throwable t) {} return x; }
+		// 0: iconst_0, 1: istore_1, 2: iload_0, 3: ifle +13, 6: iload_1, 7: iload_0, 8: iadd, 9: istore_1,
+		// 10: iinc 0 -1, 13: goto -11, 16: iload_1, 17: ireturn, 18: astore_2, 19: iload_1, 20: ireturn
+		let code = [
+			&[
+				0x03, 0x3C, 0x1A, 0x9E, 0, 13, 0x1B, 0x1A, 0x60, 0x3C, 0x84, 0, 0xFF, 0xA7, 0xFF, 0xF5,
+			][..],
+			&[0x1B, 0xAC, 0x4D, 0x1B, 0xAC][..],
+		]
+		.concat();
+		let code = CodeAttribute {
+			max_stack: 2,
+			max_locals: 3,
+			code,
+			exception_table: smallvec![CodeAttributeException {
+				start_pc: 6,
+				end_pc: 16,
+				handler_pc: 18,
+				catch_type: 0,
+			}],
+			attributes: Default::default(),
+		};
+		let mut builder = ClassBuilder::new("p/C").unwrap();
+		builder
+			.method(
+				MethodAccessFlags::STATIC,
+				"m",
+				"(I)I",
+				[IRAttribute::Code(code.clone())],
+			)
+			.unwrap();
+		let class = builder.build();
+		let cfg = ControlFlowGraph::new(&class.cp, &code).unwrap();
+		let starts = cfg.blocks().iter().map(|block| block.start_pc).collect::<Vec<_>>();
+		assert_eq!(starts, [0, 2, 6, 16, 18]);
+
+		let live = solve(&cfg, &Liveness);
+		let locals = |locals: &[u16]| locals.iter().copied().collect::<BTreeSet<_>>();
+		assert_eq!(*live.entry(0), locals(&[0]));
+		assert_eq!(*live.entry(1), locals(&[0, 1]));
+		assert_eq!(*live.entry(3), locals(&[1]));
+		assert_eq!(*live.entry(4), locals(&[1]));
+		assert_eq!(live.instruction_facts(&cfg, &Liveness, 0), [locals(&[0]), locals(&[0])]);
+		// The istore_1 at 9 overwrites x, but the handler reads it and everything before the store could throw.
+		assert_eq!(live.instruction_facts(&cfg, &Liveness, 2)[3], locals(&[0, 1]));
+		assert_eq!(*live.exit(2), locals(&[0, 1]));
+
+		let analysis = ReachingDefinitions::new(&class.methods[0]).unwrap();
+		let reaching = solve(&cfg, &analysis);
+		let defs = |defs: &[(u16, Option<u32>)]| {
+			defs.iter()
+				.map(|(local, pc)| Definition { local: *local, pc: *pc })
+				.collect::<BTreeSet<_>>()
+		};
+		let in_loop = defs(&[(0, None), (0, Some(10)), (1, Some(1)), (1, Some(9))]);
+		assert_eq!(*reaching.entry(1), in_loop);
+		assert_eq!(*reaching.exit(2), defs(&[(0, Some(10)), (1, Some(9))]));
+		// The handler can be reached before the loop body stored anything.
+		assert_eq!(*reaching.entry(4), in_loop);
+		let mut in_handler = in_loop;
+		in_handler.insert(Definition { local: 2, pc: Some(18) });
+		assert_eq!(reaching.instruction_facts(&cfg, &analysis, 4)[1], in_handler);
+	}
+}
@@ -0,0 +1,1015 @@
+// Computing StackMapTable frames for code that was built or rewritten, which class files from version 50 on need to
+// pass verification. The types of locals and stack slots are inferred by abstract interpretation over the whole
+// method, iterating to a fixed point; where paths meet, reference types merge to their common superclass, which is
+// what the `ClassHierarchy` is for. `analyze_types` hands out the types inferred before every instruction, for
+// analyses that need more than the value kinds the stack simulator tracks.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.10.1
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+	attribute::{
+		CodeAttribute, IRAttribute, IRAttributeInfo, ResolvedFrame, StackMapFrame, StackMapTableAttribute,
+		VerificationType, VerificationTypeInfo,
+	},
+	class_pool::{CPClassRef, CPUtf8Ref, IRClassfileError, IRCpTag},
+	code::Instructions,
+	descriptor::{FieldType, MethodDescriptor},
+	names, IRClassFile, IRMethodInfo,
+};
+
+const OBJECT: &str = "java/lang/Object";
+
+/// The class hierarchy as far as frame computation needs it. Names are internal names.
+pub trait ClassHierarchy {
+	/// `None` for java/lang/Object and for classes the hierarchy doesn't know.
+	fn super_class(&self, class: &str) -> Option<String>;
+
+	fn is_interface(&self, class: &str) -> bool;
+
+	/// The most specific class both `a` and `b` are subclasses of. Interfaces merge to java/lang/Object, as the
+	/// verifier treats them like it.
+	fn common_super_class(&self, a: &str, b: &str) -> String {
+		if a == b {
+			return a.to_string();
+		}
+		if self.is_interface(a) || self.is_interface(b) {
+			return OBJECT.to_string();
+		}
+
+		let mut supers_of_a = vec![a.to_string()];
+		while let Some(next) = self.super_class(supers_of_a.last().expect("starts with a")) {
+			// A cyclic hierarchy is broken input, don't loop on it.
+			if supers_of_a.contains(&next) {
+				break;
+			}
+			supers_of_a.push(next);
+		}
+
+		let mut class = b.to_string();
+		let mut seen = BTreeSet::new();
+		loop {
+			if supers_of_a.contains(&class) {
+				return class;
+			}
+			if !seen.insert(class.clone()) {
+				break;
+			}
+			match self.super_class(&class) {
+				Some(next) => class = next,
+				None => break,
+			}
+		}
+		OBJECT.to_string()
+	}
+}
+
+/// A hierarchy of the classes at hand, e.g. the ones being written together. Classes it doesn't know are taken to
+/// extend java/lang/Object directly, so merging them with anything else yields java/lang/Object.
+#[derive(Debug, Clone, Default)]
+pub struct KnownClasses {
+	// class to superclass and whether it's an interface
+	classes: HashMap<String, (Option<String>, bool)>,
+}
+
+impl KnownClasses {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn from_classes<'a>(classes: impl IntoIterator<Item = &'a IRClassFile>) -> Self {
+		let mut known = Self::new();
+		for class in classes {
+			known.add(class);
+		}
+		known
+	}
+
+	pub fn add(&mut self, class: &IRClassFile) {
+		self.classes.insert(
+			class.class_name().to_string(),
+			(
+				class.super_name().map(str::to_string),
+				class.access_flags.is_interface(),
+			),
+		);
+	}
+}
+
+impl ClassHierarchy for KnownClasses {
+	fn super_class(&self, class: &str) -> Option<String> {
+		self.classes.get(class)?.0.clone()
+	}
+
+	fn is_interface(&self, class: &str) -> bool {
+		self.classes.get(class).is_some_and(|(_, interface)| *interface)
+	}
+}
+
+fn object(name: &str) -> VerificationType {
+	VerificationType::Object(name.to_string())
+}
+
+fn parse_type(descriptor: &str) -> Result<VerificationType, IRClassfileError> {
+	Ok(VerificationType::of(&FieldType::parse(descriptor)?))
+}
+
+// Locals and stack are kept by slot, longs and doubles taking two with the second one `Top`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Frame {
+	locals: Vec<VerificationType>,
+	stack: Vec<VerificationType>,
+}
+
+/// Everything about the method the interpretation needs besides the instructions.
+struct Method<'a> {
+	class_name: &'a str,
+	hierarchy: &'a dyn ClassHierarchy,
+	// the class each `new` instantiates, by pc
+	news: HashMap<u32, String>,
+}
+
+/// The types of the locals and stack before each instruction of a method, what its frames are made from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeAnalysis {
+	// in pc order, None where the instruction can't be reached
+	frames: Vec<(u32, Option<ResolvedFrame>)>,
+}
+
+impl TypeAnalysis {
+	/// The frame before the instruction at `pc`, `None` if there's none there or it can't be reached. Locals and
+	/// stack are listed like in a StackMapTable frame: a long or double is one entry, trailing unset locals are left
+	/// out.
+	pub fn frame_at(&self, pc: u32) -> Option<&ResolvedFrame> {
+		let i = self.frames.binary_search_by_key(&pc, |(pc, _)| *pc).ok()?;
+		self.frames[i].1.as_ref()
+	}
+
+	/// The frames before every reachable instruction, in pc order.
+	pub fn frames(&self) -> impl Iterator<Item = &ResolvedFrame> {
+		self.frames.iter().filter_map(|(_, frame)| frame.as_ref())
+	}
+}
+
+/// Infers the types before every instruction of `method`, `None` if it has no code. Unreachable code is fine here, it
+/// just has no frame.
+pub fn analyze_types(
+	cp: &[IRCpTag],
+	class_name: &str,
+	method: &IRMethodInfo,
+	hierarchy: &dyn ClassHierarchy,
+) -> Result<Option<TypeAnalysis>, IRClassfileError> {
+	let Some(code) = method.code() else {
+		return Ok(None);
+	};
+	let instructions = Instructions::read_all(cp, &code.code)?;
+	let frames = infer(cp, class_name, method, code, &instructions, hierarchy)?;
+	let frames = instructions
+		.iter()
+		.zip(frames)
+		.map(|((pc, _), frame)| {
+			let frame = frame.map(|frame| ResolvedFrame {
+				pc: *pc,
+				locals: compress(&frame.locals),
+				stack: compress(&frame.stack),
+			});
+			(*pc, frame)
+		})
+		.collect();
+	Ok(Some(TypeAnalysis { frames }))
+}
+
+/// Computes the frames for `method`, `None` if it has no code. Class entries for the reference types in the frames
+/// are added to `cp`.
+///
+/// Subroutines (`jsr`/`ret`) aren't supported, they aren't allowed in class files that need frames anyway. Neither is
+/// unreachable code where a frame is required, e.g. after a `goto`, since nothing says what types it runs with.
+pub fn compute_frames(
+	cp: &mut Vec<IRCpTag>,
+	class_name: &str,
+	method: &IRMethodInfo,
+	hierarchy: &dyn ClassHierarchy,
+) -> Result<Option<StackMapTableAttribute>, IRClassfileError> {
+	let Some(code) = method.code() else {
+		return Ok(None);
+	};
+	let instructions = Instructions::read_all(cp, &code.code)?;
+	let frames = infer(cp, class_name, method, code, &instructions, hierarchy)?;
+
+	let mut required = BTreeSet::new();
+	for (i, (pc, insn)) in instructions.iter().enumerate() {
+		required.extend(insn.branch_targets(*pc));
+		if !insn.falls_through() && i + 1 < instructions.len() {
+			required.insert(instructions[i + 1].0);
+		}
+	}
+	required.extend(code.exception_table.iter().map(|entry| entry.handler_pc as u32));
+
+	let mut entries = Vec::with_capacity(required.len());
+	let mut previous = (None, compress(&entry_frame(class_name, method)?.locals));
+	for pc in required {
+		let frame = instructions
+			.binary_search_by_key(&pc, |(pc, _)| *pc)
+			.ok()
+			.and_then(|i| frames[i].as_ref())
+			.ok_or_else(|| frame_error(pc, "unreachable code needs a frame"))?;
+		let locals = compress(&frame.locals);
+		let stack = compress(&frame.stack);
+		let offset_delta = match previous.0 {
+			None => pc,
+			Some(previous) => pc - previous - 1,
+		} as u16;
+		entries.push(encode_frame(cp, offset_delta, &previous.1, &locals, &stack)?);
+		previous = (Some(pc), locals);
+	}
+
+	Ok(Some(StackMapTableAttribute { entries }))
+}
+
+// The frame before each instruction, iterating to a fixed point from the entry frame.
+fn infer(
+	cp: &[IRCpTag],
+	class_name: &str,
+	method: &IRMethodInfo,
+	code: &CodeAttribute,
+	instructions: &[(u32, Instructions)],
+	hierarchy: &dyn ClassHierarchy,
+) -> Result<Vec<Option<Frame>>, IRClassfileError> {
+	let index_of = instructions
+		.iter()
+		.enumerate()
+		.map(|(i, (pc, _))| (*pc, i))
+		.collect::<HashMap<_, _>>();
+	let news = instructions
+		.iter()
+		.filter_map(|(pc, insn)| match insn {
+			Instructions::NEW(class) => Some((*pc, class.data.data.to_string())),
+			_ => None,
+		})
+		.collect();
+	let ctx = Method {
+		class_name,
+		hierarchy,
+		news,
+	};
+
+	let mut frames = vec![None; instructions.len()];
+	let mut worklist = BTreeSet::new();
+	if !instructions.is_empty() {
+		frames[0] = Some(entry_frame(class_name, method)?);
+		worklist.insert(0);
+	}
+
+	while let Some(i) = worklist.pop_first() {
+		let (pc, insn) = &instructions[i];
+		let before = frames[i].clone().expect("only instructions with a frame are queued");
+		let mut after = before.clone();
+		execute(&ctx, &mut after, *pc, insn)?;
+
+		let mut successors = Vec::new();
+		if insn.falls_through() {
+			if i + 1 >= instructions.len() {
+				return Err(frame_error(*pc, "execution falls off the end of the code"));
+			}
+			successors.push((i + 1, after.clone()));
+		}
+		for target in insn.branch_targets(*pc) {
+			let target = *index_of
+				.get(&target)
+				.ok_or_else(|| frame_error(*pc, "branch target isn't an instruction"))?;
+			successors.push((target, after.clone()));
+		}
+		// A handler can be entered before or after the instruction ran, with only the exception on the stack.
+		for handler in &code.exception_table {
+			if (handler.start_pc as u32..handler.end_pc as u32).contains(pc) {
+				let target = *index_of
+					.get(&(handler.handler_pc as u32))
+					.ok_or_else(|| frame_error(*pc, "exception handler isn't an instruction"))?;
+				let exception = match handler.catch_type {
+					0 => object("java/lang/Throwable"),
+					index => VerificationType::Object(CPClassRef::from_cp(cp, index)?.data.data.to_string()),
+				};
+				for locals in [&before.locals, &after.locals] {
+					let frame = Frame {
+						locals: locals.clone(),
+						stack: vec![exception.clone()],
+					};
+					successors.push((target, frame));
+				}
+			}
+		}
+
+		for (target, frame) in successors {
+			let merged = match &frames[target] {
+				None => frame,
+				Some(existing) => merge_frames(&ctx, instructions[target].0, existing, &frame)?,
+			};
+			if frames[target].as_ref() != Some(&merged) {
+				frames[target] = Some(merged);
+				worklist.insert(target);
+			}
+		}
+	}
+
+	Ok(frames)
+}
+
+/// Replaces the StackMapTable of every method with code by freshly computed frames. Classes older than version 50
+/// are left alone, they're verified without frames.
+pub fn recompute_frames(class: &mut IRClassFile, hierarchy: &dyn ClassHierarchy) -> Result<(), IRClassfileError> {
+	if !class.version.supports_stack_map_table() {
+		return Ok(());
+	}
+
+	let class_name = class.class_name().to_string();
+	for method in &mut class.methods {
+		recompute_method_frames(&mut class.cp, &class_name, method, hierarchy)?;
+	}
+	Ok(())
+}
+
+/// Replaces the StackMapTable of one method, for passes that only changed some. Unlike `recompute_frames` this
+/// doesn't look at the class version.
+pub fn recompute_method_frames(
+	cp: &mut Vec<IRCpTag>,
+	class_name: &str,
+	method: &mut IRMethodInfo,
+	hierarchy: &dyn ClassHierarchy,
+) -> Result<(), IRClassfileError> {
+	let Some(table) = compute_frames(cp, class_name, method, hierarchy)? else {
+		return Ok(());
+	};
+	let name = CPUtf8Ref::find_or_add(cp, "StackMapTable")?;
+	let code = method.attributes.code_mut().expect("compute_frames found code");
+	code.attributes
+		.retain(|attr| !matches!(attr.attr, IRAttribute::StackMapTable(_)));
+	if !table.entries.is_empty() {
+		code.attributes.push(IRAttributeInfo {
+			name,
+			length: 0,
+			attr: IRAttribute::StackMapTable(table),
+		});
+	}
+	Ok(())
+}
+
+fn frame_error(pc: u32, reason: &'static str) -> IRClassfileError {
+	IRClassfileError::FrameComputation { pc, reason }
+}
+
+fn entry_frame(class_name: &str, method: &IRMethodInfo) -> Result<Frame, IRClassfileError> {
+	Ok(Frame {
+		locals: ResolvedFrame::entry(class_name, method)?.local_slots(),
+		stack: Vec::new(),
+	})
+}
+
+fn push_slots(slots: &mut Vec<VerificationType>, ty: VerificationType) {
+	let wide = ty.is_wide();
+	slots.push(ty);
+	if wide {
+		slots.push(VerificationType::Top);
+	}
+}
+
+impl Frame {
+	fn push(&mut self, ty: VerificationType) {
+		push_slots(&mut self.stack, ty);
+	}
+
+	fn pop_slot(&mut self, pc: u32) -> Result<VerificationType, IRClassfileError> {
+		self.stack
+			.pop()
+			.ok_or_else(|| frame_error(pc, "operand stack underflow"))
+	}
+
+	/// Pops a whole value, both slots of a long or double.
+	fn pop(&mut self, pc: u32) -> Result<VerificationType, IRClassfileError> {
+		match self.pop_slot(pc)? {
+			VerificationType::Top => match self.pop_slot(pc)? {
+				wide @ (VerificationType::Long | VerificationType::Double) => Ok(wide),
+				_ => Err(frame_error(pc, "top of stack isn't a value")),
+			},
+			ty => Ok(ty),
+		}
+	}
+
+	fn pop_n(&mut self, pc: u32, n: usize) -> Result<(), IRClassfileError> {
+		for _ in 0..n {
+			self.pop(pc)?;
+		}
+		Ok(())
+	}
+
+	fn load(&mut self, pc: u32, index: u16) -> Result<(), IRClassfileError> {
+		let ty = self
+			.locals
+			.get(index as usize)
+			.cloned()
+			.ok_or_else(|| frame_error(pc, "load from an unset local"))?;
+		self.push(ty);
+		Ok(())
+	}
+
+	fn store(&mut self, pc: u32, index: u16) -> Result<(), IRClassfileError> {
+		let ty = self.pop(pc)?;
+		let index = index as usize;
+		let end = index + if ty.is_wide() { 2 } else { 1 };
+		if self.locals.len() < end {
+			self.locals.resize(end, VerificationType::Top);
+		}
+		// Overwriting the second half of a long or double invalidates the first.
+		if index > 0 && self.locals[index - 1].is_wide() {
+			self.locals[index - 1] = VerificationType::Top;
+		}
+		if ty.is_wide() {
+			self.locals[index + 1] = VerificationType::Top;
+		}
+		self.locals[index] = ty;
+		Ok(())
+	}
+
+	/// Rearranges the top `n` stack slots: `order` lists the slots to push in their place, indexes counted from the
+	/// deepest one.
+	fn shuffle(&mut self, pc: u32, n: usize, order: &[usize]) -> Result<(), IRClassfileError> {
+		if self.stack.len() < n {
+			return Err(frame_error(pc, "operand stack underflow"));
+		}
+		let top = self.stack.split_off(self.stack.len() - n);
+		self.stack.extend(order.iter().map(|i| top[*i].clone()));
+		Ok(())
+	}
+
+	/// Once a constructor ran, every copy of the uninitialized value becomes the initialized type.
+	fn initialize(&mut self, uninitialized: &VerificationType, class: &str) {
+		for slot in self.locals.iter_mut().chain(self.stack.iter_mut()) {
+			if slot == uninitialized {
+				*slot = object(class);
+			}
+		}
+	}
+}
+
+fn execute(ctx: &Method, frame: &mut Frame, pc: u32, insn: &Instructions) -> Result<(), IRClassfileError> {
+	use Instructions as I;
+
+	match insn {
+		I::NOP | I::IINC { .. } | I::GOTO(_) | I::GOTO_W(_) | I::RETURN => {}
+		I::ACONST_NULL => frame.push(VerificationType::Null),
+		I::ICONST_M1
+		| I::ICONST_0
+		| I::ICONST_1
+		| I::ICONST_2
+		| I::ICONST_3
+		| I::ICONST_4
+		| I::ICONST_5
+		| I::BIPUSH(_)
+		| I::SIPUSH(_) => frame.push(VerificationType::Integer),
+		I::LCONST_0 | I::LCONST_1 => frame.push(VerificationType::Long),
+		I::FCONST_0 | I::FCONST_1 | I::FCONST_2 => frame.push(VerificationType::Float),
+		I::DCONST_0 | I::DCONST_1 => frame.push(VerificationType::Double),
+		I::LDC(tag) => frame.push(match tag {
+			IRCpTag::Integer(_) => VerificationType::Integer,
+			IRCpTag::Float(_) => VerificationType::Float,
+			IRCpTag::Long(_) => VerificationType::Long,
+			IRCpTag::Double(_) => VerificationType::Double,
+			IRCpTag::String(_) => object("java/lang/String"),
+			IRCpTag::Class(_) => object("java/lang/Class"),
+			IRCpTag::MethodType(_) => object("java/lang/invoke/MethodType"),
+			IRCpTag::MethodHandle { .. } => object("java/lang/invoke/MethodHandle"),
+			_ => return Err(frame_error(pc, "ldc of an unloadable constant")),
+		}),
+
+		I::ILOAD(index) | I::LLOAD(index) | I::FLOAD(index) | I::DLOAD(index) | I::ALOAD(index) => {
+			frame.load(pc, *index)?
+		}
+		I::ISTORE(index) | I::LSTORE(index) | I::FSTORE(index) | I::DSTORE(index) | I::ASTORE(index) => {
+			frame.store(pc, *index)?
+		}
+
+		I::IALOAD | I::BALOAD | I::CALOAD | I::SALOAD => {
+			frame.pop_n(pc, 2)?;
+			frame.push(VerificationType::Integer);
+		}
+		I::LALOAD => {
+			frame.pop_n(pc, 2)?;
+			frame.push(VerificationType::Long);
+		}
+		I::FALOAD => {
+			frame.pop_n(pc, 2)?;
+			frame.push(VerificationType::Float);
+		}
+		I::DALOAD => {
+			frame.pop_n(pc, 2)?;
+			frame.push(VerificationType::Double);
+		}
+		I::AALOAD => {
+			frame.pop(pc)?;
+			let element = match frame.pop(pc)? {
+				VerificationType::Object(array) if names::is_array(&array) => parse_type(&array[1..])?,
+				// Loading from null throws, the type doesn't matter.
+				_ => VerificationType::Null,
+			};
+			frame.push(element);
+		}
+		I::IASTORE | I::LASTORE | I::FASTORE | I::DASTORE | I::AASTORE | I::BASTORE | I::CASTORE | I::SASTORE => {
+			frame.pop_n(pc, 3)?
+		}
+
+		I::POP => frame.shuffle(pc, 1, &[])?,
+		I::POP2 => frame.shuffle(pc, 2, &[])?,
+		I::DUP => frame.shuffle(pc, 1, &[0, 0])?,
+		I::DUP_X1 => frame.shuffle(pc, 2, &[1, 0, 1])?,
+		I::DUP_X2 => frame.shuffle(pc, 3, &[2, 0, 1, 2])?,
+		I::DUP2 => frame.shuffle(pc, 2, &[0, 1, 0, 1])?,
+		I::DUP2_X1 => frame.shuffle(pc, 3, &[1, 2, 0, 1, 2])?,
+		I::DUP2_X2 => frame.shuffle(pc, 4, &[2, 3, 0, 1, 2, 3])?,
+		I::SWAP => frame.shuffle(pc, 2, &[1, 0])?,
+
+		I::IADD
+		| I::ISUB
+		| I::IMUL
+		| I::IDIV
+		| I::IREM
+		| I::ISHL
+		| I::ISHR
+		| I::IUSHR
+		| I::IAND
+		| I::IOR
+		| I::IXOR
+		| I::LCMP
+		| I::FCMPL
+		| I::FCMPG
+		| I::DCMPL
+		| I::DCMPG => {
+			frame.pop_n(pc, 2)?;
+			frame.push(VerificationType::Integer);
+		}
+		I::LADD | I::LSUB | I::LMUL | I::LDIV | I::LREM | I::LSHL | I::LSHR | I::LUSHR | I::LAND | I::LOR | I::LXOR => {
+			frame.pop_n(pc, 2)?;
+			frame.push(VerificationType::Long);
+		}
+		I::FADD | I::FSUB | I::FMUL | I::FDIV | I::FREM => {
+			frame.pop_n(pc, 2)?;
+			frame.push(VerificationType::Float);
+		}
+		I::DADD | I::DSUB | I::DMUL | I::DDIV | I::DREM => {
+			frame.pop_n(pc, 2)?;
+			frame.push(VerificationType::Double);
+		}
+		I::INEG | I::L2I | I::F2I | I::D2I | I::I2B | I::I2C | I::I2S | I::ARRAYLENGTH | I::INSTANCEOF(_) => {
+			frame.pop(pc)?;
+			frame.push(VerificationType::Integer);
+		}
+		I::LNEG | I::I2L | I::F2L | I::D2L => {
+			frame.pop(pc)?;
+			frame.push(VerificationType::Long);
+		}
+		I::FNEG | I::I2F | I::L2F | I::D2F => {
+			frame.pop(pc)?;
+			frame.push(VerificationType::Float);
+		}
+		I::DNEG | I::I2D | I::L2D | I::F2D => {
+			frame.pop(pc)?;
+			frame.push(VerificationType::Double);
+		}
+
+		I::IFEQ(_)
+		| I::IFNE(_)
+		| I::IFLT(_)
+		| I::IFGE(_)
+		| I::IFGT(_)
+		| I::IFLE(_)
+		| I::IFNULL(_)
+		| I::IFNONNULL(_)
+		| I::TABLESWITCH { .. }
+		| I::LOOKUPSWITCH { .. }
+		| I::IRETURN
+		| I::LRETURN
+		| I::FRETURN
+		| I::DRETURN
+		| I::ARETURN
+		| I::ATHROW
+		| I::MONITORENTER
+		| I::MONITOREXIT
+		| I::PUTSTATIC(_) => {
+			frame.pop(pc)?;
+		}
+		I::IF_ICMPEQ(_)
+		| I::IF_ICMPNE(_)
+		| I::IF_ICMPLT(_)
+		| I::IF_ICMPGE(_)
+		| I::IF_ICMPGT(_)
+		| I::IF_ICMPLE(_)
+		| I::IF_ACMPEQ(_)
+		| I::IF_ACMPNE(_)
+		| I::PUTFIELD(_) => frame.pop_n(pc, 2)?,
+		I::JSR(_) | I::JSR_W(_) | I::RET(_) => return Err(frame_error(pc, "subroutines aren't supported")),
+
+		I::GETSTATIC(field) => frame.push(parse_type(&field.name_and_ty.ty.data)?),
+		I::GETFIELD(field) => {
+			frame.pop(pc)?;
+			frame.push(parse_type(&field.name_and_ty.ty.data)?);
+		}
+		I::INVOKEVIRTUAL(method) | I::INVOKESTATIC(method) => invoke(
+			frame,
+			pc,
+			&method.name_and_ty.ty.data,
+			matches!(insn, I::INVOKEVIRTUAL(_)),
+		)?,
+		I::INVOKEINTERFACE { method, .. } => invoke(frame, pc, &method.name_and_ty.ty.data, true)?,
+		I::INVOKEDYNAMIC(call_site) => invoke(frame, pc, &call_site.name_and_ty.ty.data, false)?,
+		I::INVOKESPECIAL(method) => {
+			let descriptor = MethodDescriptor::parse(&method.name_and_ty.ty.data)?;
+			frame.pop_n(pc, descriptor.params.len())?;
+			let receiver = frame.pop(pc)?;
+			if method.name_and_ty.name.data.as_str() == "<init>" {
+				match &receiver {
+					VerificationType::UninitializedThis => frame.initialize(&receiver, ctx.class_name),
+					VerificationType::Uninitialized(new) => {
+						let class = ctx
+							.news
+							.get(new)
+							.ok_or_else(|| frame_error(pc, "uninitialized value without a new"))?;
+						frame.initialize(&receiver, class);
+					}
+					_ => return Err(frame_error(pc, "constructor called on an initialized value")),
+				}
+			}
+			if let Some(ret) = &descriptor.ret {
+				frame.push(VerificationType::of(ret));
+			}
+		}
+
+		I::NEW(_) => frame.push(VerificationType::Uninitialized(pc)),
+		I::NEWARRAY(ty) => {
+			frame.pop(pc)?;
+			let element = match ty {
+				4 => 'Z',
+				5 => 'C',
+				6 => 'F',
+				7 => 'D',
+				8 => 'B',
+				9 => 'S',
+				10 => 'I',
+				11 => 'J',
+				_ => return Err(frame_error(pc, "invalid newarray type")),
+			};
+			frame.push(VerificationType::Object(format!("[{element}")));
+		}
+		I::ANEWARRAY(class) => {
+			frame.pop(pc)?;
+			let element = class.data.data.as_str();
+			frame.push(VerificationType::Object(match names::is_array(element) {
+				true => format!("[{element}"),
+				false => format!("[L{element};"),
+			}));
+		}
+		I::CHECKCAST(class) => {
+			frame.pop(pc)?;
+			frame.push(object(&class.data.data));
+		}
+		I::MULTIANEWARRAY { class, dimensions } => {
+			frame.pop_n(pc, *dimensions as usize)?;
+			frame.push(object(&class.data.data));
+		}
+	}
+	Ok(())
+}
+
+fn invoke(frame: &mut Frame, pc: u32, descriptor: &str, has_receiver: bool) -> Result<(), IRClassfileError> {
+	let descriptor = MethodDescriptor::parse(descriptor)?;
+	frame.pop_n(pc, descriptor.params.len() + has_receiver as usize)?;
+	if let Some(ret) = &descriptor.ret {
+		frame.push(VerificationType::of(ret));
+	}
+	Ok(())
+}
+
+fn merge_frames(ctx: &Method, pc: u32, a: &Frame, b: &Frame) -> Result<Frame, IRClassfileError> {
+	if a.stack.len() != b.stack.len() {
+		return Err(frame_error(pc, "stack heights differ where paths meet"));
+	}
+
+	let len = a.locals.len().max(b.locals.len());
+	let local = |locals: &[VerificationType], i: usize| locals.get(i).cloned().unwrap_or(VerificationType::Top);
+	let locals = (0..len)
+		.map(|i| merge_types(ctx, local(&a.locals, i), local(&b.locals, i)))
+		.collect();
+	let stack = a
+		.stack
+		.iter()
+		.zip(&b.stack)
+		.map(|(a, b)| match merge_types(ctx, a.clone(), b.clone()) {
+			VerificationType::Top if *a != VerificationType::Top => {
+				Err(frame_error(pc, "stack types differ where paths meet"))
+			}
+			ty => Ok(ty),
+		})
+		.collect::<Result<_, _>>()?;
+	Ok(Frame { locals, stack })
+}
+
+fn merge_types(ctx: &Method, a: VerificationType, b: VerificationType) -> VerificationType {
+	match (a, b) {
+		(a, b) if a == b => a,
+		(VerificationType::Null, reference @ VerificationType::Object(_))
+		| (reference @ VerificationType::Object(_), VerificationType::Null) => reference,
+		(VerificationType::Object(a), VerificationType::Object(b)) => {
+			VerificationType::Object(merge_references(ctx, &a, &b))
+		}
+		_ => VerificationType::Top,
+	}
+}
+
+fn merge_references(ctx: &Method, a: &str, b: &str) -> String {
+	match (names::is_array(a), names::is_array(b)) {
+		(false, false) => ctx.hierarchy.common_super_class(a, b),
+		(true, true) => {
+			// Arrays of references merge element-wise, anything else has only Object in common.
+			let (a, b) = (&a[1..], &b[1..]);
+			let element = |descriptor: &str| match descriptor.as_bytes().first() {
+				Some(b'[') => Some(descriptor.to_string()),
+				Some(b'L') => names::descriptor_to_internal(descriptor).map(str::to_string),
+				_ => None,
+			};
+			match (element(a), element(b)) {
+				(Some(a), Some(b)) => {
+					let merged = merge_references(ctx, &a, &b);
+					match names::is_array(&merged) {
+						true => format!("[{merged}"),
+						false => format!("[L{merged};"),
+					}
+				}
+				_ => OBJECT.to_string(),
+			}
+		}
+		_ => OBJECT.to_string(),
+	}
+}
+
+/// Slots to frame entries: a long or double is one entry, and trailing unused slots are left out.
+fn compress(slots: &[VerificationType]) -> Vec<VerificationType> {
+	let mut out = Vec::with_capacity(slots.len());
+	let mut i = 0;
+	while i < slots.len() {
+		out.push(slots[i].clone());
+		i += if slots[i].is_wide() { 2 } else { 1 };
+	}
+	while out.last() == Some(&VerificationType::Top) {
+		out.pop();
+	}
+	out
+}
+
+fn verification_type(cp: &mut Vec<IRCpTag>, ty: &VerificationType) -> Result<VerificationTypeInfo, IRClassfileError> {
+	Ok(match ty {
+		VerificationType::Top => VerificationTypeInfo::TopVariableInfo,
+		VerificationType::Integer => VerificationTypeInfo::IntegerVariableInfo,
+		VerificationType::Float => VerificationTypeInfo::FloatVariableInfo,
+		VerificationType::Long => VerificationTypeInfo::LongVariableInfo,
+		VerificationType::Double => VerificationTypeInfo::DoubleVariableInfo,
+		VerificationType::Null => VerificationTypeInfo::NullVariableInfo,
+		VerificationType::UninitializedThis => VerificationTypeInfo::UninitializedThisVariableInfo,
+		VerificationType::Uninitialized(pc) => VerificationTypeInfo::UninitializedVariableInfo { offset: *pc as u16 },
+		VerificationType::Object(name) => VerificationTypeInfo::ObjectVariableInfo {
+			cpool_idx: CPClassRef::find_or_add(cp, name)?.index,
+		},
+	})
+}
+
+fn verification_types(
+	cp: &mut Vec<IRCpTag>,
+	types: &[VerificationType],
+) -> Result<Vec<VerificationTypeInfo>, IRClassfileError> {
+	types.iter().map(|ty| verification_type(cp, ty)).collect()
+}
+
+/// The most compact frame type for `locals` and `stack` given the previous frame's locals.
+fn encode_frame(
+	cp: &mut Vec<IRCpTag>,
+	offset_delta: u16,
+	previous: &[VerificationType],
+	locals: &[VerificationType],
+	stack: &[VerificationType],
+) -> Result<StackMapFrame, IRClassfileError> {
+	let short = offset_delta < 64;
+	Ok(match stack {
+		[] if locals == previous => match short {
+			true => StackMapFrame::SameFrame {
+				frame_type: offset_delta as u8,
+				offset_delta,
+			},
+			false => StackMapFrame::SameFrameExtended {
+				frame_type: 251,
+				offset_delta,
+			},
+		},
+		[item] if locals == previous => {
+			let stack = verification_type(cp, item)?;
+			match short {
+				true => StackMapFrame::SameLocals1StackItemFrame {
+					frame_type: 64 + offset_delta as u8,
+					offset_delta,
+					stack,
+				},
+				false => StackMapFrame::SameLocals1StackItemFrameExtended {
+					frame_type: 247,
+					offset_delta,
+					stack,
+				},
+			}
+		}
+		[] if locals.len() > previous.len() && locals.len() - previous.len() <= 3 && locals.starts_with(previous) => {
+			let added = &locals[previous.len()..];
+			StackMapFrame::AppendFrame {
+				frame_type: 251 + added.len() as u8,
+				offset_delta,
+				locals: verification_types(cp, added)?,
+			}
+		}
+		[] if previous.len() > locals.len() && previous.len() - locals.len() <= 3 && previous.starts_with(locals) => {
+			StackMapFrame::ChopFrame {
+				frame_type: 251 - (previous.len() - locals.len()) as u8,
+				offset_delta,
+			}
+		}
+		_ => StackMapFrame::FullFrame {
+			frame_type: 255,
+			offset_delta,
+			locals: verification_types(cp, locals)?,
+			stack: verification_types(cp, stack)?,
+		},
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		analysis::stack::{simulate, StackState},
+		builder::{ClassBuilder, CodeBuilder},
+		class_pool::CPMethodRef,
+		code::Opcodes,
+		flags::MethodAccessFlags,
+	};
+
+	#[test]
+	fn merged_branches_and_handler() {
+		let mut classes = Vec::new();
+		for (name, super_class) in [("p/Base", "java/lang/Object"), ("p/A", "p/Base"), ("p/B", "p/Base")] {
+			let mut builder = ClassBuilder::new(name).unwrap();
+			builder.super_class(Some(super_class)).unwrap();
+			classes.push(builder.build());
+		}
+		let hierarchy = KnownClasses::from_classes(&classes);
+		assert_eq!(hierarchy.common_super_class("p/A", "p/B"), "p/Base");
+		assert_eq!(hierarchy.common_super_class("p/A", "p/Unknown"), "java/lang/Object");
+
+		// static Object f(boolean) { Base x = flag ? new B() : new A(); try { return x; } catch (Exception e) { return null; } }
+		let mut builder = ClassBuilder::new("p/F").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		let (other, join, end, handler) = (code.new_label(), code.new_label(), code.new_label(), code.new_label());
+		code.insn(Instructions::ILOAD(0)).branch(Opcodes::IFEQ, other).unwrap();
+		for (label, class) in [(None, "p/B"), (Some(other), "p/A")] {
+			if let Some(label) = label {
+				code.bind(label).unwrap();
+			}
+			let class_ref = CPClassRef::find_or_add(code.cp(), class).unwrap();
+			let init = CPMethodRef::find_or_add(code.cp(), class, "<init>", "()V").unwrap();
+			code.insn(Instructions::NEW(class_ref))
+				.insn(Instructions::DUP)
+				.insn(Instructions::INVOKESPECIAL(init))
+				.insn(Instructions::ASTORE(1));
+			if label.is_none() {
+				code.branch(Opcodes::GOTO, join).unwrap();
+			}
+		}
+		code.bind(join)
+			.unwrap()
+			.insn(Instructions::ALOAD(1))
+			.insn(Instructions::ARETURN)
+			.bind(end)
+			.unwrap()
+			.bind(handler)
+			.unwrap()
+			.insn(Instructions::POP)
+			.insn(Instructions::ACONST_NULL)
+			.insn(Instructions::ARETURN)
+			.try_catch(join, end, handler, Some("java/lang/Exception"))
+			.unwrap();
+		code.max_stack(2).max_locals(2);
+		let code = code.build().unwrap();
+		builder
+			.method(
+				MethodAccessFlags::STATIC,
+				"f",
+				"(Z)Ljava/lang/Object;",
+				[IRAttribute::Code(code)],
+			)
+			.unwrap();
+		let mut class = builder.build();
+		recompute_frames(&mut class, &hierarchy).unwrap();
+
+		let class = IRClassFile::read(&class.to_bytes().unwrap()).unwrap();
+		let code = class.methods[0].code().unwrap();
+		let table = code.attributes.stack_map_table().unwrap();
+		assert_eq!(table.frame_pcs(), [15, 23, 25]);
+		let class_name = |info: &VerificationTypeInfo| match info {
+			VerificationTypeInfo::ObjectVariableInfo { cpool_idx } => CPClassRef::from_cp(&class.cp, *cpool_idx)
+				.unwrap()
+				.data
+				.data
+				.to_string(),
+			_ => panic!("{info:?} isn't a class"),
+		};
+		assert!(matches!(
+			table.entries[0],
+			StackMapFrame::SameFrame { frame_type: 15, .. }
+		));
+		match &table.entries[1] {
+			StackMapFrame::AppendFrame {
+				frame_type: 252,
+				locals,
+				..
+			} => assert_eq!(class_name(&locals[0]), "p/Base"),
+			frame => panic!("{frame:?}"),
+		}
+		match &table.entries[2] {
+			StackMapFrame::SameLocals1StackItemFrame {
+				frame_type: 65, stack, ..
+			} => {
+				assert_eq!(class_name(stack), "java/lang/Exception")
+			}
+			frame => panic!("{frame:?}"),
+		}
+	}
+
+	#[test]
+	fn types_before_each_instruction() {
+		let mut classes = Vec::new();
+		for (name, super_class) in [("p/Base", "java/lang/Object"), ("p/A", "p/Base"), ("p/B", "p/Base")] {
+			let mut builder = ClassBuilder::new(name).unwrap();
+			builder.super_class(Some(super_class)).unwrap();
+			classes.push(builder.build());
+		}
+		let hierarchy = KnownClasses::from_classes(&classes);
+
+		// static Object f(boolean) { Base x = flag ? new B() : new A(); return x; } and an unreachable `return null`
+		let mut builder = ClassBuilder::new("p/F").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		let (other, join) = (code.new_label(), code.new_label());
+		code.insn(Instructions::ILOAD(0)).branch(Opcodes::IFEQ, other).unwrap();
+		for (label, class) in [(None, "p/B"), (Some(other), "p/A")] {
+			if let Some(label) = label {
+				code.bind(label).unwrap();
+			}
+			let class_ref = CPClassRef::find_or_add(code.cp(), class).unwrap();
+			let init = CPMethodRef::find_or_add(code.cp(), class, "<init>", "()V").unwrap();
+			code.insn(Instructions::NEW(class_ref))
+				.insn(Instructions::DUP)
+				.insn(Instructions::INVOKESPECIAL(init))
+				.insn(Instructions::ASTORE(1));
+			if label.is_none() {
+				code.branch(Opcodes::GOTO, join).unwrap();
+			}
+		}
+		code.bind(join)
+			.unwrap()
+			.insn(Instructions::ALOAD(1))
+			.insn(Instructions::ARETURN)
+			.insn(Instructions::ACONST_NULL)
+			.insn(Instructions::ARETURN);
+		code.max_stack(2).max_locals(2);
+		let code = code.build().unwrap();
+		builder
+			.method(
+				MethodAccessFlags::STATIC,
+				"f",
+				"(Z)Ljava/lang/Object;",
+				[IRAttribute::Code(code)],
+			)
+			.unwrap();
+		let class = builder.build();
+		let method = &class.methods[0];
+
+		let analysis = analyze_types(&class.cp, "p/F", method, &hierarchy).unwrap().unwrap();
+		let at = |pc| analysis.frame_at(pc).unwrap();
+		assert_eq!(at(0).locals, [VerificationType::Integer]);
+		assert_eq!(
+			at(8).stack,
+			[VerificationType::Uninitialized(4), VerificationType::Uninitialized(4)]
+		);
+		assert_eq!(at(11).stack, [object("p/B")]);
+		assert_eq!(at(23).locals, [VerificationType::Integer, object("p/Base")]);
+		assert_eq!(analysis.frame_at(25), None);
+		assert_eq!(analysis.frames().count(), 13);
+
+		// The kinds agree with what the stack simulator tracks.
+		let simulation = simulate(&class.cp, "p/F", method).unwrap().unwrap();
+		for frame in analysis.frames() {
+			let state = simulation.state_at(frame.pc).unwrap();
+			assert_eq!(StackState::from(frame).stack, state.stack, "at {}", frame.pc);
+		}
+	}
+}
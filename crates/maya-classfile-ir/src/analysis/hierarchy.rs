@@ -0,0 +1,257 @@
+// The class hierarchy as a `ClassResolver` finds it: superclasses, the interfaces a class implements however
+// indirectly, subtyping between class and array types, and common superclasses for frame computation. Every class is
+// looked up once and what's derived from it is kept, so asking again about a large hierarchy costs map lookups. Like
+// `KnownClasses`, classes that can't be found are taken to extend java/lang/Object directly and implement nothing.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5.checkcast
+
+use std::{
+	cell::RefCell,
+	collections::{BTreeSet, HashMap},
+	rc::Rc,
+};
+
+use crate::{
+	analysis::frames::ClassHierarchy,
+	names,
+	resolver::ClassResolver,
+	symbols::{ClassId, SymbolTable},
+};
+
+const OBJECT: &str = "java/lang/Object";
+const CLONEABLE: &str = "java/lang/Cloneable";
+const SERIALIZABLE: &str = "java/io/Serializable";
+
+#[derive(Debug)]
+pub struct Hierarchy<R> {
+	resolver: R,
+	// every class asked about or found on the way, the caches below are keyed on their ids
+	symbols: RefCell<SymbolTable>,
+	// `None` for classes the resolver doesn't have
+	headers: RefCell<HashMap<ClassId, Option<Rc<Header>>>>,
+	supertypes: RefCell<HashMap<ClassId, Rc<Supertypes>>>,
+	// by the pair of classes in id order
+	common: RefCell<HashMap<(ClassId, ClassId), ClassId>>,
+}
+
+#[derive(Debug)]
+struct Header {
+	super_class: Option<ClassId>,
+	interfaces: Vec<ClassId>,
+	is_interface: bool,
+}
+
+#[derive(Debug, Default)]
+struct Supertypes {
+	// nearest first
+	classes: Vec<ClassId>,
+	interfaces: BTreeSet<ClassId>,
+}
+
+impl<R: ClassResolver> Hierarchy<R> {
+	pub fn new(resolver: R) -> Self {
+		Self {
+			resolver,
+			symbols: RefCell::default(),
+			headers: RefCell::default(),
+			supertypes: RefCell::default(),
+			common: RefCell::default(),
+		}
+	}
+
+	pub fn resolver(&self) -> &R {
+		&self.resolver
+	}
+
+	/// The superclasses of `class`, nearest first, up to java/lang/Object or the first one that can't be found.
+	pub fn super_classes(&self, class: &str) -> Vec<String> {
+		let supertypes = self.supertypes(self.id(class));
+		supertypes.classes.iter().map(|&class| self.name(class)).collect()
+	}
+
+	/// Every interface `class` implements, declared on it, on its superclasses or extended by any of those. For an
+	/// interface, the interfaces it extends.
+	pub fn interfaces(&self, class: &str) -> BTreeSet<String> {
+		let supertypes = self.supertypes(self.id(class));
+		supertypes
+			.interfaces
+			.iter()
+			.map(|&interface| self.name(interface))
+			.collect()
+	}
+
+	/// Whether a value of type `sub` can be used as a `sup`, as checkcast and instanceof decide it. Both are internal
+	/// names, array types in descriptor form.
+	pub fn is_subtype_of(&self, sub: &str, sup: &str) -> bool {
+		if sub == sup || sup == OBJECT {
+			return true;
+		}
+		match (names::is_array(sub), names::is_array(sup)) {
+			// Arrays of references follow their elements, primitive arrays are only their own type.
+			(true, true) => match (
+				names::descriptor_to_internal(&sub[1..]),
+				names::descriptor_to_internal(&sup[1..]),
+			) {
+				(Some(sub), Some(sup)) => self.is_subtype_of(sub, sup),
+				_ => false,
+			},
+			(true, false) => sup == CLONEABLE || sup == SERIALIZABLE,
+			(false, true) => false,
+			(false, false) => {
+				let supertypes = self.supertypes(self.id(sub));
+				// Any supertype was given an id on the way.
+				let Some(sup) = self.symbols.borrow().lookup_class(sup) else {
+					return false;
+				};
+				supertypes.classes.contains(&sup) || supertypes.interfaces.contains(&sup)
+			}
+		}
+	}
+
+	/// Forgets everything looked up so far, e.g. after the classes behind the resolver changed.
+	pub fn clear(&self) {
+		self.headers.borrow_mut().clear();
+		self.supertypes.borrow_mut().clear();
+		self.common.borrow_mut().clear();
+	}
+
+	fn id(&self, class: &str) -> ClassId {
+		self.symbols.borrow_mut().class(class)
+	}
+
+	fn name(&self, class: ClassId) -> String {
+		self.symbols.borrow().class_name(class).to_string()
+	}
+
+	fn header(&self, class: ClassId) -> Option<Rc<Header>> {
+		if let Some(header) = self.headers.borrow().get(&class) {
+			return header.clone();
+		}
+		let header = self.resolver.resolve(&self.name(class)).map(|resolved| {
+			let mut symbols = self.symbols.borrow_mut();
+			Rc::new(Header {
+				super_class: resolved.super_name().map(|name| symbols.class(name)),
+				interfaces: resolved.interface_names().map(|name| symbols.class(name)).collect(),
+				is_interface: resolved.access_flags.is_interface(),
+			})
+		});
+		self.headers.borrow_mut().insert(class, header.clone());
+		header
+	}
+
+	fn supertypes(&self, class: ClassId) -> Rc<Supertypes> {
+		if let Some(supertypes) = self.supertypes.borrow().get(&class) {
+			return supertypes.clone();
+		}
+
+		let mut supertypes = Supertypes::default();
+		let mut pending = Vec::new();
+		let mut current = self.header(class);
+		while let Some(header) = current {
+			pending.extend(header.interfaces.iter().copied());
+			let Some(next) = header.super_class else {
+				break;
+			};
+			// A cyclic hierarchy is broken input, don't loop on it.
+			if next == class || supertypes.classes.contains(&next) {
+				break;
+			}
+			supertypes.classes.push(next);
+			current = self.header(next);
+		}
+		while let Some(interface) = pending.pop() {
+			if interface != class && supertypes.interfaces.insert(interface) {
+				if let Some(header) = self.header(interface) {
+					pending.extend(header.interfaces.iter().copied());
+				}
+			}
+		}
+
+		let supertypes = Rc::new(supertypes);
+		self.supertypes.borrow_mut().insert(class, supertypes.clone());
+		supertypes
+	}
+
+	fn is_interface_id(&self, class: ClassId) -> bool {
+		self.header(class).is_some_and(|header| header.is_interface)
+	}
+}
+
+impl<R: ClassResolver> ClassHierarchy for Hierarchy<R> {
+	fn super_class(&self, class: &str) -> Option<String> {
+		let super_class = self.header(self.id(class))?.super_class?;
+		Some(self.name(super_class))
+	}
+
+	fn is_interface(&self, class: &str) -> bool {
+		self.is_interface_id(self.id(class))
+	}
+
+	fn common_super_class(&self, a: &str, b: &str) -> String {
+		if a == b {
+			return a.to_string();
+		}
+		let (a, b) = (self.id(a), self.id(b));
+		let key = (a.min(b), a.max(b));
+		if let Some(&common) = self.common.borrow().get(&key) {
+			return self.name(common);
+		}
+
+		let object = self.id(OBJECT);
+		let common = if self.is_interface_id(a) || self.is_interface_id(b) {
+			object
+		} else {
+			let supers_of_b = self.supertypes(b);
+			std::iter::once(a)
+				.chain(self.supertypes(a).classes.iter().copied())
+				.find(|class| *class == b || supers_of_b.classes.contains(class))
+				.unwrap_or(object)
+		};
+		self.common.borrow_mut().insert(key, common);
+		self.name(common)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{asm::assemble, classpath::MemoryProvider};
+
+	#[test]
+	fn resolved_hierarchy() {
+		let mut classes = MemoryProvider::new();
+		for source in [
+			".class public interface abstract p/J",
+			".class public interface abstract p/I\n.implements p/J",
+			".class public interface abstract p/K",
+			".class public super p/Base\n.implements p/I",
+			".class public super p/A\n.super p/Base",
+			".class public super p/B\n.super p/Base\n.implements p/K",
+		] {
+			let class = assemble(source).unwrap();
+			classes.insert(class.class_name(), class.to_bytes().unwrap());
+		}
+		let hierarchy = Hierarchy::new(classes);
+
+		assert_eq!(hierarchy.super_classes("p/A"), ["p/Base", OBJECT]);
+		assert_eq!(hierarchy.super_classes("p/Unknown"), Vec::<String>::new());
+		assert_eq!(
+			hierarchy.interfaces("p/B").into_iter().collect::<Vec<_>>(),
+			["p/I", "p/J", "p/K"]
+		);
+		assert!(hierarchy.is_subtype_of("p/A", "p/J"));
+		assert!(hierarchy.is_subtype_of("p/I", OBJECT));
+		assert!(!hierarchy.is_subtype_of("p/A", "p/K"));
+		assert!(!hierarchy.is_subtype_of("p/Base", "p/A"));
+		assert!(hierarchy.is_subtype_of("[[Lp/A;", "[[Lp/J;"));
+		assert!(hierarchy.is_subtype_of("[I", CLONEABLE));
+		assert!(!hierarchy.is_subtype_of("[I", "[J"));
+		assert!(!hierarchy.is_subtype_of("[Lp/A;", "[Lp/K;"));
+
+		assert_eq!(hierarchy.common_super_class("p/A", "p/B"), "p/Base");
+		assert_eq!(hierarchy.common_super_class("p/B", "p/A"), "p/Base");
+		assert_eq!(hierarchy.common_super_class("p/A", "p/Base"), "p/Base");
+		assert_eq!(hierarchy.common_super_class("p/A", "p/I"), OBJECT);
+		assert_eq!(hierarchy.common_super_class("p/A", "p/Unknown"), OBJECT);
+		assert_eq!(hierarchy.common.borrow().len(), 4);
+	}
+}
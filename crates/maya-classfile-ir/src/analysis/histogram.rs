@@ -0,0 +1,274 @@
+// Opcode counts and instruction mix statistics, for research on what code actually looks like and for spotting
+// obfuscated or generated hotspots: unusual branch or invoke density stands out next to javac output. Counts are by
+// decoded instruction, so short forms are folded into their general instruction (`aload_0` counts as `aload`).
+// Histograms add up, a JAR's is the merge of its classes'.
+
+use std::fmt::Write;
+
+use crate::{
+	class_pool::IRClassfileError,
+	code::{Instructions, Opcodes},
+	json::{self, JsonObject},
+	IRClassFile,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeHistogram {
+	counts: [u64; 256],
+	pub classes: usize,
+	// methods with code
+	pub methods: usize,
+}
+
+impl Default for OpcodeHistogram {
+	fn default() -> Self {
+		Self {
+			counts: [0; 256],
+			classes: 0,
+			methods: 0,
+		}
+	}
+}
+
+impl OpcodeHistogram {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn of_class(class: &IRClassFile) -> Result<Self, IRClassfileError> {
+		let mut histogram = Self::new();
+		histogram.add_class(class)?;
+		Ok(histogram)
+	}
+
+	pub fn add_class(&mut self, class: &IRClassFile) -> Result<(), IRClassfileError> {
+		self.classes += 1;
+		for method in &class.methods {
+			if let Some(code) = method.code() {
+				self.methods += 1;
+				self.add_instructions(&Instructions::read_all(&class.cp, &code.code)?);
+			}
+		}
+		Ok(())
+	}
+
+	pub fn add_instructions(&mut self, instructions: &[(u32, Instructions)]) {
+		for (_, insn) in instructions {
+			self.counts[insn.opcode() as usize] += 1;
+		}
+	}
+
+	pub fn merge(&mut self, other: &OpcodeHistogram) {
+		for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+			*count += other;
+		}
+		self.classes += other.classes;
+		self.methods += other.methods;
+	}
+
+	pub fn count(&self, opcode: u8) -> u64 {
+		self.counts[opcode as usize]
+	}
+
+	pub fn total(&self) -> u64 {
+		self.counts.iter().sum()
+	}
+
+	/// Opcodes that occur, with their counts, most frequent first.
+	pub fn most_frequent(&self) -> Vec<(u8, u64)> {
+		let mut counts = (0..=u8::MAX)
+			.map(|opcode| (opcode, self.count(opcode)))
+			.filter(|(_, count)| *count != 0)
+			.collect::<Vec<_>>();
+		counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+		counts
+	}
+
+	pub fn mix(&self) -> InstructionMix {
+		let sum = |opcodes: &[u8]| opcodes.iter().map(|opcode| self.count(*opcode)).sum();
+		InstructionMix {
+			instructions: self.total(),
+			invokes: sum(&[
+				Opcodes::INVOKEVIRTUAL,
+				Opcodes::INVOKESPECIAL,
+				Opcodes::INVOKESTATIC,
+				Opcodes::INVOKEINTERFACE,
+				Opcodes::INVOKEDYNAMIC,
+			]),
+			branches: sum(&[
+				Opcodes::IFEQ,
+				Opcodes::IFNE,
+				Opcodes::IFLT,
+				Opcodes::IFGE,
+				Opcodes::IFGT,
+				Opcodes::IFLE,
+				Opcodes::IF_ICMPEQ,
+				Opcodes::IF_ICMPNE,
+				Opcodes::IF_ICMPLT,
+				Opcodes::IF_ICMPGE,
+				Opcodes::IF_ICMPGT,
+				Opcodes::IF_ICMPLE,
+				Opcodes::IF_ACMPEQ,
+				Opcodes::IF_ACMPNE,
+				Opcodes::IFNULL,
+				Opcodes::IFNONNULL,
+				Opcodes::GOTO,
+				Opcodes::GOTO_W,
+				Opcodes::JSR,
+				Opcodes::JSR_W,
+				Opcodes::TABLESWITCH,
+				Opcodes::LOOKUPSWITCH,
+			]),
+			allocations: sum(&[
+				Opcodes::NEW,
+				Opcodes::NEWARRAY,
+				Opcodes::ANEWARRAY,
+				Opcodes::MULTIANEWARRAY,
+			]),
+		}
+	}
+
+	/// One `opcode,mnemonic,count` row per opcode that occurs, by opcode.
+	pub fn to_csv(&self) -> String {
+		let mut out = String::from("opcode,mnemonic,count\n");
+		for opcode in 0..=u8::MAX {
+			let count = self.count(opcode);
+			if count != 0 {
+				let _ = writeln!(out, "{opcode},{},{count}", mnemonic(opcode));
+			}
+		}
+		out
+	}
+
+	pub fn to_json(&self) -> String {
+		let counts = (0..=u8::MAX)
+			.filter(|opcode| self.count(*opcode) != 0)
+			.collect::<Vec<_>>();
+		let counts = json::array(counts, |opcode, out| {
+			JsonObject::new(out)
+				.num("opcode", opcode)
+				.str("mnemonic", mnemonic(opcode))
+				.num("count", self.count(opcode))
+				.end();
+		});
+
+		let mix = self.mix();
+		let mut out = String::new();
+		JsonObject::new(&mut out)
+			.num("classes", self.classes)
+			.num("methods", self.methods)
+			.num("instructions", mix.instructions)
+			.num("invokes", mix.invokes)
+			.num("branches", mix.branches)
+			.num("allocations", mix.allocations)
+			.num("invoke_density", format_args!("{:.4}", mix.invoke_density()))
+			.num("branch_density", format_args!("{:.4}", mix.branch_density()))
+			.raw("opcodes", &counts)
+			.end();
+		out
+	}
+}
+
+fn mnemonic(opcode: u8) -> &'static str {
+	// Decoded instructions only ever have real opcodes.
+	Opcodes::mnemonic(opcode).unwrap_or("unknown")
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InstructionMix {
+	pub instructions: u64,
+	pub invokes: u64,
+	// jumps and switches
+	pub branches: u64,
+	pub allocations: u64,
+}
+
+impl InstructionMix {
+	/// Invokes per instruction.
+	pub fn invoke_density(&self) -> f64 {
+		self.density(self.invokes)
+	}
+
+	/// Branches per instruction.
+	pub fn branch_density(&self) -> f64 {
+		self.density(self.branches)
+	}
+
+	fn density(&self, count: u64) -> f64 {
+		match self.instructions {
+			0 => 0.0,
+			instructions => count as f64 / instructions as f64,
+		}
+	}
+}
+
+/// A CSV table with the instruction mix of each histogram, e.g. one row per class or per JAR.
+pub fn mix_csv<'a>(rows: impl IntoIterator<Item = (&'a str, &'a OpcodeHistogram)>) -> String {
+	let mut out =
+		String::from("name,classes,methods,instructions,invokes,branches,allocations,invoke_density,branch_density\n");
+	for (name, histogram) in rows {
+		let mix = histogram.mix();
+		csv_field(name, &mut out);
+		let _ = writeln!(
+			out,
+			",{},{},{},{},{},{},{:.4},{:.4}",
+			histogram.classes,
+			histogram.methods,
+			mix.instructions,
+			mix.invokes,
+			mix.branches,
+			mix.allocations,
+			mix.invoke_density(),
+			mix.branch_density()
+		);
+	}
+	out
+}
+
+// https://www.rfc-editor.org/rfc/rfc4180#section-2
+fn csv_field(value: &str, out: &mut String) {
+	if value.contains([',', '"', '\n', '\r']) {
+		out.push('"');
+		out.push_str(&value.replace('"', "\"\""));
+		out.push('"');
+	} else {
+		out.push_str(value);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn counts_and_mix() {
+		// aload_0, aload_1, ifnull +5, iconst_0, ireturn
+		let mut histogram = OpcodeHistogram::new();
+		let instructions = Instructions::read_all(&[], &[0x2A, 0x2B, 0xC6, 0, 5, 0x03, 0xAC]).unwrap();
+		histogram.add_instructions(&instructions);
+		histogram.methods += 1;
+		let mut total = OpcodeHistogram::new();
+		total.merge(&histogram);
+		total.merge(&histogram);
+
+		assert_eq!(total.count(Opcodes::ALOAD), 4);
+		assert_eq!(total.most_frequent()[0], (Opcodes::ALOAD, 4));
+		assert_eq!(
+			total.mix(),
+			InstructionMix {
+				instructions: 10,
+				invokes: 0,
+				branches: 2,
+				allocations: 0,
+			}
+		);
+		assert_eq!(
+			total.to_csv(),
+			"opcode,mnemonic,count\n3,iconst_0,2\n25,aload,4\n172,ireturn,2\n198,ifnull,2\n"
+		);
+		assert_eq!(
+			mix_csv([("a,b", &total)]),
+			"name,classes,methods,instructions,invokes,branches,allocations,invoke_density,branch_density\n\"a,b\",0,2,10,0,2,0,0.0000,0.2000\n"
+		);
+	}
+}
@@ -0,0 +1,10 @@
+// Analyses over method bodies, built on the decoded instructions.
+
+pub mod callgraph;
+pub mod cfg;
+pub mod constants;
+pub mod dataflow;
+pub mod frames;
+pub mod hierarchy;
+pub mod histogram;
+pub mod stack;
@@ -0,0 +1,809 @@
+// Abstract interpretation of the operand stack: every path through a method is walked tracking how deep the stack is
+// and what kind of value each stack entry and local holds, without the class types frame computation needs. That's
+// enough to compute max_stack and max_locals, and to catch code that pops what isn't there, goes past max_stack or
+// uses a value as the wrong kind, reported at the offending pc.
+//
+// A long or double is one stack entry taking two slots. The untyped stack instructions (pop2, dup_x2 and the like)
+// work on slots, and taking half of a long or double with one of them is a type mismatch.
+//
+// Subroutines are followed loosely: `jsr` pushes a return address for the subroutine, execution resumes after it with
+// the stack and locals from before the call, and `ret` ends the path.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-2.html#jvms-2.11.1
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+	attribute::{CodeAttribute, ResolvedFrame, VerificationType},
+	class_pool::{IRClassfileError, IRCpTag},
+	code::Instructions,
+	descriptor::{BaseType, FieldType, MethodDescriptor},
+	IRMethodInfo,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+	// a local that's unset, or that paths disagree on
+	Top,
+	Int,
+	Long,
+	Float,
+	Double,
+	Reference,
+	UninitializedThis,
+	// made by the `new` at this pc, its constructor not called yet
+	Uninitialized(u32),
+	ReturnAddress,
+}
+
+impl ValueKind {
+	/// The kind of a value of this type, booleans, bytes, chars and shorts being ints.
+	pub fn of(ty: &FieldType) -> Self {
+		match ty {
+			FieldType::Base(BaseType::Long) => Self::Long,
+			FieldType::Base(BaseType::Float) => Self::Float,
+			FieldType::Base(BaseType::Double) => Self::Double,
+			FieldType::Base(_) => Self::Int,
+			FieldType::Object(_) | FieldType::Array(_) => Self::Reference,
+		}
+	}
+
+	/// Slots the value takes on the stack or in the locals.
+	pub fn size(self) -> u16 {
+		match self {
+			Self::Long | Self::Double => 2,
+			_ => 1,
+		}
+	}
+
+	/// References, initialized or not.
+	pub fn is_reference(self) -> bool {
+		matches!(self, Self::Reference | Self::UninitializedThis | Self::Uninitialized(_))
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Top => "no value",
+			Self::Int => "int",
+			Self::Long => "long",
+			Self::Float => "float",
+			Self::Double => "double",
+			Self::Reference => "reference",
+			Self::UninitializedThis => "uninitialized this",
+			Self::Uninitialized(_) => "uninitialized reference",
+			Self::ReturnAddress => "return address",
+		}
+	}
+}
+
+impl From<&VerificationType> for ValueKind {
+	fn from(ty: &VerificationType) -> Self {
+		match ty {
+			VerificationType::Top => Self::Top,
+			VerificationType::Integer => Self::Int,
+			VerificationType::Float => Self::Float,
+			VerificationType::Long => Self::Long,
+			VerificationType::Double => Self::Double,
+			VerificationType::Null | VerificationType::Object(_) => Self::Reference,
+			VerificationType::UninitializedThis => Self::UninitializedThis,
+			VerificationType::Uninitialized(pc) => Self::Uninitialized(*pc),
+		}
+	}
+}
+
+/// The locals and operand stack before an instruction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StackState {
+	// by slot, the second slot of a long or double being Top
+	pub locals: Vec<ValueKind>,
+	// bottom first, a long or double being one entry
+	pub stack: Vec<ValueKind>,
+}
+
+impl StackState {
+	/// Slots taken on the stack.
+	pub fn depth(&self) -> u16 {
+		self.stack.iter().map(|kind| kind.size()).sum()
+	}
+}
+
+/// The kinds of the types in a frame, e.g. one from `frames::analyze_types`.
+impl From<&ResolvedFrame> for StackState {
+	fn from(frame: &ResolvedFrame) -> Self {
+		Self {
+			locals: frame.local_slots().iter().map(ValueKind::from).collect(),
+			stack: frame.stack.iter().map(ValueKind::from).collect(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simulation {
+	// the state before each instruction, None where it can't be reached
+	states: Vec<(u32, Option<StackState>)>,
+	max_stack: u16,
+	max_locals: u16,
+}
+
+impl Simulation {
+	/// The deepest the stack gets, in slots.
+	pub fn max_stack(&self) -> u16 {
+		self.max_stack
+	}
+
+	/// Locals used, in slots, parameters and `this` included.
+	pub fn max_locals(&self) -> u16 {
+		self.max_locals
+	}
+
+	/// The state before the instruction at `pc`, `None` if there's none there or it can't be reached.
+	pub fn state_at(&self, pc: u32) -> Option<&StackState> {
+		let i = self.states.binary_search_by_key(&pc, |(pc, _)| *pc).ok()?;
+		self.states[i].1.as_ref()
+	}
+}
+
+/// Simulates the code of `method`, holding it to the max_stack and max_locals it declares. `None` if it has no code.
+pub fn simulate(
+	cp: &[IRCpTag],
+	class_name: &str,
+	method: &IRMethodInfo,
+) -> Result<Option<Simulation>, IRClassfileError> {
+	let Some(code) = method.code() else {
+		return Ok(None);
+	};
+	run(cp, class_name, method, code, Some((code.max_stack, code.max_locals))).map(Some)
+}
+
+/// The max_stack and max_locals the code of `method` needs, whatever it declares. `None` if it has no code.
+pub fn compute_maxs(
+	cp: &[IRCpTag],
+	class_name: &str,
+	method: &IRMethodInfo,
+) -> Result<Option<(u16, u16)>, IRClassfileError> {
+	let Some(code) = method.code() else {
+		return Ok(None);
+	};
+	let simulation = run(cp, class_name, method, code, None)?;
+	Ok(Some((simulation.max_stack, simulation.max_locals)))
+}
+
+fn run(
+	cp: &[IRCpTag],
+	class_name: &str,
+	method: &IRMethodInfo,
+	code: &CodeAttribute,
+	limits: Option<(u16, u16)>,
+) -> Result<Simulation, IRClassfileError> {
+	let instructions = Instructions::read_all(cp, &code.code)?;
+	let index_of = instructions
+		.iter()
+		.enumerate()
+		.map(|(i, (pc, _))| (*pc, i))
+		.collect::<HashMap<_, _>>();
+
+	let mut locals = Vec::new();
+	if !method.access_flags.is_static() {
+		locals.push(match method.name() == "<init>" && class_name != "java/lang/Object" {
+			true => ValueKind::UninitializedThis,
+			false => ValueKind::Reference,
+		});
+	}
+	for param in MethodDescriptor::parse(method.descriptor())?.params {
+		push_kind(&mut locals, ValueKind::of(&param));
+	}
+	let mut max_locals = locals.len() as u16;
+	if let Some((_, limit)) = limits {
+		if max_locals > limit {
+			return Err(IRClassfileError::LocalOutOfRange {
+				pc: 0,
+				index: max_locals - 1,
+				max_locals: limit,
+			});
+		}
+	}
+	let mut max_stack = 0;
+
+	let mut states = vec![None; instructions.len()];
+	let mut worklist = BTreeSet::new();
+	if !instructions.is_empty() {
+		states[0] = Some(StackState {
+			locals,
+			stack: Vec::new(),
+		});
+		worklist.insert(0);
+	}
+
+	while let Some(i) = worklist.pop_first() {
+		let (pc, insn) = &instructions[i];
+		let before = states[i].clone().expect("only instructions with a state are queued");
+		let mut machine = Machine {
+			pc: *pc,
+			state: before.clone(),
+			max_locals: limits.map(|(_, max_locals)| max_locals),
+			locals_used: 0,
+		};
+		machine.execute(insn)?;
+		let after = machine.state;
+		let depth = after.depth();
+		if let Some((limit, _)) = limits {
+			if depth > limit {
+				return Err(IRClassfileError::StackOverflow {
+					pc: *pc,
+					max_stack: limit,
+				});
+			}
+		}
+		max_stack = max_stack.max(depth);
+		max_locals = max_locals.max(machine.locals_used);
+
+		let mut successors = Vec::new();
+		if insn.falls_through() {
+			if i + 1 >= instructions.len() {
+				return Err(IRClassfileError::FallsOffCode { pc: *pc });
+			}
+			// Returning from a subroutine pops the address the jsr pushed.
+			let resumed = match insn {
+				Instructions::JSR(_) | Instructions::JSR_W(_) => before.clone(),
+				_ => after.clone(),
+			};
+			successors.push((i + 1, resumed));
+		}
+		for target in insn.branch_targets(*pc) {
+			let target = *index_of
+				.get(&target)
+				.ok_or(IRClassfileError::InvalidBranchTarget { pc: *pc, target })?;
+			successors.push((target, after.clone()));
+		}
+		// A handler can be entered before or after the instruction ran, with only the exception on the stack.
+		for handler in &code.exception_table {
+			if (handler.start_pc as u32..handler.end_pc as u32).contains(pc) {
+				let target =
+					*index_of
+						.get(&(handler.handler_pc as u32))
+						.ok_or(IRClassfileError::InvalidBranchTarget {
+							pc: *pc,
+							target: handler.handler_pc as u32,
+						})?;
+				for locals in [&before.locals, &after.locals] {
+					let state = StackState {
+						locals: locals.clone(),
+						stack: vec![ValueKind::Reference],
+					};
+					successors.push((target, state));
+				}
+			}
+		}
+
+		for (target, state) in successors {
+			let merged = match &states[target] {
+				None => state,
+				Some(existing) => merge_states(instructions[target].0, existing, &state)?,
+			};
+			if states[target].as_ref() != Some(&merged) {
+				states[target] = Some(merged);
+				worklist.insert(target);
+			}
+		}
+	}
+
+	Ok(Simulation {
+		states: instructions.iter().map(|(pc, _)| *pc).zip(states).collect(),
+		max_stack,
+		max_locals,
+	})
+}
+
+fn push_kind(slots: &mut Vec<ValueKind>, kind: ValueKind) {
+	slots.push(kind);
+	if kind.size() == 2 {
+		slots.push(ValueKind::Top);
+	}
+}
+
+fn merge_states(pc: u32, a: &StackState, b: &StackState) -> Result<StackState, IRClassfileError> {
+	if a.depth() != b.depth() {
+		return Err(IRClassfileError::StackHeightMismatch {
+			pc,
+			a: a.depth(),
+			b: b.depth(),
+		});
+	}
+	for (a, b) in a.stack.iter().zip(&b.stack) {
+		if a != b {
+			return Err(IRClassfileError::StackTypeMismatch {
+				pc,
+				expected: a.name(),
+				found: b.name(),
+			});
+		}
+	}
+
+	let len = a.locals.len().max(b.locals.len());
+	let local = |locals: &[ValueKind], i: usize| locals.get(i).copied().unwrap_or(ValueKind::Top);
+	let locals = (0..len)
+		.map(|i| match (local(&a.locals, i), local(&b.locals, i)) {
+			(a, b) if a == b => a,
+			_ => ValueKind::Top,
+		})
+		.collect();
+	Ok(StackState {
+		locals,
+		stack: a.stack.clone(),
+	})
+}
+
+struct Machine {
+	pc: u32,
+	state: StackState,
+	// the declared limit, None when computing it
+	max_locals: Option<u16>,
+	// locals touched by the instruction, in slots from 0
+	locals_used: u16,
+}
+
+impl Machine {
+	fn mismatch(&self, expected: &'static str, found: ValueKind) -> IRClassfileError {
+		IRClassfileError::StackTypeMismatch {
+			pc: self.pc,
+			expected,
+			found: found.name(),
+		}
+	}
+
+	fn push(&mut self, kind: ValueKind) {
+		self.state.stack.push(kind);
+	}
+
+	fn pop_any(&mut self) -> Result<ValueKind, IRClassfileError> {
+		self.state
+			.stack
+			.pop()
+			.ok_or(IRClassfileError::StackUnderflow { pc: self.pc })
+	}
+
+	/// Pops a value of this kind, any reference for `Reference`.
+	fn pop(&mut self, expected: ValueKind) -> Result<ValueKind, IRClassfileError> {
+		let found = self.pop_any()?;
+		let matches = match expected {
+			ValueKind::Reference => found.is_reference(),
+			_ => found == expected,
+		};
+		match matches {
+			true => Ok(found),
+			false => Err(self.mismatch(expected.name(), found)),
+		}
+	}
+
+	fn pop_n(&mut self, expected: ValueKind, n: usize) -> Result<(), IRClassfileError> {
+		for _ in 0..n {
+			self.pop(expected)?;
+		}
+		Ok(())
+	}
+
+	/// Pops exactly `slots` slots worth of values, bottom first.
+	fn pop_slots(&mut self, slots: u16) -> Result<Vec<ValueKind>, IRClassfileError> {
+		let mut values = Vec::new();
+		let mut taken = 0;
+		while taken < slots {
+			let value = self.pop_any()?;
+			taken += value.size();
+			if taken > slots {
+				return Err(self.mismatch("a category 1 value", value));
+			}
+			values.push(value);
+		}
+		values.reverse();
+		Ok(values)
+	}
+
+	/// Takes groups of slots off the stack, the first group from the top, and pushes them back in `order`.
+	fn shuffle(&mut self, groups: &[u16], order: &[usize]) -> Result<(), IRClassfileError> {
+		let taken = groups
+			.iter()
+			.map(|slots| self.pop_slots(*slots))
+			.collect::<Result<Vec<_>, _>>()?;
+		for group in order {
+			self.state.stack.extend(&taken[*group]);
+		}
+		Ok(())
+	}
+
+	fn use_local(&mut self, index: u16, kind: ValueKind) -> Result<(), IRClassfileError> {
+		let end = index as u32 + kind.size() as u32;
+		if let Some(max_locals) = self.max_locals {
+			if end > max_locals as u32 {
+				return Err(IRClassfileError::LocalOutOfRange {
+					pc: self.pc,
+					index,
+					max_locals,
+				});
+			}
+		}
+		self.locals_used = self.locals_used.max(end as u16);
+		Ok(())
+	}
+
+	/// Pushes a local that has to be of this kind, any reference for `Reference`.
+	fn load(&mut self, index: u16, expected: ValueKind) -> Result<(), IRClassfileError> {
+		self.use_local(index, expected)?;
+		let found = self.state.locals.get(index as usize).copied().unwrap_or(ValueKind::Top);
+		let matches = match expected {
+			ValueKind::Reference => found.is_reference(),
+			_ => found == expected,
+		};
+		if !matches {
+			return Err(self.mismatch(expected.name(), found));
+		}
+		self.push(found);
+		Ok(())
+	}
+
+	fn store(&mut self, index: u16, expected: ValueKind) -> Result<(), IRClassfileError> {
+		let kind = match expected {
+			// astore also takes the return address of a subroutine.
+			ValueKind::Reference => match self.pop_any()? {
+				kind if kind.is_reference() || kind == ValueKind::ReturnAddress => kind,
+				kind => return Err(self.mismatch("reference", kind)),
+			},
+			_ => self.pop(expected)?,
+		};
+		self.set_local(index, kind)
+	}
+
+	fn set_local(&mut self, index: u16, kind: ValueKind) -> Result<(), IRClassfileError> {
+		self.use_local(index, kind)?;
+		let locals = &mut self.state.locals;
+		let index = index as usize;
+		let end = index + kind.size() as usize;
+		if locals.len() < end {
+			locals.resize(end, ValueKind::Top);
+		}
+		// Overwriting the second half of a long or double invalidates the first.
+		if index > 0 && locals[index - 1].size() == 2 {
+			locals[index - 1] = ValueKind::Top;
+		}
+		if kind.size() == 2 {
+			locals[index + 1] = ValueKind::Top;
+		}
+		locals[index] = kind;
+		Ok(())
+	}
+
+	fn binary(&mut self, kind: ValueKind) -> Result<(), IRClassfileError> {
+		self.pop_n(kind, 2)?;
+		self.push(kind);
+		Ok(())
+	}
+
+	fn convert(&mut self, from: ValueKind, to: ValueKind) -> Result<(), IRClassfileError> {
+		self.pop(from)?;
+		self.push(to);
+		Ok(())
+	}
+
+	fn invoke(&mut self, descriptor: &str, has_receiver: bool) -> Result<Option<ValueKind>, IRClassfileError> {
+		let descriptor = MethodDescriptor::parse(descriptor)?;
+		for param in descriptor.params.iter().rev() {
+			self.pop(ValueKind::of(param))?;
+		}
+		let receiver = match has_receiver {
+			true => Some(self.pop(ValueKind::Reference)?),
+			false => None,
+		};
+		if let Some(ret) = &descriptor.ret {
+			self.push(ValueKind::of(ret));
+		}
+		Ok(receiver)
+	}
+
+	fn field(&self, descriptor: &str) -> Result<ValueKind, IRClassfileError> {
+		Ok(ValueKind::of(&FieldType::parse(descriptor)?))
+	}
+
+	fn execute(&mut self, insn: &Instructions) -> Result<(), IRClassfileError> {
+		use Instructions as I;
+		use ValueKind as K;
+
+		match insn {
+			I::NOP | I::GOTO(_) | I::GOTO_W(_) | I::RETURN => {}
+			I::ACONST_NULL => self.push(K::Reference),
+			I::ICONST_M1
+			| I::ICONST_0
+			| I::ICONST_1
+			| I::ICONST_2
+			| I::ICONST_3
+			| I::ICONST_4
+			| I::ICONST_5
+			| I::BIPUSH(_)
+			| I::SIPUSH(_) => self.push(K::Int),
+			I::LCONST_0 | I::LCONST_1 => self.push(K::Long),
+			I::FCONST_0 | I::FCONST_1 | I::FCONST_2 => self.push(K::Float),
+			I::DCONST_0 | I::DCONST_1 => self.push(K::Double),
+			I::LDC(tag) => self.push(match tag {
+				IRCpTag::Integer(_) => K::Int,
+				IRCpTag::Float(_) => K::Float,
+				IRCpTag::Long(_) => K::Long,
+				IRCpTag::Double(_) => K::Double,
+				IRCpTag::String(_) | IRCpTag::Class(_) | IRCpTag::MethodType(_) | IRCpTag::MethodHandle { .. } => {
+					K::Reference
+				}
+				_ => return Err(self.mismatch("a loadable constant", K::Top)),
+			}),
+
+			I::ILOAD(index) => self.load(*index, K::Int)?,
+			I::LLOAD(index) => self.load(*index, K::Long)?,
+			I::FLOAD(index) => self.load(*index, K::Float)?,
+			I::DLOAD(index) => self.load(*index, K::Double)?,
+			I::ALOAD(index) => self.load(*index, K::Reference)?,
+			I::ISTORE(index) => self.store(*index, K::Int)?,
+			I::LSTORE(index) => self.store(*index, K::Long)?,
+			I::FSTORE(index) => self.store(*index, K::Float)?,
+			I::DSTORE(index) => self.store(*index, K::Double)?,
+			I::ASTORE(index) => self.store(*index, K::Reference)?,
+			I::IINC { index, .. } => {
+				self.load(*index, K::Int)?;
+				self.pop_any()?;
+			}
+
+			I::IALOAD | I::BALOAD | I::CALOAD | I::SALOAD => self.array_load(K::Int)?,
+			I::LALOAD => self.array_load(K::Long)?,
+			I::FALOAD => self.array_load(K::Float)?,
+			I::DALOAD => self.array_load(K::Double)?,
+			I::AALOAD => self.array_load(K::Reference)?,
+			I::IASTORE | I::BASTORE | I::CASTORE | I::SASTORE => self.array_store(K::Int)?,
+			I::LASTORE => self.array_store(K::Long)?,
+			I::FASTORE => self.array_store(K::Float)?,
+			I::DASTORE => self.array_store(K::Double)?,
+			I::AASTORE => self.array_store(K::Reference)?,
+
+			I::POP => self.shuffle(&[1], &[])?,
+			I::POP2 => self.shuffle(&[2], &[])?,
+			I::DUP => self.shuffle(&[1], &[0, 0])?,
+			I::DUP_X1 => self.shuffle(&[1, 1], &[0, 1, 0])?,
+			I::DUP_X2 => self.shuffle(&[1, 2], &[0, 1, 0])?,
+			I::DUP2 => self.shuffle(&[2], &[0, 0])?,
+			I::DUP2_X1 => self.shuffle(&[2, 1], &[0, 1, 0])?,
+			I::DUP2_X2 => self.shuffle(&[2, 2], &[0, 1, 0])?,
+			I::SWAP => self.shuffle(&[1, 1], &[0, 1])?,
+
+			I::IADD
+			| I::ISUB
+			| I::IMUL
+			| I::IDIV
+			| I::IREM
+			| I::ISHL
+			| I::ISHR
+			| I::IUSHR
+			| I::IAND
+			| I::IOR
+			| I::IXOR => self.binary(K::Int)?,
+			I::LADD | I::LSUB | I::LMUL | I::LDIV | I::LREM | I::LAND | I::LOR | I::LXOR => self.binary(K::Long)?,
+			I::LSHL | I::LSHR | I::LUSHR => {
+				self.pop(K::Int)?;
+				self.convert(K::Long, K::Long)?;
+			}
+			I::FADD | I::FSUB | I::FMUL | I::FDIV | I::FREM => self.binary(K::Float)?,
+			I::DADD | I::DSUB | I::DMUL | I::DDIV | I::DREM => self.binary(K::Double)?,
+			I::INEG | I::I2B | I::I2C | I::I2S => self.convert(K::Int, K::Int)?,
+			I::LNEG => self.convert(K::Long, K::Long)?,
+			I::FNEG => self.convert(K::Float, K::Float)?,
+			I::DNEG => self.convert(K::Double, K::Double)?,
+			I::I2L => self.convert(K::Int, K::Long)?,
+			I::I2F => self.convert(K::Int, K::Float)?,
+			I::I2D => self.convert(K::Int, K::Double)?,
+			I::L2I => self.convert(K::Long, K::Int)?,
+			I::L2F => self.convert(K::Long, K::Float)?,
+			I::L2D => self.convert(K::Long, K::Double)?,
+			I::F2I => self.convert(K::Float, K::Int)?,
+			I::F2L => self.convert(K::Float, K::Long)?,
+			I::F2D => self.convert(K::Float, K::Double)?,
+			I::D2I => self.convert(K::Double, K::Int)?,
+			I::D2L => self.convert(K::Double, K::Long)?,
+			I::D2F => self.convert(K::Double, K::Float)?,
+			I::LCMP => self.compare(K::Long)?,
+			I::FCMPL | I::FCMPG => self.compare(K::Float)?,
+			I::DCMPL | I::DCMPG => self.compare(K::Double)?,
+
+			I::IFEQ(_)
+			| I::IFNE(_)
+			| I::IFLT(_)
+			| I::IFGE(_)
+			| I::IFGT(_)
+			| I::IFLE(_)
+			| I::TABLESWITCH { .. }
+			| I::LOOKUPSWITCH { .. } => {
+				self.pop(K::Int)?;
+			}
+			I::IF_ICMPEQ(_)
+			| I::IF_ICMPNE(_)
+			| I::IF_ICMPLT(_)
+			| I::IF_ICMPGE(_)
+			| I::IF_ICMPGT(_)
+			| I::IF_ICMPLE(_) => self.pop_n(K::Int, 2)?,
+			I::IF_ACMPEQ(_) | I::IF_ACMPNE(_) => self.pop_n(K::Reference, 2)?,
+			I::IFNULL(_) | I::IFNONNULL(_) | I::MONITORENTER | I::MONITOREXIT | I::ATHROW | I::ARETURN => {
+				self.pop(K::Reference)?;
+			}
+			I::IRETURN => {
+				self.pop(K::Int)?;
+			}
+			I::LRETURN => {
+				self.pop(K::Long)?;
+			}
+			I::FRETURN => {
+				self.pop(K::Float)?;
+			}
+			I::DRETURN => {
+				self.pop(K::Double)?;
+			}
+			I::JSR(_) | I::JSR_W(_) => self.push(K::ReturnAddress),
+			I::RET(index) => {
+				self.use_local(*index, K::ReturnAddress)?;
+				let found = self.state.locals.get(*index as usize).copied().unwrap_or(K::Top);
+				if found != K::ReturnAddress {
+					return Err(self.mismatch(K::ReturnAddress.name(), found));
+				}
+			}
+
+			I::GETSTATIC(field) => {
+				let kind = self.field(&field.name_and_ty.ty.data)?;
+				self.push(kind);
+			}
+			I::PUTSTATIC(field) => {
+				let kind = self.field(&field.name_and_ty.ty.data)?;
+				self.pop(kind)?;
+			}
+			I::GETFIELD(field) => {
+				let kind = self.field(&field.name_and_ty.ty.data)?;
+				self.convert(K::Reference, kind)?;
+			}
+			I::PUTFIELD(field) => {
+				let kind = self.field(&field.name_and_ty.ty.data)?;
+				self.pop(kind)?;
+				self.pop(K::Reference)?;
+			}
+			I::INVOKEVIRTUAL(method) => {
+				self.invoke(&method.name_and_ty.ty.data, true)?;
+			}
+			I::INVOKESTATIC(method) => {
+				self.invoke(&method.name_and_ty.ty.data, false)?;
+			}
+			I::INVOKEINTERFACE { method, .. } => {
+				self.invoke(&method.name_and_ty.ty.data, true)?;
+			}
+			I::INVOKEDYNAMIC(call_site) => {
+				self.invoke(&call_site.name_and_ty.ty.data, false)?;
+			}
+			I::INVOKESPECIAL(method) => {
+				let receiver = self.invoke(&method.name_and_ty.ty.data, true)?;
+				if method.name_and_ty.name.data.as_str() == "<init>" {
+					match receiver {
+						Some(receiver @ (K::UninitializedThis | K::Uninitialized(_))) => {
+							for slot in self.state.locals.iter_mut().chain(self.state.stack.iter_mut()) {
+								if *slot == receiver {
+									*slot = K::Reference;
+								}
+							}
+						}
+						Some(found) => return Err(self.mismatch("uninitialized reference", found)),
+						None => unreachable!("invokespecial has a receiver"),
+					}
+				}
+			}
+
+			I::NEW(_) => self.push(K::Uninitialized(self.pc)),
+			I::NEWARRAY(_) | I::ANEWARRAY(_) => self.convert(K::Int, K::Reference)?,
+			I::MULTIANEWARRAY { dimensions, .. } => {
+				self.pop_n(K::Int, *dimensions as usize)?;
+				self.push(K::Reference);
+			}
+			I::ARRAYLENGTH | I::INSTANCEOF(_) => self.convert(K::Reference, K::Int)?,
+			I::CHECKCAST(_) => self.convert(K::Reference, K::Reference)?,
+		}
+		Ok(())
+	}
+
+	fn array_load(&mut self, element: ValueKind) -> Result<(), IRClassfileError> {
+		self.pop(ValueKind::Int)?;
+		self.convert(ValueKind::Reference, element)
+	}
+
+	fn array_store(&mut self, element: ValueKind) -> Result<(), IRClassfileError> {
+		self.pop(element)?;
+		self.pop(ValueKind::Int)?;
+		self.pop(ValueKind::Reference)?;
+		Ok(())
+	}
+
+	fn compare(&mut self, kind: ValueKind) -> Result<(), IRClassfileError> {
+		self.pop_n(kind, 2)?;
+		self.push(ValueKind::Int);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use smallvec::SmallVec;
+
+	use super::*;
+	use crate::{attribute::IRAttribute, builder::ClassBuilder, flags::MethodAccessFlags};
+
+	fn method(descriptor: &str, max_stack: u16, code: &[u8]) -> (Vec<IRCpTag>, IRMethodInfo) {
+		let mut builder = ClassBuilder::new("p/C").unwrap();
+		let code = CodeAttribute {
+			max_stack,
+			max_locals: 3,
+			code: code.to_vec(),
+			exception_table: SmallVec::new(),
+			attributes: Default::default(),
+		};
+		builder
+			.method(MethodAccessFlags::STATIC, "m", descriptor, [IRAttribute::Code(code)])
+			.unwrap();
+		let mut class = builder.build();
+		(class.cp, class.methods.remove(0))
+	}
+
+	#[test]
+	fn depths_and_errors() {
+		// static long m(long a, int b) { return b > 0 ? a : -a; } with the long dup2'd around
+		// 0: lload_0, 1: dup2, 2: iload_2, 3: ifle +5, 6: pop2, 7: lreturn, 8: pop2, 9: lneg, 10: lreturn
+		let (cp, m) = method(
+			"(JI)J",
+			5,
+			&[0x1E, 0x5C, 0x1C, 0x9E, 0, 5, 0x58, 0xAD, 0x58, 0x75, 0xAD],
+		);
+		let simulation = simulate(&cp, "p/C", &m).unwrap().unwrap();
+		assert_eq!((simulation.max_stack(), simulation.max_locals()), (5, 3));
+		let state = simulation.state_at(3).unwrap();
+		assert_eq!(state.stack, [ValueKind::Long, ValueKind::Long, ValueKind::Int]);
+		assert_eq!(state.locals, [ValueKind::Long, ValueKind::Top, ValueKind::Int]);
+		assert_eq!(simulation.state_at(8).unwrap().depth(), 4);
+		assert_eq!(compute_maxs(&cp, "p/C", &m).unwrap(), Some((5, 3)));
+
+		let (cp, m) = method(
+			"(JI)J",
+			4,
+			&[0x1E, 0x5C, 0x1C, 0x9E, 0, 5, 0x58, 0xAD, 0x58, 0x75, 0xAD],
+		);
+		assert_eq!(
+			simulate(&cp, "p/C", &m).unwrap_err().to_string(),
+			"Operand stack deeper than max_stack 4 at pc 2"
+		);
+		// iconst_1, iadd
+		let (cp, m) = method("()I", 2, &[0x04, 0x60, 0xAC]);
+		assert!(matches!(
+			simulate(&cp, "p/C", &m),
+			Err(IRClassfileError::StackUnderflow { pc: 1 })
+		));
+		// fconst_0, iconst_1, iadd
+		let (cp, m) = method("()I", 2, &[0x0B, 0x04, 0x60, 0xAC]);
+		assert!(matches!(
+			simulate(&cp, "p/C", &m),
+			Err(IRClassfileError::StackTypeMismatch {
+				pc: 2,
+				expected: "int",
+				found: "float"
+			})
+		));
+		// iconst_1, lload_0, swap: half of a long
+		let (cp, m) = method("(J)V", 3, &[0x04, 0x1E, 0x5F, 0xB1]);
+		assert!(matches!(
+			simulate(&cp, "p/C", &m),
+			Err(IRClassfileError::StackTypeMismatch { pc: 2, .. })
+		));
+		// iconst_0, istore 3, iload 3 with max_locals 3
+		let (cp, m) = method("()I", 1, &[0x03, 0x36, 3, 0x15, 3, 0xAC]);
+		assert!(matches!(
+			simulate(&cp, "p/C", &m),
+			Err(IRClassfileError::LocalOutOfRange {
+				pc: 1,
+				index: 3,
+				max_locals: 3
+			})
+		));
+		assert_eq!(compute_maxs(&cp, "p/C", &m).unwrap(), Some((1, 4)));
+	}
+}
@@ -0,0 +1,207 @@
+// Reading JARs, or any zip of classes. Entries are read eagerly, in archive order, directories left out.
+//
+// A zip file can hold several entries with the same name, and real-world jars do, usually from careless shading or
+// repackaging. Which one wins differs between tools, so it's up to the caller: `DuplicatePolicy` picks, and the
+// duplicates are reported either way. This is also why zip is pinned to 0.6, later versions collapse duplicates when
+// reading the central directory.
+
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::{self, BufReader, Read, Seek},
+	path::Path,
+};
+
+use thiserror::Error;
+use zip::{result::ZipError, ZipArchive};
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+	#[error("{0}")]
+	IO(#[from] io::Error),
+	#[error("{0}")]
+	Zip(#[from] ZipError),
+	#[error("Duplicate archive entry: {0}")]
+	DuplicateEntry(String),
+}
+
+/// Which of several entries with the same name to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DuplicatePolicy {
+	FirstWins,
+	/// The entry kept is where the last one was in the archive.
+	LastWins,
+	/// Fail with [`ArchiveError::DuplicateEntry`] naming the first duplicate.
+	Error,
+	/// Keep every entry. Lookups by name see the first one.
+	CollectAll,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+	pub name: String,
+	pub data: Vec<u8>,
+}
+
+impl ArchiveEntry {
+	pub fn is_class(&self) -> bool {
+		self.name.ends_with(".class")
+	}
+}
+
+/// A name that occurs more than once in an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateEntry {
+	pub name: String,
+	pub occurrences: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Archive {
+	entries: Vec<ArchiveEntry>,
+	// in order of first occurrence
+	duplicates: Vec<DuplicateEntry>,
+}
+
+impl Archive {
+	pub fn open(path: &Path, policy: DuplicatePolicy) -> Result<Self, ArchiveError> {
+		Self::read(BufReader::new(File::open(path)?), policy)
+	}
+
+	pub fn read<R: Read + Seek>(reader: R, policy: DuplicatePolicy) -> Result<Self, ArchiveError> {
+		let mut zip = ZipArchive::new(reader)?;
+		let mut entries = Vec::with_capacity(zip.len());
+		for i in 0..zip.len() {
+			let mut file = zip.by_index(i)?;
+			if file.is_dir() {
+				continue;
+			}
+			let mut data = Vec::with_capacity(file.size() as usize);
+			file.read_to_end(&mut data)?;
+			entries.push(ArchiveEntry {
+				name: file.name().to_string(),
+				data,
+			});
+		}
+
+		let mut occurrences = HashMap::<String, usize>::new();
+		for entry in &entries {
+			*occurrences.entry(entry.name.clone()).or_default() += 1;
+		}
+
+		let mut seen = HashMap::<String, usize>::new();
+		let mut duplicates = Vec::new();
+		let mut kept = Vec::with_capacity(entries.len());
+		for entry in entries {
+			let total = occurrences[&entry.name];
+			let seen = seen.entry(entry.name.clone()).or_default();
+			*seen += 1;
+			if total > 1 && *seen == 1 {
+				if policy == DuplicatePolicy::Error {
+					return Err(ArchiveError::DuplicateEntry(entry.name));
+				}
+				duplicates.push(DuplicateEntry {
+					name: entry.name.clone(),
+					occurrences: total,
+				});
+			}
+
+			let keep = match policy {
+				DuplicatePolicy::FirstWins => *seen == 1,
+				DuplicatePolicy::LastWins => *seen == total,
+				DuplicatePolicy::Error | DuplicatePolicy::CollectAll => true,
+			};
+			if keep {
+				kept.push(entry);
+			}
+		}
+
+		Ok(Self {
+			entries: kept,
+			duplicates,
+		})
+	}
+
+	/// The kept entries, in archive order.
+	pub fn entries(&self) -> &[ArchiveEntry] {
+		&self.entries
+	}
+
+	pub fn classes(&self) -> impl Iterator<Item = &ArchiveEntry> {
+		self.entries.iter().filter(|entry| entry.is_class())
+	}
+
+	pub fn get(&self, name: &str) -> Option<&ArchiveEntry> {
+		self.entries.iter().find(|entry| entry.name == name)
+	}
+
+	/// Every kept entry called `name`, more than one only with [`DuplicatePolicy::CollectAll`].
+	pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a ArchiveEntry> {
+		self.entries.iter().filter(move |entry| entry.name == name)
+	}
+
+	/// Names that occur more than once in the archive, whatever the policy kept.
+	pub fn duplicates(&self) -> &[DuplicateEntry] {
+		&self.duplicates
+	}
+
+	/// The duplicates that are classes, which is what can change behavior depending on who reads the jar.
+	pub fn duplicate_classes(&self) -> impl Iterator<Item = &DuplicateEntry> {
+		self.duplicates
+			.iter()
+			.filter(|duplicate| duplicate.name.ends_with(".class"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Cursor, Write};
+
+	use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+	use super::*;
+
+	#[test]
+	fn duplicate_policies() {
+		let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+		let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+		for (name, data) in [("a/A.class", b"1"), ("META-INF/MANIFEST.MF", b"m"), ("a/A.class", b"2")] {
+			zip.start_file(name, options).unwrap();
+			zip.write_all(data).unwrap();
+		}
+		let bytes = zip.finish().unwrap().into_inner();
+		let read = |policy| Archive::read(Cursor::new(&bytes), policy);
+
+		let first = read(DuplicatePolicy::FirstWins).unwrap();
+		assert_eq!(first.get("a/A.class").unwrap().data, b"1");
+		assert_eq!(first.entries().len(), 2);
+		assert_eq!(
+			first.duplicate_classes().collect::<Vec<_>>(),
+			[&DuplicateEntry {
+				name: "a/A.class".to_string(),
+				occurrences: 2,
+			}]
+		);
+
+		let last = read(DuplicatePolicy::LastWins).unwrap();
+		assert_eq!(last.get("a/A.class").unwrap().data, b"2");
+		let names = |archive: &Archive| {
+			archive
+				.entries()
+				.iter()
+				.map(|entry| entry.name.clone())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(names(&first), ["a/A.class", "META-INF/MANIFEST.MF"]);
+		assert_eq!(names(&last), ["META-INF/MANIFEST.MF", "a/A.class"]);
+
+		let all = read(DuplicatePolicy::CollectAll).unwrap();
+		assert_eq!(all.get_all("a/A.class").count(), 2);
+		assert_eq!(all.classes().count(), 2);
+
+		assert!(matches!(
+			read(DuplicatePolicy::Error),
+			Err(ArchiveError::DuplicateEntry(name)) if name == "a/A.class"
+		));
+	}
+}
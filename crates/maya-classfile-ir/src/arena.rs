@@ -0,0 +1,321 @@
+// Decoding instructions and attributes into an arena, for tools going over every class of a large classpath. Each
+// `Instructions::read_all` allocates a list of its own and grows it as it goes, and once a method is done with it's
+// freed again, thousands of times over for a jar. Here lists are bump-allocated from chunks the arena keeps, and
+// `reset` between classes or methods makes their space free for the next without giving it back to the allocator.
+//
+// Only the lists live in the arena. What an instruction holds, a constant or the targets of a switch, is as it is in
+// the IR and dropped with the list.
+//
+// `ParseArena` does the same for parsing a class. `IRClassFile::read` first reads the class into an `IOClassFile`,
+// copying out every attribute into a buffer of its own and collecting them in a list per member, and then decodes the
+// attributes from those. Here the lists are in the arena and the attributes are decoded straight from the class bytes.
+// What's decoded is the IR as always, it owns what it holds and outlives the parse.
+
+use std::io::Cursor;
+
+use bumpalo::{collections::Vec as ArenaVec, Bump};
+use maya_bytes::{BytesError, BytesReadExt};
+use maya_classfile_io::{class_pool::IOCpTag, limits::Limits, IOClassFile, IOClassfileError};
+
+use crate::{
+	attribute::{Attributes, IRAttributeInfo},
+	class_pool::{CPUtf8Ref, IRClassfileError, IRCpTag},
+	code::Instructions,
+	flags::{FieldAccessFlags, MethodAccessFlags},
+	parse::{capacity, ParseContext, ParseOptions, ParseWarning},
+	IRClassFile, IRFieldInfo, IRMethodInfo,
+};
+
+/// Instructions by their offset in the code, in an arena.
+pub type ArenaInstructions<'a> = ArenaVec<'a, (u32, Instructions)>;
+
+#[derive(Debug, Default)]
+pub struct InstructionArena {
+	bump: Bump,
+}
+
+impl InstructionArena {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// With room for `bytes` before it needs another chunk.
+	pub fn with_capacity(bytes: usize) -> Self {
+		Self {
+			bump: Bump::with_capacity(bytes),
+		}
+	}
+
+	/// Like `Instructions::read_all`, the list allocated in the arena.
+	pub fn read_all<'a>(&'a self, cp: &[IRCpTag], code: &[u8]) -> Result<ArenaInstructions<'a>, IRClassfileError> {
+		// Instructions average about two bytes, growing the list leaves the old one behind in the arena.
+		let mut out = ArenaVec::with_capacity_in(code.len() / 2 + 1, &self.bump);
+		let mut buffer = std::io::Cursor::new(code);
+		while (buffer.position() as usize) < code.len() {
+			let pc = buffer.position() as u32;
+			out.push((pc, Instructions::read(cp, &mut buffer)?));
+		}
+		Ok(out)
+	}
+
+	/// Frees everything allocated for reuse. The lists must be gone, which the borrow checker sees to.
+	pub fn reset(&mut self) {
+		self.bump.reset();
+	}
+
+	/// The size of the chunks the arena holds, used or not.
+	pub fn allocated_bytes(&self) -> usize {
+		self.bump.allocated_bytes()
+	}
+}
+
+/// Parses classes with the member and attribute tables in an arena, see `ParseArena::read_with`.
+#[derive(Debug, Default)]
+pub struct ParseArena {
+	bump: Bump,
+}
+
+impl ParseArena {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Like `IRClassFile::read_with`. The arena is reset after each class, its chunks are kept for the next one.
+	pub fn read_with(
+		&mut self,
+		bytes: &[u8],
+		options: &ParseOptions,
+	) -> Result<(IRClassFile, Vec<ParseWarning>), IRClassfileError> {
+		let class = read_class(&self.bump, bytes, options);
+		self.bump.reset();
+		class
+	}
+
+	/// The size of the chunks the arena holds.
+	pub fn allocated_bytes(&self) -> usize {
+		self.bump.allocated_bytes()
+	}
+}
+
+struct RawAttribute<'a> {
+	name: u16,
+	length: u32,
+	info: &'a [u8],
+}
+
+struct RawMember<'a> {
+	access_flags: u16,
+	name: u16,
+	descriptor: u16,
+	attributes: ArenaVec<'a, RawAttribute<'a>>,
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.1
+fn read_class<'a>(
+	bump: &'a Bump,
+	bytes: &'a [u8],
+	options: &ParseOptions,
+) -> Result<(IRClassFile, Vec<ParseWarning>), IRClassfileError> {
+	let mut reader = Cursor::new(bytes);
+	let magic = reader.read_u32()?;
+	if magic != 0xCAFEBABE {
+		return Err(IOClassfileError::InvalidMagic.into());
+	}
+	let minor_version = reader.read_u16()?;
+	let major_version = reader.read_u16()?;
+	let cp_count = reader.read_u16()?;
+	Limits::check(
+		"constant pool entries",
+		options.limits.max_cp_entries.into(),
+		cp_count.saturating_sub(1).into(),
+	)
+	.map_err(IOClassfileError::from)?;
+	let mut cp = Vec::with_capacity(capacity(&mut reader, (cp_count as usize).saturating_sub(1))?);
+	while cp.len() + 1 < cp_count as usize {
+		let tag = IOCpTag::read(&mut reader)?;
+		let wide = tag.is_wide();
+		cp.push(tag);
+		if wide {
+			cp.push(IOCpTag::Unusable);
+		}
+	}
+	let access_flags = reader.read_u16()?;
+	let this_class = reader.read_u16()?;
+	let super_class = reader.read_u16()?;
+	let interface_count = reader.read_u16()?;
+	let interfaces = (0..interface_count)
+		.map(|_| reader.read_u16())
+		.collect::<Result<Vec<_>, _>>()?;
+	let fields = read_members(bump, &mut reader)?;
+	let methods = read_members(bump, &mut reader)?;
+	let attributes = read_attributes(bump, &mut reader)?;
+
+	// The header and the pool link the same as for any other class, the members are decoded from the arena below.
+	let header = IOClassFile {
+		magic,
+		minor_version,
+		major_version,
+		cp_count,
+		cp,
+		access_flags,
+		this_class,
+		super_class,
+		interface_count,
+		interfaces,
+		field_count: 0,
+		fields: Vec::new(),
+		method_count: 0,
+		methods: Vec::new(),
+		attribute_count: 0,
+		attributes: Vec::new(),
+	};
+	let (mut class, mut warnings) = IRClassFile::from_io_with(header, options)?;
+
+	let mut ctx = ParseContext::new(&class.cp, options).with_version(class.version);
+	let fields = fields
+		.iter()
+		.map(|field| {
+			Ok(IRFieldInfo {
+				access_flags: FieldAccessFlags::from_bits_retain(field.access_flags),
+				name: CPUtf8Ref::from_cp(ctx.cp, field.name)?,
+				descriptor: CPUtf8Ref::from_cp(ctx.cp, field.descriptor)?,
+				attributes: decode_attributes(&mut ctx, &field.attributes)?,
+			})
+		})
+		.collect::<Result<Vec<_>, IRClassfileError>>()?;
+	let methods = methods
+		.iter()
+		.map(|method| {
+			Ok(IRMethodInfo {
+				access_flags: MethodAccessFlags::from_bits_retain(method.access_flags),
+				name: CPUtf8Ref::from_cp(ctx.cp, method.name)?,
+				descriptor: CPUtf8Ref::from_cp(ctx.cp, method.descriptor)?,
+				attributes: decode_attributes(&mut ctx, &method.attributes)?,
+			})
+		})
+		.collect::<Result<Vec<_>, IRClassfileError>>()?;
+	let attributes = decode_attributes(&mut ctx, &attributes)?;
+	warnings.extend(ctx.warnings);
+
+	class.fields = fields;
+	class.methods = methods;
+	class.attributes = attributes;
+	Ok((class, warnings))
+}
+
+fn read_members<'a>(
+	bump: &'a Bump,
+	reader: &mut Cursor<&'a [u8]>,
+) -> Result<ArenaVec<'a, RawMember<'a>>, IRClassfileError> {
+	let count = reader.read_u16()? as usize;
+	let mut members = ArenaVec::with_capacity_in(capacity(reader, count)?, bump);
+	for _ in 0..count {
+		members.push(RawMember {
+			access_flags: reader.read_u16()?,
+			name: reader.read_u16()?,
+			descriptor: reader.read_u16()?,
+			attributes: read_attributes(bump, reader)?,
+		});
+	}
+	Ok(members)
+}
+
+fn read_attributes<'a>(
+	bump: &'a Bump,
+	reader: &mut Cursor<&'a [u8]>,
+) -> Result<ArenaVec<'a, RawAttribute<'a>>, IRClassfileError> {
+	let count = reader.read_u16()? as usize;
+	let mut attributes = ArenaVec::with_capacity_in(capacity(reader, count)?, bump);
+	for _ in 0..count {
+		let name = reader.read_u16()?;
+		let length = reader.read_u32()?;
+		let bytes: &'a [u8] = reader.get_ref();
+		let start = reader.position() as usize;
+		let end = start.checked_add(length as usize).ok_or(BytesError::NotEnoughData)?;
+		let info = bytes.get(start..end).ok_or(BytesError::NotEnoughData)?;
+		reader.set_position(end as u64);
+		attributes.push(RawAttribute { name, length, info });
+	}
+	Ok(attributes)
+}
+
+fn decode_attributes(ctx: &mut ParseContext, raw: &[RawAttribute]) -> Result<Attributes, IRClassfileError> {
+	raw.iter()
+		.map(|attr| IRAttributeInfo::from_payload(ctx, attr.name, attr.length, attr.info))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn decode_into_the_arena() {
+		let class = assemble(
+			".class public super p/Main\n.method public static run(I)I\niload_0\ntableswitch 0\nZero\ndefault: \
+			 Other\nZero:\nldc \"zero\"\npop\niconst_0\nireturn\nOther:\niload_0\nireturn\n.end method",
+		)
+		.unwrap();
+		let code = &class.methods[0].code().unwrap().code;
+
+		let mut arena = InstructionArena::new();
+		let decoded = arena.read_all(&class.cp, code).unwrap();
+		let expected = Instructions::read_all(&class.cp, code).unwrap();
+		assert_eq!(format!("{:?}", &decoded[..]), format!("{:?}", &expected[..]));
+		drop(decoded);
+		let allocated = arena.allocated_bytes();
+
+		// The same space again after a reset.
+		for _ in 0..100 {
+			arena.reset();
+			arena.read_all(&class.cp, code).unwrap();
+		}
+		assert_eq!(arena.allocated_bytes(), allocated);
+		assert!(arena.read_all(&class.cp, &code[..2]).is_err());
+	}
+
+	#[test]
+	fn parse_in_the_arena() {
+		let mut class = assemble(
+			".class public super p/Main\n.field private static count I\n.method public static run(I)I\niload_0\n\
+			 ifeq Zero\nldc \"one\"\npop\niconst_1\nireturn\nZero:\niconst_0\nireturn\n.end method\n.method public \
+			 abstract stop()V\n.end method",
+		)
+		.unwrap();
+		let signature = CPUtf8Ref::find_or_add(&mut class.cp, "Signature").unwrap();
+		let mut raw = class.to_io().unwrap();
+		// A signature pointing at pool index 0, recovered when lenient.
+		raw.attributes.push(maya_classfile_io::IOAttributeInfo {
+			attribute_name_index: signature.index,
+			attribute_length: 2,
+			info: vec![0, 0],
+		});
+		raw.attribute_count += 1;
+		let mut bytes = Vec::new();
+		raw.write(&mut bytes).unwrap();
+
+		let mut arena = ParseArena::new();
+		let lenient = ParseOptions::lenient();
+		let (parsed, warnings) = arena.read_with(&bytes, &lenient).unwrap();
+		let (expected, expected_warnings) = IRClassFile::read_with(&bytes, &lenient).unwrap();
+		assert_eq!(format!("{parsed:?}"), format!("{expected:?}"));
+		assert_eq!(format!("{warnings:?}"), format!("{expected_warnings:?}"));
+		assert_eq!(warnings.len(), 1);
+		assert_eq!(parsed.to_bytes().unwrap(), bytes);
+
+		// Strict, the same class doesn't parse either way.
+		assert!(matches!(
+			arena.read_with(&bytes, &ParseOptions::default()),
+			Err(IRClassfileError::InvalidCpIndex(0))
+		));
+		let allocated = arena.allocated_bytes();
+		for _ in 0..100 {
+			arena.read_with(&bytes, &lenient).unwrap();
+		}
+		assert_eq!(arena.allocated_bytes(), allocated);
+		for end in [3, 9, bytes.len() / 2, bytes.len() - 1] {
+			assert!(arena.read_with(&bytes[..end], &lenient).is_err(), "read {end} bytes");
+		}
+	}
+}
@@ -0,0 +1,1335 @@
+// A textual assembler, the inverse of `disasm`. The syntax is Jasmin's with a few liberties: labels instead of
+// offsets, constants written out instead of pool indices, one directive or instruction per line and `;` starting a
+// comment outside of strings.
+//
+//   .version 52 0
+//   .class public super p/Hello
+//   .super java/lang/Object
+//   .source "Hello.java"
+//
+//   .field private static final GREETING Ljava/lang/String; = "hello"
+//
+//   .method public static main([Ljava/lang/String;)V
+//     .line 3
+//     getstatic java/lang/System/out Ljava/io/PrintStream;
+//     ldc "hello"
+//     invokevirtual java/io/PrintStream/println(Ljava/lang/String;)V
+//     return
+//   .end method
+//
+// Class directives: `.version major [minor]`, `.class flags name`, `.super`, `.implements`, `.source`, `.signature`,
+// `.deprecated`, `.synthetic`, `.debug` for the SourceDebugExtension, `.inner flags inner outer|none name|none`,
+// `.enclosing class outer` or `.enclosing method outer name(args)ret`, `.nesthost`, `.nestmember`,
+// `.permittedsubclass` and `.component name descriptor` for a record. They come before the members. A field is
+// `.field flags name descriptor [= constant]`, followed by its attributes and `.end field` if it has any. A method is
+// `.method flags name(args)ret` up to `.end method`, with `.limit stack|locals n`, `.throws`, `.parameter flags
+// [name]`, and its code: `label:`, instructions, `.line n`, `.catch class|all from start to end using handler` and
+// `.var`/`.vartype n is name descriptor from start to end`.
+//
+// Annotations are `.annotation visible|invisible descriptor`, `.annotation visibleparam|invisibleparam n descriptor`
+// or `.annotation default`, one `name value` per line up to `.end annotation`. Values are a tag and what it tags,
+// `I 5`, `s "text"`, `e Lp/Kind; NAME`, `c Lp/C;`, `@ Lp/A; { name value ... }` or `[ value ... ]`. Any other
+// attribute, e.g. a Module or type annotations, can be given as its raw payload with `.attribute Name hex`.
+//
+// Field instructions take `owner/name descriptor`, invokes `owner/name(args)ret`. `invokedynamic` takes the call
+// site's `name(args)ret`, the bootstrap method handle and its static arguments. Constants are `5`, `5L`, `1.5f`,
+// `1.5` or `1.5d`, `"text"`, `class name`, `methodtype (args)ret` and `handle kind reference`, with the kind named
+// after the instruction it stands for, e.g. `handle invokestatic p/C/run()V` or `handle getfield p/C/x I`.
+//
+// Short and wide forms are picked from the operands, and max_stack and max_locals are computed unless `.limit` gives
+// them. Frames aren't, classes that need a StackMapTable can get one from `analysis::frames::recompute_frames`.
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	io::Cursor,
+	iter::Peekable,
+	str::Chars,
+};
+
+use bitflags::Flags;
+use smallvec::SmallVec;
+
+use crate::{
+	analysis::stack::compute_maxs,
+	attribute::{
+		Attributes, BootstrapMethodsMethod, ConstantValueAttribute, IRAttribute, IRAttributeInfo,
+		InnerClassesAttribute, InnerClassesAttributeClass, MethodParametersParam, RecordComponentInfo,
+		RuntimeAnnotation, RuntimeAnnotationEVPair, RuntimeAnnotationValue,
+	},
+	builder::ClassBuilder,
+	class_pool::{
+		cp_find_or_add, CPClassRef, CPConstValueRef, CPConstValueRefKind, CPFieldRef, CPInterfaceMethodRef,
+		CPInvokeDynamicRef, CPMethodHandleRef, CPMethodRef, CPNameAndTypeRef, CPTagRef, CPUtf8Ref, IRClassfileError,
+		IRCpTag, IRMethodRefKind,
+	},
+	code::{Instructions, Opcodes},
+	descriptor::MethodDescriptor,
+	disasm::array_type,
+	flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags, ParameterAccessFlags},
+	listing::{Item, LabelId, Listing, ListingHandler, ListingLocal},
+	ClassFileVersion, IRClassFile, IRFieldInfo, IRMethodInfo, Shared,
+};
+
+/// Assembles the class in `source`.
+pub fn assemble(source: &str) -> Result<IRClassFile, IRClassfileError> {
+	let lines = tokenize(source)?;
+	let mut lines = lines.iter();
+	let mut version = None;
+	let (builder, class_name) = loop {
+		let Some(line) = lines.next() else {
+			return Err(error(source.lines().count(), "expected .class"));
+		};
+		let mut tokens = line.tokens();
+		match tokens.word("directive")? {
+			".version" => version = Some(tokens.version()?),
+			".class" => {
+				let access_flags = tokens.flags::<ClassAccessFlags>();
+				let name = tokens.word("class name")?;
+				tokens.end()?;
+				let mut builder = ClassBuilder::new(name).map_err(|err| located(line.number, err))?;
+				builder.access_flags(access_flags);
+				break (builder, name.to_string());
+			}
+			directive => return Err(tokens.error(format!("expected .class, found {directive}"))),
+		}
+	};
+
+	let mut assembler = Assembler {
+		lines: lines.as_slice(),
+		class_name,
+		builder,
+		fields: Vec::new(),
+		methods: Vec::new(),
+		attributes: Attributes::new(),
+		bootstrap_methods: Vec::new(),
+	};
+	if let Some(version) = version {
+		assembler.builder.version(version);
+	}
+	assembler.class()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+	Word(String),
+	// a quoted string, unescaped
+	Str(String),
+}
+
+#[derive(Debug)]
+pub(crate) struct Line {
+	pub(crate) number: usize,
+	pub(crate) tokens: Vec<Token>,
+}
+
+impl Line {
+	fn tokens(&self) -> Tokens<'_> {
+		Tokens {
+			line: self.number,
+			tokens: &self.tokens,
+			pos: 0,
+		}
+	}
+}
+
+struct Tokens<'a> {
+	line: usize,
+	tokens: &'a [Token],
+	pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+	fn error(&self, message: impl Into<String>) -> IRClassfileError {
+		error(self.line, message)
+	}
+
+	fn peek_word(&self) -> Option<&'a str> {
+		match self.tokens.get(self.pos) {
+			Some(Token::Word(word)) => Some(word),
+			_ => None,
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		self.pos == self.tokens.len()
+	}
+
+	fn found(&self) -> String {
+		match self.tokens.get(self.pos) {
+			Some(Token::Word(word)) => word.clone(),
+			Some(Token::Str(string)) => format!("{string:?}"),
+			None => "end of line".to_string(),
+		}
+	}
+
+	fn word(&mut self, what: &str) -> Result<&'a str, IRClassfileError> {
+		let word = self
+			.peek_word()
+			.ok_or_else(|| self.error(format!("expected {what}, found {}", self.found())))?;
+		self.pos += 1;
+		Ok(word)
+	}
+
+	fn string(&mut self, what: &str) -> Result<&'a str, IRClassfileError> {
+		match self.tokens.get(self.pos) {
+			Some(Token::Str(string)) => {
+				self.pos += 1;
+				Ok(string)
+			}
+			_ => Err(self.error(format!("expected {what} as a quoted string, found {}", self.found()))),
+		}
+	}
+
+	/// A quoted string or a single word.
+	fn text(&mut self, what: &str) -> Result<&'a str, IRClassfileError> {
+		match self.tokens.get(self.pos) {
+			Some(Token::Str(_)) => self.string(what),
+			_ => self.word(what),
+		}
+	}
+
+	fn keyword(&mut self, keyword: &str) -> Result<(), IRClassfileError> {
+		match self.peek_word() == Some(keyword) {
+			true => {
+				self.pos += 1;
+				Ok(())
+			}
+			false => Err(self.error(format!("expected {keyword}, found {}", self.found()))),
+		}
+	}
+
+	fn end(&self) -> Result<(), IRClassfileError> {
+		match self.is_empty() {
+			true => Ok(()),
+			false => Err(self.error(format!("unexpected {}", self.found()))),
+		}
+	}
+
+	fn number<T: TryFrom<i64>>(&mut self, what: &str) -> Result<T, IRClassfileError> {
+		let word = self.word(what)?;
+		parse_int(word)
+			.and_then(|value| T::try_from(value).ok())
+			.ok_or_else(|| self.error(format!("expected {what}, found {word}")))
+	}
+
+	/// Flag keywords, as long as there are any.
+	fn flags<F: Flags>(&mut self) -> F {
+		let mut flags = F::empty();
+		while let Some(flag) = self.peek_word().and_then(|word| F::from_name(&word.to_uppercase())) {
+			flags.insert(flag);
+			self.pos += 1;
+		}
+		flags
+	}
+
+	fn version(&mut self) -> Result<ClassFileVersion, IRClassfileError> {
+		let major = self.number("major version")?;
+		let minor = match self.is_empty() {
+			true => 0,
+			false => self.number("minor version")?,
+		};
+		self.end()?;
+		Ok(ClassFileVersion { major, minor })
+	}
+}
+
+pub(crate) fn error(line: usize, message: impl Into<String>) -> IRClassfileError {
+	IRClassfileError::Assembly {
+		line,
+		message: message.into(),
+	}
+}
+
+// Any error gets the line it came from.
+fn located(line: usize, err: IRClassfileError) -> IRClassfileError {
+	match err {
+		IRClassfileError::Assembly { .. } => err,
+		err => error(line, err.to_string()),
+	}
+}
+
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Line>, IRClassfileError> {
+	let mut lines = Vec::new();
+	for (i, text) in source.lines().enumerate() {
+		let number = i + 1;
+		let mut tokens = Vec::new();
+		let mut chars = text.chars().peekable();
+		while let Some(&c) = chars.peek() {
+			match c {
+				';' => break,
+				'"' => {
+					chars.next();
+					tokens.push(Token::Str(
+						unescape(&mut chars).map_err(|message| error(number, message))?,
+					));
+				}
+				c if c.is_whitespace() => {
+					chars.next();
+				}
+				_ => {
+					let mut word = String::new();
+					while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+						word.push(c);
+					}
+					tokens.push(Token::Word(word));
+				}
+			}
+		}
+		if !tokens.is_empty() {
+			lines.push(Line { number, tokens });
+		}
+	}
+	Ok(lines)
+}
+
+// The rest of a string after its opening quote, with Java's escapes.
+fn unescape(chars: &mut Peekable<Chars>) -> Result<String, String> {
+	let mut value = String::new();
+	loop {
+		let c = chars.next().ok_or("unterminated string")?;
+		value.push(match c {
+			'"' => return Ok(value),
+			'\\' => match chars.next().ok_or("unterminated string")? {
+				'n' => '\n',
+				't' => '\t',
+				'r' => '\r',
+				'b' => '\u{8}',
+				'f' => '\u{c}',
+				'0' => '\0',
+				'u' => {
+					let hex = chars.by_ref().take(4).collect::<String>();
+					u32::from_str_radix(&hex, 16)
+						.ok()
+						.and_then(char::from_u32)
+						.ok_or_else(|| format!("invalid escape \\u{hex}"))?
+				}
+				c @ ('"' | '\'' | '\\') => c,
+				c => return Err(format!("invalid escape \\{c}")),
+			},
+			c => c,
+		});
+	}
+}
+
+// Decimal or 0x-prefixed hexadecimal.
+pub(crate) fn parse_int(word: &str) -> Option<i64> {
+	let (negative, digits) = match word.strip_prefix('-') {
+		Some(digits) => (true, digits),
+		None => (false, word),
+	};
+	let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+		Some(hex) => u64::from_str_radix(hex, 16).ok()? as i64,
+		None => digits.parse::<u64>().ok()? as i64,
+	};
+	Some(if negative { value.wrapping_neg() } else { value })
+}
+
+// `owner/name` and what follows it, `(args)ret` being split off first for methods.
+fn member(reference: &str) -> Option<(&str, &str)> {
+	reference.rsplit_once('/')
+}
+
+fn method_member(reference: &str) -> Option<(&str, &str, &str)> {
+	let (owner_and_name, descriptor) = reference.split_at(reference.find('(')?);
+	let (owner, name) = member(owner_and_name)?;
+	Some((owner, name, descriptor))
+}
+
+fn name_and_descriptor(word: &str) -> Option<(&str, &str)> {
+	Some(word.split_at(word.find('(')?))
+}
+
+fn hex_bytes(hex: &str) -> Option<Vec<u8>> {
+	if !hex.len().is_multiple_of(2) {
+		return None;
+	}
+	(0..hex.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+		.collect()
+}
+
+// Where an annotation directive is, which decides the kinds it may have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+	Class,
+	Field,
+	Method,
+}
+
+// The code of a method as it's read. Labels are made when first mentioned, binding them comes later.
+#[derive(Debug, Default)]
+struct Body<'a> {
+	listing: Listing,
+	labels: BTreeMap<&'a str, LabelId>,
+	bound: BTreeSet<&'a str>,
+	// every use, with its line, to report labels that are never bound
+	uses: Vec<(usize, &'a str)>,
+	max_stack: Option<u16>,
+	max_locals: Option<u16>,
+}
+
+impl<'a> Body<'a> {
+	fn label(&mut self, line: usize, name: &'a str) -> LabelId {
+		self.uses.push((line, name));
+		*self.labels.entry(name).or_insert_with(|| self.listing.new_label())
+	}
+
+	fn bind(&mut self, line: usize, name: &'a str) -> Result<(), IRClassfileError> {
+		if !self.bound.insert(name) {
+			return Err(error(line, format!("label {name} is bound twice")));
+		}
+		let label = *self.labels.entry(name).or_insert_with(|| self.listing.new_label());
+		self.listing.items.push(Item::Label(label));
+		Ok(())
+	}
+
+	fn is_empty(&self) -> bool {
+		self.listing.items.is_empty() && self.listing.handlers.is_empty()
+	}
+}
+
+struct Assembler<'a> {
+	lines: &'a [Line],
+	builder: ClassBuilder,
+	class_name: String,
+	fields: Vec<IRFieldInfo>,
+	methods: Vec<IRMethodInfo>,
+	attributes: Attributes,
+	bootstrap_methods: Vec<BootstrapMethodsMethod>,
+}
+
+impl<'a> Assembler<'a> {
+	fn next_line(&mut self) -> Option<&'a Line> {
+		let (line, rest) = self.lines.split_first()?;
+		self.lines = rest;
+		Some(line)
+	}
+
+	fn peek_directive(&self) -> Option<&'a str> {
+		self.lines.first().and_then(|line| line.tokens().peek_word())
+	}
+
+	fn class(mut self) -> Result<IRClassFile, IRClassfileError> {
+		let mut members = false;
+		while let Some(line) = self.next_line() {
+			let mut tokens = line.tokens();
+			let directive = tokens.word("directive")?;
+			let result = match directive {
+				".field" => {
+					members = true;
+					self.field(tokens)
+				}
+				".method" => {
+					members = true;
+					self.method(line.number, tokens)
+				}
+				_ if members => Err(tokens.error(format!("{directive} has to come before the fields and methods"))),
+				_ => self.class_directive(directive, tokens),
+			};
+			result.map_err(|err| located(line.number, err))?;
+		}
+
+		if !self.bootstrap_methods.is_empty() {
+			let methods = std::mem::take(&mut self.bootstrap_methods);
+			let attr = self.builder.attribute_info(IRAttribute::BootstrapMethods { methods })?;
+			self.attributes.push(attr);
+		}
+		let mut class = self.builder.build();
+		class.fields = self.fields;
+		class.methods = self.methods;
+		class.attributes = self.attributes;
+		Ok(class)
+	}
+
+	fn class_directive(&mut self, directive: &str, mut tokens: Tokens<'a>) -> Result<(), IRClassfileError> {
+		let attr = match directive {
+			".version" => {
+				let version = tokens.version()?;
+				self.builder.version(version);
+				return Ok(());
+			}
+			".super" => {
+				self.builder.super_class(Some(tokens.word("class name")?))?;
+				return tokens.end();
+			}
+			".implements" => {
+				self.builder.interface(tokens.word("interface name")?)?;
+				return tokens.end();
+			}
+			".source" => IRAttribute::SourceFile(self.utf8(tokens.text("source file")?)?),
+			".debug" => IRAttribute::SourceDebugExtension(Shared::new(tokens.string("debug extension")?.to_string())),
+			".inner" => {
+				let inner_class_access_flags = tokens.flags::<ClassAccessFlags>();
+				let inner_class_info = self.class_ref(tokens.word("inner class")?)?;
+				let outer_class_info = match tokens.word("outer class")? {
+					"none" => None,
+					outer => Some(self.class_ref(outer)?),
+				};
+				let inner_name = match tokens.word("inner name")? {
+					"none" => None,
+					name => Some(self.utf8(name)?),
+				};
+				IRAttribute::InnerClasses(InnerClassesAttribute {
+					classes: vec![InnerClassesAttributeClass {
+						inner_class_info,
+						outer_class_info,
+						inner_name,
+						inner_class_access_flags,
+					}],
+				})
+			}
+			".enclosing" => {
+				let kind = tokens.word("class or method")?;
+				let class = self.class_ref(tokens.word("enclosing class")?)?;
+				let method = match kind {
+					"class" => None,
+					"method" => {
+						let word = tokens.word("method name and descriptor")?;
+						let (name, descriptor) = name_and_descriptor(word)
+							.ok_or_else(|| tokens.error(format!("expected name(args)ret, found {word}")))?;
+						Some(CPNameAndTypeRef::find_or_add(self.builder.cp(), name, descriptor)?)
+					}
+					kind => return Err(tokens.error(format!("expected class or method, found {kind}"))),
+				};
+				IRAttribute::EnclosingMethod { class, method }
+			}
+			".nesthost" => IRAttribute::NestHost(self.class_ref(tokens.word("nest host")?)?),
+			".nestmember" => IRAttribute::NestMembers {
+				classes: vec![self.class_ref(tokens.word("nest member")?)?],
+			},
+			".permittedsubclass" => IRAttribute::PermittedSubclasses {
+				classes: vec![self.class_ref(tokens.word("permitted subclass")?)?],
+			},
+			".component" => IRAttribute::Record {
+				components: vec![RecordComponentInfo {
+					name: self.utf8(tokens.word("component name")?)?,
+					descriptor: self.utf8(tokens.word("component descriptor")?)?,
+					attributes: Attributes::new(),
+				}],
+			},
+			_ => {
+				let mut attributes = std::mem::take(&mut self.attributes);
+				let result = self.member_attribute(directive, tokens, &mut attributes, Target::Class);
+				self.attributes = attributes;
+				return result;
+			}
+		};
+		tokens.end()?;
+		let mut attributes = std::mem::take(&mut self.attributes);
+		let result = self.add(&mut attributes, attr);
+		self.attributes = attributes;
+		result
+	}
+
+	// The directives classes, fields and methods share.
+	fn member_attribute(
+		&mut self,
+		directive: &str,
+		mut tokens: Tokens<'a>,
+		attributes: &mut Attributes,
+		target: Target,
+	) -> Result<(), IRClassfileError> {
+		let attr = match directive {
+			".signature" => IRAttribute::Signature(self.utf8(tokens.text("signature")?)?),
+			".deprecated" => IRAttribute::Deprecated,
+			".synthetic" => IRAttribute::Synthetic,
+			".annotation" => return self.annotation(tokens, attributes, target),
+			".attribute" => {
+				let name = tokens.text("attribute name")?;
+				let payload = match tokens.is_empty() {
+					true => Vec::new(),
+					false => {
+						let hex = tokens.word("payload")?;
+						hex_bytes(hex).ok_or_else(|| tokens.error(format!("expected hex bytes, found {hex}")))?
+					}
+				};
+				tokens.end()?;
+				attributes.push(IRAttributeInfo {
+					name: self.utf8(name)?,
+					length: payload.len() as u32,
+					attr: IRAttribute::Unknown(payload),
+				});
+				return Ok(());
+			}
+			_ => return Err(tokens.error(format!("unknown directive {directive}"))),
+		};
+		tokens.end()?;
+		self.add(attributes, attr)
+	}
+
+	// Adds `attr`, into an attribute of the same kind if there's one, so repeated directives make a single attribute.
+	fn add(&mut self, attributes: &mut Attributes, attr: IRAttribute) -> Result<(), IRClassfileError> {
+		let existing = attributes.iter_mut().find(|existing| {
+			existing.name.data.as_str() == attr.name() && !matches!(existing.attr, IRAttribute::Unknown(_))
+		});
+		match (existing.map(|existing| &mut existing.attr), attr) {
+			(
+				Some(IRAttribute::Exceptions {
+					exception_index_table: to,
+				}),
+				IRAttribute::Exceptions {
+					exception_index_table: from,
+				},
+			)
+			| (Some(IRAttribute::NestMembers { classes: to }), IRAttribute::NestMembers { classes: from })
+			| (
+				Some(IRAttribute::PermittedSubclasses { classes: to }),
+				IRAttribute::PermittedSubclasses { classes: from },
+			) => to.extend(from),
+			(
+				Some(IRAttribute::RuntimeVisibleAnnotations { annotations: to }),
+				IRAttribute::RuntimeVisibleAnnotations { annotations: from },
+			)
+			| (
+				Some(IRAttribute::RuntimeInvisibleAnnotations { annotations: to }),
+				IRAttribute::RuntimeInvisibleAnnotations { annotations: from },
+			) => to.extend(from),
+			(
+				Some(IRAttribute::RuntimeVisibleParameterAnnotations { params: to }),
+				IRAttribute::RuntimeVisibleParameterAnnotations { params: from },
+			)
+			| (
+				Some(IRAttribute::RuntimeInvisibleParameterAnnotations { params: to }),
+				IRAttribute::RuntimeInvisibleParameterAnnotations { params: from },
+			) => {
+				if to.len() < from.len() {
+					to.resize(from.len(), Vec::new());
+				}
+				for (to, from) in to.iter_mut().zip(from) {
+					to.extend(from);
+				}
+			}
+			(Some(IRAttribute::InnerClasses(to)), IRAttribute::InnerClasses(from)) => to.classes.extend(from.classes),
+			(Some(IRAttribute::Record { components: to }), IRAttribute::Record { components: from }) => to.extend(from),
+			(
+				Some(IRAttribute::MethodParameters { parameters: to }),
+				IRAttribute::MethodParameters { parameters: from },
+			) => to.extend(from),
+			(_, attr) => {
+				let attr = self.builder.attribute_info(attr)?;
+				attributes.push(attr);
+			}
+		}
+		Ok(())
+	}
+
+	fn field(&mut self, mut tokens: Tokens<'a>) -> Result<(), IRClassfileError> {
+		let access_flags = tokens.flags::<FieldAccessFlags>();
+		let name = self.utf8(tokens.word("field name")?)?;
+		let descriptor = self.utf8(tokens.word("field descriptor")?)?;
+		let mut attributes = Attributes::new();
+		if tokens.peek_word() == Some("=") {
+			tokens.pos += 1;
+			let value = match self.constant(&mut tokens)? {
+				IRCpTag::Integer(value) => CPConstValueRefKind::Int(value),
+				IRCpTag::Long(value) => CPConstValueRefKind::Long(value),
+				IRCpTag::Float(value) => CPConstValueRefKind::Float(value),
+				IRCpTag::Double(value) => CPConstValueRefKind::Double(value),
+				IRCpTag::String(value) => CPConstValueRefKind::String(value.data),
+				_ => return Err(tokens.error("a field's constant value has to be a number or a string")),
+			};
+			let value = ConstantValueAttribute::find_or_add(self.builder.cp(), value)?;
+			self.add(&mut attributes, IRAttribute::ConstantValue(value))?;
+		}
+		tokens.end()?;
+
+		// Anything up to the next member is the field's, and ends with `.end field`.
+		if self
+			.peek_directive()
+			.is_some_and(|next| next != ".field" && next != ".method")
+		{
+			loop {
+				let line = self.next_line().ok_or_else(|| tokens.error("missing .end field"))?;
+				let mut tokens = line.tokens();
+				let directive = tokens.word("directive")?;
+				if directive == ".end" {
+					tokens.keyword("field")?;
+					tokens.end()?;
+					break;
+				}
+				self.member_attribute(directive, tokens, &mut attributes, Target::Field)
+					.map_err(|err| located(line.number, err))?;
+			}
+		}
+
+		self.fields.push(IRFieldInfo {
+			access_flags,
+			name,
+			descriptor,
+			attributes,
+		});
+		Ok(())
+	}
+
+	fn method(&mut self, start: usize, mut tokens: Tokens<'a>) -> Result<(), IRClassfileError> {
+		let access_flags = tokens.flags::<MethodAccessFlags>();
+		let word = tokens.word("method name and descriptor")?;
+		let (name, descriptor) =
+			name_and_descriptor(word).ok_or_else(|| tokens.error(format!("expected name(args)ret, found {word}")))?;
+		tokens.end()?;
+
+		let mut body = Body::default();
+		let mut attributes = Attributes::new();
+		let end = loop {
+			let line = self.next_line().ok_or_else(|| error(start, "missing .end method"))?;
+			let mut tokens = line.tokens();
+			let mut first = tokens.word("directive or instruction")?;
+			if first == ".end" {
+				tokens.keyword("method")?;
+				tokens.end()?;
+				break line.number;
+			}
+			if let Some(label) = first.strip_suffix(':') {
+				body.bind(line.number, label)?;
+				match tokens.is_empty() {
+					true => continue,
+					false => first = tokens.word("instruction")?,
+				}
+			}
+			let result = match first {
+				directive if directive.starts_with('.') => {
+					self.method_directive(directive, tokens, &mut body, &mut attributes)
+				}
+				mnemonic => self.instruction(mnemonic, tokens, &mut body),
+			};
+			result.map_err(|err| located(line.number, err))?;
+		};
+
+		if let Some((line, name)) = body.uses.iter().find(|(_, name)| !body.bound.contains(name)) {
+			return Err(error(*line, format!("label {name} is never bound")));
+		}
+		let mut method = IRMethodInfo {
+			access_flags,
+			name: self.utf8(name)?,
+			descriptor: self.utf8(descriptor)?,
+			attributes,
+		};
+		if !body.is_empty() {
+			self.code(&mut method, body).map_err(|err| located(end, err))?;
+		}
+		self.methods.push(method);
+		Ok(())
+	}
+
+	fn code(&mut self, method: &mut IRMethodInfo, body: Body) -> Result<(), IRClassfileError> {
+		let code = body.listing.to_code(self.builder.cp())?;
+		let code = self.builder.attribute_info(IRAttribute::Code(code))?;
+		method.attributes.insert(0, code);
+		let (max_stack, max_locals) = match (body.max_stack, body.max_locals) {
+			(Some(max_stack), Some(max_locals)) => (max_stack, max_locals),
+			(max_stack, max_locals) => {
+				let computed = compute_maxs(self.builder.cp(), &self.class_name, method)?.expect("the method has code");
+				(max_stack.unwrap_or(computed.0), max_locals.unwrap_or(computed.1))
+			}
+		};
+		let code = method.attributes.code_mut().expect("the method has code");
+		code.max_stack = max_stack;
+		code.max_locals = max_locals;
+		Ok(())
+	}
+
+	fn method_directive(
+		&mut self,
+		directive: &str,
+		mut tokens: Tokens<'a>,
+		body: &mut Body<'a>,
+		attributes: &mut Attributes,
+	) -> Result<(), IRClassfileError> {
+		let line = tokens.line;
+		match directive {
+			".limit" => match tokens.word("stack or locals")? {
+				"stack" => body.max_stack = Some(tokens.number("max_stack")?),
+				"locals" => body.max_locals = Some(tokens.number("max_locals")?),
+				what => return Err(tokens.error(format!("expected stack or locals, found {what}"))),
+			},
+			".line" => body.listing.items.push(Item::Line(tokens.number("line number")?)),
+			".catch" => {
+				let catch_type = match tokens.word("exception class")? {
+					"all" => None,
+					class => Some(class.to_string()),
+				};
+				tokens.keyword("from")?;
+				let start = body.label(line, tokens.word("label")?);
+				tokens.keyword("to")?;
+				let end = body.label(line, tokens.word("label")?);
+				tokens.keyword("using")?;
+				let handler = body.label(line, tokens.word("label")?);
+				body.listing.handlers.push(ListingHandler {
+					start,
+					end,
+					handler,
+					catch_type,
+				});
+			}
+			".var" | ".vartype" => {
+				let index = tokens.number("local variable index")?;
+				tokens.keyword("is")?;
+				let name = self.utf8(tokens.word("local variable name")?)?;
+				let descriptor = self.utf8(tokens.text("local variable type")?)?;
+				tokens.keyword("from")?;
+				let start = body.label(line, tokens.word("label")?);
+				tokens.keyword("to")?;
+				let end = body.label(line, tokens.word("label")?);
+				let local = ListingLocal {
+					start,
+					end,
+					name,
+					descriptor,
+					index,
+				};
+				match directive {
+					".var" => body.listing.local_variables.push(local),
+					_ => body.listing.local_variable_types.push(local),
+				}
+			}
+			".throws" => {
+				let exception_index_table = vec![self.class_ref(tokens.word("exception class")?)?];
+				tokens.end()?;
+				return self.add(attributes, IRAttribute::Exceptions { exception_index_table });
+			}
+			".parameter" => {
+				let access_flags = tokens.flags::<ParameterAccessFlags>();
+				let name = match tokens.is_empty() {
+					true => None,
+					false => Some(self.utf8(tokens.word("parameter name")?)?),
+				};
+				tokens.end()?;
+				let parameters = vec![MethodParametersParam { name, access_flags }];
+				return self.add(attributes, IRAttribute::MethodParameters { parameters });
+			}
+			_ => return self.member_attribute(directive, tokens, attributes, Target::Method),
+		}
+		tokens.end()
+	}
+
+	fn instruction(
+		&mut self,
+		mnemonic: &str,
+		mut tokens: Tokens<'a>,
+		body: &mut Body<'a>,
+	) -> Result<(), IRClassfileError> {
+		let line = tokens.line;
+		let opcode = (0..=u8::MAX)
+			.find(|opcode| Opcodes::mnemonic(*opcode) == Some(mnemonic))
+			.ok_or_else(|| tokens.error(format!("unknown instruction {mnemonic}")))?;
+		let insn = match opcode {
+			Opcodes::BIPUSH => Instructions::BIPUSH(tokens.number("byte")?),
+			Opcodes::SIPUSH => Instructions::SIPUSH(tokens.number("short")?),
+			Opcodes::LDC | Opcodes::LDC_W | Opcodes::LDC2_W => Instructions::LDC(self.constant(&mut tokens)?),
+			Opcodes::ILOAD => Instructions::ILOAD(tokens.number("local variable index")?),
+			Opcodes::LLOAD => Instructions::LLOAD(tokens.number("local variable index")?),
+			Opcodes::FLOAD => Instructions::FLOAD(tokens.number("local variable index")?),
+			Opcodes::DLOAD => Instructions::DLOAD(tokens.number("local variable index")?),
+			Opcodes::ALOAD => Instructions::ALOAD(tokens.number("local variable index")?),
+			Opcodes::ISTORE => Instructions::ISTORE(tokens.number("local variable index")?),
+			Opcodes::LSTORE => Instructions::LSTORE(tokens.number("local variable index")?),
+			Opcodes::FSTORE => Instructions::FSTORE(tokens.number("local variable index")?),
+			Opcodes::DSTORE => Instructions::DSTORE(tokens.number("local variable index")?),
+			Opcodes::ASTORE => Instructions::ASTORE(tokens.number("local variable index")?),
+			Opcodes::RET => Instructions::RET(tokens.number("local variable index")?),
+			Opcodes::IINC => Instructions::IINC {
+				index: tokens.number("local variable index")?,
+				r#const: tokens.number("increment")?,
+			},
+			Opcodes::IFEQ..=Opcodes::JSR | Opcodes::IFNULL | Opcodes::IFNONNULL | Opcodes::GOTO_W | Opcodes::JSR_W => {
+				let opcode = match opcode {
+					Opcodes::GOTO_W => Opcodes::GOTO,
+					Opcodes::JSR_W => Opcodes::JSR,
+					opcode => opcode,
+				};
+				let target = body.label(line, tokens.word("label")?);
+				tokens.end()?;
+				body.listing.items.push(Item::Jump { opcode, target });
+				return Ok(());
+			}
+			Opcodes::TABLESWITCH => {
+				let low = tokens.number("low key")?;
+				tokens.end()?;
+				let (cases, default) = self.switch_cases(line, body, Some(low))?;
+				let targets = cases.into_iter().map(|(_, target)| target).collect();
+				body.listing.items.push(Item::TableSwitch { low, default, targets });
+				return Ok(());
+			}
+			Opcodes::LOOKUPSWITCH => {
+				tokens.end()?;
+				let (pairs, default) = self.switch_cases(line, body, None)?;
+				body.listing.items.push(Item::LookupSwitch { default, pairs });
+				return Ok(());
+			}
+			Opcodes::GETSTATIC | Opcodes::PUTSTATIC | Opcodes::GETFIELD | Opcodes::PUTFIELD => {
+				let field = self.field_ref(&mut tokens)?;
+				match opcode {
+					Opcodes::GETSTATIC => Instructions::GETSTATIC(field),
+					Opcodes::PUTSTATIC => Instructions::PUTSTATIC(field),
+					Opcodes::GETFIELD => Instructions::GETFIELD(field),
+					_ => Instructions::PUTFIELD(field),
+				}
+			}
+			Opcodes::INVOKEVIRTUAL | Opcodes::INVOKESPECIAL | Opcodes::INVOKESTATIC => {
+				let method = self.method_ref(&mut tokens)?;
+				match opcode {
+					Opcodes::INVOKEVIRTUAL => Instructions::INVOKEVIRTUAL(method),
+					Opcodes::INVOKESPECIAL => Instructions::INVOKESPECIAL(method),
+					_ => Instructions::INVOKESTATIC(method),
+				}
+			}
+			Opcodes::INVOKEINTERFACE => {
+				let method = self.interface_method_ref(&mut tokens)?;
+				let count = match tokens.is_empty() {
+					false => tokens.number("argument count")?,
+					true => {
+						let descriptor = MethodDescriptor::parse(&method.name_and_ty.ty.data)?;
+						u8::try_from(descriptor.param_slots() + 1).map_err(|_| tokens.error("too many arguments"))?
+					}
+				};
+				Instructions::INVOKEINTERFACE { method, count }
+			}
+			Opcodes::INVOKEDYNAMIC => Instructions::INVOKEDYNAMIC(self.invoke_dynamic(&mut tokens)?),
+			Opcodes::NEW => Instructions::NEW(self.class_ref(tokens.word("class name")?)?),
+			Opcodes::ANEWARRAY => Instructions::ANEWARRAY(self.class_ref(tokens.word("class name")?)?),
+			Opcodes::CHECKCAST => Instructions::CHECKCAST(self.class_ref(tokens.word("class name")?)?),
+			Opcodes::INSTANCEOF => Instructions::INSTANCEOF(self.class_ref(tokens.word("class name")?)?),
+			Opcodes::NEWARRAY => {
+				let ty = tokens.word("array type")?;
+				let atype = (4..=11)
+					.find(|atype| array_type(*atype) == ty)
+					.ok_or_else(|| tokens.error(format!("expected a primitive type, found {ty}")))?;
+				Instructions::NEWARRAY(atype)
+			}
+			Opcodes::MULTIANEWARRAY => Instructions::MULTIANEWARRAY {
+				class: self.class_ref(tokens.word("array type")?)?,
+				dimensions: tokens.number("dimensions")?,
+			},
+			Opcodes::WIDE => return Err(tokens.error("wide is implied by the operands")),
+			// Everything else has no operands.
+			opcode => Instructions::read(&[], &mut Cursor::new(&[opcode][..]))?,
+		};
+		tokens.end()?;
+		body.listing.items.push(Item::Insn(insn));
+		Ok(())
+	}
+
+	// The `key: label` lines of a switch up to its `default: label`. A tableswitch counts up from `next_key`, so its
+	// lines can leave the key out.
+	fn switch_cases(
+		&mut self,
+		start: usize,
+		body: &mut Body<'a>,
+		mut next_key: Option<i32>,
+	) -> Result<(Vec<(i32, LabelId)>, LabelId), IRClassfileError> {
+		let mut cases = Vec::new();
+		loop {
+			let line = self
+				.next_line()
+				.ok_or_else(|| error(start, "switch without a default"))?;
+			let words = line
+				.tokens
+				.iter()
+				.map(|token| match token {
+					Token::Word(word) => Ok(word.as_str()),
+					Token::Str(_) => Err(error(line.number, "expected key: label")),
+				})
+				.collect::<Result<Vec<_>, _>>()?;
+			// Both `5: L1` and `5 : L1`.
+			let (key, label) = match words[..] {
+				[key, ":", label] => (Some(key), label),
+				[key, label] => (Some(key.strip_suffix(':').unwrap_or(key)), label),
+				[label] => match label.split_once(':') {
+					Some((key, label)) => (Some(key), label),
+					None => (None, label),
+				},
+				_ => return Err(error(line.number, "expected key: label")),
+			};
+			let target = body.label(line.number, label);
+			let key = match key {
+				Some("default") => return Ok((cases, target)),
+				Some(key) => {
+					let key = parse_int(key)
+						.and_then(|key| i32::try_from(key).ok())
+						.ok_or_else(|| error(line.number, format!("expected switch key, found {key}")))?;
+					if next_key.is_some_and(|next| next != key) {
+						return Err(error(line.number, "tableswitch keys have to be consecutive"));
+					}
+					key
+				}
+				None => next_key.ok_or_else(|| error(line.number, "lookupswitch needs a key for every label"))?,
+			};
+			next_key = next_key.map(|next| next.wrapping_add(1));
+			cases.push((key, target));
+		}
+	}
+
+	fn constant(&mut self, tokens: &mut Tokens<'a>) -> Result<IRCpTag, IRClassfileError> {
+		if let Some(Token::Str(_)) = tokens.tokens.get(tokens.pos) {
+			let string = tokens.string("string")?;
+			return Ok(IRCpTag::String(self.utf8(string)?));
+		}
+		let word = tokens.word("constant")?;
+		match word {
+			"class" => return Ok(IRCpTag::Class(self.utf8(tokens.word("class name")?)?)),
+			"methodtype" => return Ok(IRCpTag::MethodType(self.utf8(tokens.word("method descriptor")?)?)),
+			"handle" => return Ok(self.method_handle(tokens)?.1),
+			_ => {}
+		}
+		let hex = word.contains("0x") || word.contains("0X");
+		if let Some(value) = parse_int(word) {
+			// Hexadecimal ints may set the sign bit, as in Java.
+			let int = i32::try_from(value).or_else(|_| match hex {
+				true => u32::try_from(value).map(|value| value as i32),
+				false => i32::try_from(value),
+			});
+			return int
+				.map(IRCpTag::Integer)
+				.map_err(|_| tokens.error(format!("{word} doesn't fit an int, make it a long")));
+		}
+		if let Some(long) = word.strip_suffix(['L', 'l']).and_then(parse_int) {
+			return Ok(IRCpTag::Long(long));
+		}
+		if !hex {
+			if let Some(float) = word.strip_suffix(['F', 'f']).and_then(|float| float.parse().ok()) {
+				return Ok(IRCpTag::Float(float));
+			}
+			if let Ok(double) = word.strip_suffix(['D', 'd']).unwrap_or(word).parse() {
+				return Ok(IRCpTag::Double(double));
+			}
+		}
+		Err(tokens.error(format!("expected constant, found {word}")))
+	}
+
+	// `kind reference`, the handle as a CPMethodHandleRef and as its pool entry.
+	fn method_handle(&mut self, tokens: &mut Tokens<'a>) -> Result<(CPMethodHandleRef, IRCpTag), IRClassfileError> {
+		let kind = tokens.word("method handle kind")?;
+		let (ref_kind, ref_index) = match kind {
+			"getfield" | "getstatic" | "putfield" | "putstatic" => {
+				let ref_kind = match kind {
+					"getfield" => IRMethodRefKind::GetField,
+					"getstatic" => IRMethodRefKind::GetStatic,
+					"putfield" => IRMethodRefKind::PutField,
+					_ => IRMethodRefKind::PutStatic,
+				};
+				(ref_kind, self.field_ref(tokens)?.index)
+			}
+			"invokevirtual" | "invokestatic" | "invokespecial" | "newinvokespecial" => {
+				let ref_kind = match kind {
+					"invokevirtual" => IRMethodRefKind::InvokeVirtual,
+					"invokestatic" => IRMethodRefKind::InvokeStatic,
+					"invokespecial" => IRMethodRefKind::InvokeSpecial,
+					_ => IRMethodRefKind::NewInvokeSpecial,
+				};
+				(ref_kind, self.method_ref(tokens)?.index)
+			}
+			"invokeinterface" => (
+				IRMethodRefKind::InvokeInterface,
+				self.interface_method_ref(tokens)?.index,
+			),
+			kind => return Err(tokens.error(format!("unknown method handle kind {kind}"))),
+		};
+		let cp = self.builder.cp();
+		let tag = IRCpTag::MethodHandle {
+			ref_kind,
+			ref_index,
+			ref_tag: Box::new(cp[ref_index as usize - 1].clone()),
+		};
+		let index = cp_find_or_add(cp, tag.clone())?;
+		Ok((CPMethodHandleRef::from_cp(cp, index)?, tag))
+	}
+
+	// `name(args)ret bootstrap-handle arguments...`, adding the bootstrap method unless it's there already.
+	fn invoke_dynamic(&mut self, tokens: &mut Tokens<'a>) -> Result<CPInvokeDynamicRef, IRClassfileError> {
+		let word = tokens.word("call site name and descriptor")?;
+		let (name, descriptor) =
+			name_and_descriptor(word).ok_or_else(|| tokens.error(format!("expected name(args)ret, found {word}")))?;
+		let (method, _) = self.method_handle(tokens)?;
+		let mut arguments = Vec::new();
+		while !tokens.is_empty() {
+			let argument = self.constant(tokens)?;
+			arguments.push(CPTagRef::find_or_add(self.builder.cp(), argument)?);
+		}
+		let same = |existing: &BootstrapMethodsMethod| {
+			existing.method.index == method.index
+				&& existing.arguments.len() == arguments.len()
+				&& existing
+					.arguments
+					.iter()
+					.zip(&arguments)
+					.all(|(a, b)| a.index == b.index)
+		};
+		let bootstrap_method_attr_index = match self.bootstrap_methods.iter().position(same) {
+			Some(index) => index,
+			None => {
+				self.bootstrap_methods
+					.push(BootstrapMethodsMethod { method, arguments });
+				self.bootstrap_methods.len() - 1
+			}
+		};
+		let cp = self.builder.cp();
+		let name_and_ty = CPNameAndTypeRef::find_or_add(cp, name, descriptor)?;
+		let tag = IRCpTag::InvokeDynamic {
+			bootstrap_method_attr_index: bootstrap_method_attr_index as u16,
+			name_and_ty,
+		};
+		let index = cp_find_or_add(cp, tag)?;
+		CPInvokeDynamicRef::from_cp(cp, index)
+	}
+
+	fn annotation(
+		&mut self,
+		mut tokens: Tokens<'a>,
+		attributes: &mut Attributes,
+		target: Target,
+	) -> Result<(), IRClassfileError> {
+		let kind = tokens.word("annotation kind")?;
+		let attr = match kind {
+			"visible" | "invisible" => {
+				let ty = self.utf8(tokens.word("annotation type")?)?;
+				tokens.end()?;
+				let annotations = vec![RuntimeAnnotation {
+					ty,
+					pairs: self.annotation_pairs(tokens.line)?,
+				}];
+				match kind {
+					"visible" => IRAttribute::RuntimeVisibleAnnotations { annotations },
+					_ => IRAttribute::RuntimeInvisibleAnnotations { annotations },
+				}
+			}
+			"visibleparam" | "invisibleparam" if target == Target::Method => {
+				let index = tokens.number::<u8>("parameter index")? as usize;
+				let ty = self.utf8(tokens.word("annotation type")?)?;
+				tokens.end()?;
+				let mut params = vec![Vec::new(); index + 1];
+				params[index].push(RuntimeAnnotation {
+					ty,
+					pairs: self.annotation_pairs(tokens.line)?,
+				});
+				match kind {
+					"visibleparam" => IRAttribute::RuntimeVisibleParameterAnnotations { params },
+					_ => IRAttribute::RuntimeInvisibleParameterAnnotations { params },
+				}
+			}
+			"default" if target == Target::Method => {
+				tokens.end()?;
+				let line = self
+					.next_line()
+					.ok_or_else(|| tokens.error("missing .end annotation"))?;
+				let mut value_tokens = line.tokens();
+				let default_value = self
+					.element_value(&mut value_tokens)
+					.and_then(|value| value_tokens.end().map(|_| value))
+					.map_err(|err| located(line.number, err))?;
+				self.end_annotation(tokens.line)?;
+				IRAttribute::AnnotationDefault { default_value }
+			}
+			kind => return Err(tokens.error(format!("unexpected annotation kind {kind}"))),
+		};
+		self.add(attributes, attr)
+	}
+
+	// The `name value` lines of an annotation up to `.end annotation`.
+	fn annotation_pairs(&mut self, start: usize) -> Result<SmallVec<[RuntimeAnnotationEVPair; 2]>, IRClassfileError> {
+		let mut pairs = SmallVec::new();
+		while self.peek_directive() != Some(".end") {
+			let line = self
+				.next_line()
+				.ok_or_else(|| error(start, "missing .end annotation"))?;
+			let mut tokens = line.tokens();
+			let pair = self
+				.element_pair(&mut tokens)
+				.and_then(|pair| tokens.end().map(|_| pair))
+				.map_err(|err| located(line.number, err))?;
+			pairs.push(pair);
+		}
+		self.end_annotation(start)?;
+		Ok(pairs)
+	}
+
+	fn end_annotation(&mut self, start: usize) -> Result<(), IRClassfileError> {
+		let line = self
+			.next_line()
+			.ok_or_else(|| error(start, "missing .end annotation"))?;
+		let mut tokens = line.tokens();
+		tokens.keyword(".end")?;
+		tokens.keyword("annotation")?;
+		tokens.end()
+	}
+
+	fn element_pair(&mut self, tokens: &mut Tokens<'a>) -> Result<RuntimeAnnotationEVPair, IRClassfileError> {
+		Ok(RuntimeAnnotationEVPair {
+			name: self.utf8(tokens.word("element name")?)?,
+			value: self.element_value(tokens)?,
+		})
+	}
+
+	fn element_value(&mut self, tokens: &mut Tokens<'a>) -> Result<RuntimeAnnotationValue, IRClassfileError> {
+		let tag = tokens.word("element value tag")?;
+		let constant = match tag {
+			"B" | "C" | "I" | "S" | "Z" => match tokens.peek_word() {
+				Some("true") | Some("false") if tag == "Z" => {
+					IRCpTag::Integer((tokens.word("boolean")? == "true") as i32)
+				}
+				_ => IRCpTag::Integer(tokens.number(tag)?),
+			},
+			"J" => IRCpTag::Long(tokens.number("long")?),
+			"F" | "D" => {
+				let word = tokens.word("floating point number")?;
+				let number = word.strip_suffix(['F', 'f', 'D', 'd']).unwrap_or(word);
+				match tag {
+					"F" => number.parse().map(IRCpTag::Float).ok(),
+					_ => number.parse().map(IRCpTag::Double).ok(),
+				}
+				.ok_or_else(|| tokens.error(format!("expected floating point number, found {word}")))?
+			}
+			"s" => IRCpTag::Utf8(Shared::new(tokens.string("string")?.to_string())),
+			"e" => {
+				return Ok(RuntimeAnnotationValue::EnumConstValue {
+					type_name: self.utf8(tokens.word("enum type")?)?,
+					const_name: self.utf8(tokens.word("enum constant")?)?,
+				})
+			}
+			"c" => {
+				return Ok(RuntimeAnnotationValue::ClassInfoIndex(
+					self.utf8(tokens.word("class")?)?,
+				))
+			}
+			"@" => {
+				let ty = self.utf8(tokens.word("annotation type")?)?;
+				tokens.keyword("{")?;
+				let mut pairs = SmallVec::new();
+				while tokens.peek_word() != Some("}") {
+					pairs.push(self.element_pair(tokens)?);
+				}
+				tokens.keyword("}")?;
+				return Ok(RuntimeAnnotationValue::Annotation(Box::new(RuntimeAnnotation {
+					ty,
+					pairs,
+				})));
+			}
+			"[" => {
+				let mut values = Vec::new();
+				while tokens.peek_word() != Some("]") {
+					values.push(self.element_value(tokens)?);
+				}
+				tokens.keyword("]")?;
+				return Ok(RuntimeAnnotationValue::ArrayValue { values });
+			}
+			tag => return Err(tokens.error(format!("unknown element value tag {tag}"))),
+		};
+		let cp = self.builder.cp();
+		let index = cp_find_or_add(cp, constant)?;
+		Ok(RuntimeAnnotationValue::ConstValueIndex {
+			tag: tag.as_bytes()[0],
+			value: CPConstValueRef::from_cp(cp, index)?,
+		})
+	}
+
+	fn field_ref(&mut self, tokens: &mut Tokens<'a>) -> Result<CPFieldRef, IRClassfileError> {
+		let word = tokens.word("field")?;
+		let (owner, name) = member(word).ok_or_else(|| tokens.error(format!("expected owner/name, found {word}")))?;
+		let descriptor = tokens.word("field descriptor")?;
+		CPFieldRef::find_or_add(self.builder.cp(), owner, name, descriptor)
+	}
+
+	fn method_ref(&mut self, tokens: &mut Tokens<'a>) -> Result<CPMethodRef, IRClassfileError> {
+		let word = tokens.word("method")?;
+		let (owner, name, descriptor) =
+			method_member(word).ok_or_else(|| tokens.error(format!("expected owner/name(args)ret, found {word}")))?;
+		CPMethodRef::find_or_add(self.builder.cp(), owner, name, descriptor)
+	}
+
+	fn interface_method_ref(&mut self, tokens: &mut Tokens<'a>) -> Result<CPInterfaceMethodRef, IRClassfileError> {
+		let word = tokens.word("interface method")?;
+		let (owner, name, descriptor) =
+			method_member(word).ok_or_else(|| tokens.error(format!("expected owner/name(args)ret, found {word}")))?;
+		CPInterfaceMethodRef::find_or_add(self.builder.cp(), owner, name, descriptor)
+	}
+
+	fn utf8(&mut self, value: &str) -> Result<CPUtf8Ref, IRClassfileError> {
+		CPUtf8Ref::find_or_add(self.builder.cp(), value)
+	}
+
+	fn class_ref(&mut self, name: &str) -> Result<CPClassRef, IRClassfileError> {
+		CPClassRef::find_or_add(self.builder.cp(), name)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::disasm::disassemble_code;
+
+	#[test]
+	fn assemble_class() {
+		let source = r#"
+			.class public super p/Counter
+			.implements java/lang/Runnable
+			.annotation visible Lp/Marker;
+				names [ s "a" s "b" ]
+				level e Lp/Level; HIGH
+			.end annotation
+
+			.field static final LIMIT I = 0x10 ; sixteen
+			.field private count J
+				.deprecated
+			.end field
+
+			.method public run()V
+				.throws java/lang/Exception
+				iconst_0
+				istore_1
+			Loop: iload_1
+				getstatic p/Counter/LIMIT I
+				if_icmpge Done
+				iinc 1 300
+				goto Loop
+			Done:
+				invokedynamic tick()Ljava/lang/Runnable; invokestatic p/Counter/bootstrap()V "it's" 2.5
+				pop
+				return
+			Handler:
+				athrow
+				.catch all from Loop to Done using Handler
+			.end method
+		"#;
+		let class = assemble(source).unwrap();
+		let class = IRClassFile::read(&class.to_bytes().unwrap()).unwrap();
+
+		assert_eq!(class.interface_names().collect::<Vec<_>>(), ["java/lang/Runnable"]);
+		let annotation = class.attributes.annotations().next().unwrap();
+		assert_eq!(annotation.pairs.len(), 2);
+		// Small tables read back without a heap allocation of their own.
+		assert!(!annotation.pairs.spilled());
+		assert!(matches!(
+			class.fields[0].attributes[0].attr,
+			IRAttribute::ConstantValue(ConstantValueAttribute::Int { value: 16, .. })
+		));
+		assert!(matches!(class.fields[1].attributes[0].attr, IRAttribute::Deprecated));
+		let run = &class.methods[0];
+		let code = run.code().unwrap();
+		assert_eq!((code.max_stack, code.max_locals), (2, 2));
+		assert!(!code.exception_table.spilled());
+		assert_eq!(
+			disassemble_code(&class, run).unwrap(),
+			"      stack=2, locals=2, args_size=1
+         0: iconst_0
+         1: istore_1
+         2: iload_1
+         3: getstatic     #26                 // Field LIMIT:I
+         6: if_icmpge     18
+         9: iinc_w        1, 300
+        15: goto          2
+        18: invokedynamic #39,  0             // InvokeDynamic #0:tick:()Ljava/lang/Runnable;
+        23: pop
+        24: return
+        25: athrow
+      Exception table:
+         from    to  target type
+             2    18    25   any
+"
+		);
+
+		let err = assemble(".class p/C\n.method m()V\n  goto Nowhere\n.end method").unwrap_err();
+		assert_eq!(err.to_string(), "Line 3: label Nowhere is never bound");
+		let err = assemble(".class p/C\n.method m()V\n  bipush 200\n").unwrap_err();
+		assert_eq!(err.to_string(), "Line 3: expected byte, found 200");
+	}
+}
@@ -0,0 +1,93 @@
+// Reading classes and jars from a futures `AsyncRead`, for servers fetching them over the network that shouldn't hold
+// a thread per download. Only the reading waits: a class is gathered as its bytes arrive, see
+// `maya_classfile_io::stream::read_class_async`, and parsed once they're all there, which is quick enough to do on the
+// task. A jar's directory is at its end, so the whole jar is read before its entries are.
+
+use futures_util::io::{AsyncRead, AsyncReadExt};
+use maya_classfile_io::stream::read_class_async;
+
+use crate::{
+	archive::{Archive, ArchiveError, DuplicatePolicy},
+	class_pool::IRClassfileError,
+	jar::{Jar, JarError},
+	parse::{ParseOptions, ParseWarning},
+	IRClassFile,
+};
+
+impl IRClassFile {
+	/// Reads one class from `reader`, strictly and with the default limits, leaving anything after it unread.
+	pub async fn read_async<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Self, IRClassfileError> {
+		Self::read_async_with(reader, &ParseOptions::default())
+			.await
+			.map(|(class, _)| class)
+	}
+
+	pub async fn read_async_with<R: AsyncRead + Unpin>(
+		reader: &mut R,
+		options: &ParseOptions,
+	) -> Result<(Self, Vec<ParseWarning>), IRClassfileError> {
+		let raw = read_class_async(reader, &options.limits).await?;
+		Self::from_io_with(raw, options)
+	}
+}
+
+impl Archive {
+	/// Reads the archive to the end of `reader`.
+	pub async fn read_async<R: AsyncRead + Unpin>(
+		reader: &mut R,
+		policy: DuplicatePolicy,
+	) -> Result<Self, ArchiveError> {
+		let mut bytes = Vec::new();
+		reader.read_to_end(&mut bytes).await?;
+		Self::read(std::io::Cursor::new(bytes), policy)
+	}
+}
+
+impl Jar {
+	/// Reads the jar to the end of `reader`, its classes are parsed when asked for as usual.
+	pub async fn read_async<R: AsyncRead + Unpin>(reader: &mut R, policy: DuplicatePolicy) -> Result<Self, JarError> {
+		Ok(Archive::read_async(reader, policy).await?.into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use futures_executor::block_on;
+	use futures_util::io::Cursor;
+	use zip::{write::FileOptions, ZipWriter};
+
+	use super::*;
+	use crate::builder::ClassBuilder;
+
+	#[test]
+	fn read_without_blocking() {
+		let class = |name: &str| ClassBuilder::new(name).unwrap().to_bytes().unwrap();
+		let (first, second) = (class("p/First"), class("p/Second"));
+		let mut jar = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+		jar.start_file("p/First.class", FileOptions::default()).unwrap();
+		jar.write_all(&first).unwrap();
+		let jar = jar.finish().unwrap().into_inner();
+
+		block_on(async {
+			let mut stream = Cursor::new([first.clone(), second.clone()].concat());
+			assert_eq!(
+				IRClassFile::read_async(&mut stream).await.unwrap().class_name(),
+				"p/First"
+			);
+			assert_eq!(stream.position(), first.len() as u64);
+			assert_eq!(
+				IRClassFile::read_async(&mut stream).await.unwrap().class_name(),
+				"p/Second"
+			);
+			assert!(IRClassFile::read_async(&mut Cursor::new(&first[..20])).await.is_err());
+
+			let jar = Jar::read_async(&mut Cursor::new(jar), DuplicatePolicy::Error)
+				.await
+				.unwrap();
+			assert_eq!(jar.class_names().collect::<Vec<_>>(), ["p/First"]);
+			assert_eq!(jar.class("p/First").unwrap().unwrap().to_bytes().unwrap(), first);
+		});
+	}
+}
@@ -0,0 +1,393 @@
+// A read-only view of a class that borrows from the bytes it was parsed from, for scanners going over many classes
+// that only look at them: finding the classes referring to a name, indexing members, looking for a string. Parsing
+// the IR allocates every Utf8 entry, code array and attribute. This keeps them as slices of the input, decodes
+// modified UTF-8 only when asked, and borrows that too when it's plain UTF-8, which class names and descriptors almost
+// always are. Attributes are kept as their raw payload, `Code` is the only one read further when asked for.
+//
+// A view is always read whole and never written, `to_ir` parses the IR for a class worth changing.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.1
+
+use std::borrow::Cow;
+
+use maya_bytes::BytesError;
+use maya_classfile_io::IOClassfileError;
+
+use crate::{
+	attribute::CodeAttributeException,
+	class_pool::IRClassfileError,
+	flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+	ClassFileVersion, IRClassFile,
+};
+
+/// A constant pool entry, references to other entries as their indices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorrowedCpTag<'a> {
+	// modified UTF-8, see `BorrowedClassFile::utf8`
+	Utf8(&'a [u8]),
+	Integer(i32),
+	Float(f32),
+	Long(i64),
+	Double(f64),
+	Class(u16),
+	String(u16),
+	FieldRef { class: u16, name_and_ty: u16 },
+	MethodRef { class: u16, name_and_ty: u16 },
+	InterfaceMethodRef { class: u16, name_and_ty: u16 },
+	NameAndType { name: u16, descriptor: u16 },
+	MethodHandle { kind: u8, reference: u16 },
+	MethodType(u16),
+	Dynamic { bootstrap: u16, name_and_ty: u16 },
+	InvokeDynamic { bootstrap: u16, name_and_ty: u16 },
+	Module(u16),
+	Package(u16),
+	// the slot following a Long or Double
+	Unusable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowedAttribute<'a> {
+	pub name: u16,
+	pub info: &'a [u8],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BorrowedMember<'a> {
+	pub access_flags: u16,
+	pub name: u16,
+	pub descriptor: u16,
+	pub attributes: Vec<BorrowedAttribute<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BorrowedCode<'a> {
+	pub max_stack: u16,
+	pub max_locals: u16,
+	pub code: &'a [u8],
+	pub exception_table: Vec<CodeAttributeException>,
+	pub attributes: Vec<BorrowedAttribute<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BorrowedClassFile<'a> {
+	pub version: ClassFileVersion,
+	// index i + 1 in the pool, like `IRClassFile::cp`
+	pub cp: Vec<BorrowedCpTag<'a>>,
+	pub access_flags: ClassAccessFlags,
+	pub this_class: u16,
+	// 0 for java/lang/Object and module-info
+	pub super_class: u16,
+	pub interfaces: Vec<u16>,
+	pub fields: Vec<BorrowedMember<'a>>,
+	pub methods: Vec<BorrowedMember<'a>>,
+	pub attributes: Vec<BorrowedAttribute<'a>>,
+	bytes: &'a [u8],
+}
+
+impl<'a> BorrowedClassFile<'a> {
+	pub fn parse(bytes: &'a [u8]) -> Result<Self, IRClassfileError> {
+		let mut reader = Reader { bytes, at: 0 };
+		if reader.u32()? != 0xCAFEBABE {
+			return Err(IOClassfileError::InvalidMagic.into());
+		}
+		let minor = reader.u16()?;
+		let major = reader.u16()?;
+
+		let count = reader.u16()? as usize;
+		let mut cp = Vec::with_capacity(count.saturating_sub(1));
+		while cp.len() + 1 < count {
+			let tag = reader.cp_tag()?;
+			let wide = matches!(tag, BorrowedCpTag::Long(_) | BorrowedCpTag::Double(_));
+			cp.push(tag);
+			if wide {
+				cp.push(BorrowedCpTag::Unusable);
+			}
+		}
+
+		let access_flags = ClassAccessFlags::from_bits_retain(reader.u16()?);
+		let this_class = reader.u16()?;
+		let super_class = reader.u16()?;
+		let interfaces = (0..reader.u16()?).map(|_| reader.u16()).collect::<Result<_, _>>()?;
+		let fields = reader.members()?;
+		let methods = reader.members()?;
+		let attributes = reader.attributes()?;
+		Ok(Self {
+			version: ClassFileVersion { major, minor },
+			cp,
+			access_flags,
+			this_class,
+			super_class,
+			interfaces,
+			fields,
+			methods,
+			attributes,
+			bytes,
+		})
+	}
+
+	/// The Utf8 entry at `index`, borrowed unless it has characters modified UTF-8 writes differently.
+	pub fn utf8(&self, index: u16) -> Result<Cow<'a, str>, IRClassfileError> {
+		match self.tag(index)? {
+			// Modified UTF-8 that is valid UTF-8 reads the same, nulls and supplementary characters aren't.
+			BorrowedCpTag::Utf8(bytes) => match std::str::from_utf8(bytes) {
+				Ok(string) => Ok(Cow::Borrowed(string)),
+				Err(_) => Ok(Cow::Owned(maya_mutf8::decode(bytes)?)),
+			},
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "Utf8",
+			}),
+		}
+	}
+
+	/// The name of the Class entry at `index`.
+	pub fn class_name_at(&self, index: u16) -> Result<Cow<'a, str>, IRClassfileError> {
+		match self.tag(index)? {
+			BorrowedCpTag::Class(name) => self.utf8(name),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "Class",
+			}),
+		}
+	}
+
+	pub fn class_name(&self) -> Result<Cow<'a, str>, IRClassfileError> {
+		self.class_name_at(self.this_class)
+	}
+
+	pub fn super_name(&self) -> Result<Option<Cow<'a, str>>, IRClassfileError> {
+		match self.super_class {
+			0 => Ok(None),
+			index => self.class_name_at(index).map(Some),
+		}
+	}
+
+	pub fn interface_names(&self) -> Result<Vec<Cow<'a, str>>, IRClassfileError> {
+		self.interfaces.iter().map(|index| self.class_name_at(*index)).collect()
+	}
+
+	pub fn field_flags(&self, field: &BorrowedMember) -> FieldAccessFlags {
+		FieldAccessFlags::from_bits_retain(field.access_flags)
+	}
+
+	pub fn method_flags(&self, method: &BorrowedMember) -> MethodAccessFlags {
+		MethodAccessFlags::from_bits_retain(method.access_flags)
+	}
+
+	/// The attribute of `attributes` named `name`, the first if there are several.
+	pub fn attribute(
+		&self,
+		attributes: &[BorrowedAttribute<'a>],
+		name: &str,
+	) -> Result<Option<BorrowedAttribute<'a>>, IRClassfileError> {
+		for attribute in attributes {
+			if self.utf8(attribute.name)? == name {
+				return Ok(Some(*attribute));
+			}
+		}
+		Ok(None)
+	}
+
+	/// The `Code` of `method`, `None` for abstract and native methods.
+	pub fn code(&self, method: &BorrowedMember<'a>) -> Result<Option<BorrowedCode<'a>>, IRClassfileError> {
+		let Some(code) = self.attribute(&method.attributes, "Code")? else {
+			return Ok(None);
+		};
+		let mut reader = Reader {
+			bytes: code.info,
+			at: 0,
+		};
+		let max_stack = reader.u16()?;
+		let max_locals = reader.u16()?;
+		let length = reader.u32()?;
+		let code = reader.take(length as usize)?;
+		let exception_table = (0..reader.u16()?)
+			.map(|_| {
+				Ok(CodeAttributeException {
+					start_pc: reader.u16()?,
+					end_pc: reader.u16()?,
+					handler_pc: reader.u16()?,
+					catch_type: reader.u16()?,
+				})
+			})
+			.collect::<Result<_, BytesError>>()?;
+		let attributes = reader.attributes()?;
+		Ok(Some(BorrowedCode {
+			max_stack,
+			max_locals,
+			code,
+			exception_table,
+			attributes,
+		}))
+	}
+
+	/// The bytes the view borrows from.
+	pub fn bytes(&self) -> &'a [u8] {
+		self.bytes
+	}
+
+	/// Parses the IR of the class, to change it.
+	pub fn to_ir(&self) -> Result<IRClassFile, IRClassfileError> {
+		IRClassFile::read(self.bytes)
+	}
+
+	fn tag(&self, index: u16) -> Result<BorrowedCpTag<'a>, IRClassfileError> {
+		let tag = index.checked_sub(1).and_then(|i| self.cp.get(i as usize));
+		tag.copied().ok_or(IRClassfileError::InvalidCpIndex(index))
+	}
+}
+
+struct Reader<'a> {
+	bytes: &'a [u8],
+	at: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn take(&mut self, length: usize) -> Result<&'a [u8], BytesError> {
+		// A length from the class could overflow `at`, that's just as much past the end.
+		let end = self.at.checked_add(length).ok_or(BytesError::NotEnoughData)?;
+		let taken = self.bytes.get(self.at..end).ok_or(BytesError::NotEnoughData)?;
+		self.at = end;
+		Ok(taken)
+	}
+
+	fn array<const N: usize>(&mut self) -> Result<[u8; N], BytesError> {
+		Ok(self.take(N)?.try_into().expect("took N bytes"))
+	}
+
+	fn u8(&mut self) -> Result<u8, BytesError> {
+		Ok(self.take(1)?[0])
+	}
+
+	fn u16(&mut self) -> Result<u16, BytesError> {
+		self.array().map(u16::from_be_bytes)
+	}
+
+	fn u32(&mut self) -> Result<u32, BytesError> {
+		self.array().map(u32::from_be_bytes)
+	}
+
+	fn cp_tag(&mut self) -> Result<BorrowedCpTag<'a>, IRClassfileError> {
+		let tag = match self.u8()? {
+			1 => {
+				let length = self.u16()?;
+				BorrowedCpTag::Utf8(self.take(length as usize)?)
+			}
+			3 => BorrowedCpTag::Integer(i32::from_be_bytes(self.array()?)),
+			4 => BorrowedCpTag::Float(f32::from_be_bytes(self.array()?)),
+			5 => BorrowedCpTag::Long(i64::from_be_bytes(self.array()?)),
+			6 => BorrowedCpTag::Double(f64::from_be_bytes(self.array()?)),
+			7 => BorrowedCpTag::Class(self.u16()?),
+			8 => BorrowedCpTag::String(self.u16()?),
+			9 => BorrowedCpTag::FieldRef {
+				class: self.u16()?,
+				name_and_ty: self.u16()?,
+			},
+			10 => BorrowedCpTag::MethodRef {
+				class: self.u16()?,
+				name_and_ty: self.u16()?,
+			},
+			11 => BorrowedCpTag::InterfaceMethodRef {
+				class: self.u16()?,
+				name_and_ty: self.u16()?,
+			},
+			12 => BorrowedCpTag::NameAndType {
+				name: self.u16()?,
+				descriptor: self.u16()?,
+			},
+			15 => BorrowedCpTag::MethodHandle {
+				kind: self.u8()?,
+				reference: self.u16()?,
+			},
+			16 => BorrowedCpTag::MethodType(self.u16()?),
+			17 => BorrowedCpTag::Dynamic {
+				bootstrap: self.u16()?,
+				name_and_ty: self.u16()?,
+			},
+			18 => BorrowedCpTag::InvokeDynamic {
+				bootstrap: self.u16()?,
+				name_and_ty: self.u16()?,
+			},
+			19 => BorrowedCpTag::Module(self.u16()?),
+			20 => BorrowedCpTag::Package(self.u16()?),
+			tag => return Err(IOClassfileError::InvalidCpTag(tag).into()),
+		};
+		Ok(tag)
+	}
+
+	fn attributes(&mut self) -> Result<Vec<BorrowedAttribute<'a>>, BytesError> {
+		(0..self.u16()?)
+			.map(|_| {
+				let name = self.u16()?;
+				let length = self.u32()?;
+				Ok(BorrowedAttribute {
+					name,
+					info: self.take(length as usize)?,
+				})
+			})
+			.collect()
+	}
+
+	fn members(&mut self) -> Result<Vec<BorrowedMember<'a>>, BytesError> {
+		(0..self.u16()?)
+			.map(|_| {
+				Ok(BorrowedMember {
+					access_flags: self.u16()?,
+					name: self.u16()?,
+					descriptor: self.u16()?,
+					attributes: self.attributes()?,
+				})
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn borrow_from_the_input() {
+		let bytes = assemble(
+			".class public super p/Main\n.super p/Base\n.implements p/Runnable\n.field private name \
+			 Ljava/lang/String;\n.method public run()V\nldc \"caf\u{e9} \u{1F600}\"\npop\nreturn\n.end method\n\
+			 .method public abstract stop()V\n.end method",
+		)
+		.unwrap()
+		.to_bytes()
+		.unwrap();
+		let class = BorrowedClassFile::parse(&bytes).unwrap();
+		assert_eq!(class.class_name().unwrap(), "p/Main");
+		assert!(matches!(class.class_name().unwrap(), Cow::Borrowed(_)));
+		assert_eq!(class.super_name().unwrap().as_deref(), Some("p/Base"));
+		assert_eq!(class.interface_names().unwrap(), ["p/Runnable"]);
+		assert_eq!(class.utf8(class.fields[0].descriptor).unwrap(), "Ljava/lang/String;");
+		assert!(class.field_flags(&class.fields[0]).is_private());
+
+		let run = &class.methods[0];
+		let code = class.code(run).unwrap().unwrap();
+		assert!(bytes.as_ptr_range().contains(&code.code.as_ptr()));
+		assert_eq!(code.code, class.to_ir().unwrap().methods[0].code().unwrap().code);
+		assert!(class.code(&class.methods[1]).unwrap().is_none());
+
+		// A supplementary character is written as a surrogate pair, which isn't UTF-8.
+		let BorrowedCpTag::String(string) = class.cp[code.code[1] as usize - 1] else {
+			panic!("{:?}", class.cp);
+		};
+		let string = class.utf8(string).unwrap();
+		assert!(matches!(string, Cow::Owned(_)));
+		assert_eq!(string, "caf\u{e9} \u{1F600}");
+		assert!(BorrowedClassFile::parse(&bytes[..bytes.len() - 1]).is_err());
+	}
+
+	#[test]
+	fn take_past_the_end() {
+		let mut reader = Reader {
+			bytes: &[1, 2, 3],
+			at: 1,
+		};
+		assert!(matches!(reader.take(usize::MAX), Err(BytesError::NotEnoughData)));
+		assert!(matches!(reader.take(3), Err(BytesError::NotEnoughData)));
+		assert_eq!(reader.take(2).unwrap(), [2, 3]);
+	}
+}
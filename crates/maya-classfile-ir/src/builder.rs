@@ -0,0 +1,717 @@
+// Building classes from scratch. The builder owns the constant pool and adds entries as names, descriptors and
+// attributes come in, reusing existing ones. Attributes reference the pool by index, so anything building them needs
+// the pool too: `cp()` hands it out for the `find_or_add` helpers on the CP refs.
+//
+// Method bodies are built with a `CodeBuilder` on the same pool. Jumps and exception handlers point at labels, which
+// are turned into offsets when the code is laid out, widening jumps that don't fit 16 bits.
+
+use maya_classfile_io::limits::Limits;
+
+use crate::{
+	attribute::{Attributes, CodeAttribute, CodeAttributeException, IRAttribute, IRAttributeInfo},
+	class_pool::{CPClassRef, CPUtf8Ref, IRClassfileError, IRCpTag, PoolIndex},
+	code::{Instructions, Opcodes},
+	flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+	ClassFileVersion, IRClassFile, IRFieldInfo, IRMethodInfo, Shared,
+};
+
+#[derive(Debug, Clone)]
+pub struct ClassBuilder {
+	class: IRClassFile,
+	pool: PoolIndex,
+}
+
+impl ClassBuilder {
+	/// Java 8, the newest version that doesn't need anything the builder can't produce yet.
+	pub const DEFAULT_VERSION: ClassFileVersion = ClassFileVersion { major: 52, minor: 0 };
+
+	/// A public class extending java/lang/Object. `name` is an internal name.
+	pub fn new(name: &str) -> Result<Self, IRClassfileError> {
+		let mut cp = Vec::new();
+		let this_class = CPClassRef::find_or_add(&mut cp, name)?;
+		let super_class = CPClassRef::find_or_add(&mut cp, "java/lang/Object")?;
+
+		Ok(Self {
+			class: IRClassFile {
+				magic: 0xCAFEBABE,
+				version: Self::DEFAULT_VERSION,
+				cp,
+				access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+				this_class,
+				super_class: Some(super_class),
+				interfaces: Vec::new(),
+				fields: Vec::new(),
+				methods: Vec::new(),
+				attributes: Attributes::new(),
+			},
+			pool: PoolIndex::new(),
+		})
+	}
+
+	/// The package-info class of `package`, which carries the package's annotations. `package` is an internal name,
+	/// e.g. `com/example`.
+	pub fn package_info(package: &str) -> Result<Self, IRClassfileError> {
+		let mut builder = Self::new(&format!("{package}/package-info"))?;
+		builder.access_flags(ClassAccessFlags::INTERFACE | ClassAccessFlags::ABSTRACT | ClassAccessFlags::SYNTHETIC);
+		Ok(builder)
+	}
+
+	/// A module-info class. It still needs a `Module` attribute describing the module.
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.1-200-E.2
+	pub fn module_info() -> Result<Self, IRClassfileError> {
+		let mut builder = Self::new("module-info")?;
+		builder
+			.access_flags(ClassAccessFlags::MODULE)
+			.version(ClassFileVersion { major: 53, minor: 0 })
+			.class
+			.super_class = None;
+		Ok(builder)
+	}
+
+	pub fn version(&mut self, version: ClassFileVersion) -> &mut Self {
+		self.class.version = version;
+		self
+	}
+
+	pub fn access_flags(&mut self, flags: ClassAccessFlags) -> &mut Self {
+		self.class.access_flags = flags;
+		self
+	}
+
+	/// `None` only for java/lang/Object and module-info.
+	pub fn super_class(&mut self, name: Option<&str>) -> Result<&mut Self, IRClassfileError> {
+		self.class.super_class = name.map(|name| self.class_ref(name)).transpose()?;
+		Ok(self)
+	}
+
+	pub fn interface(&mut self, name: &str) -> Result<&mut Self, IRClassfileError> {
+		let interface = self.class_ref(name)?;
+		self.class.interfaces.push(interface);
+		Ok(self)
+	}
+
+	pub fn field(
+		&mut self,
+		access_flags: FieldAccessFlags,
+		name: &str,
+		descriptor: &str,
+		attributes: impl IntoIterator<Item = IRAttribute>,
+	) -> Result<&mut Self, IRClassfileError> {
+		let field = IRFieldInfo {
+			access_flags,
+			name: self.utf8(name)?,
+			descriptor: self.utf8(descriptor)?,
+			attributes: self.attributes(attributes)?,
+		};
+		self.class.fields.push(field);
+		Ok(self)
+	}
+
+	pub fn method(
+		&mut self,
+		access_flags: MethodAccessFlags,
+		name: &str,
+		descriptor: &str,
+		attributes: impl IntoIterator<Item = IRAttribute>,
+	) -> Result<&mut Self, IRClassfileError> {
+		let method = IRMethodInfo {
+			access_flags,
+			name: self.utf8(name)?,
+			descriptor: self.utf8(descriptor)?,
+			attributes: self.attributes(attributes)?,
+		};
+		self.class.methods.push(method);
+		Ok(self)
+	}
+
+	/// Attaches a class attribute.
+	pub fn attribute(&mut self, attr: IRAttribute) -> Result<&mut Self, IRClassfileError> {
+		let attr = self.attribute_info(attr)?;
+		self.class.attributes.push(attr);
+		Ok(self)
+	}
+
+	/// Adds the attribute's name to the pool, for attributes nested somewhere the builder doesn't reach, like a Code
+	/// attribute's own attributes.
+	pub fn attribute_info(&mut self, attr: IRAttribute) -> Result<IRAttributeInfo, IRClassfileError> {
+		// An unknown attribute doesn't know its own name, see `custom_attribute`.
+		if let IRAttribute::Unknown(_) = attr {
+			return Err(IRClassfileError::UnnamedAttribute);
+		}
+
+		let name = attr.name();
+
+		Ok(IRAttributeInfo {
+			name: self.utf8(name)?,
+			length: 0,
+			attr,
+		})
+	}
+
+	/// An attribute the IR doesn't know, e.g. a tool's own metadata, as its raw payload.
+	pub fn custom_attribute(&mut self, name: &str, payload: Vec<u8>) -> Result<&mut Self, IRClassfileError> {
+		let attr = IRAttributeInfo {
+			name: self.utf8(name)?,
+			length: payload.len() as u32,
+			attr: IRAttribute::Unknown(payload),
+		};
+		self.class.attributes.push(attr);
+		Ok(self)
+	}
+
+	/// The constant pool, to add the entries attributes and code refer to.
+	pub fn cp(&mut self) -> &mut Vec<IRCpTag> {
+		&mut self.class.cp
+	}
+
+	pub fn build(self) -> IRClassFile {
+		self.class
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, IRClassfileError> {
+		self.class.to_bytes()
+	}
+
+	fn utf8(&mut self, value: &str) -> Result<CPUtf8Ref, IRClassfileError> {
+		let tag = IRCpTag::Utf8(Shared::new(value.to_string()));
+		let index = self.pool.find_or_add(&mut self.class.cp, tag)?;
+		CPUtf8Ref::from_cp(&self.class.cp, index)
+	}
+
+	fn class_ref(&mut self, name: &str) -> Result<CPClassRef, IRClassfileError> {
+		let tag = IRCpTag::Class(self.utf8(name)?);
+		let index = self.pool.find_or_add(&mut self.class.cp, tag)?;
+		CPClassRef::from_cp(&self.class.cp, index)
+	}
+
+	fn attributes(
+		&mut self,
+		attributes: impl IntoIterator<Item = IRAttribute>,
+	) -> Result<Attributes, IRClassfileError> {
+		attributes.into_iter().map(|attr| self.attribute_info(attr)).collect()
+	}
+}
+
+/// A position in code under construction. Made by `CodeBuilder::new_label`, only meaningful for that builder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(u32);
+
+#[derive(Debug, Clone)]
+enum CodeItem {
+	Insn(Instructions),
+	Branch {
+		opcode: u8,
+		target: Label,
+	},
+	TableSwitch {
+		low: i32,
+		default: Label,
+		targets: Vec<Label>,
+	},
+	LookupSwitch {
+		default: Label,
+		pairs: Vec<(i32, Label)>,
+	},
+}
+
+#[derive(Debug, Clone)]
+struct Handler {
+	start: Label,
+	end: Label,
+	handler: Label,
+	catch_type: u16,
+}
+
+/// Builds a Code attribute. Instructions go in as `Instructions`, except anything that jumps: branches and switches
+/// target labels instead of offsets and go through `branch` and the switch methods.
+#[derive(Debug)]
+pub struct CodeBuilder<'cp> {
+	cp: &'cp mut Vec<IRCpTag>,
+	pool: PoolIndex,
+	items: Vec<CodeItem>,
+	// The item each label is bound before, `items.len()` for the end of the code.
+	labels: Vec<Option<usize>>,
+	handlers: Vec<Handler>,
+	max_stack: u16,
+	max_locals: u16,
+}
+
+impl<'cp> CodeBuilder<'cp> {
+	/// The longest code array a method can have.
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.3
+	pub const MAX_CODE_LENGTH: u64 = 65535;
+
+	pub fn new(cp: &'cp mut Vec<IRCpTag>) -> Self {
+		Self {
+			cp,
+			pool: PoolIndex::new(),
+			items: Vec::new(),
+			labels: Vec::new(),
+			handlers: Vec::new(),
+			max_stack: 0,
+			max_locals: 0,
+		}
+	}
+
+	pub fn max_stack(&mut self, max_stack: u16) -> &mut Self {
+		self.max_stack = max_stack;
+		self
+	}
+
+	pub fn max_locals(&mut self, max_locals: u16) -> &mut Self {
+		self.max_locals = max_locals;
+		self
+	}
+
+	/// A label to jump to, bound to a position later with `bind`.
+	pub fn new_label(&mut self) -> Label {
+		self.labels.push(None);
+		Label(self.labels.len() as u32 - 1)
+	}
+
+	/// Binds `label` to the position of the next instruction.
+	pub fn bind(&mut self, label: Label) -> Result<&mut Self, IRClassfileError> {
+		let slot = &mut self.labels[label.0 as usize];
+		if slot.is_some() {
+			return Err(IRClassfileError::LabelBoundTwice(label.0));
+		}
+		*slot = Some(self.items.len());
+		Ok(self)
+	}
+
+	/// A label bound to the position of the next instruction, e.g. for a loop head.
+	pub fn mark(&mut self) -> Label {
+		let label = self.new_label();
+		self.labels[label.0 as usize] = Some(self.items.len());
+		label
+	}
+
+	/// Appends an instruction that doesn't jump. Offsets in jumps passed here are kept as they are, which is only right
+	/// if nothing between the jump and its target changes size.
+	pub fn insn(&mut self, insn: Instructions) -> &mut Self {
+		self.items.push(CodeItem::Insn(insn));
+		self
+	}
+
+	/// Appends a jump to `target`: any `if<cond>`, `goto` or `jsr`, by opcode. `goto` and `jsr` become `goto_w` and
+	/// `jsr_w` if the target is too far away, conditional jumps are inverted to skip over a `goto_w`.
+	pub fn branch(&mut self, opcode: u8, target: Label) -> Result<&mut Self, IRClassfileError> {
+		if !matches!(
+			opcode,
+			Opcodes::IFEQ..=Opcodes::JSR | Opcodes::IFNULL | Opcodes::IFNONNULL | Opcodes::GOTO_W | Opcodes::JSR_W
+		) {
+			return Err(IRClassfileError::InvalidTag {
+				kind: "branch opcode",
+				value: opcode,
+			});
+		}
+		self.items.push(CodeItem::Branch { opcode, target });
+		Ok(self)
+	}
+
+	/// A `tableswitch` over `low..low + targets.len()`.
+	pub fn table_switch(&mut self, low: i32, default: Label, targets: Vec<Label>) -> &mut Self {
+		self.items.push(CodeItem::TableSwitch { low, default, targets });
+		self
+	}
+
+	/// A `lookupswitch`; the pairs are sorted by key when the code is built.
+	pub fn lookup_switch(&mut self, default: Label, pairs: Vec<(i32, Label)>) -> &mut Self {
+		self.items.push(CodeItem::LookupSwitch { default, pairs });
+		self
+	}
+
+	/// Handles exceptions thrown from `start` up to `end` with the code at `handler`. `catch_type` is an internal
+	/// name, `None` catches everything like a `finally` block does. Handlers are tried in the order they're added.
+	pub fn try_catch(
+		&mut self,
+		start: Label,
+		end: Label,
+		handler: Label,
+		catch_type: Option<&str>,
+	) -> Result<&mut Self, IRClassfileError> {
+		let catch_type = match catch_type {
+			Some(name) => CPClassRef::find_or_add(self.cp, name)?.index,
+			None => 0,
+		};
+		self.handlers.push(Handler {
+			start,
+			end,
+			handler,
+			catch_type,
+		});
+		Ok(self)
+	}
+
+	/// The constant pool, to add the entries instructions refer to.
+	pub fn cp(&mut self) -> &mut Vec<IRCpTag> {
+		self.cp
+	}
+
+	/// Lays out the code and resolves every label.
+	pub fn build(self) -> Result<CodeAttribute, IRClassfileError> {
+		self.build_with_labels().map(|(code, _)| code)
+	}
+
+	/// Like `build`, also telling where each label ended up, for attributes that refer to pcs.
+	pub fn build_with_labels(self) -> Result<(CodeAttribute, LabelPositions), IRClassfileError> {
+		let Self {
+			cp,
+			mut pool,
+			mut items,
+			labels,
+			handlers,
+			max_stack,
+			max_locals,
+		} = self;
+
+		for item in &mut items {
+			if let CodeItem::LookupSwitch { pairs, .. } = item {
+				pairs.sort_by_key(|(key, _)| *key);
+			}
+		}
+
+		// Jumps start out short and are widened until they all fit. Widening only ever makes code longer, so this
+		// settles.
+		let mut wide = items
+			.iter()
+			.map(|item| {
+				matches!(
+					item,
+					CodeItem::Branch {
+						opcode: Opcodes::GOTO_W | Opcodes::JSR_W,
+						..
+					}
+				)
+			})
+			.collect::<Vec<_>>();
+		let (pcs, targets) = loop {
+			let pcs = layout(cp, &mut pool, &items, &wide)?;
+			let targets = labels
+				.iter()
+				.enumerate()
+				.map(|(label, item)| {
+					item.map(|item| pcs[item])
+						.ok_or(IRClassfileError::UnboundLabel(label as u32))
+				})
+				.collect::<Result<Vec<_>, _>>()?;
+
+			let mut changed = false;
+			for (i, item) in items.iter().enumerate() {
+				if let CodeItem::Branch { target, .. } = item {
+					let offset = targets[target.0 as usize] as i64 - pcs[i] as i64;
+					if !wide[i] && i16::try_from(offset).is_err() {
+						wide[i] = true;
+						changed = true;
+					}
+				}
+			}
+			if !changed {
+				break (pcs, targets);
+			}
+		};
+
+		let offset = |pc: u32, label: &Label| (targets[label.0 as usize] as i64 - pc as i64) as i32;
+		let mut code = Vec::with_capacity(pcs[items.len()] as usize);
+		for (i, item) in items.into_iter().enumerate() {
+			let pc = pcs[i];
+			match item {
+				CodeItem::Insn(insn) => insn.write_with(&mut pool, cp, pc, &mut code)?,
+				CodeItem::Branch { opcode, target } => write_branch(&mut code, opcode, wide[i], offset(pc, &target)),
+				CodeItem::TableSwitch { low, default, targets } => Instructions::TABLESWITCH {
+					default: offset(pc, &default),
+					low,
+					high: low + targets.len() as i32 - 1,
+					offsets: targets.iter().map(|target| offset(pc, target)).collect(),
+				}
+				.write_with(&mut pool, cp, pc, &mut code)?,
+				CodeItem::LookupSwitch { default, pairs } => Instructions::LOOKUPSWITCH {
+					default: offset(pc, &default),
+					pairs: pairs.iter().map(|(key, target)| (*key, offset(pc, target))).collect(),
+				}
+				.write_with(&mut pool, cp, pc, &mut code)?,
+			}
+		}
+
+		let exception_table = handlers
+			.iter()
+			.map(|handler| CodeAttributeException {
+				start_pc: targets[handler.start.0 as usize] as u16,
+				end_pc: targets[handler.end.0 as usize] as u16,
+				handler_pc: targets[handler.handler.0 as usize] as u16,
+				catch_type: handler.catch_type,
+			})
+			.collect();
+
+		let code = CodeAttribute {
+			max_stack,
+			max_locals,
+			code,
+			exception_table,
+			attributes: Attributes::new(),
+		};
+		Ok((code, LabelPositions(targets)))
+	}
+}
+
+/// The pcs labels were bound to, from `CodeBuilder::build_with_labels`.
+#[derive(Debug, Clone)]
+pub struct LabelPositions(Vec<u32>);
+
+impl LabelPositions {
+	pub fn pc(&self, label: Label) -> u32 {
+		self.0[label.0 as usize]
+	}
+}
+
+/// The pc of every item, plus the end of the code.
+fn layout(
+	cp: &mut Vec<IRCpTag>,
+	pool: &mut PoolIndex,
+	items: &[CodeItem],
+	wide: &[bool],
+) -> Result<Vec<u32>, IRClassfileError> {
+	let mut pcs = Vec::with_capacity(items.len() + 1);
+	let mut pc = 0u32;
+	let mut scratch = Vec::new();
+	for (item, wide) in items.iter().zip(wide) {
+		pcs.push(pc);
+		let padding = (4 - (pc + 1) % 4) % 4;
+		pc += match item {
+			CodeItem::Insn(insn) => {
+				scratch.clear();
+				insn.write_with(pool, cp, pc, &mut scratch)?;
+				scratch.len() as u32
+			}
+			CodeItem::Branch { opcode, .. } => match (wide, *opcode) {
+				(false, _) => 3,
+				(true, Opcodes::GOTO | Opcodes::GOTO_W | Opcodes::JSR | Opcodes::JSR_W) => 5,
+				// The inverted jump, then a goto_w.
+				(true, _) => 3 + 5,
+			},
+			CodeItem::TableSwitch { targets, .. } => 1 + padding + 12 + 4 * targets.len() as u32,
+			CodeItem::LookupSwitch { pairs, .. } => 1 + padding + 8 + 8 * pairs.len() as u32,
+		};
+		Limits::check("code length", CodeBuilder::MAX_CODE_LENGTH, pc as u64)?;
+	}
+	pcs.push(pc);
+	Ok(pcs)
+}
+
+fn write_branch(code: &mut Vec<u8>, opcode: u8, wide: bool, offset: i32) {
+	let mut write = |opcode: u8, bytes: &[u8]| {
+		code.push(opcode);
+		code.extend_from_slice(bytes);
+	};
+	match (wide, opcode) {
+		(false, _) => write(opcode, &(offset as i16).to_be_bytes()),
+		(true, Opcodes::GOTO | Opcodes::GOTO_W) => write(Opcodes::GOTO_W, &offset.to_be_bytes()),
+		(true, Opcodes::JSR | Opcodes::JSR_W) => write(Opcodes::JSR_W, &offset.to_be_bytes()),
+		(true, _) => {
+			// if<not cond> +8; goto_w target. The goto_w is 3 bytes further along than the jump.
+			write(invert_condition(opcode), &8i16.to_be_bytes());
+			write(Opcodes::GOTO_W, &(offset - 3).to_be_bytes());
+		}
+	}
+}
+
+// Conditional jumps come in pairs of opposite conditions, ifeq/ifne up to if_acmpeq/if_acmpne and ifnull/ifnonnull.
+fn invert_condition(opcode: u8) -> u8 {
+	match opcode {
+		Opcodes::IFNULL => Opcodes::IFNONNULL,
+		Opcodes::IFNONNULL => Opcodes::IFNULL,
+		_ => Opcodes::IFEQ + ((opcode - Opcodes::IFEQ) ^ 1),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use smallvec::SmallVec;
+
+	use super::*;
+	use crate::{
+		attribute::{CodeAttribute, ConstantValueAttribute, ModuleRequiresEntry, RuntimeAnnotation},
+		class_pool::{CPConstValueRefKind, CPMethodRef, CPModuleInfoRef},
+		flags::{ModuleFlags, RequiresFlags},
+	};
+
+	#[test]
+	fn class_round_trips() {
+		let mut builder = ClassBuilder::new("com/example/Greeter").unwrap();
+		let object_init = CPMethodRef::find_or_add(builder.cp(), "java/lang/Object", "<init>", "()V").unwrap();
+		let [hi, lo] = object_init.index.to_be_bytes();
+		let code = CodeAttribute {
+			max_stack: 1,
+			max_locals: 1,
+			// aload_0, invokespecial Object.<init>, return
+			code: vec![0x2A, 0xB7, hi, lo, 0xB1],
+			exception_table: SmallVec::new(),
+			attributes: Attributes::new(),
+		};
+		let greeting =
+			ConstantValueAttribute::find_or_add(builder.cp(), CPConstValueRefKind::String("hello".to_string().into()))
+				.unwrap();
+
+		builder
+			.access_flags(ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER | ClassAccessFlags::ABSTRACT)
+			.interface("java/lang/Runnable")
+			.unwrap()
+			.field(
+				FieldAccessFlags::PUBLIC | FieldAccessFlags::STATIC | FieldAccessFlags::FINAL,
+				"GREETING",
+				"Ljava/lang/String;",
+				[IRAttribute::ConstantValue(greeting)],
+			)
+			.unwrap()
+			.method(MethodAccessFlags::PUBLIC, "<init>", "()V", [IRAttribute::Code(code)])
+			.unwrap()
+			.method(
+				MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT,
+				"run",
+				"()V",
+				[],
+			)
+			.unwrap()
+			.custom_attribute("Marker", vec![1, 2, 3])
+			.unwrap();
+
+		let bytes = builder.to_bytes().unwrap();
+		let class = IRClassFile::read(&bytes).unwrap();
+		assert_eq!(class.class_name(), "com/example/Greeter");
+		assert_eq!(class.super_name(), Some("java/lang/Object"));
+		assert_eq!(class.interface_names().collect::<Vec<_>>(), ["java/lang/Runnable"]);
+		assert_eq!(
+			class
+				.find_field("GREETING", "Ljava/lang/String;")
+				.unwrap()
+				.constant_value(),
+			Some(CPConstValueRefKind::String("hello".to_string().into()))
+		);
+		assert_eq!(
+			class.find_method("<init>", "()V").unwrap().code().unwrap().code.len(),
+			5
+		);
+		assert!(class.find_method("run", "()V").unwrap().code().is_none());
+		assert!(class.attributes().by_name("Marker").is_some());
+		assert_eq!(class.to_bytes().unwrap(), bytes);
+	}
+
+	#[test]
+	fn insertion_order_is_kept() {
+		let build = || {
+			let mut builder = ClassBuilder::new("p/Ordered").unwrap();
+			for name in ["Zeta", "Alpha", "Mid"] {
+				builder.custom_attribute(name, Vec::new()).unwrap();
+			}
+			for name in ["z", "a"] {
+				builder
+					.method(MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT, name, "()V", [])
+					.unwrap();
+			}
+			builder.to_bytes().unwrap()
+		};
+		let bytes = build();
+		assert_eq!(bytes, build());
+
+		let mut class = IRClassFile::read(&bytes).unwrap();
+		let attributes = class
+			.attributes
+			.iter()
+			.map(|attr| attr.name.data.to_string())
+			.collect::<Vec<_>>();
+		assert_eq!(attributes, ["Zeta", "Alpha", "Mid"]);
+		assert_eq!(
+			class.methods.iter().map(|method| method.name()).collect::<Vec<_>>(),
+			["z", "a"]
+		);
+
+		let zeta = CPUtf8Ref::find_or_add(&mut class.cp, "Zeta").unwrap().index;
+		let added = CPUtf8Ref::find_or_add(&mut class.cp, "Added").unwrap().index;
+		assert_eq!(added as usize, class.cp.len());
+		assert_eq!(CPUtf8Ref::find_or_add(&mut class.cp, "Zeta").unwrap().index, zeta);
+	}
+
+	#[test]
+	fn package_and_module_info() {
+		let mut builder = ClassBuilder::package_info("com/example").unwrap();
+		let ty = CPUtf8Ref::find_or_add(builder.cp(), "Ljava/lang/Deprecated;").unwrap();
+		builder
+			.attribute(IRAttribute::RuntimeVisibleAnnotations {
+				annotations: vec![RuntimeAnnotation {
+					ty,
+					pairs: SmallVec::new(),
+				}],
+			})
+			.unwrap();
+		let class = IRClassFile::read(&builder.to_bytes().unwrap()).unwrap();
+		assert!(class.is_package_info());
+		assert_eq!(class.package_annotations().count(), 1);
+
+		let mut builder = ClassBuilder::module_info().unwrap();
+		let module_name = CPModuleInfoRef::find_or_add(builder.cp(), "com.example").unwrap();
+		let java_base = CPModuleInfoRef::find_or_add(builder.cp(), "java.base").unwrap();
+		builder
+			.attribute(IRAttribute::Module {
+				module_name,
+				module_flags: ModuleFlags::empty(),
+				module_version: None,
+				requires: vec![ModuleRequiresEntry {
+					module: java_base,
+					flags: RequiresFlags::MANDATED,
+					version: None,
+				}],
+				exports: Vec::new(),
+				opens: Vec::new(),
+				uses: Vec::new(),
+				provides: Vec::new(),
+			})
+			.unwrap();
+		let class = IRClassFile::read(&builder.to_bytes().unwrap()).unwrap();
+		let module = class.module_info().unwrap();
+		assert_eq!(module.name, "com.example");
+		assert_eq!(module.requires[0].module.data.data.as_str(), "java.base");
+		assert_eq!(class.super_name(), None);
+	}
+
+	#[test]
+	fn code_labels() {
+		let mut cp = Vec::new();
+		let mut code = CodeBuilder::new(&mut cp);
+		let (far, end, handler) = (code.new_label(), code.new_label(), code.new_label());
+		let head = code.mark();
+		code.insn(Instructions::ILOAD(0))
+			.branch(Opcodes::IFEQ, far)
+			.unwrap()
+			.insn(Instructions::IINC { index: 0, r#const: -1 })
+			.branch(Opcodes::GOTO, head)
+			.unwrap();
+		for _ in 0..40000 {
+			code.insn(Instructions::NOP);
+		}
+		code.bind(far)
+			.unwrap()
+			.insn(Instructions::RETURN)
+			.bind(handler)
+			.unwrap()
+			.insn(Instructions::ATHROW)
+			.bind(end)
+			.unwrap()
+			.try_catch(head, end, handler, Some("java/lang/Exception"))
+			.unwrap();
+		let code = code.build().unwrap();
+
+		let instructions = Instructions::read_all(&cp, &code.code).unwrap();
+		let far_pc = instructions[instructions.len() - 2].0;
+		// iload_0, ifne +8, goto_w far, iinc, goto head
+		assert!(matches!(instructions[1], (1, Instructions::IFNE(8))));
+		assert_eq!(instructions[2].1.branch_targets(instructions[2].0), [far_pc]);
+		assert_eq!(instructions[4].1.branch_targets(instructions[4].0), [0]);
+		assert_eq!(code.exception_table[0].end_pc as usize, code.code.len());
+		assert_eq!(code.exception_table[0].handler_pc as u32, far_pc + 1);
+
+		let mut code = CodeBuilder::new(&mut cp);
+		let label = code.new_label();
+		code.branch(Opcodes::GOTO, label).unwrap();
+		assert!(matches!(code.build(), Err(IRClassfileError::UnboundLabel(0))));
+	}
+}
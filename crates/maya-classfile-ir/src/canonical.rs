@@ -0,0 +1,220 @@
+// One way of writing a class, for reproducible builds and for caching transformed classes by their bytes. Writing the
+// same IR always gives the same bytes, but the IR keeps what the class was read or built with: a pool in whatever order
+// the compiler or the transforms added entries, with entries nothing uses anymore, attributes in any order and code
+// with `ldc_w` or wide forms where shorter ones would do. Two classes that only differ there come out the same once
+// canonicalized:
+//
+// - attributes are sorted by name, everywhere they're nested too
+// - the pool keeps only the entries the class uses, in the order the class is walked to write it, each entry
+//   followed by those it refers to. Constants an `ldc` loads come first, so their indices fit the instruction.
+// - code is laid out again in its shortest encoding, with its frames recomputed from version 50 on. Code with
+//   attributes a `Listing` doesn't carry, like type annotations, is left as it is.
+//
+// Attributes this crate doesn't parse may refer to the pool in ways that can't be seen, so a class with any of them
+// keeps its pool as it is. Canonicalizing a canonical class changes nothing.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.4
+
+use std::collections::BTreeSet;
+
+use crate::{
+	analysis::frames::{recompute_method_frames, ClassHierarchy},
+	attribute::{Attributes, IRAttribute, IRAttributeInfo},
+	class_pool::{CPUtf8Ref, IRClassfileError},
+	code::{Instructions, Opcodes},
+	listing::Listing,
+	transform::{ChangeLog, ChangeRecord, Transform},
+	IRClassFile,
+};
+
+impl IRClassFile {
+	/// Puts the class in its canonical form, see the module docs. `hierarchy` is for recomputing the frames of code
+	/// that got shorter.
+	pub fn canonicalize(&mut self, hierarchy: &dyn ClassHierarchy) -> Result<(), IRClassfileError> {
+		self.sort_attributes();
+		self.canonical_pool()?;
+		if self.shorten_code(hierarchy)? {
+			// Frames may have added entries, and shorter code may load more constants with `ldc`.
+			self.sort_attributes();
+			self.canonical_pool()?;
+		}
+		Ok(())
+	}
+
+	/// The bytes of the class in its canonical form, leaving the class as it is.
+	pub fn to_canonical_bytes(&self, hierarchy: &dyn ClassHierarchy) -> Result<Vec<u8>, IRClassfileError> {
+		let mut class = self.clone();
+		class.canonicalize(hierarchy)?;
+		class.to_bytes()
+	}
+
+	fn sort_attributes(&mut self) {
+		sort(&mut self.attributes);
+		for field in &mut self.fields {
+			sort(&mut field.attributes);
+		}
+		for method in &mut self.methods {
+			sort(&mut method.attributes);
+		}
+	}
+
+	fn canonical_pool(&mut self) -> Result<(), IRClassfileError> {
+		let Some(uses) = self.pool_uses()? else {
+			return Ok(());
+		};
+		let (mut ldc, mut ldc_w) = (BTreeSet::new(), BTreeSet::new());
+		for method in &self.methods {
+			let Some(code) = method.code() else {
+				continue;
+			};
+			for (pc, _) in Instructions::read_all(&self.cp, &code.code)? {
+				let at = pc as usize + 1;
+				match code.code[pc as usize] {
+					Opcodes::LDC => ldc.insert(u16::from(code.code[at])),
+					Opcodes::LDC_W => ldc_w.insert(u16::from_be_bytes([code.code[at], code.code[at + 1]])),
+					_ => continue,
+				};
+			}
+		}
+
+		let mut order = uses
+			.iter()
+			.filter(|index| ldc.contains(index))
+			.copied()
+			.collect::<Vec<_>>();
+		order.extend(
+			uses.iter()
+				.filter(|index| ldc_w.contains(index) && !ldc.contains(index)),
+		);
+		order.extend(
+			uses.iter()
+				.filter(|index| !ldc.contains(index) && !ldc_w.contains(index)),
+		);
+		self.reorder_pool(&order)
+	}
+
+	/// Lays out the code of every method again, returns whether any changed.
+	fn shorten_code(&mut self, hierarchy: &dyn ClassHierarchy) -> Result<bool, IRClassfileError> {
+		let class_name = self.class_name().to_string();
+		let mut changed = false;
+		for method in &mut self.methods {
+			let Some(code) = method.code() else {
+				continue;
+			};
+			let carried = code.attributes.iter().all(|attr| {
+				matches!(
+					attr.attr,
+					IRAttribute::LineNumberTable(_)
+						| IRAttribute::LocalVariableTable { .. }
+						| IRAttribute::LocalVariableTypeTable { .. }
+						| IRAttribute::StackMapTable(_)
+				)
+			});
+			if !carried {
+				continue;
+			}
+			let mut shortened = Listing::from_code(&self.cp, code)?.to_code(&mut self.cp)?;
+			if shortened.code == code.code {
+				continue;
+			}
+
+			sort(&mut shortened.attributes);
+			let code_name = CPUtf8Ref::find_or_add(&mut self.cp, "Code")?;
+			let slot = method
+				.attributes
+				.iter_mut()
+				.find(|attr| matches!(attr.attr, IRAttribute::Code(_)))
+				.expect("the method has code");
+			*slot = IRAttributeInfo {
+				name: code_name,
+				length: 0,
+				attr: IRAttribute::Code(shortened),
+			};
+			if self.version.supports_stack_map_table() {
+				recompute_method_frames(&mut self.cp, &class_name, method, hierarchy)?;
+			}
+			changed = true;
+		}
+		Ok(changed)
+	}
+}
+
+/// Canonicalizes every class, best run last.
+pub struct Canonicalizer<H> {
+	hierarchy: H,
+}
+
+impl<H: ClassHierarchy> Canonicalizer<H> {
+	pub fn new(hierarchy: H) -> Self {
+		Self { hierarchy }
+	}
+}
+
+impl<H: ClassHierarchy> Transform for Canonicalizer<H> {
+	fn name(&self) -> &str {
+		"canonicalize"
+	}
+
+	fn apply(&mut self, class: &mut IRClassFile, log: &mut ChangeLog) -> Result<(), IRClassfileError> {
+		let before = class.to_bytes()?;
+		class.canonicalize(&self.hierarchy)?;
+		let after = class.to_bytes()?;
+		if before != after {
+			log.record(ChangeRecord::Custom {
+				kind: "canonicalized".to_string(),
+				detail: format!("{} -> {} bytes", before.len(), after.len()),
+			});
+		}
+		Ok(())
+	}
+}
+
+fn sort(attributes: &mut Attributes) {
+	attributes.sort_by(|a, b| a.name.data.cmp(&b.name.data));
+	for info in attributes.iter_mut() {
+		match &mut info.attr {
+			IRAttribute::Code(code) => sort(&mut code.attributes),
+			IRAttribute::Record { components } => {
+				for component in components {
+					sort(&mut component.attributes);
+				}
+			}
+			_ => {}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{analysis::frames::KnownClasses, asm::assemble, class_pool::IRCpTag};
+
+	#[test]
+	fn canonical_bytes() {
+		let source = |extra: &str| {
+			format!(
+				".class public super p/Main\n.source \"Main.java\"\n.deprecated\n.field static count I\n.method public \
+				 static run()I\n{extra}ldc \"hello\"\npop\ngetstatic p/Main/count I\nireturn\n.end method"
+			)
+		};
+		let first = assemble(&source("")).unwrap();
+		// The same class with a constant it doesn't load anymore first in its pool, `ldc_w` where `ldc` does and its
+		// attributes the other way around.
+		let mut second = assemble(&source("ldc \"unused\"\npop\n")).unwrap();
+		let code = second.methods[0].attributes.code_mut().unwrap();
+		let index = code.code[4];
+		code.code.splice(..5, [Opcodes::LDC_W, 0, index]);
+		second.attributes.reverse();
+		assert_ne!(second.to_bytes().unwrap(), first.to_bytes().unwrap());
+
+		let hierarchy = KnownClasses::new();
+		let canonical = first.to_canonical_bytes(&hierarchy).unwrap();
+		assert_eq!(second.to_canonical_bytes(&hierarchy).unwrap(), canonical);
+
+		let mut class = IRClassFile::read(&canonical).unwrap();
+		class.canonicalize(&hierarchy).unwrap();
+		assert_eq!(class.to_bytes().unwrap(), canonical);
+		// Loaded by `ldc`, so it comes first.
+		assert!(matches!(&class.cp[0], IRCpTag::String(_)));
+		assert_eq!(class.attributes[0].name.data.as_str(), "Deprecated");
+	}
+}
@@ -1,22 +1,214 @@
-use std::{rc::Rc, string::FromUtf8Error};
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, LowerExp, Write},
+	string::FromUtf8Error,
+};
 
 use maya_bytes::BytesError;
-use maya_classfile_io::class_pool::IOCpTag;
+use maya_classfile_io::{
+	class_pool::IOCpTag,
+	limits::{LimitExceeded, Limits},
+	IOClassfileError,
+};
 use maya_mutf8::MUTFError;
 use thiserror::Error;
 
+use crate::{ClassFileVersion, Shared};
+
 #[derive(Debug, Error)]
 pub enum IRClassfileError {
+	#[error("{0}")]
+	IO(#[from] IOClassfileError),
 	#[error("{0}")]
 	Mutf8(#[from] MUTFError),
 	#[error("{0}")]
 	Bytes(#[from] BytesError),
 	#[error("{0}")]
+	LimitExceeded(#[from] LimitExceeded),
+	#[error("{0}")]
 	Utf8(#[from] FromUtf8Error),
+	#[error("Invalid constant pool index: {0}")]
+	InvalidCpIndex(u16),
+	#[error("Expected {expected} at constant pool index {index}")]
+	UnexpectedCpTag { index: u16, expected: &'static str },
+	#[error("Invalid {kind}: {value}")]
+	InvalidTag { kind: &'static str, value: u8 },
+	#[error("Invalid descriptor: {0}")]
+	InvalidDescriptor(String),
+	#[error("Invalid signature: {0}")]
+	InvalidSignature(String),
+	#[error("tableswitch high bound {high} is below its low bound {low}")]
+	InvalidTableSwitch { low: i32, high: i32 },
+	#[error("Attribute {name} declares {declared} bytes but {consumed} were parsed")]
+	AttributeLengthMismatch { name: String, declared: u32, consumed: u64 },
+	#[error("Unknown attributes can only be added together with their name")]
+	UnnamedAttribute,
+	#[error("Label {0} is used but never bound")]
+	UnboundLabel(u32),
+	#[error("Label {0} is bound twice")]
+	LabelBoundTwice(u32),
+	#[error("Instruction at pc {pc} refers to {target}, which isn't the start of an instruction")]
+	InvalidBranchTarget { pc: u32, target: u32 },
+	#[error("The {what} refers to pc {pc}, which isn't the start of an instruction")]
+	InvalidCodePosition { what: &'static str, pc: u32 },
+	#[error("Invalid stack map frame at pc {pc}: {reason}")]
+	InvalidStackMapFrame { pc: u32, reason: &'static str },
+	#[error("Can't compute frames at pc {pc}: {reason}")]
+	FrameComputation { pc: u32, reason: &'static str },
+	#[error("Operand stack underflow at pc {pc}")]
+	StackUnderflow { pc: u32 },
+	#[error("Operand stack deeper than max_stack {max_stack} at pc {pc}")]
+	StackOverflow { pc: u32, max_stack: u16 },
+	#[error("Expected {expected} at pc {pc}, found {found}")]
+	StackTypeMismatch {
+		pc: u32,
+		expected: &'static str,
+		found: &'static str,
+	},
+	#[error("Stack heights {a} and {b} meet at pc {pc}")]
+	StackHeightMismatch { pc: u32, a: u16, b: u16 },
+	#[error("Local {index} at pc {pc} is out of max_locals {max_locals}")]
+	LocalOutOfRange { pc: u32, index: u16, max_locals: u16 },
+	#[error("Execution falls off the end of the code at pc {pc}")]
+	FallsOffCode { pc: u32 },
+	#[error("Bootstrap method index {0} is out of range")]
+	InvalidBootstrapIndex(u16),
+	#[error("Invalid arguments to bootstrap method {bootstrap}: {reason}")]
+	InvalidBootstrapArguments { bootstrap: String, reason: &'static str },
+	#[error("The class is version {version}, but has {what}")]
+	UnsupportedByVersion { version: ClassFileVersion, what: String },
+	#[error("Line {line}: {message}")]
+	Assembly { line: usize, message: String },
+}
+
+pub fn cp_get(cp: &[IRCpTag], index: u16) -> Result<&IRCpTag, IRClassfileError> {
+	match index {
+		0 => Err(IRClassfileError::InvalidCpIndex(index)),
+		_ => cp
+			.get(index as usize - 1)
+			.ok_or(IRClassfileError::InvalidCpIndex(index)),
+	}
+}
+
+/// The index of an entry equal to `tag`, appending `tag` if there's none yet. Entries are never moved, so indexes
+/// handed out stay valid, and adding the same entries in the same order always gives the same pool.
+///
+/// This looks through the whole pool, to add many entries use a `PoolIndex`.
+pub fn cp_find_or_add(cp: &mut Vec<IRCpTag>, tag: IRCpTag) -> Result<u16, IRClassfileError> {
+	if let Some(i) = cp.iter().position(|existing| existing.same_entry(&tag)) {
+		return Ok(i as u16 + 1);
+	}
+	cp_push(cp, tag)
+}
+
+/// `cp_find_or_add` with a hash map from the entries to their index, kept alongside the pool. Entries appended since
+/// the last lookup are indexed first, however they were added. An entry replaced in place is caught when what the map
+/// points at doesn't match anymore, and the map is built again.
+#[derive(Debug, Clone, Default)]
+pub struct PoolIndex {
+	entries: HashMap<EntryKey, u16>,
+	indexed: usize,
+}
+
+impl PoolIndex {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn find_or_add(&mut self, cp: &mut Vec<IRCpTag>, tag: IRCpTag) -> Result<u16, IRClassfileError> {
+		let key = EntryKey::of(&tag);
+		if let Some(index) = self.find(cp, &key) {
+			if cp[index as usize - 1].same_entry(&tag) {
+				return Ok(index);
+			}
+			*self = Self::default();
+			if let Some(index) = self.find(cp, &key) {
+				return Ok(index);
+			}
+		}
+		cp_push(cp, tag)
+	}
+
+	fn find(&mut self, cp: &[IRCpTag], key: &EntryKey) -> Option<u16> {
+		if cp.len() < self.indexed {
+			*self = Self::default();
+		}
+		for (i, entry) in cp.iter().enumerate().skip(self.indexed) {
+			// The first of equal entries, like `cp_find_or_add`.
+			self.entries.entry(EntryKey::of(entry)).or_insert(i as u16 + 1);
+		}
+		self.indexed = cp.len();
+		self.entries.get(key).copied()
+	}
+}
+
+// What `same_entry` compares.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum EntryKey {
+	Utf8(Shared<String>),
+	// Numbers by their tag and bits.
+	Value(u8, u64),
+	// Everything else by its tag and the indexes it points at.
+	Indexes(u8, u16, u16),
+}
+
+impl EntryKey {
+	fn of(tag: &IRCpTag) -> Self {
+		match tag {
+			IRCpTag::Unusable => Self::Indexes(0, 0, 0),
+			IRCpTag::Utf8(value) => Self::Utf8(value.clone()),
+			IRCpTag::Integer(value) => Self::Value(3, *value as u32 as u64),
+			IRCpTag::Float(value) => Self::Value(4, value.to_bits().into()),
+			IRCpTag::Long(value) => Self::Value(5, *value as u64),
+			IRCpTag::Double(value) => Self::Value(6, value.to_bits()),
+			IRCpTag::Class(name) => Self::Indexes(7, name.index, 0),
+			IRCpTag::String(value) => Self::Indexes(8, value.index, 0),
+			IRCpTag::FieldRef {
+				class_index,
+				name_and_ty,
+			} => Self::Indexes(9, *class_index, name_and_ty.index),
+			IRCpTag::MethodRef {
+				class_index,
+				name_and_ty,
+			} => Self::Indexes(10, *class_index, name_and_ty.index),
+			IRCpTag::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
+			} => Self::Indexes(11, *class_index, name_and_ty.index),
+			IRCpTag::NameAndType { name, descriptor } => Self::Indexes(12, name.index, descriptor.index),
+			IRCpTag::MethodHandle {
+				ref_kind, ref_index, ..
+			} => Self::Indexes(15, ref_kind.clone() as u16, *ref_index),
+			IRCpTag::MethodType(descriptor) => Self::Indexes(16, descriptor.index, 0),
+			IRCpTag::InvokeDynamic {
+				bootstrap_method_attr_index,
+				name_and_ty,
+			} => Self::Indexes(18, *bootstrap_method_attr_index, name_and_ty.index),
+			IRCpTag::Module { name } => Self::Indexes(19, name.index, 0),
+			IRCpTag::Package { name } => Self::Indexes(20, name.index, 0),
+		}
+	}
+}
+
+fn cp_push(cp: &mut Vec<IRCpTag>, tag: IRCpTag) -> Result<u16, IRClassfileError> {
+	let wide = matches!(tag, IRCpTag::Long(_) | IRCpTag::Double(_));
+	let needed = if wide { 2 } else { 1 };
+	Limits::check(
+		"constant pool entries",
+		u64::from(u16::MAX - 1),
+		(cp.len() + needed) as u64,
+	)?;
+	cp.push(tag);
+	let index = cp.len() as u16;
+	if wide {
+		cp.push(IRCpTag::Unusable);
+	}
+	Ok(index)
 }
 
 // https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-5.html#jvms-5.4.3.5
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum IRMethodRefKind {
 	GetField = 1,
@@ -30,9 +222,11 @@ pub enum IRMethodRefKind {
 	InvokeInterface,
 }
 
-impl IRMethodRefKind {
-	pub fn from(value: u8) -> IRMethodRefKind {
-		match value {
+impl TryFrom<u8> for IRMethodRefKind {
+	type Error = IRClassfileError;
+
+	fn try_from(value: u8) -> Result<Self, Self::Error> {
+		Ok(match value {
 			1 => Self::GetField,
 			2 => Self::GetStatic,
 			3 => Self::PutField,
@@ -42,29 +236,36 @@ impl IRMethodRefKind {
 			7 => Self::InvokeSpecial,
 			8 => Self::NewInvokeSpecial,
 			9 => Self::InvokeInterface,
-			_ => panic!("fuck you"),
-		}
+			_ => {
+				return Err(IRClassfileError::InvalidTag {
+					kind: "method handle reference kind",
+					value,
+				})
+			}
+		})
 	}
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CPConstValueRefKind {
 	Double(f64),
 	Float(f32),
 	Int(i32),
 	Long(i64),
-	String(Rc<String>),
+	String(Shared<String>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPConstValueRef {
 	pub index: u16,
 	pub kind: CPConstValueRefKind,
 }
 
 impl CPConstValueRef {
-	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Self {
-		match utf8_tag {
+	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
+		Ok(match utf8_tag {
 			IRCpTag::Double(data) => Self {
 				kind: CPConstValueRefKind::Double(*data),
 				index,
@@ -85,63 +286,87 @@ impl CPConstValueRef {
 				kind: CPConstValueRefKind::String(data.clone()),
 				index,
 			},
-			_ => panic!("trying to make CPConstValueRef from non-const tag. {utf8_tag:?}"),
-		}
+			_ => {
+				return Err(IRClassfileError::UnexpectedCpTag {
+					index,
+					expected: "constant value",
+				})
+			}
+		})
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index as usize - 1).expect("expected tag");
-		Self::new(index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(index, cp_get(cp, index)?)
 	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPUtf8Ref {
-	pub data: Rc<String>,
+	pub data: Shared<String>,
 	pub index: u16,
 }
 
 impl CPUtf8Ref {
-	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
 		match utf8_tag {
-			IRCpTag::Utf8(data) => Self {
+			IRCpTag::Utf8(data) => Ok(Self {
 				data: data.clone(),
 				index,
-			},
-			_ => panic!("trying to make CPUtf8Ref from non-utf8 tag. {utf8_tag:?}"),
+			}),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "Utf8",
+			}),
 		}
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index.saturating_sub(1) as usize).expect("expected tag");
-		Self::new(index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(index, cp_get(cp, index)?)
+	}
+
+	/// Reuses an existing entry for `value`, or appends one to the pool.
+	pub fn find_or_add(cp: &mut Vec<IRCpTag>, value: &str) -> Result<Self, IRClassfileError> {
+		let index = cp_find_or_add(cp, IRCpTag::Utf8(Shared::new(value.to_string())))?;
+		Self::from_cp(cp, index)
 	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPClassRef {
 	pub data: CPUtf8Ref,
 	pub index: u16,
 }
 
 impl CPClassRef {
-	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
 		match utf8_tag {
-			IRCpTag::Class(this) => Self {
+			IRCpTag::Class(this) => Ok(Self {
 				data: this.clone(),
 				index,
-			},
-			_ => panic!("trying to make CPUtf8Ref from non-utf8 tag. {utf8_tag:?}"),
+			}),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "Class",
+			}),
 		}
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index as usize - 1).expect("expected tag");
-		Self::new(index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(index, cp_get(cp, index)?)
+	}
+
+	/// `name` is an internal name, or an array descriptor.
+	pub fn find_or_add(cp: &mut Vec<IRCpTag>, name: &str) -> Result<Self, IRClassfileError> {
+		let name = CPUtf8Ref::find_or_add(cp, name)?;
+		let index = cp_find_or_add(cp, IRCpTag::Class(name))?;
+		Self::from_cp(cp, index)
 	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPNameAndTypeRef {
 	pub index: u16,
 	pub name: CPUtf8Ref,
@@ -149,25 +374,35 @@ pub struct CPNameAndTypeRef {
 }
 
 impl CPNameAndTypeRef {
-	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
 		match utf8_tag {
-			IRCpTag::NameAndType { name, descriptor } => Self {
+			IRCpTag::NameAndType { name, descriptor } => Ok(Self {
 				name: name.clone(),
 				ty: descriptor.clone(),
 				index,
-			},
-			_ => panic!("trying to make CPNameAndTypeRef from non-NameAndType tag. {utf8_tag:?}"),
+			}),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "NameAndType",
+			}),
 		}
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index.saturating_sub(1) as usize).expect("expected tag");
-		Self::new(index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(index, cp_get(cp, index)?)
+	}
+
+	pub fn find_or_add(cp: &mut Vec<IRCpTag>, name: &str, descriptor: &str) -> Result<Self, IRClassfileError> {
+		let name = CPUtf8Ref::find_or_add(cp, name)?;
+		let descriptor = CPUtf8Ref::find_or_add(cp, descriptor)?;
+		let index = cp_find_or_add(cp, IRCpTag::NameAndType { name, descriptor })?;
+		Self::from_cp(cp, index)
 	}
 }
 
 // https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.4.8
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPMethodHandleRef {
 	pub ref_kind: IRMethodRefKind,
 	pub ref_tag: Box<IRCpTag>,
@@ -176,86 +411,96 @@ pub struct CPMethodHandleRef {
 }
 
 impl CPMethodHandleRef {
-	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
 		match utf8_tag {
 			IRCpTag::MethodHandle {
 				ref_kind,
 				ref_index,
 				ref_tag,
-			} => Self {
+			} => Ok(Self {
 				ref_kind: ref_kind.clone(),
 				ref_tag: ref_tag.clone(),
 				ref_index: *ref_index,
 				index,
-			},
-			_ => panic!("trying to make CPMethodHandleRef from non-MethodHandle tag. {utf8_tag:?}"),
+			}),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "MethodHandle",
+			}),
 		}
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index as usize - 1).expect("expected tag");
-		Self::new(index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(index, cp_get(cp, index)?)
 	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPModuleInfoRef {
 	pub data: CPUtf8Ref,
 	pub index: u16,
 }
 
 impl CPModuleInfoRef {
-	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
 		match utf8_tag {
-			IRCpTag::Module { name } => Self {
+			IRCpTag::Module { name } => Ok(Self {
 				data: name.clone(),
 				index,
-			},
-			_ => panic!("trying to make CPModuleInfoRef from non-CPModuleInfoRef tag. {utf8_tag:?}"),
+			}),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "Module",
+			}),
 		}
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index as usize - 1).expect("expected tag");
-		Self::new(index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(index, cp_get(cp, index)?)
+	}
+
+	pub fn find_or_add(cp: &mut Vec<IRCpTag>, name: &str) -> Result<Self, IRClassfileError> {
+		let name = CPUtf8Ref::find_or_add(cp, name)?;
+		let index = cp_find_or_add(cp, IRCpTag::Module { name })?;
+		Self::from_cp(cp, index)
 	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPPackageInfoRef {
 	pub data: CPUtf8Ref,
 	pub index: u16,
 }
 
 impl CPPackageInfoRef {
-	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
 		match utf8_tag {
-			IRCpTag::Package { name } => Self {
+			IRCpTag::Package { name } => Ok(Self {
 				data: name.clone(),
 				index,
-			},
-			_ => panic!("trying to make CPUtf8Ref from non-utf8 tag. {utf8_tag:?}"),
+			}),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "Package",
+			}),
 		}
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index as usize - 1).expect("expected tag");
-		Self::new(index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(index, cp_get(cp, index)?)
 	}
-}
 
-#[macro_export]
-macro_rules! get_from_cp {
-	($cp:ident, $idx:ident, $ty:ident) => {{
-		match $cp.get(*$idx as usize - 1).expect("fuck") {
-			IRCpTag::$ty(v) => v,
-			t => panic!("expected different type: {} | got: {t:?}", stringify!($ty)),
-		}
-		.clone()
-	}};
+	pub fn find_or_add(cp: &mut Vec<IRCpTag>, name: &str) -> Result<Self, IRClassfileError> {
+		let name = CPUtf8Ref::find_or_add(cp, name)?;
+		let index = cp_find_or_add(cp, IRCpTag::Package { name })?;
+		Self::from_cp(cp, index)
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPFieldRef {
 	pub class: CPClassRef,
 	pub name_and_ty: CPNameAndTypeRef,
@@ -263,30 +508,49 @@ pub struct CPFieldRef {
 }
 
 impl CPFieldRef {
-	pub fn new(cp: &[IRCpTag], index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(cp: &[IRCpTag], index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
 		match utf8_tag {
 			IRCpTag::FieldRef {
 				class_index,
 				name_and_ty,
-			} => {
-				let class_tag = cp.get(class_index.saturating_sub(1) as usize).expect("fuck");
-				Self {
-					class: CPClassRef::new(*class_index, class_tag),
-					name_and_ty: name_and_ty.clone(),
-					index,
-				}
-			}
-			_ => panic!("trying to make CPUtf8Ref from non-utf8 tag. {utf8_tag:?}"),
+			} => Ok(Self {
+				class: CPClassRef::from_cp(cp, *class_index)?,
+				name_and_ty: name_and_ty.clone(),
+				index,
+			}),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "FieldRef",
+			}),
 		}
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index as usize - 1).expect("expected tag");
-		Self::new(cp, index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(cp, index, cp_get(cp, index)?)
+	}
+
+	/// `owner` is the internal name of the class declaring the member.
+	pub fn find_or_add(
+		cp: &mut Vec<IRCpTag>,
+		owner: &str,
+		name: &str,
+		descriptor: &str,
+	) -> Result<Self, IRClassfileError> {
+		let class_index = CPClassRef::find_or_add(cp, owner)?.index;
+		let name_and_ty = CPNameAndTypeRef::find_or_add(cp, name, descriptor)?;
+		let index = cp_find_or_add(
+			cp,
+			IRCpTag::FieldRef {
+				class_index,
+				name_and_ty,
+			},
+		)?;
+		Self::from_cp(cp, index)
 	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPMethodRef {
 	pub class: CPClassRef,
 	pub name_and_ty: CPNameAndTypeRef,
@@ -294,30 +558,54 @@ pub struct CPMethodRef {
 }
 
 impl CPMethodRef {
-	pub fn new(cp: &[IRCpTag], index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(cp: &[IRCpTag], index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
 		match utf8_tag {
+			// invokespecial and invokestatic may also reference interface methods since Java 8
 			IRCpTag::MethodRef {
 				class_index,
 				name_and_ty,
-			} => {
-				let class_tag = cp.get(class_index.saturating_sub(1) as usize).expect("fuck");
-				Self {
-					class: CPClassRef::new(*class_index, class_tag),
-					name_and_ty: name_and_ty.clone(),
-					index,
-				}
 			}
-			_ => panic!("trying to make CPUtf8Ref from non-utf8 tag. {utf8_tag:?}"),
+			| IRCpTag::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
+			} => Ok(Self {
+				class: CPClassRef::from_cp(cp, *class_index)?,
+				name_and_ty: name_and_ty.clone(),
+				index,
+			}),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "MethodRef",
+			}),
 		}
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index as usize - 1).expect("expected tag");
-		Self::new(cp, index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(cp, index, cp_get(cp, index)?)
+	}
+
+	/// `owner` is the internal name of the class declaring the member.
+	pub fn find_or_add(
+		cp: &mut Vec<IRCpTag>,
+		owner: &str,
+		name: &str,
+		descriptor: &str,
+	) -> Result<Self, IRClassfileError> {
+		let class_index = CPClassRef::find_or_add(cp, owner)?.index;
+		let name_and_ty = CPNameAndTypeRef::find_or_add(cp, name, descriptor)?;
+		let index = cp_find_or_add(
+			cp,
+			IRCpTag::MethodRef {
+				class_index,
+				name_and_ty,
+			},
+		)?;
+		Self::from_cp(cp, index)
 	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPInvokeDynamicRef {
 	pub bootstrap_method_attr_index: u16,
 	pub name_and_ty: CPNameAndTypeRef,
@@ -325,27 +613,30 @@ pub struct CPInvokeDynamicRef {
 }
 
 impl CPInvokeDynamicRef {
-	pub fn new(cp: &[IRCpTag], index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(_cp: &[IRCpTag], index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
 		match utf8_tag {
 			IRCpTag::InvokeDynamic {
 				bootstrap_method_attr_index,
 				name_and_ty,
-			} => Self {
+			} => Ok(Self {
 				bootstrap_method_attr_index: *bootstrap_method_attr_index,
 				name_and_ty: name_and_ty.clone(),
 				index,
-			},
-			_ => panic!("trying to make CPUtf8Ref from non-utf8 tag. {utf8_tag:?}"),
+			}),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "InvokeDynamic",
+			}),
 		}
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index as usize - 1).expect("expected tag");
-		Self::new(cp, index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(cp, index, cp_get(cp, index)?)
 	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPInterfaceMethodRef {
 	pub class: CPClassRef,
 	pub name_and_ty: CPNameAndTypeRef,
@@ -353,50 +644,75 @@ pub struct CPInterfaceMethodRef {
 }
 
 impl CPInterfaceMethodRef {
-	pub fn new(cp: &[IRCpTag], index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(cp: &[IRCpTag], index: u16, utf8_tag: &IRCpTag) -> Result<Self, IRClassfileError> {
 		match utf8_tag {
 			IRCpTag::InterfaceMethodRef {
 				class_index,
 				name_and_ty,
-			} => {
-				let class_tag = cp.get(class_index.saturating_sub(1) as usize).expect("fuck");
-				Self {
-					class: CPClassRef::new(*class_index, class_tag),
-					name_and_ty: name_and_ty.clone(),
-					index,
-				}
-			}
-			_ => panic!("trying to make CPUtf8Ref from non-utf8 tag. {utf8_tag:?}"),
+			} => Ok(Self {
+				class: CPClassRef::from_cp(cp, *class_index)?,
+				name_and_ty: name_and_ty.clone(),
+				index,
+			}),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index,
+				expected: "InterfaceMethodRef",
+			}),
 		}
 	}
 
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index as usize - 1).expect("expected tag");
-		Self::new(cp, index, tag)
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Self::new(cp, index, cp_get(cp, index)?)
+	}
+
+	/// `owner` is the internal name of the class declaring the member.
+	pub fn find_or_add(
+		cp: &mut Vec<IRCpTag>,
+		owner: &str,
+		name: &str,
+		descriptor: &str,
+	) -> Result<Self, IRClassfileError> {
+		let class_index = CPClassRef::find_or_add(cp, owner)?.index;
+		let name_and_ty = CPNameAndTypeRef::find_or_add(cp, name, descriptor)?;
+		let index = cp_find_or_add(
+			cp,
+			IRCpTag::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
+			},
+		)?;
+		Self::from_cp(cp, index)
 	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPTagRef {
 	pub tag: IRCpTag,
 	pub index: u16,
 }
 
 impl CPTagRef {
-	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
-		let tag = cp.get(index as usize - 1).expect("expected tag");
-		Self {
-			tag: tag.clone(),
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Result<Self, IRClassfileError> {
+		Ok(Self {
+			tag: cp_get(cp, index)?.clone(),
 			index,
-		}
+		})
+	}
+
+	pub fn find_or_add(cp: &mut Vec<IRCpTag>, tag: IRCpTag) -> Result<Self, IRClassfileError> {
+		let index = cp_find_or_add(cp, tag)?;
+		Self::from_cp(cp, index)
 	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum IRCpTag {
-	Idfk = 0,
-	Utf8(Rc<String>) = 1,
+	// The slot following a Long or Double, see IOCpTag::Unusable.
+	Unusable = 0,
+	Utf8(Shared<String>) = 1,
 	Integer(i32) = 3,
 	Float(f32) = 4,
 	Long(i64) = 5,
@@ -440,159 +756,759 @@ pub enum IRCpTag {
 	} = 20,
 }
 
-macro_rules! parse_tag_idx {
-	($idx:ident, $raw_tags:ident, $formed_tags:ident) => {
-		$formed_tags.get(*$idx as usize - 1).cloned().or(Some(Self::parse_tag(
-			&$raw_tags[*$idx as usize - 1],
-			$raw_tags,
-			$formed_tags,
-		)?))
-	};
+// Converting a pool is two passes over it. The first decodes the entries holding their own data, Utf8s and numbers,
+// the second links the rest by what they point at: the entries naming a Utf8 first, then the member refs and
+// InvokeDynamic through a NameAndType, then method handles through a member ref. Every entry is converted once,
+// whatever order the pool has them in, and one pointing at an entry of the wrong kind finds it still unlinked.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.4
+
+/// The pass of `IRCpTag::from_io` an entry is linked in, 0 for the ones decoded on their own.
+fn link_stage(tag: &IOCpTag) -> u8 {
+	match tag {
+		IOCpTag::Unusable
+		| IOCpTag::Utf8 { .. }
+		| IOCpTag::Integer { .. }
+		| IOCpTag::Float { .. }
+		| IOCpTag::Long { .. }
+		| IOCpTag::Double { .. } => 0,
+		IOCpTag::Class { .. }
+		| IOCpTag::String { .. }
+		| IOCpTag::NameAndType { .. }
+		| IOCpTag::MethodType { .. }
+		| IOCpTag::Module { .. }
+		| IOCpTag::Package { .. } => 1,
+		IOCpTag::FieldRef { .. }
+		| IOCpTag::MethodRef { .. }
+		| IOCpTag::InterfaceMethodRef { .. }
+		| IOCpTag::InvokeDynamic { .. } => 2,
+		IOCpTag::MethodHandle { .. } => 3,
+	}
+}
+
+/// An entry converted in an earlier pass, or what it should have been.
+fn linked<'a>(
+	tags: &'a [Option<IRCpTag>],
+	index: u16,
+	expected: &'static str,
+) -> Result<&'a IRCpTag, IRClassfileError> {
+	match tags.get((index as usize).wrapping_sub(1)) {
+		Some(Some(tag)) => Ok(tag),
+		Some(None) => Err(IRClassfileError::UnexpectedCpTag { index, expected }),
+		None => Err(IRClassfileError::InvalidCpIndex(index)),
+	}
+}
+
+fn linked_utf8(tags: &[Option<IRCpTag>], index: u16) -> Result<CPUtf8Ref, IRClassfileError> {
+	CPUtf8Ref::new(index, linked(tags, index, "Utf8")?)
+}
+
+/// A handle's target, which can only be a field or method reference.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.4.8
+fn linked_member(tags: &[Option<IRCpTag>], index: u16) -> Result<&IRCpTag, IRClassfileError> {
+	match linked(tags, index, "member reference")? {
+		tag @ (IRCpTag::FieldRef { .. } | IRCpTag::MethodRef { .. } | IRCpTag::InterfaceMethodRef { .. }) => Ok(tag),
+		_ => Err(IRClassfileError::UnexpectedCpTag {
+			index,
+			expected: "member reference",
+		}),
+	}
+}
+
+fn linked_name_and_ty(tags: &[Option<IRCpTag>], index: u16) -> Result<CPNameAndTypeRef, IRClassfileError> {
+	CPNameAndTypeRef::new(index, linked(tags, index, "NameAndType")?)
 }
 
 impl IRCpTag {
-	fn parse_tag(tag: &IOCpTag, raw_tags: &[IOCpTag], formed_tags: &[IRCpTag]) -> Result<IRCpTag, IRClassfileError> {
-		Ok(match tag {
-			IOCpTag::Utf8 { length: _, bytes } => IRCpTag::Utf8(Rc::new(maya_mutf8::decode(bytes)?)),
+	/// Entries holding their own data, `None` for the ones pointing at others.
+	fn decode(tag: &IOCpTag) -> Result<Option<IRCpTag>, IRClassfileError> {
+		Ok(Some(match tag {
+			IOCpTag::Utf8 { length: _, bytes } => IRCpTag::Utf8(Shared::new(maya_mutf8::decode(bytes)?)),
 			IOCpTag::Integer { bytes } => IRCpTag::Integer(i32::from_be_bytes(*bytes)),
 			IOCpTag::Float { bytes } => IRCpTag::Float(f32::from_be_bytes(*bytes)),
 			IOCpTag::Long { bytes } => IRCpTag::Long(i64::from_be_bytes(*bytes)),
 			IOCpTag::Double { bytes } => IRCpTag::Double(f64::from_be_bytes(*bytes)),
-			IOCpTag::Class { name_index } => {
-				let utf8_tag = parse_tag_idx!(name_index, raw_tags, formed_tags).expect("invalid Class name_index");
-				IRCpTag::Class(CPUtf8Ref::new(*name_index, &utf8_tag))
-			}
-			IOCpTag::String { utf8_index } => {
-				let utf8_tag = parse_tag_idx!(utf8_index, raw_tags, formed_tags).expect("invalid String utf8_index");
-				IRCpTag::String(CPUtf8Ref::new(*utf8_index, &utf8_tag))
-			}
+			IOCpTag::Unusable => IRCpTag::Unusable,
+			_ => return Ok(None),
+		}))
+	}
+
+	/// Entries pointing at others, with everything they point at already in `tags`.
+	fn link(tag: &IOCpTag, tags: &[Option<IRCpTag>]) -> Result<IRCpTag, IRClassfileError> {
+		Ok(match tag {
+			IOCpTag::Class { name_index } => IRCpTag::Class(linked_utf8(tags, *name_index)?),
+			IOCpTag::String { utf8_index } => IRCpTag::String(linked_utf8(tags, *utf8_index)?),
 			IOCpTag::FieldRef {
 				class_index,
 				name_and_ty_index,
-			} => {
-				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags)
-					.expect("invalid FieldRef name_and_ty_index")
-				{
-					IRCpTag::NameAndType { name, descriptor } => (name, descriptor),
-					t => panic!("expected NameAndType. got {t:?}"),
-				};
-				IRCpTag::FieldRef {
-					class_index: *class_index,
-					name_and_ty: CPNameAndTypeRef {
-						index: *name_and_ty_index,
-						name: name.clone(),
-						ty: ty.clone(),
-					},
-				}
-			}
+			} => IRCpTag::FieldRef {
+				class_index: *class_index,
+				name_and_ty: linked_name_and_ty(tags, *name_and_ty_index)?,
+			},
 			IOCpTag::MethodRef {
 				class_index,
 				name_and_ty_index,
-			} => {
-				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags)
-					.expect("invalid MethodRef name_and_ty_index")
-				{
-					IRCpTag::NameAndType { name, descriptor } => (name, descriptor),
-					t => panic!("expected NameAndType. got {t:?}"),
-				};
-				IRCpTag::MethodRef {
-					class_index: *class_index,
-					name_and_ty: CPNameAndTypeRef {
-						index: *name_and_ty_index,
-						name: name.clone(),
-						ty: ty.clone(),
-					},
-				}
-			}
+			} => IRCpTag::MethodRef {
+				class_index: *class_index,
+				name_and_ty: linked_name_and_ty(tags, *name_and_ty_index)?,
+			},
 			IOCpTag::InterfaceMethodRef {
 				class_index,
 				name_and_ty_index,
-			} => {
-				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags)
-					.expect("invalid InterfaceMethodRef name_and_ty_index")
-				{
-					IRCpTag::NameAndType { name, descriptor } => (name, descriptor),
-					t => panic!("expected NameAndType. got {t:?}"),
-				};
-				IRCpTag::InterfaceMethodRef {
-					class_index: *class_index,
-					name_and_ty: CPNameAndTypeRef {
-						index: *name_and_ty_index,
-						name: name.clone(),
-						ty: ty.clone(),
-					},
-				}
-			}
+			} => IRCpTag::InterfaceMethodRef {
+				class_index: *class_index,
+				name_and_ty: linked_name_and_ty(tags, *name_and_ty_index)?,
+			},
 			IOCpTag::NameAndType {
 				name_index,
 				descriptor_index,
-			} => {
-				let name_tag = parse_tag_idx!(name_index, raw_tags, formed_tags).expect("expected utf8 tag");
-				let descriptor_tag =
-					parse_tag_idx!(descriptor_index, raw_tags, formed_tags).expect("expected utf8 tag");
-				IRCpTag::NameAndType {
-					name: CPUtf8Ref::new(*name_index, &name_tag),
-					descriptor: CPUtf8Ref::new(*descriptor_index, &descriptor_tag),
-				}
-			}
+			} => IRCpTag::NameAndType {
+				name: linked_utf8(tags, *name_index)?,
+				descriptor: linked_utf8(tags, *descriptor_index)?,
+			},
 			IOCpTag::MethodHandle {
-				reference_kind: reference_kind_idx,
+				reference_kind,
 				reference_index,
-			} => {
-				let kind = IRMethodRefKind::from(*reference_kind_idx);
-				let tag = parse_tag_idx!(reference_index, raw_tags, formed_tags).expect("expected tag");
-				IRCpTag::MethodHandle {
-					ref_kind: kind,
-					ref_tag: Box::new(tag.clone()),
-					ref_index: *reference_index,
-				}
-			}
-			IOCpTag::MethodType { descriptor_index } => {
-				let tag = parse_tag_idx!(descriptor_index, raw_tags, formed_tags).expect("expected utf8 tag");
-				IRCpTag::MethodType(CPUtf8Ref::new(*descriptor_index, &tag))
-			}
+			} => IRCpTag::MethodHandle {
+				ref_kind: IRMethodRefKind::try_from(*reference_kind)?,
+				ref_index: *reference_index,
+				ref_tag: Box::new(linked_member(tags, *reference_index)?.clone()),
+			},
+			IOCpTag::MethodType { descriptor_index } => IRCpTag::MethodType(linked_utf8(tags, *descriptor_index)?),
 			IOCpTag::InvokeDynamic {
 				bootstrap_method_attr_index,
 				name_and_ty_index,
-			} => {
-				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags)
-					.expect("invalid InvokeDynamic name_and_ty_index")
-				{
-					IRCpTag::NameAndType { name, descriptor } => (name, descriptor),
-					t => panic!("expected NameAndType. got {t:?}"),
-				};
-				IRCpTag::InvokeDynamic {
-					bootstrap_method_attr_index: *bootstrap_method_attr_index,
-					name_and_ty: CPNameAndTypeRef {
-						index: *name_and_ty_index,
-						name: name.clone(),
-						ty: ty.clone(),
-					},
+			} => IRCpTag::InvokeDynamic {
+				bootstrap_method_attr_index: *bootstrap_method_attr_index,
+				name_and_ty: linked_name_and_ty(tags, *name_and_ty_index)?,
+			},
+			IOCpTag::Module { name_index } => IRCpTag::Module {
+				name: linked_utf8(tags, *name_index)?,
+			},
+			IOCpTag::Package { name_index } => IRCpTag::Package {
+				name: linked_utf8(tags, *name_index)?,
+			},
+			IOCpTag::Unusable
+			| IOCpTag::Utf8 { .. }
+			| IOCpTag::Integer { .. }
+			| IOCpTag::Float { .. }
+			| IOCpTag::Long { .. }
+			| IOCpTag::Double { .. } => unreachable!("decoded in the first pass"),
+		})
+	}
+
+	pub fn from_io(raw_tags: Vec<IOCpTag>) -> Result<Vec<IRCpTag>, IRClassfileError> {
+		let mut tags = raw_tags.iter().map(Self::decode).collect::<Result<Vec<_>, _>>()?;
+		for stage in 1..=3 {
+			for (i, raw_tag) in raw_tags.iter().enumerate() {
+				if link_stage(raw_tag) == stage {
+					tags[i] = Some(Self::link(raw_tag, &tags)?);
 				}
 			}
-			IOCpTag::Module { name_index } => {
-				let name_tag = parse_tag_idx!(name_index, raw_tags, formed_tags).expect("expected utf8 tag");
-				IRCpTag::Module {
-					name: CPUtf8Ref::new(*name_index, &name_tag),
+		}
+		Ok(tags
+			.into_iter()
+			.map(|tag| tag.expect("every entry is linked in some pass"))
+			.collect())
+	}
+
+	pub fn to_io(&self) -> Result<IOCpTag, IRClassfileError> {
+		Ok(match self {
+			Self::Unusable => IOCpTag::Unusable,
+			Self::Utf8(data) => {
+				let bytes = maya_mutf8::encode(data);
+				Limits::check("Utf8 constant length", u64::from(u16::MAX), bytes.len() as u64)?;
+				IOCpTag::Utf8 {
+					length: bytes.len() as u16,
+					bytes,
 				}
 			}
-			IOCpTag::Package { name_index } => {
-				let name_tag = parse_tag_idx!(name_index, raw_tags, formed_tags).expect("expected utf8 tag");
-				IRCpTag::Package {
-					name: CPUtf8Ref::new(*name_index, &name_tag),
-				}
+			Self::Integer(value) => IOCpTag::Integer {
+				bytes: value.to_be_bytes(),
+			},
+			Self::Float(value) => IOCpTag::Float {
+				bytes: value.to_be_bytes(),
+			},
+			Self::Long(value) => IOCpTag::Long {
+				bytes: value.to_be_bytes(),
+			},
+			Self::Double(value) => IOCpTag::Double {
+				bytes: value.to_be_bytes(),
+			},
+			Self::Class(name) => IOCpTag::Class { name_index: name.index },
+			Self::String(utf8) => IOCpTag::String { utf8_index: utf8.index },
+			Self::FieldRef {
+				class_index,
+				name_and_ty,
+			} => IOCpTag::FieldRef {
+				class_index: *class_index,
+				name_and_ty_index: name_and_ty.index,
+			},
+			Self::MethodRef {
+				class_index,
+				name_and_ty,
+			} => IOCpTag::MethodRef {
+				class_index: *class_index,
+				name_and_ty_index: name_and_ty.index,
+			},
+			Self::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
+			} => IOCpTag::InterfaceMethodRef {
+				class_index: *class_index,
+				name_and_ty_index: name_and_ty.index,
+			},
+			Self::NameAndType { name, descriptor } => IOCpTag::NameAndType {
+				name_index: name.index,
+				descriptor_index: descriptor.index,
+			},
+			Self::MethodHandle {
+				ref_kind, ref_index, ..
+			} => IOCpTag::MethodHandle {
+				reference_kind: ref_kind.clone() as u8,
+				reference_index: *ref_index,
+			},
+			Self::MethodType(descriptor) => IOCpTag::MethodType {
+				descriptor_index: descriptor.index,
+			},
+			Self::InvokeDynamic {
+				bootstrap_method_attr_index,
+				name_and_ty,
+			} => IOCpTag::InvokeDynamic {
+				bootstrap_method_attr_index: *bootstrap_method_attr_index,
+				name_and_ty_index: name_and_ty.index,
+			},
+			Self::Module { name } => IOCpTag::Module { name_index: name.index },
+			Self::Package { name } => IOCpTag::Package { name_index: name.index },
+		})
+	}
+
+	/// Whether both entries would be written the same way. Floats compare by bits, so `NaN` entries can be shared.
+	pub fn same_entry(&self, other: &IRCpTag) -> bool {
+		match (self, other) {
+			(Self::Unusable, Self::Unusable) => true,
+			(Self::Utf8(a), Self::Utf8(b)) => a == b,
+			(Self::Integer(a), Self::Integer(b)) => a == b,
+			(Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+			(Self::Long(a), Self::Long(b)) => a == b,
+			(Self::Double(a), Self::Double(b)) => a.to_bits() == b.to_bits(),
+			(Self::Class(a), Self::Class(b))
+			| (Self::String(a), Self::String(b))
+			| (Self::MethodType(a), Self::MethodType(b))
+			| (Self::Module { name: a }, Self::Module { name: b })
+			| (Self::Package { name: a }, Self::Package { name: b }) => a.index == b.index,
+			(
+				Self::FieldRef {
+					class_index: a,
+					name_and_ty: a_nat,
+				},
+				Self::FieldRef {
+					class_index: b,
+					name_and_ty: b_nat,
+				},
+			)
+			| (
+				Self::MethodRef {
+					class_index: a,
+					name_and_ty: a_nat,
+				},
+				Self::MethodRef {
+					class_index: b,
+					name_and_ty: b_nat,
+				},
+			)
+			| (
+				Self::InterfaceMethodRef {
+					class_index: a,
+					name_and_ty: a_nat,
+				},
+				Self::InterfaceMethodRef {
+					class_index: b,
+					name_and_ty: b_nat,
+				},
+			)
+			| (
+				Self::InvokeDynamic {
+					bootstrap_method_attr_index: a,
+					name_and_ty: a_nat,
+				},
+				Self::InvokeDynamic {
+					bootstrap_method_attr_index: b,
+					name_and_ty: b_nat,
+				},
+			) => a == b && a_nat.index == b_nat.index,
+			(
+				Self::NameAndType {
+					name: a_name,
+					descriptor: a_descriptor,
+				},
+				Self::NameAndType {
+					name: b_name,
+					descriptor: b_descriptor,
+				},
+			) => a_name.index == b_name.index && a_descriptor.index == b_descriptor.index,
+			(
+				Self::MethodHandle {
+					ref_kind: a_kind,
+					ref_index: a,
+					..
+				},
+				Self::MethodHandle {
+					ref_kind: b_kind,
+					ref_index: b,
+					..
+				},
+			) => a_kind.clone() as u8 == b_kind.clone() as u8 && a == b,
+			_ => false,
+		}
+	}
+}
+
+// javap-style rendering. A tag on its own only knows the indices it points at, so `Display` prints those, while
+// `CpEntry` has the whole pool and adds javap's trailing comment with everything resolved.
+
+impl IRCpTag {
+	/// The name javap uses for this kind of entry.
+	pub fn kind_name(&self) -> &'static str {
+		match self {
+			Self::Unusable => "Unusable",
+			Self::Utf8(_) => "Utf8",
+			Self::Integer(_) => "Integer",
+			Self::Float(_) => "Float",
+			Self::Long(_) => "Long",
+			Self::Double(_) => "Double",
+			Self::Class(_) => "Class",
+			Self::String(_) => "String",
+			Self::FieldRef { .. } => "Fieldref",
+			Self::MethodRef { .. } => "Methodref",
+			Self::InterfaceMethodRef { .. } => "InterfaceMethodref",
+			Self::NameAndType { .. } => "NameAndType",
+			Self::MethodHandle { .. } => "MethodHandle",
+			Self::MethodType(_) => "MethodType",
+			Self::InvokeDynamic { .. } => "InvokeDynamic",
+			Self::Module { .. } => "Module",
+			Self::Package { .. } => "Package",
+		}
+	}
+
+	/// The entry's value with every reference resolved, the part javap prints after `//`.
+	/// `None` for entries that hold their value directly.
+	pub fn resolved(&self, cp: &[IRCpTag]) -> Option<String> {
+		let member = |class_index: u16, name_and_ty: &CPNameAndTypeRef| {
+			let class = CPClassRef::from_cp(cp, class_index)
+				.map_or_else(|_| format!("#{class_index}"), |class| javap_name(&class.data.data));
+			format!("{class}.{}", javap_name_and_type(name_and_ty))
+		};
+		Some(match self {
+			Self::Unusable | Self::Utf8(_) | Self::Integer(_) | Self::Float(_) | Self::Long(_) | Self::Double(_) => {
+				return None
+			}
+			Self::Class(name) => javap_name(&name.data),
+			Self::String(name) | Self::MethodType(name) => escape(&name.data),
+			Self::Module { name } | Self::Package { name } => escape(&name.data),
+			Self::FieldRef {
+				class_index,
+				name_and_ty,
+			}
+			| Self::MethodRef {
+				class_index,
+				name_and_ty,
+			}
+			| Self::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
+			} => member(*class_index, name_and_ty),
+			Self::NameAndType { name, descriptor } => format!("{}:{}", javap_name(&name.data), descriptor.data),
+			Self::MethodHandle { ref_kind, ref_tag, .. } => {
+				format!("{ref_kind} {}", ref_tag.resolved(cp).unwrap_or_default())
 			}
+			Self::InvokeDynamic {
+				bootstrap_method_attr_index,
+				name_and_ty,
+			} => format!("#{bootstrap_method_attr_index}:{}", javap_name_and_type(name_and_ty)),
 		})
 	}
+}
 
-	pub fn from_io(raw_tags: Vec<IOCpTag>) -> Result<Vec<IRCpTag>, IRClassfileError> {
-		let mut res = Vec::with_capacity(raw_tags.len());
+impl Display for IRCpTag {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let operands = match self {
+			Self::Unusable => String::new(),
+			Self::Utf8(data) => escape(data),
+			Self::Integer(value) => value.to_string(),
+			Self::Float(value) => format!("{}f", java_number(*value)),
+			Self::Long(value) => format!("{value}l"),
+			Self::Double(value) => format!("{}d", java_number(*value)),
+			Self::Class(name) | Self::String(name) | Self::MethodType(name) => format!("#{}", name.index),
+			Self::Module { name } | Self::Package { name } => format!("#{}", name.index),
+			Self::FieldRef {
+				class_index,
+				name_and_ty,
+			}
+			| Self::MethodRef {
+				class_index,
+				name_and_ty,
+			}
+			| Self::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
+			} => format!("#{class_index}.#{}", name_and_ty.index),
+			Self::NameAndType { name, descriptor } => format!("#{}:#{}", name.index, descriptor.index),
+			Self::MethodHandle {
+				ref_kind, ref_index, ..
+			} => format!("{}:#{ref_index}", ref_kind.clone() as u8),
+			Self::InvokeDynamic {
+				bootstrap_method_attr_index,
+				name_and_ty,
+			} => format!("#{bootstrap_method_attr_index}:#{}", name_and_ty.index),
+		};
+		write!(f, "{:<18} {operands}", self.kind_name())
+	}
+}
+
+/// One line of a javap constant pool listing, e.g. `#12 = Methodref #3.#45 // java/lang/Object."<init>":()V`.
+pub struct CpEntry<'a> {
+	pub cp: &'a [IRCpTag],
+	pub index: u16,
+}
 
-		for raw_tag in &raw_tags {
-			let tag = Self::parse_tag(raw_tag, &raw_tags, &res)?;
-			res.push(tag);
+impl Display for CpEntry<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let tag = cp_get(self.cp, self.index).map_err(|_| fmt::Error)?;
+		// The width, if any, right-aligns the index like javap does to line up a whole listing.
+		let index = format!("#{}", self.index);
+		let line = format!("{index:>width$} = {tag}", width = f.width().unwrap_or(0));
+		match tag.resolved(self.cp) {
+			Some(comment) => write!(f, "{line:<40} // {comment}"),
+			None => f.write_str(&line),
 		}
+	}
+}
+
+/// The whole pool the way `javap -v` lists it, one entry per line. Slots after longs and doubles are skipped.
+pub fn dump(cp: &[IRCpTag]) -> String {
+	let width = cp.len().to_string().len() + 1;
+	let mut out = String::new();
+	for (i, tag) in cp.iter().enumerate() {
+		if matches!(tag, IRCpTag::Unusable) {
+			continue;
+		}
+		let entry = CpEntry {
+			cp,
+			index: i as u16 + 1,
+		};
+		let _ = writeln!(out, "{entry:width$}");
+	}
+	out
+}
+
+// javap quotes class and member names that aren't Java identifiers separated by slashes, `"<init>"` or `"[I"`.
+pub(crate) fn javap_name(name: &str) -> String {
+	let mut parts = name.split('/');
+	let identifiers = parts.all(|part| {
+		let mut chars = part.chars();
+		chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+			&& chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+	});
+	match identifiers {
+		true => escape(name),
+		false => format!("\"{}\"", escape(name)),
+	}
+}
+
+pub(crate) fn javap_name_and_type(name_and_ty: &CPNameAndTypeRef) -> String {
+	format!("{}:{}", javap_name(&name_and_ty.name.data), name_and_ty.ty.data)
+}
+
+// javap prints Utf8 contents raw except for control characters.
+pub(crate) fn escape(value: &str) -> String {
+	let mut out = String::with_capacity(value.len());
+	for c in value.chars() {
+		match c {
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			'\u{8}' => out.push_str("\\b"),
+			'\u{c}' => out.push_str("\\f"),
+			'"' | '\'' | '\\' => {
+				out.push('\\');
+				out.push(c);
+			}
+			c if c.is_control() => {
+				let _ = write!(out, "\\u{:04x}", c as u32);
+			}
+			c => out.push(c),
+		}
+	}
+	out
+}
+
+// Java's `Double.toString` and `Float.toString`, which javap prints with: plain from 10^-3 to 10^7 and with an `E`
+// exponent outside of that, always with a digit after the point.
+pub(crate) fn java_number<T: LowerExp + Into<f64> + Copy>(value: T) -> String {
+	let float = value.into();
+	if float.is_nan() {
+		return "NaN".to_string();
+	}
+	if float.is_infinite() {
+		return if float > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+	}
+	// The shortest digits that read back as the same value, `-1.5e-7`.
+	let scientific = format!("{value:e}");
+	let (sign, scientific) = match scientific.strip_prefix('-') {
+		Some(rest) => ("-", rest),
+		None => ("", scientific.as_str()),
+	};
+	let (mantissa, exponent) = scientific.split_once('e').unwrap_or((scientific, "0"));
+	let exponent = exponent.parse::<i32>().unwrap_or_default();
+	let digits = mantissa.replace('.', "");
+	let (int, frac) = match exponent {
+		0..=6 => {
+			let split = exponent as usize + 1;
+			let digits = format!("{digits:0<split$}");
+			let (int, frac) = digits.split_at(split);
+			(int.to_string(), frac.to_string())
+		}
+		-3..=-1 => (
+			"0".to_string(),
+			format!("{}{digits}", "0".repeat((-exponent - 1) as usize)),
+		),
+		_ => {
+			let (int, frac) = digits.split_at(1);
+			let frac = if frac.is_empty() { "0" } else { frac };
+			return format!("{sign}{int}.{frac}E{exponent}");
+		}
+	};
+	let frac = if frac.is_empty() { "0" } else { frac.as_str() };
+	format!("{sign}{int}.{frac}")
+}
+
+impl Display for IRMethodRefKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::GetField => "REF_getField",
+			Self::GetStatic => "REF_getStatic",
+			Self::PutField => "REF_putField",
+			Self::PutStatic => "REF_putStatic",
+			Self::InvokeVirtual => "REF_invokeVirtual",
+			Self::InvokeStatic => "REF_invokeStatic",
+			Self::InvokeSpecial => "REF_invokeSpecial",
+			Self::NewInvokeSpecial => "REF_newInvokeSpecial",
+			Self::InvokeInterface => "REF_invokeInterface",
+		})
+	}
+}
+
+impl Display for CPConstValueRefKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Double(value) => write!(f, "{value:?}d"),
+			Self::Float(value) => write!(f, "{value:?}f"),
+			Self::Int(value) => write!(f, "{value}"),
+			Self::Long(value) => write!(f, "{value}l"),
+			Self::String(value) => f.write_str(&escape(value)),
+		}
+	}
+}
+
+impl Display for CPConstValueRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.kind.fmt(f)
+	}
+}
+
+impl Display for CPUtf8Ref {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&escape(&self.data))
+	}
+}
+
+impl Display for CPClassRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.data.fmt(f)
+	}
+}
+
+impl Display for CPModuleInfoRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.data.fmt(f)
+	}
+}
+
+impl Display for CPPackageInfoRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.data.fmt(f)
+	}
+}
+
+impl Display for CPNameAndTypeRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}:{}", self.name, self.ty)
+	}
+}
+
+impl Display for CPFieldRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}.{}", self.class, self.name_and_ty)
+	}
+}
+
+impl Display for CPMethodRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}.{}", self.class, self.name_and_ty)
+	}
+}
+
+impl Display for CPInterfaceMethodRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}.{}", self.class, self.name_and_ty)
+	}
+}
+
+impl Display for CPInvokeDynamicRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "#{}:{}", self.bootstrap_method_attr_index, self.name_and_ty)
+	}
+}
+
+// Without the pool the owner of the target member is unknown, so only its name and type are printed.
+impl Display for CPMethodHandleRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &*self.ref_tag {
+			IRCpTag::FieldRef { name_and_ty, .. }
+			| IRCpTag::MethodRef { name_and_ty, .. }
+			| IRCpTag::InterfaceMethodRef { name_and_ty, .. } => write!(f, "{} {name_and_ty}", self.ref_kind),
+			_ => write!(f, "{} #{}", self.ref_kind, self.ref_index),
+		}
+	}
+}
+
+impl Display for CPTagRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.tag.fmt(f)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn link_forward_references() {
+		let utf8 = |value: &str| IOCpTag::Utf8 {
+			length: value.len() as u16,
+			bytes: value.as_bytes().to_vec(),
+		};
+		// Every entry points further down the pool.
+		let pool = || {
+			vec![
+				IOCpTag::MethodHandle {
+					reference_kind: 2,
+					reference_index: 2,
+				},
+				IOCpTag::FieldRef {
+					class_index: 3,
+					name_and_ty_index: 4,
+				},
+				IOCpTag::Class { name_index: 5 },
+				IOCpTag::NameAndType {
+					name_index: 6,
+					descriptor_index: 7,
+				},
+				utf8("p/Main"),
+				utf8("count"),
+				utf8("I"),
+			]
+		};
+		let cp = IRCpTag::from_io(pool()).unwrap();
+		let IRCpTag::MethodHandle { ref_tag, .. } = &cp[0] else {
+			panic!("expected a MethodHandle");
+		};
+		let IRCpTag::FieldRef { name_and_ty, .. } = &**ref_tag else {
+			panic!("expected a FieldRef");
+		};
+		assert_eq!(
+			(name_and_ty.name.data.as_str(), name_and_ty.ty.data.as_str()),
+			("count", "I")
+		);
+		assert!(matches!(&cp[2], IRCpTag::Class(name) if name.data.as_str() == "p/Main"));
+
+		// Entries of the wrong kind, a handle to itself among them, fail rather than recurse.
+		let mut cycle = pool();
+		cycle[0] = IOCpTag::MethodHandle {
+			reference_kind: 2,
+			reference_index: 1,
+		};
+		assert!(matches!(
+			IRCpTag::from_io(cycle),
+			Err(IRClassfileError::UnexpectedCpTag { index: 1, .. })
+		));
+		// A handle to a handle, which would otherwise copy the whole chain below it.
+		let mut handles = pool();
+		handles.push(IOCpTag::MethodHandle {
+			reference_kind: 2,
+			reference_index: 1,
+		});
+		assert!(matches!(
+			IRCpTag::from_io(handles),
+			Err(IRClassfileError::UnexpectedCpTag {
+				index: 1,
+				expected: "member reference"
+			})
+		));
+		let mut class_of_class = pool();
+		class_of_class[2] = IOCpTag::Class { name_index: 3 };
+		assert!(matches!(
+			IRCpTag::from_io(class_of_class),
+			Err(IRClassfileError::UnexpectedCpTag {
+				index: 3,
+				expected: "Utf8"
+			})
+		));
+		let mut out_of_range = pool();
+		out_of_range[2] = IOCpTag::Class { name_index: 0 };
+		assert!(matches!(
+			IRCpTag::from_io(out_of_range),
+			Err(IRClassfileError::InvalidCpIndex(0))
+		));
+	}
+
+	#[test]
+	fn pool_index_matches_linear_search() {
+		let utf8 = |value: &str| IRCpTag::Utf8(Shared::new(value.to_string()));
+		let tags = || {
+			vec![
+				utf8("a"),
+				IRCpTag::Long(7),
+				IRCpTag::Integer(7),
+				utf8("b"),
+				IRCpTag::Long(7),
+				utf8("a"),
+				IRCpTag::Integer(8),
+			]
+		};
+
+		let mut linear = Vec::new();
+		let linear_indexes: Vec<u16> = tags()
+			.into_iter()
+			.map(|tag| cp_find_or_add(&mut linear, tag).unwrap())
+			.collect();
+		let mut cp = Vec::new();
+		let mut pool = PoolIndex::new();
+		let indexes: Vec<u16> = tags()
+			.into_iter()
+			.map(|tag| pool.find_or_add(&mut cp, tag).unwrap())
+			.collect();
+		assert_eq!(indexes, linear_indexes);
+		assert_eq!(indexes, [1, 2, 4, 5, 2, 1, 6]);
+		assert!(matches!(cp[2], IRCpTag::Unusable));
+		assert_eq!(cp.len(), linear.len());
 
-		Ok(res)
+		// Entries added behind the index's back are still found.
+		cp.push(utf8("c"));
+		assert_eq!(pool.find_or_add(&mut cp, utf8("c")).unwrap(), 7);
+		// An entry replaced in place isn't handed out for what it used to be.
+		cp[0] = utf8("z");
+		assert_eq!(pool.find_or_add(&mut cp, utf8("a")).unwrap(), 8);
+		assert_eq!(pool.find_or_add(&mut cp, utf8("z")).unwrap(), 1);
 	}
 }
@@ -0,0 +1,842 @@
+// Class paths, written the way the java launcher takes them so they can be pasted from a build or a command line:
+// entries split on the platform's path separator, an empty entry meaning the current directory, `dir/*` standing for
+// the jars in `dir`, and `@file` reading arguments from a file.
+// https://docs.oracle.com/en/java/javase/22/docs/specs/man/java.html#java-command-line-argument-files
+// https://docs.oracle.com/en/java/javase/22/docs/specs/man/java.html#standard-options-for-java
+//
+// A `ClassCache` loads classes from a class path on demand and keeps them around, for as long as its `CachePolicy`
+// allows: scanning a big class path in a long-running process shouldn't mean holding every class it ever touched.
+//
+// Class bytes don't have to come from disk. A `ClassProvider` is anything that can look classes up by name, a class
+// path entry can be one, and `read_classes` parses everything one has. Build tools with virtual file systems hand
+// theirs over as providers instead of writing classes out to temp files.
+
+use std::{
+	cell::RefCell,
+	collections::{BTreeMap, HashMap},
+	fmt,
+	fs::{self, File},
+	io::{self, BufReader, Read},
+	path::{Path, PathBuf},
+	rc::{Rc, Weak},
+};
+
+use thiserror::Error;
+use zip::{result::ZipError, ZipArchive};
+
+use crate::{archive::Archive, class_pool::IRClassfileError, jimage::JImageError, IRClassFile};
+
+#[derive(Debug, Error)]
+pub enum ClasspathError {
+	#[error("{0}")]
+	IO(#[from] io::Error),
+	#[error("{0}")]
+	Zip(#[from] ZipError),
+	#[error("Can't read argument file {path}: {source}")]
+	Argfile { path: PathBuf, source: io::Error },
+	#[error("{0} requires a class path")]
+	MissingValue(String),
+	#[error("{name}: {source}")]
+	Class { name: String, source: IRClassfileError },
+	#[error("{0}")]
+	JImage(#[from] JImageError),
+	#[cfg(feature = "maven")]
+	#[error("Invalid Maven coordinate {0}, expected group:artifact:version")]
+	InvalidCoordinate(String),
+	#[cfg(feature = "maven")]
+	#[error("Can't fetch {url}: {source}")]
+	Fetch { url: String, source: Box<ureq::Error> },
+}
+
+/// A source of class file bytes, looked up by internal name.
+pub trait ClassProvider: fmt::Debug {
+	/// The bytes of the class with this internal name, `None` if the provider doesn't have it.
+	fn read_class(&self, name: &str) -> Result<Option<Vec<u8>>, ClasspathError>;
+
+	/// Internal names of the classes the provider has, sorted. Providers that can only look classes up, like one
+	/// fetching them from a remote cache, list none.
+	fn class_names(&self) -> Result<Vec<String>, ClasspathError> {
+		Ok(Vec::new())
+	}
+}
+
+/// Classes in a directory tree, `p/A` in `p/A.class` under the root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryProvider {
+	root: PathBuf,
+}
+
+impl DirectoryProvider {
+	pub fn new(root: PathBuf) -> Self {
+		Self { root }
+	}
+}
+
+impl ClassProvider for DirectoryProvider {
+	fn read_class(&self, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+		read_from_dir(&self.root, name)
+	}
+
+	fn class_names(&self) -> Result<Vec<String>, ClasspathError> {
+		let mut names = Vec::new();
+		list_dir(&self.root, "", &mut names)?;
+		names.sort();
+		Ok(names)
+	}
+}
+
+/// Classes in a jar, read from it on every lookup rather than held in memory like an [`Archive`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JarProvider {
+	path: PathBuf,
+}
+
+impl JarProvider {
+	pub fn new(path: PathBuf) -> Self {
+		Self { path }
+	}
+}
+
+impl ClassProvider for JarProvider {
+	fn read_class(&self, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+		read_from_jar(&self.path, name)
+	}
+
+	fn class_names(&self) -> Result<Vec<String>, ClasspathError> {
+		let zip = ZipArchive::new(BufReader::new(File::open(&self.path)?))?;
+		let mut names = zip
+			.file_names()
+			.filter_map(|file| file.strip_suffix(".class"))
+			.map(str::to_string)
+			.collect::<Vec<_>>();
+		names.sort();
+		names.dedup();
+		Ok(names)
+	}
+}
+
+/// Classes held in memory, by internal name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryProvider {
+	classes: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryProvider {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a class, replacing any with the same name.
+	pub fn insert(&mut self, name: impl Into<String>, bytes: Vec<u8>) {
+		self.classes.insert(name.into(), bytes);
+	}
+}
+
+impl From<BTreeMap<String, Vec<u8>>> for MemoryProvider {
+	fn from(classes: BTreeMap<String, Vec<u8>>) -> Self {
+		Self { classes }
+	}
+}
+
+impl ClassProvider for MemoryProvider {
+	fn read_class(&self, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+		Ok(self.classes.get(name).cloned())
+	}
+
+	fn class_names(&self) -> Result<Vec<String>, ClasspathError> {
+		Ok(self.classes.keys().cloned().collect())
+	}
+}
+
+/// Classes looked up by a function, e.g. fetching them from a build tool's remote cache. It can't list any.
+pub struct FetchProvider<F> {
+	fetch: F,
+}
+
+impl<F> FetchProvider<F>
+where
+	F: Fn(&str) -> Result<Option<Vec<u8>>, ClasspathError>,
+{
+	pub fn new(fetch: F) -> Self {
+		Self { fetch }
+	}
+}
+
+impl<F> fmt::Debug for FetchProvider<F> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("FetchProvider").finish_non_exhaustive()
+	}
+}
+
+impl<F> ClassProvider for FetchProvider<F>
+where
+	F: Fn(&str) -> Result<Option<Vec<u8>>, ClasspathError>,
+{
+	fn read_class(&self, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+		(self.fetch)(name)
+	}
+}
+
+/// The archive's classes as it kept them, lookups seeing the first of any duplicates.
+impl ClassProvider for Archive {
+	fn read_class(&self, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+		Ok(self.get(&format!("{name}.class")).map(|entry| entry.data.clone()))
+	}
+
+	fn class_names(&self) -> Result<Vec<String>, ClasspathError> {
+		let mut names = self
+			.classes()
+			.filter_map(|entry| entry.name.strip_suffix(".class"))
+			.map(str::to_string)
+			.collect::<Vec<_>>();
+		names.sort();
+		names.dedup();
+		Ok(names)
+	}
+}
+
+/// Where classes are looked up, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Classpath {
+	entries: Vec<ClasspathEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ClasspathEntry {
+	/// A directory or an archive, told apart by what's on disk.
+	Path(PathBuf),
+	Provider(Rc<dyn ClassProvider>),
+}
+
+impl ClasspathEntry {
+	pub fn path(&self) -> Option<&Path> {
+		match self {
+			Self::Path(path) => Some(path),
+			Self::Provider(_) => None,
+		}
+	}
+}
+
+/// Providers are equal only to themselves.
+impl PartialEq for ClasspathEntry {
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Self::Path(a), Self::Path(b)) => a == b,
+			(Self::Provider(a), Self::Provider(b)) => Rc::ptr_eq(a, b),
+			_ => false,
+		}
+	}
+}
+
+impl Eq for ClasspathEntry {}
+
+// Launcher options that take the next argument as their value, so it isn't mistaken for the main class.
+const OPTIONS_WITH_VALUE: &[&str] = &[
+	"-p",
+	"--module-path",
+	"--upgrade-module-path",
+	"--add-modules",
+	"--limit-modules",
+	"--add-reads",
+	"--add-exports",
+	"--add-opens",
+	"--patch-module",
+	"--enable-native-access",
+	"--source",
+];
+
+impl Classpath {
+	/// What separates entries on this platform, as in `java.io.File.pathSeparator`.
+	pub const SEPARATOR: char = if cfg!(windows) { ';' } else { ':' };
+
+	pub fn new(entries: Vec<PathBuf>) -> Self {
+		Self {
+			entries: entries.into_iter().map(ClasspathEntry::Path).collect(),
+		}
+	}
+
+	/// Parses a class path as given to `-cp`, or an `@file` with the arguments to take it from.
+	pub fn parse(classpath: &str) -> Result<Self, ClasspathError> {
+		Self::parse_with_separator(classpath, Self::SEPARATOR)
+	}
+
+	/// Like `parse`, for class paths from another platform.
+	pub fn parse_with_separator(classpath: &str, separator: char) -> Result<Self, ClasspathError> {
+		if let Some(path) = classpath.strip_prefix('@').filter(|path| !path.starts_with('@')) {
+			let args = read_argfile(Path::new(path))?;
+			// A whole command line's worth of arguments, or just the class path.
+			if let Some(classpath) = Self::find_option(&args)? {
+				return Ok(Self::parse_entries(&classpath, separator));
+			}
+			return Ok(Self::parse_entries(&args.join(&separator.to_string()), separator));
+		}
+		let classpath = classpath.strip_prefix('@').unwrap_or(classpath);
+		Ok(Self::parse_entries(classpath, separator))
+	}
+
+	/// The class path java would run with given these launcher arguments: `-cp`, `-classpath` or `--class-path`, the
+	/// last one winning, with `@file` arguments expanded. `None` if there's none, java then falls back to `CLASSPATH`.
+	pub fn from_args<S: AsRef<str>>(args: &[S]) -> Result<Option<Self>, ClasspathError> {
+		let args = expand_argfiles(args)?;
+		Ok(Self::find_option(&args)?.map(|classpath| Self::parse_entries(&classpath, Self::SEPARATOR)))
+	}
+
+	fn find_option(args: &[String]) -> Result<Option<String>, ClasspathError> {
+		let mut classpath = None;
+		let mut args = args.iter();
+		while let Some(arg) = args.next() {
+			match arg.as_str() {
+				"-cp" | "-classpath" | "--class-path" => {
+					classpath = Some(
+						args.next()
+							.ok_or_else(|| ClasspathError::MissingValue(arg.clone()))?
+							.clone(),
+					)
+				}
+				// Everything from the main class or jar on is for the program.
+				"-jar" | "-m" | "--module" => break,
+				arg if OPTIONS_WITH_VALUE.contains(&arg) => {
+					args.next();
+				}
+				arg => match arg.strip_prefix("--class-path=") {
+					Some(value) => classpath = Some(value.to_string()),
+					None if !arg.starts_with('-') => break,
+					None => {}
+				},
+			}
+		}
+		Ok(classpath)
+	}
+
+	fn parse_entries(classpath: &str, separator: char) -> Self {
+		let mut entries = Vec::new();
+		for entry in classpath.split(separator) {
+			match entry {
+				"" => entries.push(PathBuf::from(".")),
+				"*" => entries.extend(jars_in(Path::new("."))),
+				_ => match entry.strip_suffix("/*").or_else(|| entry.strip_suffix("\\*")) {
+					Some(dir) => entries.extend(jars_in(Path::new(dir))),
+					None => entries.push(PathBuf::from(entry)),
+				},
+			}
+		}
+		Self::new(entries)
+	}
+
+	pub fn entries(&self) -> &[ClasspathEntry] {
+		&self.entries
+	}
+
+	/// The entries that are paths, in order.
+	pub fn paths(&self) -> impl Iterator<Item = &Path> {
+		self.entries.iter().filter_map(ClasspathEntry::path)
+	}
+
+	pub fn push(&mut self, entry: PathBuf) {
+		self.entries.push(ClasspathEntry::Path(entry));
+	}
+
+	pub fn push_provider(&mut self, provider: Rc<dyn ClassProvider>) {
+		self.entries.push(ClasspathEntry::Provider(provider));
+	}
+
+	/// The bytes of the class with this internal name from the first entry that has it. Paths that don't exist are
+	/// skipped, like java does.
+	pub fn read_class(&self, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+		for entry in &self.entries {
+			let data = match entry {
+				ClasspathEntry::Path(path) if path.is_dir() => read_from_dir(path, name)?,
+				ClasspathEntry::Path(path) if path.is_file() => read_from_jar(path, name)?,
+				ClasspathEntry::Path(_) => None,
+				ClasspathEntry::Provider(provider) => provider.read_class(name)?,
+			};
+			if data.is_some() {
+				return Ok(data);
+			}
+		}
+		Ok(None)
+	}
+
+	/// The path entries joined back into one class path for this platform. Providers have no path and are left out.
+	pub fn to_string_lossy(&self) -> String {
+		let entries = self.paths().map(Path::to_string_lossy).collect::<Vec<_>>();
+		entries.join(&Self::SEPARATOR.to_string())
+	}
+}
+
+/// Every class on the class path once, those shadowed by an earlier entry left out.
+impl ClassProvider for Classpath {
+	fn read_class(&self, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+		Classpath::read_class(self, name)
+	}
+
+	fn class_names(&self) -> Result<Vec<String>, ClasspathError> {
+		let mut names = Vec::new();
+		for entry in &self.entries {
+			names.extend(match entry {
+				ClasspathEntry::Path(path) if path.is_dir() => DirectoryProvider::new(path.clone()).class_names()?,
+				ClasspathEntry::Path(path) if path.is_file() => JarProvider::new(path.clone()).class_names()?,
+				ClasspathEntry::Path(_) => Vec::new(),
+				ClasspathEntry::Provider(provider) => provider.class_names()?,
+			});
+		}
+		names.sort();
+		names.dedup();
+		Ok(names)
+	}
+}
+
+/// Parses every class the provider lists, in name order.
+pub fn read_classes(provider: &dyn ClassProvider) -> Result<Vec<IRClassFile>, ClasspathError> {
+	let mut classes = Vec::new();
+	for name in provider.class_names()? {
+		// Listed but gone by now, e.g. deleted from a directory in the meantime.
+		if let Some(bytes) = provider.read_class(&name)? {
+			classes.push(decode(&name, &bytes)?);
+		}
+	}
+	Ok(classes)
+}
+
+fn read_from_dir(root: &Path, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+	match fs::read(root.join(format!("{name}.class"))) {
+		Ok(data) => Ok(Some(data)),
+		Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+		Err(err) => Err(err.into()),
+	}
+}
+
+fn read_from_jar(path: &Path, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+	let mut zip = ZipArchive::new(BufReader::new(File::open(path)?))?;
+	let mut class = match zip.by_name(&format!("{name}.class")) {
+		Ok(class) => class,
+		Err(ZipError::FileNotFound) => return Ok(None),
+		Err(err) => return Err(err.into()),
+	};
+	let mut data = Vec::with_capacity(class.size() as usize);
+	class.read_to_end(&mut data)?;
+	Ok(Some(data))
+}
+
+// Internal names of the classes under `dir`, which is `prefix` below the root.
+fn list_dir(dir: &Path, prefix: &str, names: &mut Vec<String>) -> Result<(), ClasspathError> {
+	for entry in fs::read_dir(dir)? {
+		let entry = entry?;
+		let file_name = entry.file_name();
+		let file_name = file_name.to_string_lossy();
+		if entry.file_type()?.is_dir() {
+			list_dir(&entry.path(), &format!("{prefix}{file_name}/"), names)?;
+		} else if let Some(name) = file_name.strip_suffix(".class") {
+			names.push(format!("{prefix}{name}"));
+		}
+	}
+	Ok(())
+}
+
+/// How much of what it loaded a `ClassCache` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+	/// Every class stays loaded.
+	Unbounded,
+	/// Classes stay loaded until they add up to more than `max_bytes`, then the least recently used ones go. Classes
+	/// are sized by their class file, which their decoded form is roughly proportional to.
+	Lru { max_bytes: usize },
+	/// Only the class file bytes are kept. A decoded class is shared while anyone holds on to it and decoded again
+	/// from the bytes once nobody does.
+	Weak,
+}
+
+/// What a `ClassCache` has done so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+	pub hits: u64,
+	pub misses: u64,
+	// classes decoded, again or for the first time
+	pub decodes: u64,
+	pub evictions: u64,
+}
+
+/// Classes loaded from a class path by internal name, decoded once and shared. Classes that aren't there are
+/// remembered too.
+#[derive(Debug)]
+pub struct ClassCache {
+	classpath: Classpath,
+	policy: CachePolicy,
+	state: RefCell<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+	entries: HashMap<String, CacheEntry>,
+	// LRU order, by the use count at the last use
+	recent: BTreeMap<u64, String>,
+	uses: u64,
+	size: usize,
+	stats: CacheStats,
+}
+
+#[derive(Debug)]
+enum CacheEntry {
+	Missing,
+	Loaded {
+		class: Rc<IRClassFile>,
+		size: usize,
+		last_use: u64,
+	},
+	Bytes {
+		bytes: Vec<u8>,
+		class: Weak<IRClassFile>,
+	},
+}
+
+impl ClassCache {
+	pub fn new(classpath: Classpath, policy: CachePolicy) -> Self {
+		Self {
+			classpath,
+			policy,
+			state: RefCell::default(),
+		}
+	}
+
+	pub fn classpath(&self) -> &Classpath {
+		&self.classpath
+	}
+
+	pub fn policy(&self) -> CachePolicy {
+		self.policy
+	}
+
+	/// The class with this internal name, `None` if the class path doesn't have it.
+	pub fn get(&self, name: &str) -> Result<Option<Rc<IRClassFile>>, ClasspathError> {
+		let mut state = self.state.borrow_mut();
+		let state = &mut *state;
+		state.uses += 1;
+		let uses = state.uses;
+
+		match state.entries.get_mut(name) {
+			Some(CacheEntry::Missing) => {
+				state.stats.hits += 1;
+				return Ok(None);
+			}
+			Some(CacheEntry::Loaded { class, last_use, .. }) => {
+				state.stats.hits += 1;
+				let name = state
+					.recent
+					.remove(last_use)
+					.expect("loaded classes are in the LRU order");
+				state.recent.insert(uses, name);
+				*last_use = uses;
+				return Ok(Some(class.clone()));
+			}
+			Some(CacheEntry::Bytes { bytes, class }) => {
+				if let Some(class) = class.upgrade() {
+					state.stats.hits += 1;
+					return Ok(Some(class));
+				}
+				let decoded = Rc::new(decode(name, bytes)?);
+				*class = Rc::downgrade(&decoded);
+				state.stats.hits += 1;
+				state.stats.decodes += 1;
+				return Ok(Some(decoded));
+			}
+			None => state.stats.misses += 1,
+		}
+
+		let Some(bytes) = self.classpath.read_class(name)? else {
+			state.entries.insert(name.to_string(), CacheEntry::Missing);
+			return Ok(None);
+		};
+		let class = Rc::new(decode(name, &bytes)?);
+		state.stats.decodes += 1;
+		let entry = match self.policy {
+			CachePolicy::Weak => CacheEntry::Bytes {
+				class: Rc::downgrade(&class),
+				bytes,
+			},
+			CachePolicy::Unbounded | CachePolicy::Lru { .. } => {
+				state.size += bytes.len();
+				state.recent.insert(uses, name.to_string());
+				CacheEntry::Loaded {
+					class: class.clone(),
+					size: bytes.len(),
+					last_use: uses,
+				}
+			}
+		};
+		state.entries.insert(name.to_string(), entry);
+
+		if let CachePolicy::Lru { max_bytes } = self.policy {
+			// The class just loaded stays even if it's bigger than the whole budget, it's about to be used.
+			while state.size > max_bytes && state.recent.len() > 1 {
+				let (_, evicted) = state.recent.pop_first().expect("more than one class is loaded");
+				if let Some(CacheEntry::Loaded { size, .. }) = state.entries.remove(&evicted) {
+					state.size -= size;
+					state.stats.evictions += 1;
+				}
+			}
+		}
+		Ok(Some(class))
+	}
+
+	/// Class file bytes of the loaded classes, what `CachePolicy::Lru` limits.
+	pub fn size(&self) -> usize {
+		self.state.borrow().size
+	}
+
+	pub fn stats(&self) -> CacheStats {
+		self.state.borrow().stats
+	}
+
+	/// Forgets everything loaded, classes still in use elsewhere stay alive with their users.
+	pub fn clear(&self) {
+		let mut state = self.state.borrow_mut();
+		let stats = state.stats;
+		*state = CacheState {
+			stats,
+			..Default::default()
+		};
+	}
+}
+
+pub(crate) fn decode(name: &str, bytes: &[u8]) -> Result<IRClassFile, ClasspathError> {
+	IRClassFile::read(bytes).map_err(|source| ClasspathError::Class {
+		name: name.to_string(),
+		source,
+	})
+}
+
+/// The jars a `dir/*` entry stands for: files in `dir` ending in .jar or .JAR, not recursing. java doesn't specify an
+/// order, these are sorted by name so a class path means the same thing everywhere.
+fn jars_in(dir: &Path) -> Vec<PathBuf> {
+	// A wildcard on a directory that doesn't exist expands to nothing.
+	let Ok(read_dir) = fs::read_dir(dir) else {
+		return Vec::new();
+	};
+	let mut jars = read_dir
+		.filter_map(Result::ok)
+		.map(|entry| entry.path())
+		.filter(|path| path.is_file())
+		.filter(|path| path.extension().is_some_and(|ext| ext == "jar" || ext == "JAR"))
+		.collect::<Vec<_>>();
+	jars.sort();
+	jars
+}
+
+/// Replaces every `@file` argument with the arguments in the file, as the launcher does. `@@arg` is the literal `@arg`,
+/// and `--disable-@files` stops expansion for the arguments after it. Argument files don't nest.
+pub fn expand_argfiles<S: AsRef<str>>(args: &[S]) -> Result<Vec<String>, ClasspathError> {
+	let mut out = Vec::with_capacity(args.len());
+	let mut expand = true;
+	for arg in args {
+		let arg = arg.as_ref();
+		match arg.strip_prefix('@') {
+			Some(path) if expand && !path.starts_with('@') => out.extend(read_argfile(Path::new(path))?),
+			Some(escaped) if expand => out.push(escaped.to_string()),
+			_ => {
+				if arg == "--disable-@files" {
+					expand = false;
+				}
+				out.push(arg.to_string());
+			}
+		}
+	}
+	Ok(out)
+}
+
+fn read_argfile(path: &Path) -> Result<Vec<String>, ClasspathError> {
+	let contents = fs::read_to_string(path).map_err(|source| ClasspathError::Argfile {
+		path: path.to_path_buf(),
+		source,
+	})?;
+	Ok(parse_argfile(&contents))
+}
+
+/// Splits the contents of an argument file into arguments. Arguments are separated by whitespace and can be quoted
+/// with `"` or `'`, `#` comments out the rest of the line. Inside quotes a backslash escapes the next character
+/// (`\n`, `\t`, `\r` and `\f` as in C), and one at the end of a line continues the argument on the next, without its
+/// leading whitespace. A quote that isn't closed ends at the end of the line.
+pub fn parse_argfile(contents: &str) -> Vec<String> {
+	let mut args = Vec::new();
+	let mut arg = String::new();
+	let mut in_arg = false;
+	let mut quote = None;
+	let mut chars = contents.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		match quote {
+			Some(q) => match c {
+				c if c == q => quote = None,
+				'\n' | '\r' => {
+					quote = None;
+					args.push(std::mem::take(&mut arg));
+					in_arg = false;
+				}
+				'\\' => match chars.next() {
+					Some('n') => arg.push('\n'),
+					Some('t') => arg.push('\t'),
+					Some('r') => arg.push('\r'),
+					Some('f') => arg.push('\u{c}'),
+					Some('\r' | '\n') => {
+						while chars.peek().is_some_and(|c| c.is_whitespace()) {
+							chars.next();
+						}
+					}
+					Some(c) => arg.push(c),
+					None => {}
+				},
+				c => arg.push(c),
+			},
+			None => match c {
+				'"' | '\'' => {
+					quote = Some(c);
+					in_arg = true;
+				}
+				'#' if !in_arg => while chars.next_if(|c| *c != '\n' && *c != '\r').is_some() {},
+				c if c.is_whitespace() => {
+					if in_arg {
+						args.push(std::mem::take(&mut arg));
+						in_arg = false;
+					}
+				}
+				c => {
+					arg.push(c);
+					in_arg = true;
+				}
+			},
+		}
+	}
+	if in_arg {
+		args.push(arg);
+	}
+	args
+}
+
+#[cfg(test)]
+mod tests {
+	use zip::ZipWriter;
+
+	use super::*;
+	use crate::{builder::ClassBuilder, scratch::ScratchDir};
+
+	#[test]
+	fn launcher_syntax() {
+		assert_eq!(
+			parse_argfile("# build\n-cp \"lib/my app.jar\":'b\\tc' \\x\n  Main\t'unclosed\nnext\n"),
+			["-cp", "lib/my app.jar:b\tc", "\\x", "Main", "unclosed", "next"]
+		);
+		assert_eq!(
+			Classpath::parse_with_separator("a.jar;;classes", ';')
+				.unwrap()
+				.paths()
+				.collect::<Vec<_>>(),
+			[PathBuf::from("a.jar"), PathBuf::from("."), PathBuf::from("classes")]
+		);
+
+		let dir = ScratchDir::new("classpath");
+		fs::create_dir_all(dir.join("lib/nested.jar")).unwrap();
+		for file in ["lib/b.jar", "lib/a.JAR", "lib/c.zip"] {
+			ZipWriter::new(File::create(dir.join(file)).unwrap()).finish().unwrap();
+		}
+		fs::create_dir_all(dir.join("classes/p")).unwrap();
+		fs::write(dir.join("classes/p/A.class"), b"class").unwrap();
+		let argfile = dir.join("args");
+		fs::write(&argfile, format!("-cp \"{0}/lib/*:{0}/classes\"\nMain", dir.display())).unwrap();
+
+		let classpath = Classpath::parse_with_separator(&format!("@{}", argfile.display()), ':').unwrap();
+		assert_eq!(
+			classpath.paths().collect::<Vec<_>>(),
+			[
+				dir.join("lib/a.JAR"),
+				dir.join("lib/b.jar"),
+				PathBuf::from(format!("{}/classes", dir.display()))
+			]
+		);
+		let from_args = Classpath::from_args(&["-ea", &format!("@{}", argfile.display()), "-cp", "ignored"]).unwrap();
+		assert_eq!(from_args.unwrap().entries(), classpath.entries());
+		assert_eq!(classpath.read_class("p/A").unwrap().unwrap(), b"class");
+		assert_eq!(classpath.read_class("p/B").unwrap(), None);
+	}
+
+	#[test]
+	fn cache_policies() {
+		let dir = ScratchDir::new("class-cache");
+		fs::create_dir_all(dir.join("p")).unwrap();
+		let mut size = 0;
+		for name in ["p/A", "p/B", "p/C"] {
+			let bytes = ClassBuilder::new(name).unwrap().to_bytes().unwrap();
+			size = bytes.len();
+			fs::write(dir.join(format!("{name}.class")), bytes).unwrap();
+		}
+		let classpath = Classpath::new(vec![dir.to_path_buf()]);
+
+		let lru = ClassCache::new(classpath.clone(), CachePolicy::Lru { max_bytes: size * 2 });
+		for name in ["p/A", "p/B", "p/A", "p/C", "p/Missing", "p/Missing"] {
+			lru.get(name).unwrap();
+		}
+		// B was used least recently when C came in.
+		assert_eq!(lru.size(), size * 2);
+		assert_eq!(lru.get("p/A").unwrap().unwrap().class_name(), "p/A");
+		assert_eq!(
+			lru.stats(),
+			CacheStats {
+				hits: 3,
+				misses: 4,
+				decodes: 3,
+				evictions: 1,
+			}
+		);
+		lru.get("p/B").unwrap();
+		assert_eq!(lru.stats().decodes, 4);
+
+		let weak = ClassCache::new(classpath, CachePolicy::Weak);
+		let held = weak.get("p/A").unwrap().unwrap();
+		assert!(Rc::ptr_eq(&held, &weak.get("p/A").unwrap().unwrap()));
+		drop(held);
+		weak.get("p/A").unwrap();
+		assert_eq!(weak.stats().decodes, 2);
+		assert_eq!(weak.size(), 0);
+	}
+
+	#[test]
+	fn composite_providers() {
+		let dir = ScratchDir::new("class-providers");
+		fs::create_dir_all(dir.join("p/q")).unwrap();
+		let class = |name: &str| ClassBuilder::new(name).unwrap().to_bytes().unwrap();
+		fs::write(dir.join("p/A.class"), class("p/A")).unwrap();
+		fs::write(dir.join("p/q/B.class"), class("p/q/B")).unwrap();
+		fs::write(dir.join("p/README"), b"not a class").unwrap();
+
+		let mut memory = MemoryProvider::new();
+		memory.insert("p/A", class("p/Shadowed"));
+		memory.insert("p/C", class("p/C"));
+		let fetches = Rc::new(RefCell::new(Vec::new()));
+		let fetched = fetches.clone();
+		let fetch = FetchProvider::new(move |name: &str| {
+			fetched.borrow_mut().push(name.to_string());
+			Ok((name == "p/Remote").then(|| class("p/Remote")))
+		});
+
+		let mut classpath = Classpath::new(vec![dir.join("missing"), dir.to_path_buf()]);
+		classpath.push_provider(Rc::new(memory));
+		classpath.push_provider(Rc::new(fetch));
+		assert_eq!(
+			classpath.to_string_lossy(),
+			format!("{0}/missing{1}{0}", dir.display(), Classpath::SEPARATOR)
+		);
+		assert_eq!(classpath.class_names().unwrap(), ["p/A", "p/C", "p/q/B"]);
+
+		// The directory comes first and shadows the in-memory p/A, the fetcher is only asked for what's left.
+		let names = read_classes(&classpath)
+			.unwrap()
+			.iter()
+			.map(|class| class.class_name().to_string())
+			.collect::<Vec<_>>();
+		assert_eq!(names, ["p/A", "p/C", "p/q/B"]);
+		assert!(fetches.borrow().is_empty());
+
+		let cache = ClassCache::new(classpath, CachePolicy::Unbounded);
+		assert_eq!(cache.get("p/Remote").unwrap().unwrap().class_name(), "p/Remote");
+		assert!(cache.get("p/Nowhere").unwrap().is_none());
+		assert_eq!(*fetches.borrow(), ["p/Remote", "p/Nowhere"]);
+	}
+}
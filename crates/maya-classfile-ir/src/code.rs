@@ -1,8 +1,13 @@
-use maya_bytes::BytesReadExt;
+use std::io::Cursor;
 
-use crate::class_pool::{
-	CPClassRef, CPFieldRef, CPInterfaceMethodRef, CPInvokeDynamicRef, CPMethodHandleRef, CPMethodRef, CPUtf8Ref,
-	IRClassfileError, IRCpTag,
+use maya_bytes::{BytesError, BytesReadExt};
+
+use crate::{
+	class_pool::{
+		cp_get, CPClassRef, CPFieldRef, CPInterfaceMethodRef, CPInvokeDynamicRef, CPMethodRef, IRClassfileError,
+		IRCpTag,
+	},
+	parse::capacity,
 };
 
 #[allow(non_camel_case_types)]
@@ -10,169 +15,388 @@ use crate::class_pool::{
 pub struct Opcodes {}
 
 impl Opcodes {
-	const NOP: u8 = 0;
-	const ACONST_NULL: u8 = 1;
-	const ICONST_M1: u8 = 2;
-	const ICONST_0: u8 = 3;
-	const ICONST_1: u8 = 4;
-	const ICONST_2: u8 = 5;
-	const ICONST_3: u8 = 6;
-	const ICONST_4: u8 = 7;
-	const ICONST_5: u8 = 8;
-	const LCONST_0: u8 = 9;
-	const LCONST_1: u8 = 10;
-	const FCONST_0: u8 = 11;
-	const FCONST_1: u8 = 12;
-	const FCONST_2: u8 = 13;
-	const DCONST_0: u8 = 14;
-	const DCONST_1: u8 = 15;
-	const BIPUSH: u8 = 16;
-	const SIPUSH: u8 = 17;
-	const LDC: u8 = 18;
-	const ILOAD: u8 = 21;
-	const LLOAD: u8 = 22;
-	const FLOAD: u8 = 23;
-	const DLOAD: u8 = 24;
-	const ALOAD: u8 = 25;
-	const IALOAD: u8 = 46;
-	const LALOAD: u8 = 47;
-	const FALOAD: u8 = 48;
-	const DALOAD: u8 = 49;
-	const AALOAD: u8 = 50;
-	const BALOAD: u8 = 51;
-	const CALOAD: u8 = 52;
-	const SALOAD: u8 = 53;
-	const ISTORE: u8 = 54;
-	const LSTORE: u8 = 55;
-	const FSTORE: u8 = 56;
-	const DSTORE: u8 = 57;
-	const ASTORE: u8 = 58;
-	const IASTORE: u8 = 79;
-	const LASTORE: u8 = 80;
-	const FASTORE: u8 = 81;
-	const DASTORE: u8 = 82;
-	const AASTORE: u8 = 83;
-	const BASTORE: u8 = 84;
-	const CASTORE: u8 = 85;
-	const SASTORE: u8 = 86;
-	const POP: u8 = 87;
-	const POP2: u8 = 88;
-	const DUP: u8 = 89;
-	const DUP_X1: u8 = 90;
-	const DUP_X2: u8 = 91;
-	const DUP2: u8 = 92;
-	const DUP2_X1: u8 = 93;
-	const DUP2_X2: u8 = 94;
-	const SWAP: u8 = 95;
-	const IADD: u8 = 96;
-	const LADD: u8 = 97;
-	const FADD: u8 = 98;
-	const DADD: u8 = 99;
-	const ISUB: u8 = 100;
-	const LSUB: u8 = 101;
-	const FSUB: u8 = 102;
-	const DSUB: u8 = 103;
-	const IMUL: u8 = 104;
-	const LMUL: u8 = 105;
-	const FMUL: u8 = 106;
-	const DMUL: u8 = 107;
-	const IDIV: u8 = 108;
-	const LDIV: u8 = 109;
-	const FDIV: u8 = 110;
-	const DDIV: u8 = 111;
-	const IREM: u8 = 112;
-	const LREM: u8 = 113;
-	const FREM: u8 = 114;
-	const DREM: u8 = 115;
-	const INEG: u8 = 116;
-	const LNEG: u8 = 117;
-	const FNEG: u8 = 118;
-	const DNEG: u8 = 119;
-	const ISHL: u8 = 120;
-	const LSHL: u8 = 121;
-	const ISHR: u8 = 122;
-	const LSHR: u8 = 123;
-	const IUSHR: u8 = 124;
-	const LUSHR: u8 = 125;
-	const IAND: u8 = 126;
-	const LAND: u8 = 127;
-	const IOR: u8 = 128;
-	const LOR: u8 = 129;
-	const IXOR: u8 = 130;
-	const LXOR: u8 = 131;
-	const IINC: u8 = 132;
-	const I2L: u8 = 133;
-	const I2F: u8 = 134;
-	const I2D: u8 = 135;
-	const L2I: u8 = 136;
-	const L2F: u8 = 137;
-	const L2D: u8 = 138;
-	const F2I: u8 = 139;
-	const F2L: u8 = 140;
-	const F2D: u8 = 141;
-	const D2I: u8 = 142;
-	const D2L: u8 = 143;
-	const D2F: u8 = 144;
-	const I2B: u8 = 145;
-	const I2C: u8 = 146;
-	const I2S: u8 = 147;
-	const LCMP: u8 = 148;
-	const FCMPL: u8 = 149;
-	const FCMPG: u8 = 150;
-	const DCMPL: u8 = 151;
-	const DCMPG: u8 = 152;
-	const IFEQ: u8 = 153;
-	const IFNE: u8 = 154;
-	const IFLT: u8 = 155;
-	const IFGE: u8 = 156;
-	const IFGT: u8 = 157;
-	const IFLE: u8 = 158;
-	const IF_ICMPEQ: u8 = 159;
-	const IF_ICMPNE: u8 = 160;
-	const IF_ICMPLT: u8 = 161;
-	const IF_ICMPGE: u8 = 162;
-	const IF_ICMPGT: u8 = 163;
-	const IF_ICMPLE: u8 = 164;
-	const IF_ACMPEQ: u8 = 165;
-	const IF_ACMPNE: u8 = 166;
-	const GOTO: u8 = 167;
-	const JSR: u8 = 168;
-	const RET: u8 = 169;
-	const TABLESWITCH: u8 = 170;
-	const LOOKUPSWITCH: u8 = 171;
-	const IRETURN: u8 = 172;
-	const LRETURN: u8 = 173;
-	const FRETURN: u8 = 174;
-	const DRETURN: u8 = 175;
-	const ARETURN: u8 = 176;
-	const RETURN: u8 = 177;
-	const GETSTATIC: u8 = 178;
-	const PUTSTATIC: u8 = 179;
-	const GETFIELD: u8 = 180;
-	const PUTFIELD: u8 = 181;
-	const INVOKEVIRTUAL: u8 = 182;
-	const INVOKESPECIAL: u8 = 183;
-	const INVOKESTATIC: u8 = 184;
-	const INVOKEINTERFACE: u8 = 185;
-	const INVOKEDYNAMIC: u8 = 186;
-	const NEW: u8 = 187;
-	const NEWARRAY: u8 = 188;
-	const ANEWARRAY: u8 = 189;
-	const ARRAYLENGTH: u8 = 190;
-	const ATHROW: u8 = 191;
-	const CHECKCAST: u8 = 192;
-	const INSTANCEOF: u8 = 193;
-	const MONITORENTER: u8 = 194;
-	const MONITOREXIT: u8 = 195;
-	const MULTIANEWARRAY: u8 = 197;
-	const IFNULL: u8 = 198;
-	const IFNONNULL: u8 = 199;
+	pub const NOP: u8 = 0;
+	pub const ACONST_NULL: u8 = 1;
+	pub const ICONST_M1: u8 = 2;
+	pub const ICONST_0: u8 = 3;
+	pub const ICONST_1: u8 = 4;
+	pub const ICONST_2: u8 = 5;
+	pub const ICONST_3: u8 = 6;
+	pub const ICONST_4: u8 = 7;
+	pub const ICONST_5: u8 = 8;
+	pub const LCONST_0: u8 = 9;
+	pub const LCONST_1: u8 = 10;
+	pub const FCONST_0: u8 = 11;
+	pub const FCONST_1: u8 = 12;
+	pub const FCONST_2: u8 = 13;
+	pub const DCONST_0: u8 = 14;
+	pub const DCONST_1: u8 = 15;
+	pub const BIPUSH: u8 = 16;
+	pub const SIPUSH: u8 = 17;
+	pub const LDC: u8 = 18;
+	pub const LDC_W: u8 = 19;
+	pub const LDC2_W: u8 = 20;
+	pub const ILOAD: u8 = 21;
+	pub const LLOAD: u8 = 22;
+	pub const FLOAD: u8 = 23;
+	pub const DLOAD: u8 = 24;
+	pub const ALOAD: u8 = 25;
+	pub const IALOAD: u8 = 46;
+	pub const LALOAD: u8 = 47;
+	pub const FALOAD: u8 = 48;
+	pub const DALOAD: u8 = 49;
+	pub const AALOAD: u8 = 50;
+	pub const BALOAD: u8 = 51;
+	pub const CALOAD: u8 = 52;
+	pub const SALOAD: u8 = 53;
+	pub const ISTORE: u8 = 54;
+	pub const LSTORE: u8 = 55;
+	pub const FSTORE: u8 = 56;
+	pub const DSTORE: u8 = 57;
+	pub const ASTORE: u8 = 58;
+	pub const IASTORE: u8 = 79;
+	pub const LASTORE: u8 = 80;
+	pub const FASTORE: u8 = 81;
+	pub const DASTORE: u8 = 82;
+	pub const AASTORE: u8 = 83;
+	pub const BASTORE: u8 = 84;
+	pub const CASTORE: u8 = 85;
+	pub const SASTORE: u8 = 86;
+	pub const POP: u8 = 87;
+	pub const POP2: u8 = 88;
+	pub const DUP: u8 = 89;
+	pub const DUP_X1: u8 = 90;
+	pub const DUP_X2: u8 = 91;
+	pub const DUP2: u8 = 92;
+	pub const DUP2_X1: u8 = 93;
+	pub const DUP2_X2: u8 = 94;
+	pub const SWAP: u8 = 95;
+	pub const IADD: u8 = 96;
+	pub const LADD: u8 = 97;
+	pub const FADD: u8 = 98;
+	pub const DADD: u8 = 99;
+	pub const ISUB: u8 = 100;
+	pub const LSUB: u8 = 101;
+	pub const FSUB: u8 = 102;
+	pub const DSUB: u8 = 103;
+	pub const IMUL: u8 = 104;
+	pub const LMUL: u8 = 105;
+	pub const FMUL: u8 = 106;
+	pub const DMUL: u8 = 107;
+	pub const IDIV: u8 = 108;
+	pub const LDIV: u8 = 109;
+	pub const FDIV: u8 = 110;
+	pub const DDIV: u8 = 111;
+	pub const IREM: u8 = 112;
+	pub const LREM: u8 = 113;
+	pub const FREM: u8 = 114;
+	pub const DREM: u8 = 115;
+	pub const INEG: u8 = 116;
+	pub const LNEG: u8 = 117;
+	pub const FNEG: u8 = 118;
+	pub const DNEG: u8 = 119;
+	pub const ISHL: u8 = 120;
+	pub const LSHL: u8 = 121;
+	pub const ISHR: u8 = 122;
+	pub const LSHR: u8 = 123;
+	pub const IUSHR: u8 = 124;
+	pub const LUSHR: u8 = 125;
+	pub const IAND: u8 = 126;
+	pub const LAND: u8 = 127;
+	pub const IOR: u8 = 128;
+	pub const LOR: u8 = 129;
+	pub const IXOR: u8 = 130;
+	pub const LXOR: u8 = 131;
+	pub const IINC: u8 = 132;
+	pub const I2L: u8 = 133;
+	pub const I2F: u8 = 134;
+	pub const I2D: u8 = 135;
+	pub const L2I: u8 = 136;
+	pub const L2F: u8 = 137;
+	pub const L2D: u8 = 138;
+	pub const F2I: u8 = 139;
+	pub const F2L: u8 = 140;
+	pub const F2D: u8 = 141;
+	pub const D2I: u8 = 142;
+	pub const D2L: u8 = 143;
+	pub const D2F: u8 = 144;
+	pub const I2B: u8 = 145;
+	pub const I2C: u8 = 146;
+	pub const I2S: u8 = 147;
+	pub const LCMP: u8 = 148;
+	pub const FCMPL: u8 = 149;
+	pub const FCMPG: u8 = 150;
+	pub const DCMPL: u8 = 151;
+	pub const DCMPG: u8 = 152;
+	pub const IFEQ: u8 = 153;
+	pub const IFNE: u8 = 154;
+	pub const IFLT: u8 = 155;
+	pub const IFGE: u8 = 156;
+	pub const IFGT: u8 = 157;
+	pub const IFLE: u8 = 158;
+	pub const IF_ICMPEQ: u8 = 159;
+	pub const IF_ICMPNE: u8 = 160;
+	pub const IF_ICMPLT: u8 = 161;
+	pub const IF_ICMPGE: u8 = 162;
+	pub const IF_ICMPGT: u8 = 163;
+	pub const IF_ICMPLE: u8 = 164;
+	pub const IF_ACMPEQ: u8 = 165;
+	pub const IF_ACMPNE: u8 = 166;
+	pub const GOTO: u8 = 167;
+	pub const JSR: u8 = 168;
+	pub const RET: u8 = 169;
+	pub const TABLESWITCH: u8 = 170;
+	pub const LOOKUPSWITCH: u8 = 171;
+	pub const IRETURN: u8 = 172;
+	pub const LRETURN: u8 = 173;
+	pub const FRETURN: u8 = 174;
+	pub const DRETURN: u8 = 175;
+	pub const ARETURN: u8 = 176;
+	pub const RETURN: u8 = 177;
+	pub const GETSTATIC: u8 = 178;
+	pub const PUTSTATIC: u8 = 179;
+	pub const GETFIELD: u8 = 180;
+	pub const PUTFIELD: u8 = 181;
+	pub const INVOKEVIRTUAL: u8 = 182;
+	pub const INVOKESPECIAL: u8 = 183;
+	pub const INVOKESTATIC: u8 = 184;
+	pub const INVOKEINTERFACE: u8 = 185;
+	pub const INVOKEDYNAMIC: u8 = 186;
+	pub const NEW: u8 = 187;
+	pub const NEWARRAY: u8 = 188;
+	pub const ANEWARRAY: u8 = 189;
+	pub const ARRAYLENGTH: u8 = 190;
+	pub const ATHROW: u8 = 191;
+	pub const CHECKCAST: u8 = 192;
+	pub const INSTANCEOF: u8 = 193;
+	pub const MONITORENTER: u8 = 194;
+	pub const MONITOREXIT: u8 = 195;
+	pub const WIDE: u8 = 196;
+	pub const MULTIANEWARRAY: u8 = 197;
+	pub const IFNULL: u8 = 198;
+	pub const IFNONNULL: u8 = 199;
+	pub const GOTO_W: u8 = 200;
+	pub const JSR_W: u8 = 201;
+
+	/// The mnemonic javap prints for `opcode`, `None` for bytes that aren't opcodes.
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-7.html
+	pub fn mnemonic(opcode: u8) -> Option<&'static str> {
+		Some(match opcode {
+			Self::NOP => "nop",
+			Self::ACONST_NULL => "aconst_null",
+			Self::ICONST_M1 => "iconst_m1",
+			Self::ICONST_0 => "iconst_0",
+			Self::ICONST_1 => "iconst_1",
+			Self::ICONST_2 => "iconst_2",
+			Self::ICONST_3 => "iconst_3",
+			Self::ICONST_4 => "iconst_4",
+			Self::ICONST_5 => "iconst_5",
+			Self::LCONST_0 => "lconst_0",
+			Self::LCONST_1 => "lconst_1",
+			Self::FCONST_0 => "fconst_0",
+			Self::FCONST_1 => "fconst_1",
+			Self::FCONST_2 => "fconst_2",
+			Self::DCONST_0 => "dconst_0",
+			Self::DCONST_1 => "dconst_1",
+			Self::BIPUSH => "bipush",
+			Self::SIPUSH => "sipush",
+			Self::LDC => "ldc",
+			Self::LDC_W => "ldc_w",
+			Self::LDC2_W => "ldc2_w",
+			Self::ILOAD => "iload",
+			Self::LLOAD => "lload",
+			Self::FLOAD => "fload",
+			Self::DLOAD => "dload",
+			Self::ALOAD => "aload",
+			0x1A => "iload_0",
+			0x1B => "iload_1",
+			0x1C => "iload_2",
+			0x1D => "iload_3",
+			0x1E => "lload_0",
+			0x1F => "lload_1",
+			0x20 => "lload_2",
+			0x21 => "lload_3",
+			0x22 => "fload_0",
+			0x23 => "fload_1",
+			0x24 => "fload_2",
+			0x25 => "fload_3",
+			0x26 => "dload_0",
+			0x27 => "dload_1",
+			0x28 => "dload_2",
+			0x29 => "dload_3",
+			0x2A => "aload_0",
+			0x2B => "aload_1",
+			0x2C => "aload_2",
+			0x2D => "aload_3",
+			Self::IALOAD => "iaload",
+			Self::LALOAD => "laload",
+			Self::FALOAD => "faload",
+			Self::DALOAD => "daload",
+			Self::AALOAD => "aaload",
+			Self::BALOAD => "baload",
+			Self::CALOAD => "caload",
+			Self::SALOAD => "saload",
+			Self::ISTORE => "istore",
+			Self::LSTORE => "lstore",
+			Self::FSTORE => "fstore",
+			Self::DSTORE => "dstore",
+			Self::ASTORE => "astore",
+			0x3B => "istore_0",
+			0x3C => "istore_1",
+			0x3D => "istore_2",
+			0x3E => "istore_3",
+			0x3F => "lstore_0",
+			0x40 => "lstore_1",
+			0x41 => "lstore_2",
+			0x42 => "lstore_3",
+			0x43 => "fstore_0",
+			0x44 => "fstore_1",
+			0x45 => "fstore_2",
+			0x46 => "fstore_3",
+			0x47 => "dstore_0",
+			0x48 => "dstore_1",
+			0x49 => "dstore_2",
+			0x4A => "dstore_3",
+			0x4B => "astore_0",
+			0x4C => "astore_1",
+			0x4D => "astore_2",
+			0x4E => "astore_3",
+			Self::IASTORE => "iastore",
+			Self::LASTORE => "lastore",
+			Self::FASTORE => "fastore",
+			Self::DASTORE => "dastore",
+			Self::AASTORE => "aastore",
+			Self::BASTORE => "bastore",
+			Self::CASTORE => "castore",
+			Self::SASTORE => "sastore",
+			Self::POP => "pop",
+			Self::POP2 => "pop2",
+			Self::DUP => "dup",
+			Self::DUP_X1 => "dup_x1",
+			Self::DUP_X2 => "dup_x2",
+			Self::DUP2 => "dup2",
+			Self::DUP2_X1 => "dup2_x1",
+			Self::DUP2_X2 => "dup2_x2",
+			Self::SWAP => "swap",
+			Self::IADD => "iadd",
+			Self::LADD => "ladd",
+			Self::FADD => "fadd",
+			Self::DADD => "dadd",
+			Self::ISUB => "isub",
+			Self::LSUB => "lsub",
+			Self::FSUB => "fsub",
+			Self::DSUB => "dsub",
+			Self::IMUL => "imul",
+			Self::LMUL => "lmul",
+			Self::FMUL => "fmul",
+			Self::DMUL => "dmul",
+			Self::IDIV => "idiv",
+			Self::LDIV => "ldiv",
+			Self::FDIV => "fdiv",
+			Self::DDIV => "ddiv",
+			Self::IREM => "irem",
+			Self::LREM => "lrem",
+			Self::FREM => "frem",
+			Self::DREM => "drem",
+			Self::INEG => "ineg",
+			Self::LNEG => "lneg",
+			Self::FNEG => "fneg",
+			Self::DNEG => "dneg",
+			Self::ISHL => "ishl",
+			Self::LSHL => "lshl",
+			Self::ISHR => "ishr",
+			Self::LSHR => "lshr",
+			Self::IUSHR => "iushr",
+			Self::LUSHR => "lushr",
+			Self::IAND => "iand",
+			Self::LAND => "land",
+			Self::IOR => "ior",
+			Self::LOR => "lor",
+			Self::IXOR => "ixor",
+			Self::LXOR => "lxor",
+			Self::IINC => "iinc",
+			Self::I2L => "i2l",
+			Self::I2F => "i2f",
+			Self::I2D => "i2d",
+			Self::L2I => "l2i",
+			Self::L2F => "l2f",
+			Self::L2D => "l2d",
+			Self::F2I => "f2i",
+			Self::F2L => "f2l",
+			Self::F2D => "f2d",
+			Self::D2I => "d2i",
+			Self::D2L => "d2l",
+			Self::D2F => "d2f",
+			Self::I2B => "i2b",
+			Self::I2C => "i2c",
+			Self::I2S => "i2s",
+			Self::LCMP => "lcmp",
+			Self::FCMPL => "fcmpl",
+			Self::FCMPG => "fcmpg",
+			Self::DCMPL => "dcmpl",
+			Self::DCMPG => "dcmpg",
+			Self::IFEQ => "ifeq",
+			Self::IFNE => "ifne",
+			Self::IFLT => "iflt",
+			Self::IFGE => "ifge",
+			Self::IFGT => "ifgt",
+			Self::IFLE => "ifle",
+			Self::IF_ICMPEQ => "if_icmpeq",
+			Self::IF_ICMPNE => "if_icmpne",
+			Self::IF_ICMPLT => "if_icmplt",
+			Self::IF_ICMPGE => "if_icmpge",
+			Self::IF_ICMPGT => "if_icmpgt",
+			Self::IF_ICMPLE => "if_icmple",
+			Self::IF_ACMPEQ => "if_acmpeq",
+			Self::IF_ACMPNE => "if_acmpne",
+			Self::GOTO => "goto",
+			Self::JSR => "jsr",
+			Self::RET => "ret",
+			Self::TABLESWITCH => "tableswitch",
+			Self::LOOKUPSWITCH => "lookupswitch",
+			Self::IRETURN => "ireturn",
+			Self::LRETURN => "lreturn",
+			Self::FRETURN => "freturn",
+			Self::DRETURN => "dreturn",
+			Self::ARETURN => "areturn",
+			Self::RETURN => "return",
+			Self::GETSTATIC => "getstatic",
+			Self::PUTSTATIC => "putstatic",
+			Self::GETFIELD => "getfield",
+			Self::PUTFIELD => "putfield",
+			Self::INVOKEVIRTUAL => "invokevirtual",
+			Self::INVOKESPECIAL => "invokespecial",
+			Self::INVOKESTATIC => "invokestatic",
+			Self::INVOKEINTERFACE => "invokeinterface",
+			Self::INVOKEDYNAMIC => "invokedynamic",
+			Self::NEW => "new",
+			Self::NEWARRAY => "newarray",
+			Self::ANEWARRAY => "anewarray",
+			Self::ARRAYLENGTH => "arraylength",
+			Self::ATHROW => "athrow",
+			Self::CHECKCAST => "checkcast",
+			Self::INSTANCEOF => "instanceof",
+			Self::MONITORENTER => "monitorenter",
+			Self::MONITOREXIT => "monitorexit",
+			Self::WIDE => "wide",
+			Self::MULTIANEWARRAY => "multianewarray",
+			Self::IFNULL => "ifnull",
+			Self::IFNONNULL => "ifnonnull",
+			Self::GOTO_W => "goto_w",
+			Self::JSR_W => "jsr_w",
+			_ => return None,
+		})
+	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 #[allow(non_camel_case_types)]
 /// An 'Instructions' variant represents an Opcode with the data it contains, if any.
+///
+/// Short forms are folded into their general instruction (`aload_0` reads as `ALOAD(0)`, `ldc_w` and `ldc2_w` as `LDC`,
+/// `wide` prefixes into the widened instruction), and branch offsets are relative to the pc of the instruction.
 pub enum Instructions {
 	NOP = 0,
 	ACONST_NULL = 1,
@@ -190,14 +414,14 @@ pub enum Instructions {
 	FCONST_2 = 13,
 	DCONST_0 = 14,
 	DCONST_1 = 15,
-	BIPUSH(u8) = 16,
-	SIPUSH(u16) = 17,
+	BIPUSH(i8) = 16,
+	SIPUSH(i16) = 17,
 	LDC(IRCpTag) = 18,
-	ILOAD(u8) = 21,
-	LLOAD(u8) = 22,
-	FLOAD(u8) = 23,
-	DLOAD(u8) = 24,
-	ALOAD(u8) = 25,
+	ILOAD(u16) = 21,
+	LLOAD(u16) = 22,
+	FLOAD(u16) = 23,
+	DLOAD(u16) = 24,
+	ALOAD(u16) = 25,
 	IALOAD = 46,
 	LALOAD = 47,
 	FALOAD = 48,
@@ -206,11 +430,11 @@ pub enum Instructions {
 	BALOAD = 51,
 	CALOAD = 52,
 	SALOAD = 53,
-	ISTORE(u8) = 54,
-	LSTORE(u8) = 55,
-	FSTORE(u8) = 56,
-	DSTORE(u8) = 57,
-	ASTORE(u8) = 58,
+	ISTORE(u16) = 54,
+	LSTORE(u16) = 55,
+	FSTORE(u16) = 56,
+	DSTORE(u16) = 57,
+	ASTORE(u16) = 58,
 	IASTORE = 79,
 	LASTORE = 80,
 	FASTORE = 81,
@@ -264,8 +488,11 @@ pub enum Instructions {
 	LOR = 129,
 	IXOR = 130,
 	LXOR = 131,
-	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5.areturn
-	IINC { index: u8, r#const: u8 } = 132,
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5.iinc
+	IINC {
+		index: u16,
+		r#const: i16,
+	} = 132,
 	I2L = 133,
 	I2F = 134,
 	I2D = 135,
@@ -286,25 +513,35 @@ pub enum Instructions {
 	FCMPG = 150,
 	DCMPL = 151,
 	DCMPG = 152,
-	IFEQ(u16) = 153,
-	IFNE(u16) = 154,
-	IFLT(u16) = 155,
-	IFGE(u16) = 156,
-	IFGT(u16) = 157,
-	IFLE(u16) = 158,
-	IF_ICMPEQ(u16) = 159,
-	IF_ICMPNE(u16) = 160,
-	IF_ICMPLT(u16) = 161,
-	IF_ICMPGE(u16) = 162,
-	IF_ICMPGT(u16) = 163,
-	IF_ICMPLE(u16) = 164,
-	IF_ACMPEQ(u16) = 165,
-	IF_ACMPNE(u16) = 166,
-	GOTO(u16) = 167,
-	JSR = 168,
-	RET = 169,
-	TABLESWITCH = 170,
-	LOOKUPSWITCH = 171,
+	IFEQ(i16) = 153,
+	IFNE(i16) = 154,
+	IFLT(i16) = 155,
+	IFGE(i16) = 156,
+	IFGT(i16) = 157,
+	IFLE(i16) = 158,
+	IF_ICMPEQ(i16) = 159,
+	IF_ICMPNE(i16) = 160,
+	IF_ICMPLT(i16) = 161,
+	IF_ICMPGE(i16) = 162,
+	IF_ICMPGT(i16) = 163,
+	IF_ICMPLE(i16) = 164,
+	IF_ACMPEQ(i16) = 165,
+	IF_ACMPNE(i16) = 166,
+	GOTO(i16) = 167,
+	JSR(i16) = 168,
+	RET(u16) = 169,
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5.tableswitch
+	TABLESWITCH {
+		default: i32,
+		low: i32,
+		high: i32,
+		offsets: Vec<i32>,
+	} = 170,
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5.lookupswitch
+	LOOKUPSWITCH {
+		default: i32,
+		pairs: Vec<(i32, i32)>,
+	} = 171,
 	IRETURN = 172,
 	LRETURN = 173,
 	FRETURN = 174,
@@ -318,10 +555,13 @@ pub enum Instructions {
 	INVOKEVIRTUAL(CPMethodRef) = 182,
 	INVOKESPECIAL(CPMethodRef) = 183,
 	INVOKESTATIC(CPMethodRef) = 184,
-	INVOKEINTERFACE { method: CPInterfaceMethodRef, count: u8 } = 185,
+	INVOKEINTERFACE {
+		method: CPInterfaceMethodRef,
+		count: u8,
+	} = 185,
 	INVOKEDYNAMIC(CPInvokeDynamicRef) = 186,
 	NEW(CPClassRef) = 187,
-	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5.areturn
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5.newarray
 	NEWARRAY(u8) = 188,
 	ANEWARRAY(CPClassRef) = 189,
 	ARRAYLENGTH = 190,
@@ -330,59 +570,23 @@ pub enum Instructions {
 	INSTANCEOF(CPClassRef) = 193,
 	MONITORENTER = 194,
 	MONITOREXIT = 195,
-	MULTIANEWARRAY = 197,
-	IFNULL(u16) = 198,
-	IFNONNULL(u16) = 199,
+	MULTIANEWARRAY {
+		class: CPClassRef,
+		dimensions: u8,
+	} = 197,
+	IFNULL(i16) = 198,
+	IFNONNULL(i16) = 199,
+	GOTO_W(i32) = 200,
+	JSR_W(i32) = 201,
 }
 
 impl Instructions {
+	/// Reads one instruction. `buffer` must be positioned relative to the start of the code array,
+	/// the padding of the switch instructions depends on it.
 	pub fn read<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B) -> Result<Instructions, IRClassfileError> {
 		Ok(match buffer.read_u8()? {
-			Opcodes::GETFIELD => Instructions::GETFIELD(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::GETSTATIC => Instructions::GETSTATIC(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::PUTSTATIC => Instructions::PUTSTATIC(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::LDC => Instructions::LDC(
-				cp.get(buffer.read_u8()?.saturating_sub(1) as usize)
-					.cloned()
-					.expect("fuck"),
-			),
-			/* LDC_W */
-			0x13 => Instructions::LDC(
-				cp.get(buffer.read_u16()?.saturating_sub(1) as usize)
-					.cloned()
-					.expect("fuck"),
-			),
-			Opcodes::INVOKEVIRTUAL => Instructions::INVOKEVIRTUAL(CPMethodRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::INVOKESPECIAL => Instructions::INVOKESPECIAL(CPMethodRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::INVOKESTATIC => Instructions::INVOKESTATIC(CPMethodRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::INVOKEINTERFACE => {
-				let s = Instructions::INVOKEINTERFACE {
-					method: CPInterfaceMethodRef::from_cp(cp, buffer.read_u16()?),
-					count: buffer.read_u8()?,
-				};
-				buffer.read_u8()?;
-				s
-			}
-			Opcodes::INVOKEDYNAMIC => {
-				let s = Instructions::INVOKEDYNAMIC(CPInvokeDynamicRef::from_cp(cp, buffer.read_u16()?));
-				buffer.read_u16()?;
-				s
-			}
-			Opcodes::RETURN => Instructions::RETURN,
-			Opcodes::ALOAD => Instructions::ALOAD(buffer.read_u8()?),
-			/* aload_0 */ 0x2A => Instructions::ALOAD(0),
-			/* aload_1 */ 0x2B => Instructions::ALOAD(1),
-			/* aload_2 */ 0x2C => Instructions::ALOAD(2),
-			/* aload_3 */ 0x2D => Instructions::ALOAD(3),
-
-			Opcodes::ASTORE => Instructions::ASTORE(buffer.read_u8()?),
-			/* astore_0 */ 0x4B => Instructions::ASTORE(0),
-			/* astore_1 */ 0x4C => Instructions::ASTORE(1),
-			/* astore_2 */ 0x4D => Instructions::ASTORE(2),
-			/* astore_3 */ 0x4E => Instructions::ASTORE(3),
-			Opcodes::NEW => Instructions::NEW(CPClassRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::DUP => Instructions::DUP,
-			Opcodes::PUTFIELD => Instructions::PUTFIELD(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::NOP => Instructions::NOP,
+			Opcodes::ACONST_NULL => Instructions::ACONST_NULL,
 			Opcodes::ICONST_M1 => Instructions::ICONST_M1,
 			Opcodes::ICONST_0 => Instructions::ICONST_0,
 			Opcodes::ICONST_1 => Instructions::ICONST_1,
@@ -390,88 +594,539 @@ impl Instructions {
 			Opcodes::ICONST_3 => Instructions::ICONST_3,
 			Opcodes::ICONST_4 => Instructions::ICONST_4,
 			Opcodes::ICONST_5 => Instructions::ICONST_5,
-			Opcodes::IRETURN => Instructions::IRETURN,
-			Opcodes::ARETURN => Instructions::ARETURN,
-			Opcodes::FRETURN => Instructions::FRETURN,
-			Opcodes::ILOAD => Instructions::ILOAD(buffer.read_u8()?),
-			/* iload_0 */ 0x1A => Instructions::ILOAD(0),
-			/* iload_1 */ 0x1B => Instructions::ILOAD(1),
-			/* iload_2 */ 0x1C => Instructions::ILOAD(2),
-			/* iload_3 */ 0x1D => Instructions::ILOAD(3),
-
-			Opcodes::ISTORE => Instructions::ISTORE(buffer.read_u8()?),
-			/* istore_0 */ 0x3B => Instructions::ISTORE(0),
-			/* istore_1 */ 0x3C => Instructions::ISTORE(1),
-			/* istore_2 */ 0x3D => Instructions::ISTORE(2),
-			/* istore_3 */ 0x3E => Instructions::ISTORE(3),
-
-			Opcodes::IFEQ => Instructions::IFEQ(buffer.read_u16()?),
-			Opcodes::IFNE => Instructions::IFNE(buffer.read_u16()?),
-			Opcodes::IFLT => Instructions::IFLT(buffer.read_u16()?),
-			Opcodes::IFGE => Instructions::IFGE(buffer.read_u16()?),
-			Opcodes::IFGT => Instructions::IFGT(buffer.read_u16()?),
-			Opcodes::IFLE => Instructions::IFLE(buffer.read_u16()?),
-			Opcodes::IF_ICMPEQ => Instructions::IF_ICMPEQ(buffer.read_u16()?),
-			Opcodes::IF_ICMPNE => Instructions::IF_ICMPNE(buffer.read_u16()?),
-			Opcodes::IF_ICMPLT => Instructions::IF_ICMPLT(buffer.read_u16()?),
-			Opcodes::IF_ICMPGE => Instructions::IF_ICMPGE(buffer.read_u16()?),
-			Opcodes::IF_ICMPGT => Instructions::IF_ICMPGT(buffer.read_u16()?),
-			Opcodes::IF_ICMPLE => Instructions::IF_ICMPLE(buffer.read_u16()?),
-			Opcodes::IF_ACMPEQ => Instructions::IF_ACMPEQ(buffer.read_u16()?),
-			Opcodes::IF_ACMPNE => Instructions::IF_ACMPNE(buffer.read_u16()?),
-			Opcodes::IFNULL => Instructions::IFNULL(buffer.read_u16()?),
-			Opcodes::IFNONNULL => Instructions::IFNONNULL(buffer.read_u16()?),
-			Opcodes::GOTO => Instructions::GOTO(buffer.read_u16()?),
-
 			Opcodes::LCONST_0 => Instructions::LCONST_0,
 			Opcodes::LCONST_1 => Instructions::LCONST_1,
+			Opcodes::FCONST_0 => Instructions::FCONST_0,
+			Opcodes::FCONST_1 => Instructions::FCONST_1,
+			Opcodes::FCONST_2 => Instructions::FCONST_2,
+			Opcodes::DCONST_0 => Instructions::DCONST_0,
+			Opcodes::DCONST_1 => Instructions::DCONST_1,
+			Opcodes::BIPUSH => Instructions::BIPUSH(buffer.read_i8()?),
+			Opcodes::SIPUSH => Instructions::SIPUSH(buffer.read_i16()?),
+			Opcodes::LDC => Instructions::LDC(cp_get(cp, buffer.read_u8()? as u16)?.clone()),
+			Opcodes::LDC_W | Opcodes::LDC2_W => Instructions::LDC(cp_get(cp, buffer.read_u16()?)?.clone()),
 
-			Opcodes::AASTORE => Instructions::AASTORE,
+			Opcodes::ILOAD => Instructions::ILOAD(buffer.read_u8()? as u16),
+			Opcodes::LLOAD => Instructions::LLOAD(buffer.read_u8()? as u16),
+			Opcodes::FLOAD => Instructions::FLOAD(buffer.read_u8()? as u16),
+			Opcodes::DLOAD => Instructions::DLOAD(buffer.read_u8()? as u16),
+			Opcodes::ALOAD => Instructions::ALOAD(buffer.read_u8()? as u16),
+			/* iload_<n> */ b @ 0x1A..=0x1D => Instructions::ILOAD((b - 0x1A) as u16),
+			/* lload_<n> */ b @ 0x1E..=0x21 => Instructions::LLOAD((b - 0x1E) as u16),
+			/* fload_<n> */ b @ 0x22..=0x25 => Instructions::FLOAD((b - 0x22) as u16),
+			/* dload_<n> */ b @ 0x26..=0x29 => Instructions::DLOAD((b - 0x26) as u16),
+			/* aload_<n> */ b @ 0x2A..=0x2D => Instructions::ALOAD((b - 0x2A) as u16),
 
-			Opcodes::FLOAD => Instructions::FLOAD(buffer.read_u8()?),
-			/* fload_0 */ 0x22 => Instructions::FLOAD(0),
-			/* fload_1 */ 0x23 => Instructions::FLOAD(1),
-			/* fload_2 */ 0x24 => Instructions::FLOAD(2),
-			/* fload_3 */ 0x25 => Instructions::FLOAD(3),
+			Opcodes::IALOAD => Instructions::IALOAD,
+			Opcodes::LALOAD => Instructions::LALOAD,
+			Opcodes::FALOAD => Instructions::FALOAD,
+			Opcodes::DALOAD => Instructions::DALOAD,
+			Opcodes::AALOAD => Instructions::AALOAD,
+			Opcodes::BALOAD => Instructions::BALOAD,
+			Opcodes::CALOAD => Instructions::CALOAD,
+			Opcodes::SALOAD => Instructions::SALOAD,
 
-			Opcodes::LLOAD => Instructions::LLOAD(buffer.read_u8()?),
-			/* lload_0 */ 0x1E => Instructions::LLOAD(0),
-			/* lload_1 */ 0x1F => Instructions::LLOAD(1),
-			/* lload_2 */ 0x20 => Instructions::LLOAD(2),
-			/* lload_3 */ 0x21 => Instructions::LLOAD(3),
+			Opcodes::ISTORE => Instructions::ISTORE(buffer.read_u8()? as u16),
+			Opcodes::LSTORE => Instructions::LSTORE(buffer.read_u8()? as u16),
+			Opcodes::FSTORE => Instructions::FSTORE(buffer.read_u8()? as u16),
+			Opcodes::DSTORE => Instructions::DSTORE(buffer.read_u8()? as u16),
+			Opcodes::ASTORE => Instructions::ASTORE(buffer.read_u8()? as u16),
+			/* istore_<n> */ b @ 0x3B..=0x3E => Instructions::ISTORE((b - 0x3B) as u16),
+			/* lstore_<n> */ b @ 0x3F..=0x42 => Instructions::LSTORE((b - 0x3F) as u16),
+			/* fstore_<n> */ b @ 0x43..=0x46 => Instructions::FSTORE((b - 0x43) as u16),
+			/* dstore_<n> */ b @ 0x47..=0x4A => Instructions::DSTORE((b - 0x47) as u16),
+			/* astore_<n> */ b @ 0x4B..=0x4E => Instructions::ASTORE((b - 0x4B) as u16),
 
-			Opcodes::LSTORE => Instructions::LSTORE(buffer.read_u8()?),
-			/* lstore_0 */ 0x3F => Instructions::LSTORE(0),
-			/* lstore_1 */ 0x40 => Instructions::LSTORE(1),
-			/* lstore_2 */ 0x41 => Instructions::LSTORE(2),
-			/* lstore_3 */ 0x42 => Instructions::LSTORE(3),
+			Opcodes::IASTORE => Instructions::IASTORE,
+			Opcodes::LASTORE => Instructions::LASTORE,
+			Opcodes::FASTORE => Instructions::FASTORE,
+			Opcodes::DASTORE => Instructions::DASTORE,
+			Opcodes::AASTORE => Instructions::AASTORE,
+			Opcodes::BASTORE => Instructions::BASTORE,
+			Opcodes::CASTORE => Instructions::CASTORE,
+			Opcodes::SASTORE => Instructions::SASTORE,
 
-			Opcodes::LADD => Instructions::LADD,
-			Opcodes::BIPUSH => Instructions::BIPUSH(buffer.read_u8()?),
-			Opcodes::SIPUSH => Instructions::SIPUSH(buffer.read_u16()?),
-			Opcodes::ATHROW => Instructions::ATHROW,
 			Opcodes::POP => Instructions::POP,
-			Opcodes::CHECKCAST => Instructions::CHECKCAST(CPClassRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::INSTANCEOF => Instructions::INSTANCEOF(CPClassRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::POP2 => Instructions::POP2,
+			Opcodes::DUP => Instructions::DUP,
+			Opcodes::DUP_X1 => Instructions::DUP_X1,
+			Opcodes::DUP_X2 => Instructions::DUP_X2,
+			Opcodes::DUP2 => Instructions::DUP2,
+			Opcodes::DUP2_X1 => Instructions::DUP2_X1,
+			Opcodes::DUP2_X2 => Instructions::DUP2_X2,
 			Opcodes::SWAP => Instructions::SWAP,
-			Opcodes::NOP => Instructions::NOP,
-			Opcodes::ANEWARRAY => Instructions::ANEWARRAY(CPClassRef::from_cp(cp, buffer.read_u16()?)),
+
+			Opcodes::IADD => Instructions::IADD,
+			Opcodes::LADD => Instructions::LADD,
+			Opcodes::FADD => Instructions::FADD,
+			Opcodes::DADD => Instructions::DADD,
+			Opcodes::ISUB => Instructions::ISUB,
+			Opcodes::LSUB => Instructions::LSUB,
+			Opcodes::FSUB => Instructions::FSUB,
+			Opcodes::DSUB => Instructions::DSUB,
+			Opcodes::IMUL => Instructions::IMUL,
+			Opcodes::LMUL => Instructions::LMUL,
+			Opcodes::FMUL => Instructions::FMUL,
+			Opcodes::DMUL => Instructions::DMUL,
+			Opcodes::IDIV => Instructions::IDIV,
+			Opcodes::LDIV => Instructions::LDIV,
+			Opcodes::FDIV => Instructions::FDIV,
+			Opcodes::DDIV => Instructions::DDIV,
+			Opcodes::IREM => Instructions::IREM,
+			Opcodes::LREM => Instructions::LREM,
+			Opcodes::FREM => Instructions::FREM,
+			Opcodes::DREM => Instructions::DREM,
+			Opcodes::INEG => Instructions::INEG,
+			Opcodes::LNEG => Instructions::LNEG,
+			Opcodes::FNEG => Instructions::FNEG,
+			Opcodes::DNEG => Instructions::DNEG,
+			Opcodes::ISHL => Instructions::ISHL,
+			Opcodes::LSHL => Instructions::LSHL,
+			Opcodes::ISHR => Instructions::ISHR,
+			Opcodes::LSHR => Instructions::LSHR,
+			Opcodes::IUSHR => Instructions::IUSHR,
+			Opcodes::LUSHR => Instructions::LUSHR,
 			Opcodes::IAND => Instructions::IAND,
-			Opcodes::ACONST_NULL => Instructions::ACONST_NULL,
-			Opcodes::ARRAYLENGTH => Instructions::ARRAYLENGTH,
-			Opcodes::IASTORE => Instructions::IASTORE,
-			Opcodes::IALOAD => Instructions::IALOAD,
-			Opcodes::AALOAD => Instructions::AALOAD,
+			Opcodes::LAND => Instructions::LAND,
+			Opcodes::IOR => Instructions::IOR,
+			Opcodes::LOR => Instructions::LOR,
+			Opcodes::IXOR => Instructions::IXOR,
+			Opcodes::LXOR => Instructions::LXOR,
 			Opcodes::IINC => Instructions::IINC {
-				index: buffer.read_u8()?,
-				r#const: buffer.read_u8()?,
+				index: buffer.read_u8()? as u16,
+				r#const: buffer.read_i8()? as i16,
 			},
-			Opcodes::NEWARRAY => Instructions::NEWARRAY(buffer.read_u8()?),
+
+			Opcodes::I2L => Instructions::I2L,
+			Opcodes::I2F => Instructions::I2F,
+			Opcodes::I2D => Instructions::I2D,
+			Opcodes::L2I => Instructions::L2I,
+			Opcodes::L2F => Instructions::L2F,
+			Opcodes::L2D => Instructions::L2D,
+			Opcodes::F2I => Instructions::F2I,
+			Opcodes::F2L => Instructions::F2L,
+			Opcodes::F2D => Instructions::F2D,
+			Opcodes::D2I => Instructions::D2I,
+			Opcodes::D2L => Instructions::D2L,
+			Opcodes::D2F => Instructions::D2F,
+			Opcodes::I2B => Instructions::I2B,
+			Opcodes::I2C => Instructions::I2C,
+			Opcodes::I2S => Instructions::I2S,
+			Opcodes::LCMP => Instructions::LCMP,
+			Opcodes::FCMPL => Instructions::FCMPL,
+			Opcodes::FCMPG => Instructions::FCMPG,
+			Opcodes::DCMPL => Instructions::DCMPL,
+			Opcodes::DCMPG => Instructions::DCMPG,
+
+			Opcodes::IFEQ => Instructions::IFEQ(buffer.read_i16()?),
+			Opcodes::IFNE => Instructions::IFNE(buffer.read_i16()?),
+			Opcodes::IFLT => Instructions::IFLT(buffer.read_i16()?),
+			Opcodes::IFGE => Instructions::IFGE(buffer.read_i16()?),
+			Opcodes::IFGT => Instructions::IFGT(buffer.read_i16()?),
+			Opcodes::IFLE => Instructions::IFLE(buffer.read_i16()?),
+			Opcodes::IF_ICMPEQ => Instructions::IF_ICMPEQ(buffer.read_i16()?),
+			Opcodes::IF_ICMPNE => Instructions::IF_ICMPNE(buffer.read_i16()?),
+			Opcodes::IF_ICMPLT => Instructions::IF_ICMPLT(buffer.read_i16()?),
+			Opcodes::IF_ICMPGE => Instructions::IF_ICMPGE(buffer.read_i16()?),
+			Opcodes::IF_ICMPGT => Instructions::IF_ICMPGT(buffer.read_i16()?),
+			Opcodes::IF_ICMPLE => Instructions::IF_ICMPLE(buffer.read_i16()?),
+			Opcodes::IF_ACMPEQ => Instructions::IF_ACMPEQ(buffer.read_i16()?),
+			Opcodes::IF_ACMPNE => Instructions::IF_ACMPNE(buffer.read_i16()?),
+			Opcodes::GOTO => Instructions::GOTO(buffer.read_i16()?),
+			Opcodes::JSR => Instructions::JSR(buffer.read_i16()?),
+			Opcodes::RET => Instructions::RET(buffer.read_u8()? as u16),
 			Opcodes::TABLESWITCH => {
-				todo!("https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5.tableswitch")
+				Self::skip_switch_padding(buffer)?;
+				let default = buffer.read_i32()?;
+				let low = buffer.read_i32()?;
+				let high = buffer.read_i32()?;
+				if high < low {
+					return Err(IRClassfileError::InvalidTableSwitch { low, high });
+				}
+
+				let count = (high as i64 - low as i64 + 1) as usize;
+				let mut offsets = Vec::with_capacity(capacity(buffer, count)?);
+				for _ in 0..count {
+					offsets.push(buffer.read_i32()?);
+				}
+
+				Instructions::TABLESWITCH {
+					default,
+					low,
+					high,
+					offsets,
+				}
+			}
+			Opcodes::LOOKUPSWITCH => {
+				Self::skip_switch_padding(buffer)?;
+				let default = buffer.read_i32()?;
+				let n_pairs = buffer.read_i32()?.max(0) as usize;
+				let mut pairs = Vec::with_capacity(capacity(buffer, n_pairs)?);
+				for _ in 0..n_pairs {
+					pairs.push((buffer.read_i32()?, buffer.read_i32()?));
+				}
+
+				Instructions::LOOKUPSWITCH { default, pairs }
 			}
 
-			b => todo!("unparsed opcode 0x{b:02X}"),
+			Opcodes::IRETURN => Instructions::IRETURN,
+			Opcodes::LRETURN => Instructions::LRETURN,
+			Opcodes::FRETURN => Instructions::FRETURN,
+			Opcodes::DRETURN => Instructions::DRETURN,
+			Opcodes::ARETURN => Instructions::ARETURN,
+			Opcodes::RETURN => Instructions::RETURN,
+
+			Opcodes::GETSTATIC => Instructions::GETSTATIC(CPFieldRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::PUTSTATIC => Instructions::PUTSTATIC(CPFieldRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::GETFIELD => Instructions::GETFIELD(CPFieldRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::PUTFIELD => Instructions::PUTFIELD(CPFieldRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::INVOKEVIRTUAL => Instructions::INVOKEVIRTUAL(CPMethodRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::INVOKESPECIAL => Instructions::INVOKESPECIAL(CPMethodRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::INVOKESTATIC => Instructions::INVOKESTATIC(CPMethodRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::INVOKEINTERFACE => {
+				let s = Instructions::INVOKEINTERFACE {
+					method: CPInterfaceMethodRef::from_cp(cp, buffer.read_u16()?)?,
+					count: buffer.read_u8()?,
+				};
+				buffer.read_u8()?;
+				s
+			}
+			Opcodes::INVOKEDYNAMIC => {
+				let s = Instructions::INVOKEDYNAMIC(CPInvokeDynamicRef::from_cp(cp, buffer.read_u16()?)?);
+				buffer.read_u16()?;
+				s
+			}
+
+			Opcodes::NEW => Instructions::NEW(CPClassRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::NEWARRAY => Instructions::NEWARRAY(buffer.read_u8()?),
+			Opcodes::ANEWARRAY => Instructions::ANEWARRAY(CPClassRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::ARRAYLENGTH => Instructions::ARRAYLENGTH,
+			Opcodes::ATHROW => Instructions::ATHROW,
+			Opcodes::CHECKCAST => Instructions::CHECKCAST(CPClassRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::INSTANCEOF => Instructions::INSTANCEOF(CPClassRef::from_cp(cp, buffer.read_u16()?)?),
+			Opcodes::MONITORENTER => Instructions::MONITORENTER,
+			Opcodes::MONITOREXIT => Instructions::MONITOREXIT,
+			// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5.wide
+			Opcodes::WIDE => {
+				let opcode = buffer.read_u8()?;
+				let index = buffer.read_u16()?;
+				match opcode {
+					Opcodes::ILOAD => Instructions::ILOAD(index),
+					Opcodes::LLOAD => Instructions::LLOAD(index),
+					Opcodes::FLOAD => Instructions::FLOAD(index),
+					Opcodes::DLOAD => Instructions::DLOAD(index),
+					Opcodes::ALOAD => Instructions::ALOAD(index),
+					Opcodes::ISTORE => Instructions::ISTORE(index),
+					Opcodes::LSTORE => Instructions::LSTORE(index),
+					Opcodes::FSTORE => Instructions::FSTORE(index),
+					Opcodes::DSTORE => Instructions::DSTORE(index),
+					Opcodes::ASTORE => Instructions::ASTORE(index),
+					Opcodes::RET => Instructions::RET(index),
+					Opcodes::IINC => Instructions::IINC {
+						index,
+						r#const: buffer.read_i16()?,
+					},
+					_ => {
+						return Err(IRClassfileError::InvalidTag {
+							kind: "wide opcode",
+							value: opcode,
+						})
+					}
+				}
+			}
+			Opcodes::MULTIANEWARRAY => Instructions::MULTIANEWARRAY {
+				class: CPClassRef::from_cp(cp, buffer.read_u16()?)?,
+				dimensions: buffer.read_u8()?,
+			},
+			Opcodes::IFNULL => Instructions::IFNULL(buffer.read_i16()?),
+			Opcodes::IFNONNULL => Instructions::IFNONNULL(buffer.read_i16()?),
+			Opcodes::GOTO_W => Instructions::GOTO_W(buffer.read_i32()?),
+			Opcodes::JSR_W => Instructions::JSR_W(buffer.read_i32()?),
+
+			b => {
+				return Err(IRClassfileError::InvalidTag {
+					kind: "opcode",
+					value: b,
+				})
+			}
 		})
 	}
+
+	// The switch operands start at the next multiple of 4 from the start of the code array.
+	fn skip_switch_padding<B: BytesReadExt>(buffer: &mut B) -> Result<(), IRClassfileError> {
+		let pos = buffer.stream_position().map_err(BytesError::from)?;
+		for _ in 0..(4 - pos % 4) % 4 {
+			buffer.read_u8()?;
+		}
+		Ok(())
+	}
+
+	/// Decodes a whole code array into its instructions, paired with their pc.
+	pub fn read_all(cp: &[IRCpTag], code: &[u8]) -> Result<Vec<(u32, Instructions)>, IRClassfileError> {
+		let mut buffer = Cursor::new(code);
+		let mut out = Vec::new();
+		while (buffer.position() as usize) < code.len() {
+			let pc = buffer.position() as u32;
+			out.push((pc, Self::read(cp, &mut buffer)?));
+		}
+		Ok(out)
+	}
+
+	pub fn opcode(&self) -> u8 {
+		// SAFETY: `Self` is `repr(u8)`, so its first byte is the discriminant.
+		// https://doc.rust-lang.org/reference/items/enumerations.html#pointer-casting
+		unsafe { *(self as *const Self as *const u8) }
+	}
+
+	/// Offsets of every branch this instruction can take, relative to its own pc.
+	/// Doesn't include falling through to the next instruction.
+	pub fn branch_offsets(&self) -> Vec<i32> {
+		match self {
+			Self::IFEQ(offset)
+			| Self::IFNE(offset)
+			| Self::IFLT(offset)
+			| Self::IFGE(offset)
+			| Self::IFGT(offset)
+			| Self::IFLE(offset)
+			| Self::IF_ICMPEQ(offset)
+			| Self::IF_ICMPNE(offset)
+			| Self::IF_ICMPLT(offset)
+			| Self::IF_ICMPGE(offset)
+			| Self::IF_ICMPGT(offset)
+			| Self::IF_ICMPLE(offset)
+			| Self::IF_ACMPEQ(offset)
+			| Self::IF_ACMPNE(offset)
+			| Self::IFNULL(offset)
+			| Self::IFNONNULL(offset)
+			| Self::GOTO(offset)
+			| Self::JSR(offset) => vec![*offset as i32],
+			Self::GOTO_W(offset) | Self::JSR_W(offset) => vec![*offset],
+			Self::TABLESWITCH { default, offsets, .. } => {
+				std::iter::once(*default).chain(offsets.iter().copied()).collect()
+			}
+			Self::LOOKUPSWITCH { default, pairs } => std::iter::once(*default)
+				.chain(pairs.iter().map(|(_, offset)| *offset))
+				.collect(),
+			_ => Vec::new(),
+		}
+	}
+
+	/// Absolute pcs of the branch targets, see `branch_offsets`.
+	pub fn branch_targets(&self, pc: u32) -> Vec<u32> {
+		self.branch_offsets()
+			.into_iter()
+			.map(|offset| (pc as i64 + offset as i64) as u32)
+			.collect()
+	}
+
+	/// Whether execution can continue with the next instruction in the code array.
+	pub fn falls_through(&self) -> bool {
+		!matches!(
+			self,
+			Self::GOTO(_)
+				| Self::GOTO_W(_)
+				| Self::TABLESWITCH { .. }
+				| Self::LOOKUPSWITCH { .. }
+				| Self::IRETURN
+				| Self::LRETURN
+				| Self::FRETURN
+				| Self::DRETURN
+				| Self::ARETURN
+				| Self::RETURN
+				| Self::ATHROW
+				| Self::RET(_)
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use maya_classfile_io::class_pool::IOCpTag;
+
+	use super::*;
+
+	// A constant at 1, a class at 3, and at 7 to 10 a field, method, interface method and invokedynamic.
+	fn pool() -> Vec<IRCpTag> {
+		let utf8 = |value: &str| IOCpTag::Utf8 {
+			length: value.len() as u16,
+			bytes: value.as_bytes().to_vec(),
+		};
+		let member = (3, 6);
+		IRCpTag::from_io(vec![
+			IOCpTag::Integer { bytes: [0, 0, 0, 5] },
+			utf8("p/C"),
+			IOCpTag::Class { name_index: 2 },
+			utf8("f"),
+			utf8("I"),
+			IOCpTag::NameAndType {
+				name_index: 4,
+				descriptor_index: 5,
+			},
+			IOCpTag::FieldRef {
+				class_index: member.0,
+				name_and_ty_index: member.1,
+			},
+			IOCpTag::MethodRef {
+				class_index: member.0,
+				name_and_ty_index: member.1,
+			},
+			IOCpTag::InterfaceMethodRef {
+				class_index: member.0,
+				name_and_ty_index: member.1,
+			},
+			IOCpTag::InvokeDynamic {
+				bootstrap_method_attr_index: 0,
+				name_and_ty_index: 6,
+			},
+		])
+		.unwrap()
+	}
+
+	fn read(code: &[u8]) -> Result<Vec<(u32, Instructions)>, IRClassfileError> {
+		Instructions::read_all(&pool(), code)
+	}
+
+	#[test]
+	fn wide() {
+		let code = [
+			Opcodes::WIDE,
+			Opcodes::IINC,
+			1,
+			0,
+			0xFF,
+			0xFE,
+			Opcodes::WIDE,
+			Opcodes::ILOAD,
+			0,
+			4,
+		];
+		let insns = read(&code).unwrap();
+		assert!(matches!(
+			insns[0],
+			(
+				0,
+				Instructions::IINC {
+					index: 256,
+					r#const: -2
+				}
+			)
+		));
+		assert!(matches!(insns[1], (6, Instructions::ILOAD(4))));
+		assert_eq!(insns.len(), 2);
+
+		assert!(matches!(
+			read(&[Opcodes::WIDE, Opcodes::NOP, 0, 0]),
+			Err(IRClassfileError::InvalidTag {
+				kind: "wide opcode",
+				..
+			})
+		));
+	}
+
+	#[test]
+	fn switch_padding() {
+		let words = |words: &[i32]| words.iter().flat_map(|word| word.to_be_bytes()).collect::<Vec<_>>();
+		// default, low, high, offsets
+		let table = words(&[9, 1, 2, 3, 4]);
+		// default, count, key, offset
+		let lookup = words(&[9, 1, 7, 11]);
+
+		for nops in 0..4 {
+			let padding = (3 - nops) % 4;
+			let mut code = vec![Opcodes::NOP; nops];
+			code.push(Opcodes::TABLESWITCH);
+			code.extend(vec![0; padding]);
+			code.extend(&table);
+			code.push(Opcodes::LOOKUPSWITCH);
+			let pc = code.len() - 1;
+			code.extend(vec![0; (3 - pc % 4) % 4]);
+			code.extend(&lookup);
+
+			let insns = read(&code).unwrap();
+			assert_eq!(insns.len(), nops + 2);
+			match &insns[nops] {
+				(
+					pc,
+					Instructions::TABLESWITCH {
+						default: 9,
+						low: 1,
+						high: 2,
+						offsets,
+					},
+				) if *pc == nops as u32 => assert_eq!(offsets, &[3, 4]),
+				other => panic!("{other:?}"),
+			}
+			match &insns[nops + 1] {
+				(at, Instructions::LOOKUPSWITCH { default: 9, pairs }) if *at == pc as u32 => {
+					assert_eq!(pairs, &[(7, 11)])
+				}
+				other => panic!("{other:?}"),
+			}
+		}
+	}
+
+	#[test]
+	fn truncated() {
+		let truncated: [&[u8]; 6] = [
+			&[Opcodes::SIPUSH, 1],
+			&[Opcodes::GOTO_W, 0, 0, 0],
+			&[Opcodes::WIDE, Opcodes::IINC, 0, 1, 0],
+			&[Opcodes::TABLESWITCH, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+			&[Opcodes::LOOKUPSWITCH, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1],
+			&[Opcodes::INVOKEINTERFACE, 0, 9, 1],
+		];
+		for code in truncated {
+			assert!(
+				matches!(read(code), Err(IRClassfileError::Bytes(_))),
+				"{code:?} read as {:?}",
+				read(code)
+			);
+		}
+	}
+
+	// Whatever a byte decodes to, `opcode()` gives back the byte, or the instruction its short form is folded into.
+	#[test]
+	fn opcode_matches_table() {
+		const LOADS: [u8; 5] = [
+			Opcodes::ILOAD,
+			Opcodes::LLOAD,
+			Opcodes::FLOAD,
+			Opcodes::DLOAD,
+			Opcodes::ALOAD,
+		];
+		const STORES: [u8; 5] = [
+			Opcodes::ISTORE,
+			Opcodes::LSTORE,
+			Opcodes::FSTORE,
+			Opcodes::DSTORE,
+			Opcodes::ASTORE,
+		];
+		let mut operands = vec![vec![1; 24], vec![0; 24], vec![Opcodes::IINC, 0, 1, 0, 1]];
+		for index in [1, 3, 7, 8, 9, 10] {
+			let mut operand = vec![0, index];
+			operand.extend([1; 22]);
+			operands.push(operand);
+		}
+
+		for byte in 0..=u8::MAX {
+			let decoded = operands.iter().find_map(|operand| {
+				let mut code = vec![byte];
+				code.extend(operand);
+				Instructions::read(&pool(), &mut Cursor::new(&code)).ok()
+			});
+			let Some(mnemonic) = Opcodes::mnemonic(byte) else {
+				assert!(decoded.is_none(), "{byte} has no mnemonic but decodes to {decoded:?}");
+				continue;
+			};
+			let insn = decoded.unwrap_or_else(|| panic!("{mnemonic} doesn't decode"));
+			let expected = match byte {
+				0x1A..=0x2D => LOADS[(byte - 0x1A) as usize / 4],
+				0x3B..=0x4E => STORES[(byte - 0x3B) as usize / 4],
+				Opcodes::LDC_W | Opcodes::LDC2_W => Opcodes::LDC,
+				Opcodes::WIDE => Opcodes::IINC,
+				_ => byte,
+			};
+			assert_eq!(insn.opcode(), expected, "{mnemonic} decodes to {insn:?}");
+		}
+	}
 }
@@ -0,0 +1,512 @@
+// Dropping the constant pool entries nothing uses anymore. Transforms add the entries they need and leave behind the
+// ones they stop using: remapping leaves the old names, stripping debug info the local variable names and attribute
+// names, inlining the references of calls that are gone. This pass marks the entries the class refers to, from its
+// members, their attributes and code and the entries those refer to in turn, drops the rest and renumbers every
+// reference. The entries left keep their order, so indices only get smaller: an `ldc` stays an `ldc` and the code
+// keeps its layout.
+//
+// Attributes this crate doesn't parse may refer to the pool in ways that can't be seen, so a class with any of them
+// keeps its pool whole. Like remapping, the class is parsed again afterwards, so everything in the IR reads the new
+// indices.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.4
+
+use crate::{
+	attribute::{
+		Attributes, ConstantValueAttribute, IRAttribute, RuntimeAnnotation, RuntimeAnnotationValue, StackMapFrame,
+		VerificationTypeInfo,
+	},
+	class_pool::{IRClassfileError, IRCpTag},
+	code::{Instructions, Opcodes},
+	transform::{ChangeLog, ChangeRecord, Transform},
+	IRClassFile,
+};
+
+impl IRClassFile {
+	/// Removes the constant pool entries nothing refers to and renumbers the references to the rest. Returns how many
+	/// entries were removed.
+	pub fn compact_pool(&mut self) -> Result<usize, IRClassfileError> {
+		let Some(mut used) = self.pool_uses()? else {
+			return Ok(0);
+		};
+		used.sort();
+		let entries = self.cp.iter().filter(|tag| !matches!(tag, IRCpTag::Unusable)).count();
+		if used.len() == entries {
+			return Ok(0);
+		}
+		self.reorder_pool(&used)?;
+		Ok(entries - used.len())
+	}
+
+	/// The entries the class refers to, in the order they're first reached, each followed by the ones it refers to.
+	/// `None` if the class has attributes whose references can't be seen.
+	pub(crate) fn pool_uses(&mut self) -> Result<Option<Vec<u16>>, IRClassfileError> {
+		if has_unknown(&self.attributes)
+			|| self.fields.iter().any(|field| has_unknown(&field.attributes))
+			|| self.methods.iter().any(|method| has_unknown(&method.attributes))
+		{
+			return Ok(None);
+		}
+
+		let mut roots = Vec::new();
+		class_indices(self, &mut |index| roots.push(*index))?;
+		let mut used = vec![false; self.cp.len() + 1];
+		let mut order = Vec::new();
+		for root in roots {
+			let mut pending = vec![root];
+			while let Some(index) = pending.pop() {
+				match used.get_mut(index as usize) {
+					Some(used) if index != 0 && !*used => *used = true,
+					_ => continue,
+				}
+				order.push(index);
+				let mut nested = Vec::new();
+				if let Some(tag) = self.cp.get_mut(index as usize - 1) {
+					tag_indices(tag, &mut |index| nested.push(*index));
+				}
+				pending.extend(nested.into_iter().rev());
+			}
+		}
+		Ok(Some(order))
+	}
+
+	/// Rebuilds the pool from the entries at `order`, in that order, and renumbers every reference. Entries left out
+	/// must be unused, and ones an `ldc` loads must stay below 256.
+	pub(crate) fn reorder_pool(&mut self, order: &[u16]) -> Result<(), IRClassfileError> {
+		let mut renumbered = vec![0; self.cp.len() + 1];
+		let mut cp = Vec::with_capacity(order.len());
+		for index in order {
+			let tag = &self.cp[*index as usize - 1];
+			cp.push(tag.clone());
+			renumbered[*index as usize] = cp.len() as u16;
+			// The slot after a Long or Double goes with it.
+			if matches!(tag, IRCpTag::Long(_) | IRCpTag::Double(_)) {
+				cp.push(IRCpTag::Unusable);
+			}
+		}
+
+		let mut renumber = |index: &mut u16| {
+			if let Some(&new) = renumbered.get(*index as usize) {
+				*index = new;
+			}
+		};
+		class_indices(self, &mut renumber)?;
+		for tag in &mut cp {
+			tag_indices(tag, &mut renumber);
+		}
+		self.cp = cp;
+		*self = IRClassFile::read(&self.to_bytes()?)?;
+		Ok(())
+	}
+}
+
+/// Compacts the constant pool of every class, best run last.
+pub struct PoolCompactor;
+
+impl Transform for PoolCompactor {
+	fn name(&self) -> &str {
+		"compact_pool"
+	}
+
+	fn apply(&mut self, class: &mut IRClassFile, log: &mut ChangeLog) -> Result<(), IRClassfileError> {
+		let removed = class.compact_pool()?;
+		if removed != 0 {
+			log.record(ChangeRecord::Custom {
+				kind: "compacted_pool".to_string(),
+				detail: format!("removed {removed} constant pool entries"),
+			});
+		}
+		Ok(())
+	}
+}
+
+fn has_unknown(attributes: &Attributes) -> bool {
+	attributes.iter().any(|info| match &info.attr {
+		IRAttribute::Unknown(_) => true,
+		IRAttribute::Code(code) => has_unknown(&code.attributes),
+		IRAttribute::Record { components } => components.iter().any(|component| has_unknown(&component.attributes)),
+		_ => false,
+	})
+}
+
+// Every pool index the class is written with, outside the pool itself. Absent ones, written as 0, are left out.
+fn class_indices(class: &mut IRClassFile, visit: &mut impl FnMut(&mut u16)) -> Result<(), IRClassfileError> {
+	let IRClassFile {
+		cp,
+		this_class,
+		super_class,
+		interfaces,
+		fields,
+		methods,
+		attributes: class_attributes,
+		..
+	} = class;
+
+	visit(&mut this_class.index);
+	for class in super_class.iter_mut().chain(interfaces) {
+		visit(&mut class.index);
+	}
+	for field in fields {
+		visit(&mut field.name.index);
+		visit(&mut field.descriptor.index);
+		attributes(cp, &mut field.attributes, visit)?;
+	}
+	for method in methods {
+		visit(&mut method.name.index);
+		visit(&mut method.descriptor.index);
+		attributes(cp, &mut method.attributes, visit)?;
+	}
+	attributes(cp, class_attributes, visit)
+}
+
+fn attributes(
+	cp: &[IRCpTag],
+	attributes: &mut Attributes,
+	visit: &mut impl FnMut(&mut u16),
+) -> Result<(), IRClassfileError> {
+	for info in attributes.iter_mut() {
+		visit(&mut info.name.index);
+		match &mut info.attr {
+			IRAttribute::ConstantValue(
+				ConstantValueAttribute::Long { cp_idx, .. }
+				| ConstantValueAttribute::Float { cp_idx, .. }
+				| ConstantValueAttribute::Double { cp_idx, .. }
+				| ConstantValueAttribute::Int { cp_idx, .. }
+				| ConstantValueAttribute::String { cp_idx, .. },
+			) => visit(cp_idx),
+			IRAttribute::Code(code) => {
+				code_indices(cp, &mut code.code, visit)?;
+				for entry in &mut code.exception_table {
+					if entry.catch_type != 0 {
+						visit(&mut entry.catch_type);
+					}
+				}
+				self::attributes(cp, &mut code.attributes, visit)?;
+			}
+			IRAttribute::StackMapTable(table) => {
+				for frame in &mut table.entries {
+					for info in frame_types(frame) {
+						if let VerificationTypeInfo::ObjectVariableInfo { cpool_idx } = info {
+							visit(cpool_idx);
+						}
+					}
+				}
+			}
+			IRAttribute::Exceptions {
+				exception_index_table: classes,
+			}
+			| IRAttribute::NestMembers { classes }
+			| IRAttribute::PermittedSubclasses { classes } => {
+				for class in classes {
+					visit(&mut class.index);
+				}
+			}
+			IRAttribute::InnerClasses(inner) => {
+				for class in &mut inner.classes {
+					visit(&mut class.inner_class_info.index);
+					if let Some(outer) = &mut class.outer_class_info {
+						visit(&mut outer.index);
+					}
+					if let Some(name) = &mut class.inner_name {
+						visit(&mut name.index);
+					}
+				}
+			}
+			IRAttribute::EnclosingMethod { class, method } => {
+				visit(&mut class.index);
+				if let Some(method) = method {
+					visit(&mut method.index);
+				}
+			}
+			IRAttribute::Signature(utf8)
+			| IRAttribute::SourceFile(utf8)
+			| IRAttribute::CompilationID(utf8)
+			| IRAttribute::SourceID(utf8) => visit(&mut utf8.index),
+			IRAttribute::LocalVariableTable { table } => {
+				for entry in table {
+					visit(&mut entry.name.index);
+					visit(&mut entry.descriptor.index);
+				}
+			}
+			IRAttribute::LocalVariableTypeTable { table } => {
+				for entry in table {
+					visit(&mut entry.name.index);
+					visit(&mut entry.signature.index);
+				}
+			}
+			IRAttribute::RuntimeVisibleAnnotations { annotations }
+			| IRAttribute::RuntimeInvisibleAnnotations { annotations } => {
+				for annotation in annotations {
+					annotation_indices(annotation, visit);
+				}
+			}
+			IRAttribute::RuntimeVisibleParameterAnnotations { params }
+			| IRAttribute::RuntimeInvisibleParameterAnnotations { params } => {
+				for annotation in params.iter_mut().flatten() {
+					annotation_indices(annotation, visit);
+				}
+			}
+			IRAttribute::AnnotationDefault { default_value } => value_indices(default_value, visit),
+			IRAttribute::RuntimeVisibleTypeAnnotations { annotations }
+			| IRAttribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+				for annotation in annotations {
+					visit(&mut annotation.type_index);
+					for pair in &mut annotation.pairs {
+						visit(&mut pair.name.index);
+						value_indices(&mut pair.value, visit);
+					}
+				}
+			}
+			IRAttribute::BootstrapMethods { methods } => {
+				for method in methods {
+					visit(&mut method.method.index);
+					for argument in &mut method.arguments {
+						visit(&mut argument.index);
+					}
+				}
+			}
+			IRAttribute::NestHost(class) | IRAttribute::ModuleMainClass { class } => visit(&mut class.index),
+			IRAttribute::MethodParameters { parameters } => {
+				for name in parameters.iter_mut().filter_map(|param| param.name.as_mut()) {
+					visit(&mut name.index);
+				}
+			}
+			IRAttribute::Record { components } => {
+				for component in components {
+					visit(&mut component.name.index);
+					visit(&mut component.descriptor.index);
+					self::attributes(cp, &mut component.attributes, visit)?;
+				}
+			}
+			IRAttribute::Module {
+				module_name,
+				module_version,
+				requires,
+				exports,
+				opens,
+				uses,
+				provides,
+				..
+			} => {
+				visit(&mut module_name.index);
+				if let Some(version) = module_version {
+					visit(&mut version.index);
+				}
+				for entry in requires {
+					visit(&mut entry.module.index);
+					if let Some(version) = &mut entry.version {
+						visit(&mut version.index);
+					}
+				}
+				for entry in exports {
+					visit(&mut entry.package.index);
+					for module in &mut entry.exports {
+						visit(&mut module.index);
+					}
+				}
+				for entry in opens {
+					visit(&mut entry.package.index);
+					for module in &mut entry.opens {
+						visit(&mut module.index);
+					}
+				}
+				for class in uses {
+					visit(&mut class.index);
+				}
+				for entry in provides {
+					visit(&mut entry.class.index);
+					for class in &mut entry.provides {
+						visit(&mut class.index);
+					}
+				}
+			}
+			IRAttribute::ModulePackages { packages } => {
+				for package in packages {
+					visit(&mut package.index);
+				}
+			}
+			IRAttribute::Synthetic
+			| IRAttribute::Deprecated
+			| IRAttribute::SourceDebugExtension(_)
+			| IRAttribute::LineNumberTable(_)
+			| IRAttribute::CharacterRangeTable { .. }
+			| IRAttribute::Unknown(_) => {}
+		}
+	}
+	Ok(())
+}
+
+fn frame_types(frame: &mut StackMapFrame) -> Vec<&mut VerificationTypeInfo> {
+	use StackMapFrame as F;
+
+	match frame {
+		F::SameLocals1StackItemFrame { stack, .. } | F::SameLocals1StackItemFrameExtended { stack, .. } => {
+			vec![stack]
+		}
+		F::AppendFrame { locals, .. } => locals.iter_mut().collect(),
+		F::FullFrame { locals, stack, .. } => locals.iter_mut().chain(stack).collect(),
+		F::SameFrame { .. } | F::ChopFrame { .. } | F::SameFrameExtended { .. } => Vec::new(),
+	}
+}
+
+fn annotation_indices(annotation: &mut RuntimeAnnotation, visit: &mut impl FnMut(&mut u16)) {
+	visit(&mut annotation.ty.index);
+	for pair in &mut annotation.pairs {
+		visit(&mut pair.name.index);
+		value_indices(&mut pair.value, visit);
+	}
+}
+
+fn value_indices(value: &mut RuntimeAnnotationValue, visit: &mut impl FnMut(&mut u16)) {
+	match value {
+		RuntimeAnnotationValue::ConstValueIndex { value, .. } => visit(&mut value.index),
+		RuntimeAnnotationValue::EnumConstValue { type_name, const_name } => {
+			visit(&mut type_name.index);
+			visit(&mut const_name.index);
+		}
+		RuntimeAnnotationValue::ClassInfoIndex(class) => visit(&mut class.index),
+		RuntimeAnnotationValue::Annotation(annotation) => annotation_indices(annotation, visit),
+		RuntimeAnnotationValue::ArrayValue { values } => {
+			for value in values {
+				value_indices(value, visit);
+			}
+		}
+	}
+}
+
+// The pool indices in the code, rewritten in place. Only `ldc` has a one byte index, the entries it loads have to be
+// renumbered below 256.
+fn code_indices(cp: &[IRCpTag], code: &mut [u8], visit: &mut impl FnMut(&mut u16)) -> Result<(), IRClassfileError> {
+	for (pc, _) in Instructions::read_all(cp, code)? {
+		let at = pc as usize + 1;
+		match code[pc as usize] {
+			Opcodes::LDC => {
+				let mut index = u16::from(code[at]);
+				visit(&mut index);
+				code[at] = index as u8;
+			}
+			Opcodes::LDC_W
+			| Opcodes::LDC2_W
+			| Opcodes::GETSTATIC..=Opcodes::INVOKEDYNAMIC
+			| Opcodes::NEW
+			| Opcodes::ANEWARRAY
+			| Opcodes::CHECKCAST
+			| Opcodes::INSTANCEOF
+			| Opcodes::MULTIANEWARRAY => {
+				let mut index = u16::from_be_bytes([code[at], code[at + 1]]);
+				visit(&mut index);
+				code[at..at + 2].copy_from_slice(&index.to_be_bytes());
+			}
+			_ => {}
+		}
+	}
+	Ok(())
+}
+
+// The indices of the entries `tag` refers to, as it's written.
+fn tag_indices(tag: &mut IRCpTag, visit: &mut impl FnMut(&mut u16)) {
+	match tag {
+		IRCpTag::Class(utf8)
+		| IRCpTag::String(utf8)
+		| IRCpTag::MethodType(utf8)
+		| IRCpTag::Module { name: utf8 }
+		| IRCpTag::Package { name: utf8 } => visit(&mut utf8.index),
+		IRCpTag::FieldRef {
+			class_index,
+			name_and_ty,
+		}
+		| IRCpTag::MethodRef {
+			class_index,
+			name_and_ty,
+		}
+		| IRCpTag::InterfaceMethodRef {
+			class_index,
+			name_and_ty,
+		} => {
+			visit(class_index);
+			visit(&mut name_and_ty.index);
+		}
+		IRCpTag::NameAndType { name, descriptor } => {
+			visit(&mut name.index);
+			visit(&mut descriptor.index);
+		}
+		IRCpTag::MethodHandle { ref_index, .. } => visit(ref_index),
+		IRCpTag::InvokeDynamic { name_and_ty, .. } => visit(&mut name_and_ty.index),
+		IRCpTag::Unusable
+		| IRCpTag::Utf8(_)
+		| IRCpTag::Integer(_)
+		| IRCpTag::Float(_)
+		| IRCpTag::Long(_)
+		| IRCpTag::Double(_) => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		asm::assemble,
+		strip::{DebugStripper, StripOptions},
+		transform::Pipeline,
+	};
+
+	#[test]
+	fn drop_unused_entries() {
+		let mut class = assemble(
+			r#"
+.class public super p/Main
+.source "Main.java"
+.field static final LIMIT J = 7L
+.method public static run()J
+	.line 3
+	ldc "text"
+	pop
+	ldc2_w 5L
+	getstatic p/Main/LIMIT J
+	ladd
+	lreturn
+.end method
+.method public size(Ljava/util/List;)I
+Start:
+	aload_1
+	invokeinterface java/util/List/size()I
+	ireturn
+End:
+	.var 1 is items Ljava/util/List; from Start to End
+.end method
+"#,
+		)
+		.unwrap();
+		let code = |class: &IRClassFile| {
+			class
+				.methods
+				.iter()
+				.map(|method| {
+					let code = Instructions::read_all(&class.cp, &method.code().unwrap().code).unwrap();
+					code.into_iter()
+						.map(|(pc, instruction)| format!("{pc} {:?}", Opcodes::mnemonic(instruction.opcode())))
+						.collect::<Vec<_>>()
+				})
+				.collect::<Vec<_>>()
+		};
+		let (before, dependencies) = (code(&class), class.dependencies());
+		Pipeline::new()
+			.with(DebugStripper::new(StripOptions::default()))
+			.with(PoolCompactor)
+			.run(&mut class)
+			.unwrap();
+
+		let utf8 = |value: &str| {
+			class
+				.cp
+				.iter()
+				.any(|tag| matches!(tag, IRCpTag::Utf8(data) if **data == value))
+		};
+		assert!(!utf8("Main.java") && !utf8("items") && !utf8("LineNumberTable"));
+		assert!(utf8("text") && utf8("LIMIT"));
+		assert_eq!(code(&class), before);
+		assert_eq!(class.dependencies(), dependencies);
+		let run = Instructions::read_all(&class.cp, &class.methods[0].code().unwrap().code).unwrap();
+		assert!(matches!(run[2], (3, Instructions::LDC(IRCpTag::Long(5)))));
+		assert_eq!(class.compact_pool().unwrap(), 0);
+	}
+}
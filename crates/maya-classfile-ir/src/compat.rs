@@ -0,0 +1,213 @@
+// Checking a class against the version it's written as. Lowering the version of a class, or adding attributes to an
+// old one, easily leaves it with attributes the JVM ignores at that version, a Record nobody sees below 60, or
+// constant pool entries it rejects outright. Attributes can be dropped to make the class fit, constants can't since
+// the code refers to them.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.4-310
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7-310
+
+use std::fmt::{self, Display};
+
+use crate::{
+	attribute::{Attributes, IRAttribute},
+	class_pool::IRClassfileError,
+	ClassFileVersion, IRClassFile,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VersionPolicy {
+	/// Fail on anything the version doesn't allow.
+	#[default]
+	Reject,
+	/// Drop the attributes the version predates, still failing on constant pool entries.
+	DropAttributes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionViolation {
+	Attribute {
+		// what it's on, e.g. `method run()V`
+		location: String,
+		name: String,
+		since: ClassFileVersion,
+	},
+	ConstantPoolEntry {
+		index: u16,
+		kind: &'static str,
+		since: ClassFileVersion,
+	},
+}
+
+impl Display for VersionViolation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Attribute { location, name, since } => write!(f, "attribute {name} on {location}, since {since}"),
+			Self::ConstantPoolEntry { index, kind, since } => {
+				write!(f, "{kind} at constant pool index {index}, since {since}")
+			}
+		}
+	}
+}
+
+impl IRClassFile {
+	/// The attributes and constant pool entries the class's version doesn't allow. Attributes kept as raw data are
+	/// left out, the JVM doesn't interpret them either way.
+	pub fn version_violations(&self) -> Vec<VersionViolation> {
+		let mut violations = Vec::new();
+		for (i, tag) in self.cp.iter().enumerate() {
+			if let Some(since) = ClassFileVersion::tag_since(tag).filter(|since| self.version < *since) {
+				violations.push(VersionViolation::ConstantPoolEntry {
+					index: i as u16 + 1,
+					kind: tag.kind_name(),
+					since,
+				});
+			}
+		}
+		self.visit_attributes(|location, attributes| {
+			for attr in attributes.iter() {
+				if matches!(attr.attr, IRAttribute::Unknown(_)) {
+					continue;
+				}
+				if let Some(since) =
+					ClassFileVersion::attribute_since(&attr.name.data).filter(|since| self.version < *since)
+				{
+					violations.push(VersionViolation::Attribute {
+						location: location.to_string(),
+						name: attr.name.data.to_string(),
+						since,
+					});
+				}
+			}
+		});
+		violations
+	}
+
+	/// Writes the class once it fits its version, dropping attributes from a copy of it if `policy` allows.
+	pub fn to_bytes_for_version(&self, policy: VersionPolicy) -> Result<Vec<u8>, IRClassfileError> {
+		let violations = self.version_violations();
+		let rejected = violations.iter().find(|violation| {
+			policy == VersionPolicy::Reject || matches!(violation, VersionViolation::ConstantPoolEntry { .. })
+		});
+		if let Some(violation) = rejected {
+			return Err(IRClassfileError::UnsupportedByVersion {
+				version: self.version,
+				what: violation.to_string(),
+			});
+		}
+		if violations.is_empty() {
+			return self.to_bytes();
+		}
+		let mut class = self.clone();
+		class.drop_unsupported_attributes();
+		class.to_bytes()
+	}
+
+	/// Drops the attributes the class's version predates, returning how many.
+	pub fn drop_unsupported_attributes(&mut self) -> usize {
+		let version = self.version;
+		let supported = |attributes: &mut Attributes| retain_supported(version, attributes);
+		let mut dropped = supported(&mut self.attributes);
+		for field in &mut self.fields {
+			dropped += supported(&mut field.attributes);
+		}
+		for method in &mut self.methods {
+			dropped += supported(&mut method.attributes);
+		}
+		dropped
+	}
+
+	fn visit_attributes(&self, mut visit: impl FnMut(&str, &Attributes)) {
+		visit("class", &self.attributes);
+		for attr in self.attributes.iter() {
+			if let IRAttribute::Record { components } = &attr.attr {
+				for component in components {
+					visit(
+						&format!("record component {}", component.name.data),
+						&component.attributes,
+					);
+				}
+			}
+		}
+		for field in &self.fields {
+			visit(&format!("field {}", field.name()), &field.attributes);
+		}
+		for method in &self.methods {
+			let location = format!("method {}{}", method.name(), method.descriptor());
+			visit(&location, &method.attributes);
+			if let Some(code) = method.code() {
+				visit(&location, &code.attributes);
+			}
+		}
+	}
+}
+
+fn retain_supported(version: ClassFileVersion, attributes: &mut Attributes) -> usize {
+	let before = attributes.len();
+	attributes
+		.retain(|attr| matches!(attr.attr, IRAttribute::Unknown(_)) || version.supports_attribute(&attr.name.data));
+	let mut dropped = before - attributes.len();
+	for attr in attributes.iter_mut() {
+		match &mut attr.attr {
+			IRAttribute::Code(code) => dropped += retain_supported(version, &mut code.attributes),
+			IRAttribute::Record { components } => {
+				for component in components {
+					dropped += retain_supported(version, &mut component.attributes);
+				}
+			}
+			_ => {}
+		}
+	}
+	dropped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		builder::ClassBuilder,
+		class_pool::{CPClassRef, CPTagRef, CPUtf8Ref, IRCpTag},
+		flags::MethodAccessFlags,
+	};
+
+	#[test]
+	fn check_against_version() {
+		let mut builder = ClassBuilder::new("p/Outer").unwrap();
+		builder.version(ClassFileVersion::JAVA_8);
+		let classes = vec![CPClassRef::find_or_add(builder.cp(), "p/Outer$Inner").unwrap()];
+		let parameters = IRAttribute::MethodParameters { parameters: Vec::new() };
+		builder
+			.attribute(IRAttribute::NestMembers { classes })
+			.unwrap()
+			.method(MethodAccessFlags::PUBLIC, "run", "()V", [parameters])
+			.unwrap();
+		let class = builder.build();
+
+		assert_eq!(
+			class.version_violations(),
+			[VersionViolation::Attribute {
+				location: "class".to_string(),
+				name: "NestMembers".to_string(),
+				since: ClassFileVersion::JAVA_11,
+			}]
+		);
+		let err = class.to_bytes_for_version(VersionPolicy::Reject).unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"The class is version 52.0 (Java 8), but has attribute NestMembers on class, since 55.0 (Java 11)"
+		);
+		let bytes = class.to_bytes_for_version(VersionPolicy::DropAttributes).unwrap();
+		let written = IRClassFile::read(&bytes).unwrap();
+		assert!(written.attributes.is_empty());
+		assert_eq!(written.methods[0].attributes.len(), 1);
+
+		let mut old = ClassBuilder::new("p/Old").unwrap();
+		old.version(ClassFileVersion::JAVA_6);
+		let descriptor = CPUtf8Ref::find_or_add(old.cp(), "()V").unwrap();
+		CPTagRef::find_or_add(old.cp(), IRCpTag::MethodType(descriptor)).unwrap();
+		let old = old.build();
+		assert!(matches!(
+			&old.version_violations()[..],
+			[VersionViolation::ConstantPoolEntry { kind: "MethodType", .. }]
+		));
+		assert!(old.to_bytes_for_version(VersionPolicy::DropAttributes).is_err());
+	}
+}
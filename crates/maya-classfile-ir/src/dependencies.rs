@@ -0,0 +1,364 @@
+// Everything a class refers to outside itself: the classes it names anywhere, and the fields and methods of other
+// classes it uses. This is what a shrinker keeps alive, what a class path audit checks is there and what a build tool
+// recompiles against. Instructions reach classes and members through the constant pool, so the pool is walked instead
+// of the code, and entries nothing uses count too, as they do for javap and jdeps. Besides the pool, classes are
+// named by descriptors, generic signatures, annotations and debug info.
+//
+// Arrays are dependencies on their element class, primitive types aren't dependencies. References to the class's own
+// members are left out, but references to members it inherits through its own name are kept, since resolving them
+// takes a superclass.
+
+use std::collections::BTreeSet;
+
+use crate::{
+	attribute::{Attributes, IRAttribute, RuntimeAnnotation, RuntimeAnnotationValue},
+	class_pool::{cp_get, CPNameAndTypeRef, CPUtf8Ref, IRCpTag},
+	names,
+	query::MemberRef,
+	signature::{
+		ClassSignature, ClassTypeSignature, JavaTypeSignature, MethodSignature, ReferenceTypeSignature, TypeArgument,
+		TypeParameter,
+	},
+	IRClassFile,
+};
+
+/// The external symbols a class references. Class names are internal names.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dependencies {
+	pub classes: BTreeSet<String>,
+	pub fields: BTreeSet<MemberRef>,
+	pub methods: BTreeSet<MemberRef>,
+}
+
+impl IRClassFile {
+	pub fn dependencies(&self) -> Dependencies {
+		let mut collector = Collector {
+			class: self,
+			dependencies: Dependencies::default(),
+		};
+		collector.constant_pool();
+		collector.attributes(&self.attributes);
+		for field in &self.fields {
+			collector.descriptor(field.descriptor());
+			collector.attributes(&field.attributes);
+		}
+		for method in &self.methods {
+			collector.descriptor(method.descriptor());
+			collector.attributes(&method.attributes);
+		}
+		collector.dependencies
+	}
+}
+
+struct Collector<'a> {
+	class: &'a IRClassFile,
+	dependencies: Dependencies,
+}
+
+impl Collector<'_> {
+	fn class(&mut self, name: &str) {
+		if names::is_array(name) {
+			self.descriptor(name);
+		} else if name != self.class.class_name() {
+			self.dependencies.classes.insert(name.to_string());
+		}
+	}
+
+	/// The classes in a field or method descriptor.
+	fn descriptor(&mut self, descriptor: &str) {
+		let mut rest = descriptor;
+		while let Some(start) = rest.find('L') {
+			let Some(end) = rest[start..].find(';') else {
+				break;
+			};
+			self.class(&rest[start + 1..start + end]);
+			rest = &rest[start + end + 1..];
+		}
+	}
+
+	fn member(&mut self, class_index: u16, name_and_ty: &CPNameAndTypeRef, method: bool) {
+		let Ok(IRCpTag::Class(owner)) = cp_get(&self.class.cp, class_index) else {
+			return;
+		};
+		let (owner, name, descriptor) = (owner.data.as_str(), &name_and_ty.name.data, &name_and_ty.ty.data);
+		self.class(owner);
+		self.descriptor(descriptor);
+		let declared = owner == self.class.class_name()
+			&& match method {
+				true => self
+					.class
+					.methods
+					.iter()
+					.any(|candidate| candidate.name() == **name && candidate.descriptor() == **descriptor),
+				false => self
+					.class
+					.fields
+					.iter()
+					.any(|candidate| candidate.name() == **name && candidate.descriptor() == **descriptor),
+			};
+		if !declared {
+			let member = MemberRef::new(owner, name, descriptor);
+			match method {
+				true => self.dependencies.methods.insert(member),
+				false => self.dependencies.fields.insert(member),
+			};
+		}
+	}
+
+	fn constant_pool(&mut self) {
+		for tag in &self.class.cp {
+			match tag {
+				IRCpTag::Class(name) => self.class(&name.data),
+				IRCpTag::FieldRef {
+					class_index,
+					name_and_ty,
+				} => self.member(*class_index, name_and_ty, false),
+				IRCpTag::MethodRef {
+					class_index,
+					name_and_ty,
+				}
+				| IRCpTag::InterfaceMethodRef {
+					class_index,
+					name_and_ty,
+				} => self.member(*class_index, name_and_ty, true),
+				IRCpTag::MethodType(descriptor) => self.descriptor(&descriptor.data),
+				IRCpTag::InvokeDynamic { name_and_ty, .. } => self.descriptor(&name_and_ty.ty.data),
+				_ => {}
+			}
+		}
+	}
+
+	fn attributes(&mut self, attributes: &Attributes) {
+		for info in attributes.iter() {
+			match &info.attr {
+				IRAttribute::Code(code) => self.attributes(&code.attributes),
+				IRAttribute::Signature(signature) => self.signature(&signature.data),
+				IRAttribute::EnclosingMethod {
+					class,
+					method: Some(method),
+				} => {
+					let (owner, name, descriptor) = (&class.data.data, &method.name.data, &method.ty.data);
+					self.class(owner);
+					self.descriptor(descriptor);
+					self.dependencies
+						.methods
+						.insert(MemberRef::new(owner, name, descriptor));
+				}
+				IRAttribute::LocalVariableTable { table } => {
+					for entry in table {
+						self.descriptor(&entry.descriptor.data);
+					}
+				}
+				IRAttribute::LocalVariableTypeTable { table } => {
+					for entry in table {
+						self.signature(&entry.signature.data);
+					}
+				}
+				IRAttribute::RuntimeVisibleAnnotations { annotations }
+				| IRAttribute::RuntimeInvisibleAnnotations { annotations } => {
+					for annotation in annotations {
+						self.annotation(annotation);
+					}
+				}
+				IRAttribute::RuntimeVisibleParameterAnnotations { params }
+				| IRAttribute::RuntimeInvisibleParameterAnnotations { params } => {
+					for annotation in params.iter().flatten() {
+						self.annotation(annotation);
+					}
+				}
+				IRAttribute::RuntimeVisibleTypeAnnotations { annotations }
+				| IRAttribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+					for annotation in annotations {
+						if let Ok(ty) = CPUtf8Ref::from_cp(&self.class.cp, annotation.type_index) {
+							self.descriptor(&ty.data);
+						}
+						for pair in &annotation.pairs {
+							self.annotation_value(&pair.value);
+						}
+					}
+				}
+				IRAttribute::AnnotationDefault { default_value } => self.annotation_value(default_value),
+				IRAttribute::Record { components } => {
+					for component in components {
+						self.descriptor(&component.descriptor.data);
+						self.attributes(&component.attributes);
+					}
+				}
+				_ => {}
+			}
+		}
+	}
+
+	fn annotation(&mut self, annotation: &RuntimeAnnotation) {
+		self.descriptor(&annotation.ty.data);
+		for pair in &annotation.pairs {
+			self.annotation_value(&pair.value);
+		}
+	}
+
+	fn annotation_value(&mut self, value: &RuntimeAnnotationValue) {
+		match value {
+			RuntimeAnnotationValue::EnumConstValue { type_name, const_name } => {
+				self.descriptor(&type_name.data);
+				if let Some(owner) = names::descriptor_to_internal(&type_name.data) {
+					if owner != self.class.class_name() {
+						let constant = MemberRef::new(owner, &const_name.data, &type_name.data);
+						self.dependencies.fields.insert(constant);
+					}
+				}
+			}
+			RuntimeAnnotationValue::ClassInfoIndex(class) => self.descriptor(&class.data),
+			RuntimeAnnotationValue::Annotation(annotation) => self.annotation(annotation),
+			RuntimeAnnotationValue::ArrayValue { values } => {
+				for value in values {
+					self.annotation_value(value);
+				}
+			}
+			RuntimeAnnotationValue::ConstValueIndex { .. } => {}
+		}
+	}
+
+	/// The classes in a class, method or field signature. A malformed signature names none.
+	fn signature(&mut self, signature: &str) {
+		if let Ok(field) = ReferenceTypeSignature::parse(signature) {
+			self.reference_type(&field);
+		} else if let Ok(class) = ClassSignature::parse(signature) {
+			self.type_parameters(&class.type_params);
+			self.class_type(&class.superclass);
+			for interface in &class.interfaces {
+				self.class_type(interface);
+			}
+		} else if let Ok(method) = MethodSignature::parse(signature) {
+			self.type_parameters(&method.type_params);
+			for ty in method.params.iter().chain(&method.ret) {
+				self.java_type(ty);
+			}
+			for thrown in &method.throws {
+				self.reference_type(thrown);
+			}
+		}
+	}
+
+	fn type_parameters(&mut self, params: &[TypeParameter]) {
+		for param in params {
+			for bound in param.class_bound.iter().chain(&param.interface_bounds) {
+				self.reference_type(bound);
+			}
+		}
+	}
+
+	fn java_type(&mut self, ty: &JavaTypeSignature) {
+		if let JavaTypeSignature::Reference(reference) = ty {
+			self.reference_type(reference);
+		}
+	}
+
+	fn reference_type(&mut self, ty: &ReferenceTypeSignature) {
+		match ty {
+			ReferenceTypeSignature::Class(class) => self.class_type(class),
+			ReferenceTypeSignature::Array(element) => self.java_type(element),
+			ReferenceTypeSignature::TypeVariable(_) => {}
+		}
+	}
+
+	/// `Map<K, V>.Entry<K, V>` names java/util/Map and java/util/Map$Entry.
+	fn class_type(&mut self, ty: &ClassTypeSignature) {
+		let mut name = ty.name.clone();
+		self.class(&name);
+		self.type_arguments(&ty.args);
+		for inner in &ty.inner {
+			name = format!("{name}${}", inner.name);
+			self.class(&name);
+			self.type_arguments(&inner.args);
+		}
+	}
+
+	fn type_arguments(&mut self, args: &[TypeArgument]) {
+		for arg in args {
+			match arg {
+				TypeArgument::Exact(ty) | TypeArgument::Extends(ty) | TypeArgument::Super(ty) => {
+					self.reference_type(ty)
+				}
+				TypeArgument::Any => {}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn external_symbols() {
+		let class = assemble(
+			r#"
+.class public super p/Main
+.super p/Base
+.signature "Lp/Base;Ljava/lang/Iterable<Ljava/util/Map<TK;TV;>.Entry<Lp/Key;[Lp/Value;>;>;"
+.annotation visible Lp/Marker;
+	kind e Lp/Kind; FAST
+.end annotation
+.field private count I
+.field private items [[Lp/Item;
+.method public run(Lp/Input;)[I
+	aload_0
+	getfield p/Main/count I
+	pop
+	aload_0
+	getfield p/Main/inherited J
+	pop2
+	aload_0
+	aconst_null
+	invokevirtual p/Main/run(Lp/Input;)[I
+	pop
+	getstatic java/lang/System/out Ljava/io/PrintStream;
+	ldc "p/NotAClass"
+	invokevirtual java/io/PrintStream/println(Ljava/lang/String;)V
+	aconst_null
+	areturn
+.end method
+"#,
+		)
+		.unwrap();
+		let dependencies = class.dependencies();
+
+		assert_eq!(
+			dependencies.classes.iter().map(String::as_str).collect::<Vec<_>>(),
+			[
+				"java/io/PrintStream",
+				"java/lang/Iterable",
+				// left in the pool by the builder's default superclass
+				"java/lang/Object",
+				"java/lang/String",
+				"java/lang/System",
+				"java/util/Map",
+				"java/util/Map$Entry",
+				"p/Base",
+				"p/Input",
+				"p/Item",
+				"p/Key",
+				"p/Kind",
+				"p/Marker",
+				"p/Value",
+			]
+		);
+		assert_eq!(
+			dependencies.fields,
+			BTreeSet::from([
+				MemberRef::new("java/lang/System", "out", "Ljava/io/PrintStream;"),
+				MemberRef::new("p/Kind", "FAST", "Lp/Kind;"),
+				MemberRef::new("p/Main", "inherited", "J"),
+			])
+		);
+		assert_eq!(
+			dependencies.methods,
+			BTreeSet::from([MemberRef::new(
+				"java/io/PrintStream",
+				"println",
+				"(Ljava/lang/String;)V"
+			)])
+		);
+	}
+}
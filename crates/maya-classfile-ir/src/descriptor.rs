@@ -0,0 +1,212 @@
+use std::fmt::{self, Display};
+
+use crate::class_pool::IRClassfileError;
+
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BaseType {
+	Byte,
+	Char,
+	Double,
+	Float,
+	Int,
+	Long,
+	Short,
+	Boolean,
+}
+
+impl BaseType {
+	pub fn from_char(c: char) -> Option<Self> {
+		Some(match c {
+			'B' => Self::Byte,
+			'C' => Self::Char,
+			'D' => Self::Double,
+			'F' => Self::Float,
+			'I' => Self::Int,
+			'J' => Self::Long,
+			'S' => Self::Short,
+			'Z' => Self::Boolean,
+			_ => return None,
+		})
+	}
+
+	pub fn as_char(self) -> char {
+		match self {
+			Self::Byte => 'B',
+			Self::Char => 'C',
+			Self::Double => 'D',
+			Self::Float => 'F',
+			Self::Int => 'I',
+			Self::Long => 'J',
+			Self::Short => 'S',
+			Self::Boolean => 'Z',
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FieldType {
+	Base(BaseType),
+	// internal name
+	Object(String),
+	Array(Box<FieldType>),
+}
+
+impl FieldType {
+	pub fn parse(descriptor: &str) -> Result<Self, IRClassfileError> {
+		let mut chars = descriptor.char_indices().peekable();
+		let ty = Self::parse_next(descriptor, &mut chars)?;
+		match chars.next() {
+			None => Ok(ty),
+			Some(_) => Err(IRClassfileError::InvalidDescriptor(descriptor.to_string())),
+		}
+	}
+
+	fn parse_next(
+		descriptor: &str,
+		chars: &mut std::iter::Peekable<std::str::CharIndices>,
+	) -> Result<Self, IRClassfileError> {
+		let invalid = || IRClassfileError::InvalidDescriptor(descriptor.to_string());
+		let (start, c) = chars.next().ok_or_else(invalid)?;
+
+		Ok(match c {
+			'L' => {
+				let end = descriptor[start..].find(';').ok_or_else(invalid)? + start;
+				let name = &descriptor[start + 1..end];
+				if name.is_empty()
+					|| name
+						.split('/')
+						.any(|part| part.is_empty() || part.contains(['.', '[', ';']))
+				{
+					return Err(invalid());
+				}
+
+				while chars.next_if(|(idx, _)| *idx <= end).is_some() {}
+				Self::Object(name.to_string())
+			}
+			'[' => Self::Array(Box::new(Self::parse_next(descriptor, chars)?)),
+			c => Self::Base(BaseType::from_char(c).ok_or_else(invalid)?),
+		})
+	}
+
+	// The number of local variable / operand stack slots a value of this type takes.
+	pub fn slots(&self) -> u16 {
+		match self {
+			Self::Base(BaseType::Long | BaseType::Double) => 2,
+			_ => 1,
+		}
+	}
+
+	pub fn array_dimensions(&self) -> usize {
+		match self {
+			Self::Array(inner) => 1 + inner.array_dimensions(),
+			_ => 0,
+		}
+	}
+
+	pub fn is_reference(&self) -> bool {
+		matches!(self, Self::Object(_) | Self::Array(_))
+	}
+}
+
+impl Display for FieldType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Base(base) => write!(f, "{}", base.as_char()),
+			Self::Object(name) => write!(f, "L{name};"),
+			Self::Array(inner) => write!(f, "[{inner}"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MethodDescriptor {
+	pub params: Vec<FieldType>,
+	// None for void
+	pub ret: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+	pub fn parse(descriptor: &str) -> Result<Self, IRClassfileError> {
+		let invalid = || IRClassfileError::InvalidDescriptor(descriptor.to_string());
+		let mut chars = descriptor.char_indices().peekable();
+		if chars.next().map(|(_, c)| c) != Some('(') {
+			return Err(invalid());
+		}
+
+		let mut params = Vec::new();
+		while chars.next_if(|(_, c)| *c == ')').is_none() {
+			if chars.peek().is_none() {
+				return Err(invalid());
+			}
+			params.push(FieldType::parse_next(descriptor, &mut chars)?);
+		}
+
+		let ret = match chars.peek() {
+			Some((_, 'V')) => {
+				chars.next();
+				None
+			}
+			_ => Some(FieldType::parse_next(descriptor, &mut chars)?),
+		};
+
+		match chars.next() {
+			None => Ok(Self { params, ret }),
+			Some(_) => Err(invalid()),
+		}
+	}
+
+	/// Slots taken by the parameters, not counting `this`.
+	pub fn param_slots(&self) -> u16 {
+		self.params.iter().map(FieldType::slots).sum()
+	}
+}
+
+impl Display for MethodDescriptor {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "(")?;
+		for param in &self.params {
+			write!(f, "{param}")?;
+		}
+		write!(f, ")")?;
+		match &self.ret {
+			Some(ret) => write!(f, "{ret}"),
+			None => write!(f, "V"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn field_descriptors() {
+		assert_eq!(FieldType::parse("I").unwrap(), FieldType::Base(BaseType::Int));
+		assert_eq!(
+			FieldType::parse("[Ljava/lang/String;").unwrap(),
+			FieldType::Array(Box::new(FieldType::Object("java/lang/String".into())))
+		);
+		assert!(FieldType::parse("V").is_err());
+		assert!(FieldType::parse("Ljava/lang/String").is_err());
+		assert!(FieldType::parse("II").is_err());
+		assert!(FieldType::parse("L;").is_err());
+	}
+
+	#[test]
+	fn method_descriptors() {
+		let desc = MethodDescriptor::parse("(IJ[Ljava/lang/Object;D)V").unwrap();
+		assert_eq!(desc.params.len(), 4);
+		assert_eq!(desc.param_slots(), 6);
+		assert_eq!(desc.ret, None);
+		assert_eq!(desc.to_string(), "(IJ[Ljava/lang/Object;D)V");
+
+		assert!(MethodDescriptor::parse("()").is_err());
+		assert!(MethodDescriptor::parse("(V)V").is_err());
+		assert!(MethodDescriptor::parse("(I").is_err());
+		assert!(MethodDescriptor::parse("()VV").is_err());
+	}
+}
@@ -0,0 +1,459 @@
+// Comparing two versions of a class by what they mean rather than how they're laid out, on their canonical forms (see
+// `semantic`). Any two compiles number their constant pools differently, so everything referring to the pool is
+// compared by what it resolves to. Code is compared instruction by instruction, with branches as distances in
+// instructions rather than bytes, so inserting one instruction shows up as just that.
+
+use std::{
+	collections::BTreeMap,
+	fmt::{self, Display},
+};
+
+use crate::{
+	class_pool::IRClassfileError,
+	flags::ClassAccessFlags,
+	semantic::{AttributeTexts, CanonicalClass, CanonicalCode, CanonicalMember},
+	ClassFileVersion, IRClassFile,
+};
+
+// Past this many cells the instructions between the common start and end are reported as replaced wholesale.
+const MAX_LCS_CELLS: usize = 1 << 22;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ClassDiff {
+	pub header: Vec<HeaderChange>,
+	/// Changes to the attributes of the class itself.
+	pub attributes: Vec<AttributeChange>,
+	pub fields: Vec<MemberChange>,
+	pub methods: Vec<MemberChange>,
+}
+
+impl ClassDiff {
+	/// Whether the classes mean the same, however their pools are laid out.
+	pub fn is_empty(&self) -> bool {
+		self.header.is_empty() && self.attributes.is_empty() && self.fields.is_empty() && self.methods.is_empty()
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderChange {
+	Version {
+		old: ClassFileVersion,
+		new: ClassFileVersion,
+	},
+	AccessFlags {
+		old: ClassAccessFlags,
+		new: ClassAccessFlags,
+	},
+	Name {
+		old: String,
+		new: String,
+	},
+	SuperClass {
+		old: Option<String>,
+		new: Option<String>,
+	},
+	InterfaceAdded(String),
+	InterfaceRemoved(String),
+}
+
+/// An attribute by name. Attributes that can appear more than once count as changed if any of them did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeChange {
+	Added(String),
+	Removed(String),
+	Changed(String),
+}
+
+/// A field or method, by its name and descriptor, e.g. `run()V`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemberChange {
+	Added(String),
+	Removed(String),
+	Changed(MemberDiff),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberDiff {
+	pub member: String,
+	// raw, fields and methods have their own flags
+	pub access_flags: Option<(u16, u16)>,
+	/// Everything but `Code` when both have it, which goes in `code`.
+	pub attributes: Vec<AttributeChange>,
+	pub code: Option<CodeDiff>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CodeDiff {
+	pub max_stack: Option<(u16, u16)>,
+	pub max_locals: Option<(u16, u16)>,
+	pub instructions: Vec<Edit>,
+	pub exception_table: Vec<Edit>,
+	/// The attributes of the code, whose pcs change along with any instruction.
+	pub attributes: Vec<AttributeChange>,
+}
+
+impl CodeDiff {
+	pub fn is_empty(&self) -> bool {
+		*self == Self::default()
+	}
+}
+
+/// A line of the old listing that isn't in the new one or the other way around, with its position in its listing.
+/// Instructions are listed like `invokevirtual java/io/PrintStream.println:(I)V`, with branch targets as `+n`
+/// instructions from the branch, and exception handlers like `0 4 6 java/io/IOException` in instruction positions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+	Removed { index: usize, text: String },
+	Added { index: usize, text: String },
+}
+
+/// The differences from `old` to `new`.
+pub fn diff(old: &IRClassFile, new: &IRClassFile) -> Result<ClassDiff, IRClassfileError> {
+	Ok(CanonicalClass::new(old)?.diff(&CanonicalClass::new(new)?))
+}
+
+impl IRClassFile {
+	/// The differences from this class to `new`, see `diff::diff`.
+	pub fn diff(&self, new: &IRClassFile) -> Result<ClassDiff, IRClassfileError> {
+		diff(self, new)
+	}
+}
+
+impl CanonicalClass {
+	/// The differences from this class to `new`, members in the order of this class and then what's added in the
+	/// order of `new`.
+	pub fn diff(&self, new: &CanonicalClass) -> ClassDiff {
+		ClassDiff {
+			header: self.header_changes(new),
+			attributes: attribute_changes(&self.attributes, &new.attributes),
+			fields: member_changes(&self.fields, &new.fields),
+			methods: member_changes(&self.methods, &new.methods),
+		}
+	}
+
+	fn header_changes(&self, new: &CanonicalClass) -> Vec<HeaderChange> {
+		let mut changes = Vec::new();
+		if self.version != new.version {
+			changes.push(HeaderChange::Version {
+				old: self.version,
+				new: new.version,
+			});
+		}
+		if self.access_flags != new.access_flags {
+			changes.push(HeaderChange::AccessFlags {
+				old: self.access_flags,
+				new: new.access_flags,
+			});
+		}
+		if self.name != new.name {
+			changes.push(HeaderChange::Name {
+				old: self.name.clone(),
+				new: new.name.clone(),
+			});
+		}
+		if self.super_class != new.super_class {
+			changes.push(HeaderChange::SuperClass {
+				old: self.super_class.clone(),
+				new: new.super_class.clone(),
+			});
+		}
+		let removed = self.interfaces.iter().filter(|name| !new.interfaces.contains(name));
+		changes.extend(removed.cloned().map(HeaderChange::InterfaceRemoved));
+		let added = new.interfaces.iter().filter(|name| !self.interfaces.contains(name));
+		changes.extend(added.cloned().map(HeaderChange::InterfaceAdded));
+		changes
+	}
+}
+
+fn member_changes(old: &[CanonicalMember], new: &[CanonicalMember]) -> Vec<MemberChange> {
+	let new_by_id: BTreeMap<_, _> = new.iter().map(|member| (&member.id, member)).collect();
+	let mut changes = Vec::new();
+	for member in old {
+		let Some(other) = new_by_id.get(&member.id) else {
+			changes.push(MemberChange::Removed(member.id.clone()));
+			continue;
+		};
+		let mut diff = MemberDiff {
+			member: member.id.clone(),
+			access_flags: changed(member.access_flags, other.access_flags),
+			attributes: attribute_changes(&member.attributes, &other.attributes),
+			code: None,
+		};
+		match (&member.code, &other.code) {
+			(Some(code), Some(other)) => diff.code = Some(code_diff(code, other)).filter(|code| !code.is_empty()),
+			(Some(_), None) => diff.attributes.push(AttributeChange::Removed("Code".to_string())),
+			(None, Some(_)) => diff.attributes.push(AttributeChange::Added("Code".to_string())),
+			(None, None) => {}
+		}
+		if diff.access_flags.is_some() || !diff.attributes.is_empty() || diff.code.is_some() {
+			changes.push(MemberChange::Changed(diff));
+		}
+	}
+	let added = new.iter().filter(|member| !old.iter().any(|old| old.id == member.id));
+	changes.extend(added.map(|member| MemberChange::Added(member.id.clone())));
+	changes
+}
+
+fn attribute_changes(old: &AttributeTexts, new: &AttributeTexts) -> Vec<AttributeChange> {
+	let mut changes = Vec::new();
+	for (name, texts) in old {
+		match new.get(name) {
+			None => changes.push(AttributeChange::Removed(name.clone())),
+			Some(other) if other != texts => changes.push(AttributeChange::Changed(name.clone())),
+			Some(_) => {}
+		}
+	}
+	let added = new.keys().filter(|name| !old.contains_key(*name));
+	changes.extend(added.cloned().map(AttributeChange::Added));
+	changes
+}
+
+fn code_diff(old: &CanonicalCode, new: &CanonicalCode) -> CodeDiff {
+	CodeDiff {
+		max_stack: changed(old.max_stack, new.max_stack),
+		max_locals: changed(old.max_locals, new.max_locals),
+		instructions: edits(&old.instructions, &new.instructions),
+		exception_table: edits(&old.exception_table, &new.exception_table),
+		attributes: attribute_changes(&old.attributes, &new.attributes),
+	}
+}
+
+fn changed(old: u16, new: u16) -> Option<(u16, u16)> {
+	(old != new).then_some((old, new))
+}
+
+// The lines removed from `old` and added in `new`, by longest common subsequence.
+fn edits(old: &[String], new: &[String]) -> Vec<Edit> {
+	let prefix = old.iter().zip(new).take_while(|(old, new)| old == new).count();
+	let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+	let suffix = old_rest
+		.iter()
+		.rev()
+		.zip(new_rest.iter().rev())
+		.take_while(|(old, new)| old == new)
+		.count();
+	let (old_rest, new_rest) = (
+		&old_rest[..old_rest.len() - suffix],
+		&new_rest[..new_rest.len() - suffix],
+	);
+	let (n, m) = (old_rest.len(), new_rest.len());
+
+	let removed = |index: usize| Edit::Removed {
+		index: prefix + index,
+		text: old_rest[index].clone(),
+	};
+	let added = |index: usize| Edit::Added {
+		index: prefix + index,
+		text: new_rest[index].clone(),
+	};
+	if (n + 1).saturating_mul(m + 1) > MAX_LCS_CELLS {
+		return (0..n).map(removed).chain((0..m).map(added)).collect();
+	}
+	// lengths[i][j] is the longest common subsequence of old_rest[i..] and new_rest[j..]
+	let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+	for i in (0..n).rev() {
+		for j in (0..m).rev() {
+			lengths[i][j] = match old_rest[i] == new_rest[j] {
+				true => lengths[i + 1][j + 1] + 1,
+				false => lengths[i + 1][j].max(lengths[i][j + 1]),
+			};
+		}
+	}
+	let (mut i, mut j, mut edits) = (0, 0, Vec::new());
+	while i < n || j < m {
+		if i < n && j < m && old_rest[i] == new_rest[j] {
+			i += 1;
+			j += 1;
+		} else if j == m || (i < n && lengths[i + 1][j] >= lengths[i][j + 1]) {
+			edits.push(removed(i));
+			i += 1;
+		} else {
+			edits.push(added(j));
+			j += 1;
+		}
+	}
+	edits
+}
+
+impl Display for ClassDiff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for change in &self.header {
+			writeln!(f, "{change}")?;
+		}
+		for change in &self.attributes {
+			writeln!(f, "{change}")?;
+		}
+		for (kind, members) in [("field", &self.fields), ("method", &self.methods)] {
+			for change in members {
+				match change {
+					MemberChange::Added(id) => writeln!(f, "+ {kind} {id}")?,
+					MemberChange::Removed(id) => writeln!(f, "- {kind} {id}")?,
+					MemberChange::Changed(member) => {
+						writeln!(f, "~ {kind} {}", member.member)?;
+						if let Some((old, new)) = member.access_flags {
+							writeln!(f, "    access_flags {old:#06x} -> {new:#06x}")?;
+						}
+						for change in &member.attributes {
+							writeln!(f, "    {change}")?;
+						}
+						if let Some(code) = &member.code {
+							write!(f, "{code}")?;
+						}
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Display for CodeDiff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if let Some((old, new)) = self.max_stack {
+			writeln!(f, "    max_stack {old} -> {new}")?;
+		}
+		if let Some((old, new)) = self.max_locals {
+			writeln!(f, "    max_locals {old} -> {new}")?;
+		}
+		for edit in &self.instructions {
+			writeln!(f, "    {edit}")?;
+		}
+		for edit in &self.exception_table {
+			writeln!(f, "    handler {edit}")?;
+		}
+		for change in &self.attributes {
+			writeln!(f, "    {change}")?;
+		}
+		Ok(())
+	}
+}
+
+impl Display for HeaderChange {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Version { old, new } => write!(f, "~ version {old} -> {new}"),
+			Self::AccessFlags { old, new } => write!(f, "~ access_flags {:#06x} -> {:#06x}", old.bits(), new.bits()),
+			Self::Name { old, new } => write!(f, "~ this_class {old} -> {new}"),
+			Self::SuperClass { old, new } => {
+				let name = |name: &Option<String>| name.clone().unwrap_or_else(|| "none".to_string());
+				write!(f, "~ super_class {} -> {}", name(old), name(new))
+			}
+			Self::InterfaceAdded(name) => write!(f, "+ interface {name}"),
+			Self::InterfaceRemoved(name) => write!(f, "- interface {name}"),
+		}
+	}
+}
+
+impl Display for AttributeChange {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Added(name) => write!(f, "+ attribute {name}"),
+			Self::Removed(name) => write!(f, "- attribute {name}"),
+			Self::Changed(name) => write!(f, "~ attribute {name}"),
+		}
+	}
+}
+
+impl Display for Edit {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Removed { index, text } => write!(f, "- {index}: {text}"),
+			Self::Added { index, text } => write!(f, "+ {index}: {text}"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn diff_ignoring_pool_layout() {
+		let old = assemble(
+			r#"
+			.class public super p/Counter
+			.field private count I
+			.field static final NAME Ljava/lang/String; = "counter"
+			.method public run()V
+				aload_0
+				getfield p/Counter/count I
+				ifeq Done
+				ldc "busy"
+				pop
+			Done:
+				return
+			.end method
+			.method public reset()V
+				return
+			.end method
+			"#,
+		)
+		.unwrap();
+		// the same members the other way around puts everything at different pool indices
+		let reordered = assemble(
+			r#"
+			.class public super p/Counter
+			.method public reset()V
+				return
+			.end method
+			.method public run()V
+				aload_0
+				getfield p/Counter/count I
+				ifeq Done
+				ldc "busy"
+				pop
+			Done:
+				return
+			.end method
+			.field static final NAME Ljava/lang/String; = "counter"
+			.field private count I
+			"#,
+		)
+		.unwrap();
+		assert_ne!(old.to_bytes().unwrap(), reordered.to_bytes().unwrap());
+		assert!(diff(&old, &reordered).unwrap().is_empty());
+
+		let new = assemble(
+			r#"
+			.class public super p/Counter
+			.implements java/lang/Runnable
+			.field static final NAME Ljava/lang/String; = "count"
+			.method public run()V
+				aload_0
+				getfield p/Counter/count I
+				ifeq Done
+				ldc "busy"
+				invokestatic p/Log/info(Ljava/lang/String;)V
+			Done:
+				return
+			.end method
+			.method public stop()V
+				return
+			.end method
+			"#,
+		)
+		.unwrap();
+		let diff = old.diff(&new).unwrap();
+		assert_eq!(
+			diff.header,
+			[HeaderChange::InterfaceAdded("java/lang/Runnable".to_string())]
+		);
+		assert_eq!(diff.fields[0], MemberChange::Removed("countI".to_string()));
+		assert!(
+			matches!(&diff.fields[1], MemberChange::Changed(field) if field.attributes == [AttributeChange::Changed("ConstantValue".to_string())])
+		);
+		assert_eq!(
+			diff.to_string(),
+			"+ interface java/lang/Runnable\n\
+			- field countI\n\
+			~ field NAMELjava/lang/String;\n    \
+			    ~ attribute ConstantValue\n\
+			~ method run()V\n    \
+			    - 4: pop\n    \
+			    + 4: invokestatic p/Log.info:(Ljava/lang/String;)V\n\
+			- method reset()V\n\
+			+ method stop()V\n"
+		);
+	}
+}
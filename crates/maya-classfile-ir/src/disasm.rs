@@ -0,0 +1,970 @@
+// A textual listing of a class in the layout of `javap -v -p`, so the two can be diffed: the header, the constant pool,
+// every member with its code laid out instruction by instruction and the attributes javap knows how to print. Only
+// the file details javap starts with (path, modification time, checksum) are left out. Attributes without a layout of
+// their own are printed the way javap prints the ones it doesn't know, by name and length.
+// https://docs.oracle.com/en/java/javase/22/docs/specs/man/javap.html
+
+use std::fmt::Write;
+
+use crate::{
+	attribute::{
+		CodeAttribute, IRAttribute, IRAttributeInfo, RuntimeAnnotation, RuntimeAnnotationValue, StackMapFrame,
+		VerificationTypeInfo,
+	},
+	class_pool::{
+		self, java_number, javap_name, javap_name_and_type, CPClassRef, CPConstValueRefKind, CPTagRef,
+		IRClassfileError, IRCpTag,
+	},
+	code::{Instructions, Opcodes},
+	descriptor::MethodDescriptor,
+	flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+	names,
+	signature::{
+		ClassSignature, ClassTypeSignature, JavaTypeSignature, MethodSignature, ReferenceTypeSignature, TypeParameter,
+	},
+	IRClassFile, IRFieldInfo, IRMethodInfo,
+};
+
+/// The whole listing, ending in a newline.
+pub fn disassemble(class: &IRClassFile) -> Result<String, IRClassfileError> {
+	let mut disasm = Disassembler {
+		class,
+		out: String::new(),
+	};
+	disasm.class()?;
+	Ok(disasm.out)
+}
+
+/// The listing of one method's code, from `stack=` on, without the method around it.
+pub fn disassemble_code(class: &IRClassFile, method: &IRMethodInfo) -> Result<String, IRClassfileError> {
+	let mut disasm = Disassembler {
+		class,
+		out: String::new(),
+	};
+	if let Some(code) = method.code() {
+		disasm.code(method, code)?;
+	}
+	Ok(disasm.out)
+}
+
+struct Disassembler<'a> {
+	class: &'a IRClassFile,
+	out: String,
+}
+
+impl Disassembler<'_> {
+	// javap trims the end of every line.
+	fn line(&mut self, indent: usize, text: impl AsRef<str>) {
+		let _ = writeln!(self.out, "{:indent$}{}", "", text.as_ref().trim_end());
+	}
+
+	// javap lines the comments up 40 columns after the indent.
+	fn commented(&mut self, indent: usize, text: impl AsRef<str>, comment: impl AsRef<str>) {
+		let line = format!("{:<39} // {}", text.as_ref(), comment.as_ref());
+		self.line(indent, line);
+	}
+
+	fn class(&mut self) -> Result<(), IRClassfileError> {
+		let class = self.class;
+		if let Some(source) = class.attributes.source_file() {
+			self.line(2, format!("Compiled from \"{source}\""));
+		}
+		self.line(0, class_declaration(class));
+		self.line(2, format!("minor version: {}", class.version.minor));
+		self.line(2, format!("major version: {}", class.version.major));
+		self.line(
+			2,
+			flags_line(class.access_flags.bits(), class.access_flags.iter_names()),
+		);
+		self.commented(
+			2,
+			format!("this_class: #{}", class.this_class.index),
+			javap_name(&class.this_class.data.data),
+		);
+		match &class.super_class {
+			Some(super_class) => self.commented(
+				2,
+				format!("super_class: #{}", super_class.index),
+				javap_name(&super_class.data.data),
+			),
+			None => self.line(2, "super_class: #0"),
+		}
+		self.line(
+			2,
+			format!(
+				"interfaces: {}, fields: {}, methods: {}, attributes: {}",
+				class.interfaces.len(),
+				class.fields.len(),
+				class.methods.len(),
+				class.attributes.len()
+			),
+		);
+		self.line(0, "Constant pool:");
+		self.constant_pool();
+		self.line(0, "{");
+		for (i, field) in class.fields.iter().enumerate() {
+			if i > 0 {
+				self.line(0, "");
+			}
+			self.field(field)?;
+		}
+		for (i, method) in class.methods.iter().enumerate() {
+			if i > 0 || !class.fields.is_empty() {
+				self.line(0, "");
+			}
+			self.method(method)?;
+		}
+		self.line(0, "}");
+		for attr in class.attributes.iter() {
+			self.attribute(0, attr, None)?;
+		}
+		Ok(())
+	}
+
+	// Laid out like `class_pool::dump`, but indented, with the comments lined up further right the more digits the
+	// indices take.
+	fn constant_pool(&mut self) {
+		let cp = &self.class.cp;
+		let width = (cp.len() + 1).to_string().len() + 3;
+		for (i, tag) in cp.iter().enumerate() {
+			if matches!(tag, IRCpTag::Unusable) {
+				continue;
+			}
+			let line = format!("{:>width$} = {tag}", format!("#{}", i + 1));
+			match tag.resolved(cp) {
+				Some(comment) => {
+					// javap leaves an extra space before a method type.
+					let space = if matches!(tag, IRCpTag::MethodType(_)) { " " } else { "" };
+					self.line(0, format!("{line:<41} // {space}{comment}"));
+				}
+				None => self.line(0, line),
+			}
+		}
+	}
+
+	fn field(&mut self, field: &IRFieldInfo) -> Result<(), IRClassfileError> {
+		let ty = match field
+			.attributes
+			.signature()
+			.and_then(|sig| ReferenceTypeSignature::parse(sig).ok())
+		{
+			Some(sig) => sig.to_java(),
+			None => names::descriptor_to_source(field.descriptor()).unwrap_or_else(|| field.descriptor().to_string()),
+		};
+		let modifiers = field_modifiers(field.access_flags);
+		self.line(2, format!("{modifiers}{ty} {};", field.name()));
+		self.line(4, format!("descriptor: {}", field.descriptor()));
+		self.line(
+			4,
+			flags_line(field.access_flags.bits(), field.access_flags.iter_names()),
+		);
+		for attr in field.attributes.iter() {
+			self.attribute(4, attr, None)?;
+		}
+		Ok(())
+	}
+
+	fn method(&mut self, method: &IRMethodInfo) -> Result<(), IRClassfileError> {
+		self.line(2, self.method_declaration(method)?);
+		self.line(4, format!("descriptor: {}", method.descriptor()));
+		self.line(
+			4,
+			flags_line(method.access_flags.bits(), method.access_flags.iter_names()),
+		);
+		for attr in method.attributes.iter() {
+			self.attribute(4, attr, Some(method))?;
+		}
+		Ok(())
+	}
+
+	fn method_declaration(&self, method: &IRMethodInfo) -> Result<String, IRClassfileError> {
+		if method.name() == "<clinit>" {
+			return Ok("static {};".to_string());
+		}
+		let descriptor = MethodDescriptor::parse(method.descriptor())?;
+		let source = |descriptor: String| names::descriptor_to_source(&descriptor).unwrap_or(descriptor);
+		let signature = method
+			.attributes
+			.signature()
+			.and_then(|sig| MethodSignature::parse(sig).ok());
+		let (type_params, mut params, ret) = match &signature {
+			Some(sig) => (
+				type_params(&sig.type_params),
+				sig.params.iter().map(JavaTypeSignature::to_java).collect(),
+				sig.ret.as_ref().map_or("void".to_string(), JavaTypeSignature::to_java),
+			),
+			None => (
+				String::new(),
+				descriptor
+					.params
+					.iter()
+					.map(|param| source(param.to_string()))
+					.collect::<Vec<_>>(),
+				descriptor
+					.ret
+					.as_ref()
+					.map_or("void".to_string(), |ret| source(ret.to_string())),
+			),
+		};
+		if method.access_flags.contains(MethodAccessFlags::VARARGS) {
+			if let Some(last) = params.last_mut().filter(|last| last.ends_with("[]")) {
+				last.truncate(last.len() - 2);
+				last.push_str("...");
+			}
+		}
+
+		let mut declaration = method_modifiers(self.class, method);
+		if !type_params.is_empty() {
+			let _ = write!(declaration, "{type_params} ");
+		}
+		match method.name() {
+			"<init>" => declaration.push_str(&names::internal_to_binary(self.class.class_name())),
+			name => {
+				let _ = write!(declaration, "{ret} {name}");
+			}
+		}
+		let _ = write!(declaration, "({})", params.join(", "));
+		if let Some(exceptions) = method.attributes.iter().find_map(|attr| match &attr.attr {
+			IRAttribute::Exceptions { exception_index_table } => Some(exception_index_table),
+			_ => None,
+		}) {
+			let thrown = exceptions
+				.iter()
+				.map(|class| names::internal_to_binary(&class.data.data))
+				.collect::<Vec<_>>();
+			let _ = write!(declaration, " throws {}", thrown.join(", "));
+		}
+		declaration.push(';');
+		Ok(declaration)
+	}
+
+	fn code(&mut self, method: &IRMethodInfo, code: &CodeAttribute) -> Result<(), IRClassfileError> {
+		// Counted in parameters rather than the slots they take.
+		let params = MethodDescriptor::parse(method.descriptor())?.params.len();
+		let args_size = params + usize::from(!method.access_flags.contains(MethodAccessFlags::STATIC));
+		self.line(
+			6,
+			format!(
+				"stack={}, locals={}, args_size={args_size}",
+				code.max_stack, code.max_locals
+			),
+		);
+
+		let instructions = Instructions::read_all(&self.class.cp, &code.code)?;
+		for (pc, _) in &instructions {
+			self.instruction(&code.code, *pc as usize);
+		}
+
+		if !code.exception_table.is_empty() {
+			self.line(6, "Exception table:");
+			self.line(9, "from    to  target type");
+			for exception in &code.exception_table {
+				let ty = match exception.catch_type {
+					0 => "any".to_string(),
+					index => CPClassRef::from_cp(&self.class.cp, index)
+						.map_or_else(|_| format!("#{index}"), |class| format!("Class {}", class.data.data)),
+				};
+				self.line(
+					9,
+					format!(
+						"{:5} {:5} {:5}   {ty}",
+						exception.start_pc, exception.end_pc, exception.handler_pc
+					),
+				);
+			}
+		}
+		for attr in code.attributes.iter() {
+			self.attribute(6, attr, None)?;
+		}
+		Ok(())
+	}
+
+	fn instruction(&mut self, code: &[u8], pc: usize) {
+		let u8_at = |at: usize| code[at];
+		let u16_at = |at: usize| u16::from_be_bytes([code[at], code[at + 1]]);
+		let i32_at = |at: usize| i32::from_be_bytes([code[at], code[at + 1], code[at + 2], code[at + 3]]);
+		let target = |offset: i32| (pc as i64 + i64::from(offset)).to_string();
+
+		let opcode = code[pc];
+		let mnemonic = Opcodes::mnemonic(opcode).unwrap_or("???");
+		let head = format!("{pc:4}: {mnemonic:<13} ");
+		let (operands, comment) = match opcode {
+			Opcodes::BIPUSH => ((u8_at(pc + 1) as i8).to_string(), None),
+			Opcodes::SIPUSH => ((u16_at(pc + 1) as i16).to_string(), None),
+			Opcodes::LDC => {
+				let index = u16::from(u8_at(pc + 1));
+				(format!("#{index}"), self.operand_comment(index))
+			}
+			Opcodes::LDC_W
+			| Opcodes::LDC2_W
+			| Opcodes::GETSTATIC..=Opcodes::INVOKESTATIC
+			| Opcodes::NEW
+			| Opcodes::ANEWARRAY
+			| Opcodes::CHECKCAST
+			| Opcodes::INSTANCEOF => {
+				let index = u16_at(pc + 1);
+				(format!("#{index}"), self.operand_comment(index))
+			}
+			Opcodes::INVOKEINTERFACE => {
+				let index = u16_at(pc + 1);
+				(format!("#{index},  {}", u8_at(pc + 3)), self.operand_comment(index))
+			}
+			Opcodes::INVOKEDYNAMIC => {
+				let index = u16_at(pc + 1);
+				(format!("#{index},  0"), self.operand_comment(index))
+			}
+			Opcodes::MULTIANEWARRAY => {
+				let index = u16_at(pc + 1);
+				(format!("#{index},  {}", u8_at(pc + 3)), self.operand_comment(index))
+			}
+			Opcodes::ILOAD..=Opcodes::ALOAD | Opcodes::ISTORE..=Opcodes::ASTORE | Opcodes::RET => {
+				(u8_at(pc + 1).to_string(), None)
+			}
+			Opcodes::IINC => (format!("{}, {}", u8_at(pc + 1), u8_at(pc + 2) as i8), None),
+			Opcodes::IFEQ..=Opcodes::JSR | Opcodes::IFNULL | Opcodes::IFNONNULL => {
+				(target(i32::from(u16_at(pc + 1) as i16)), None)
+			}
+			Opcodes::GOTO_W | Opcodes::JSR_W => (target(i32_at(pc + 1)), None),
+			Opcodes::NEWARRAY => (format!(" {}", array_type(u8_at(pc + 1))), None),
+			Opcodes::WIDE => {
+				let modified = u8_at(pc + 1);
+				let mnemonic = format!("{}_w", Opcodes::mnemonic(modified).unwrap_or("???"));
+				let operands = match modified {
+					Opcodes::IINC => format!("{}, {}", u16_at(pc + 2), u16_at(pc + 4) as i16),
+					_ => u16_at(pc + 2).to_string(),
+				};
+				self.line(6, format!("{pc:4}: {mnemonic:<13} {operands}"));
+				return;
+			}
+			Opcodes::TABLESWITCH | Opcodes::LOOKUPSWITCH => {
+				let base = (pc + 4) & !3;
+				let default = i32_at(base);
+				let cases = match opcode {
+					Opcodes::TABLESWITCH => {
+						let (low, high) = (i32_at(base + 4), i32_at(base + 8));
+						self.line(6, format!("{head}{{ // {low} to {high}"));
+						(low..=high)
+							.enumerate()
+							.map(|(i, key)| (key, i32_at(base + 12 + i * 4)))
+							.collect::<Vec<_>>()
+					}
+					_ => {
+						let pairs = i32_at(base + 4) as usize;
+						self.line(6, format!("{head}{{ // {pairs}"));
+						(0..pairs)
+							.map(|i| (i32_at(base + 8 + i * 8), i32_at(base + 12 + i * 8)))
+							.collect()
+					}
+				};
+				for (key, offset) in cases {
+					self.line(6, format!("{key:>18}: {}", target(offset)));
+				}
+				self.line(6, format!("{:>18}: {}", "default", target(default)));
+				self.line(12, "}");
+				return;
+			}
+			_ => {
+				self.line(6, format!("{pc:4}: {mnemonic}"));
+				return;
+			}
+		};
+		let text = format!("{head}{operands}");
+		match comment {
+			Some(comment) => self.commented(6, text, comment),
+			None => self.line(6, text),
+		}
+	}
+
+	// javap's comment on an instruction's constant, the entry's kind and value. Members of this class are named without
+	// it.
+	fn operand_comment(&self, index: u16) -> Option<String> {
+		let cp = &self.class.cp;
+		let tag = CPTagRef::from_cp(cp, index).ok()?.tag;
+		let member = |class_index: u16, name_and_ty| match CPClassRef::from_cp(cp, class_index) {
+			Ok(class) if class.data.data == self.class.this_class.data.data => javap_name_and_type(name_and_ty),
+			_ => tag.resolved(cp).unwrap_or_default(),
+		};
+		Some(match &tag {
+			IRCpTag::FieldRef {
+				class_index,
+				name_and_ty,
+			} => format!("Field {}", member(*class_index, name_and_ty)),
+			IRCpTag::MethodRef {
+				class_index,
+				name_and_ty,
+			} => format!("Method {}", member(*class_index, name_and_ty)),
+			IRCpTag::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
+			} => format!("InterfaceMethod {}", member(*class_index, name_and_ty)),
+			IRCpTag::Class(_) => format!("class {}", tag.resolved(cp)?),
+			_ => constant(cp, &tag)?,
+		})
+	}
+
+	fn attribute(
+		&mut self,
+		indent: usize,
+		info: &IRAttributeInfo,
+		method: Option<&IRMethodInfo>,
+	) -> Result<(), IRClassfileError> {
+		let cp = &self.class.cp;
+		let name = info.name.data.as_str();
+		match &info.attr {
+			IRAttribute::Code(code) => {
+				self.line(indent, "Code:");
+				if let Some(method) = method {
+					self.code(method, code)?;
+				}
+			}
+			IRAttribute::ConstantValue(value) => {
+				let value = match value.value() {
+					CPConstValueRefKind::Int(int) => format!("int {int}"),
+					CPConstValueRefKind::Float(float) => format!("float {}f", java_number(float)),
+					CPConstValueRefKind::Long(long) => format!("long {long}l"),
+					CPConstValueRefKind::Double(double) => format!("double {}d", java_number(double)),
+					CPConstValueRefKind::String(string) => format!("String {}", class_pool::escape(&string)),
+				};
+				self.line(indent, format!("ConstantValue: {value}"));
+			}
+			IRAttribute::Exceptions { exception_index_table } => {
+				self.line(indent, "Exceptions:");
+				let thrown = exception_index_table
+					.iter()
+					.map(|class| names::internal_to_binary(&class.data.data))
+					.collect::<Vec<_>>();
+				self.line(indent + 2, format!("throws {}", thrown.join(", ")));
+			}
+			IRAttribute::Signature(signature) => {
+				self.commented(
+					indent,
+					format!("Signature: #{}", signature.index),
+					signature.data.as_str(),
+				);
+			}
+			IRAttribute::SourceFile(file) => self.line(indent, format!("SourceFile: \"{}\"", file.data)),
+			IRAttribute::Deprecated | IRAttribute::Synthetic => self.line(indent, format!("{name}: true")),
+			IRAttribute::LineNumberTable(table) => {
+				self.line(indent, "LineNumberTable:");
+				for entry in &table.line_number_table {
+					self.line(indent + 2, format!("line {}: {}", entry.line_number, entry.start_pc));
+				}
+			}
+			IRAttribute::LocalVariableTable { table } => {
+				let rows = table.iter().map(|entry| {
+					(
+						entry.start_pc,
+						entry.length,
+						entry.index,
+						&entry.name,
+						&entry.descriptor,
+					)
+				});
+				self.local_variables(indent, name, rows);
+			}
+			IRAttribute::LocalVariableTypeTable { table } => {
+				let rows = table
+					.iter()
+					.map(|entry| (entry.start_pc, entry.length, entry.index, &entry.name, &entry.signature));
+				self.local_variables(indent, name, rows);
+			}
+			IRAttribute::StackMapTable(table) => {
+				self.line(
+					indent,
+					format!("StackMapTable: number_of_entries = {}", table.entries.len()),
+				);
+				for frame in &table.entries {
+					self.frame(indent + 2, frame);
+				}
+			}
+			IRAttribute::InnerClasses(inner) => {
+				self.line(indent, "InnerClasses:");
+				for entry in &inner.classes {
+					let mut text = inner_class_modifiers(entry.inner_class_access_flags);
+					let mut comment = String::new();
+					if let Some(name) = &entry.inner_name {
+						let _ = write!(text, "#{}= ", name.index);
+						let _ = write!(comment, "{}=", name.data);
+					}
+					let _ = write!(text, "#{}", entry.inner_class_info.index);
+					let _ = write!(comment, "class {}", javap_name(&entry.inner_class_info.data.data));
+					if let Some(outer) = &entry.outer_class_info {
+						let _ = write!(text, " of #{}", outer.index);
+						let _ = write!(comment, " of class {}", javap_name(&outer.data.data));
+					}
+					text.push(';');
+					self.commented(indent + 2, text, comment);
+				}
+			}
+			IRAttribute::EnclosingMethod { class, method } => {
+				let mut comment = names::internal_to_binary(&class.data.data);
+				if let Some(method) = method {
+					let _ = write!(comment, ".{}", method.name.data);
+				}
+				let method = method.as_ref().map_or(0, |method| method.index);
+				self.commented(indent, format!("EnclosingMethod: #{}.#{method}", class.index), comment);
+			}
+			IRAttribute::NestHost(host) => {
+				self.line(indent, format!("NestHost: class {}", javap_name(&host.data.data)))
+			}
+			IRAttribute::NestMembers { classes } | IRAttribute::PermittedSubclasses { classes } => {
+				self.line(indent, format!("{name}:"));
+				for class in classes {
+					self.line(indent + 2, class.data.data.as_str());
+				}
+			}
+			IRAttribute::BootstrapMethods { methods } => {
+				self.line(indent, "BootstrapMethods:");
+				for (i, method) in methods.iter().enumerate() {
+					let handle = CPTagRef::from_cp(cp, method.method.index)?;
+					let resolved = handle.tag.resolved(cp).unwrap_or_default();
+					self.line(indent + 2, format!("{i}: #{} {resolved}", method.method.index));
+					self.line(indent + 4, "Method arguments:");
+					for argument in &method.arguments {
+						let value = constant_value(cp, &argument.tag).unwrap_or_default();
+						self.line(indent + 6, format!("#{} {value}", argument.index));
+					}
+				}
+			}
+			IRAttribute::RuntimeVisibleAnnotations { annotations }
+			| IRAttribute::RuntimeInvisibleAnnotations { annotations } => {
+				self.line(indent, format!("{name}:"));
+				for (i, annotation) in annotations.iter().enumerate() {
+					self.line(indent + 2, format!("{i}: {}", annotation_indices(annotation)));
+					self.annotation(indent + 4, annotation);
+				}
+			}
+			IRAttribute::AnnotationDefault { default_value } => {
+				self.line(indent, "AnnotationDefault:");
+				self.line(indent + 2, format!("default_value: {}", value_indices(default_value)));
+				self.line(indent + 4, annotation_value(default_value));
+			}
+			IRAttribute::Unknown(data) => self.line(
+				indent,
+				format!("{name}: length = 0x{:X} (unknown attribute)", data.len()),
+			),
+			_ => {
+				let length = info.to_io()?.attribute_length;
+				self.line(indent, format!("{name}: length = 0x{length:X}"));
+			}
+		}
+		Ok(())
+	}
+
+	fn local_variables<'r>(
+		&mut self,
+		indent: usize,
+		name: &str,
+		rows: impl Iterator<Item = (u16, u16, u16, &'r class_pool::CPUtf8Ref, &'r class_pool::CPUtf8Ref)>,
+	) {
+		self.line(indent, format!("{name}:"));
+		self.line(indent + 2, "Start  Length  Slot  Name   Signature");
+		for (start, length, slot, name, ty) in rows {
+			self.line(
+				indent + 2,
+				format!("{start:5} {length:7} {slot:5} {:>5}   {}", name.data, ty.data),
+			);
+		}
+	}
+
+	fn frame(&mut self, indent: usize, frame: &StackMapFrame) {
+		let (frame_type, kind, offset_delta, locals, stack) = match frame {
+			StackMapFrame::SameFrame { frame_type, .. } => (frame_type, "same", None, None, None),
+			StackMapFrame::SameLocals1StackItemFrame { frame_type, stack, .. } => (
+				frame_type,
+				"same_locals_1_stack_item",
+				None,
+				None,
+				Some(std::slice::from_ref(stack)),
+			),
+			StackMapFrame::SameLocals1StackItemFrameExtended {
+				frame_type,
+				offset_delta,
+				stack,
+			} => (
+				frame_type,
+				"same_locals_1_stack_item_frame_extended",
+				Some(offset_delta),
+				None,
+				Some(std::slice::from_ref(stack)),
+			),
+			StackMapFrame::ChopFrame {
+				frame_type,
+				offset_delta,
+			} => (frame_type, "chop", Some(offset_delta), None, None),
+			StackMapFrame::SameFrameExtended {
+				frame_type,
+				offset_delta,
+			} => (frame_type, "same_frame_extended", Some(offset_delta), None, None),
+			StackMapFrame::AppendFrame {
+				frame_type,
+				offset_delta,
+				locals,
+			} => (frame_type, "append", Some(offset_delta), Some(&locals[..]), None),
+			StackMapFrame::FullFrame {
+				frame_type,
+				offset_delta,
+				locals,
+				stack,
+			} => (
+				frame_type,
+				"full_frame",
+				Some(offset_delta),
+				Some(&locals[..]),
+				Some(&stack[..]),
+			),
+		};
+		self.line(indent, format!("frame_type = {frame_type} /* {kind} */"));
+		if let Some(offset_delta) = offset_delta {
+			self.line(indent + 2, format!("offset_delta = {offset_delta}"));
+		}
+		for (name, types) in [("locals", locals), ("stack", stack)] {
+			if let Some(types) = types {
+				let types = types.iter().map(|ty| self.verification_type(ty)).collect::<Vec<_>>();
+				match types.is_empty() {
+					true => self.line(indent + 2, format!("{name} = []")),
+					false => self.line(indent + 2, format!("{name} = [ {} ]", types.join(", "))),
+				}
+			}
+		}
+	}
+
+	fn verification_type(&self, ty: &VerificationTypeInfo) -> String {
+		match ty {
+			VerificationTypeInfo::TopVariableInfo => "top".to_string(),
+			VerificationTypeInfo::IntegerVariableInfo => "int".to_string(),
+			VerificationTypeInfo::FloatVariableInfo => "float".to_string(),
+			VerificationTypeInfo::LongVariableInfo => "long".to_string(),
+			VerificationTypeInfo::DoubleVariableInfo => "double".to_string(),
+			VerificationTypeInfo::NullVariableInfo => "null".to_string(),
+			VerificationTypeInfo::UninitializedThisVariableInfo => "this".to_string(),
+			VerificationTypeInfo::ObjectVariableInfo { cpool_idx } => CPClassRef::from_cp(&self.class.cp, *cpool_idx)
+				.map_or_else(
+					|_| format!("#{cpool_idx}"),
+					|class| format!("class {}", javap_name(&class.data.data)),
+				),
+			VerificationTypeInfo::UninitializedVariableInfo { offset } => format!("uninitialized {offset}"),
+		}
+	}
+
+	fn annotation(&mut self, indent: usize, annotation: &RuntimeAnnotation) {
+		let ty = names::descriptor_to_source(&annotation.ty.data).unwrap_or_else(|| annotation.ty.data.to_string());
+		if annotation.pairs.is_empty() {
+			self.line(indent, ty);
+			return;
+		}
+		self.line(indent, format!("{ty}("));
+		for pair in &annotation.pairs {
+			self.line(
+				indent + 2,
+				format!("{}={}", pair.name.data, annotation_value(&pair.value)),
+			);
+		}
+		self.line(indent, ")");
+	}
+}
+
+fn flags_line<'a>(bits: u16, names: impl Iterator<Item = (&'a str, impl Sized)>) -> String {
+	let names = names.map(|(name, _)| format!("ACC_{name}")).collect::<Vec<_>>();
+	match names.is_empty() {
+		true => format!("flags: (0x{bits:04x})"),
+		false => format!("flags: (0x{bits:04x}) {}", names.join(", ")),
+	}
+}
+
+fn modifiers(names: &[(bool, &str)]) -> String {
+	names
+		.iter()
+		.filter(|(set, _)| *set)
+		.map(|(_, name)| format!("{name} "))
+		.collect()
+}
+
+fn class_declaration(class: &IRClassFile) -> String {
+	let flags = class.access_flags;
+	let interface = flags.contains(ClassAccessFlags::INTERFACE);
+	let mut declaration = modifiers(&[
+		(flags.contains(ClassAccessFlags::PUBLIC), "public"),
+		(flags.contains(ClassAccessFlags::FINAL), "final"),
+		(flags.contains(ClassAccessFlags::ABSTRACT) && !interface, "abstract"),
+	]);
+	let kind = if interface { "interface" } else { "class" };
+	let _ = write!(declaration, "{kind} {}", names::internal_to_binary(class.class_name()));
+	let signature = class
+		.attributes
+		.signature()
+		.and_then(|sig| ClassSignature::parse(sig).ok());
+	// Without a signature, javap leaves out extending Object, and separates interfaces differently.
+	let (super_name, interfaces, separator) = match &signature {
+		Some(sig) => {
+			declaration.push_str(&type_params(&sig.type_params));
+			(
+				Some(sig.superclass.to_java()),
+				sig.interfaces.iter().map(ClassTypeSignature::to_java).collect(),
+				", ",
+			)
+		}
+		None => (
+			class
+				.super_name()
+				.filter(|name| *name != "java/lang/Object")
+				.map(names::internal_to_binary),
+			class
+				.interface_names()
+				.map(names::internal_to_binary)
+				.collect::<Vec<_>>(),
+			",",
+		),
+	};
+	if let Some(super_name) = super_name.filter(|_| !interface) {
+		let _ = write!(declaration, " extends {super_name}");
+	}
+	if !interfaces.is_empty() {
+		let keyword = if interface { "extends" } else { "implements" };
+		let _ = write!(declaration, " {keyword} {}", interfaces.join(separator));
+	}
+	declaration
+}
+
+// Unlike `signature::type_params_to_java`, javap keeps every bound, `<T extends java.lang.Object>`.
+fn type_params(params: &[TypeParameter]) -> String {
+	if params.is_empty() {
+		return String::new();
+	}
+	let params = params
+		.iter()
+		.map(|param| {
+			let bounds = param
+				.class_bound
+				.iter()
+				.chain(&param.interface_bounds)
+				.map(ReferenceTypeSignature::to_java)
+				.collect::<Vec<_>>();
+			format!("{} extends {}", param.name, bounds.join(" & "))
+		})
+		.collect::<Vec<_>>();
+	format!("<{}>", params.join(", "))
+}
+
+fn field_modifiers(flags: FieldAccessFlags) -> String {
+	modifiers(&[
+		(flags.contains(FieldAccessFlags::PUBLIC), "public"),
+		(flags.contains(FieldAccessFlags::PRIVATE), "private"),
+		(flags.contains(FieldAccessFlags::PROTECTED), "protected"),
+		(flags.contains(FieldAccessFlags::STATIC), "static"),
+		(flags.contains(FieldAccessFlags::FINAL), "final"),
+		(flags.contains(FieldAccessFlags::VOLATILE), "volatile"),
+		(flags.contains(FieldAccessFlags::TRANSIENT), "transient"),
+	])
+}
+
+fn method_modifiers(class: &IRClassFile, method: &IRMethodInfo) -> String {
+	let flags = method.access_flags;
+	// javap calls out the methods of an interface that have a body and are inherited.
+	let default = class.access_flags.contains(ClassAccessFlags::INTERFACE)
+		&& !flags.intersects(MethodAccessFlags::ABSTRACT | MethodAccessFlags::STATIC | MethodAccessFlags::PRIVATE);
+	modifiers(&[
+		(flags.contains(MethodAccessFlags::PUBLIC), "public"),
+		(flags.contains(MethodAccessFlags::PRIVATE), "private"),
+		(flags.contains(MethodAccessFlags::PROTECTED), "protected"),
+		(flags.contains(MethodAccessFlags::STATIC), "static"),
+		(flags.contains(MethodAccessFlags::FINAL), "final"),
+		(flags.contains(MethodAccessFlags::SYNCHRONIZED), "synchronized"),
+		(flags.contains(MethodAccessFlags::NATIVE), "native"),
+		(flags.contains(MethodAccessFlags::ABSTRACT), "abstract"),
+		(flags.contains(MethodAccessFlags::STRICT), "strictfp"),
+		(default, "default"),
+	])
+}
+
+fn inner_class_modifiers(flags: ClassAccessFlags) -> String {
+	let interface = flags.contains(ClassAccessFlags::INTERFACE);
+	modifiers(&[
+		(flags.contains(ClassAccessFlags::PUBLIC), "public"),
+		(flags.contains(ClassAccessFlags::PRIVATE), "private"),
+		(flags.contains(ClassAccessFlags::PROTECTED), "protected"),
+		(flags.contains(ClassAccessFlags::STATIC), "static"),
+		(flags.contains(ClassAccessFlags::FINAL), "final"),
+		(flags.contains(ClassAccessFlags::ABSTRACT) && !interface, "abstract"),
+	])
+}
+
+// A loadable constant the way javap comments on it, e.g. `int 42` or `String hi`.
+pub(crate) fn constant(cp: &[IRCpTag], tag: &IRCpTag) -> Option<String> {
+	let kind = match tag {
+		IRCpTag::Integer(_) => "int",
+		IRCpTag::Float(_) => "float",
+		IRCpTag::Long(_) => "long",
+		IRCpTag::Double(_) => "double",
+		IRCpTag::Class(_) => "class",
+		tag => tag.kind_name(),
+	};
+	Some(format!("{kind} {}", constant_value(cp, tag)?))
+}
+
+fn constant_value(cp: &[IRCpTag], tag: &IRCpTag) -> Option<String> {
+	match tag {
+		IRCpTag::Integer(value) => Some(value.to_string()),
+		IRCpTag::Float(value) => Some(format!("{}f", java_number(*value))),
+		IRCpTag::Long(value) => Some(format!("{value}l")),
+		IRCpTag::Double(value) => Some(format!("{}d", java_number(*value))),
+		tag => tag.resolved(cp),
+	}
+}
+
+pub(crate) fn array_type(atype: u8) -> &'static str {
+	match atype {
+		4 => "boolean",
+		5 => "char",
+		6 => "float",
+		7 => "double",
+		8 => "byte",
+		9 => "short",
+		10 => "int",
+		11 => "long",
+		_ => "???",
+	}
+}
+
+// The first line javap prints for an annotation, with pool indices only: `#24(#25=s#26)`.
+fn annotation_indices(annotation: &RuntimeAnnotation) -> String {
+	let pairs = annotation
+		.pairs
+		.iter()
+		.map(|pair| format!("#{}={}", pair.name.index, value_indices(&pair.value)))
+		.collect::<Vec<_>>();
+	format!("#{}({})", annotation.ty.index, pairs.join(","))
+}
+
+fn value_indices(value: &RuntimeAnnotationValue) -> String {
+	match value {
+		RuntimeAnnotationValue::ConstValueIndex { tag, value } => format!("{}#{}", *tag as char, value.index),
+		RuntimeAnnotationValue::EnumConstValue { type_name, const_name } => {
+			format!("e#{}.#{}", type_name.index, const_name.index)
+		}
+		RuntimeAnnotationValue::ClassInfoIndex(class) => format!("c#{}", class.index),
+		RuntimeAnnotationValue::Annotation(annotation) => format!("@{}", annotation_indices(annotation)),
+		RuntimeAnnotationValue::ArrayValue { values } => {
+			format!("[{}]", values.iter().map(value_indices).collect::<Vec<_>>().join(","))
+		}
+	}
+}
+
+fn annotation_value(value: &RuntimeAnnotationValue) -> String {
+	match value {
+		RuntimeAnnotationValue::ConstValueIndex { tag, value } => match (tag, &value.kind) {
+			(b's', CPConstValueRefKind::String(string)) => format!("\"{}\"", class_pool::escape(string)),
+			(b'Z', CPConstValueRefKind::Int(int)) => (*int != 0).to_string(),
+			(b'C', CPConstValueRefKind::Int(int)) => {
+				format!(
+					"'{}'",
+					char::from_u32(*int as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+				)
+			}
+			(_, CPConstValueRefKind::Int(int)) => int.to_string(),
+			(_, CPConstValueRefKind::Long(long)) => format!("{long}l"),
+			(_, CPConstValueRefKind::Float(float)) => format!("{}f", java_number(*float)),
+			(_, CPConstValueRefKind::Double(double)) => format!("{}d", java_number(*double)),
+			(_, CPConstValueRefKind::String(string)) => string.to_string(),
+		},
+		// Types stay descriptors, `Ljava/lang/annotation/RetentionPolicy;.RUNTIME`.
+		RuntimeAnnotationValue::EnumConstValue { type_name, const_name } => {
+			format!("{}.{}", type_name.data, const_name.data)
+		}
+		RuntimeAnnotationValue::ClassInfoIndex(class) => format!("class {}", class.data),
+		RuntimeAnnotationValue::Annotation(annotation) => {
+			let ty = names::descriptor_to_source(&annotation.ty.data).unwrap_or_else(|| annotation.ty.data.to_string());
+			let pairs = annotation
+				.pairs
+				.iter()
+				.map(|pair| format!("{}={}", pair.name.data, annotation_value(&pair.value)))
+				.collect::<Vec<_>>();
+			format!("@{ty}({})", pairs.join(","))
+		}
+		RuntimeAnnotationValue::ArrayValue { values } => {
+			format!(
+				"[{}]",
+				values.iter().map(annotation_value).collect::<Vec<_>>().join(",")
+			)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		builder::{ClassBuilder, CodeBuilder},
+		class_pool::{CPFieldRef, CPMethodRef, CPUtf8Ref},
+		flags::FieldAccessFlags,
+	};
+
+	#[test]
+	fn javap_layout() {
+		let mut builder = ClassBuilder::new("p/Counter").unwrap();
+		let count = CPFieldRef::find_or_add(builder.cp(), "p/Counter", "count", "I").unwrap();
+		let parse =
+			CPMethodRef::find_or_add(builder.cp(), "java/lang/Integer", "parseInt", "(Ljava/lang/String;)I").unwrap();
+		let text = CPUtf8Ref::find_or_add(builder.cp(), "it's").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		let (start, one, other, handler) = (code.new_label(), code.new_label(), code.new_label(), code.new_label());
+		code.max_stack(1)
+			.max_locals(1)
+			.bind(start)
+			.unwrap()
+			.insn(Instructions::ILOAD(0));
+		code.table_switch(1, other, vec![one]).bind(one).unwrap();
+		code.insn(Instructions::GETSTATIC(count))
+			.insn(Instructions::IRETURN)
+			.bind(other)
+			.unwrap();
+		code.insn(Instructions::LDC(IRCpTag::String(text)))
+			.insn(Instructions::INVOKESTATIC(parse))
+			.insn(Instructions::IRETURN)
+			.bind(handler)
+			.unwrap()
+			.insn(Instructions::POP)
+			.insn(Instructions::ICONST_M1)
+			.insn(Instructions::IRETURN)
+			.try_catch(start, other, handler, Some("java/lang/RuntimeException"))
+			.unwrap();
+		let code = code.build().unwrap();
+		builder
+			.field(FieldAccessFlags::STATIC, "count", "I", [])
+			.unwrap()
+			.method(MethodAccessFlags::STATIC, "pick", "(J)I", [IRAttribute::Code(code)])
+			.unwrap();
+		let class = builder.build();
+
+		let listing = disassemble(&class).unwrap();
+		assert!(listing.starts_with("public class p.Counter\n  minor version: 0\n"));
+		assert!(listing.contains("  #18 = String             #15            // it\\'s\n"));
+		let method = &listing[listing.find("  static int pick").unwrap()..];
+		assert_eq!(
+			method,
+			r"  static int pick(long);
+    descriptor: (J)I
+    flags: (0x0008) ACC_STATIC
+    Code:
+      stack=1, locals=1, args_size=1
+         0: iload_0
+         1: tableswitch   { // 1 to 1
+                       1: 20
+                 default: 24
+            }
+        20: getstatic     #8                  // Field count:I
+        23: ireturn
+        24: ldc           #18                 // String it\'s
+        26: invokestatic  #14                 // Method java/lang/Integer.parseInt:(Ljava/lang/String;)I
+        29: ireturn
+        30: pop
+        31: iconst_m1
+        32: ireturn
+      Exception table:
+         from    to  target type
+             0    24    30   Class java/lang/RuntimeException
+}
+"
+		);
+	}
+}
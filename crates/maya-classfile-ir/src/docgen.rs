@@ -0,0 +1,710 @@
+// API reference generation for classes without sources. Only what a caller can see is rendered: public classes and
+// their public and protected members, with generic signatures and annotations but without any code.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+	attribute::{
+		Attributes, ConstantValueAttribute, DecodedAnnotation, DecodedAnnotationValue, NestedKind, RuntimeAnnotation,
+	},
+	descriptor::MethodDescriptor,
+	flags::ClassAccessFlags,
+	names,
+	signature::{type_params_to_java, ClassSignature, JavaTypeSignature, MethodSignature, ReferenceTypeSignature},
+	IRClassFile, IRFieldInfo, IRMethodInfo,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+	Markdown,
+	Html,
+}
+
+struct ClassDoc {
+	// binary simple name, nested classes are spelled Outer.Inner
+	name: String,
+	kind: &'static str,
+	annotations: Vec<String>,
+	declaration: String,
+	deprecated: bool,
+	fields: Vec<MemberDoc>,
+	constructors: Vec<MemberDoc>,
+	methods: Vec<MemberDoc>,
+}
+
+struct MemberDoc {
+	annotations: Vec<String>,
+	declaration: String,
+	deprecated: bool,
+}
+
+/// Renders the public API of `classes`, grouped by package and sorted by name.
+pub fn render(classes: &[IRClassFile], format: DocFormat) -> String {
+	let mut packages: BTreeMap<String, Vec<ClassDoc>> = BTreeMap::new();
+	for class in classes {
+		if let Some(doc) = document_class(class) {
+			let package = names::internal_to_binary(names::package_name(class.class_name()));
+			packages.entry(package).or_default().push(doc);
+		}
+	}
+	for docs in packages.values_mut() {
+		docs.sort_by(|a, b| a.name.cmp(&b.name));
+	}
+
+	match format {
+		DocFormat::Markdown => render_markdown(&packages),
+		DocFormat::Html => render_html(&packages),
+	}
+}
+
+// The flags as declared in source. For nested classes only the InnerClasses entry has the real visibility and `static`.
+fn declared_flags(class: &IRClassFile) -> Option<ClassAccessFlags> {
+	// Anonymous and local classes aren't part of any API.
+	match class.nested_kind() {
+		Some(NestedKind::Local | NestedKind::Anonymous) => None,
+		_ => Some(class.declared_access_flags()),
+	}
+}
+
+fn document_class(class: &IRClassFile) -> Option<ClassDoc> {
+	if class.is_module_info() || class.is_package_info() {
+		return None;
+	}
+	let flags = declared_flags(class)?;
+	if !(flags.is_public() || flags.is_protected()) || flags.is_synthetic() {
+		return None;
+	}
+
+	let name = names::simple_name(class.class_name()).replace('$', ".");
+	let kind = if flags.is_annotation() {
+		"@interface"
+	} else if flags.is_interface() {
+		"interface"
+	} else if flags.is_enum() {
+		"enum"
+	} else if class.super_name() == Some("java/lang/Record") {
+		"record"
+	} else {
+		"class"
+	};
+
+	let mut modifiers = visibility(flags.is_public(), flags.is_protected());
+	if flags.is_static() && !flags.is_interface() && kind != "enum" && kind != "record" {
+		modifiers.push("static");
+	}
+	if flags.is_abstract() && !flags.is_interface() {
+		modifiers.push("abstract");
+	}
+	if flags.is_final() && kind == "class" {
+		modifiers.push("final");
+	}
+
+	let mut declaration = format!("{} {kind} {name}", modifiers.join(" "));
+	let signature = class
+		.attributes
+		.signature()
+		.and_then(|sig| ClassSignature::parse(sig).ok());
+	let (superclass, interfaces) = match &signature {
+		Some(sig) => {
+			declaration.push_str(&type_params_to_java(&sig.type_params));
+			(
+				sig.superclass.to_java(),
+				sig.interfaces.iter().map(|interface| interface.to_java()).collect(),
+			)
+		}
+		None => (
+			class.super_name().map(names::internal_to_binary).unwrap_or_default(),
+			class
+				.interface_names()
+				.map(names::internal_to_binary)
+				.collect::<Vec<_>>(),
+		),
+	};
+
+	// Supertypes the source can't or doesn't spell out.
+	let implied = [
+		"java.lang.Object",
+		"java.lang.Record",
+		"java.lang.annotation.Annotation",
+	];
+	let is_enum_super = superclass.starts_with("java.lang.Enum<");
+	if kind == "class" && !implied.contains(&superclass.as_str()) && !is_enum_super {
+		write!(declaration, " extends {superclass}").unwrap();
+	}
+	let interfaces: Vec<&String> = interfaces
+		.iter()
+		.filter(|interface| !implied.contains(&interface.as_str()))
+		.collect();
+	if !interfaces.is_empty() {
+		let keyword = if flags.is_interface() { "extends" } else { "implements" };
+		let interfaces: Vec<&str> = interfaces.iter().map(|interface| interface.as_str()).collect();
+		write!(declaration, " {keyword} {}", interfaces.join(", ")).unwrap();
+	}
+
+	let mut doc = ClassDoc {
+		name: name.clone(),
+		kind,
+		annotations: annotations_of(class.attributes()),
+		declaration,
+		deprecated: is_deprecated(class.attributes()),
+		fields: Vec::new(),
+		constructors: Vec::new(),
+		methods: Vec::new(),
+	};
+
+	for field in class.fields() {
+		if let Some(member) = document_field(field) {
+			doc.fields.push(member);
+		}
+	}
+	for method in class.methods() {
+		let Some(member) = document_method(method, &name, flags) else {
+			continue;
+		};
+		match method.name() {
+			"<init>" => doc.constructors.push(member),
+			_ => doc.methods.push(member),
+		}
+	}
+
+	Some(doc)
+}
+
+fn document_field(field: &IRFieldInfo) -> Option<MemberDoc> {
+	let flags = field.access_flags;
+	if !(flags.is_public() || flags.is_protected()) || field.is_synthetic() {
+		return None;
+	}
+
+	let mut modifiers = visibility(flags.is_public(), flags.is_protected());
+	if flags.is_static() {
+		modifiers.push("static");
+	}
+	if flags.is_final() {
+		modifiers.push("final");
+	}
+	if flags.is_volatile() {
+		modifiers.push("volatile");
+	}
+	if flags.is_transient() {
+		modifiers.push("transient");
+	}
+
+	let ty = field
+		.attributes
+		.signature()
+		.and_then(|sig| ReferenceTypeSignature::parse(sig).ok())
+		.map(|sig| sig.to_java())
+		.or_else(|| names::descriptor_to_source(field.descriptor()))
+		.unwrap_or_else(|| field.descriptor().to_string());
+
+	let mut declaration = format!("{} {ty} {}", modifiers.join(" "), field.name());
+	if let Some(constant) = field.attributes.constant_value() {
+		write!(declaration, " = {}", constant_to_java(constant, field.descriptor())).unwrap();
+	}
+
+	Some(MemberDoc {
+		annotations: annotations_of(&field.attributes),
+		declaration,
+		deprecated: is_deprecated(&field.attributes),
+	})
+}
+
+fn document_method(method: &IRMethodInfo, class_name: &str, class_flags: ClassAccessFlags) -> Option<MemberDoc> {
+	let flags = method.access_flags;
+	let hidden = method.is_synthetic() || method.is_bridge() || method.name() == "<clinit>";
+	if !(flags.is_public() || flags.is_protected()) || hidden {
+		return None;
+	}
+
+	let mut modifiers = visibility(flags.is_public(), flags.is_protected());
+	if flags.is_static() {
+		modifiers.push("static");
+	}
+	if class_flags.is_interface() {
+		if !flags.is_abstract() && !flags.is_static() {
+			modifiers.push("default");
+		}
+	} else if flags.is_abstract() {
+		modifiers.push("abstract");
+	}
+	if flags.is_final() {
+		modifiers.push("final");
+	}
+
+	let source = |descriptor: String| names::descriptor_to_source(&descriptor).unwrap_or(descriptor);
+	let descriptor = MethodDescriptor::parse(method.descriptor()).ok()?;
+	let signature = method
+		.attributes
+		.signature()
+		.and_then(|sig| MethodSignature::parse(sig).ok());
+	let (type_params, params, ret, throws) = match &signature {
+		Some(sig) => (
+			type_params_to_java(&sig.type_params),
+			sig.params.iter().map(JavaTypeSignature::to_java).collect(),
+			sig.ret.as_ref().map_or("void".to_string(), JavaTypeSignature::to_java),
+			sig.throws.iter().map(ReferenceTypeSignature::to_java).collect(),
+		),
+		None => (
+			String::new(),
+			descriptor
+				.params
+				.iter()
+				.map(|param| source(param.to_string()))
+				.collect(),
+			descriptor
+				.ret
+				.as_ref()
+				.map_or("void".to_string(), |ret| source(ret.to_string())),
+			Vec::new(),
+		),
+	};
+
+	Some(finish_method(
+		method,
+		modifiers,
+		type_params,
+		params,
+		ret,
+		throws,
+		class_name,
+	))
+}
+
+fn finish_method(
+	method: &IRMethodInfo,
+	modifiers: Vec<&str>,
+	type_params: String,
+	mut params: Vec<String>,
+	ret: String,
+	mut throws: Vec<String>,
+	class_name: &str,
+) -> MemberDoc {
+	if throws.is_empty() {
+		throws = method
+			.attributes
+			.exceptions()
+			.unwrap_or_default()
+			.iter()
+			.map(|class| names::internal_to_binary(&class.data.data))
+			.collect();
+	}
+
+	if method.access_flags.is_varargs() {
+		if let Some(last) = params.last_mut() {
+			if let Some(element) = last.strip_suffix("[]") {
+				*last = format!("{element}...");
+			}
+		}
+	}
+
+	let param_names: Vec<String> = method
+		.attributes
+		.method_parameters()
+		.filter(|parameters| parameters.len() == params.len())
+		.map(|parameters| {
+			parameters
+				.iter()
+				.enumerate()
+				.map(|(i, param)| {
+					param
+						.name
+						.as_ref()
+						.map_or(format!("arg{i}"), |name| name.data.to_string())
+				})
+				.collect()
+		})
+		.unwrap_or_else(|| (0..params.len()).map(|i| format!("arg{i}")).collect());
+	let params: Vec<String> = params
+		.iter()
+		.zip(&param_names)
+		.map(|(ty, name)| format!("{ty} {name}"))
+		.collect();
+
+	let mut declaration = modifiers.join(" ");
+	if !type_params.is_empty() {
+		write!(declaration, " {type_params}").unwrap();
+	}
+	match method.name() {
+		"<init>" => write!(declaration, " {class_name}").unwrap(),
+		name => write!(declaration, " {ret} {name}").unwrap(),
+	}
+	write!(declaration, "({})", params.join(", ")).unwrap();
+	if !throws.is_empty() {
+		write!(declaration, " throws {}", throws.join(", ")).unwrap();
+	}
+
+	MemberDoc {
+		annotations: annotations_of(&method.attributes),
+		declaration,
+		deprecated: is_deprecated(&method.attributes),
+	}
+}
+
+fn visibility(is_public: bool, is_protected: bool) -> Vec<&'static str> {
+	match (is_public, is_protected) {
+		(true, _) => vec!["public"],
+		(false, true) => vec!["protected"],
+		(false, false) => Vec::new(),
+	}
+}
+
+fn is_deprecated(attributes: &Attributes) -> bool {
+	attributes.is_deprecated() || attributes.has_annotation("Ljava/lang/Deprecated;")
+}
+
+fn annotations_of(attributes: &Attributes) -> Vec<String> {
+	attributes.annotations().map(annotation_to_java).collect()
+}
+
+fn annotation_to_java(annotation: &RuntimeAnnotation) -> String {
+	match annotation.resolve() {
+		Ok(annotation) => decoded_annotation_to_java(&annotation),
+		Err(_) => format!("@{}", type_to_java(&annotation.ty.data)),
+	}
+}
+
+fn type_to_java(descriptor: &str) -> String {
+	match descriptor {
+		"V" => "void".to_string(),
+		descriptor => names::descriptor_to_source(descriptor).unwrap_or_else(|| descriptor.to_string()),
+	}
+}
+
+fn decoded_annotation_to_java(annotation: &DecodedAnnotation) -> String {
+	let ty = type_to_java(&annotation.ty);
+	match annotation.elements.as_slice() {
+		[] => format!("@{ty}"),
+		[(name, value)] if name == "value" => format!("@{ty}({})", value_to_java(value)),
+		elements => {
+			let elements: Vec<String> = elements
+				.iter()
+				.map(|(name, value)| format!("{name} = {}", value_to_java(value)))
+				.collect();
+			format!("@{ty}({})", elements.join(", "))
+		}
+	}
+}
+
+fn value_to_java(value: &DecodedAnnotationValue) -> String {
+	match value {
+		DecodedAnnotationValue::Byte(value) => format!("(byte) {value}"),
+		DecodedAnnotationValue::Char(value) => {
+			char::from_u32(u32::from(*value)).map_or(value.to_string(), |c| format!("{c:?}"))
+		}
+		DecodedAnnotationValue::Short(value) => format!("(short) {value}"),
+		DecodedAnnotationValue::Boolean(value) => value.to_string(),
+		DecodedAnnotationValue::Int(value) => value.to_string(),
+		DecodedAnnotationValue::Long(value) => format!("{value}L"),
+		DecodedAnnotationValue::Float(value) => format!("{value}f"),
+		DecodedAnnotationValue::Double(value) => value.to_string(),
+		DecodedAnnotationValue::Str(value) => format!("{value:?}"),
+		DecodedAnnotationValue::Enum { ty, name } => format!("{}.{name}", type_to_java(ty)),
+		DecodedAnnotationValue::Class(class) => format!("{}.class", type_to_java(class)),
+		DecodedAnnotationValue::Nested(annotation) => decoded_annotation_to_java(annotation),
+		DecodedAnnotationValue::Array(values) => {
+			let values: Vec<String> = values.iter().map(value_to_java).collect();
+			format!("{{{}}}", values.join(", "))
+		}
+	}
+}
+
+fn constant_to_java(constant: &ConstantValueAttribute, descriptor: &str) -> String {
+	match constant {
+		ConstantValueAttribute::Int { value, .. } => match descriptor {
+			"Z" => (*value != 0).to_string(),
+			"C" => char::from_u32(*value as u32).map_or(value.to_string(), |c| format!("{c:?}")),
+			_ => value.to_string(),
+		},
+		ConstantValueAttribute::Long { value, .. } => format!("{value}L"),
+		ConstantValueAttribute::Float { value, .. } => format!("{value}f"),
+		ConstantValueAttribute::Double { value, .. } => value.to_string(),
+		ConstantValueAttribute::String { value, .. } => format!("{:?}", value.data),
+	}
+}
+
+fn render_markdown(packages: &BTreeMap<String, Vec<ClassDoc>>) -> String {
+	let mut out = String::from("# API reference\n");
+	for (package, classes) in packages {
+		let package = if package.is_empty() {
+			"(default package)"
+		} else {
+			package
+		};
+		write!(out, "\n## Package `{package}`\n").unwrap();
+
+		for class in classes {
+			write!(out, "\n### {} `{}`\n\n```java\n", class.kind, class.name).unwrap();
+			for annotation in &class.annotations {
+				writeln!(out, "{annotation}").unwrap();
+			}
+			write!(out, "{}\n```\n", class.declaration).unwrap();
+			if class.deprecated {
+				out.push_str("\n**Deprecated.**\n");
+			}
+
+			for (title, members) in [
+				("Fields", &class.fields),
+				("Constructors", &class.constructors),
+				("Methods", &class.methods),
+			] {
+				if members.is_empty() {
+					continue;
+				}
+
+				write!(out, "\n#### {title}\n\n").unwrap();
+				for member in members {
+					let mut line = member.annotations.join(" ");
+					if !line.is_empty() {
+						line.push(' ');
+					}
+					line.push_str(&member.declaration);
+					let deprecated = if member.deprecated { " *(deprecated)*" } else { "" };
+					writeln!(out, "- `{line}`{deprecated}").unwrap();
+				}
+			}
+		}
+	}
+	out
+}
+
+fn render_html(packages: &BTreeMap<String, Vec<ClassDoc>>) -> String {
+	let mut out = String::from(
+		"<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>API reference</title></head>\n<body>\n<h1>API reference</h1>\n",
+	);
+	for (package, classes) in packages {
+		let title = if package.is_empty() {
+			"(default package)"
+		} else {
+			package
+		};
+		writeln!(
+			out,
+			"<h2 id=\"{}\">Package <code>{}</code></h2>",
+			html_escape(&anchor(package, None)),
+			html_escape(title)
+		)
+		.unwrap();
+
+		for class in classes {
+			writeln!(
+				out,
+				"<h3 id=\"{}\">{} <code>{}</code></h3>",
+				html_escape(&anchor(package, Some(&class.name))),
+				html_escape(class.kind),
+				html_escape(&class.name)
+			)
+			.unwrap();
+			out.push_str("<pre><code>");
+			for annotation in &class.annotations {
+				writeln!(out, "{}", html_escape(annotation)).unwrap();
+			}
+			writeln!(out, "{}</code></pre>", html_escape(&class.declaration)).unwrap();
+			if class.deprecated {
+				out.push_str("<p><strong>Deprecated.</strong></p>\n");
+			}
+
+			for (title, members) in [
+				("Fields", &class.fields),
+				("Constructors", &class.constructors),
+				("Methods", &class.methods),
+			] {
+				if members.is_empty() {
+					continue;
+				}
+
+				writeln!(out, "<h4>{title}</h4>\n<ul>").unwrap();
+				for member in members {
+					let mut line = member.annotations.join(" ");
+					if !line.is_empty() {
+						line.push(' ');
+					}
+					line.push_str(&member.declaration);
+					let deprecated = if member.deprecated {
+						" <em>(deprecated)</em>"
+					} else {
+						""
+					};
+					writeln!(out, "<li><code>{}</code>{deprecated}</li>", html_escape(&line)).unwrap();
+				}
+				out.push_str("</ul>\n");
+			}
+		}
+	}
+	out.push_str("</body>\n</html>\n");
+	out
+}
+
+// Fragment ids for linking into the reference, `package-` and the package name for a package and the qualified name
+// for a class.
+fn anchor(package: &str, class: Option<&str>) -> String {
+	match class {
+		None if package.is_empty() => "default-package".to_string(),
+		None => format!("package-{package}"),
+		Some(class) if package.is_empty() => class.to_string(),
+		Some(class) => format!("{package}.{class}"),
+	}
+}
+
+fn html_escape(text: &str) -> String {
+	let mut out = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => out.push_str("&amp;"),
+			'<' => out.push_str("&lt;"),
+			'>' => out.push_str("&gt;"),
+			'"' => out.push_str("&quot;"),
+			_ => out.push(c),
+		}
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	fn classes() -> Vec<IRClassFile> {
+		let boxed = assemble(
+			r#"
+.class public super p/Box
+.super java/lang/Object
+.implements java/util/function/Supplier
+.signature "<T::Ljava/lang/Comparable<TT;>;>Ljava/lang/Object;Ljava/util/function/Supplier<TT;>;"
+.annotation visible Lp/Note;
+	value s "a & \"b\""
+.end annotation
+.field protected final value Ljava/lang/Object;
+	.signature "TT;"
+.end field
+.field private secret I
+.method public <init>(Ljava/lang/Object;)V
+	.signature "(TT;)V"
+	.parameter final value
+	aload_0
+	invokespecial java/lang/Object/<init>()V
+	return
+.end method
+.method public get()Ljava/lang/Object;
+	.signature "()TT;"
+	aconst_null
+	areturn
+.end method
+.method public static of(Ljava/util/List;)Ljava/util/Map;
+	.signature "<K:Ljava/lang/Object;>(Ljava/util/List<+TK;>;)Ljava/util/Map<TK;Ljava/lang/Integer;>;"
+	.deprecated
+	aconst_null
+	areturn
+.end method
+.method private hidden()V
+	return
+.end method
+"#,
+		)
+		.unwrap();
+		let hidden = assemble(
+			r#"
+.class super p/Internal
+.super java/lang/Object
+"#,
+		)
+		.unwrap();
+		let entry = assemble(
+			r#"
+.class public super p/Box$Entry
+.super java/lang/Object
+.inner public static p/Box$Entry p/Box Entry
+"#,
+		)
+		.unwrap();
+		let top = assemble(
+			r#"
+.class public interface abstract Top
+.super java/lang/Object
+"#,
+		)
+		.unwrap();
+		vec![hidden, entry, boxed, top]
+	}
+
+	// Private members and package-private classes are left out, generics are spelled as in source.
+	#[test]
+	fn markdown() {
+		let expected = r#"# API reference
+
+## Package `(default package)`
+
+### interface `Top`
+
+```java
+public interface Top
+```
+
+## Package `p`
+
+### class `Box`
+
+```java
+@p.Note("a & \"b\"")
+public class Box<T extends java.lang.Comparable<T>> implements java.util.function.Supplier<T>
+```
+
+#### Fields
+
+- `protected final T value`
+
+#### Constructors
+
+- `public Box(T value)`
+
+#### Methods
+
+- `public T get()`
+- `public static <K> java.util.Map<K, java.lang.Integer> of(java.util.List<? extends K> arg0)` *(deprecated)*
+
+### class `Box.Entry`
+
+```java
+public static class Box.Entry
+```
+"#;
+		assert_eq!(render(&classes(), DocFormat::Markdown), expected);
+	}
+
+	// The same with everything escaped, and ids on the package and class headings.
+	#[test]
+	fn html() {
+		let expected = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>API reference</title></head>
+<body>
+<h1>API reference</h1>
+<h2 id="default-package">Package <code>(default package)</code></h2>
+<h3 id="Top">interface <code>Top</code></h3>
+<pre><code>public interface Top</code></pre>
+<h2 id="package-p">Package <code>p</code></h2>
+<h3 id="p.Box">class <code>Box</code></h3>
+<pre><code>@p.Note(&quot;a &amp; \&quot;b\&quot;&quot;)
+public class Box&lt;T extends java.lang.Comparable&lt;T&gt;&gt; implements java.util.function.Supplier&lt;T&gt;</code></pre>
+<h4>Fields</h4>
+<ul>
+<li><code>protected final T value</code></li>
+</ul>
+<h4>Constructors</h4>
+<ul>
+<li><code>public Box(T value)</code></li>
+</ul>
+<h4>Methods</h4>
+<ul>
+<li><code>public T get()</code></li>
+<li><code>public static &lt;K&gt; java.util.Map&lt;K, java.lang.Integer&gt; of(java.util.List&lt;? extends K&gt; arg0)</code> <em>(deprecated)</em></li>
+</ul>
+<h3 id="p.Box.Entry">class <code>Box.Entry</code></h3>
+<pre><code>public static class Box.Entry</code></pre>
+</body>
+</html>
+"#;
+		assert_eq!(render(&classes(), DocFormat::Html), expected);
+	}
+}
@@ -0,0 +1,166 @@
+// The exception table of a Code attribute as regions of bytecode and the handlers guarding them. When an instruction
+// throws, the JVM tries the entries covering it in table order and jumps to the first whose catch type the exception
+// is an instance of, so the order of the table is the precedence of the handlers. javac emits a nested try's handlers
+// before the enclosing one's and a finally block as a catch of any type after the catches it follows.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-2.html#jvms-2.10
+
+use crate::{
+	attribute::{CodeAttribute, CodeAttributeException},
+	class_pool::{CPClassRef, IRClassfileError, IRCpTag},
+};
+
+#[derive(Debug, Clone)]
+pub enum CatchType {
+	/// A catch type of 0, any exception at all, as `finally` and `synchronized` blocks are compiled.
+	Any,
+	Class(CPClassRef),
+}
+
+impl CatchType {
+	/// The internal name of the class caught, `any` for [`CatchType::Any`] as javap prints it.
+	pub fn name(&self) -> &str {
+		match self {
+			Self::Any => "any",
+			Self::Class(class) => &class.data.data,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct ExceptionRegion {
+	/// The first covered offset.
+	pub start: u32,
+	/// The offset after the last covered one.
+	pub end: u32,
+	pub handler: u32,
+	pub catch_type: CatchType,
+}
+
+impl ExceptionRegion {
+	pub fn covers(&self, pc: u32) -> bool {
+		(self.start..self.end).contains(&pc)
+	}
+
+	pub fn catches_any(&self) -> bool {
+		matches!(self.catch_type, CatchType::Any)
+	}
+}
+
+/// The exception table of a method, in table order.
+#[derive(Debug, Clone, Default)]
+pub struct ExceptionRegions {
+	pub regions: Vec<ExceptionRegion>,
+}
+
+impl ExceptionRegions {
+	pub fn new(cp: &[IRCpTag], exception_table: &[CodeAttributeException]) -> Result<Self, IRClassfileError> {
+		let regions = exception_table
+			.iter()
+			.map(|entry| {
+				Ok(ExceptionRegion {
+					start: entry.start_pc as u32,
+					end: entry.end_pc as u32,
+					handler: entry.handler_pc as u32,
+					catch_type: match entry.catch_type {
+						0 => CatchType::Any,
+						index => CatchType::Class(CPClassRef::from_cp(cp, index)?),
+					},
+				})
+			})
+			.collect::<Result<_, IRClassfileError>>()?;
+		Ok(Self { regions })
+	}
+
+	/// The handlers an exception thrown at `pc` may go to, the one tried first first. Handlers after one that
+	/// catches any exception are never reached and left out.
+	pub fn handlers_at(&self, pc: u32) -> impl Iterator<Item = &ExceptionRegion> {
+		let mut done = false;
+		self.regions
+			.iter()
+			.filter(move |region| region.covers(pc))
+			.take_while(move |region| !std::mem::replace(&mut done, region.catches_any()))
+	}
+
+	/// The handler an exception of class `exception` thrown at `pc` goes to. `is_subclass(a, b)` tells whether `a` is
+	/// `b` or extends it.
+	pub fn handler_for<F>(&self, pc: u32, exception: &str, mut is_subclass: F) -> Option<&ExceptionRegion>
+	where
+		F: FnMut(&str, &str) -> bool,
+	{
+		self.handlers_at(pc).find(|region| match &region.catch_type {
+			CatchType::Any => true,
+			CatchType::Class(class) => is_subclass(exception, &class.data.data),
+		})
+	}
+
+	/// The offsets where handlers start, each once.
+	pub fn handler_offsets(&self) -> impl Iterator<Item = u32> + '_ {
+		let mut seen = Vec::new();
+		self.regions.iter().filter_map(move |region| {
+			if seen.contains(&region.handler) {
+				return None;
+			}
+			seen.push(region.handler);
+			Some(region.handler)
+		})
+	}
+}
+
+impl CodeAttribute {
+	pub fn exception_regions(&self, cp: &[IRCpTag]) -> Result<ExceptionRegions, IRClassfileError> {
+		ExceptionRegions::new(cp, &self.exception_table)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn handlers_by_precedence() {
+		let mut cp = Vec::new();
+		let io = CPClassRef::find_or_add(&mut cp, "java/io/IOException").unwrap().index;
+		let exception = CPClassRef::find_or_add(&mut cp, "java/lang/Exception").unwrap().index;
+		let entry = |start_pc, end_pc, handler_pc, catch_type| CodeAttributeException {
+			start_pc,
+			end_pc,
+			handler_pc,
+			catch_type,
+		};
+		// try { try { 0..10 } catch (IOException) { 10..15 } 0..20 } catch (Exception) { 20..25 } finally { 30 }
+		let table = [
+			entry(0, 10, 10, io),
+			entry(0, 20, 20, exception),
+			entry(0, 20, 30, 0),
+			entry(20, 25, 30, 0),
+			entry(0, 40, 50, exception),
+		];
+		let regions = ExceptionRegions::new(&cp, &table).unwrap();
+
+		let handlers = |pc| {
+			regions
+				.handlers_at(pc)
+				.map(|region| (region.handler, region.catch_type.name()))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(
+			handlers(5),
+			[(10, "java/io/IOException"), (20, "java/lang/Exception"), (30, "any")]
+		);
+		assert_eq!(handlers(22), [(30, "any")]);
+		assert_eq!(handlers(35), [(50, "java/lang/Exception")]);
+		assert!(handlers(45).is_empty());
+
+		let is_subclass = |a: &str, b: &str| a == b || (a == "java/io/IOException" && b == "java/lang/Exception");
+		let handler = |pc, exception| {
+			regions
+				.handler_for(pc, exception, is_subclass)
+				.map(|region| region.handler)
+		};
+		assert_eq!(handler(5, "java/io/IOException"), Some(10));
+		assert_eq!(handler(15, "java/io/IOException"), Some(20));
+		assert_eq!(handler(15, "java/lang/Error"), Some(30));
+		assert_eq!(handler(35, "java/lang/Error"), None);
+		assert_eq!(regions.handler_offsets().collect::<Vec<_>>(), [10, 20, 30, 50]);
+	}
+}
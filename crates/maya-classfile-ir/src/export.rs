@@ -0,0 +1,429 @@
+// A resolved view of a class for tools outside Rust: names instead of pool indices, flags as their names and
+// annotation values decoded, serialized as JSON. Unlike the IR's own serde representation it can't be read back into a
+// class, it leaves out what scripts have no use for (the constant pool, frames, raw attributes) in favor of being
+// readable. Class names are internal (`java/lang/String`) and types are descriptors, as in the class file.
+
+use bitflags::Flags;
+use serde::Serialize;
+
+use crate::{
+	attribute::{Attributes, CodeAttribute, DecodedAnnotation, DecodedAnnotationValue, IRAttribute},
+	class_pool::{CPClassRef, CPConstValueRefKind, IRClassfileError},
+	flags::ParameterAccessFlags,
+	IRClassFile, IRFieldInfo, IRMethodInfo,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassExport {
+	pub name: String,
+	/// e.g. `52.0 (Java 8)`
+	pub version: String,
+	pub access: Vec<String>,
+	pub super_class: Option<String>,
+	pub interfaces: Vec<String>,
+	pub source_file: Option<String>,
+	pub signature: Option<String>,
+	pub deprecated: bool,
+	pub annotations: Vec<AnnotationExport>,
+	pub inner_classes: Vec<InnerClassExport>,
+	pub nest_host: Option<String>,
+	pub nest_members: Vec<String>,
+	pub permitted_subclasses: Vec<String>,
+	pub record_components: Vec<RecordComponentExport>,
+	pub fields: Vec<FieldExport>,
+	pub methods: Vec<MethodExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldExport {
+	pub name: String,
+	pub descriptor: String,
+	pub access: Vec<String>,
+	pub signature: Option<String>,
+	pub constant_value: Option<ValueExport>,
+	pub deprecated: bool,
+	pub annotations: Vec<AnnotationExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MethodExport {
+	pub name: String,
+	pub descriptor: String,
+	pub access: Vec<String>,
+	pub signature: Option<String>,
+	pub exceptions: Vec<String>,
+	pub parameters: Vec<ParameterExport>,
+	pub deprecated: bool,
+	pub annotations: Vec<AnnotationExport>,
+	/// The default of an annotation interface's element.
+	pub annotation_default: Option<ValueExport>,
+	pub code: Option<CodeExport>,
+}
+
+/// A parameter from MethodParameters or parameter annotations, whichever there are.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParameterExport {
+	pub name: Option<String>,
+	pub access: Vec<String>,
+	pub annotations: Vec<AnnotationExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CodeExport {
+	pub max_stack: u16,
+	pub max_locals: u16,
+	pub code_length: usize,
+	pub handlers: Vec<HandlerExport>,
+	/// `(start_pc, line)` pairs from the LineNumberTable.
+	pub lines: Vec<(u16, u16)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HandlerExport {
+	pub start_pc: u16,
+	pub end_pc: u16,
+	pub handler_pc: u16,
+	/// `None` for a handler catching everything, as `finally` compiles to.
+	pub catch_type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InnerClassExport {
+	pub name: String,
+	pub outer: Option<String>,
+	pub simple_name: Option<String>,
+	pub access: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordComponentExport {
+	pub name: String,
+	pub descriptor: String,
+	pub signature: Option<String>,
+	pub annotations: Vec<AnnotationExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnnotationExport {
+	/// The descriptor of the annotation interface.
+	#[serde(rename = "type")]
+	pub ty: String,
+	pub visible: bool,
+	pub elements: Vec<ElementExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ElementExport {
+	pub name: String,
+	pub value: ValueExport,
+}
+
+/// A constant or annotation element value, as `{"type": "int", "value": 5}` so the Java type isn't lost.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum ValueExport {
+	Byte(i8),
+	Char(u16),
+	Short(i16),
+	Boolean(bool),
+	Int(i32),
+	Long(i64),
+	// NaN and infinities come out as null in JSON
+	Float(f32),
+	Double(f64),
+	String(String),
+	Enum { class: String, name: String },
+	Class(String),
+	Annotation(Box<AnnotationExport>),
+	Array(Vec<ValueExport>),
+}
+
+impl IRClassFile {
+	/// The resolved view of the class, failing on annotations whose values don't match their pool entries.
+	pub fn export(&self) -> Result<ClassExport, IRClassfileError> {
+		let attributes = &self.attributes;
+		let inner_classes = attributes
+			.inner_classes()
+			.map(|inner| &inner.classes[..])
+			.unwrap_or_default();
+		Ok(ClassExport {
+			name: self.class_name().to_string(),
+			version: self.version.to_string(),
+			access: flag_names(self.access_flags),
+			super_class: self.super_name().map(str::to_string),
+			interfaces: self.interface_names().map(str::to_string).collect(),
+			source_file: attributes.source_file().map(str::to_string),
+			signature: attributes.signature().map(str::to_string),
+			deprecated: attributes.is_deprecated(),
+			annotations: annotations(attributes)?,
+			inner_classes: inner_classes
+				.iter()
+				.map(|inner| InnerClassExport {
+					name: inner.class_name().to_string(),
+					outer: inner.outer_name().map(str::to_string),
+					simple_name: inner.simple_name().map(str::to_string),
+					access: flag_names(inner.inner_class_access_flags),
+				})
+				.collect(),
+			nest_host: attributes.nest_host().map(class_name),
+			nest_members: attributes
+				.nest_members()
+				.unwrap_or_default()
+				.iter()
+				.map(class_name)
+				.collect(),
+			permitted_subclasses: attributes
+				.permitted_subclasses()
+				.unwrap_or_default()
+				.iter()
+				.map(class_name)
+				.collect(),
+			record_components: attributes
+				.record_components()
+				.unwrap_or_default()
+				.iter()
+				.map(|component| {
+					Ok(RecordComponentExport {
+						name: component.name.data.to_string(),
+						descriptor: component.descriptor.data.to_string(),
+						signature: component.attributes.signature().map(str::to_string),
+						annotations: annotations(&component.attributes)?,
+					})
+				})
+				.collect::<Result<_, IRClassfileError>>()?,
+			fields: self.fields.iter().map(export_field).collect::<Result<_, _>>()?,
+			methods: self
+				.methods
+				.iter()
+				.map(|method| self.export_method(method))
+				.collect::<Result<_, _>>()?,
+		})
+	}
+
+	/// [`IRClassFile::export`] as pretty-printed JSON.
+	pub fn to_json(&self) -> Result<String, IRClassfileError> {
+		let export = self.export()?;
+		Ok(serde_json::to_string_pretty(&export).expect("the export only has JSON-compatible types"))
+	}
+
+	fn export_method(&self, method: &IRMethodInfo) -> Result<MethodExport, IRClassfileError> {
+		let attributes = &method.attributes;
+		Ok(MethodExport {
+			name: method.name().to_string(),
+			descriptor: method.descriptor().to_string(),
+			access: flag_names(method.access_flags),
+			signature: attributes.signature().map(str::to_string),
+			exceptions: attributes
+				.exceptions()
+				.unwrap_or_default()
+				.iter()
+				.map(class_name)
+				.collect(),
+			parameters: parameters(attributes)?,
+			deprecated: attributes.is_deprecated(),
+			annotations: annotations(attributes)?,
+			annotation_default: match attributes.annotation_default() {
+				// the JVM reads defaults whenever it reads the annotation interface
+				Some(value) => Some(ValueExport::new(value.resolve()?, true)),
+				None => None,
+			},
+			code: attributes.code().map(|code| self.export_code(code)),
+		})
+	}
+
+	fn export_code(&self, code: &CodeAttribute) -> CodeExport {
+		let lines = code
+			.attributes
+			.line_number_table()
+			.map(|table| &table.line_number_table[..]);
+		CodeExport {
+			max_stack: code.max_stack,
+			max_locals: code.max_locals,
+			code_length: code.code.len(),
+			handlers: code
+				.exception_table
+				.iter()
+				.map(|handler| HandlerExport {
+					start_pc: handler.start_pc,
+					end_pc: handler.end_pc,
+					handler_pc: handler.handler_pc,
+					catch_type: match handler.catch_type {
+						0 => None,
+						index => CPClassRef::from_cp(&self.cp, index).ok().as_ref().map(class_name),
+					},
+				})
+				.collect(),
+			lines: lines
+				.unwrap_or_default()
+				.iter()
+				.map(|entry| (entry.start_pc, entry.line_number))
+				.collect(),
+		}
+	}
+}
+
+fn export_field(field: &IRFieldInfo) -> Result<FieldExport, IRClassfileError> {
+	let attributes = &field.attributes;
+	Ok(FieldExport {
+		name: field.name().to_string(),
+		descriptor: field.descriptor().to_string(),
+		access: flag_names(field.access_flags),
+		signature: attributes.signature().map(str::to_string),
+		constant_value: field.constant_value().map(|value| match value {
+			CPConstValueRefKind::Int(value) => ValueExport::Int(value),
+			CPConstValueRefKind::Long(value) => ValueExport::Long(value),
+			CPConstValueRefKind::Float(value) => ValueExport::Float(value),
+			CPConstValueRefKind::Double(value) => ValueExport::Double(value),
+			CPConstValueRefKind::String(value) => ValueExport::String(value.to_string()),
+		}),
+		deprecated: attributes.is_deprecated(),
+		annotations: annotations(attributes)?,
+	})
+}
+
+fn annotations(attributes: &Attributes) -> Result<Vec<AnnotationExport>, IRClassfileError> {
+	let visible = attributes.runtime_visible_annotations().unwrap_or_default();
+	let invisible = attributes.runtime_invisible_annotations().unwrap_or_default();
+	let visible = visible.iter().map(|annotation| (true, annotation));
+	let invisible = invisible.iter().map(|annotation| (false, annotation));
+	visible
+		.chain(invisible)
+		.map(|(visible, annotation)| Ok(AnnotationExport::new(annotation.resolve()?, visible)))
+		.collect()
+}
+
+fn parameters(attributes: &Attributes) -> Result<Vec<ParameterExport>, IRClassfileError> {
+	let declared = attributes.method_parameters().unwrap_or_default();
+	let (mut visible, mut invisible) = (&[][..], &[][..]);
+	for attr in attributes.iter() {
+		match &attr.attr {
+			IRAttribute::RuntimeVisibleParameterAnnotations { params } => visible = params,
+			IRAttribute::RuntimeInvisibleParameterAnnotations { params } => invisible = params,
+			_ => {}
+		}
+	}
+	let count = declared.len().max(visible.len()).max(invisible.len());
+	(0..count)
+		.map(|i| {
+			let param = declared.get(i);
+			let visible = visible.get(i).map(|annotations| (true, annotations));
+			let invisible = invisible.get(i).map(|annotations| (false, annotations));
+			let annotations = visible
+				.into_iter()
+				.chain(invisible)
+				.flat_map(|(visible, annotations)| annotations.iter().map(move |annotation| (visible, annotation)))
+				.map(|(visible, annotation)| Ok(AnnotationExport::new(annotation.resolve()?, visible)))
+				.collect::<Result<_, IRClassfileError>>()?;
+			Ok(ParameterExport {
+				name: param
+					.and_then(|param| param.name.as_ref())
+					.map(|name| name.data.to_string()),
+				access: param.map_or_else(Vec::new, |param| flag_names::<ParameterAccessFlags>(param.access_flags)),
+				annotations,
+			})
+		})
+		.collect()
+}
+
+impl AnnotationExport {
+	fn new(annotation: DecodedAnnotation, visible: bool) -> Self {
+		Self {
+			ty: annotation.ty,
+			visible,
+			elements: annotation
+				.elements
+				.into_iter()
+				.map(|(name, value)| ElementExport {
+					name,
+					value: ValueExport::new(value, visible),
+				})
+				.collect(),
+		}
+	}
+}
+
+impl ValueExport {
+	// Nested annotations are as visible as the one they're in.
+	fn new(value: DecodedAnnotationValue, visible: bool) -> Self {
+		match value {
+			DecodedAnnotationValue::Byte(value) => Self::Byte(value),
+			DecodedAnnotationValue::Char(value) => Self::Char(value),
+			DecodedAnnotationValue::Short(value) => Self::Short(value),
+			DecodedAnnotationValue::Boolean(value) => Self::Boolean(value),
+			DecodedAnnotationValue::Int(value) => Self::Int(value),
+			DecodedAnnotationValue::Long(value) => Self::Long(value),
+			DecodedAnnotationValue::Float(value) => Self::Float(value),
+			DecodedAnnotationValue::Double(value) => Self::Double(value),
+			DecodedAnnotationValue::Str(value) => Self::String(value),
+			DecodedAnnotationValue::Enum { ty, name } => Self::Enum { class: ty, name },
+			DecodedAnnotationValue::Class(class) => Self::Class(class),
+			DecodedAnnotationValue::Nested(annotation) => {
+				Self::Annotation(Box::new(AnnotationExport::new(annotation, visible)))
+			}
+			DecodedAnnotationValue::Array(values) => {
+				Self::Array(values.into_iter().map(|value| Self::new(value, visible)).collect())
+			}
+		}
+	}
+}
+
+fn class_name(class: &CPClassRef) -> String {
+	class.data.data.to_string()
+}
+
+fn flag_names<F: Flags>(flags: F) -> Vec<String> {
+	flags.iter_names().map(|(name, _)| name.to_lowercase()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn export_class() {
+		let class = assemble(
+			r#"
+			.class public final super p/Export
+			.super java/lang/Object
+			.source "Export.java"
+			.annotation visible Lp/Marker;
+				level e Lp/Level; HIGH
+				names [ s "a" ]
+			.end annotation
+			.field public static final LIMIT I = 3
+			.method public run()V
+				.throws java/io/IOException
+				.parameter final count
+				.line 7
+			Start:
+				return
+			Handler:
+				athrow
+				.catch java/lang/Exception from Start to Handler using Handler
+			.end method
+			"#,
+		)
+		.unwrap();
+		let export = class.export().unwrap();
+
+		assert_eq!(export.access, ["public", "final", "super"]);
+		assert_eq!(export.source_file.as_deref(), Some("Export.java"));
+		assert_eq!(export.fields[0].constant_value, Some(ValueExport::Int(3)));
+		let method = &export.methods[0];
+		assert_eq!(method.exceptions, ["java/io/IOException"]);
+		assert_eq!(method.parameters[0].name.as_deref(), Some("count"));
+		let code = method.code.as_ref().unwrap();
+		assert_eq!(code.handlers[0].catch_type.as_deref(), Some("java/lang/Exception"));
+		assert_eq!(code.lines, [(0, 7)]);
+
+		let json = class.to_json().unwrap();
+		assert!(json.contains(r#""type": "Lp/Marker;""#));
+		assert!(json.contains(r#""type": "enum""#) && json.contains(r#""class": "Lp/Level;""#));
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(
+			value["annotations"][0]["elements"][1]["value"]["value"][0]["value"],
+			"a"
+		);
+	}
+}
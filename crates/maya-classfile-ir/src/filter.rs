@@ -0,0 +1,221 @@
+// Choosing which attributes make it into a written class file. Attributes are matched by the name they're stored
+// under, so attributes we don't recognize can be dropped or kept like any other, and the same filter applies to the
+// class, its fields, methods and record components, and the attributes nested in `Code`.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7
+
+use std::collections::BTreeSet;
+
+use crate::{
+	attribute::{Attributes, IRAttribute},
+	class_pool::IRClassfileError,
+	strip::StripOptions,
+	IRClassFile,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AttributeCategory {
+	/// `SourceFile`, `SourceDebugExtension`, `LineNumberTable`, `LocalVariableTable` and `LocalVariableTypeTable`,
+	/// and javac's `CharacterRangeTable`, `CompilationID` and `SourceID`.
+	Debug,
+	/// Declaration, parameter and type annotations, visible and invisible, and `AnnotationDefault`.
+	Annotations,
+	/// `Module`, `ModulePackages` and `ModuleMainClass`.
+	Module,
+}
+
+impl AttributeCategory {
+	/// The category of the attribute stored under `name`, `None` for the ones outside of any.
+	pub fn of(name: &str) -> Option<Self> {
+		match name {
+			"SourceFile"
+			| "SourceDebugExtension"
+			| "LineNumberTable"
+			| "LocalVariableTable"
+			| "LocalVariableTypeTable"
+			| "CharacterRangeTable"
+			| "CompilationID"
+			| "SourceID" => Some(Self::Debug),
+			"RuntimeVisibleAnnotations"
+			| "RuntimeInvisibleAnnotations"
+			| "RuntimeVisibleParameterAnnotations"
+			| "RuntimeInvisibleParameterAnnotations"
+			| "RuntimeVisibleTypeAnnotations"
+			| "RuntimeInvisibleTypeAnnotations"
+			| "AnnotationDefault" => Some(Self::Annotations),
+			"Module" | "ModulePackages" | "ModuleMainClass" => Some(Self::Module),
+			_ => None,
+		}
+	}
+}
+
+/// Keeps every attribute unless told otherwise. An attribute kept or dropped by name is, whatever its category.
+#[derive(Debug, Clone, Default)]
+pub struct AttributeFilter {
+	keep: BTreeSet<String>,
+	drop: BTreeSet<String>,
+	drop_categories: BTreeSet<AttributeCategory>,
+}
+
+impl AttributeFilter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn keep(mut self, name: &str) -> Self {
+		self.drop.remove(name);
+		self.keep.insert(name.to_string());
+		self
+	}
+
+	pub fn drop(mut self, name: &str) -> Self {
+		self.keep.remove(name);
+		self.drop.insert(name.to_string());
+		self
+	}
+
+	pub fn drop_category(mut self, category: AttributeCategory) -> Self {
+		self.drop_categories.insert(category);
+		self
+	}
+
+	pub fn keeps(&self, name: &str) -> bool {
+		if self.keep.contains(name) {
+			return true;
+		}
+		if self.drop.contains(name) {
+			return false;
+		}
+		AttributeCategory::of(name).is_none_or(|category| !self.drop_categories.contains(&category))
+	}
+
+	pub fn keeps_everything(&self) -> bool {
+		self.drop.is_empty() && self.drop_categories.is_empty()
+	}
+
+	/// Removes the attributes the filter doesn't keep from the class and everything in it.
+	pub fn apply(&self, class: &mut IRClassFile) {
+		self.retain(&mut class.attributes);
+		for field in &mut class.fields {
+			self.retain(&mut field.attributes);
+		}
+		for method in &mut class.methods {
+			self.retain(&mut method.attributes);
+		}
+	}
+
+	fn retain(&self, attributes: &mut Attributes) {
+		attributes.retain(|info| self.keeps(&info.name.data));
+		for info in attributes.iter_mut() {
+			match &mut info.attr {
+				IRAttribute::Code(code) => self.retain(&mut code.attributes),
+				IRAttribute::Record { components } => {
+					for component in components {
+						self.retain(&mut component.attributes);
+					}
+				}
+				_ => {}
+			}
+		}
+	}
+}
+
+impl From<StripOptions> for AttributeFilter {
+	fn from(options: StripOptions) -> Self {
+		[
+			(options.line_numbers, "LineNumberTable"),
+			(options.local_variables, "LocalVariableTable"),
+			(options.local_variable_types, "LocalVariableTypeTable"),
+			(options.source_file, "SourceFile"),
+			(options.source_debug_extension, "SourceDebugExtension"),
+		]
+		.into_iter()
+		.filter(|(drop, _)| *drop)
+		.fold(Self::new(), |filter, (_, name)| filter.drop(name))
+	}
+}
+
+impl IRClassFile {
+	/// Writes the class with only the attributes `filter` keeps, leaving `self` as it is.
+	pub fn to_bytes_filtered(&self, filter: &AttributeFilter) -> Result<Vec<u8>, IRClassfileError> {
+		if filter.keeps_everything() {
+			return self.to_bytes();
+		}
+		let mut class = self.clone();
+		filter.apply(&mut class);
+		class.to_bytes()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use smallvec::SmallVec;
+
+	use super::*;
+	use crate::{
+		attribute::{LineNumberTableAttribute, LineNumberTableAttributeEntry, RuntimeAnnotation},
+		builder::{ClassBuilder, CodeBuilder},
+		class_pool::CPUtf8Ref,
+		code::Instructions,
+		flags::{FieldAccessFlags, MethodAccessFlags},
+	};
+
+	#[test]
+	fn filter_by_name_and_category() {
+		let mut builder = ClassBuilder::new("p/C").unwrap();
+		let source_file = CPUtf8Ref::find_or_add(builder.cp(), "C.java").unwrap();
+		let annotation = RuntimeAnnotation {
+			ty: CPUtf8Ref::find_or_add(builder.cp(), "Lp/A;").unwrap(),
+			pairs: SmallVec::new(),
+		};
+		let mut code = CodeBuilder::new(builder.cp());
+		code.max_stack(0).max_locals(1).insn(Instructions::RETURN);
+		let mut code = code.build().unwrap();
+		code.attributes = [IRAttribute::LineNumberTable(LineNumberTableAttribute {
+			line_number_table: vec![LineNumberTableAttributeEntry {
+				start_pc: 0,
+				line_number: 3,
+			}],
+		})]
+		.into_iter()
+		.map(|attr| builder.attribute_info(attr))
+		.collect::<Result<_, _>>()
+		.unwrap();
+		builder
+			.method(MethodAccessFlags::PUBLIC, "run", "()V", [IRAttribute::Code(code)])
+			.unwrap()
+			.field(
+				FieldAccessFlags::PRIVATE,
+				"x",
+				"I",
+				[IRAttribute::RuntimeInvisibleAnnotations {
+					annotations: vec![annotation],
+				}],
+			)
+			.unwrap()
+			.attribute(IRAttribute::SourceFile(source_file))
+			.unwrap()
+			.attribute(IRAttribute::Deprecated)
+			.unwrap();
+		let class = builder.build();
+
+		let filter = AttributeFilter::new()
+			.drop_category(AttributeCategory::Debug)
+			.drop_category(AttributeCategory::Annotations)
+			.keep("SourceFile")
+			.drop("Deprecated");
+		let filtered = IRClassFile::read(&class.to_bytes_filtered(&filter).unwrap()).unwrap();
+		let names = |attributes: &Attributes| attributes.iter().map(|attr| attr.attr.name()).collect::<Vec<_>>();
+		assert_eq!(names(&filtered.attributes), ["SourceFile"]);
+		assert!(filtered.fields[0].attributes.is_empty());
+		assert!(filtered.methods[0].code().unwrap().attributes.is_empty());
+		assert_eq!(names(&class.attributes), ["SourceFile", "Deprecated"]);
+
+		let bytes = class.to_bytes().unwrap();
+		assert_eq!(class.to_bytes_filtered(&AttributeFilter::new()).unwrap(), bytes);
+		let stripped = crate::strip::strip_class(&bytes, StripOptions::default()).unwrap();
+		assert_eq!(
+			class.to_bytes_filtered(&StripOptions::default().into()).unwrap(),
+			stripped
+		);
+	}
+}
@@ -0,0 +1,262 @@
+// Access and property flags, one type per place they can appear since the same bit means different things
+// depending on where it's set (0x0020 is ACC_SUPER on a class but ACC_SYNCHRONIZED on a method).
+// Bits without a name are kept as is so classes round-trip unchanged.
+
+use bitflags::bitflags;
+
+macro_rules! flag_helpers {
+	($ty:ty { $($helper:ident => $flag:ident),* $(,)? }) => {
+		impl $ty {
+			$(
+				pub fn $helper(&self) -> bool {
+					self.contains(Self::$flag)
+				}
+			)*
+		}
+	};
+}
+
+bitflags! {
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.1-200-E.1
+	// PRIVATE, PROTECTED and STATIC are only valid on inner_class_access_flags.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+	#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+	pub struct ClassAccessFlags: u16 {
+		const PUBLIC = 0x0001;
+		const PRIVATE = 0x0002;
+		const PROTECTED = 0x0004;
+		const STATIC = 0x0008;
+		const FINAL = 0x0010;
+		const SUPER = 0x0020;
+		const INTERFACE = 0x0200;
+		const ABSTRACT = 0x0400;
+		const SYNTHETIC = 0x1000;
+		const ANNOTATION = 0x2000;
+		const ENUM = 0x4000;
+		const MODULE = 0x8000;
+		const _ = !0;
+	}
+
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.5-200-A.1
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+	#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+	pub struct FieldAccessFlags: u16 {
+		const PUBLIC = 0x0001;
+		const PRIVATE = 0x0002;
+		const PROTECTED = 0x0004;
+		const STATIC = 0x0008;
+		const FINAL = 0x0010;
+		const VOLATILE = 0x0040;
+		const TRANSIENT = 0x0080;
+		const SYNTHETIC = 0x1000;
+		const ENUM = 0x4000;
+		const _ = !0;
+	}
+
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.6-200-A.1
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+	#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+	pub struct MethodAccessFlags: u16 {
+		const PUBLIC = 0x0001;
+		const PRIVATE = 0x0002;
+		const PROTECTED = 0x0004;
+		const STATIC = 0x0008;
+		const FINAL = 0x0010;
+		const SYNCHRONIZED = 0x0020;
+		const BRIDGE = 0x0040;
+		const VARARGS = 0x0080;
+		const NATIVE = 0x0100;
+		const ABSTRACT = 0x0400;
+		const STRICT = 0x0800;
+		const SYNTHETIC = 0x1000;
+		const _ = !0;
+	}
+
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.24
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+	#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+	pub struct ParameterAccessFlags: u16 {
+		const FINAL = 0x0010;
+		const SYNTHETIC = 0x1000;
+		const MANDATED = 0x8000;
+		const _ = !0;
+	}
+
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.25
+	// Used for module_flags, exports_flags and opens_flags. OPEN is only valid on module_flags.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+	#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+	pub struct ModuleFlags: u16 {
+		const OPEN = 0x0020;
+		const SYNTHETIC = 0x1000;
+		const MANDATED = 0x8000;
+		const _ = !0;
+	}
+
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.25
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+	#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+	pub struct RequiresFlags: u16 {
+		const TRANSITIVE = 0x0020;
+		const STATIC_PHASE = 0x0040;
+		const SYNTHETIC = 0x1000;
+		const MANDATED = 0x8000;
+		const _ = !0;
+	}
+
+	// Not in the JVMS, javac's CRT_* constants for the CharacterRangeTable it emits with -Xjcov.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+	#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+	pub struct CharacterRangeFlags: u16 {
+		const STATEMENT = 0x0001;
+		const BLOCK = 0x0002;
+		const ASSIGNMENT = 0x0004;
+		const FLOW_CONTROLLER = 0x0008;
+		const FLOW_TARGET = 0x0010;
+		const INVOKE = 0x0020;
+		const CREATE = 0x0040;
+		const BRANCH_TRUE = 0x0080;
+		const BRANCH_FALSE = 0x0100;
+		const _ = !0;
+	}
+}
+
+flag_helpers!(ClassAccessFlags {
+	is_public => PUBLIC,
+	is_private => PRIVATE,
+	is_protected => PROTECTED,
+	is_static => STATIC,
+	is_final => FINAL,
+	is_super => SUPER,
+	is_interface => INTERFACE,
+	is_abstract => ABSTRACT,
+	is_synthetic => SYNTHETIC,
+	is_annotation => ANNOTATION,
+	is_enum => ENUM,
+	is_module => MODULE,
+});
+
+flag_helpers!(FieldAccessFlags {
+	is_public => PUBLIC,
+	is_private => PRIVATE,
+	is_protected => PROTECTED,
+	is_static => STATIC,
+	is_final => FINAL,
+	is_volatile => VOLATILE,
+	is_transient => TRANSIENT,
+	is_synthetic => SYNTHETIC,
+	is_enum => ENUM,
+});
+
+flag_helpers!(MethodAccessFlags {
+	is_public => PUBLIC,
+	is_private => PRIVATE,
+	is_protected => PROTECTED,
+	is_static => STATIC,
+	is_final => FINAL,
+	is_synchronized => SYNCHRONIZED,
+	is_bridge => BRIDGE,
+	is_varargs => VARARGS,
+	is_native => NATIVE,
+	is_abstract => ABSTRACT,
+	is_strict => STRICT,
+	is_synthetic => SYNTHETIC,
+});
+
+flag_helpers!(ParameterAccessFlags {
+	is_final => FINAL,
+	is_synthetic => SYNTHETIC,
+	is_mandated => MANDATED,
+});
+
+flag_helpers!(ModuleFlags {
+	is_open => OPEN,
+	is_synthetic => SYNTHETIC,
+	is_mandated => MANDATED,
+});
+
+flag_helpers!(RequiresFlags {
+	is_transitive => TRANSITIVE,
+	is_static_phase => STATIC_PHASE,
+	is_synthetic => SYNTHETIC,
+	is_mandated => MANDATED,
+});
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{asm::assemble, IRClassFile};
+
+	#[test]
+	fn bit_values() {
+		assert_eq!(ClassAccessFlags::PUBLIC.bits(), 0x0001);
+		assert_eq!(ClassAccessFlags::SUPER.bits(), 0x0020);
+		assert_eq!(ClassAccessFlags::INTERFACE.bits(), 0x0200);
+		assert_eq!(ClassAccessFlags::ANNOTATION.bits(), 0x2000);
+		assert_eq!(ClassAccessFlags::MODULE.bits(), 0x8000);
+		assert_eq!(FieldAccessFlags::VOLATILE.bits(), 0x0040);
+		assert_eq!(FieldAccessFlags::TRANSIENT.bits(), 0x0080);
+		assert_eq!(FieldAccessFlags::ENUM.bits(), 0x4000);
+		assert_eq!(MethodAccessFlags::SYNCHRONIZED.bits(), 0x0020);
+		assert_eq!(MethodAccessFlags::BRIDGE.bits(), 0x0040);
+		assert_eq!(MethodAccessFlags::VARARGS.bits(), 0x0080);
+		assert_eq!(MethodAccessFlags::NATIVE.bits(), 0x0100);
+		assert_eq!(MethodAccessFlags::STRICT.bits(), 0x0800);
+		assert_eq!(ParameterAccessFlags::MANDATED.bits(), 0x8000);
+		assert_eq!(ModuleFlags::OPEN.bits(), 0x0020);
+		assert_eq!(RequiresFlags::TRANSITIVE.bits(), 0x0020);
+		assert_eq!(RequiresFlags::STATIC_PHASE.bits(), 0x0040);
+		assert_eq!(CharacterRangeFlags::BRANCH_FALSE.bits(), 0x0100);
+	}
+
+	// The same bit is named after where it's set.
+	#[test]
+	fn overlapping_bits() {
+		let class = ClassAccessFlags::from_bits_retain(0x0020);
+		let method = MethodAccessFlags::from_bits_retain(0x0020);
+		assert!(class.is_super());
+		assert!(method.is_synchronized());
+		assert_eq!(format!("{class:?}"), "ClassAccessFlags(SUPER)");
+		assert_eq!(format!("{method:?}"), "MethodAccessFlags(SYNCHRONIZED)");
+
+		let field = FieldAccessFlags::from_bits_retain(0x0040);
+		let method = MethodAccessFlags::from_bits_retain(0x0040);
+		assert!(field.is_volatile() && !field.is_transient());
+		assert!(method.is_bridge() && !method.is_varargs());
+		assert_eq!(format!("{field:?}"), "FieldAccessFlags(VOLATILE)");
+		assert_eq!(format!("{method:?}"), "MethodAccessFlags(BRIDGE)");
+
+		let field = FieldAccessFlags::from_bits_retain(0x0080);
+		let method = MethodAccessFlags::from_bits_retain(0x0080);
+		assert!(field.is_transient() && !field.is_volatile());
+		assert!(method.is_varargs() && !method.is_bridge());
+		assert_eq!(format!("{field:?}"), "FieldAccessFlags(TRANSIENT)");
+		assert_eq!(format!("{method:?}"), "MethodAccessFlags(VARARGS)");
+	}
+
+	#[test]
+	fn unknown_bits_round_trip() {
+		let mut class = assemble(
+			r#"
+.class public super p/C
+.super java/lang/Object
+.field public x I
+.method public run()V
+	return
+.end method
+"#,
+		)
+		.unwrap();
+		// 0x0100 and 0x0800 have no name on a class or a field, 0x0200 none on a method.
+		class.access_flags = ClassAccessFlags::from_bits_retain(0x0921);
+		class.fields[0].access_flags = FieldAccessFlags::from_bits_retain(0x0901);
+		class.methods[0].access_flags = MethodAccessFlags::from_bits_retain(0x0201);
+		assert!(class.access_flags.is_public() && class.access_flags.is_super());
+		assert_eq!(class.access_flags.iter_names().count(), 2);
+
+		let read = IRClassFile::read(&class.to_bytes().unwrap()).unwrap();
+		assert_eq!(read.access_flags.bits(), 0x0921);
+		assert_eq!(read.fields[0].access_flags.bits(), 0x0901);
+		assert_eq!(read.methods[0].access_flags.bits(), 0x0201);
+	}
+}
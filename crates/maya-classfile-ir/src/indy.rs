@@ -0,0 +1,458 @@
+// What `invokedynamic` call sites do, worked out from their bootstrap method and its static arguments. javac links
+// lambdas and method references through `LambdaMetafactory`, which spins a class implementing the functional interface
+// that forwards to the implementation method, usually a synthetic `lambda$...` method in the same class. Since Java 9,
+// string concatenation goes through `StringConcatFactory`, with a recipe mixing the literal parts with the arguments.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5.invokedynamic
+// https://docs.oracle.com/en/java/javase/22/docs/api/java.base/java/lang/invoke/LambdaMetafactory.html
+// https://docs.oracle.com/en/java/javase/22/docs/api/java.base/java/lang/invoke/StringConcatFactory.html
+
+use bitflags::bitflags;
+
+use crate::{
+	attribute::BootstrapMethodsMethod,
+	class_pool::{
+		CPClassRef, CPInvokeDynamicRef, CPMethodHandleRef, CPTagRef, IRClassfileError, IRCpTag, IRMethodRefKind,
+	},
+	descriptor::{FieldType, MethodDescriptor},
+};
+
+const LAMBDA_METAFACTORY: &str = "java/lang/invoke/LambdaMetafactory";
+const STRING_CONCAT_FACTORY: &str = "java/lang/invoke/StringConcatFactory";
+
+// In a recipe, where the next argument and the next constant go.
+const TAG_ARG: char = '\u{1}';
+const TAG_CONST: char = '\u{2}';
+
+bitflags! {
+	// The `flags` argument of `altMetafactory`.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+	pub struct LambdaFlags: i32 {
+		const SERIALIZABLE = 1;
+		const MARKERS = 2;
+		const BRIDGES = 4;
+		const _ = !0;
+	}
+}
+
+/// The member a method handle constant refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodHandleTarget {
+	pub kind: IRMethodRefKind,
+	pub owner: String,
+	pub name: String,
+	pub descriptor: String,
+	/// Whether the member is referred to through an `InterfaceMethodref`.
+	pub interface: bool,
+}
+
+impl MethodHandleTarget {
+	pub fn resolve(cp: &[IRCpTag], handle: &CPMethodHandleRef) -> Result<Self, IRClassfileError> {
+		let (class_index, name_and_ty, interface) = match &*handle.ref_tag {
+			IRCpTag::FieldRef {
+				class_index,
+				name_and_ty,
+			}
+			| IRCpTag::MethodRef {
+				class_index,
+				name_and_ty,
+			} => (class_index, name_and_ty, false),
+			IRCpTag::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
+			} => (class_index, name_and_ty, true),
+			_ => {
+				return Err(IRClassfileError::UnexpectedCpTag {
+					index: handle.ref_index,
+					expected: "FieldRef or MethodRef",
+				})
+			}
+		};
+		Ok(Self {
+			kind: handle.ref_kind.clone(),
+			owner: CPClassRef::from_cp(cp, *class_index)?.data.data.to_string(),
+			name: name_and_ty.name.data.to_string(),
+			descriptor: name_and_ty.ty.data.to_string(),
+			interface,
+		})
+	}
+}
+
+/// A lambda or method reference, linked by `LambdaMetafactory.metafactory` or `altMetafactory`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LambdaInfo {
+	/// The name of the functional interface's abstract method, e.g. `apply`.
+	pub sam_name: String,
+	/// The internal name of the functional interface, e.g. `java/util/function/Function`.
+	pub sam_type: String,
+	/// The erased descriptor of the abstract method, e.g. `(Ljava/lang/Object;)Ljava/lang/Object;`.
+	pub sam_descriptor: String,
+	/// The values the lambda captures, passed to the call site and prepended to the arguments of the implementation.
+	pub captured: Vec<FieldType>,
+	pub impl_method_handle: MethodHandleTarget,
+	/// The descriptor the abstract method is specialized to, e.g. `(Ljava/lang/String;)Ljava/lang/Integer;`.
+	pub instantiated_type: String,
+	/// Empty for `metafactory`.
+	pub flags: LambdaFlags,
+	/// Further interfaces the lambda object implements, internal names.
+	pub markers: Vec<String>,
+	/// Further descriptors of the abstract method that forward to the implementation as well.
+	pub bridges: Vec<String>,
+}
+
+impl LambdaInfo {
+	/// `None` if the call site isn't bootstrapped by `LambdaMetafactory`.
+	pub fn decode(
+		cp: &[IRCpTag],
+		bootstrap_methods: &[BootstrapMethodsMethod],
+		indy: &CPInvokeDynamicRef,
+	) -> Result<Option<Self>, IRClassfileError> {
+		let (bootstrap, target) = bootstrap(cp, bootstrap_methods, indy)?;
+		if target.owner != LAMBDA_METAFACTORY || !matches!(target.name.as_str(), "metafactory" | "altMetafactory") {
+			return Ok(None);
+		}
+
+		let invalid = |reason| IRClassfileError::InvalidBootstrapArguments {
+			bootstrap: format!("{}.{}", target.owner, target.name),
+			reason,
+		};
+		let factory = MethodDescriptor::parse(&indy.name_and_ty.ty.data)?;
+		let Some(FieldType::Object(sam_type)) = factory.ret else {
+			return Err(invalid("the call site doesn't return an interface"));
+		};
+		let mut args = bootstrap.arguments.iter();
+		let method_type = |args: &mut std::slice::Iter<CPTagRef>| match args.next().map(|arg| &arg.tag) {
+			Some(IRCpTag::MethodType(descriptor)) => Ok(descriptor.data.to_string()),
+			_ => Err(invalid("expected a MethodType")),
+		};
+		let sam_descriptor = method_type(&mut args)?;
+		let impl_method_handle = match args.next().map(|arg| &arg.tag) {
+			Some(tag @ IRCpTag::MethodHandle { .. }) => {
+				let index = bootstrap.arguments[1].index;
+				MethodHandleTarget::resolve(cp, &CPMethodHandleRef::new(index, tag)?)?
+			}
+			_ => return Err(invalid("expected a MethodHandle")),
+		};
+		let instantiated_type = method_type(&mut args)?;
+
+		let mut info = Self {
+			sam_name: indy.name_and_ty.name.data.to_string(),
+			sam_type,
+			sam_descriptor,
+			captured: factory.params,
+			impl_method_handle,
+			instantiated_type,
+			flags: LambdaFlags::empty(),
+			markers: Vec::new(),
+			bridges: Vec::new(),
+		};
+		if target.name == "metafactory" {
+			return Ok(Some(info));
+		}
+
+		let int = |args: &mut std::slice::Iter<CPTagRef>| match args.next().map(|arg| &arg.tag) {
+			Some(IRCpTag::Integer(value)) => Ok(*value),
+			_ => Err(invalid("expected an Integer")),
+		};
+		info.flags = LambdaFlags::from_bits_retain(int(&mut args)?);
+		if info.flags.contains(LambdaFlags::MARKERS) {
+			for _ in 0..int(&mut args)? {
+				match args.next().map(|arg| &arg.tag) {
+					Some(IRCpTag::Class(name)) => info.markers.push(name.data.to_string()),
+					_ => return Err(invalid("expected a Class")),
+				}
+			}
+		}
+		if info.flags.contains(LambdaFlags::BRIDGES) {
+			for _ in 0..int(&mut args)? {
+				info.bridges.push(method_type(&mut args)?);
+			}
+		}
+		Ok(Some(info))
+	}
+
+	pub fn is_serializable(&self) -> bool {
+		self.flags.contains(LambdaFlags::SERIALIZABLE)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConcatPart {
+	Literal(String),
+	/// Index into the call site's arguments.
+	Argument(usize),
+	/// Index into the recipe's constants.
+	Constant(usize),
+}
+
+/// A string concatenation, linked by `StringConcatFactory.makeConcatWithConstants` or `makeConcat`.
+#[derive(Debug, Clone)]
+pub struct StringConcatInfo {
+	/// `\u{1}` stands for the next argument and `\u{2}` for the next constant. `makeConcat` has no recipe, it's made
+	/// up of one `\u{1}` per argument.
+	pub recipe: String,
+	/// The static arguments after the recipe, any loadable constant.
+	pub constants: Vec<CPTagRef>,
+	/// The types of the values passed to the call site.
+	pub arguments: Vec<FieldType>,
+}
+
+impl StringConcatInfo {
+	/// `None` if the call site isn't bootstrapped by `StringConcatFactory`.
+	pub fn decode(
+		cp: &[IRCpTag],
+		bootstrap_methods: &[BootstrapMethodsMethod],
+		indy: &CPInvokeDynamicRef,
+	) -> Result<Option<Self>, IRClassfileError> {
+		let (bootstrap, target) = bootstrap(cp, bootstrap_methods, indy)?;
+		if target.owner != STRING_CONCAT_FACTORY {
+			return Ok(None);
+		}
+
+		let invalid = |reason| IRClassfileError::InvalidBootstrapArguments {
+			bootstrap: format!("{}.{}", target.owner, target.name),
+			reason,
+		};
+		let arguments = MethodDescriptor::parse(&indy.name_and_ty.ty.data)?.params;
+		let (recipe, constants) = match target.name.as_str() {
+			"makeConcat" => (TAG_ARG.to_string().repeat(arguments.len()), Vec::new()),
+			"makeConcatWithConstants" => match bootstrap.arguments.split_first() {
+				Some((
+					CPTagRef {
+						tag: IRCpTag::String(recipe),
+						..
+					},
+					constants,
+				)) => (recipe.data.to_string(), constants.to_vec()),
+				_ => return Err(invalid("expected a String recipe")),
+			},
+			_ => return Ok(None),
+		};
+		if recipe.matches(TAG_ARG).count() != arguments.len() {
+			return Err(invalid("the recipe doesn't take as many arguments as the call site"));
+		}
+		if recipe.matches(TAG_CONST).count() != constants.len() {
+			return Err(invalid("the recipe doesn't take as many constants as there are"));
+		}
+		Ok(Some(Self {
+			recipe,
+			constants,
+			arguments,
+		}))
+	}
+
+	/// The recipe split into its literal parts and where the arguments and constants go, in order.
+	pub fn parts(&self) -> Vec<ConcatPart> {
+		let (mut parts, mut literal, mut argument, mut constant) = (Vec::new(), String::new(), 0, 0);
+		for c in self.recipe.chars() {
+			let part = match c {
+				TAG_ARG => ConcatPart::Argument(argument),
+				TAG_CONST => ConcatPart::Constant(constant),
+				c => {
+					literal.push(c);
+					continue;
+				}
+			};
+			if !literal.is_empty() {
+				parts.push(ConcatPart::Literal(std::mem::take(&mut literal)));
+			}
+			match part {
+				ConcatPart::Argument(_) => argument += 1,
+				_ => constant += 1,
+			}
+			parts.push(part);
+		}
+		if !literal.is_empty() {
+			parts.push(ConcatPart::Literal(literal));
+		}
+		parts
+	}
+}
+
+/// The entry of the BootstrapMethods attribute that links the call site, its bootstrap method and static arguments.
+pub fn bootstrap_method<'a>(
+	bootstrap_methods: &'a [BootstrapMethodsMethod],
+	indy: &CPInvokeDynamicRef,
+) -> Result<&'a BootstrapMethodsMethod, IRClassfileError> {
+	bootstrap_methods
+		.get(indy.bootstrap_method_attr_index as usize)
+		.ok_or(IRClassfileError::InvalidBootstrapIndex(
+			indy.bootstrap_method_attr_index,
+		))
+}
+
+fn bootstrap<'a>(
+	cp: &[IRCpTag],
+	bootstrap_methods: &'a [BootstrapMethodsMethod],
+	indy: &CPInvokeDynamicRef,
+) -> Result<(&'a BootstrapMethodsMethod, MethodHandleTarget), IRClassfileError> {
+	let bootstrap = bootstrap_method(bootstrap_methods, indy)?;
+	Ok((bootstrap, MethodHandleTarget::resolve(cp, &bootstrap.method)?))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		attribute::IRAttribute,
+		builder::ClassBuilder,
+		class_pool::{cp_find_or_add, CPMethodRef, CPNameAndTypeRef, CPUtf8Ref},
+	};
+
+	fn handle(cp: &mut Vec<IRCpTag>, ref_kind: IRMethodRefKind, index: u16) -> CPTagRef {
+		let ref_tag = Box::new(cp[index as usize - 1].clone());
+		CPTagRef::find_or_add(
+			cp,
+			IRCpTag::MethodHandle {
+				ref_kind,
+				ref_index: index,
+				ref_tag,
+			},
+		)
+		.unwrap()
+	}
+
+	fn method_type(cp: &mut Vec<IRCpTag>, descriptor: &str) -> CPTagRef {
+		let descriptor = CPUtf8Ref::find_or_add(cp, descriptor).unwrap();
+		CPTagRef::find_or_add(cp, IRCpTag::MethodType(descriptor)).unwrap()
+	}
+
+	#[test]
+	fn decode_metafactory_and_alt_metafactory() {
+		let mut builder = ClassBuilder::new("p/C").unwrap();
+		let cp = builder.cp();
+		let bootstrap = |cp: &mut Vec<IRCpTag>, name: &str| {
+			let descriptor = "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;\
+			                  [Ljava/lang/Object;)Ljava/lang/invoke/CallSite;";
+			let method = CPMethodRef::find_or_add(cp, LAMBDA_METAFACTORY, name, descriptor).unwrap();
+			let handle = handle(cp, IRMethodRefKind::InvokeStatic, method.index);
+			CPMethodHandleRef::from_cp(cp, handle.index).unwrap()
+		};
+		let lambda = CPMethodRef::find_or_add(cp, "p/C", "lambda$run$0", "(Ljava/lang/String;I)Ljava/lang/Integer;")
+			.unwrap()
+			.index;
+		let lambda = handle(cp, IRMethodRefKind::InvokeStatic, lambda);
+		let sam = method_type(cp, "(Ljava/lang/Object;)Ljava/lang/Object;");
+		let instantiated = method_type(cp, "(I)Ljava/lang/Integer;");
+		let bridge = method_type(cp, "(Ljava/lang/Integer;)Ljava/lang/Object;");
+		let marker = CPUtf8Ref::find_or_add(cp, "p/Marker").unwrap();
+		let marker = CPTagRef::find_or_add(cp, IRCpTag::Class(marker)).unwrap();
+		let other = CPMethodRef::find_or_add(cp, "p/C", "bootstrap", "()V").unwrap().index;
+		let other = handle(cp, IRMethodRefKind::InvokeStatic, other);
+		let methods = vec![
+			BootstrapMethodsMethod {
+				method: bootstrap(cp, "metafactory"),
+				arguments: vec![sam.clone(), lambda.clone(), instantiated.clone()],
+			},
+			BootstrapMethodsMethod {
+				method: bootstrap(cp, "altMetafactory"),
+				arguments: vec![
+					sam,
+					lambda,
+					instantiated,
+					CPTagRef::find_or_add(cp, IRCpTag::Integer(7)).unwrap(),
+					CPTagRef::find_or_add(cp, IRCpTag::Integer(1)).unwrap(),
+					marker,
+					CPTagRef::find_or_add(cp, IRCpTag::Integer(1)).unwrap(),
+					bridge,
+				],
+			},
+			BootstrapMethodsMethod {
+				method: CPMethodHandleRef::from_cp(cp, other.index).unwrap(),
+				arguments: Vec::new(),
+			},
+		];
+		let indy = |cp: &mut Vec<IRCpTag>, bootstrap_method_attr_index| {
+			let name_and_ty = CPNameAndTypeRef::find_or_add(cp, "apply", "(Ljava/lang/String;)Lp/F;").unwrap();
+			let tag = IRCpTag::InvokeDynamic {
+				bootstrap_method_attr_index,
+				name_and_ty,
+			};
+			let index = cp_find_or_add(cp, tag).unwrap();
+			CPInvokeDynamicRef::from_cp(cp, index).unwrap()
+		};
+		let (plain, alt, other) = (indy(cp, 0), indy(cp, 1), indy(cp, 2));
+		let cp = &builder.build().cp;
+
+		let info = LambdaInfo::decode(cp, &methods, &plain).unwrap().unwrap();
+		assert_eq!((info.sam_name.as_str(), info.sam_type.as_str()), ("apply", "p/F"));
+		assert_eq!(info.sam_descriptor, "(Ljava/lang/Object;)Ljava/lang/Object;");
+		assert_eq!(info.instantiated_type, "(I)Ljava/lang/Integer;");
+		assert_eq!(info.captured, [FieldType::Object("java/lang/String".to_string())]);
+		assert_eq!(
+			info.impl_method_handle,
+			MethodHandleTarget {
+				kind: IRMethodRefKind::InvokeStatic,
+				owner: "p/C".to_string(),
+				name: "lambda$run$0".to_string(),
+				descriptor: "(Ljava/lang/String;I)Ljava/lang/Integer;".to_string(),
+				interface: false,
+			}
+		);
+		assert!(info.flags.is_empty());
+
+		let info = LambdaInfo::decode(cp, &methods, &alt).unwrap().unwrap();
+		assert!(info.is_serializable());
+		assert_eq!(info.markers, ["p/Marker"]);
+		assert_eq!(info.bridges, ["(Ljava/lang/Integer;)Ljava/lang/Object;"]);
+
+		assert!(LambdaInfo::decode(cp, &methods, &other).unwrap().is_none());
+		assert!(LambdaInfo::decode(cp, &methods[..1], &alt).is_err());
+	}
+
+	#[test]
+	fn decode_string_concat() {
+		let mut builder = ClassBuilder::new("p/C").unwrap();
+		let cp = builder.cp();
+		let factory = CPMethodRef::find_or_add(cp, STRING_CONCAT_FACTORY, "makeConcatWithConstants", "()V")
+			.unwrap()
+			.index;
+		let factory = handle(cp, IRMethodRefKind::InvokeStatic, factory);
+		let recipe = CPUtf8Ref::find_or_add(cp, "x = \u{1}, \u{2}\u{1}!").unwrap();
+		let recipe = CPTagRef::find_or_add(cp, IRCpTag::String(recipe)).unwrap();
+		let constant = CPTagRef::find_or_add(cp, IRCpTag::Integer(7)).unwrap();
+		let methods = vec![BootstrapMethodsMethod {
+			method: CPMethodHandleRef::from_cp(cp, factory.index).unwrap(),
+			arguments: vec![recipe, constant],
+		}];
+		let name_and_ty =
+			CPNameAndTypeRef::find_or_add(cp, "makeConcatWithConstants", "(ILjava/lang/String;)Ljava/lang/String;")
+				.unwrap();
+		let tag = IRCpTag::InvokeDynamic {
+			bootstrap_method_attr_index: 0,
+			name_and_ty,
+		};
+		let index = cp_find_or_add(cp, tag).unwrap();
+		let indy = CPInvokeDynamicRef::from_cp(cp, index).unwrap();
+		builder
+			.attribute(IRAttribute::BootstrapMethods {
+				methods: methods.clone(),
+			})
+			.unwrap();
+		let class = builder.build();
+		let cp = &class.cp;
+		assert_eq!(class.bootstrap_method(&indy).unwrap().arguments.len(), 2);
+
+		let info = StringConcatInfo::decode(cp, &methods, &indy).unwrap().unwrap();
+		assert_eq!(
+			info.arguments,
+			[
+				FieldType::Base(crate::descriptor::BaseType::Int),
+				FieldType::Object("java/lang/String".to_string())
+			]
+		);
+		assert!(matches!(info.constants[0].tag, IRCpTag::Integer(7)));
+		assert_eq!(
+			info.parts(),
+			[
+				ConcatPart::Literal("x = ".to_string()),
+				ConcatPart::Argument(0),
+				ConcatPart::Literal(", ".to_string()),
+				ConcatPart::Constant(0),
+				ConcatPart::Argument(1),
+				ConcatPart::Literal("!".to_string()),
+			]
+		);
+		assert!(LambdaInfo::decode(cp, &methods, &indy).unwrap().is_none());
+		assert!(StringConcatInfo::decode(cp, &[], &indy).is_err());
+	}
+}
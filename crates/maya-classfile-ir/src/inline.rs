@@ -0,0 +1,431 @@
+// Method inlining: `invokestatic` and `invokespecial` calls to methods of the same class are replaced by the callee's
+// body. The arguments are stored into locals past the caller's own, the callee's locals are moved up to them, its
+// returns jump to after the call with the result on the stack, and its exception handlers go in front of the caller's,
+// as they're the inner ones. Afterwards the maxs and frames of the caller are recomputed.
+//
+// Only the same class is looked at, as instructions refer to the constant pool by index, and a method's code can use
+// members only its own class can access anyway. The bodies inlined are the ones the methods had before the pass, so
+// inlining doesn't cascade and recursion can't blow up the code.
+
+use crate::{
+	analysis::{
+		frames::{recompute_method_frames, ClassHierarchy},
+		stack,
+	},
+	attribute::{IRAttribute, IRAttributeInfo},
+	class_pool::{CPMethodRef, CPUtf8Ref, IRClassfileError, IRCpTag},
+	code::{Instructions, Opcodes},
+	descriptor::{FieldType, MethodDescriptor},
+	listing::{Item, Listing},
+	transform::{ChangeLog, Transform},
+	IRClassFile, IRMethodInfo,
+};
+
+/// Inlines calls to the small methods of a class within that class.
+pub struct Inliner<H> {
+	hierarchy: H,
+	max_callee_length: usize,
+}
+
+impl<H: ClassHierarchy> Inliner<H> {
+	/// HotSpot's MaxInlineSize.
+	pub const DEFAULT_MAX_CALLEE_LENGTH: usize = 35;
+
+	pub fn new(hierarchy: H) -> Self {
+		Self {
+			hierarchy,
+			max_callee_length: Self::DEFAULT_MAX_CALLEE_LENGTH,
+		}
+	}
+
+	/// Methods with longer code, in bytes, aren't inlined.
+	pub fn max_callee_length(mut self, length: usize) -> Self {
+		self.max_callee_length = length;
+		self
+	}
+}
+
+impl<H: ClassHierarchy> Transform for Inliner<H> {
+	fn name(&self) -> &str {
+		"inline"
+	}
+
+	fn apply(&mut self, class: &mut IRClassFile, log: &mut ChangeLog) -> Result<(), IRClassfileError> {
+		let class_name = class.class_name().to_string();
+		let mut callees = Vec::new();
+		for method in &class.methods {
+			if let Some(callee) = Callee::new(&class.cp, &class_name, method, self.max_callee_length)? {
+				callees.push(callee);
+			}
+		}
+		if callees.is_empty() {
+			return Ok(());
+		}
+
+		for method in &mut class.methods {
+			let Some(code) = method.code() else {
+				continue;
+			};
+			let mut listing = Listing::from_code(&class.cp, code)?;
+			// The stack depth before each of the original instructions, if the code simulates.
+			let depths = match stack::simulate(&class.cp, &class_name, method) {
+				Ok(Some(simulation)) => Instructions::read_all(&class.cp, &code.code)?
+					.iter()
+					.map(|(pc, _)| simulation.state_at(*pc).map(|state| state.depth()))
+					.collect(),
+				_ => Vec::new(),
+			};
+			let old = match log.is_enabled() {
+				true => Instructions::read_all(&class.cp, &code.code)?,
+				false => Vec::new(),
+			};
+			let base = listing.max_locals;
+			let mut inlined = 0;
+			let (mut at, mut instruction) = (0, 0);
+			while at < listing.items.len() {
+				let callee = match &listing.items[at] {
+					Item::Insn(Instructions::INVOKESTATIC(target)) => find(&callees, &class_name, target, true),
+					Item::Insn(Instructions::INVOKESPECIAL(target)) => find(&callees, &class_name, target, false),
+					_ => None,
+				};
+				let depth = depths.get(instruction).copied().flatten();
+				if listing.items[at].is_instruction() {
+					instruction += 1;
+				}
+				// Never into itself, that would only unroll a recursion once. A handler starts with only the exception
+				// on the stack, so a callee with any is only inlined where the stack holds nothing but the arguments.
+				let Some(callee) = callee.filter(|callee| {
+					(callee.method.name() != method.name() || callee.method.descriptor() != method.descriptor())
+						&& (callee.listing.handlers.is_empty() || depth == Some(callee.arguments))
+				}) else {
+					at += 1;
+					continue;
+				};
+
+				let body = callee.expand(&mut class.cp, &mut listing, base)?;
+				let length = body.len();
+				listing.items.splice(at..at + 1, body);
+				listing.max_locals = listing.max_locals.max(base + callee.max_locals);
+				inlined += 1;
+				at += length;
+			}
+			if inlined == 0 {
+				continue;
+			}
+
+			// Code after a call to a method that always throws can't be reached anymore.
+			listing.remove_unreachable();
+			listing.prune();
+			let code = listing.to_code(&mut class.cp)?;
+			let code_name = CPUtf8Ref::find_or_add(&mut class.cp, "Code")?;
+			let slot = method
+				.attributes
+				.iter_mut()
+				.find(|attr| matches!(attr.attr, IRAttribute::Code(_)))
+				.expect("the method has code");
+			*slot = IRAttributeInfo {
+				name: code_name,
+				length: 0,
+				attr: IRAttribute::Code(code),
+			};
+			if let Some((max_stack, _)) = stack::compute_maxs(&class.cp, &class_name, method)? {
+				method.attributes.code_mut().expect("the method has code").max_stack = max_stack;
+			}
+			if class.version.supports_stack_map_table() {
+				recompute_method_frames(&mut class.cp, &class_name, method, &self.hierarchy)?;
+			}
+
+			if log.is_enabled() {
+				let code = method.code().expect("the method has code");
+				let new = Instructions::read_all(&class.cp, &code.code)?;
+				log.record_code_changes(&format!("{}{}", method.name(), method.descriptor()), &old, &new);
+			}
+		}
+		Ok(())
+	}
+}
+
+fn find<'a>(callees: &'a [Callee], class_name: &str, target: &CPMethodRef, is_static: bool) -> Option<&'a Callee> {
+	if target.class.data.data.as_str() != class_name {
+		return None;
+	}
+	callees.iter().find(|callee| {
+		callee.method.access_flags.is_static() == is_static
+			&& callee.method.name() == target.name_and_ty.name.data.as_str()
+			&& callee.method.descriptor() == target.name_and_ty.ty.data.as_str()
+	})
+}
+
+/// A method that can be inlined, with its body as it was before the pass.
+struct Callee {
+	method: IRMethodInfo,
+	listing: Listing,
+	max_locals: u16,
+	/// The slots the arguments take on the stack, the receiver included.
+	arguments: u16,
+}
+
+impl Callee {
+	fn new(
+		cp: &[IRCpTag],
+		class_name: &str,
+		method: &IRMethodInfo,
+		max_length: usize,
+	) -> Result<Option<Self>, IRClassfileError> {
+		let flags = method.access_flags;
+		let Some(code) = method.code() else {
+			return Ok(None);
+		};
+		// Instance methods are only called with invokespecial if they're private, others are dispatched virtually.
+		// Constructors initialize `this`, which only they can do, and inlining a synchronized method would lose the lock.
+		if code.code.len() > max_length
+			|| method.name().starts_with('<')
+			|| (!flags.is_static() && !flags.is_private())
+			|| flags.is_synchronized()
+		{
+			return Ok(None);
+		}
+
+		let listing = Listing::from_code(cp, code)?;
+		if listing.items.iter().any(|item| {
+			matches!(
+				item,
+				Item::Jump {
+					opcode: Opcodes::JSR,
+					..
+				} | Item::Insn(Instructions::RET(_))
+			)
+		}) {
+			return Ok(None);
+		}
+		// A return may leave more than its value on the stack, which is fine for the caller but would be left on the
+		// stack of the inlined code.
+		let Ok(Some(simulation)) = stack::simulate(cp, class_name, method) else {
+			return Ok(None);
+		};
+		let descriptor = MethodDescriptor::parse(method.descriptor())?;
+		let ret = descriptor.ret;
+		let returns_cleanly = Instructions::read_all(cp, &code.code)?.iter().all(|(pc, insn)| {
+			!is_return(insn)
+				|| simulation
+					.state_at(*pc)
+					.is_none_or(|state| state.depth() == ret.as_ref().map_or(0, FieldType::slots))
+		});
+		if !returns_cleanly {
+			return Ok(None);
+		}
+
+		Ok(Some(Self {
+			method: method.clone(),
+			listing,
+			max_locals: code.max_locals,
+			arguments: descriptor.params.iter().map(FieldType::slots).sum::<u16>() + !flags.is_static() as u16,
+		}))
+	}
+
+	/// The items replacing a call, the callee's locals starting at `base`.
+	fn expand(&self, cp: &mut Vec<IRCpTag>, caller: &mut Listing, base: u16) -> Result<Vec<Item>, IRClassfileError> {
+		let mut body = self.listing.clone();
+		caller.adopt(&mut body);
+		let end = caller.new_label();
+		let mut items = Vec::with_capacity(body.items.len() + 8);
+
+		// The arguments come off the stack last one first.
+		let descriptor = MethodDescriptor::parse(self.method.descriptor())?;
+		let receiver = !self.method.access_flags.is_static() as u16;
+		let mut slots = Vec::with_capacity(descriptor.params.len());
+		let mut slot = base + receiver;
+		for param in &descriptor.params {
+			slots.push((slot, param));
+			slot += param.slots();
+		}
+		for (slot, param) in slots.into_iter().rev() {
+			items.push(Item::Insn(store(param, slot)));
+		}
+		if receiver == 1 {
+			// invokespecial throws on a null receiver, javac's null check keeps that.
+			let get_class = CPMethodRef::find_or_add(cp, "java/lang/Object", "getClass", "()Ljava/lang/Class;")?;
+			items.push(Item::Insn(Instructions::DUP));
+			items.push(Item::Insn(Instructions::INVOKEVIRTUAL(get_class)));
+			items.push(Item::Insn(Instructions::POP));
+			items.push(Item::Insn(Instructions::ASTORE(base)));
+		}
+
+		let last = body.items.iter().rposition(Item::is_instruction);
+		for (i, item) in body.items.into_iter().enumerate() {
+			items.push(match item {
+				Item::Insn(insn) if is_return(&insn) => match Some(i) == last {
+					true => continue,
+					false => Item::Jump {
+						opcode: Opcodes::GOTO,
+						target: end,
+					},
+				},
+				Item::Insn(insn) => Item::Insn(shift_local(insn, base)),
+				item => item,
+			});
+		}
+		items.push(Item::Label(end));
+
+		// The callee's handlers are nested in whatever covers the call.
+		caller.handlers.splice(0..0, body.handlers);
+		for local in body.local_variables.iter_mut().chain(&mut body.local_variable_types) {
+			local.index += base;
+		}
+		caller.local_variables.extend(body.local_variables);
+		caller.local_variable_types.extend(body.local_variable_types);
+		Ok(items)
+	}
+}
+
+fn is_return(insn: &Instructions) -> bool {
+	matches!(
+		insn,
+		Instructions::IRETURN
+			| Instructions::LRETURN
+			| Instructions::FRETURN
+			| Instructions::DRETURN
+			| Instructions::ARETURN
+			| Instructions::RETURN
+	)
+}
+
+fn store(ty: &FieldType, slot: u16) -> Instructions {
+	match stack::ValueKind::of(ty) {
+		stack::ValueKind::Long => Instructions::LSTORE(slot),
+		stack::ValueKind::Float => Instructions::FSTORE(slot),
+		stack::ValueKind::Double => Instructions::DSTORE(slot),
+		stack::ValueKind::Int => Instructions::ISTORE(slot),
+		_ => Instructions::ASTORE(slot),
+	}
+}
+
+fn shift_local(insn: Instructions, base: u16) -> Instructions {
+	use Instructions as I;
+
+	match insn {
+		I::ILOAD(index) => I::ILOAD(index + base),
+		I::LLOAD(index) => I::LLOAD(index + base),
+		I::FLOAD(index) => I::FLOAD(index + base),
+		I::DLOAD(index) => I::DLOAD(index + base),
+		I::ALOAD(index) => I::ALOAD(index + base),
+		I::ISTORE(index) => I::ISTORE(index + base),
+		I::LSTORE(index) => I::LSTORE(index + base),
+		I::FSTORE(index) => I::FSTORE(index + base),
+		I::DSTORE(index) => I::DSTORE(index + base),
+		I::ASTORE(index) => I::ASTORE(index + base),
+		I::IINC { index, r#const } => I::IINC {
+			index: index + base,
+			r#const,
+		},
+		insn => insn,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		analysis::frames::KnownClasses,
+		builder::{ClassBuilder, CodeBuilder},
+		flags::MethodAccessFlags,
+		transform::ChangeRecord,
+	};
+
+	#[test]
+	fn static_and_private_calls() {
+		let mut builder = ClassBuilder::new("p/C").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		code.max_stack(2)
+			.max_locals(2)
+			.insn(Instructions::ILOAD(0))
+			.insn(Instructions::ILOAD(1))
+			.insn(Instructions::IADD)
+			.insn(Instructions::IRETURN);
+		let add = code.build().unwrap();
+
+		// try { return 100 / x; } catch (ArithmeticException e) { return 0; }
+		let mut code = CodeBuilder::new(builder.cp());
+		let (start, end, handler) = (code.new_label(), code.new_label(), code.new_label());
+		code.max_stack(2)
+			.max_locals(2)
+			.bind(start)
+			.unwrap()
+			.insn(Instructions::BIPUSH(100))
+			.insn(Instructions::ILOAD(1))
+			.insn(Instructions::IDIV)
+			.insn(Instructions::IRETURN)
+			.bind(end)
+			.unwrap()
+			.bind(handler)
+			.unwrap()
+			.insn(Instructions::POP)
+			.insn(Instructions::ICONST_0)
+			.insn(Instructions::IRETURN)
+			.try_catch(start, end, handler, Some("java/lang/ArithmeticException"))
+			.unwrap();
+		let safe = code.build().unwrap();
+
+		// return safe(add(x, 2));
+		let add_ref = CPMethodRef::find_or_add(builder.cp(), "p/C", "add", "(II)I").unwrap();
+		let safe_ref = CPMethodRef::find_or_add(builder.cp(), "p/C", "safe", "(I)I").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		code.max_stack(3)
+			.max_locals(2)
+			.insn(Instructions::ALOAD(0))
+			.insn(Instructions::ILOAD(1))
+			.insn(Instructions::ICONST_2)
+			.insn(Instructions::INVOKESTATIC(add_ref))
+			.insn(Instructions::INVOKESPECIAL(safe_ref))
+			.insn(Instructions::IRETURN);
+		let call = code.build().unwrap();
+
+		builder
+			.method(MethodAccessFlags::STATIC, "add", "(II)I", [IRAttribute::Code(add)])
+			.unwrap()
+			.method(MethodAccessFlags::PRIVATE, "safe", "(I)I", [IRAttribute::Code(safe)])
+			.unwrap()
+			.method(MethodAccessFlags::PUBLIC, "call", "(I)I", [IRAttribute::Code(call)])
+			.unwrap();
+		let mut class = builder.build();
+
+		let mut log = ChangeLog::new();
+		Inliner::new(KnownClasses::new()).apply(&mut class, &mut log).unwrap();
+		let changes = log.entries().iter().map(|entry| &entry.change).collect::<Vec<_>>();
+		// Both calls are replaced by the bodies, stores of the arguments and jumps to after them.
+		let method = "call(I)I".to_string();
+		assert_eq!(
+			changes,
+			[
+				&ChangeRecord::RemovedInstructions {
+					method: method.clone(),
+					pc: 3,
+					count: 2
+				},
+				&ChangeRecord::InsertedInstructions {
+					method,
+					pc: 3,
+					count: 16
+				},
+			]
+		);
+
+		let method = &class.methods[2];
+		let code = method.code().unwrap();
+		assert_eq!((code.max_stack, code.max_locals), (3, 4));
+		assert_eq!(code.exception_table.len(), 1);
+		assert!(code.attributes.stack_map_table().is_some());
+		let instructions = Instructions::read_all(&class.cp, &code.code)
+			.unwrap()
+			.into_iter()
+			.map(|(_, insn)| insn)
+			.collect::<Vec<_>>();
+		assert!(!instructions
+			.iter()
+			.any(|insn| matches!(insn, Instructions::INVOKESTATIC(_) | Instructions::INVOKESPECIAL(_))));
+		assert!(instructions.iter().any(|insn| matches!(insn, Instructions::ISTORE(3))));
+		assert!(stack::simulate(&class.cp, "p/C", method).unwrap().is_some());
+		// The callees themselves stay as they were.
+		assert_eq!(class.methods[0].code().unwrap().code, [0x1A, 0x1B, 0x60, 0xAC]);
+	}
+}
@@ -0,0 +1,285 @@
+// A jar the way the JVM sees it, on top of `archive`: classes parsed the first time they're asked for, the manifest,
+// the service providers under META-INF/services, and everything else as bytes.
+// https://docs.oracle.com/en/java/javase/22/docs/specs/jar/jar.html
+
+use std::{
+	cell::RefCell,
+	collections::{BTreeMap, HashMap},
+	io::{Read, Seek},
+	path::Path,
+};
+
+use thiserror::Error;
+
+use crate::{
+	archive::{Archive, ArchiveEntry, ArchiveError, DuplicatePolicy},
+	class_pool::IRClassfileError,
+	IRClassFile, Shared,
+};
+
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+const SERVICES: &str = "META-INF/services/";
+
+#[derive(Debug, Error)]
+pub enum JarError {
+	#[error("{0}")]
+	Archive(#[from] ArchiveError),
+	#[error("{name}: {source}")]
+	Class { name: String, source: IRClassfileError },
+}
+
+#[derive(Debug)]
+pub struct Jar {
+	archive: Archive,
+	// by index in the archive's entries, duplicates kept by the policy are classes of their own
+	classes: RefCell<HashMap<usize, Shared<IRClassFile>>>,
+}
+
+impl From<Archive> for Jar {
+	fn from(archive: Archive) -> Self {
+		Self {
+			archive,
+			classes: RefCell::default(),
+		}
+	}
+}
+
+impl Jar {
+	pub fn open(path: &Path, policy: DuplicatePolicy) -> Result<Self, JarError> {
+		Ok(Archive::open(path, policy)?.into())
+	}
+
+	pub fn read<R: Read + Seek>(reader: R, policy: DuplicatePolicy) -> Result<Self, JarError> {
+		Ok(Archive::read(reader, policy)?.into())
+	}
+
+	pub fn archive(&self) -> &Archive {
+		&self.archive
+	}
+
+	/// Every entry, classes included, in archive order.
+	pub fn entries(&self) -> &[ArchiveEntry] {
+		self.archive.entries()
+	}
+
+	/// The internal names of the classes, in archive order.
+	pub fn class_names(&self) -> impl Iterator<Item = &str> {
+		self.archive
+			.classes()
+			.filter_map(|entry| entry.name.strip_suffix(".class"))
+	}
+
+	/// The class with this internal name, parsed on first use. `None` if the jar doesn't have it.
+	pub fn class(&self, name: &str) -> Result<Option<Shared<IRClassFile>>, JarError> {
+		let entry = format!("{name}.class");
+		match self.entries().iter().position(|candidate| candidate.name == entry) {
+			Some(index) => self.parse(index).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	/// Every class, each parsed as the iterator gets to it.
+	pub fn classes(&self) -> impl Iterator<Item = Result<Shared<IRClassFile>, JarError>> + '_ {
+		let entries = self.entries().iter().enumerate();
+		entries
+			.filter(|(_, entry)| entry.is_class())
+			.map(|(index, _)| self.parse(index))
+	}
+
+	/// The entries that aren't classes, the manifest and service files included.
+	pub fn resources(&self) -> impl Iterator<Item = &ArchiveEntry> {
+		self.entries().iter().filter(|entry| !entry.is_class())
+	}
+
+	pub fn resource(&self, name: &str) -> Option<&[u8]> {
+		self.archive.get(name).map(|entry| entry.data.as_slice())
+	}
+
+	/// META-INF/MANIFEST.MF, if there is one.
+	pub fn manifest(&self) -> Option<Manifest> {
+		self.resource(MANIFEST)
+			.map(|data| Manifest::parse(&String::from_utf8_lossy(data)))
+	}
+
+	/// The providers of each service declared under META-INF/services, by the service's binary name.
+	pub fn services(&self) -> BTreeMap<String, Vec<String>> {
+		let mut services = BTreeMap::<_, Vec<_>>::new();
+		for entry in self.resources() {
+			let Some(service) = entry
+				.name
+				.strip_prefix(SERVICES)
+				.filter(|service| !service.contains('/'))
+			else {
+				continue;
+			};
+			// One provider per line, `#` starts a comment.
+			let providers = String::from_utf8_lossy(&entry.data);
+			let providers = providers
+				.lines()
+				.map(|line| line.split('#').next().unwrap_or_default().trim())
+				.filter(|provider| !provider.is_empty());
+			services
+				.entry(service.to_string())
+				.or_default()
+				.extend(providers.map(str::to_string));
+		}
+		services
+	}
+
+	fn parse(&self, index: usize) -> Result<Shared<IRClassFile>, JarError> {
+		if let Some(class) = self.classes.borrow().get(&index) {
+			return Ok(class.clone());
+		}
+		let entry = &self.entries()[index];
+		let class = IRClassFile::read(&entry.data).map_err(|source| JarError::Class {
+			name: entry.name.clone(),
+			source,
+		})?;
+		let class = Shared::new(class);
+		self.classes.borrow_mut().insert(index, class.clone());
+		Ok(class)
+	}
+}
+
+/// The main section of a manifest and the sections for single entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+	pub main: ManifestSection,
+	// by their `Name` attribute
+	pub entries: Vec<(String, ManifestSection)>,
+}
+
+/// Attributes in the order they're in, names as written. Lookups ignore case, as the JVM does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestSection {
+	pub attributes: Vec<(String, String)>,
+}
+
+impl ManifestSection {
+	pub fn get(&self, name: &str) -> Option<&str> {
+		self.attributes
+			.iter()
+			.find(|(candidate, _)| candidate.eq_ignore_ascii_case(name))
+			.map(|(_, value)| value.as_str())
+	}
+}
+
+impl Manifest {
+	/// Reads a manifest leniently: lines without a `name: value` are skipped rather than failing the whole jar.
+	pub fn parse(text: &str) -> Self {
+		// A line starting with a space continues the one before it.
+		let mut lines = Vec::<String>::new();
+		for line in text.split("\r\n").flat_map(|line| line.split(['\r', '\n'])) {
+			match (line.strip_prefix(' '), lines.last_mut()) {
+				(Some(rest), Some(last)) if !last.is_empty() => last.push_str(rest),
+				_ => lines.push(line.to_string()),
+			}
+		}
+
+		let mut manifest = Manifest::default();
+		let mut sections = lines
+			.split(|line| line.is_empty())
+			.filter(|section| !section.is_empty());
+		let section = |lines: &[String]| ManifestSection {
+			attributes: lines
+				.iter()
+				.filter_map(|line| line.split_once(": "))
+				.map(|(name, value)| (name.to_string(), value.to_string()))
+				.collect(),
+		};
+		if let Some(main) = sections.next() {
+			manifest.main = section(main);
+		}
+		for lines in sections {
+			let section = section(lines);
+			if let Some(name) = section.get("Name") {
+				manifest.entries.push((name.to_string(), section));
+			}
+		}
+		manifest
+	}
+
+	pub fn main_class(&self) -> Option<&str> {
+		self.main.get("Main-Class")
+	}
+
+	/// The relative URLs in `Class-Path`.
+	pub fn class_path(&self) -> Vec<&str> {
+		self.main
+			.get("Class-Path")
+			.map_or_else(Vec::new, |paths| paths.split_whitespace().collect())
+	}
+
+	pub fn is_multi_release(&self) -> bool {
+		self.main
+			.get("Multi-Release")
+			.is_some_and(|value| value.eq_ignore_ascii_case("true"))
+	}
+
+	/// The section for the entry called `name`.
+	pub fn entry(&self, name: &str) -> Option<&ManifestSection> {
+		self.entries
+			.iter()
+			.find(|(candidate, _)| candidate == name)
+			.map(|(_, section)| section)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Cursor, Write};
+
+	use zip::{write::FileOptions, ZipWriter};
+
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn read_jar() {
+		let class = assemble(".class public super p/Main").unwrap().to_bytes().unwrap();
+		let manifest = "Manifest-Version: 1.0\r\nMain-Class: p.Main\r\nClass-Path: lib/a.jar\r\n  lib/b.jar\r\n\r\n\
+			Name: p/data.txt\r\nContent-Type: text/plain\r\n";
+		let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+		for (name, data) in [
+			(MANIFEST, manifest.as_bytes()),
+			("p/Main.class", &class),
+			("p/Broken.class", b"\xca\xfe"),
+			(
+				"META-INF/services/p.Plugin",
+				b"# plugins\np.First\n\np.Second # the other one\n",
+			),
+			("p/data.txt", b"data"),
+		] {
+			zip.start_file(name, FileOptions::default()).unwrap();
+			zip.write_all(data).unwrap();
+		}
+		let bytes = zip.finish().unwrap().into_inner();
+		let jar = Jar::read(Cursor::new(bytes), DuplicatePolicy::FirstWins).unwrap();
+
+		assert_eq!(jar.class_names().collect::<Vec<_>>(), ["p/Main", "p/Broken"]);
+		let main = jar.class("p/Main").unwrap().unwrap();
+		assert_eq!(main.class_name(), "p/Main");
+		assert!(Shared::ptr_eq(&main, &jar.class("p/Main").unwrap().unwrap()));
+		assert!(jar.class("p/Missing").unwrap().is_none());
+		let classes = jar.classes().collect::<Vec<_>>();
+		assert!(classes[0].is_ok());
+		assert!(matches!(&classes[1], Err(JarError::Class { name, .. }) if name == "p/Broken.class"));
+
+		let manifest = jar.manifest().unwrap();
+		assert_eq!(manifest.main_class(), Some("p.Main"));
+		assert_eq!(manifest.class_path(), ["lib/a.jar", "lib/b.jar"]);
+		assert_eq!(
+			manifest.entry("p/data.txt").unwrap().get("content-type"),
+			Some("text/plain")
+		);
+		assert_eq!(
+			jar.services(),
+			BTreeMap::from([(
+				"p.Plugin".to_string(),
+				vec!["p.First".to_string(), "p.Second".to_string()]
+			)])
+		);
+		assert_eq!(jar.resources().count(), 3);
+		assert_eq!(jar.resource("p/data.txt"), Some(&b"data"[..]));
+	}
+}
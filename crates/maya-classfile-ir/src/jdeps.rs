@@ -0,0 +1,119 @@
+// A dependency report like jdeps gives: which packages the classes of a jar or module use from which others, the
+// modules those packages are in, and where JDK-internal APIs are used. It's built on `IRClassFile::dependencies`, so it
+// sees every class a class names, not only the ones its code calls into.
+//
+// Jars and modules are added one at a time, each under its module name, or the name in its module-info if it has
+// one. Packages are looked up among everything added first, then by the function given to `modules`, e.g. one asking
+// a `JImage` of the JDK. Packages found nowhere go to the module called "not found", as jdeps reports them.
+// https://docs.oracle.com/en/java/javase/22/docs/specs/man/jdeps.html
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{names, IRClassFile};
+
+pub const NOT_FOUND: &str = "not found";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyReport {
+	// package to the packages its classes use, itself left out, internal form
+	pub packages: BTreeMap<String, BTreeSet<String>>,
+	// package of the classes added to their module
+	pub package_modules: BTreeMap<String, String>,
+	// class to the JDK-internal classes it uses
+	pub internal_uses: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DependencyReport {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds the classes of one jar or module.
+	pub fn add<'a>(&mut self, module: &str, classes: impl IntoIterator<Item = &'a IRClassFile>) {
+		let classes = classes.into_iter().collect::<Vec<_>>();
+		let module = classes
+			.iter()
+			.find_map(|class| class.module_info())
+			.map_or(module, |info| info.name);
+
+		for class in classes.into_iter().filter(|class| !class.is_module_info()) {
+			let package = names::package_name(class.class_name());
+			self.package_modules.insert(package.to_string(), module.to_string());
+			let uses = self.packages.entry(package.to_string()).or_default();
+			for dependency in class.dependencies().classes {
+				let used = names::package_name(&dependency);
+				if used != package {
+					uses.insert(used.to_string());
+				}
+				if is_jdk_internal(used) && !is_jdk_internal(package) {
+					self.internal_uses
+						.entry(class.class_name().to_string())
+						.or_default()
+						.insert(dependency);
+				}
+			}
+		}
+	}
+
+	/// Each module added to the modules its packages use, itself left out. `module_of` finds the module of a package
+	/// that wasn't added.
+	pub fn modules(&self, module_of: impl Fn(&str) -> Option<String>) -> BTreeMap<String, BTreeSet<String>> {
+		let mut modules = BTreeMap::<_, BTreeSet<_>>::new();
+		for (package, uses) in &self.packages {
+			let module = &self.package_modules[package];
+			let used = uses.iter().map(|used| match self.package_modules.get(used) {
+				Some(module) => module.clone(),
+				None => module_of(used).unwrap_or_else(|| NOT_FOUND.to_string()),
+			});
+			let used = used.filter(|used| used != module).collect::<Vec<_>>();
+			modules.entry(module.clone()).or_default().extend(used);
+		}
+		modules
+	}
+}
+
+/// Whether the package with this internal name is one of the JDK's internal ones, `jdk.internal.*` or `sun.*`, which
+/// code outside the JDK shouldn't use.
+pub fn is_jdk_internal(package: &str) -> bool {
+	["jdk/internal", "sun"]
+		.iter()
+		.any(|internal| package == *internal || package.starts_with(&format!("{internal}/")))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn report() {
+		let class = |source: &str| assemble(source).unwrap();
+		let app = [
+			class(
+				".class public super p/Main\n.method public run()V\ninvokestatic q/Util/run()V\n\
+				invokestatic jdk/internal/misc/Unsafe/getUnsafe()Ljdk/internal/misc/Unsafe;\npop\n\
+				getstatic r/Missing/x Lp/Other;\npop\nreturn\n.end method",
+			),
+			class(".class public super p/Other"),
+		];
+		let lib = [
+			class(".class public super q/Util\n.method public static run()V\nreturn\n.end method"),
+			class(".class public super sun/misc/Helper\n.field x Ljdk/internal/misc/Unsafe;"),
+		];
+		let mut report = DependencyReport::new();
+		report.add("app.jar", &app);
+		report.add("lib.jar", &lib);
+
+		let set = |items: &[&str]| items.iter().map(|item| item.to_string()).collect::<BTreeSet<_>>();
+		assert_eq!(report.packages["p"], set(&["java/lang", "jdk/internal/misc", "q", "r"]));
+		assert_eq!(
+			report.internal_uses,
+			BTreeMap::from([("p/Main".to_string(), set(&["jdk/internal/misc/Unsafe"]))])
+		);
+		let modules = report.modules(|package| {
+			(package == "java/lang" || package.starts_with("jdk/")).then(|| "java.base".to_string())
+		});
+		assert_eq!(modules["app.jar"], set(&["java.base", "lib.jar", NOT_FOUND]));
+		assert_eq!(modules["lib.jar"], set(&["java.base"]));
+	}
+}
@@ -0,0 +1,499 @@
+// Reading the jimage file a JDK keeps its modules in, `lib/modules`, the way the jrt filesystem does. It's an index, a
+// perfect hash from resource names to locations, followed by the resources themselves. Resources are named
+// `/module/parent/base.extension`, e.g. `/java.base/java/lang/Object.class`. Everything in the index is in the byte
+// order of the platform that wrote it, which the magic tells.
+// https://github.com/openjdk/jdk/blob/master/src/java.base/share/native/libjimage/imageFile.hpp
+
+use std::{
+	cell::{OnceCell, RefCell},
+	collections::{BTreeSet, HashMap},
+	fmt,
+	fs::File,
+	io::{self, BufReader, Read, Seek, SeekFrom},
+	path::Path,
+};
+
+use flate2::read::ZlibDecoder;
+use thiserror::Error;
+
+use crate::classpath::{ClassProvider, ClasspathError};
+
+const MAGIC: u32 = 0xCAFE_DADA;
+const MAJOR_VERSION: u16 = 1;
+const HEADER_SIZE: usize = 7 * 4;
+const HASH_MULTIPLIER: u32 = 0x0100_0193;
+
+// A compressed resource starts with this header, and is compressed again as long as the data it decompresses to does.
+// https://github.com/openjdk/jdk/blob/master/src/java.base/share/native/libjimage/imageDecompressor.hpp
+const COMPRESSED_MAGIC: u32 = 0xCAFE_FAFA;
+const COMPRESSED_HEADER_SIZE: usize = 4 + 8 + 8 + 4 + 4 + 1;
+// Deflate can't compress better than this, so a zipped payload is never claimed to inflate to more up front.
+const MAX_DEFLATE_RATIO: usize = 1032;
+
+// The kinds of location attributes.
+const ATTRIBUTE_END: u8 = 0;
+const ATTRIBUTE_MODULE: u8 = 1;
+const ATTRIBUTE_PARENT: u8 = 2;
+const ATTRIBUTE_BASE: u8 = 3;
+const ATTRIBUTE_EXTENSION: u8 = 4;
+const ATTRIBUTE_OFFSET: u8 = 5;
+const ATTRIBUTE_COMPRESSED: u8 = 6;
+const ATTRIBUTE_UNCOMPRESSED: u8 = 7;
+
+#[derive(Debug, Error)]
+pub enum JImageError {
+	#[error("{0}")]
+	IO(#[from] io::Error),
+	#[error("Not a jimage file")]
+	InvalidMagic,
+	#[error("Unsupported jimage version {major}.{minor}")]
+	UnsupportedVersion { major: u16, minor: u16 },
+	#[error("Invalid jimage: {0}")]
+	Invalid(&'static str),
+	#[error("{resource} is compressed with {decompressor}, which isn't supported")]
+	UnsupportedCompression { resource: String, decompressor: String },
+}
+
+/// Where a resource is and what it's called, taken apart the way the image stores names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageLocation {
+	pub module: String,
+	// the directory, e.g. `java/lang`
+	pub parent: String,
+	pub base: String,
+	pub extension: String,
+	// from the end of the index
+	pub offset: u64,
+	// 0 if the resource isn't compressed
+	pub compressed_size: u64,
+	pub uncompressed_size: u64,
+}
+
+impl ImageLocation {
+	/// The full name, `/module/parent/base.extension` with the empty parts left out.
+	pub fn name(&self) -> String {
+		let mut name = String::new();
+		if !self.module.is_empty() {
+			name.push_str(&format!("/{}/", self.module));
+		}
+		if !self.parent.is_empty() {
+			name.push_str(&format!("{}/", self.parent));
+		}
+		name.push_str(&self.base);
+		if !self.extension.is_empty() {
+			name.push_str(&format!(".{}", self.extension));
+		}
+		name
+	}
+}
+
+pub struct JImage<R> {
+	reader: RefCell<R>,
+	big_endian: bool,
+	// indexed by the name's hash, negative for a location index, positive for the seed to hash with again
+	redirect: Vec<i32>,
+	offsets: Vec<u32>,
+	locations: Vec<u8>,
+	strings: Vec<u8>,
+	index_size: u64,
+	file_size: u64,
+	// the module of each package, by internal name
+	packages: OnceCell<HashMap<String, String>>,
+}
+
+impl<R> fmt::Debug for JImage<R> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("JImage")
+			.field("resources", &self.offsets.len())
+			.field("big_endian", &self.big_endian)
+			.finish_non_exhaustive()
+	}
+}
+
+impl JImage<BufReader<File>> {
+	pub fn open(path: &Path) -> Result<Self, JImageError> {
+		Self::new(BufReader::new(File::open(path)?))
+	}
+
+	/// The modules of the JDK installed at `java_home`.
+	pub fn open_jdk(java_home: &Path) -> Result<Self, JImageError> {
+		Self::open(&java_home.join("lib").join("modules"))
+	}
+}
+
+impl<R: Read + Seek> JImage<R> {
+	/// Reads the index, the resources are read when asked for.
+	pub fn new(mut reader: R) -> Result<Self, JImageError> {
+		let file_size = reader.seek(SeekFrom::End(0))?;
+		reader.seek(SeekFrom::Start(0))?;
+		let mut header = [0; HEADER_SIZE];
+		reader.read_exact(&mut header)?;
+		let big_endian = match u32::from_le_bytes(header[..4].try_into().expect("4 bytes")) {
+			MAGIC => false,
+			magic if magic.swap_bytes() == MAGIC => true,
+			_ => return Err(JImageError::InvalidMagic),
+		};
+		let u4 = |i: usize| read_u4(&header[i * 4..], big_endian);
+		let (major, minor) = ((u4(1) >> 16) as u16, u4(1) as u16);
+		if major != MAJOR_VERSION {
+			return Err(JImageError::UnsupportedVersion { major, minor });
+		}
+		// u4(2) is flags and u4(3) the resource count, neither needed for reading
+		let (table_length, locations_size, strings_size) = (u4(4) as usize, u4(5) as usize, u4(6) as usize);
+
+		let mut read = |length: usize| -> Result<Vec<u8>, JImageError> {
+			let mut bytes = vec![0; length];
+			reader.read_exact(&mut bytes)?;
+			Ok(bytes)
+		};
+		let table_size = table_length
+			.checked_mul(4)
+			.ok_or(JImageError::Invalid("table too long"))?;
+		// Sizes are checked against the file before anything is allocated for them.
+		let index_size = [table_size, table_size, locations_size, strings_size]
+			.into_iter()
+			.try_fold(HEADER_SIZE as u64, |total, size| total.checked_add(size as u64))
+			.filter(|index_size| *index_size <= file_size)
+			.ok_or(JImageError::Invalid("index larger than the file"))?;
+		let redirect = read(table_size)?;
+		let offsets = read(table_size)?;
+		let locations = read(locations_size)?;
+		let strings = read(strings_size)?;
+		let words = |bytes: &[u8]| {
+			bytes
+				.chunks_exact(4)
+				.map(|word| read_u4(word, big_endian))
+				.collect::<Vec<_>>()
+		};
+		Ok(Self {
+			redirect: words(&redirect).into_iter().map(|value| value as i32).collect(),
+			offsets: words(&offsets),
+			locations,
+			strings,
+			index_size,
+			file_size,
+			reader: RefCell::new(reader),
+			big_endian,
+			packages: OnceCell::new(),
+		})
+	}
+
+	/// The resource called `name`, e.g. `/java.base/java/lang/Object.class`.
+	pub fn find(&self, name: &str) -> Option<ImageLocation> {
+		if self.redirect.is_empty() {
+			return None;
+		}
+		let length = self.redirect.len() as u32;
+		let index = hash(name, HASH_MULTIPLIER) % length;
+		let index = match self.redirect[index as usize] {
+			0 => return None,
+			value if value < 0 => (-1 - value) as u32,
+			seed => hash(name, seed as u32) % length,
+		};
+		// the hash only tells where the name would be, not whether it's there
+		self.location(index as usize).filter(|location| location.name() == name)
+	}
+
+	/// Every resource, in index order.
+	pub fn locations(&self) -> impl Iterator<Item = ImageLocation> + '_ {
+		(0..self.offsets.len()).filter_map(|index| self.location(index))
+	}
+
+	/// The distinct modules, sorted.
+	pub fn modules(&self) -> Vec<String> {
+		let modules = self
+			.locations()
+			.map(|location| location.module)
+			.filter(|module| !module.is_empty());
+		modules.collect::<BTreeSet<_>>().into_iter().collect()
+	}
+
+	/// The module the package with this internal name is in, e.g. `java.base` for `java/lang`.
+	pub fn module_of(&self, package: &str) -> Option<&str> {
+		let packages = self.packages.get_or_init(|| {
+			let classes = self.locations().filter(|location| location.extension == "class");
+			classes.map(|location| (location.parent, location.module)).collect()
+		});
+		packages.get(package).map(String::as_str)
+	}
+
+	/// The resource's bytes, decompressed.
+	pub fn read(&self, location: &ImageLocation) -> Result<Vec<u8>, JImageError> {
+		let stored_size = match location.compressed_size {
+			0 => location.uncompressed_size,
+			size => size,
+		};
+		let start = self.index_size.checked_add(location.offset);
+		let start = start
+			.filter(|start| start.checked_add(stored_size).is_some_and(|end| end <= self.file_size))
+			.ok_or(JImageError::Invalid("resource out of range"))?;
+		let mut data = vec![0; stored_size as usize];
+		let mut reader = self.reader.borrow_mut();
+		reader.seek(SeekFrom::Start(start))?;
+		reader.read_exact(&mut data)?;
+		drop(reader);
+		if location.compressed_size == 0 {
+			return Ok(data);
+		}
+		while data.len() >= COMPRESSED_HEADER_SIZE && read_u4(&data, self.big_endian) == COMPRESSED_MAGIC {
+			let u8_at = |i: usize| {
+				let bytes = data[i..i + 8].try_into().expect("8 bytes");
+				match self.big_endian {
+					true => u64::from_be_bytes(bytes),
+					false => u64::from_le_bytes(bytes),
+				}
+			};
+			let (compressed, uncompressed) = (u8_at(4), u8_at(12));
+			let decompressor = self.string(read_u4(&data[20..], self.big_endian));
+			let end = usize::try_from(compressed)
+				.ok()
+				.and_then(|compressed| COMPRESSED_HEADER_SIZE.checked_add(compressed))
+				.filter(|end| *end <= data.len());
+			let payload = &data[COMPRESSED_HEADER_SIZE..end.ok_or(JImageError::Invalid("compressed size"))?];
+			data = match decompressor.as_str() {
+				"zip" => {
+					let capacity = payload.len().saturating_mul(MAX_DEFLATE_RATIO);
+					let mut decompressed =
+						Vec::with_capacity(capacity.min(uncompressed.try_into().unwrap_or(usize::MAX)));
+					ZlibDecoder::new(payload)
+						.take(uncompressed)
+						.read_to_end(&mut decompressed)?;
+					if decompressed.len() as u64 != uncompressed {
+						return Err(JImageError::Invalid("uncompressed size"));
+					}
+					decompressed
+				}
+				_ => {
+					return Err(JImageError::UnsupportedCompression {
+						resource: location.name(),
+						decompressor,
+					})
+				}
+			};
+		}
+		Ok(data)
+	}
+
+	/// The bytes of the resource called `name`, `None` if there's none.
+	pub fn read_resource(&self, name: &str) -> Result<Option<Vec<u8>>, JImageError> {
+		self.find(name).map(|location| self.read(&location)).transpose()
+	}
+
+	/// The class file of the class with this internal name, from whichever module has its package.
+	pub fn read_class_file(&self, name: &str) -> Result<Option<Vec<u8>>, JImageError> {
+		let package = name.rsplit_once('/').map_or("", |(package, _)| package);
+		match self.module_of(package) {
+			Some(module) => self.read_resource(&format!("/{module}/{name}.class")),
+			None => Ok(None),
+		}
+	}
+
+	fn location(&self, index: usize) -> Option<ImageLocation> {
+		let mut bytes = self.locations.get(*self.offsets.get(index)? as usize..)?.iter();
+		let mut location = ImageLocation {
+			module: String::new(),
+			parent: String::new(),
+			base: String::new(),
+			extension: String::new(),
+			offset: 0,
+			compressed_size: 0,
+			uncompressed_size: 0,
+		};
+		loop {
+			let byte = *bytes.next()?;
+			let kind = byte >> 3;
+			if kind == ATTRIBUTE_END {
+				return Some(location);
+			}
+			// values are big-endian whatever the rest of the image is
+			let mut value = 0u64;
+			for _ in 0..(byte & 7) + 1 {
+				value = value << 8 | *bytes.next()? as u64;
+			}
+			match kind {
+				ATTRIBUTE_MODULE => location.module = self.string(value as u32),
+				ATTRIBUTE_PARENT => location.parent = self.string(value as u32),
+				ATTRIBUTE_BASE => location.base = self.string(value as u32),
+				ATTRIBUTE_EXTENSION => location.extension = self.string(value as u32),
+				ATTRIBUTE_OFFSET => location.offset = value,
+				ATTRIBUTE_COMPRESSED => location.compressed_size = value,
+				ATTRIBUTE_UNCOMPRESSED => location.uncompressed_size = value,
+				_ => return None,
+			}
+		}
+	}
+
+	// The NUL-terminated modified UTF-8 string at `offset` in the strings table.
+	fn string(&self, offset: u32) -> String {
+		let bytes = self.strings.get(offset as usize..).unwrap_or_default();
+		let bytes = &bytes[..bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len())];
+		maya_mutf8::decode(bytes).map_or_else(|_| String::from_utf8_lossy(bytes).into_owned(), |text| text.to_string())
+	}
+}
+
+impl<R: Read + Seek> ClassProvider for JImage<R> {
+	fn read_class(&self, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+		Ok(self.read_class_file(name)?)
+	}
+
+	fn class_names(&self) -> Result<Vec<String>, ClasspathError> {
+		let classes = self
+			.locations()
+			.filter(|location| location.extension == "class" && !location.module.is_empty());
+		let mut names = classes
+			.map(|location| match location.parent.is_empty() {
+				true => location.base,
+				false => format!("{}/{}", location.parent, location.base),
+			})
+			.filter(|name| name != "module-info")
+			.collect::<Vec<_>>();
+		names.sort();
+		names.dedup();
+		Ok(names)
+	}
+}
+
+fn read_u4(bytes: &[u8], big_endian: bool) -> u32 {
+	let bytes = bytes[..4].try_into().expect("4 bytes");
+	match big_endian {
+		true => u32::from_be_bytes(bytes),
+		false => u32::from_le_bytes(bytes),
+	}
+}
+
+// ImageStringsReader::hash_code, over the name's UTF-8 bytes.
+fn hash(name: &str, seed: u32) -> u32 {
+	let hash = name
+		.bytes()
+		.fold(seed, |hash, byte| hash.wrapping_mul(HASH_MULTIPLIER) ^ byte as u32);
+	hash & 0x7FFF_FFFF
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Cursor, Write};
+
+	use flate2::{write::ZlibEncoder, Compression};
+
+	use super::*;
+
+	// module, parent, base, extension, data, and whether it's zipped
+	type Resource<'a> = (&'a str, &'a str, &'a str, &'a str, &'a [u8], bool);
+
+	// A little-endian image of `resources`.
+	fn image(resources: &[Resource]) -> Vec<u8> {
+		let mut strings = vec![0];
+		let mut string = |text: &str| {
+			let offset = strings.len() as u64;
+			strings.extend_from_slice(text.as_bytes());
+			strings.push(0);
+			offset
+		};
+		let zip = string("zip");
+		let (mut locations, mut offsets, mut data) = (Vec::new(), Vec::new(), Vec::new());
+		let mut redirect = vec![0i32; resources.len()];
+		for (i, (module, parent, base, extension, bytes, zipped)) in resources.iter().enumerate() {
+			let name = format!("/{module}/{parent}/{base}.{extension}");
+			let bucket = (hash(&name, HASH_MULTIPLIER) % resources.len() as u32) as usize;
+			assert_eq!(redirect[bucket], 0, "{name} collides");
+			redirect[bucket] = -1 - i as i32;
+
+			let mut stored = bytes.to_vec();
+			if *zipped {
+				let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+				encoder.write_all(bytes).unwrap();
+				let payload = encoder.finish().unwrap();
+				stored = COMPRESSED_MAGIC.to_le_bytes().to_vec();
+				stored.extend((payload.len() as u64).to_le_bytes());
+				stored.extend((bytes.len() as u64).to_le_bytes());
+				stored.extend((zip as u32).to_le_bytes());
+				stored.extend(0u32.to_le_bytes());
+				stored.push(1);
+				stored.extend(payload);
+			}
+			offsets.push(locations.len() as u32);
+			let compressed = if *zipped { stored.len() as u64 } else { 0 };
+			for (kind, value) in [
+				(ATTRIBUTE_MODULE, string(module)),
+				(ATTRIBUTE_PARENT, string(parent)),
+				(ATTRIBUTE_BASE, string(base)),
+				(ATTRIBUTE_EXTENSION, string(extension)),
+				(ATTRIBUTE_OFFSET, data.len() as u64),
+				(ATTRIBUTE_COMPRESSED, compressed),
+				(ATTRIBUTE_UNCOMPRESSED, bytes.len() as u64),
+			] {
+				locations.push(kind << 3 | 7);
+				locations.extend(value.to_be_bytes());
+			}
+			locations.push(ATTRIBUTE_END);
+			data.extend(stored);
+		}
+
+		let mut out = Vec::new();
+		let header = [
+			MAGIC,
+			(MAJOR_VERSION as u32) << 16,
+			0,
+			resources.len() as u32,
+			resources.len() as u32,
+		];
+		header.iter().for_each(|word| out.extend(word.to_le_bytes()));
+		out.extend((locations.len() as u32).to_le_bytes());
+		out.extend((strings.len() as u32).to_le_bytes());
+		redirect.iter().for_each(|value| out.extend(value.to_le_bytes()));
+		offsets.iter().for_each(|offset| out.extend(offset.to_le_bytes()));
+		out.extend(locations);
+		out.extend(strings);
+		out.extend(data);
+		out
+	}
+
+	#[test]
+	fn read_image() {
+		let bytes = image(&[
+			("java.base", "java/lang", "Object", "class", b"\xca\xfe\xba\xbe", false),
+			("java.sql", "java/sql", "Time", "class", b"time", true),
+		]);
+		let image = JImage::new(Cursor::new(bytes)).unwrap();
+
+		let object = image.find("/java.base/java/lang/Object.class").unwrap();
+		assert_eq!((object.module.as_str(), object.base.as_str()), ("java.base", "Object"));
+		assert!(image.find("/java.base/java/lang/String.class").is_none());
+		assert_eq!(image.read(&object).unwrap(), b"\xca\xfe\xba\xbe");
+		assert_eq!(image.read_class("java/sql/Time").unwrap().unwrap(), b"time");
+		assert_eq!(image.module_of("java/sql"), Some("java.sql"));
+		assert_eq!(image.modules(), ["java.base", "java.sql"]);
+		assert_eq!(image.class_names().unwrap(), ["java/lang/Object", "java/sql/Time"]);
+		assert!(matches!(
+			JImage::new(Cursor::new(vec![0; HEADER_SIZE])),
+			Err(JImageError::InvalidMagic)
+		));
+	}
+
+	#[test]
+	fn reject_sizes_past_the_data() {
+		let resources: &[Resource] = &[("java.sql", "java/sql", "Time", "class", b"time", true)];
+
+		// The strings table running past the end of the file.
+		let mut bytes = image(resources);
+		bytes[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(matches!(JImage::new(Cursor::new(bytes)), Err(JImageError::Invalid(_))));
+
+		// A location claiming more stored bytes than there are.
+		let image_bytes = image(resources);
+		let image = JImage::new(Cursor::new(image_bytes.clone())).unwrap();
+		let mut time = image.find("/java.sql/java/sql/Time.class").unwrap();
+		time.compressed_size = u64::MAX;
+		assert!(matches!(image.read(&time), Err(JImageError::Invalid(_))));
+
+		// A compressed header claiming to inflate to everything.
+		let mut bytes = image_bytes;
+		let data = image.index_size as usize;
+		bytes[data + 12..data + 20].copy_from_slice(&u64::MAX.to_le_bytes());
+		let image = JImage::new(Cursor::new(bytes)).unwrap();
+		assert!(matches!(
+			image.read_class_file("java/sql/Time"),
+			Err(JImageError::Invalid("uncompressed size"))
+		));
+	}
+}
@@ -0,0 +1,269 @@
+// Mapping Java methods to JNI for people implementing native methods in Rust. Covers the C types and prototypes
+// javac -h would generate, the matching jni-rs types, and `extern "system"` skeletons for every native method of a class.
+// https://docs.oracle.com/en/java/javase/22/docs/specs/jni/design.html#resolving-native-method-names
+
+use std::fmt::Write;
+
+use crate::{
+	class_pool::IRClassfileError,
+	descriptor::{BaseType, FieldType, MethodDescriptor},
+	IRClassFile, IRMethodInfo,
+};
+
+/// How a Java type is passed across JNI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JniType {
+	Primitive(BaseType),
+	Object,
+	String,
+	Class,
+	Throwable,
+	PrimitiveArray(BaseType),
+	ObjectArray,
+}
+
+impl JniType {
+	pub fn of(ty: &FieldType) -> Self {
+		match ty {
+			FieldType::Base(base) => Self::Primitive(*base),
+			FieldType::Object(name) => match name.as_str() {
+				"java/lang/String" => Self::String,
+				"java/lang/Class" => Self::Class,
+				"java/lang/Throwable" => Self::Throwable,
+				_ => Self::Object,
+			},
+			FieldType::Array(element) => match element.as_ref() {
+				FieldType::Base(base) => Self::PrimitiveArray(*base),
+				_ => Self::ObjectArray,
+			},
+		}
+	}
+
+	/// The type in C, as in jni.h.
+	pub fn c_name(self) -> &'static str {
+		match self {
+			Self::Primitive(base) => match base {
+				BaseType::Boolean => "jboolean",
+				BaseType::Byte => "jbyte",
+				BaseType::Char => "jchar",
+				BaseType::Short => "jshort",
+				BaseType::Int => "jint",
+				BaseType::Long => "jlong",
+				BaseType::Float => "jfloat",
+				BaseType::Double => "jdouble",
+			},
+			Self::Object => "jobject",
+			Self::String => "jstring",
+			Self::Class => "jclass",
+			Self::Throwable => "jthrowable",
+			Self::PrimitiveArray(base) => match base {
+				BaseType::Boolean => "jbooleanArray",
+				BaseType::Byte => "jbyteArray",
+				BaseType::Char => "jcharArray",
+				BaseType::Short => "jshortArray",
+				BaseType::Int => "jintArray",
+				BaseType::Long => "jlongArray",
+				BaseType::Float => "jfloatArray",
+				BaseType::Double => "jdoubleArray",
+			},
+			Self::ObjectArray => "jobjectArray",
+		}
+	}
+
+	/// The type in jni-rs: primitives from `jni::sys`, references as the `jni::objects` wrappers bound to `'local`.
+	pub fn rust_name(self) -> &'static str {
+		match self {
+			Self::Primitive(_) => self.c_name(),
+			Self::Object => "JObject<'local>",
+			Self::String => "JString<'local>",
+			Self::Class => "JClass<'local>",
+			Self::Throwable => "JThrowable<'local>",
+			Self::PrimitiveArray(base) => match base {
+				BaseType::Boolean => "JBooleanArray<'local>",
+				BaseType::Byte => "JByteArray<'local>",
+				BaseType::Char => "JCharArray<'local>",
+				BaseType::Short => "JShortArray<'local>",
+				BaseType::Int => "JIntArray<'local>",
+				BaseType::Long => "JLongArray<'local>",
+				BaseType::Float => "JFloatArray<'local>",
+				BaseType::Double => "JDoubleArray<'local>",
+			},
+			Self::ObjectArray => "JObjectArray<'local>",
+		}
+	}
+}
+
+/// The native side of a method: parameter and return types after the `JNIEnv` and `this`/class arguments.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JniSignature {
+	pub params: Vec<JniType>,
+	// None for void
+	pub ret: Option<JniType>,
+}
+
+impl JniSignature {
+	pub fn new(descriptor: &MethodDescriptor) -> Self {
+		Self {
+			params: descriptor.params.iter().map(JniType::of).collect(),
+			ret: descriptor.ret.as_ref().map(JniType::of),
+		}
+	}
+
+	pub fn parse(descriptor: &str) -> Result<Self, IRClassfileError> {
+		Ok(Self::new(&MethodDescriptor::parse(descriptor)?))
+	}
+
+	/// The prototype javac -h would declare for a function called `name`.
+	pub fn c_prototype(&self, name: &str, is_static: bool) -> String {
+		let ret = self.ret.map_or("void", JniType::c_name);
+		let mut out = format!(
+			"JNIEXPORT {ret} JNICALL {name}(JNIEnv *, {}",
+			if is_static { "jclass" } else { "jobject" }
+		);
+		for param in &self.params {
+			let _ = write!(out, ", {}", param.c_name());
+		}
+		out.push_str(");");
+		out
+	}
+}
+
+/// Escapes a name the way JNI function names require: `/` separates packages, `_`, `;` and `[` get escape sequences
+/// and everything that isn't an ASCII letter or digit is spelled as its UTF-16 code units.
+pub fn mangle(name: &str) -> String {
+	let mut out = String::with_capacity(name.len());
+	for c in name.chars() {
+		match c {
+			'/' => out.push('_'),
+			'_' => out.push_str("_1"),
+			';' => out.push_str("_2"),
+			'[' => out.push_str("_3"),
+			c if c.is_ascii_alphanumeric() => out.push(c),
+			c => {
+				for unit in c.encode_utf16(&mut [0; 2]) {
+					let _ = write!(out, "_0{unit:04x}");
+				}
+			}
+		}
+	}
+	out
+}
+
+/// The symbol the JVM looks up for a native method. `overloaded` selects the long form, which appends the mangled
+/// parameter descriptors; it's needed when the class has several native methods with the same name.
+pub fn function_name(class: &str, method: &str, descriptor: &str, overloaded: bool) -> String {
+	let mut out = format!("Java_{}_{}", mangle(class), mangle(method));
+	if overloaded {
+		let params = descriptor
+			.strip_prefix('(')
+			.and_then(|rest| rest.split_once(')'))
+			.map_or("", |(params, _)| params);
+		out.push_str("__");
+		out.push_str(&mangle(params));
+	}
+	out
+}
+
+/// A native method of a class with everything needed to implement it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeMethod {
+	pub name: String,
+	pub descriptor: String,
+	pub is_static: bool,
+	pub function_name: String,
+	pub signature: JniSignature,
+}
+
+impl NativeMethod {
+	/// The `extern "system"` function jni-rs users would write, with a `todo!()` body.
+	pub fn rust_skeleton(&self) -> String {
+		let mut out = format!("// {}{}\n", self.name, self.descriptor);
+		out.push_str("#[no_mangle]\n");
+		let _ = writeln!(out, "pub extern \"system\" fn {}<'local>(", self.function_name);
+		out.push_str("\tmut env: JNIEnv<'local>,\n");
+		if self.is_static {
+			out.push_str("\tclass: JClass<'local>,\n");
+		} else {
+			out.push_str("\tthis: JObject<'local>,\n");
+		}
+		for (i, param) in self.signature.params.iter().enumerate() {
+			let _ = writeln!(out, "\targ{i}: {},", param.rust_name());
+		}
+		out.push(')');
+		if let Some(ret) = self.signature.ret {
+			let _ = write!(out, " -> {}", ret.rust_name());
+		}
+		out.push_str(" {\n\ttodo!()\n}\n");
+		out
+	}
+
+	pub fn c_prototype(&self) -> String {
+		self.signature.c_prototype(&self.function_name, self.is_static)
+	}
+}
+
+/// Every native method of `class`, in declaration order.
+pub fn native_methods(class: &IRClassFile) -> Result<Vec<NativeMethod>, IRClassfileError> {
+	let natives = class
+		.methods
+		.iter()
+		.filter(|method| method.access_flags.is_native())
+		.collect::<Vec<_>>();
+
+	natives
+		.iter()
+		.map(|method| {
+			let overloaded = natives.iter().filter(|other| other.name() == method.name()).count() > 1;
+			native_method(class.class_name(), method, overloaded)
+		})
+		.collect()
+}
+
+fn native_method(class: &str, method: &IRMethodInfo, overloaded: bool) -> Result<NativeMethod, IRClassfileError> {
+	Ok(NativeMethod {
+		name: method.name().to_string(),
+		descriptor: method.descriptor().to_string(),
+		is_static: method.access_flags.is_static(),
+		function_name: function_name(class, method.name(), method.descriptor(), overloaded),
+		signature: JniSignature::parse(method.descriptor())?,
+	})
+}
+
+/// A Rust source file with a skeleton for every native method of `class`, empty if it has none.
+pub fn rust_skeletons(class: &IRClassFile) -> Result<String, IRClassfileError> {
+	let methods = native_methods(class)?;
+	if methods.is_empty() {
+		return Ok(String::new());
+	}
+
+	let mut out = String::from("use jni::{objects::*, sys::*, JNIEnv};\n");
+	for method in methods {
+		out.push('\n');
+		out.push_str(&method.rust_skeleton());
+	}
+	Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn names_and_types() {
+		assert_eq!(mangle("com/example/My_Class$Inner"), "com_example_My_1Class_00024Inner");
+		assert_eq!(
+			function_name("p/Q", "f", "(I[Ljava/lang/String;)V", true),
+			"Java_p_Q_f__I_3Ljava_lang_String_2"
+		);
+
+		let signature = JniSignature::parse("(I[BLjava/lang/String;[Ljava/lang/Object;)J").unwrap();
+		assert_eq!(
+			signature.c_prototype("Java_p_Q_f", true),
+			"JNIEXPORT jlong JNICALL Java_p_Q_f(JNIEnv *, jclass, jint, jbyteArray, jstring, jobjectArray);"
+		);
+		assert_eq!(
+			signature.params.iter().map(|ty| ty.rust_name()).collect::<Vec<_>>(),
+			["jint", "JByteArray<'local>", "JString<'local>", "JObjectArray<'local>"]
+		);
+	}
+}
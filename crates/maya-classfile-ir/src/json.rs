@@ -0,0 +1,76 @@
+use std::fmt::{Display, Write};
+
+// Minimal JSON emitter for the report/export APIs, so the core crate doesn't need serde.
+pub(crate) fn escape(value: &str, out: &mut String) {
+	out.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			c if (c as u32) < 0x20 => {
+				let _ = write!(out, "\\u{:04x}", c as u32);
+			}
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+}
+
+pub(crate) struct JsonObject<'a> {
+	out: &'a mut String,
+	first: bool,
+}
+
+impl<'a> JsonObject<'a> {
+	pub fn new(out: &'a mut String) -> Self {
+		out.push('{');
+		Self { out, first: true }
+	}
+
+	fn key(&mut self, key: &str) {
+		if !self.first {
+			self.out.push(',');
+		}
+		self.first = false;
+		escape(key, self.out);
+		self.out.push(':');
+	}
+
+	pub fn str(&mut self, key: &str, value: &str) -> &mut Self {
+		self.key(key);
+		escape(value, self.out);
+		self
+	}
+
+	pub fn num(&mut self, key: &str, value: impl Display) -> &mut Self {
+		self.key(key);
+		let _ = write!(self.out, "{value}");
+		self
+	}
+
+	/// `value` must already be valid JSON.
+	pub fn raw(&mut self, key: &str, value: &str) -> &mut Self {
+		self.key(key);
+		self.out.push_str(value);
+		self
+	}
+
+	pub fn end(&mut self) {
+		self.out.push('}');
+	}
+}
+
+pub(crate) fn array<T>(items: impl IntoIterator<Item = T>, mut write: impl FnMut(T, &mut String)) -> String {
+	let mut out = String::from("[");
+	for (i, item) in items.into_iter().enumerate() {
+		if i != 0 {
+			out.push(',');
+		}
+		write(item, &mut out);
+	}
+	out.push(']');
+	out
+}
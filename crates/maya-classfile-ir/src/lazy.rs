@@ -0,0 +1,290 @@
+// Parsing a class without the code of its methods, for indexing jars of thousands of classes by their members. The
+// Code attribute is most of a class, and the attributes nested in it, line numbers, local variables and frames, are
+// most of the work of parsing one. Here the pool, fields, method names, descriptors, flags and the other attributes
+// parse as usual, and each method keeps its Code as it was read until `code` is first called.
+//
+// Warnings from parsing code later are dropped, and a method whose code doesn't parse only fails when asked for it.
+// `into_class` gives the class with all its code, e.g. for one worth changing.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.3
+
+use std::{io::Cursor, sync::OnceLock};
+
+use maya_classfile_io::{IOAttributeInfo, IOClassFile};
+
+use crate::{
+	attribute::{Attributes, CodeAttribute, IRAttribute, IRAttributeInfo},
+	class_pool::{CPClassRef, CPUtf8Ref, IRClassfileError, IRCpTag},
+	flags::{ClassAccessFlags, MethodAccessFlags},
+	parse::{ParseContext, ParseOptions, ParseWarning},
+	ClassFileVersion, IRClassFile, IRFieldInfo, IRMethodInfo, Shared,
+};
+
+/// A Code attribute as it was read, and parsed once asked for.
+#[derive(Debug, Clone)]
+struct LazyCode {
+	name: CPUtf8Ref,
+	length: u32,
+	info: Vec<u8>,
+	// where it goes among the method's attributes
+	position: usize,
+	parsed: OnceLock<CodeAttribute>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LazyMethod {
+	pub access_flags: MethodAccessFlags,
+	pub name: CPUtf8Ref,
+	pub descriptor: CPUtf8Ref,
+	// all but Code
+	pub attributes: Attributes,
+	code: Option<LazyCode>,
+	version: ClassFileVersion,
+	cp: Shared<[IRCpTag]>,
+	options: Shared<ParseOptions>,
+}
+
+impl LazyMethod {
+	pub fn name(&self) -> &str {
+		&self.name.data
+	}
+
+	pub fn descriptor(&self) -> &str {
+		&self.descriptor.data
+	}
+
+	/// Parses the code the first time, `None` for abstract and native methods.
+	pub fn code(&self) -> Result<Option<&CodeAttribute>, IRClassfileError> {
+		let Some(code) = &self.code else {
+			return Ok(None);
+		};
+		if let Some(parsed) = code.parsed.get() {
+			return Ok(Some(parsed));
+		}
+		let parsed = match self.parse_code(code)?.attr {
+			IRAttribute::Code(parsed) => parsed,
+			_ => unreachable!("Code attributes parse as code"),
+		};
+		Ok(Some(code.parsed.get_or_init(|| parsed)))
+	}
+
+	/// Whether `code` was called, or there's no code to parse.
+	pub fn is_parsed(&self) -> bool {
+		self.code.as_ref().is_none_or(|code| code.parsed.get().is_some())
+	}
+
+	/// The method with its code in its place among the attributes.
+	pub fn into_method(self) -> Result<IRMethodInfo, IRClassfileError> {
+		let code = match &self.code {
+			Some(code) => match code.parsed.get() {
+				Some(parsed) => Some((
+					code.position,
+					IRAttributeInfo {
+						name: code.name.clone(),
+						length: code.length,
+						attr: IRAttribute::Code(parsed.clone()),
+					},
+				)),
+				None => Some((code.position, self.parse_code(code)?)),
+			},
+			None => None,
+		};
+		let mut attributes = self.attributes.into_inner();
+		if let Some((position, code)) = code {
+			attributes.insert(position.min(attributes.len()), code);
+		}
+		Ok(IRMethodInfo {
+			access_flags: self.access_flags,
+			name: self.name,
+			descriptor: self.descriptor,
+			attributes: attributes.into(),
+		})
+	}
+
+	fn parse_code(&self, code: &LazyCode) -> Result<IRAttributeInfo, IRClassfileError> {
+		let mut ctx = ParseContext::new(&self.cp, &self.options).with_version(self.version);
+		let raw = IOAttributeInfo {
+			attribute_name_index: code.name.index,
+			attribute_length: code.length,
+			info: code.info.clone(),
+		};
+		IRAttributeInfo::from_io(&mut ctx, raw)
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct LazyClassFile {
+	pub version: ClassFileVersion,
+	pub cp: Shared<[IRCpTag]>,
+	pub access_flags: ClassAccessFlags,
+	pub this_class: CPClassRef,
+	// None for java/lang/Object and module-info
+	pub super_class: Option<CPClassRef>,
+	pub interfaces: Vec<CPClassRef>,
+	pub fields: Vec<IRFieldInfo>,
+	pub methods: Vec<LazyMethod>,
+	pub attributes: Attributes,
+}
+
+impl LazyClassFile {
+	/// Reads a class strictly and with the default limits, leaving the code of its methods for later.
+	pub fn read(bytes: &[u8]) -> Result<Self, IRClassfileError> {
+		Self::read_with(bytes, &ParseOptions::default()).map(|(class, _)| class)
+	}
+
+	pub fn read_with(bytes: &[u8], options: &ParseOptions) -> Result<(Self, Vec<ParseWarning>), IRClassfileError> {
+		let raw = IOClassFile::read_with(&mut Cursor::new(bytes), &options.limits)?;
+		let version = ClassFileVersion {
+			major: raw.major_version,
+			minor: raw.minor_version,
+		};
+		let cp = Shared::<[IRCpTag]>::from(IRCpTag::from_io(raw.cp)?);
+		let this_class = CPClassRef::from_cp(&cp, raw.this_class)?;
+		let super_class = match raw.super_class {
+			0 => None,
+			index => Some(CPClassRef::from_cp(&cp, index)?),
+		};
+		let interfaces = raw
+			.interfaces
+			.iter()
+			.map(|index| CPClassRef::from_cp(&cp, *index))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let shared_options = Shared::new(options.clone());
+		let mut ctx = ParseContext::new(&cp, options).with_version(version);
+		let fields = raw
+			.fields
+			.into_iter()
+			.map(|field| IRFieldInfo::from_io(&mut ctx, field))
+			.collect::<Result<Vec<_>, _>>()?;
+		let mut methods = Vec::with_capacity(raw.methods.len());
+		for method in raw.methods {
+			let mut attributes = Attributes::new();
+			let mut code = None;
+			for attribute in method.attributes {
+				let name = CPUtf8Ref::from_cp(ctx.cp, attribute.attribute_name_index)?;
+				if *name.data == "Code" && code.is_none() {
+					code = Some(LazyCode {
+						name,
+						length: attribute.attribute_length,
+						info: attribute.info,
+						position: attributes.len(),
+						parsed: OnceLock::new(),
+					});
+				} else {
+					attributes.push(IRAttributeInfo::from_io(&mut ctx, attribute)?);
+				}
+			}
+			methods.push(LazyMethod {
+				access_flags: MethodAccessFlags::from_bits_retain(method.access_flags),
+				name: CPUtf8Ref::from_cp(ctx.cp, method.name_index)?,
+				descriptor: CPUtf8Ref::from_cp(ctx.cp, method.descriptor_index)?,
+				attributes,
+				code,
+				version,
+				cp: cp.clone(),
+				options: shared_options.clone(),
+			});
+		}
+		let attributes = raw
+			.attributes
+			.into_iter()
+			.map(|attribute| IRAttributeInfo::from_io(&mut ctx, attribute))
+			.collect::<Result<Attributes, _>>()?;
+		let warnings = ctx.warnings;
+
+		let class = Self {
+			version,
+			cp,
+			access_flags: ClassAccessFlags::from_bits_retain(raw.access_flags),
+			this_class,
+			super_class,
+			interfaces,
+			fields,
+			methods,
+			attributes,
+		};
+		Ok((class, warnings))
+	}
+
+	pub fn class_name(&self) -> &str {
+		&self.this_class.data.data
+	}
+
+	/// Internal name of the super class, `None` for java/lang/Object and module-info.
+	pub fn super_name(&self) -> Option<&str> {
+		self.super_class.as_ref().map(|class| class.data.data.as_str())
+	}
+
+	pub fn interface_names(&self) -> impl Iterator<Item = &str> {
+		self.interfaces.iter().map(|class| class.data.data.as_str())
+	}
+
+	pub fn method(&self, name: &str, descriptor: &str) -> Option<&LazyMethod> {
+		self.methods
+			.iter()
+			.find(|method| method.name() == name && method.descriptor() == descriptor)
+	}
+
+	/// The class with the code of every method parsed.
+	pub fn into_class(self) -> Result<IRClassFile, IRClassfileError> {
+		let methods = self
+			.methods
+			.into_iter()
+			.map(LazyMethod::into_method)
+			.collect::<Result<_, _>>()?;
+		Ok(IRClassFile {
+			magic: 0xCAFEBABE,
+			version: self.version,
+			cp: self.cp.to_vec(),
+			access_flags: self.access_flags,
+			this_class: self.this_class,
+			super_class: self.super_class,
+			interfaces: self.interfaces,
+			fields: self.fields,
+			methods,
+			attributes: self.attributes,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn parse_code_when_asked() {
+		let bytes = assemble(
+			".class public super p/Main\n.field static count I\n.method public static run()I\n.throws \
+			 java/io/IOException\ngetstatic p/Main/count I\n.line 7\nireturn\n.end method\n.method public abstract \
+			 stop()V\n.end method",
+		)
+		.unwrap()
+		.to_bytes()
+		.unwrap();
+		let class = LazyClassFile::read(&bytes).unwrap();
+		assert_eq!(class.class_name(), "p/Main");
+		assert_eq!(class.fields[0].name(), "count");
+		let run = class.method("run", "()I").unwrap();
+		assert!(!run.is_parsed());
+		assert_eq!(
+			run.attributes.exceptions().unwrap()[0].data.data.as_str(),
+			"java/io/IOException"
+		);
+
+		let eager = IRClassFile::read(&bytes).unwrap();
+		let code = &eager.methods[0].code().unwrap().code;
+		assert_eq!(&run.code().unwrap().unwrap().code, code);
+		assert!(run.is_parsed());
+		assert!(class.method("stop", "()V").unwrap().code().unwrap().is_none());
+		assert_eq!(class.into_class().unwrap().to_bytes().unwrap(), bytes);
+
+		// Broken code only fails once it's parsed, here a code_length running past the attribute.
+		let mut broken = bytes.clone();
+		let at = broken.windows(code.len()).position(|window| window == code).unwrap();
+		broken[at - 1] = 0xFF;
+		let class = LazyClassFile::read(&broken).unwrap();
+		assert!(class.methods[0].code().is_err());
+		assert!(IRClassFile::read(&broken).is_err());
+	}
+}
@@ -1,14 +1,110 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, io::Cursor};
 
-use attribute::IRAttributeInfo;
-use class_pool::{CPClassRef, CPUtf8Ref, IRClassfileError, IRCpTag};
+use attribute::{
+	Attributes, BootstrapMethodsMethod, CodeAttribute, ConstantValueAttribute, IRAttributeInfo, MethodParametersParam,
+	RuntimeAnnotation,
+};
+use class_pool::{CPClassRef, CPConstValueRefKind, CPInvokeDynamicRef, CPUtf8Ref, IRClassfileError, IRCpTag};
+use flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags};
 use maya_classfile_io::{IOClassFile, IOFieldInfo, IOMethodInfo};
+use module::ModuleInfo;
+use parse::{ParseContext, ParseOptions, ParseWarning};
+use record::RecordView;
 
+pub mod analysis;
+pub mod archive;
+#[cfg(feature = "arena")]
+pub mod arena;
+pub mod asm;
+#[cfg(feature = "async")]
+pub mod async_read;
 pub mod attribute;
+pub mod borrowed;
+pub mod builder;
+pub mod canonical;
 pub mod class_pool;
+pub mod classpath;
 pub mod code;
+pub mod compact;
+pub mod compat;
+pub mod dependencies;
+pub mod descriptor;
+pub mod diff;
+pub mod disasm;
+pub mod docgen;
+pub mod exceptions;
+#[cfg(feature = "serde")]
+pub mod export;
+pub mod filter;
+pub mod flags;
+pub mod indy;
+pub mod inline;
+pub mod jar;
+pub mod jdeps;
+pub mod jimage;
+pub mod jni;
+pub mod lazy;
+pub mod listing;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+pub mod mappings;
+#[cfg(feature = "maven")]
+pub mod maven;
+pub mod metrics;
+pub mod module;
+pub mod names;
+pub mod nest;
+pub mod nested;
+pub mod package;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod parse;
+pub mod pattern;
+pub mod peephole;
+pub mod persistent;
+pub mod pool_stats;
+pub mod query;
+pub mod record;
+pub mod relocate;
+pub mod remap;
+pub mod resolver;
+pub mod retention;
+pub mod sealed;
+pub mod search;
+pub mod semantic;
+pub mod signature;
+pub mod staging;
+pub mod stream;
+pub mod strip;
+pub mod symbols;
+pub mod text;
+pub mod transform;
+pub mod version;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watermark;
+pub mod write;
 
-#[derive(Debug, PartialEq, Eq)]
+mod json;
+#[cfg(test)]
+mod scratch;
+
+/// How the IR shares strings between its constant pool and the references into it: `Rc`, or `Arc` with the `sync`
+/// feature so classes can be parsed and analyzed across threads.
+#[cfg(not(feature = "sync"))]
+pub type Shared<T> = std::rc::Rc<T>;
+#[cfg(feature = "sync")]
+pub type Shared<T> = std::sync::Arc<T>;
+
+// Anything in the IR that isn't thread-safe would break the feature, this doesn't compile then.
+#[cfg(feature = "sync")]
+const _: () = {
+	const fn thread_safe<T: Send + Sync>() {}
+	thread_safe::<IRClassFile>();
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClassFileVersion {
 	pub major: u16,
 	pub minor: u16,
@@ -30,139 +126,206 @@ impl Ord for ClassFileVersion {
 	}
 }
 
-pub struct AccessFlags;
-impl AccessFlags {
-	pub const PUBLIC: u16 = 0x001;
-	pub const FINAL: u16 = 0x010;
-	pub const SUPER: u16 = 0x020;
-	pub const INTERFACE: u16 = 0x0200;
-	pub const ABSTRACT: u16 = 0x0400;
-	pub const SYNTHETIC: u16 = 0x1000;
-	pub const ANNOTATION: u16 = 0x2000;
-	pub const ENUM: u16 = 0x4000;
-	pub const MODULE: u16 = 0x8000;
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IRFieldInfo {
-	pub access_flags: u16,
+	pub access_flags: FieldAccessFlags,
 	pub name: CPUtf8Ref,
 	pub descriptor: CPUtf8Ref,
-	pub attributes: Vec<IRAttributeInfo>,
+	pub attributes: Attributes,
 }
 
 impl IRFieldInfo {
-	pub fn from_io(cp: &[IRCpTag], raw: IOFieldInfo) -> Result<Self, IRClassfileError> {
-		let name = CPUtf8Ref::new(
-			raw.name_index,
-			cp.get(raw.name_index as usize - 1).expect("invalid idx"),
-		);
-		let descriptor = CPUtf8Ref::new(
-			raw.descriptor_index,
-			cp.get(raw.descriptor_index as usize - 1).expect("invalid idx"),
-		);
+	pub fn from_io(ctx: &mut ParseContext, raw: IOFieldInfo) -> Result<Self, IRClassfileError> {
+		let name = CPUtf8Ref::from_cp(ctx.cp, raw.name_index)?;
+		let descriptor = CPUtf8Ref::from_cp(ctx.cp, raw.descriptor_index)?;
 		let attributes = raw
 			.attributes
 			.into_iter()
-			.map(|attr| IRAttributeInfo::from_io(cp, attr))
-			.collect::<Result<Vec<_>, _>>()?;
+			.map(|attr| IRAttributeInfo::from_io(ctx, attr))
+			.collect::<Result<Attributes, _>>()?;
 
 		Ok(Self {
-			access_flags: raw.access_flags,
+			access_flags: FieldAccessFlags::from_bits_retain(raw.access_flags),
 			name,
 			descriptor,
 			attributes,
 		})
 	}
+
+	pub fn name(&self) -> &str {
+		&self.name.data
+	}
+
+	pub fn descriptor(&self) -> &str {
+		&self.descriptor.data
+	}
+
+	/// The initial value of a `static final` constant field. Booleans, chars, bytes and shorts are stored as `Int`.
+	pub fn constant_value(&self) -> Option<CPConstValueRefKind> {
+		self.attributes.constant_value().map(ConstantValueAttribute::value)
+	}
+
+	/// The annotation of type `descriptor` on this field, visible or invisible.
+	pub fn annotation(&self, descriptor: &str) -> Option<&RuntimeAnnotation> {
+		self.attributes.annotation(descriptor)
+	}
+
+	pub fn is_annotated_with(&self, descriptor: &str) -> bool {
+		self.attributes.has_annotation(descriptor)
+	}
+
+	/// Generated by the compiler, e.g. `this$0` or `$assertionsDisabled`. Compilers before Java 5 marked these with a
+	/// `Synthetic` attribute instead of the flag.
+	pub fn is_synthetic(&self) -> bool {
+		self.access_flags.is_synthetic() || self.attributes.is_synthetic()
+	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IRMethodInfo {
-	pub access_flags: u16,
+	pub access_flags: MethodAccessFlags,
 	pub name: CPUtf8Ref,
 	pub descriptor: CPUtf8Ref,
-	pub attributes: Vec<IRAttributeInfo>,
+	pub attributes: Attributes,
 }
 
 impl IRMethodInfo {
-	pub fn from_io(cp: &[IRCpTag], raw: IOMethodInfo) -> Result<Self, IRClassfileError> {
-		let name = CPUtf8Ref::new(
-			raw.name_index,
-			cp.get(raw.name_index as usize - 1).expect("invalid idx"),
-		);
-		let descriptor = CPUtf8Ref::new(
-			raw.descriptor_index,
-			cp.get(raw.descriptor_index as usize - 1).expect("invalid idx"),
-		);
+	pub fn from_io(ctx: &mut ParseContext, raw: IOMethodInfo) -> Result<Self, IRClassfileError> {
+		let name = CPUtf8Ref::from_cp(ctx.cp, raw.name_index)?;
+		let descriptor = CPUtf8Ref::from_cp(ctx.cp, raw.descriptor_index)?;
 		let attributes = raw
 			.attributes
 			.into_iter()
-			.map(|attr| IRAttributeInfo::from_io(cp, attr))
-			.collect::<Result<Vec<_>, _>>()?;
+			.map(|attr| IRAttributeInfo::from_io(ctx, attr))
+			.collect::<Result<Attributes, _>>()?;
 
 		Ok(Self {
-			access_flags: raw.access_flags,
+			access_flags: MethodAccessFlags::from_bits_retain(raw.access_flags),
 			name,
 			descriptor,
 			attributes,
 		})
 	}
+
+	pub fn name(&self) -> &str {
+		&self.name.data
+	}
+
+	pub fn descriptor(&self) -> &str {
+		&self.descriptor.data
+	}
+
+	/// `None` for abstract and native methods.
+	pub fn code(&self) -> Option<&CodeAttribute> {
+		self.attributes.code()
+	}
+
+	/// The annotation of type `descriptor` on this method, visible or invisible.
+	pub fn annotation(&self, descriptor: &str) -> Option<&RuntimeAnnotation> {
+		self.attributes.annotation(descriptor)
+	}
+
+	pub fn is_annotated_with(&self, descriptor: &str) -> bool {
+		self.attributes.has_annotation(descriptor)
+	}
+
+	/// Generated by the compiler, e.g. lambda bodies, accessors and bridges. Compilers before Java 5 marked these with a
+	/// `Synthetic` attribute instead of the flag.
+	pub fn is_synthetic(&self) -> bool {
+		self.access_flags.is_synthetic() || self.attributes.is_synthetic()
+	}
+
+	/// A bridge the compiler generated to forward an erased or covariant override to the method it overrides with.
+	pub fn is_bridge(&self) -> bool {
+		self.access_flags.is_bridge()
+	}
+
+	/// Whether the parameter at `index` is implicitly declared, like the outer instance of an inner class
+	/// constructor. Only known when the method has a `MethodParameters` attribute, `false` otherwise.
+	pub fn is_mandated_parameter(&self, index: usize) -> bool {
+		self.attributes
+			.method_parameters()
+			.and_then(|parameters| parameters.get(index))
+			.is_some_and(MethodParametersParam::is_mandated_parameter)
+	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IRClassFile {
 	pub magic: u32,
 	pub version: ClassFileVersion,
+	// index i + 1 in the pool, in class file order
 	pub cp: Vec<IRCpTag>,
-	pub access_flags: u16,
+	pub access_flags: ClassAccessFlags,
 	pub this_class: CPClassRef,
-	pub super_class: CPClassRef,
+	// None for java/lang/Object and module-info
+	pub super_class: Option<CPClassRef>,
 	pub interfaces: Vec<CPClassRef>,
 	pub fields: Vec<IRFieldInfo>,
 	pub methods: Vec<IRMethodInfo>,
-	pub attributes: Vec<IRAttributeInfo>,
+	pub attributes: Attributes,
 }
 
 impl IRClassFile {
+	/// Reads and parses a class in one go, strictly and with the default limits.
+	pub fn read(bytes: &[u8]) -> Result<Self, IRClassfileError> {
+		Self::read_with(bytes, &ParseOptions::default()).map(|(class, _)| class)
+	}
+
+	pub fn read_with(bytes: &[u8], options: &ParseOptions) -> Result<(Self, Vec<ParseWarning>), IRClassfileError> {
+		let raw = IOClassFile::read_with(&mut Cursor::new(bytes), &options.limits)?;
+		Self::from_io_with(raw, options)
+	}
+
 	pub fn from_io(raw: IOClassFile) -> Result<Self, IRClassfileError> {
+		Self::from_io_with(raw, &ParseOptions::default()).map(|(class, _)| class)
+	}
+
+	pub fn from_io_with(
+		raw: IOClassFile,
+		options: &ParseOptions,
+	) -> Result<(Self, Vec<ParseWarning>), IRClassfileError> {
 		let magic = raw.magic;
 		let version = ClassFileVersion {
 			major: raw.major_version,
 			minor: raw.minor_version,
 		};
-		let cp = IRCpTag::from_io(raw.cp).unwrap();
-		let access_flags = raw.access_flags;
-		let this_class = CPClassRef::new(
-			raw.this_class,
-			cp.get(raw.this_class.saturating_sub(1) as usize).unwrap(),
-		);
-		let super_class = CPClassRef::new(
-			raw.super_class,
-			cp.get(raw.super_class.saturating_sub(1) as usize).unwrap(),
-		);
+		let cp = IRCpTag::from_io(raw.cp)?;
+		let access_flags = ClassAccessFlags::from_bits_retain(raw.access_flags);
+		let this_class = CPClassRef::from_cp(&cp, raw.this_class)?;
+		let super_class = if raw.super_class == 0 {
+			None
+		} else {
+			Some(CPClassRef::from_cp(&cp, raw.super_class)?)
+		};
 		let interfaces = raw
 			.interfaces
 			.iter()
-			.copied()
-			.map(|idx| CPClassRef::new(idx, cp.get(idx as usize - 1).unwrap()))
-			.collect();
+			.map(|idx| CPClassRef::from_cp(&cp, *idx))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let mut ctx = ParseContext::new(&cp, options).with_version(version);
 		let fields = raw
 			.fields
 			.into_iter()
-			.map(|f| IRFieldInfo::from_io(&cp, f))
+			.map(|f| IRFieldInfo::from_io(&mut ctx, f))
 			.collect::<Result<Vec<_>, _>>()?;
 		let methods = raw
 			.methods
 			.into_iter()
-			.map(|f| IRMethodInfo::from_io(&cp, f))
+			.map(|f| IRMethodInfo::from_io(&mut ctx, f))
 			.collect::<Result<Vec<_>, _>>()?;
 		let attributes = raw
 			.attributes
 			.into_iter()
-			.map(|attr| IRAttributeInfo::from_io(&cp, attr))
-			.collect::<Result<Vec<_>, _>>()?;
+			.map(|attr| IRAttributeInfo::from_io(&mut ctx, attr))
+			.collect::<Result<Attributes, _>>()?;
+		let warnings = ctx.warnings;
 
-		Ok(Self {
+		let class = Self {
 			magic,
 			version,
 			cp,
@@ -173,6 +336,138 @@ impl IRClassFile {
 			fields,
 			methods,
 			attributes,
-		})
+		};
+
+		Ok((class, warnings))
+	}
+
+	/// Internal name of this class.
+	pub fn class_name(&self) -> &str {
+		&self.this_class.data.data
+	}
+
+	/// Internal name of the super class, `None` for java/lang/Object and module-info.
+	pub fn super_name(&self) -> Option<&str> {
+		self.super_class.as_ref().map(|class| class.data.data.as_str())
+	}
+
+	pub fn interface_names(&self) -> impl Iterator<Item = &str> {
+		self.interfaces.iter().map(|class| class.data.data.as_str())
+	}
+
+	pub fn constant_pool(&self) -> &[IRCpTag] {
+		&self.cp
+	}
+
+	pub fn fields(&self) -> &[IRFieldInfo] {
+		&self.fields
+	}
+
+	pub fn methods(&self) -> &[IRMethodInfo] {
+		&self.methods
+	}
+
+	pub fn find_method(&self, name: &str, descriptor: &str) -> Option<&IRMethodInfo> {
+		self.methods
+			.iter()
+			.find(|method| method.name() == name && method.descriptor() == descriptor)
+	}
+
+	pub fn find_method_mut(&mut self, name: &str, descriptor: &str) -> Option<&mut IRMethodInfo> {
+		self.methods
+			.iter_mut()
+			.find(|method| method.name() == name && method.descriptor() == descriptor)
+	}
+
+	pub fn find_field(&self, name: &str, descriptor: &str) -> Option<&IRFieldInfo> {
+		self.fields
+			.iter()
+			.find(|field| field.name() == name && field.descriptor() == descriptor)
+	}
+
+	pub fn find_field_mut(&mut self, name: &str, descriptor: &str) -> Option<&mut IRFieldInfo> {
+		self.fields
+			.iter_mut()
+			.find(|field| field.name() == name && field.descriptor() == descriptor)
+	}
+
+	/// Every overload called `name`, in declaration order.
+	pub fn methods_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a IRMethodInfo> {
+		self.methods.iter().filter(move |method| method.name() == name)
+	}
+
+	pub fn attributes(&self) -> &Attributes {
+		&self.attributes
+	}
+
+	pub fn is_module_info(&self) -> bool {
+		self.access_flags.is_module()
+	}
+
+	pub fn is_package_info(&self) -> bool {
+		!self.is_module_info() && names::simple_name(self.class_name()) == "package-info"
+	}
+
+	/// The annotations of the package, visible and invisible. Empty unless this is a package-info class.
+	pub fn package_annotations(&self) -> impl Iterator<Item = &RuntimeAnnotation> {
+		self.attributes.annotations().filter(|_| self.is_package_info())
+	}
+
+	/// The module declared by this class, `None` unless this is a module-info class.
+	pub fn module_info(&self) -> Option<ModuleInfo<'_>> {
+		if !self.is_module_info() {
+			return None;
+		}
+
+		ModuleInfo::from_class(self)
+	}
+
+	/// The components of the record, `None` unless this is a record class.
+	pub fn record_view(&self) -> Option<RecordView<'_>> {
+		RecordView::from_class(self)
+	}
+
+	/// The annotation of type `descriptor` on this class, visible or invisible.
+	pub fn annotation(&self, descriptor: &str) -> Option<&RuntimeAnnotation> {
+		self.attributes.annotation(descriptor)
+	}
+
+	pub fn is_annotated_with(&self, descriptor: &str) -> bool {
+		self.attributes.has_annotation(descriptor)
+	}
+
+	/// The bootstrap method and static arguments an `invokedynamic` call site of this class is linked with.
+	pub fn bootstrap_method(&self, indy: &CPInvokeDynamicRef) -> Result<&BootstrapMethodsMethod, IRClassfileError> {
+		indy::bootstrap_method(self.attributes.bootstrap_methods().unwrap_or_default(), indy)
+	}
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn serde_round_trip() {
+		let class = assemble(
+			r#"
+			.class public super p/Serde
+			.super java/lang/Object
+			.field static final RATE D = 0.5
+			.method public static run()I
+				.line 3
+				ldc "serde"
+				invokevirtual java/lang/String/length()I
+				ireturn
+			.end method
+			"#,
+		)
+		.unwrap();
+		let bytes = class.to_bytes().unwrap();
+
+		let json = serde_json::to_string(&class).unwrap();
+		assert!(json.contains(r#""access_flags":"PUBLIC | SUPER""#));
+		let read: IRClassFile = serde_json::from_str(&json).unwrap();
+		assert_eq!(read.to_bytes().unwrap(), bytes);
 	}
 }
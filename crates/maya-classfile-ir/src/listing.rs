@@ -0,0 +1,617 @@
+// Code as a list of instructions that refer to positions by label instead of by pc, so passes can insert and remove
+// instructions without fixing up offsets: jumps, switches, the exception table, line numbers and local variable
+// ranges all follow the labels. `to_code` lays it out again through the `CodeBuilder`.
+//
+// The StackMapTable and any other attribute of the code is dropped on the way back. Frames change with the code and
+// have to be computed again, see `analysis::frames`, and the rest refers to pcs in ways this doesn't follow.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+	attribute::{
+		CodeAttribute, IRAttribute, IRAttributeInfo, LineNumberTableAttribute, LineNumberTableAttributeEntry,
+		LocalVariableTableEntry, LocalVariableTypeTableEntry,
+	},
+	builder::CodeBuilder,
+	class_pool::{CPClassRef, CPUtf8Ref, IRClassfileError, IRCpTag},
+	code::{Instructions, Opcodes},
+};
+
+/// A position in a `Listing`, made by `Listing::new_label`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LabelId(u32);
+
+#[derive(Debug, Clone)]
+pub enum Item {
+	/// Binds a label to the position of the next instruction.
+	Label(LabelId),
+	/// The source line of the instructions that follow.
+	Line(u16),
+	/// Anything that doesn't jump. Never one of the instructions below.
+	Insn(Instructions),
+	/// Any `if<cond>`, `goto` or `jsr` by opcode, wide forms being their short ones.
+	Jump { opcode: u8, target: LabelId },
+	TableSwitch {
+		low: i32,
+		default: LabelId,
+		targets: Vec<LabelId>,
+	},
+	LookupSwitch {
+		default: LabelId,
+		pairs: Vec<(i32, LabelId)>,
+	},
+}
+
+impl Item {
+	/// Whether this is executed, rather than describing a position.
+	pub fn is_instruction(&self) -> bool {
+		!matches!(self, Self::Label(_) | Self::Line(_))
+	}
+
+	/// The labels this can jump to.
+	pub fn targets(&self) -> Vec<LabelId> {
+		match self {
+			Self::Jump { target, .. } => vec![*target],
+			Self::TableSwitch { default, targets, .. } => {
+				std::iter::once(*default).chain(targets.iter().copied()).collect()
+			}
+			Self::LookupSwitch { default, pairs } => std::iter::once(*default)
+				.chain(pairs.iter().map(|(_, target)| *target))
+				.collect(),
+			_ => Vec::new(),
+		}
+	}
+
+	/// Whether execution can continue with the next item. `jsr` does, once the subroutine returns.
+	pub fn falls_through(&self) -> bool {
+		match self {
+			Self::Label(_) | Self::Line(_) => true,
+			Self::Insn(insn) => insn.falls_through(),
+			Self::Jump { opcode, .. } => *opcode != Opcodes::GOTO,
+			Self::TableSwitch { .. } | Self::LookupSwitch { .. } => false,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingHandler {
+	pub start: LabelId,
+	// exclusive
+	pub end: LabelId,
+	pub handler: LabelId,
+	// an internal name, None catching everything
+	pub catch_type: Option<String>,
+}
+
+/// An entry of the LocalVariableTable, or of the LocalVariableTypeTable with `descriptor` being the signature.
+#[derive(Debug, Clone)]
+pub struct ListingLocal {
+	pub start: LabelId,
+	// exclusive
+	pub end: LabelId,
+	pub name: CPUtf8Ref,
+	pub descriptor: CPUtf8Ref,
+	pub index: u16,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Listing {
+	pub items: Vec<Item>,
+	pub handlers: Vec<ListingHandler>,
+	pub local_variables: Vec<ListingLocal>,
+	pub local_variable_types: Vec<ListingLocal>,
+	pub max_stack: u16,
+	pub max_locals: u16,
+	next_label: u32,
+}
+
+impl Listing {
+	pub fn from_code(cp: &[IRCpTag], code: &CodeAttribute) -> Result<Self, IRClassfileError> {
+		let instructions = Instructions::read_all(cp, &code.code)?;
+		let code_length = code.code.len() as u32;
+		let is_position = |pc: u32| pc == code_length || instructions.binary_search_by_key(&pc, |(pc, _)| *pc).is_ok();
+
+		let mut positions = BTreeSet::new();
+		let mut add = |what: &'static str, pc: u32| match is_position(pc) {
+			true => Ok(positions.insert(pc)),
+			false => Err(IRClassfileError::InvalidCodePosition { what, pc }),
+		};
+		for (pc, insn) in &instructions {
+			for target in insn.branch_targets(*pc) {
+				add("instruction", target).map_err(|_| IRClassfileError::InvalidBranchTarget { pc: *pc, target })?;
+			}
+		}
+		for entry in &code.exception_table {
+			for pc in [entry.start_pc, entry.end_pc, entry.handler_pc] {
+				add("exception table", pc as u32)?;
+			}
+		}
+		let mut locals = Vec::new();
+		let mut local_types = Vec::new();
+		let mut lines = BTreeMap::<u32, Vec<u16>>::new();
+		for attr in code.attributes.iter() {
+			match &attr.attr {
+				IRAttribute::LineNumberTable(table) => {
+					for entry in &table.line_number_table {
+						lines.entry(entry.start_pc as u32).or_default().push(entry.line_number);
+					}
+				}
+				IRAttribute::LocalVariableTable { table } => {
+					for entry in table {
+						add("LocalVariableTable", entry.start_pc as u32)?;
+						add("LocalVariableTable", entry.start_pc as u32 + entry.length as u32)?;
+						locals.push((
+							entry.start_pc,
+							entry.length,
+							&entry.name,
+							&entry.descriptor,
+							entry.index,
+						));
+					}
+				}
+				IRAttribute::LocalVariableTypeTable { table } => {
+					for entry in table {
+						add("LocalVariableTypeTable", entry.start_pc as u32)?;
+						add("LocalVariableTypeTable", entry.start_pc as u32 + entry.length as u32)?;
+						local_types.push((entry.start_pc, entry.length, &entry.name, &entry.signature, entry.index));
+					}
+				}
+				_ => {}
+			}
+		}
+
+		let mut listing = Self {
+			items: Vec::with_capacity(instructions.len() + positions.len()),
+			handlers: Vec::new(),
+			local_variables: Vec::new(),
+			local_variable_types: Vec::new(),
+			max_stack: code.max_stack,
+			max_locals: code.max_locals,
+			next_label: 0,
+		};
+		let labels = positions
+			.into_iter()
+			.map(|pc| (pc, listing.new_label()))
+			.collect::<BTreeMap<_, _>>();
+		let label = |pc: u32| labels[&pc];
+
+		for (pc, insn) in instructions {
+			if let Some(label) = labels.get(&pc) {
+				listing.items.push(Item::Label(*label));
+			}
+			for line in lines.get(&pc).into_iter().flatten() {
+				listing.items.push(Item::Line(*line));
+			}
+			let targets = insn.branch_targets(pc);
+			listing.items.push(match insn {
+				Instructions::TABLESWITCH { low, .. } => Item::TableSwitch {
+					low,
+					default: label(targets[0]),
+					targets: targets[1..].iter().map(|pc| label(*pc)).collect(),
+				},
+				Instructions::LOOKUPSWITCH { pairs, .. } => Item::LookupSwitch {
+					default: label(targets[0]),
+					pairs: pairs
+						.iter()
+						.zip(&targets[1..])
+						.map(|((key, _), pc)| (*key, label(*pc)))
+						.collect(),
+				},
+				insn if !targets.is_empty() => Item::Jump {
+					opcode: match insn.opcode() {
+						Opcodes::GOTO_W => Opcodes::GOTO,
+						Opcodes::JSR_W => Opcodes::JSR,
+						opcode => opcode,
+					},
+					target: label(targets[0]),
+				},
+				insn => Item::Insn(insn),
+			});
+		}
+		if let Some(label) = labels.get(&code_length) {
+			listing.items.push(Item::Label(*label));
+		}
+
+		for entry in &code.exception_table {
+			listing.handlers.push(ListingHandler {
+				start: label(entry.start_pc as u32),
+				end: label(entry.end_pc as u32),
+				handler: label(entry.handler_pc as u32),
+				catch_type: match entry.catch_type {
+					0 => None,
+					index => Some(CPClassRef::from_cp(cp, index)?.data.data.to_string()),
+				},
+			});
+		}
+		for (table, entries) in [
+			(&mut listing.local_variables, locals),
+			(&mut listing.local_variable_types, local_types),
+		] {
+			table.extend(
+				entries
+					.into_iter()
+					.map(|(start_pc, length, name, descriptor, index)| ListingLocal {
+						start: label(start_pc as u32),
+						end: label(start_pc as u32 + length as u32),
+						name: name.clone(),
+						descriptor: descriptor.clone(),
+						index,
+					}),
+			);
+		}
+		Ok(listing)
+	}
+
+	pub fn new_label(&mut self) -> LabelId {
+		self.next_label += 1;
+		LabelId(self.next_label - 1)
+	}
+
+	/// Gives the labels of `other` new ones of this listing, so its items, handlers and local variables can be moved
+	/// in.
+	pub fn adopt(&mut self, other: &mut Listing) {
+		let offset = self.next_label;
+		let relabel = |label: &mut LabelId| label.0 += offset;
+		for item in &mut other.items {
+			match item {
+				Item::Label(label) | Item::Jump { target: label, .. } => relabel(label),
+				Item::TableSwitch { default, targets, .. } => {
+					relabel(default);
+					targets.iter_mut().for_each(relabel);
+				}
+				Item::LookupSwitch { default, pairs } => {
+					relabel(default);
+					pairs.iter_mut().for_each(|(_, target)| relabel(target));
+				}
+				Item::Line(_) | Item::Insn(_) => {}
+			}
+		}
+		for handler in &mut other.handlers {
+			[&mut handler.start, &mut handler.end, &mut handler.handler]
+				.into_iter()
+				.for_each(relabel);
+		}
+		for local in other.local_variables.iter_mut().chain(&mut other.local_variable_types) {
+			relabel(&mut local.start);
+			relabel(&mut local.end);
+		}
+		self.next_label += other.next_label;
+		other.next_label = self.next_label;
+	}
+
+	/// The index of the item binding `label`.
+	pub fn position(&self, label: LabelId) -> Option<usize> {
+		self.items
+			.iter()
+			.position(|item| matches!(item, Item::Label(bound) if *bound == label))
+	}
+
+	/// The items from `at` up to the next label, code nothing else jumps into.
+	pub fn block(&self, at: usize) -> &[Item] {
+		let rest = &self.items[at..];
+		let end = rest
+			.iter()
+			.position(|item| matches!(item, Item::Label(_)))
+			.unwrap_or(rest.len());
+		&rest[..end]
+	}
+
+	/// The instructions executed from `label` on, in code order.
+	pub fn code_at(&self, label: LabelId) -> impl Iterator<Item = &Item> {
+		let start = self.position(label).unwrap_or(self.items.len());
+		self.items[start..].iter().filter(|item| item.is_instruction())
+	}
+
+	/// Removes the instructions no path from the start of the code reaches, and returns how many there were. A
+	/// handler is reached once anything it covers is.
+	pub fn remove_unreachable(&mut self) -> usize {
+		let positions = self.label_positions();
+		let mut reachable = vec![false; self.items.len()];
+		let mut worklist = vec![0];
+		let mut handlers = self.handlers.iter().collect::<Vec<_>>();
+		loop {
+			while let Some(i) = worklist.pop() {
+				if i >= self.items.len() || reachable[i] {
+					continue;
+				}
+				reachable[i] = true;
+				let item = &self.items[i];
+				if item.falls_through() {
+					worklist.push(i + 1);
+				}
+				worklist.extend(item.targets().iter().filter_map(|label| positions.get(label)));
+			}
+
+			let before = handlers.len();
+			handlers.retain(|handler| {
+				let range = positions[&handler.start]..positions[&handler.end];
+				let covered = range
+					.into_iter()
+					.any(|i| reachable[i] && self.items[i].is_instruction());
+				if covered {
+					worklist.push(positions[&handler.handler]);
+				}
+				!covered
+			});
+			if handlers.len() == before {
+				break;
+			}
+		}
+
+		let mut removed = 0;
+		let mut reachable = reachable.into_iter();
+		self.items.retain(|item| {
+			let keep = reachable.next().expect("one per item") || matches!(item, Item::Label(_));
+			removed += (!keep && item.is_instruction()) as usize;
+			keep
+		});
+		removed
+	}
+
+	/// Drops handlers that no longer cover any instruction, then labels nothing refers to.
+	pub fn prune(&mut self) {
+		let positions = self.label_positions();
+		let items = &self.items;
+		self.handlers.retain(|handler| {
+			items[positions[&handler.start]..positions[&handler.end]]
+				.iter()
+				.any(Item::is_instruction)
+		});
+
+		let mut used = BTreeSet::new();
+		for item in &self.items {
+			used.extend(item.targets());
+		}
+		for handler in &self.handlers {
+			used.extend([handler.start, handler.end, handler.handler]);
+		}
+		for local in self.local_variables.iter().chain(&self.local_variable_types) {
+			used.extend([local.start, local.end]);
+		}
+		self.items
+			.retain(|item| !matches!(item, Item::Label(label) if !used.contains(label)));
+	}
+
+	fn label_positions(&self) -> BTreeMap<LabelId, usize> {
+		self.items
+			.iter()
+			.enumerate()
+			.filter_map(|(i, item)| match item {
+				Item::Label(label) => Some((*label, i)),
+				_ => None,
+			})
+			.collect()
+	}
+
+	/// Lays the code out again. Handlers and local variables whose range came out empty are left out.
+	pub fn to_code(&self, cp: &mut Vec<IRCpTag>) -> Result<CodeAttribute, IRClassfileError> {
+		let mut code = CodeBuilder::new(cp);
+		// Only labels still in the items get one, the builder wants every label it made bound.
+		let labels = self
+			.items
+			.iter()
+			.filter_map(|item| match item {
+				Item::Label(id) => Some((*id, code.new_label())),
+				_ => None,
+			})
+			.collect::<BTreeMap<_, _>>();
+		let label = |id: &LabelId| labels.get(id).copied().ok_or(IRClassfileError::UnboundLabel(id.0));
+		let mut lines = Vec::new();
+		for item in &self.items {
+			match item {
+				Item::Label(id) => {
+					code.bind(label(id)?)?;
+				}
+				Item::Line(line) => lines.push((code.mark(), *line)),
+				Item::Insn(insn) => {
+					code.insn(insn.clone());
+				}
+				Item::Jump { opcode, target } => {
+					code.branch(*opcode, label(target)?)?;
+				}
+				Item::TableSwitch { low, default, targets } => {
+					let targets = targets.iter().map(label).collect::<Result<_, _>>()?;
+					code.table_switch(*low, label(default)?, targets);
+				}
+				Item::LookupSwitch { default, pairs } => {
+					let pairs = pairs
+						.iter()
+						.map(|(key, target)| Ok((*key, label(target)?)))
+						.collect::<Result<_, IRClassfileError>>()?;
+					code.lookup_switch(label(default)?, pairs);
+				}
+			}
+		}
+		for handler in &self.handlers {
+			code.try_catch(
+				label(&handler.start)?,
+				label(&handler.end)?,
+				label(&handler.handler)?,
+				handler.catch_type.as_deref(),
+			)?;
+		}
+		code.max_stack(self.max_stack).max_locals(self.max_locals);
+		let (mut code, positions) = code.build_with_labels()?;
+		code.exception_table.retain(|entry| entry.start_pc < entry.end_pc);
+
+		let code_length = code.code.len() as u32;
+		let line_number_table = lines
+			.iter()
+			.map(|(label, line)| (positions.pc(*label), *line))
+			.filter(|(pc, _)| *pc < code_length)
+			.map(|(pc, line)| LineNumberTableAttributeEntry {
+				start_pc: pc as u16,
+				line_number: line,
+			})
+			.collect::<Vec<_>>();
+		let mut local_variables = Vec::new();
+		let mut local_variable_types = Vec::new();
+		for (locals, typed) in [(&self.local_variables, false), (&self.local_variable_types, true)] {
+			for local in locals {
+				let start = positions.pc(label(&local.start)?);
+				let end = positions.pc(label(&local.end)?);
+				if end <= start {
+					continue;
+				}
+				let (start_pc, length) = (start as u16, (end - start) as u16);
+				match typed {
+					false => local_variables.push(LocalVariableTableEntry {
+						start_pc,
+						length,
+						name: local.name.clone(),
+						descriptor: local.descriptor.clone(),
+						index: local.index,
+					}),
+					true => local_variable_types.push(LocalVariableTypeTableEntry {
+						start_pc,
+						length,
+						name: local.name.clone(),
+						signature: local.descriptor.clone(),
+						index: local.index,
+					}),
+				}
+			}
+		}
+
+		let mut attributes = Vec::new();
+		if !line_number_table.is_empty() {
+			attributes.push((
+				"LineNumberTable",
+				IRAttribute::LineNumberTable(LineNumberTableAttribute { line_number_table }),
+			));
+		}
+		if !local_variables.is_empty() {
+			attributes.push((
+				"LocalVariableTable",
+				IRAttribute::LocalVariableTable { table: local_variables },
+			));
+		}
+		if !local_variable_types.is_empty() {
+			attributes.push((
+				"LocalVariableTypeTable",
+				IRAttribute::LocalVariableTypeTable {
+					table: local_variable_types,
+				},
+			));
+		}
+		for (name, attr) in attributes {
+			code.attributes.push(IRAttributeInfo {
+				name: CPUtf8Ref::find_or_add(cp, name)?,
+				length: 0,
+				attr,
+			});
+		}
+		Ok(code)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{builder::ClassBuilder, code::Opcodes};
+
+	#[test]
+	fn round_trip_and_edit() {
+		let mut builder = ClassBuilder::new("p/C").unwrap();
+		let name = CPUtf8Ref::find_or_add(builder.cp(), "x").unwrap();
+		let descriptor = CPUtf8Ref::find_or_add(builder.cp(), "I").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		let (start, end, handler) = (code.new_label(), code.new_label(), code.new_label());
+		// 0: iconst_1, 1: istore_1, 2: nop, 3: iload_1, 4: ifeq +5, 7: iload_1, 8: ireturn, 9: pop, 10: iconst_0,
+		// 11: ireturn
+		code.insn(Instructions::ICONST_1)
+			.insn(Instructions::ISTORE(1))
+			.bind(start)
+			.unwrap()
+			.insn(Instructions::NOP)
+			.insn(Instructions::ILOAD(1))
+			.branch(Opcodes::IFEQ, end)
+			.unwrap()
+			.insn(Instructions::ILOAD(1))
+			.insn(Instructions::IRETURN)
+			.bind(end)
+			.unwrap()
+			.bind(handler)
+			.unwrap()
+			.insn(Instructions::POP)
+			.insn(Instructions::ICONST_0)
+			.insn(Instructions::IRETURN)
+			.try_catch(start, end, handler, Some("java/lang/RuntimeException"))
+			.unwrap();
+		code.max_stack(1).max_locals(2);
+		let mut code = code.build().unwrap();
+		let lines = [(0, 10), (2, 11), (9, 12)]
+			.map(|(start_pc, line_number)| LineNumberTableAttributeEntry { start_pc, line_number })
+			.to_vec();
+		let local = LocalVariableTableEntry {
+			start_pc: 2,
+			length: 10,
+			name,
+			descriptor,
+			index: 1,
+		};
+		for (name, attr) in [
+			(
+				"LineNumberTable",
+				IRAttribute::LineNumberTable(LineNumberTableAttribute {
+					line_number_table: lines,
+				}),
+			),
+			(
+				"LocalVariableTable",
+				IRAttribute::LocalVariableTable { table: vec![local] },
+			),
+		] {
+			code.attributes.push(IRAttributeInfo {
+				name: CPUtf8Ref::find_or_add(builder.cp(), name).unwrap(),
+				length: 0,
+				attr,
+			});
+		}
+
+		let mut listing = Listing::from_code(builder.cp(), &code).unwrap();
+		let same = listing.to_code(builder.cp()).unwrap();
+		assert_eq!(same.code, code.code);
+		assert_eq!(same.exception_table.len(), 1);
+		assert_eq!(
+			(same.exception_table[0].start_pc, same.exception_table[0].end_pc),
+			(2, 9)
+		);
+
+		// Without the nop everything after it moves up by one.
+		let nop = listing
+			.items
+			.iter()
+			.position(|item| matches!(item, Item::Insn(Instructions::NOP)))
+			.unwrap();
+		listing.items.remove(nop);
+		let code = listing.to_code(builder.cp()).unwrap();
+		assert_eq!(code.code[3], 0x99);
+		assert_eq!(i16::from_be_bytes([code.code[4], code.code[5]]), 5);
+		let handler = &code.exception_table[0];
+		assert_eq!((handler.start_pc, handler.end_pc, handler.handler_pc), (2, 8, 8));
+		let lines = code.attributes.line_number_table().unwrap();
+		let lines = lines
+			.line_number_table
+			.iter()
+			.map(|entry| (entry.start_pc, entry.line_number))
+			.collect::<Vec<_>>();
+		assert_eq!(lines, [(0, 10), (2, 11), (8, 12)]);
+		let locals = code
+			.attributes
+			.iter()
+			.find_map(|attr| match &attr.attr {
+				IRAttribute::LocalVariableTable { table } => Some(table),
+				_ => None,
+			})
+			.unwrap();
+		assert_eq!((locals[0].start_pc, locals[0].length), (2, 9));
+
+		// Jumping to a label that isn't in the code anymore is an error.
+		listing.items.retain(|item| !matches!(item, Item::Label(_)));
+		assert!(matches!(
+			listing.to_code(builder.cp()),
+			Err(IRClassfileError::UnboundLabel(_))
+		));
+	}
+}
@@ -0,0 +1,134 @@
+// Classes and jars read from memory-mapped files, so their bytes are paged in by the OS as they're looked at rather
+// than copied onto the heap first. Together with `BorrowedClassFile` a class file is parsed in place. Jar entries
+// stored without compression, which is how many build tools write classes, are slices of the map too, compressed ones
+// have to be inflated into a buffer of their own.
+//
+// A map reflects the file: if another process truncates or rewrites it while it's mapped, reads see the change or
+// fault. Map only files nothing else writes to while they're in use.
+
+use std::{borrow::Cow, fs::File, io::Read, path::Path};
+
+use memmap2::Mmap;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::{archive::ArchiveError, borrowed::BorrowedClassFile, class_pool::IRClassfileError, IRClassFile};
+
+/// A jar entry, borrowed from the map unless it had to be inflated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedEntry<'a> {
+	pub name: String,
+	pub data: Cow<'a, [u8]>,
+}
+
+impl MappedEntry<'_> {
+	pub fn is_class(&self) -> bool {
+		self.name.ends_with(".class")
+	}
+
+	pub fn is_borrowed(&self) -> bool {
+		matches!(self.data, Cow::Borrowed(_))
+	}
+}
+
+#[derive(Debug)]
+pub struct MappedFile {
+	map: Mmap,
+}
+
+impl MappedFile {
+	pub fn open(path: &Path) -> Result<Self, std::io::Error> {
+		let file = File::open(path)?;
+		// SAFETY: see the module docs, the file mustn't change while mapped.
+		let map = unsafe { Mmap::map(&file)? };
+		Ok(Self { map })
+	}
+
+	pub fn bytes(&self) -> &[u8] {
+		&self.map
+	}
+
+	/// The file as a class, borrowing from the map.
+	pub fn class(&self) -> Result<BorrowedClassFile<'_>, IRClassfileError> {
+		BorrowedClassFile::parse(&self.map)
+	}
+
+	/// The file as a class, parsed into the IR.
+	pub fn read_class(&self) -> Result<IRClassFile, IRClassfileError> {
+		IRClassFile::read(&self.map)
+	}
+
+	/// The file as a jar: every entry in archive order, duplicates included and directories left out.
+	pub fn entries(&self) -> Result<Vec<MappedEntry<'_>>, ArchiveError> {
+		let bytes = self.bytes();
+		let mut zip = ZipArchive::new(std::io::Cursor::new(bytes))?;
+		let mut entries = Vec::with_capacity(zip.len());
+		for i in 0..zip.len() {
+			let file = zip.by_index_raw(i)?;
+			if file.is_dir() {
+				continue;
+			}
+			let name = file.name().to_string();
+			let stored = file.compression() == CompressionMethod::Stored;
+			let start = file.data_start() as usize;
+			let end = start + file.compressed_size() as usize;
+			drop(file);
+
+			let data = match bytes.get(start..end) {
+				Some(data) if stored => Cow::Borrowed(data),
+				_ => {
+					let mut file = zip.by_index(i)?;
+					let mut data = Vec::with_capacity(file.size() as usize);
+					file.read_to_end(&mut data)?;
+					Cow::Owned(data)
+				}
+			};
+			entries.push(MappedEntry { name, data });
+		}
+		Ok(entries)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fs, io::Write};
+
+	use zip::{write::FileOptions, ZipWriter};
+
+	use super::*;
+	use crate::{builder::ClassBuilder, scratch::ScratchDir};
+
+	#[test]
+	fn parse_from_the_map() {
+		let dir = ScratchDir::new("mapped");
+		let class = |name: &str| ClassBuilder::new(name).unwrap().to_bytes().unwrap();
+		fs::write(dir.join("Main.class"), class("p/Main")).unwrap();
+		let mut zip = ZipWriter::new(File::create(dir.join("lib.jar")).unwrap());
+		let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+		zip.add_directory("p/", stored).unwrap();
+		zip.start_file("p/Stored.class", stored).unwrap();
+		zip.write_all(&class("p/Stored")).unwrap();
+		zip.start_file("p/Deflated.class", FileOptions::default()).unwrap();
+		zip.write_all(&class("p/Deflated")).unwrap();
+		zip.finish().unwrap();
+
+		let file = MappedFile::open(&dir.join("Main.class")).unwrap();
+		assert_eq!(file.class().unwrap().class_name().unwrap(), "p/Main");
+		assert_eq!(file.read_class().unwrap().class_name(), "p/Main");
+
+		let jar = MappedFile::open(&dir.join("lib.jar")).unwrap();
+		let entries = jar.entries().unwrap();
+		assert_eq!(entries.len(), 2);
+		assert!(entries[0].is_borrowed() && !entries[1].is_borrowed());
+		let names = entries
+			.iter()
+			.map(|entry| {
+				BorrowedClassFile::parse(&entry.data)
+					.unwrap()
+					.class_name()
+					.unwrap()
+					.into_owned()
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(names, ["p/Stored", "p/Deflated"]);
+	}
+}
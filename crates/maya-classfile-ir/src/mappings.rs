@@ -0,0 +1,386 @@
+// Reading mapping files into a `Remapper`.
+//
+// ProGuard/R8:  the `-printmapping` output, original names to obfuscated ones in Java source spelling. It's read the
+//               way it's used, to turn obfuscated classes back into the original names.
+//               https://www.guardsquare.com/manual/tools/retrace
+// Tiny v2:      tab separated, any number of namespaces (e.g. official, intermediary, named), descriptors in the
+//               first one. Mapped from one namespace to another.
+//               https://fabricmc.net/wiki/documentation:tiny2
+// SRG:          `CL:`, `FD:` and `MD:` lines, one per class or member, from obfuscated to SRG names.
+// TSRG:         SRG with members indented under their class instead of repeating it.
+//
+// Package lines (`PK:`, TSRG lines ending in `/`) are skipped, they only tell the packages apart for the default
+// package. Parameters, local variables and comments aren't part of a class's names and are skipped too.
+
+use thiserror::Error;
+
+use crate::{names, remap::Remapper};
+
+#[derive(Debug, Error)]
+pub enum MappingError {
+	#[error("Line {line}: {reason}")]
+	Malformed { line: usize, reason: &'static str },
+	#[error("The mappings have no namespace {0}")]
+	UnknownNamespace(String),
+}
+
+// Lines are counted from 1, as editors do.
+fn malformed(index: usize, reason: &'static str) -> MappingError {
+	MappingError::Malformed {
+		line: index + 1,
+		reason,
+	}
+}
+
+/// A field has a descriptor in the formats that give one.
+enum Member {
+	Field(Option<String>),
+	Method(String),
+}
+
+struct MappedMember {
+	owner: String,
+	name: String,
+	member: Member,
+	to: String,
+}
+
+impl MappedMember {
+	fn add_to(&self, remapper: &mut Remapper) {
+		match &self.member {
+			Member::Field(descriptor) => remapper.add_field(&self.owner, &self.name, descriptor.as_deref(), &self.to),
+			Member::Method(descriptor) => remapper.add_method(&self.owner, &self.name, descriptor, &self.to),
+		};
+	}
+}
+
+impl Remapper {
+	/// Reads a ProGuard or R8 mapping file into mappings from the obfuscated names back to the original ones.
+	pub fn from_proguard(mappings: &str) -> Result<Self, MappingError> {
+		// Members come with their original types, which can only be obfuscated once all classes are read.
+		let mut obfuscate = Remapper::new();
+		let mut members = Vec::new();
+		let mut owner = None;
+		for (index, line) in mappings.lines().enumerate() {
+			let trimmed = line.trim();
+			if trimmed.is_empty() || trimmed.starts_with('#') {
+				continue;
+			}
+			let (original, obfuscated) = trimmed
+				.split_once(" -> ")
+				.ok_or_else(|| malformed(index, "expected `original -> obfuscated`"))?;
+
+			if !line.starts_with(char::is_whitespace) {
+				let obfuscated = obfuscated
+					.strip_suffix(':')
+					.ok_or_else(|| malformed(index, "expected a colon after the class"))?;
+				let original = names::binary_to_internal(original);
+				obfuscate.add_class(&original, &names::binary_to_internal(obfuscated));
+				owner = Some(original);
+				continue;
+			}
+
+			let owner = owner
+				.clone()
+				.ok_or_else(|| malformed(index, "member outside of a class"))?;
+			// R8 puts the line range of a method in front of it.
+			let member = original.trim_start_matches(|c: char| c.is_ascii_digit() || c == ':');
+			let (ty, member) = member
+				.split_once(' ')
+				.ok_or_else(|| malformed(index, "expected a type and a name"))?;
+			let ty = names::source_to_descriptor(ty);
+			let (name, member) = match member.split_once('(') {
+				Some((name, params)) => {
+					// Methods inlined from other classes are named with their class, and aren't members of this one.
+					if name.contains('.') {
+						continue;
+					}
+					let (params, _) = params
+						.split_once(')')
+						.ok_or_else(|| malformed(index, "expected a closing parenthesis"))?;
+					let params = params
+						.split(',')
+						.filter(|param| !param.trim().is_empty())
+						.map(names::source_to_descriptor)
+						.collect::<String>();
+					(name, Member::Method(format!("({params}){ty}")))
+				}
+				None => (member, Member::Field(Some(ty))),
+			};
+			members.push(MappedMember {
+				owner,
+				name: name.to_string(),
+				member,
+				to: obfuscated.to_string(),
+			});
+		}
+
+		let mut remapper = obfuscate.reversed();
+		for member in members {
+			let member = MappedMember {
+				owner: obfuscate.map_class(&member.owner),
+				name: member.to,
+				member: match member.member {
+					Member::Field(descriptor) => Member::Field(descriptor.map(|d| obfuscate.map_descriptor(&d))),
+					Member::Method(descriptor) => Member::Method(obfuscate.map_descriptor(&descriptor)),
+				},
+				to: member.name,
+			};
+			member.add_to(&mut remapper);
+		}
+		Ok(remapper)
+	}
+
+	/// Reads a Tiny v2 file into mappings from namespace `from` to namespace `to`.
+	pub fn from_tiny_v2(mappings: &str, from: &str, to: &str) -> Result<Self, MappingError> {
+		let mut lines = mappings.lines().enumerate();
+		let (_, header) = lines.next().ok_or_else(|| malformed(0, "missing header"))?;
+		let namespaces = match header.split('\t').collect::<Vec<_>>()[..] {
+			["tiny", "2", _, ref namespaces @ ..] if namespaces.len() >= 2 => namespaces.to_vec(),
+			_ => return Err(malformed(0, "expected a `tiny 2` header with at least two namespaces")),
+		};
+		let namespace = |name: &str| {
+			namespaces
+				.iter()
+				.position(|namespace| *namespace == name)
+				.ok_or_else(|| MappingError::UnknownNamespace(name.to_string()))
+		};
+		let (from, to) = (namespace(from)?, namespace(to)?);
+
+		// Descriptors are in the first namespace, which needs the classes of both to be mapped to `from`.
+		let mut to_from = Remapper::new();
+		let mut remapper = Remapper::new();
+		let mut members = Vec::new();
+		let mut escaped = false;
+		let mut owner: Option<Vec<String>> = None;
+		for (index, line) in lines {
+			let depth = line.bytes().take_while(|b| *b == b'\t').count();
+			let columns = line[depth..].split('\t').collect::<Vec<_>>();
+			match (depth, columns[0]) {
+				(1, "escaped-names") if owner.is_none() => escaped = true,
+				(1, _) if owner.is_none() => {}
+				(0, "c") => {
+					let names = tiny_names(&columns[1..], namespaces.len(), escaped)
+						.ok_or_else(|| malformed(index, "expected a name for each namespace"))?;
+					to_from.add_class(&names[0], &names[from]);
+					remapper.add_class(&names[from], &names[to]);
+					owner = Some(names);
+				}
+				(1, kind @ ("f" | "m")) => {
+					let owner = owner
+						.as_ref()
+						.ok_or_else(|| malformed(index, "member outside of a class"))?;
+					let (descriptor, names) = columns[1..]
+						.split_first()
+						.and_then(|(descriptor, names)| {
+							Some((*descriptor, tiny_names(names, namespaces.len(), escaped)?))
+						})
+						.ok_or_else(|| malformed(index, "expected a descriptor and a name for each namespace"))?;
+					let descriptor = descriptor.to_string();
+					members.push(MappedMember {
+						owner: owner[from].clone(),
+						name: names[from].clone(),
+						member: match kind {
+							"f" => Member::Field(Some(descriptor)),
+							_ => Member::Method(descriptor),
+						},
+						to: names[to].clone(),
+					});
+				}
+				// Comments, parameters and local variables.
+				_ => {}
+			}
+		}
+
+		for mut member in members {
+			member.member = match member.member {
+				Member::Field(descriptor) => Member::Field(descriptor.map(|d| to_from.map_descriptor(&d))),
+				Member::Method(descriptor) => Member::Method(to_from.map_descriptor(&descriptor)),
+			};
+			member.add_to(&mut remapper);
+		}
+		Ok(remapper)
+	}
+
+	/// Reads an SRG file into mappings from the obfuscated names to the SRG ones.
+	pub fn from_srg(mappings: &str) -> Result<Self, MappingError> {
+		let mut remapper = Remapper::new();
+		for (index, line) in mappings.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let columns = line.split_whitespace().collect::<Vec<_>>();
+			match columns[..] {
+				["PK:", _, _] => {}
+				["CL:", from, to] => {
+					remapper.add_class(from, to);
+				}
+				["FD:", from, to] => {
+					let ((owner, name), (_, to)) = split_member(from)
+						.zip(split_member(to))
+						.ok_or_else(|| malformed(index, "expected owner/name"))?;
+					remapper.add_field(owner, name, None, to);
+				}
+				["MD:", from, descriptor, to, _] => {
+					let ((owner, name), (_, to)) = split_member(from)
+						.zip(split_member(to))
+						.ok_or_else(|| malformed(index, "expected owner/name"))?;
+					remapper.add_method(owner, name, descriptor, to);
+				}
+				_ => return Err(malformed(index, "expected a PK:, CL:, FD: or MD: line")),
+			}
+		}
+		Ok(remapper)
+	}
+
+	/// Reads a TSRG file into mappings from the obfuscated names to the SRG ones.
+	pub fn from_tsrg(mappings: &str) -> Result<Self, MappingError> {
+		let mut remapper = Remapper::new();
+		let mut owner = None;
+		for (index, line) in mappings.lines().enumerate() {
+			if line.trim().is_empty() || line.trim_start().starts_with('#') {
+				continue;
+			}
+			let member = line.starts_with(char::is_whitespace);
+			let columns = line.split_whitespace().collect::<Vec<_>>();
+			match (member, &columns[..]) {
+				(false, [from, _]) if from.ends_with('/') => owner = None,
+				(false, [from, to]) => {
+					remapper.add_class(from, to);
+					owner = Some(from.to_string());
+				}
+				(true, [name, to]) => {
+					let owner = owner
+						.as_ref()
+						.ok_or_else(|| malformed(index, "member outside of a class"))?;
+					remapper.add_field(owner, name, None, to);
+				}
+				(true, [name, descriptor, to]) => {
+					let owner = owner
+						.as_ref()
+						.ok_or_else(|| malformed(index, "member outside of a class"))?;
+					remapper.add_method(owner, name, descriptor, to);
+				}
+				_ => return Err(malformed(index, "expected a class, field or method line")),
+			}
+		}
+		Ok(remapper)
+	}
+}
+
+fn split_member(member: &str) -> Option<(&str, &str)> {
+	member.rsplit_once('/')
+}
+
+// An empty name means the name is the same as in the first namespace.
+fn tiny_names(columns: &[&str], namespaces: usize, escaped: bool) -> Option<Vec<String>> {
+	if columns.len() != namespaces || columns[0].is_empty() {
+		return None;
+	}
+	let unescape = |name: &str| match escaped {
+		true => tiny_unescape(name),
+		false => name.to_string(),
+	};
+	Some(
+		columns
+			.iter()
+			.map(|name| match name.is_empty() {
+				true => unescape(columns[0]),
+				false => unescape(name),
+			})
+			.collect(),
+	)
+}
+
+fn tiny_unescape(name: &str) -> String {
+	let mut out = String::with_capacity(name.len());
+	let mut chars = name.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			out.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n') => out.push('\n'),
+			Some('r') => out.push('\r'),
+			Some('t') => out.push('\t'),
+			Some('0') => out.push('\0'),
+			Some(other) => out.push(other),
+			None => out.push('\\'),
+		}
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// The same names in each format, obfuscated `a` being com/example/Widget with field `b` and method `c`.
+	fn assert_widget(remapper: &Remapper, field_descriptor: &str) {
+		assert_eq!(remapper.map_class("a"), "com/example/Widget");
+		assert_eq!(remapper.map_class("d"), "com/example/Part");
+		assert_eq!(remapper.map_field("a", "b", field_descriptor), Some("parts"));
+		assert_eq!(remapper.map_method("a", "c", "(Ld;I)V"), Some("attach"));
+		assert_eq!(remapper.map_method("a", "c", "()V"), None);
+	}
+
+	#[test]
+	fn read_each_format() {
+		let proguard = "\
+# compiler: R8
+com.example.Part -> d:
+com.example.Widget -> a:
+    java.util.List parts -> b
+    1:4:void attach(com.example.Part,int):10:13 -> c
+    5:5:void com.example.Part.check():20:20 -> c
+";
+		assert_widget(&Remapper::from_proguard(proguard).unwrap(), "Ljava/util/List;");
+
+		let tiny = "\
+tiny\t2\t0\tofficial\tintermediary\tnamed
+c\td\tclass_1\tcom/example/Part
+c\ta\tclass_2\tcom/example/Widget
+\tf\tLjava/util/List;\tb\tfield_1\tparts
+\tm\t(Ld;I)V\tc\tmethod_1\tattach
+\t\tp\t1\t\t\tpart
+\tc\tA widget.
+";
+		assert_widget(
+			&Remapper::from_tiny_v2(tiny, "official", "named").unwrap(),
+			"Ljava/util/List;",
+		);
+		let intermediary = Remapper::from_tiny_v2(tiny, "intermediary", "named").unwrap();
+		assert_eq!(
+			intermediary.map_method("class_2", "method_1", "(Lclass_1;I)V"),
+			Some("attach")
+		);
+		assert!(matches!(
+			Remapper::from_tiny_v2(tiny, "official", "mojang"),
+			Err(MappingError::UnknownNamespace(_))
+		));
+
+		let srg = "\
+PK: . com/example
+CL: d com/example/Part
+CL: a com/example/Widget
+FD: a/b com/example/Widget/parts
+MD: a/c (Ld;I)V com/example/Widget/attach (Lcom/example/Part;I)V
+";
+		assert_widget(&Remapper::from_srg(srg).unwrap(), "Ljava/util/List;");
+
+		let tsrg = "\
+d com/example/Part
+a com/example/Widget
+\tb parts
+\tc (Ld;I)V attach
+";
+		assert_widget(&Remapper::from_tsrg(tsrg).unwrap(), "I");
+
+		match Remapper::from_srg("CL: a\n") {
+			Err(MappingError::Malformed { line, .. }) => assert_eq!(line, 1),
+			other => panic!("{other:?}"),
+		}
+	}
+}
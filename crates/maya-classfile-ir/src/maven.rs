@@ -0,0 +1,199 @@
+// Classes from Maven repositories, to look at a dependency by its coordinates without downloading it by hand first.
+// Jars are fetched once into a local repository laid out like ~/.m2/repository, which can be that one, and read from
+// there after. Only the artifacts named are fetched, their dependencies aren't resolved.
+// https://maven.apache.org/repositories/layout.html
+
+use std::{
+	fmt::{self, Display},
+	fs::{self, File},
+	io,
+	path::PathBuf,
+};
+
+use crate::classpath::{ClassProvider, ClasspathError, JarProvider};
+
+/// A Maven artifact as `group:artifact:version`, or `group:artifact:version:classifier` for e.g. a `tests` jar.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Coordinate {
+	pub group: String,
+	pub artifact: String,
+	pub version: String,
+	pub classifier: Option<String>,
+}
+
+impl Coordinate {
+	pub fn parse(coordinate: &str) -> Result<Self, ClasspathError> {
+		let parts = coordinate.split(':').collect::<Vec<_>>();
+		if parts.iter().any(|part| part.is_empty()) {
+			return Err(ClasspathError::InvalidCoordinate(coordinate.to_string()));
+		}
+		match parts[..] {
+			[group, artifact, version] | [group, artifact, version, _] => Ok(Self {
+				group: group.to_string(),
+				artifact: artifact.to_string(),
+				version: version.to_string(),
+				classifier: parts.get(3).map(|classifier| classifier.to_string()),
+			}),
+			_ => Err(ClasspathError::InvalidCoordinate(coordinate.to_string())),
+		}
+	}
+
+	/// Where the jar is in a repository, e.g. `org/ow2/asm/asm/9.7/asm-9.7.jar`.
+	pub fn jar_path(&self) -> String {
+		let file = match &self.classifier {
+			Some(classifier) => format!("{}-{}-{classifier}.jar", self.artifact, self.version),
+			None => format!("{}-{}.jar", self.artifact, self.version),
+		};
+		format!(
+			"{}/{}/{}/{file}",
+			self.group.replace('.', "/"),
+			self.artifact,
+			self.version
+		)
+	}
+}
+
+impl Display for Coordinate {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}:{}:{}", self.group, self.artifact, self.version)?;
+		if let Some(classifier) = &self.classifier {
+			write!(f, ":{classifier}")?;
+		}
+		Ok(())
+	}
+}
+
+/// A remote repository and the local one its jars are kept in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MavenRepository {
+	url: String,
+	local: PathBuf,
+}
+
+impl MavenRepository {
+	pub const CENTRAL: &'static str = "https://repo.maven.apache.org/maven2";
+
+	pub fn new(url: &str, local: PathBuf) -> Self {
+		Self {
+			url: url.trim_end_matches('/').to_string(),
+			local,
+		}
+	}
+
+	pub fn central(local: PathBuf) -> Self {
+		Self::new(Self::CENTRAL, local)
+	}
+
+	/// The jar of `coordinate` in the local repository, fetched first if it isn't there yet.
+	pub fn fetch(&self, coordinate: &Coordinate) -> Result<PathBuf, ClasspathError> {
+		let path = self.local.join(coordinate.jar_path());
+		if path.is_file() {
+			return Ok(path);
+		}
+
+		let url = format!("{}/{}", self.url, coordinate.jar_path());
+		let response = ureq::get(&url).call().map_err(|source| ClasspathError::Fetch {
+			url: url.clone(),
+			source: Box::new(source),
+		})?;
+		if let Some(dir) = path.parent() {
+			fs::create_dir_all(dir)?;
+		}
+		// Written next to the jar and renamed once complete, so an interrupted fetch doesn't leave half a jar behind.
+		let partial = path.with_extension("jar.part");
+		io::copy(&mut response.into_reader(), &mut File::create(&partial)?)?;
+		fs::rename(&partial, &path)?;
+		Ok(path)
+	}
+}
+
+/// The classes of some Maven artifacts, looked up in the order the artifacts were given.
+#[derive(Debug, Clone)]
+pub struct MavenProvider {
+	artifacts: Vec<(Coordinate, JarProvider)>,
+}
+
+impl MavenProvider {
+	/// Fetches the artifacts that aren't in the local repository yet.
+	pub fn new(repository: &MavenRepository, coordinates: Vec<Coordinate>) -> Result<Self, ClasspathError> {
+		let artifacts = coordinates
+			.into_iter()
+			.map(|coordinate| {
+				let jar = repository.fetch(&coordinate)?;
+				Ok((coordinate, JarProvider::new(jar)))
+			})
+			.collect::<Result<_, ClasspathError>>()?;
+		Ok(Self { artifacts })
+	}
+
+	pub fn coordinates(&self) -> impl Iterator<Item = &Coordinate> {
+		self.artifacts.iter().map(|(coordinate, _)| coordinate)
+	}
+}
+
+impl ClassProvider for MavenProvider {
+	fn read_class(&self, name: &str) -> Result<Option<Vec<u8>>, ClasspathError> {
+		for (_, jar) in &self.artifacts {
+			if let Some(data) = jar.read_class(name)? {
+				return Ok(Some(data));
+			}
+		}
+		Ok(None)
+	}
+
+	fn class_names(&self) -> Result<Vec<String>, ClasspathError> {
+		let mut names = Vec::new();
+		for (_, jar) in &self.artifacts {
+			names.extend(jar.class_names()?);
+		}
+		names.sort();
+		names.dedup();
+		Ok(names)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use zip::{write::FileOptions, ZipWriter};
+
+	use super::*;
+	use crate::{builder::ClassBuilder, scratch::ScratchDir};
+
+	#[test]
+	fn local_repository() {
+		let asm = Coordinate::parse("org.ow2.asm:asm:9.7").unwrap();
+		assert_eq!(asm.jar_path(), "org/ow2/asm/asm/9.7/asm-9.7.jar");
+		let tests = Coordinate::parse("com.example:lib:1.0:tests").unwrap();
+		assert_eq!(tests.jar_path(), "com/example/lib/1.0/lib-1.0-tests.jar");
+		assert_eq!(tests.to_string(), "com.example:lib:1.0:tests");
+		for invalid in ["org.ow2.asm:asm", "org.ow2.asm::9.7", "a:b:c:d:e"] {
+			assert!(matches!(
+				Coordinate::parse(invalid),
+				Err(ClasspathError::InvalidCoordinate(_))
+			));
+		}
+
+		// Nothing listens on port 1, so anything not in the local repository fails to fetch.
+		let local = ScratchDir::new("maven");
+		let repository = MavenRepository::new("http://127.0.0.1:1/", local.to_path_buf());
+		let jar = local.join(asm.jar_path());
+		fs::create_dir_all(jar.parent().unwrap()).unwrap();
+		let mut zip = ZipWriter::new(File::create(&jar).unwrap());
+		zip.start_file("org/objectweb/asm/Opcodes.class", FileOptions::default())
+			.unwrap();
+		let class = ClassBuilder::new("org/objectweb/asm/Opcodes")
+			.unwrap()
+			.to_bytes()
+			.unwrap();
+		io::Write::write_all(&mut zip, &class).unwrap();
+		zip.finish().unwrap();
+
+		let provider = MavenProvider::new(&repository, vec![asm.clone()]).unwrap();
+		assert_eq!(provider.class_names().unwrap(), ["org/objectweb/asm/Opcodes"]);
+		assert_eq!(provider.read_class("org/objectweb/asm/Opcodes").unwrap(), Some(class));
+		let missing = MavenProvider::new(&repository, vec![asm, tests]).unwrap_err();
+		assert!(
+			matches!(&missing, ClasspathError::Fetch { url, .. } if url == "http://127.0.0.1:1/com/example/lib/1.0/lib-1.0-tests.jar")
+		);
+	}
+}
@@ -0,0 +1,187 @@
+// Summary of what a pipeline did, meant for CI logs. The pipeline fills in what it can see itself; byte sizes and
+// verifier warnings come from outside the IR and are added by whoever reads, writes and verifies the classes.
+
+use std::{
+	collections::{hash_map::DefaultHasher, HashSet},
+	fmt::{self, Display},
+	hash::{Hash, Hasher},
+	time::Duration,
+};
+
+use crate::{
+	json::{self, JsonObject},
+	IRClassFile,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PassMetrics {
+	pub name: String,
+	pub wall_time: Duration,
+	// classes the pass changed at least one method of
+	pub classes_changed: usize,
+	pub methods_changed: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipelineMetrics {
+	pub classes_processed: usize,
+	// methods whose code differs after the whole pipeline ran, including added ones
+	pub methods_transformed: usize,
+	pub verifier_warnings: usize,
+	pub bytes_before: u64,
+	pub bytes_after: u64,
+	// in pipeline order
+	pub passes: Vec<PassMetrics>,
+}
+
+impl PipelineMetrics {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn add_bytes(&mut self, before: usize, after: usize) {
+		self.bytes_before += before as u64;
+		self.bytes_after += after as u64;
+	}
+
+	pub fn add_verifier_warnings(&mut self, count: usize) {
+		self.verifier_warnings += count;
+	}
+
+	pub fn wall_time(&self) -> Duration {
+		self.passes.iter().map(|pass| pass.wall_time).sum()
+	}
+
+	/// Adds up the metrics of pipelines with the same passes, e.g. one per worker thread.
+	pub fn merge(&mut self, other: &PipelineMetrics) {
+		self.classes_processed += other.classes_processed;
+		self.methods_transformed += other.methods_transformed;
+		self.verifier_warnings += other.verifier_warnings;
+		self.bytes_before += other.bytes_before;
+		self.bytes_after += other.bytes_after;
+		for (i, pass) in other.passes.iter().enumerate() {
+			match self.passes.get_mut(i) {
+				Some(own) if own.name == pass.name => {
+					own.wall_time += pass.wall_time;
+					own.classes_changed += pass.classes_changed;
+					own.methods_changed += pass.methods_changed;
+				}
+				_ => self.passes.push(pass.clone()),
+			}
+		}
+	}
+
+	pub fn to_json(&self) -> String {
+		let passes = json::array(&self.passes, |pass, out| {
+			JsonObject::new(out)
+				.str("name", &pass.name)
+				.num("wall_time_ms", millis(pass.wall_time))
+				.num("classes_changed", pass.classes_changed)
+				.num("methods_changed", pass.methods_changed)
+				.end();
+		});
+
+		let mut out = String::new();
+		JsonObject::new(&mut out)
+			.num("classes_processed", self.classes_processed)
+			.num("methods_transformed", self.methods_transformed)
+			.num("verifier_warnings", self.verifier_warnings)
+			.num("bytes_before", self.bytes_before)
+			.num("bytes_after", self.bytes_after)
+			.num("wall_time_ms", millis(self.wall_time()))
+			.raw("passes", &passes)
+			.end();
+		out
+	}
+}
+
+fn millis(duration: Duration) -> String {
+	format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+impl Display for PipelineMetrics {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(
+			f,
+			"{} classes processed, {} methods transformed, {} verifier warnings",
+			self.classes_processed, self.methods_transformed, self.verifier_warnings
+		)?;
+		if self.bytes_before != 0 || self.bytes_after != 0 {
+			let delta = self.bytes_after as i64 - self.bytes_before as i64;
+			writeln!(
+				f,
+				"{} bytes -> {} bytes ({delta:+})",
+				self.bytes_before, self.bytes_after
+			)?;
+		}
+		writeln!(f, "{} ms total", millis(self.wall_time()))?;
+		for pass in &self.passes {
+			writeln!(
+				f,
+				"  {}: {} ms, {} methods in {} classes changed",
+				pass.name,
+				millis(pass.wall_time),
+				pass.methods_changed,
+				pass.classes_changed
+			)?;
+		}
+		Ok(())
+	}
+}
+
+/// A hash per method over its name, descriptor and code, to tell which methods a pass changed without keeping a copy
+/// of the class around.
+pub(crate) fn method_fingerprints(class: &IRClassFile) -> HashSet<u64> {
+	class
+		.methods
+		.iter()
+		.map(|method| {
+			let mut hasher = DefaultHasher::new();
+			method.name().hash(&mut hasher);
+			method.descriptor().hash(&mut hasher);
+			if let Some(code) = method.code() {
+				code.code.hash(&mut hasher);
+			}
+			hasher.finish()
+		})
+		.collect()
+}
+
+/// How many methods are new or have different code than before.
+pub(crate) fn changed_methods(before: &HashSet<u64>, after: &HashSet<u64>) -> usize {
+	after.difference(before).count()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn merge_and_json() {
+		let pass = |ms| PassMetrics {
+			name: "inline".into(),
+			wall_time: Duration::from_millis(ms),
+			classes_changed: 1,
+			methods_changed: 2,
+		};
+		let mut total = PipelineMetrics {
+			classes_processed: 3,
+			passes: vec![pass(5)],
+			..PipelineMetrics::new()
+		};
+		let mut worker = PipelineMetrics {
+			classes_processed: 4,
+			methods_transformed: 2,
+			passes: vec![pass(7)],
+			..PipelineMetrics::new()
+		};
+		worker.add_bytes(100, 90);
+		worker.add_verifier_warnings(1);
+		total.merge(&worker);
+
+		assert_eq!(
+			total.to_json(),
+			r#"{"classes_processed":7,"methods_transformed":2,"verifier_warnings":1,"bytes_before":100,"bytes_after":90,"wall_time_ms":12.000,"passes":[{"name":"inline","wall_time_ms":12.000,"classes_changed":2,"methods_changed":4}]}"#
+		);
+	}
+}
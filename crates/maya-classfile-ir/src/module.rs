@@ -0,0 +1,409 @@
+use crate::{
+	attribute::{IRAttribute, ModuleExportsEntry, ModuleOpensEntry, ModuleProvidesEntry, ModuleRequiresEntry},
+	builder::ClassBuilder,
+	class_pool::{CPClassRef, CPModuleInfoRef, CPPackageInfoRef, CPUtf8Ref, IRClassfileError},
+	flags::{ModuleFlags, RequiresFlags},
+	names, IRClassFile,
+};
+
+/// The module declared by a module-info class, gathered from its Module, ModulePackages and ModuleMainClass attributes.
+#[derive(Debug, Clone, Copy)]
+pub struct ModuleInfo<'a> {
+	pub name: &'a str,
+	pub flags: ModuleFlags,
+	pub version: Option<&'a str>,
+	pub requires: &'a [ModuleRequiresEntry],
+	pub exports: &'a [ModuleExportsEntry],
+	pub opens: &'a [ModuleOpensEntry],
+	pub uses: &'a [CPClassRef],
+	pub provides: &'a [ModuleProvidesEntry],
+	// Every package of the module, not only the exported and opened ones. Empty if the attribute is missing.
+	pub packages: &'a [CPPackageInfoRef],
+	pub main_class: Option<&'a str>,
+}
+
+impl<'a> ModuleInfo<'a> {
+	/// Returns `None` if `class` has no Module attribute.
+	pub fn from_class(class: &'a IRClassFile) -> Option<Self> {
+		let mut info = class.attributes.iter().find_map(|attr| match &attr.attr {
+			IRAttribute::Module {
+				module_name,
+				module_flags,
+				module_version,
+				requires,
+				exports,
+				opens,
+				uses,
+				provides,
+			} => Some(Self {
+				name: &module_name.data.data,
+				flags: *module_flags,
+				version: module_version.as_ref().map(|version| version.data.as_str()),
+				requires,
+				exports,
+				opens,
+				uses,
+				provides,
+				packages: &[],
+				main_class: None,
+			}),
+			_ => None,
+		})?;
+
+		for attr in &class.attributes {
+			match &attr.attr {
+				IRAttribute::ModulePackages { packages } => info.packages = packages,
+				IRAttribute::ModuleMainClass { class } => info.main_class = Some(&class.data.data),
+				_ => {}
+			}
+		}
+
+		Some(info)
+	}
+
+	/// Packages named anywhere in the descriptor, deduplicated, in first-seen order.
+	pub fn all_packages(&self) -> Vec<&'a str> {
+		let mut out = Vec::new();
+		let names = self
+			.packages
+			.iter()
+			.chain(self.exports.iter().map(|export| &export.package))
+			.chain(self.opens.iter().map(|open| &open.package));
+		for package in names {
+			let name = package.data.data.as_str();
+			if !out.contains(&name) {
+				out.push(name);
+			}
+		}
+		out
+	}
+}
+
+/// A module declaration with plain names, independent of any constant pool. Module names are dotted (`java.base`),
+/// package and class names internal (`java/lang`, `java/lang/Object`), as in the class file.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ModuleDescriptor {
+	pub name: String,
+	pub flags: ModuleFlags,
+	pub version: Option<String>,
+	pub requires: Vec<ModuleRequires>,
+	pub exports: Vec<ModulePackageAccess>,
+	pub opens: Vec<ModulePackageAccess>,
+	pub uses: Vec<String>,
+	pub provides: Vec<ModuleProvides>,
+	/// Every package of the module, including the ones it exports and opens.
+	pub packages: Vec<String>,
+	pub main_class: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleRequires {
+	pub module: String,
+	pub flags: RequiresFlags,
+	/// The version of the module when this one was compiled against it.
+	pub version: Option<String>,
+}
+
+/// An `exports` or `opens` directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModulePackageAccess {
+	pub package: String,
+	pub flags: ModuleFlags,
+	/// The modules the package is exported or opened to, all of them if empty.
+	pub to: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleProvides {
+	pub service: String,
+	pub implementations: Vec<String>,
+}
+
+impl ModuleDescriptor {
+	pub fn new(name: &str) -> Self {
+		Self {
+			name: name.to_string(),
+			..Self::default()
+		}
+	}
+
+	/// Returns `None` if `class` has no Module attribute.
+	pub fn from_class(class: &IRClassFile) -> Option<Self> {
+		class.module_info().map(Self::from)
+	}
+
+	pub fn open(mut self) -> Self {
+		self.flags |= ModuleFlags::OPEN;
+		self
+	}
+
+	pub fn version(mut self, version: &str) -> Self {
+		self.version = Some(version.to_string());
+		self
+	}
+
+	pub fn requires(mut self, module: &str, flags: RequiresFlags) -> Self {
+		self.requires.push(ModuleRequires {
+			module: module.to_string(),
+			flags,
+			version: None,
+		});
+		self
+	}
+
+	/// Exports `package` to the modules in `to`, or to all modules if it's empty.
+	pub fn exports(mut self, package: &str, to: &[&str]) -> Self {
+		self.exports.push(ModulePackageAccess::new(package, to));
+		self.package(package)
+	}
+
+	/// Opens `package` to the modules in `to`, or to all modules if it's empty.
+	pub fn opens(mut self, package: &str, to: &[&str]) -> Self {
+		self.opens.push(ModulePackageAccess::new(package, to));
+		self.package(package)
+	}
+
+	pub fn uses(mut self, service: &str) -> Self {
+		self.uses.push(service.to_string());
+		self
+	}
+
+	pub fn provides(mut self, service: &str, implementations: &[&str]) -> Self {
+		self.provides.push(ModuleProvides {
+			service: service.to_string(),
+			implementations: implementations.iter().map(|name| name.to_string()).collect(),
+		});
+		for implementation in implementations {
+			self = self.package(names::package_name(implementation));
+		}
+		self
+	}
+
+	/// Adds a package of the module, once.
+	pub fn package(mut self, package: &str) -> Self {
+		if !self.packages.iter().any(|existing| existing == package) {
+			self.packages.push(package.to_string());
+		}
+		self
+	}
+
+	pub fn main_class(mut self, class: &str) -> Self {
+		self.main_class = Some(class.to_string());
+		self.package(names::package_name(class))
+	}
+
+	/// A module-info class declaring the module. Every module but java.base reads java.base, so that's required with
+	/// the MANDATED flag unless it already is.
+	pub fn to_class(&self) -> Result<IRClassFile, IRClassfileError> {
+		let mut builder = ClassBuilder::module_info()?;
+		let cp = builder.cp();
+		let module = |cp: &mut _, name: &str| CPModuleInfoRef::find_or_add(cp, name);
+		let utf8 = |cp: &mut _, value: &Option<String>| {
+			value
+				.as_deref()
+				.map(|value| CPUtf8Ref::find_or_add(cp, value))
+				.transpose()
+		};
+
+		let mut requires = Vec::with_capacity(self.requires.len() + 1);
+		if self.name != "java.base" && !self.requires.iter().any(|require| require.module == "java.base") {
+			requires.push(ModuleRequiresEntry {
+				module: module(cp, "java.base")?,
+				flags: RequiresFlags::MANDATED,
+				version: None,
+			});
+		}
+		for require in &self.requires {
+			requires.push(ModuleRequiresEntry {
+				module: module(cp, &require.module)?,
+				flags: require.flags,
+				version: utf8(cp, &require.version)?,
+			});
+		}
+		let access = |cp: &mut _, access: &ModulePackageAccess| {
+			let package = CPPackageInfoRef::find_or_add(cp, &access.package)?;
+			let to = access
+				.to
+				.iter()
+				.map(|name| module(cp, name))
+				.collect::<Result<Vec<_>, _>>()?;
+			Ok::<_, IRClassfileError>((package, access.flags, to))
+		};
+		let exports = self
+			.exports
+			.iter()
+			.map(|export| {
+				let (package, flags, exports) = access(cp, export)?;
+				Ok(ModuleExportsEntry {
+					package,
+					flags,
+					exports,
+				})
+			})
+			.collect::<Result<_, IRClassfileError>>()?;
+		let opens = self
+			.opens
+			.iter()
+			.map(|open| {
+				let (package, flags, opens) = access(cp, open)?;
+				Ok(ModuleOpensEntry { package, flags, opens })
+			})
+			.collect::<Result<_, IRClassfileError>>()?;
+		let uses = self
+			.uses
+			.iter()
+			.map(|service| CPClassRef::find_or_add(cp, service))
+			.collect::<Result<_, _>>()?;
+		let provides = self
+			.provides
+			.iter()
+			.map(|provide| {
+				Ok(ModuleProvidesEntry {
+					class: CPClassRef::find_or_add(cp, &provide.service)?,
+					provides: provide
+						.implementations
+						.iter()
+						.map(|name| CPClassRef::find_or_add(cp, name))
+						.collect::<Result<_, _>>()?,
+				})
+			})
+			.collect::<Result<_, IRClassfileError>>()?;
+		let packages = self
+			.packages
+			.iter()
+			.map(|package| CPPackageInfoRef::find_or_add(cp, package))
+			.collect::<Result<Vec<_>, _>>()?;
+		let main_class = self
+			.main_class
+			.as_deref()
+			.map(|class| CPClassRef::find_or_add(cp, class))
+			.transpose()?;
+
+		let module_name = module(cp, &self.name)?;
+		let module_version = utf8(cp, &self.version)?;
+
+		builder.attribute(IRAttribute::Module {
+			module_name,
+			module_flags: self.flags,
+			module_version,
+			requires,
+			exports,
+			opens,
+			uses,
+			provides,
+		})?;
+		if !packages.is_empty() {
+			builder.attribute(IRAttribute::ModulePackages { packages })?;
+		}
+		if let Some(class) = main_class {
+			builder.attribute(IRAttribute::ModuleMainClass { class })?;
+		}
+		Ok(builder.build())
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, IRClassfileError> {
+		self.to_class()?.to_bytes()
+	}
+}
+
+impl ModulePackageAccess {
+	fn new(package: &str, to: &[&str]) -> Self {
+		Self {
+			package: package.to_string(),
+			flags: ModuleFlags::empty(),
+			to: to.iter().map(|module| module.to_string()).collect(),
+		}
+	}
+}
+
+impl From<ModuleInfo<'_>> for ModuleDescriptor {
+	fn from(info: ModuleInfo<'_>) -> Self {
+		let name = |module: &CPModuleInfoRef| module.data.data.to_string();
+		let class = |class: &CPClassRef| class.data.data.to_string();
+		let package = |package: &CPPackageInfoRef| package.data.data.to_string();
+		Self {
+			name: info.name.to_string(),
+			flags: info.flags,
+			version: info.version.map(str::to_string),
+			requires: info
+				.requires
+				.iter()
+				.map(|require| ModuleRequires {
+					module: name(&require.module),
+					flags: require.flags,
+					version: require.version.as_ref().map(|version| version.data.to_string()),
+				})
+				.collect(),
+			exports: info
+				.exports
+				.iter()
+				.map(|export| ModulePackageAccess {
+					package: package(&export.package),
+					flags: export.flags,
+					to: export.exports.iter().map(name).collect(),
+				})
+				.collect(),
+			opens: info
+				.opens
+				.iter()
+				.map(|open| ModulePackageAccess {
+					package: package(&open.package),
+					flags: open.flags,
+					to: open.opens.iter().map(name).collect(),
+				})
+				.collect(),
+			uses: info.uses.iter().map(class).collect(),
+			provides: info
+				.provides
+				.iter()
+				.map(|provide| ModuleProvides {
+					service: class(&provide.class),
+					implementations: provide.provides.iter().map(class).collect(),
+				})
+				.collect(),
+			packages: info.packages.iter().map(package).collect(),
+			main_class: info.main_class.map(str::to_string),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn write_and_read_descriptor() {
+		let descriptor = ModuleDescriptor::new("com.example")
+			.version("1.0")
+			.requires("java.sql", RequiresFlags::TRANSITIVE)
+			.requires("java.compiler", RequiresFlags::STATIC_PHASE)
+			.exports("com/example/api", &[])
+			.opens("com/example/model", &["com.fasterxml.jackson.databind"])
+			.uses("com/example/api/Plugin")
+			.provides("com/example/api/Plugin", &["com/example/impl/DefaultPlugin"])
+			.main_class("com/example/Main");
+		assert_eq!(
+			descriptor.packages,
+			[
+				"com/example/api",
+				"com/example/model",
+				"com/example/impl",
+				"com/example"
+			]
+		);
+
+		let class = IRClassFile::read(&descriptor.to_bytes().unwrap()).unwrap();
+		assert!(class.is_module_info());
+		let read = ModuleDescriptor::from_class(&class).unwrap();
+		assert_eq!(read.requires[0].module, "java.base");
+		assert!(read.requires[0].flags.is_mandated());
+		assert_eq!(read.requires[1..], descriptor.requires);
+		assert_eq!(
+			ModuleDescriptor {
+				requires: read.requires[1..].to_vec(),
+				..read
+			},
+			descriptor
+		);
+	}
+}
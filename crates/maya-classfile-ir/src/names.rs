@@ -0,0 +1,170 @@
+// Conversions between the different spellings of a class name.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.2.1
+//
+// binary name:   java.lang.String
+// internal name: java/lang/String (arrays use their descriptor: [Ljava/lang/String;)
+// descriptor:    Ljava/lang/String;
+
+pub fn binary_to_internal(binary: &str) -> String {
+	binary.replace('.', "/")
+}
+
+pub fn internal_to_binary(internal: &str) -> String {
+	internal.replace('/', ".")
+}
+
+pub fn internal_to_descriptor(internal: &str) -> String {
+	if internal.starts_with('[') {
+		internal.to_string()
+	} else {
+		format!("L{internal};")
+	}
+}
+
+pub fn binary_to_descriptor(binary: &str) -> String {
+	internal_to_descriptor(&binary_to_internal(binary))
+}
+
+/// Returns the internal name for an object or array descriptor, `None` for primitives and malformed input.
+pub fn descriptor_to_internal(descriptor: &str) -> Option<&str> {
+	if descriptor.starts_with('[') {
+		return Some(descriptor);
+	}
+
+	descriptor.strip_prefix('L')?.strip_suffix(';')
+}
+
+pub fn primitive_name(descriptor: char) -> Option<&'static str> {
+	Some(match descriptor {
+		'B' => "byte",
+		'C' => "char",
+		'D' => "double",
+		'F' => "float",
+		'I' => "int",
+		'J' => "long",
+		'S' => "short",
+		'Z' => "boolean",
+		'V' => "void",
+		_ => return None,
+	})
+}
+
+pub fn primitive_descriptor(name: &str) -> Option<char> {
+	Some(match name {
+		"byte" => 'B',
+		"char" => 'C',
+		"double" => 'D',
+		"float" => 'F',
+		"int" => 'I',
+		"long" => 'J',
+		"short" => 'S',
+		"boolean" => 'Z',
+		"void" => 'V',
+		_ => return None,
+	})
+}
+
+/// Turns a field descriptor into its Java source spelling, e.g. `[[I` -> `int[][]`.
+pub fn descriptor_to_source(descriptor: &str) -> Option<String> {
+	let dims = array_dimensions(descriptor);
+	let element = &descriptor[dims..];
+
+	let mut name = match element.len() {
+		1 => primitive_name(element.chars().next()?)?.to_string(),
+		_ => internal_to_binary(descriptor_to_internal(element)?),
+	};
+
+	for _ in 0..dims {
+		name.push_str("[]");
+	}
+
+	Some(name)
+}
+
+/// The inverse of `descriptor_to_source`, e.g. `java.lang.String[]` -> `[Ljava/lang/String;`.
+pub fn source_to_descriptor(source: &str) -> String {
+	let mut element = source.trim();
+	let mut dims = 0;
+	while let Some(rest) = element.strip_suffix("[]") {
+		element = rest.trim_end();
+		dims += 1;
+	}
+
+	let element = match primitive_descriptor(element) {
+		Some(c) => c.to_string(),
+		None => binary_to_descriptor(element),
+	};
+
+	array_of(&element, dims)
+}
+
+pub fn array_of(descriptor: &str, dims: usize) -> String {
+	let mut out = "[".repeat(dims);
+	out.push_str(descriptor);
+	out
+}
+
+pub fn array_dimensions(descriptor: &str) -> usize {
+	descriptor.bytes().take_while(|b| *b == b'[').count()
+}
+
+pub fn array_element(descriptor: &str) -> &str {
+	descriptor.trim_start_matches('[')
+}
+
+pub fn is_array(descriptor: &str) -> bool {
+	descriptor.starts_with('[')
+}
+
+/// Splits an internal name into package and simple name, the package is empty for the default package.
+pub fn split_package(internal: &str) -> (&str, &str) {
+	match internal.rfind('/') {
+		Some(idx) => (&internal[..idx], &internal[idx + 1..]),
+		None => ("", internal),
+	}
+}
+
+pub fn package_name(internal: &str) -> &str {
+	split_package(internal).0
+}
+
+pub fn simple_name(internal: &str) -> &str {
+	split_package(internal).1
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn class_names() {
+		assert_eq!(binary_to_internal("java.lang.String"), "java/lang/String");
+		assert_eq!(internal_to_binary("java/lang/String"), "java.lang.String");
+		assert_eq!(internal_to_descriptor("java/lang/String"), "Ljava/lang/String;");
+		assert_eq!(internal_to_descriptor("[I"), "[I");
+		assert_eq!(descriptor_to_internal("Ljava/lang/String;"), Some("java/lang/String"));
+		assert_eq!(descriptor_to_internal("[[I"), Some("[[I"));
+		assert_eq!(descriptor_to_internal("I"), None);
+	}
+
+	#[test]
+	fn arrays() {
+		assert_eq!(array_of("I", 2), "[[I");
+		assert_eq!(array_dimensions("[[Ljava/lang/Object;"), 2);
+		assert_eq!(array_element("[[Ljava/lang/Object;"), "Ljava/lang/Object;");
+		assert_eq!(descriptor_to_source("[[I").as_deref(), Some("int[][]"));
+		assert_eq!(
+			descriptor_to_source("[Ljava/lang/String;").as_deref(),
+			Some("java.lang.String[]")
+		);
+		assert_eq!(source_to_descriptor("java.lang.String[]"), "[Ljava/lang/String;");
+		assert_eq!(source_to_descriptor("long"), "J");
+	}
+
+	#[test]
+	fn packages() {
+		assert_eq!(split_package("java/util/Map$Entry"), ("java/util", "Map$Entry"));
+		assert_eq!(package_name("Main"), "");
+		assert_eq!(simple_name("a/b/C"), "C");
+	}
+}
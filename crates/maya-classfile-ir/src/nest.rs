@@ -0,0 +1,104 @@
+// Nests: since Java 11, a top-level class and the classes nested in it can access each other's private members without
+// synthetic accessors. The host lists its members in NestMembers and each member names the host in NestHost. A claim
+// to be in a nest only counts if the host confirms it and is in the same package, otherwise the class is the host of a
+// nest of its own, as it is without a NestHost attribute. Checking that takes loading the host, which is what the
+// resolver is for: `|name| cache.get(name)` on a `ClassCache` will do.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-5.html#jvms-5.4.4
+
+use std::rc::Rc;
+
+use crate::{classpath::ClasspathError, names, IRClassFile};
+
+impl IRClassFile {
+	/// The host named by the NestHost attribute, `None` if the class has none and hosts its own nest.
+	pub fn declared_nest_host(&self) -> Option<&str> {
+		self.attributes.nest_host().map(|host| host.data.data.as_str())
+	}
+
+	/// The members listed by the NestMembers attribute of a nest host, empty for other classes.
+	pub fn nest_members(&self) -> impl Iterator<Item = &str> {
+		self.attributes
+			.nest_members()
+			.unwrap_or_default()
+			.iter()
+			.map(|member| member.data.data.as_str())
+	}
+}
+
+/// The host of the nest `class` is in, as the JVM validates it. A class whose declared host can't be found, doesn't
+/// list it or is in another package is its own host.
+pub fn nest_host_of<R>(class: &IRClassFile, resolve: R) -> Result<String, ClasspathError>
+where
+	R: Fn(&str) -> Result<Option<Rc<IRClassFile>>, ClasspathError>,
+{
+	let name = class.class_name();
+	let Some(host) = class.declared_nest_host().filter(|host| *host != name) else {
+		return Ok(name.to_string());
+	};
+	if names::package_name(host) != names::package_name(name) {
+		return Ok(name.to_string());
+	}
+	let confirmed = resolve(host)?.is_some_and(|host| host.nest_members().any(|member| member == name));
+	Ok(match confirmed {
+		true => host.to_string(),
+		false => name.to_string(),
+	})
+}
+
+/// Whether `a` and `b` are in the same nest and so can access each other's private members.
+pub fn are_nestmates<R>(a: &IRClassFile, b: &IRClassFile, resolve: R) -> Result<bool, ClasspathError>
+where
+	R: Fn(&str) -> Result<Option<Rc<IRClassFile>>, ClasspathError>,
+{
+	if a.class_name() == b.class_name() {
+		return Ok(true);
+	}
+	Ok(nest_host_of(a, &resolve)? == nest_host_of(b, &resolve)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+
+	use super::*;
+	use crate::{attribute::IRAttribute, builder::ClassBuilder, class_pool::CPClassRef};
+
+	fn class(name: &str, host: Option<&str>, members: &[&str]) -> IRClassFile {
+		let mut builder = ClassBuilder::new(name).unwrap();
+		if let Some(host) = host {
+			let host = CPClassRef::find_or_add(builder.cp(), host).unwrap();
+			builder.attribute(IRAttribute::NestHost(host)).unwrap();
+		}
+		if !members.is_empty() {
+			let classes = members
+				.iter()
+				.map(|member| CPClassRef::find_or_add(builder.cp(), member))
+				.collect::<Result<_, _>>()
+				.unwrap();
+			builder.attribute(IRAttribute::NestMembers { classes }).unwrap();
+		}
+		builder.build()
+	}
+
+	#[test]
+	fn validate_nest_hosts() {
+		let outer = class("p/Outer", None, &["p/Outer$A", "p/Outer$B"]);
+		let a = class("p/Outer$A", Some("p/Outer"), &[]);
+		let b = class("p/Outer$B", Some("p/Outer"), &[]);
+		// Claims to be in the nest, but the host doesn't list it.
+		let impostor = class("p/Impostor", Some("p/Outer"), &[]);
+		let orphan = class("p/Orphan$C", Some("p/Orphan"), &[]);
+		let classes = BTreeMap::from([("p/Outer", Rc::new(outer.clone()))]);
+		let resolve = |name: &str| Ok(classes.get(name).cloned());
+
+		assert_eq!(outer.nest_members().collect::<Vec<_>>(), ["p/Outer$A", "p/Outer$B"]);
+		assert_eq!(a.declared_nest_host(), Some("p/Outer"));
+		assert_eq!(nest_host_of(&a, resolve).unwrap(), "p/Outer");
+		assert_eq!(nest_host_of(&outer, resolve).unwrap(), "p/Outer");
+		assert_eq!(nest_host_of(&impostor, resolve).unwrap(), "p/Impostor");
+		assert_eq!(nest_host_of(&orphan, resolve).unwrap(), "p/Orphan$C");
+		assert!(are_nestmates(&a, &b, resolve).unwrap());
+		assert!(are_nestmates(&outer, &b, resolve).unwrap());
+		assert!(!are_nestmates(&a, &impostor, resolve).unwrap());
+	}
+}
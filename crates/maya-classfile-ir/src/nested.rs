@@ -0,0 +1,128 @@
+// Nested classes, as far as the class file tells: the InnerClasses entry a class has for itself says what kind of
+// nested class it is, its name in source and the flags it was declared with, and EnclosingMethod where a local or
+// anonymous class was declared. Names with `$` in them are no evidence either way, `Outer$Inner` can be top-level.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.6
+// https://docs.oracle.com/javase/specs/jls/se22/html/jls-8.html#jls-8.1.3
+
+use crate::{
+	attribute::{InnerClassesAttributeClass, NestedKind},
+	flags::ClassAccessFlags,
+	names, IRClassFile,
+};
+
+impl IRClassFile {
+	/// The InnerClasses entry describing this class, `None` for top-level classes.
+	pub fn nested_entry(&self) -> Option<&InnerClassesAttributeClass> {
+		self.attributes.inner_classes()?.entry(self.class_name())
+	}
+
+	/// `None` for top-level classes.
+	pub fn nested_kind(&self) -> Option<NestedKind> {
+		self.nested_entry().map(InnerClassesAttributeClass::kind)
+	}
+
+	pub fn is_nested(&self) -> bool {
+		self.nested_entry().is_some()
+	}
+
+	pub fn is_member_class(&self) -> bool {
+		self.nested_kind() == Some(NestedKind::Member)
+	}
+
+	pub fn is_local_class(&self) -> bool {
+		self.nested_kind() == Some(NestedKind::Local)
+	}
+
+	pub fn is_anonymous_class(&self) -> bool {
+		self.nested_kind() == Some(NestedKind::Anonymous)
+	}
+
+	/// The name in source, `Entry` for `java/util/Map$Entry` and empty for anonymous classes.
+	pub fn simple_name(&self) -> &str {
+		match self.nested_entry() {
+			Some(entry) => entry.simple_name().unwrap_or_default(),
+			None => names::simple_name(self.class_name()),
+		}
+	}
+
+	/// The class a member class is declared in, `None` for top-level, local and anonymous classes.
+	pub fn declaring_class(&self) -> Option<&str> {
+		self.nested_entry()?.outer_name()
+	}
+
+	/// The innermost class around this one: the declaring class of a member class, the class whose method or
+	/// initializer declares a local or anonymous class.
+	pub fn enclosing_class(&self) -> Option<&str> {
+		let entry = self.nested_entry()?;
+		entry.outer_name().or_else(|| {
+			let (class, _) = self.attributes.enclosing_method()?;
+			Some(class.data.data.as_str())
+		})
+	}
+
+	/// The flags the class was declared with. Only the InnerClasses entry of a nested class has its real visibility
+	/// and `static`, the class itself is at most public.
+	pub fn declared_access_flags(&self) -> ClassAccessFlags {
+		self.nested_entry()
+			.map_or(self.access_flags, |entry| entry.inner_class_access_flags)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		attribute::{IRAttribute, InnerClassesAttribute},
+		builder::ClassBuilder,
+		class_pool::{CPClassRef, CPUtf8Ref},
+	};
+
+	fn nested(name: &str, outer: Option<&str>, simple_name: Option<&str>, flags: ClassAccessFlags) -> IRClassFile {
+		let mut builder = ClassBuilder::new(name).unwrap();
+		let cp = builder.cp();
+		let entry = InnerClassesAttributeClass {
+			inner_class_info: CPClassRef::find_or_add(cp, name).unwrap(),
+			outer_class_info: outer.map(|outer| CPClassRef::find_or_add(cp, outer).unwrap()),
+			inner_name: simple_name.map(|simple_name| CPUtf8Ref::find_or_add(cp, simple_name).unwrap()),
+			inner_class_access_flags: flags,
+		};
+		builder
+			.attribute(IRAttribute::InnerClasses(InnerClassesAttribute {
+				classes: vec![entry],
+			}))
+			.unwrap();
+		if outer.is_none() {
+			let class = CPClassRef::find_or_add(builder.cp(), "p/Outer").unwrap();
+			builder
+				.attribute(IRAttribute::EnclosingMethod { class, method: None })
+				.unwrap();
+		}
+		builder.build()
+	}
+
+	#[test]
+	fn classify_nested_classes() {
+		let top = ClassBuilder::new("p/Outer$Top").unwrap().build();
+		assert!(!top.is_nested());
+		assert_eq!(top.simple_name(), "Outer$Top");
+		assert_eq!(top.enclosing_class(), None);
+
+		let private_static = ClassAccessFlags::PRIVATE | ClassAccessFlags::STATIC;
+		let member = nested("p/Outer$Inner", Some("p/Outer"), Some("Inner"), private_static);
+		assert!(member.is_member_class());
+		assert_eq!(member.simple_name(), "Inner");
+		assert_eq!(member.declaring_class(), Some("p/Outer"));
+		assert_eq!(member.declared_access_flags(), private_static);
+
+		let local = nested("p/Outer$1Local", None, Some("Local"), ClassAccessFlags::empty());
+		assert!(local.is_local_class());
+		assert_eq!(local.simple_name(), "Local");
+		assert_eq!(local.declaring_class(), None);
+		assert_eq!(local.enclosing_class(), Some("p/Outer"));
+
+		let anonymous = nested("p/Outer$1", None, None, ClassAccessFlags::empty());
+		assert!(anonymous.is_anonymous_class());
+		assert_eq!(anonymous.simple_name(), "");
+		assert_eq!(anonymous.enclosing_class(), Some("p/Outer"));
+	}
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use crate::{
+	attribute::RuntimeAnnotation,
+	classpath::{self, ClassProvider, Classpath, ClasspathError},
+	names, IRClassFile,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct PackageEntry {
+	// internal form, empty for the default package
+	pub name: String,
+	// internal names of the classes in this package, package-info excluded
+	pub classes: Vec<String>,
+	pub has_package_info: bool,
+	// Annotations declared on package-info, visible and invisible.
+	pub annotations: Vec<RuntimeAnnotation>,
+	// The module whose module-info lists this package.
+	pub module: Option<String>,
+}
+
+/// Classes grouped by package, along with their package-info annotations and owning module.
+#[derive(Debug, Clone, Default)]
+pub struct PackageIndex {
+	packages: BTreeMap<String, PackageEntry>,
+}
+
+impl PackageIndex {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn from_classes<'a>(classes: impl IntoIterator<Item = &'a IRClassFile>) -> Self {
+		let mut index = Self::new();
+		for class in classes {
+			index.add(class);
+		}
+		index
+	}
+
+	/// Every class on `classpath`, those shadowed by an earlier entry left out.
+	pub fn from_classpath(classpath: &Classpath) -> Result<Self, ClasspathError> {
+		Self::from_provider(classpath)
+	}
+
+	/// Every class `provider` lists, e.g. an `Archive` or a single jar. Classes are parsed one at a time and only
+	/// their names kept.
+	pub fn from_provider(provider: &dyn ClassProvider) -> Result<Self, ClasspathError> {
+		let mut index = Self::new();
+		for name in provider.class_names()? {
+			if let Some(bytes) = provider.read_class(&name)? {
+				index.add(&classpath::decode(&name, &bytes)?);
+			}
+		}
+		Ok(index)
+	}
+
+	fn entry(&mut self, package: &str) -> &mut PackageEntry {
+		self.packages
+			.entry(package.to_string())
+			.or_insert_with(|| PackageEntry {
+				name: package.to_string(),
+				..Default::default()
+			})
+	}
+
+	pub fn add(&mut self, class: &IRClassFile) {
+		if let Some(module) = class.module_info() {
+			for package in module.all_packages() {
+				self.entry(package).module = Some(module.name.to_string());
+			}
+			return;
+		}
+
+		let entry = self.entry(names::package_name(class.class_name()));
+		if class.is_package_info() {
+			entry.has_package_info = true;
+			entry.annotations.extend(class.package_annotations().cloned());
+		} else if !class.is_module_info() {
+			entry.classes.push(class.class_name().to_string());
+		}
+	}
+
+	pub fn get(&self, package: &str) -> Option<&PackageEntry> {
+		self.packages.get(package)
+	}
+
+	/// Packages in name order.
+	pub fn packages(&self) -> impl Iterator<Item = &PackageEntry> {
+		self.packages.values()
+	}
+
+	pub fn classes_in(&self, package: &str) -> &[String] {
+		self.get(package).map_or(&[], |entry| &entry.classes)
+	}
+
+	pub fn module_of(&self, package: &str) -> Option<&str> {
+		self.get(package)?.module.as_deref()
+	}
+
+	/// Packages owned by `module`, in name order.
+	pub fn packages_of_module<'a>(&'a self, module: &'a str) -> impl Iterator<Item = &'a PackageEntry> {
+		self.packages()
+			.filter(move |entry| entry.module.as_deref() == Some(module))
+	}
+
+	pub fn len(&self) -> usize {
+		self.packages.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.packages.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::rc::Rc;
+
+	use super::*;
+	use crate::{asm::assemble, classpath::MemoryProvider, module::ModuleDescriptor};
+
+	#[test]
+	fn index_a_classpath() {
+		let mut provider = MemoryProvider::new();
+		for source in [
+			".class public super p/api/Shape",
+			".class public super p/api/Circle\n.super p/api/Shape",
+			".class public super p/impl/Cache",
+			".class public super Main",
+			".class interface abstract synthetic p/api/package-info\n.annotation invisible Lp/Stable;\nsince I 2\n.end \
+			 annotation",
+		] {
+			let class = assemble(source).unwrap();
+			provider.insert(class.class_name(), class.to_bytes().unwrap());
+		}
+		let module = ModuleDescriptor::new("app").exports("p/api", &[]).package("p/impl");
+		provider.insert("module-info", module.to_bytes().unwrap());
+
+		let mut classpath = Classpath::new(Vec::new());
+		classpath.push_provider(Rc::new(provider));
+		let index = PackageIndex::from_classpath(&classpath).unwrap();
+
+		assert_eq!(
+			index.packages().map(|entry| entry.name.as_str()).collect::<Vec<_>>(),
+			["", "p/api", "p/impl"]
+		);
+		assert_eq!(index.classes_in("p/api"), ["p/api/Circle", "p/api/Shape"]);
+		assert_eq!(index.classes_in(""), ["Main"]);
+
+		let api = index.get("p/api").unwrap();
+		assert!(api.has_package_info);
+		assert_eq!(api.annotations.len(), 1);
+		assert_eq!(api.annotations[0].ty.data.as_str(), "Lp/Stable;");
+		assert!(!index.get("p/impl").unwrap().has_package_info);
+
+		assert_eq!(index.module_of("p/api"), Some("app"));
+		assert_eq!(index.module_of("p/impl"), Some("app"));
+		assert_eq!(index.module_of(""), None);
+		assert_eq!(
+			index
+				.packages_of_module("app")
+				.map(|entry| entry.name.as_str())
+				.collect::<Vec<_>>(),
+			["p/api", "p/impl"]
+		);
+	}
+}
@@ -0,0 +1,100 @@
+// Parsing classes across threads. Whole-program tools read thousands of classes before they can do anything else, and
+// parsing them one at a time leaves every core but one idle. The bytes are parsed on rayon's thread pool and each
+// class is handed back as soon as it's done, so the caller can start on the first ones while the rest are parsing.
+// Classes come back in the order they finish, not the order they went in.
+
+use std::sync::mpsc::{self, Receiver};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+	archive::Archive,
+	classpath::{decode, ClassProvider, ClasspathError},
+	IRClassFile,
+};
+
+/// The classes being parsed, each as it's done. Dropping it stops the parsing of the ones not started yet.
+#[derive(Debug)]
+pub struct Parsing {
+	receiver: Receiver<Result<IRClassFile, ClasspathError>>,
+}
+
+impl Iterator for Parsing {
+	type Item = Result<IRClassFile, ClasspathError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.receiver.recv().ok()
+	}
+}
+
+/// Parses class bytes by their internal names, the names going into the errors.
+pub fn parse_classes(classes: impl IntoIterator<Item = (String, Vec<u8>)>) -> Parsing {
+	let classes = classes.into_iter().collect::<Vec<_>>();
+	let (sender, receiver) = mpsc::channel();
+	rayon::spawn(move || {
+		// Sending only fails once nobody is listening, and then there's no point in parsing the rest.
+		let _ = classes
+			.into_par_iter()
+			.try_for_each_with(sender, |sender, (name, bytes)| {
+				sender.send(decode(&name, &bytes)).map_err(drop)
+			});
+	});
+	Parsing { receiver }
+}
+
+/// Parses the classes of an archive, duplicates kept by its policy included.
+pub fn parse_archive(archive: &Archive) -> Parsing {
+	parse_classes(archive.classes().map(|entry| {
+		let name = entry.name.strip_suffix(".class").unwrap_or(&entry.name);
+		(name.to_string(), entry.data.clone())
+	}))
+}
+
+/// Parses every class the provider lists. Reading them happens up front, on this thread, as providers aren't made to
+/// be shared between threads.
+pub fn read_classes(provider: &dyn ClassProvider) -> Result<Parsing, ClasspathError> {
+	let mut classes = Vec::new();
+	for name in provider.class_names()? {
+		if let Some(bytes) = provider.read_class(&name)? {
+			classes.push((name, bytes));
+		}
+	}
+	Ok(parse_classes(classes))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{builder::ClassBuilder, classpath::MemoryProvider};
+
+	#[test]
+	fn parse_in_parallel() {
+		let mut provider = MemoryProvider::new();
+		for index in 0..200 {
+			let name = format!("p/C{index}");
+			provider.insert(&name, ClassBuilder::new(&name).unwrap().to_bytes().unwrap());
+		}
+		provider.insert("p/Broken", b"\xca\xfe".to_vec());
+
+		let (classes, errors): (Vec<_>, Vec<_>) = read_classes(&provider).unwrap().partition(Result::is_ok);
+		let mut names = classes
+			.into_iter()
+			.map(|class| class.unwrap().class_name().to_string())
+			.collect::<Vec<_>>();
+		names.sort();
+		assert_eq!(names, provider.class_names().unwrap()[1..]);
+		assert!(matches!(
+			&errors[..],
+			[Err(ClasspathError::Class { name, .. })] if name == "p/Broken"
+		));
+
+		// Whatever was parsed can go to another thread.
+		let class = parse_classes([("p/C0".to_string(), provider.read_class("p/C0").unwrap().unwrap())])
+			.next()
+			.unwrap()
+			.unwrap();
+		std::thread::spawn(move || class.class_name().to_string())
+			.join()
+			.unwrap();
+	}
+}
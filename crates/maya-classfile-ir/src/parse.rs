@@ -0,0 +1,213 @@
+use std::fmt::{self, Display};
+
+use maya_bytes::BytesReadExt;
+use maya_classfile_io::limits::Limits;
+
+use crate::{
+	class_pool::{IRClassfileError, IRCpTag},
+	ClassFileVersion,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+	/// Any malformed attribute fails the whole class.
+	#[default]
+	Strict,
+	/// Malformed non-critical attributes are kept as `IRAttribute::Unknown` and reported as a warning.
+	/// The constant pool and the attributes the JVM itself depends on still parse strictly.
+	Lenient,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+	pub mode: ParseMode,
+	pub limits: Limits,
+}
+
+impl ParseOptions {
+	pub fn lenient() -> Self {
+		Self {
+			mode: ParseMode::Lenient,
+			..Default::default()
+		}
+	}
+
+	pub fn with_limits(mut self, limits: Limits) -> Self {
+		self.limits = limits;
+		self
+	}
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseWarning {
+	RecoveredAttribute { name: String, reason: String },
+	// The attribute parsed, but left some of its declared bytes unread.
+	AttributeLengthMismatch { name: String, declared: u32, consumed: u64 },
+	// A predefined attribute in a class older than the attribute, kept as raw data since the JVM ignores it.
+	UnsupportedAttribute { name: String, version: ClassFileVersion },
+}
+
+impl Display for ParseWarning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::RecoveredAttribute { name, reason } => {
+				write!(
+					f,
+					"attribute {name} could not be parsed and was kept as raw data: {reason}"
+				)
+			}
+			Self::AttributeLengthMismatch {
+				name,
+				declared,
+				consumed,
+			} => {
+				write!(
+					f,
+					"attribute {name} declares {declared} bytes but only {consumed} were parsed"
+				)
+			}
+			Self::UnsupportedAttribute { name, version } => {
+				write!(
+					f,
+					"attribute {name} isn't recognized in class file version {version} and was kept as raw data"
+				)
+			}
+		}
+	}
+}
+
+/// State threaded through attribute parsing.
+pub struct ParseContext<'a> {
+	pub cp: &'a [IRCpTag],
+	pub options: &'a ParseOptions,
+	pub warnings: Vec<ParseWarning>,
+	/// The version of the class being parsed, attributes it predates are kept as raw data. `None` parses them all.
+	pub version: Option<ClassFileVersion>,
+	depth: u16,
+}
+
+impl<'a> ParseContext<'a> {
+	pub fn new(cp: &'a [IRCpTag], options: &'a ParseOptions) -> Self {
+		Self {
+			cp,
+			options,
+			warnings: Vec::new(),
+			version: None,
+			depth: 0,
+		}
+	}
+
+	pub fn with_version(mut self, version: ClassFileVersion) -> Self {
+		self.version = Some(version);
+		self
+	}
+
+	pub fn is_lenient(&self) -> bool {
+		self.options.mode == ParseMode::Lenient
+	}
+
+	pub fn limits(&self) -> &Limits {
+		&self.options.limits
+	}
+
+	/// Runs `parse` one nesting level deeper, failing once `Limits::max_attribute_depth` is reached.
+	pub fn nested<T>(
+		&mut self,
+		parse: impl FnOnce(&mut Self) -> Result<T, IRClassfileError>,
+	) -> Result<T, IRClassfileError> {
+		let limit = self.options.limits.max_attribute_depth;
+		Limits::check("attribute nesting depth", limit.into(), u64::from(self.depth) + 1)?;
+
+		self.depth += 1;
+		let result = parse(self);
+		self.depth -= 1;
+		result
+	}
+}
+
+/// Capacity to reserve for `count` elements, capped by the bytes left in `buffer` since every element takes at least one.
+/// Keeps a forged count from reserving far more memory than the input could ever fill.
+pub(crate) fn capacity<B: BytesReadExt>(buffer: &mut B, count: usize) -> Result<usize, IRClassfileError> {
+	let remaining = usize::try_from(buffer.remaining()?).unwrap_or(usize::MAX);
+	Ok(count.min(remaining))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use maya_classfile_io::IOAttributeInfo;
+
+	use super::*;
+	use crate::{
+		attribute::{IRAttribute, IRAttributeInfo, RuntimeAnnotationValue},
+		Shared,
+	};
+
+	fn utf8_pool(values: &[&str]) -> Vec<IRCpTag> {
+		values
+			.iter()
+			.map(|value| IRCpTag::Utf8(Shared::new(value.to_string())))
+			.collect()
+	}
+
+	fn attribute(name_index: u16, info: &[u8]) -> IOAttributeInfo {
+		IOAttributeInfo {
+			attribute_name_index: name_index,
+			attribute_length: info.len() as u32,
+			info: info.to_vec(),
+		}
+	}
+
+	// An array element value nested `depth` times.
+	fn nested_array(depth: usize) -> Vec<u8> {
+		let mut bytes = [b'[', 0, 1].repeat(depth);
+		bytes.extend([b'[', 0, 0]);
+		bytes
+	}
+
+	#[test]
+	fn nesting_depth_limit() {
+		let options = ParseOptions::default();
+		let mut ctx = ParseContext::new(&[], &options);
+		assert!(RuntimeAnnotationValue::new(&mut ctx, &mut Cursor::new(nested_array(8))).is_ok());
+
+		let err = RuntimeAnnotationValue::new(&mut ctx, &mut Cursor::new(nested_array(10_000))).unwrap_err();
+		assert!(matches!(err, IRClassfileError::LimitExceeded(_)));
+	}
+
+	#[test]
+	fn lenient_recovers_optional_attributes() {
+		// A signature at pool index 0.
+		let cp = utf8_pool(&["Signature"]);
+		let broken = || attribute(1, &[0, 0]);
+		let strict = ParseOptions::default();
+		let mut ctx = ParseContext::new(&cp, &strict);
+		assert!(matches!(
+			IRAttributeInfo::from_io(&mut ctx, broken()),
+			Err(IRClassfileError::InvalidCpIndex(0))
+		));
+
+		let lenient = ParseOptions::lenient();
+		let mut ctx = ParseContext::new(&cp, &lenient);
+		let attr = IRAttributeInfo::from_io(&mut ctx, broken()).unwrap();
+		assert!(matches!(&attr.attr, IRAttribute::Unknown(bytes) if bytes == &[0, 0]));
+		assert!(matches!(
+			&ctx.warnings[..],
+			[ParseWarning::RecoveredAttribute { name, .. }] if name == "Signature"
+		));
+	}
+
+	#[test]
+	fn lenient_keeps_critical_attributes_strict() {
+		let critical = ["ConstantValue", "Code", "StackMapTable", "BootstrapMethods", "NestHost"];
+		let cp = utf8_pool(&critical);
+		let lenient = ParseOptions::lenient();
+		let mut ctx = ParseContext::new(&cp, &lenient);
+		// Each cut off part way through its first field.
+		for name_index in 1..=critical.len() as u16 {
+			assert!(IRAttributeInfo::from_io(&mut ctx, attribute(name_index, &[0])).is_err());
+		}
+		assert!(ctx.warnings.is_empty());
+	}
+}
@@ -0,0 +1,505 @@
+// Patterns over instruction sequences, for what would otherwise be a hand-written loop and slice `match`: peephole
+// rules, signature scans for known code shapes, lints. A pattern is a list of instructions separated by `;` or new
+// lines, e.g.
+//
+//     aload $obj; getfield p/Node next; ifnull
+//
+// Each is a mnemonic and then its operands, each a word. Mnemonics are those of the general form, so `aload` is also
+// `aload_0` and `ldc` is also `ldc_w`, and `goto` and `jsr` are also their wide forms. Mnemonics and operands are
+// globs, `*` standing for any run of characters and `?` for any one, and `_` is anything. `$name` is anything too,
+// captured under that name, and a name used again must be the same operand. Operands left off at the end match
+// anything. An operand with spaces in it, a string mostly, goes in double quotes.
+//
+// `...` between two instructions is any number of instructions, `...3` at most three. Gaps match as few as they can.
+//
+// The operands are, in order:
+// - field and method instructions: the owner, the name and the descriptor, `invokedynamic` the last two
+// - `new`, `anewarray`, `checkcast` and `instanceof`: the class, `multianewarray` also the dimensions
+// - loads, stores and `ret`: the local, `iinc` also the increment
+// - `bipush` and `sipush`: the value, `newarray` the element type, e.g. `int`
+// - `ldc`: a string or number as is, the internal name of a class, the descriptor of a method type, else the kind of
+//   constant, e.g. `MethodHandle`
+// Branch targets aren't operands, they're positions in the code that depend on everything around them.
+//
+// The `;` closing a class name in a descriptor is the descriptor's own. Descriptors are always the last operand, so it
+// ends the instruction all the same: `getfield p/Node next Lp/Node; ifnull` is two instructions.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use thiserror::Error;
+
+use crate::{
+	class_pool::IRCpTag,
+	code::{Instructions, Opcodes},
+	disasm::array_type,
+	listing::{Item, Listing},
+	peephole::{PeepholeRule, Rewrite},
+	query::MemberRef,
+	search::{glob_matches, Hit},
+	IRClassFile,
+};
+
+#[derive(Debug, Error)]
+pub enum PatternError {
+	#[error("The instruction pattern is empty")]
+	Empty,
+	#[error("Instruction {element}: {reason}")]
+	Malformed { element: usize, reason: &'static str },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+	Any,
+	Capture(String),
+	Glob(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Element {
+	Insn { mnemonic: Operand, operands: Vec<Operand> },
+	// at most this many instructions, any number if None
+	Gap(Option<usize>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InsnPattern {
+	elements: Vec<Element>,
+}
+
+/// Where a pattern matched, as indices into the instructions or items it was matched against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternMatch {
+	pub start: usize,
+	// exclusive
+	pub end: usize,
+	pub captures: BTreeMap<String, String>,
+}
+
+// An instruction as the pattern sees it.
+#[derive(Debug)]
+struct Insn {
+	mnemonic: &'static str,
+	operands: Vec<String>,
+}
+
+impl InsnPattern {
+	pub fn parse(text: &str) -> Result<Self, PatternError> {
+		let mut elements = Vec::new();
+		let mut words = Vec::new();
+		let mut rest = text;
+		loop {
+			rest = rest.trim_start_matches(|c: char| c.is_whitespace() && c != '\n');
+			let Some(c) = rest.chars().next() else {
+				close(&mut elements, &mut words)?;
+				break;
+			};
+			if c == '\n' || c == ';' {
+				close(&mut elements, &mut words)?;
+				rest = &rest[1..];
+			} else if let Some(quoted) = rest.strip_prefix('"') {
+				let end = quoted.find('"').ok_or(PatternError::Malformed {
+					element: elements.len() + 1,
+					reason: "unclosed quote",
+				})?;
+				words.push((quoted[..end].to_string(), true));
+				rest = &quoted[end + 1..];
+			} else {
+				// Only a class name in a descriptor has a `;` of its own.
+				let descriptor = rest.starts_with(['L', '[', '(']);
+				let (mut end, mut in_class) = (0, false);
+				for (index, c) in rest.char_indices() {
+					if c.is_whitespace() || c == '"' || (c == ';' && !in_class) {
+						break;
+					}
+					in_class = descriptor && (c == 'L' || in_class) && c != ';';
+					end = index + c.len_utf8();
+				}
+				let word = &rest[..end];
+				rest = &rest[end..];
+				words.push((word.to_string(), false));
+				if descriptor && word.ends_with(';') {
+					close(&mut elements, &mut words)?;
+				}
+			}
+		}
+
+		match (elements.first(), elements.last()) {
+			(None, _) => Err(PatternError::Empty),
+			(Some(Element::Gap(_)), _) => Err(PatternError::Malformed {
+				element: 1,
+				reason: "a pattern can't start with a gap",
+			}),
+			(_, Some(Element::Gap(_))) => Err(PatternError::Malformed {
+				element: elements.len(),
+				reason: "a pattern can't end with a gap",
+			}),
+			_ => Ok(Self { elements }),
+		}
+	}
+
+	/// The match starting with `code[at]`, in code as `Instructions::read_all` decodes it.
+	pub fn match_at(&self, code: &[(u32, Instructions)], at: usize) -> Option<PatternMatch> {
+		let insns = code
+			.iter()
+			.map(|(_, instruction)| Some(describe(instruction)))
+			.collect::<Vec<_>>();
+		self.anchored(&insns, at)
+	}
+
+	/// The matches in code as `Instructions::read_all` decodes it, first to last, none overlapping.
+	pub fn find_all(&self, code: &[(u32, Instructions)]) -> Vec<PatternMatch> {
+		let insns = code
+			.iter()
+			.map(|(_, instruction)| Some(describe(instruction)))
+			.collect::<Vec<_>>();
+		let mut matches = Vec::new();
+		let mut at = 0;
+		while at < insns.len() {
+			match self.anchored(&insns, at) {
+				Some(found) => {
+					at = found.end;
+					matches.push(found);
+				}
+				None => at += 1,
+			}
+		}
+		matches
+	}
+
+	/// The match at the start of listing items, `Listing::block` say. Labels and lines between the instructions are
+	/// passed over, and the end is after the last instruction matched.
+	pub fn match_items(&self, items: &[Item]) -> Option<PatternMatch> {
+		let insns = items.iter().map(describe_item).collect::<Vec<_>>();
+		let start = next(&insns, 0)?;
+		self.anchored(&insns, start)
+	}
+
+	fn anchored(&self, insns: &[Option<Insn>], start: usize) -> Option<PatternMatch> {
+		insns.get(start)?.as_ref()?;
+		let mut captures = BTreeMap::new();
+		let end = self.match_from(0, insns, start, &mut captures)?;
+		Some(PatternMatch { start, end, captures })
+	}
+
+	// The end of the match of the elements from `element` on with the instructions from `at` on.
+	fn match_from(
+		&self,
+		element: usize,
+		insns: &[Option<Insn>],
+		at: usize,
+		captures: &mut BTreeMap<String, String>,
+	) -> Option<usize> {
+		match self.elements.get(element) {
+			None => Some(at),
+			Some(Element::Insn { mnemonic, operands }) => {
+				let index = next(insns, at)?;
+				let insn = insns[index].as_ref()?;
+				let saved = captures.clone();
+				let matched = mnemonic.matches(insn.mnemonic, captures)
+					&& operands.len() <= insn.operands.len()
+					&& operands
+						.iter()
+						.zip(&insn.operands)
+						.all(|(operand, value)| operand.matches(value, captures));
+				if matched {
+					if let Some(end) = self.match_from(element + 1, insns, index + 1, captures) {
+						return Some(end);
+					}
+				}
+				*captures = saved;
+				None
+			}
+			Some(Element::Gap(max)) => {
+				let (mut at, mut skipped) = (at, 0);
+				loop {
+					if let Some(end) = self.match_from(element + 1, insns, at, captures) {
+						return Some(end);
+					}
+					if max.is_some_and(|max| skipped == max) {
+						return None;
+					}
+					at = next(insns, at)? + 1;
+					skipped += 1;
+				}
+			}
+		}
+	}
+}
+
+impl FromStr for InsnPattern {
+	type Err = PatternError;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		Self::parse(text)
+	}
+}
+
+impl Operand {
+	fn parse(word: &str, quoted: bool) -> Self {
+		match word.strip_prefix('$') {
+			_ if quoted => Self::Glob(word.to_string()),
+			_ if word == "_" => Self::Any,
+			Some(name) if !name.is_empty() => Self::Capture(name.to_string()),
+			_ => Self::Glob(word.to_string()),
+		}
+	}
+
+	fn matches(&self, value: &str, captures: &mut BTreeMap<String, String>) -> bool {
+		match self {
+			Self::Any => true,
+			Self::Capture(name) => match captures.get(name) {
+				Some(captured) => captured == value,
+				None => {
+					captures.insert(name.clone(), value.to_string());
+					true
+				}
+			},
+			Self::Glob(glob) => glob_matches(glob.as_bytes(), value.as_bytes()),
+		}
+	}
+}
+
+// Ends the instruction of the words so far, if there are any.
+fn close(elements: &mut Vec<Element>, words: &mut Vec<(String, bool)>) -> Result<(), PatternError> {
+	let Some(((first, quoted), operands)) = words.split_first() else {
+		return Ok(());
+	};
+	let malformed = |reason| PatternError::Malformed {
+		element: elements.len() + 1,
+		reason,
+	};
+	let element = match first.strip_prefix("...") {
+		Some(_) if !operands.is_empty() => return Err(malformed("a gap has no operands")),
+		Some("") if !quoted => Element::Gap(None),
+		Some(max) if !quoted => Element::Gap(Some(max.parse().map_err(|_| malformed("invalid gap length"))?)),
+		_ => Element::Insn {
+			mnemonic: Operand::parse(first, *quoted),
+			operands: operands
+				.iter()
+				.map(|(word, quoted)| Operand::parse(word, *quoted))
+				.collect(),
+		},
+	};
+	elements.push(element);
+	words.clear();
+	Ok(())
+}
+
+// The first instruction from `at` on.
+fn next(insns: &[Option<Insn>], at: usize) -> Option<usize> {
+	(at..insns.len()).find(|index| insns[*index].is_some())
+}
+
+fn describe_item(item: &Item) -> Option<Insn> {
+	let mnemonic = match item {
+		Item::Label(_) | Item::Line(_) => return None,
+		Item::Insn(instruction) => return Some(describe(instruction)),
+		Item::Jump { opcode, .. } => Opcodes::mnemonic(*opcode).unwrap_or_default(),
+		Item::TableSwitch { .. } => "tableswitch",
+		Item::LookupSwitch { .. } => "lookupswitch",
+	};
+	Some(Insn {
+		mnemonic,
+		operands: Vec::new(),
+	})
+}
+
+fn describe(instruction: &Instructions) -> Insn {
+	use Instructions as I;
+
+	let member =
+		|owner: &str, name: &str, descriptor: &str| vec![owner.to_string(), name.to_string(), descriptor.to_string()];
+	let operands = match instruction {
+		I::GETSTATIC(field) | I::PUTSTATIC(field) | I::GETFIELD(field) | I::PUTFIELD(field) => member(
+			&field.class.data.data,
+			&field.name_and_ty.name.data,
+			&field.name_and_ty.ty.data,
+		),
+		I::INVOKEVIRTUAL(method) | I::INVOKESPECIAL(method) | I::INVOKESTATIC(method) => member(
+			&method.class.data.data,
+			&method.name_and_ty.name.data,
+			&method.name_and_ty.ty.data,
+		),
+		I::INVOKEINTERFACE { method, .. } => member(
+			&method.class.data.data,
+			&method.name_and_ty.name.data,
+			&method.name_and_ty.ty.data,
+		),
+		I::INVOKEDYNAMIC(call_site) => vec![
+			call_site.name_and_ty.name.data.to_string(),
+			call_site.name_and_ty.ty.data.to_string(),
+		],
+		I::NEW(class) | I::ANEWARRAY(class) | I::CHECKCAST(class) | I::INSTANCEOF(class) => {
+			vec![class.data.data.to_string()]
+		}
+		I::MULTIANEWARRAY { class, dimensions } => vec![class.data.data.to_string(), dimensions.to_string()],
+		I::ILOAD(index)
+		| I::LLOAD(index)
+		| I::FLOAD(index)
+		| I::DLOAD(index)
+		| I::ALOAD(index)
+		| I::ISTORE(index)
+		| I::LSTORE(index)
+		| I::FSTORE(index)
+		| I::DSTORE(index)
+		| I::ASTORE(index)
+		| I::RET(index) => vec![index.to_string()],
+		I::IINC { index, r#const } => vec![index.to_string(), r#const.to_string()],
+		I::BIPUSH(value) => vec![value.to_string()],
+		I::SIPUSH(value) => vec![value.to_string()],
+		I::NEWARRAY(atype) => vec![array_type(*atype).to_string()],
+		I::LDC(constant) => vec![match constant {
+			IRCpTag::String(string) | IRCpTag::Class(string) | IRCpTag::MethodType(string) => string.data.to_string(),
+			IRCpTag::Integer(value) => value.to_string(),
+			IRCpTag::Float(value) => value.to_string(),
+			IRCpTag::Long(value) => value.to_string(),
+			IRCpTag::Double(value) => value.to_string(),
+			tag => tag.kind_name().to_string(),
+		}],
+		_ => Vec::new(),
+	};
+	let mnemonic = match instruction.opcode() {
+		Opcodes::GOTO_W => "goto",
+		Opcodes::JSR_W => "jsr",
+		opcode => Opcodes::mnemonic(opcode).unwrap_or_default(),
+	};
+	Insn { mnemonic, operands }
+}
+
+type Rewriter = dyn Fn(&[Item], &PatternMatch) -> Option<Vec<Item>>;
+
+/// A peephole rule replacing what a pattern matches with what `rewrite` makes of the items matched, if anything.
+pub struct PatternRule {
+	name: String,
+	pattern: InsnPattern,
+	rewrite: Box<Rewriter>,
+}
+
+impl PatternRule {
+	pub fn new(
+		name: &str,
+		pattern: InsnPattern,
+		rewrite: impl Fn(&[Item], &PatternMatch) -> Option<Vec<Item>> + 'static,
+	) -> Self {
+		Self {
+			name: name.to_string(),
+			pattern,
+			rewrite: Box::new(rewrite),
+		}
+	}
+}
+
+impl PeepholeRule for PatternRule {
+	fn name(&self) -> &str {
+		&self.name
+	}
+
+	fn rewrite(&self, listing: &Listing, at: usize) -> Option<Rewrite> {
+		let block = listing.block(at);
+		let found = self.pattern.match_items(block)?;
+		let with = (self.rewrite)(&block[..found.end], &found)?;
+		Some(Rewrite {
+			replaced: found.end,
+			with,
+		})
+	}
+}
+
+impl IRClassFile {
+	/// The matches of a pattern in every method, each with the offset of its first instruction. The indices of a
+	/// match are into the method's instructions as `Instructions::read_all` decodes them.
+	pub fn find_pattern(&self, pattern: &InsnPattern) -> Vec<(Hit, PatternMatch)> {
+		let mut found = Vec::new();
+		for method in &self.methods {
+			let Some(Ok(code)) = method.code().map(|code| Instructions::read_all(&self.cp, &code.code)) else {
+				continue;
+			};
+			found.extend(pattern.find_all(&code).into_iter().map(|matched| {
+				let hit = Hit {
+					method: MemberRef::new(self.class_name(), method.name(), method.descriptor()),
+					offset: code[matched.start].0,
+				};
+				(hit, matched)
+			}));
+		}
+		found
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{asm::assemble, peephole::optimize};
+
+	#[test]
+	fn match_patterns() {
+		let class = assemble(
+			r#"
+.class public super p/Main
+.method public static size(Lp/Node;)I
+	iconst_0
+	istore_2
+	aload_0
+	getfield p/Node/next Lp/Node;
+	ifnull Empty
+	aload_0
+	getfield p/Node/next Lp/Node;
+	astore_1
+	aload_1
+	ldc "hello world"
+	invokevirtual p/Node/add(Ljava/lang/String;)V
+	iinc 2 1
+	iconst_1
+	ireturn
+Empty:
+	aload_0
+	aload_0
+	invokevirtual p/Node/link(Lp/Node;)V
+	iconst_0
+	ireturn
+.end method
+"#,
+		)
+		.unwrap();
+		let found = |pattern: &str| {
+			class
+				.find_pattern(&pattern.parse().unwrap())
+				.into_iter()
+				.map(|(hit, matched)| (hit.offset, matched.captures.into_values().collect::<Vec<_>>()))
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(
+			found("aload $obj; getfield p/Node next Lp/Node; if*null"),
+			[(2, vec!["0".to_string()])]
+		);
+		let fields = found("aload $a; getfield _ $f");
+		assert_eq!(fields.iter().map(|(offset, _)| *offset).collect::<Vec<_>>(), [2, 9]);
+		assert_eq!(fields[1].1, ["0", "next"]);
+		assert_eq!(
+			found("aload $x\naload $x\ninvokevirtual"),
+			[(25, vec!["0".to_string()])]
+		);
+		assert_eq!(found(r#"astore $x; ...1; ldc "hello *""#).len(), 1);
+		assert!(found(r#"astore; ldc "hello *""#).is_empty());
+		assert_eq!(found("getfield; ...; ireturn").len(), 1);
+		assert_eq!(found("iinc 2 $by; ...2; ireturn").len(), 1);
+		assert_eq!(found("iinc 2 $by; ...; iconst_0"), [(20, vec!["1".to_string()])]);
+		assert!(matches!(
+			InsnPattern::parse("...; nop"),
+			Err(PatternError::Malformed { element: 1, .. })
+		));
+		assert!(matches!(InsnPattern::parse(" ;\n"), Err(PatternError::Empty)));
+
+		let mut listing = Listing::from_code(&class.cp, class.methods[0].code().unwrap()).unwrap();
+		let rule = PatternRule::new("store_load", "astore $x; aload $x".parse().unwrap(), |items, _| {
+			Some(vec![Item::Insn(Instructions::DUP), items[0].clone()])
+		});
+		let applied = optimize(&mut listing, &[Box::new(rule)]);
+		assert_eq!(applied["store_load"], 1);
+		assert!(InsnPattern::parse("dup; astore 1; ldc")
+			.unwrap()
+			.match_items(&listing.items[7..])
+			.is_some());
+	}
+}
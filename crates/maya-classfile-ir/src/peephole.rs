@@ -0,0 +1,489 @@
+// Peephole optimization: small local rewrites of the instruction list, applied wherever they match until none does
+// anymore. A rewrite only ever replaces instructions between two labels, code nothing jumps into the middle of, so the
+// rules don't need to know about control flow to be safe. Code a rewrite leaves unreachable, e.g. after a branch that
+// became a `goto`, is removed along the way.
+//
+// The built-in rules are deliberately conservative and never change what the code computes, only how. Users add
+// their own by implementing `PeepholeRule`.
+
+use std::collections::BTreeMap;
+
+use crate::{
+	analysis::{
+		frames::{recompute_method_frames, ClassHierarchy},
+		stack,
+	},
+	attribute::{IRAttribute, IRAttributeInfo},
+	class_pool::{CPUtf8Ref, IRClassfileError, IRCpTag},
+	code::{Instructions, Opcodes},
+	listing::{Item, Listing},
+	transform::{ChangeLog, Transform},
+	IRClassFile,
+};
+
+/// Rules that keep matching, e.g. two undoing each other, are stopped after this many passes over the code.
+const MAX_PASSES: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Rewrite {
+	/// How many items from the matched position are replaced, none of them labels.
+	pub replaced: usize,
+	pub with: Vec<Item>,
+}
+
+pub trait PeepholeRule {
+	fn name(&self) -> &str;
+
+	/// The rewrite of the code at `listing.items[at]`, `None` if the rule doesn't apply there. `Listing::block` gives
+	/// the items a rewrite may replace. A rewrite should make the code simpler, so that rewriting ends.
+	fn rewrite(&self, listing: &Listing, at: usize) -> Option<Rewrite>;
+}
+
+/// The rules below, in the order they're tried.
+pub fn default_rules() -> Vec<Box<dyn PeepholeRule>> {
+	vec![
+		Box::new(RemoveNop),
+		Box::new(JumpThreading),
+		Box::new(ConstantBranch),
+		Box::new(RedundantPop),
+		Box::new(LoadStore),
+	]
+}
+
+/// Rewrites `listing` with `rules` until none applies anymore, and returns how often each rule applied.
+pub fn optimize(listing: &mut Listing, rules: &[Box<dyn PeepholeRule>]) -> BTreeMap<String, usize> {
+	let mut counts = BTreeMap::new();
+	for _ in 0..MAX_PASSES {
+		let mut changed = false;
+		let mut at = 0;
+		while at < listing.items.len() {
+			let rewrite = rules.iter().find_map(|rule| {
+				let rewrite = rule.rewrite(listing, at)?;
+				// Labels are where other code jumps in, replacing them would lose that.
+				let replaced = listing.items.get(at..at + rewrite.replaced)?;
+				(!replaced.iter().any(|item| matches!(item, Item::Label(_)))).then(|| (rule.name(), rewrite))
+			});
+			match rewrite {
+				Some((name, rewrite)) => {
+					*counts.entry(name.to_string()).or_default() += 1;
+					listing.items.splice(at..at + rewrite.replaced, rewrite.with);
+					changed = true;
+				}
+				None => at += 1,
+			}
+		}
+		if listing.remove_unreachable() > 0 {
+			changed = true;
+		}
+		listing.prune();
+		if !changed {
+			break;
+		}
+	}
+	counts
+}
+
+/// Optimizes every method of the classes it runs over. Changed methods get their max_stack and max_locals
+/// recomputed, and their frames from version 50 on, which is what the hierarchy is for.
+pub struct Peephole<H> {
+	hierarchy: H,
+	rules: Vec<Box<dyn PeepholeRule>>,
+}
+
+impl<H: ClassHierarchy> Peephole<H> {
+	/// With the default rules.
+	pub fn new(hierarchy: H) -> Self {
+		Self {
+			hierarchy,
+			rules: default_rules(),
+		}
+	}
+
+	/// With no rules, only removing unreachable code until some are added.
+	pub fn empty(hierarchy: H) -> Self {
+		Self {
+			hierarchy,
+			rules: Vec::new(),
+		}
+	}
+
+	/// Adds a rule, tried after the ones before it.
+	pub fn with_rule(mut self, rule: impl PeepholeRule + 'static) -> Self {
+		self.rules.push(Box::new(rule));
+		self
+	}
+}
+
+impl<H: ClassHierarchy> Transform for Peephole<H> {
+	fn name(&self) -> &str {
+		"peephole"
+	}
+
+	fn apply(&mut self, class: &mut IRClassFile, log: &mut ChangeLog) -> Result<(), IRClassfileError> {
+		let class_name = class.class_name().to_string();
+		for method in &mut class.methods {
+			let Some(code) = method.code() else {
+				continue;
+			};
+			let mut listing = Listing::from_code(&class.cp, code)?;
+			let before = listing.items.iter().filter(|item| item.is_instruction()).count();
+			let counts = optimize(&mut listing, &self.rules);
+			let after = listing.items.iter().filter(|item| item.is_instruction()).count();
+			if counts.is_empty() && before == after {
+				continue;
+			}
+
+			let old = match log.is_enabled() {
+				true => Instructions::read_all(&class.cp, &code.code)?,
+				false => Vec::new(),
+			};
+			let code = listing.to_code(&mut class.cp)?;
+			let code_name = CPUtf8Ref::find_or_add(&mut class.cp, "Code")?;
+			let slot = method
+				.attributes
+				.iter_mut()
+				.find(|attr| matches!(attr.attr, IRAttribute::Code(_)))
+				.expect("the method has code");
+			*slot = IRAttributeInfo {
+				name: code_name,
+				length: 0,
+				attr: IRAttribute::Code(code),
+			};
+			if let Some((max_stack, max_locals)) = stack::compute_maxs(&class.cp, &class_name, method)? {
+				let code = method.attributes.code_mut().expect("the method has code");
+				code.max_stack = max_stack;
+				// The LocalVariableTable may name locals the code doesn't use anymore, they have to stay in range.
+				code.max_locals = code.max_locals.max(max_locals);
+			}
+			if class.version.supports_stack_map_table() {
+				recompute_method_frames(&mut class.cp, &class_name, method, &self.hierarchy)?;
+			}
+
+			if log.is_enabled() {
+				let code = method.code().expect("the method has code");
+				let new = Instructions::read_all(&class.cp, &code.code)?;
+				log.record_code_changes(&format!("{}{}", method.name(), method.descriptor()), &old, &new);
+			}
+		}
+		Ok(())
+	}
+}
+
+fn insn(item: &Item) -> Option<&Instructions> {
+	match item {
+		Item::Insn(insn) => Some(insn),
+		_ => None,
+	}
+}
+
+fn removed(replaced: usize) -> Option<Rewrite> {
+	Some(Rewrite {
+		replaced,
+		with: Vec::new(),
+	})
+}
+
+/// Removes `nop`s.
+pub struct RemoveNop;
+
+impl PeepholeRule for RemoveNop {
+	fn name(&self) -> &str {
+		"remove_nop"
+	}
+
+	fn rewrite(&self, listing: &Listing, at: usize) -> Option<Rewrite> {
+		match insn(&listing.items[at])? {
+			Instructions::NOP => removed(1),
+			_ => None,
+		}
+	}
+}
+
+/// Jumps to a `goto` go to where it goes instead, a `goto` to a return returns right away, and a `goto` to the next
+/// instruction is removed.
+pub struct JumpThreading;
+
+impl PeepholeRule for JumpThreading {
+	fn name(&self) -> &str {
+		"jump_threading"
+	}
+
+	fn rewrite(&self, listing: &Listing, at: usize) -> Option<Rewrite> {
+		let Item::Jump { opcode, target } = listing.items[at] else {
+			return None;
+		};
+		if opcode == Opcodes::JSR {
+			return None;
+		}
+
+		if opcode == Opcodes::GOTO {
+			let mut next = listing.items[at + 1..].iter().take_while(|item| !item.is_instruction());
+			if next.any(|item| matches!(item, Item::Label(label) if *label == target)) {
+				return removed(1);
+			}
+		}
+
+		// Follow the whole chain at once, a cycle of gotos is left alone.
+		let mut end = target;
+		let mut seen = vec![target];
+		while let Some(Item::Jump {
+			opcode: Opcodes::GOTO,
+			target: next,
+		}) = listing.code_at(end).next()
+		{
+			if seen.contains(next) {
+				return None;
+			}
+			seen.push(*next);
+			end = *next;
+		}
+		if end != target {
+			return Some(Rewrite {
+				replaced: 1,
+				with: vec![Item::Jump { opcode, target: end }],
+			});
+		}
+
+		match listing.code_at(target).next() {
+			Some(Item::Insn(
+				ret @ (Instructions::IRETURN
+				| Instructions::LRETURN
+				| Instructions::FRETURN
+				| Instructions::DRETURN
+				| Instructions::ARETURN
+				| Instructions::RETURN),
+			)) if opcode == Opcodes::GOTO => Some(Rewrite {
+				replaced: 1,
+				with: vec![Item::Insn(ret.clone())],
+			}),
+			_ => None,
+		}
+	}
+}
+
+/// A constant compared against zero or null decides the branch: it becomes a `goto`, or goes away.
+pub struct ConstantBranch;
+
+impl PeepholeRule for ConstantBranch {
+	fn name(&self) -> &str {
+		"constant_branch"
+	}
+
+	fn rewrite(&self, listing: &Listing, at: usize) -> Option<Rewrite> {
+		let [Item::Insn(constant), Item::Jump { opcode, target }, ..] = listing.block(at) else {
+			return None;
+		};
+		let taken = match (constant, *opcode) {
+			(Instructions::ACONST_NULL, Opcodes::IFNULL) => true,
+			(Instructions::ACONST_NULL, Opcodes::IFNONNULL) => false,
+			(constant, Opcodes::IFEQ..=Opcodes::IFLE) => {
+				let value = int_constant(constant)?;
+				match *opcode {
+					Opcodes::IFEQ => value == 0,
+					Opcodes::IFNE => value != 0,
+					Opcodes::IFLT => value < 0,
+					Opcodes::IFGE => value >= 0,
+					Opcodes::IFGT => value > 0,
+					_ => value <= 0,
+				}
+			}
+			_ => return None,
+		};
+		Some(Rewrite {
+			replaced: 2,
+			with: match taken {
+				true => vec![Item::Jump {
+					opcode: Opcodes::GOTO,
+					target: *target,
+				}],
+				false => Vec::new(),
+			},
+		})
+	}
+}
+
+fn int_constant(insn: &Instructions) -> Option<i32> {
+	Some(match insn {
+		Instructions::ICONST_M1 => -1,
+		Instructions::ICONST_0 => 0,
+		Instructions::ICONST_1 => 1,
+		Instructions::ICONST_2 => 2,
+		Instructions::ICONST_3 => 3,
+		Instructions::ICONST_4 => 4,
+		Instructions::ICONST_5 => 5,
+		Instructions::BIPUSH(value) => *value as i32,
+		Instructions::SIPUSH(value) => *value as i32,
+		Instructions::LDC(IRCpTag::Integer(value)) => *value,
+		_ => return None,
+	})
+}
+
+/// A value pushed only to be popped again, by a `dup` or by an instruction that can't fail or have side effects.
+pub struct RedundantPop;
+
+impl PeepholeRule for RedundantPop {
+	fn name(&self) -> &str {
+		"redundant_pop"
+	}
+
+	fn rewrite(&self, listing: &Listing, at: usize) -> Option<Rewrite> {
+		use Instructions as I;
+
+		let [Item::Insn(push), Item::Insn(pop @ (I::POP | I::POP2)), ..] = listing.block(at) else {
+			return None;
+		};
+		let size = match push {
+			I::DUP
+			| I::ACONST_NULL
+			| I::ICONST_M1
+			| I::ICONST_0
+			| I::ICONST_1
+			| I::ICONST_2
+			| I::ICONST_3
+			| I::ICONST_4
+			| I::ICONST_5
+			| I::FCONST_0
+			| I::FCONST_1
+			| I::FCONST_2
+			| I::BIPUSH(_)
+			| I::SIPUSH(_)
+			| I::ILOAD(_)
+			| I::FLOAD(_)
+			| I::ALOAD(_)
+			| I::LDC(IRCpTag::Integer(_) | IRCpTag::Float(_) | IRCpTag::String(_)) => 1,
+			I::DUP2
+			| I::LCONST_0
+			| I::LCONST_1
+			| I::DCONST_0
+			| I::DCONST_1
+			| I::LLOAD(_)
+			| I::DLOAD(_)
+			| I::LDC(IRCpTag::Long(_) | IRCpTag::Double(_)) => 2,
+			_ => return None,
+		};
+		match (size, pop) {
+			(1, I::POP) | (2, I::POP2) => removed(2),
+			_ => None,
+		}
+	}
+}
+
+/// Loading a local only to store it right back.
+pub struct LoadStore;
+
+impl PeepholeRule for LoadStore {
+	fn name(&self) -> &str {
+		"load_store"
+	}
+
+	fn rewrite(&self, listing: &Listing, at: usize) -> Option<Rewrite> {
+		use Instructions as I;
+
+		let [Item::Insn(load), Item::Insn(store), ..] = listing.block(at) else {
+			return None;
+		};
+		match (load, store) {
+			(I::ILOAD(a), I::ISTORE(b))
+			| (I::LLOAD(a), I::LSTORE(b))
+			| (I::FLOAD(a), I::FSTORE(b))
+			| (I::DLOAD(a), I::DSTORE(b))
+			| (I::ALOAD(a), I::ASTORE(b))
+				if a == b =>
+			{
+				removed(2)
+			}
+			_ => None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		analysis::frames::KnownClasses,
+		builder::{ClassBuilder, CodeBuilder},
+		flags::MethodAccessFlags,
+		transform::ChangeRecord,
+	};
+
+	struct TimesOne;
+
+	impl PeepholeRule for TimesOne {
+		fn name(&self) -> &str {
+			"times_one"
+		}
+
+		fn rewrite(&self, listing: &Listing, at: usize) -> Option<Rewrite> {
+			match listing.block(at) {
+				[Item::Insn(Instructions::ICONST_1), Item::Insn(Instructions::IMUL), ..] => removed(2),
+				_ => None,
+			}
+		}
+	}
+
+	#[test]
+	fn rules_to_a_fixed_point() {
+		let mut builder = ClassBuilder::new("p/C").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		let (first, second, third) = (code.new_label(), code.new_label(), code.new_label());
+		code.insn(Instructions::ICONST_0)
+			.branch(Opcodes::IFEQ, first)
+			.unwrap()
+			// unreachable once the branch is known to be taken
+			.insn(Instructions::ICONST_5)
+			.insn(Instructions::IRETURN)
+			.bind(first)
+			.unwrap()
+			.insn(Instructions::NOP)
+			.insn(Instructions::ILOAD(0))
+			.insn(Instructions::ISTORE(0))
+			.insn(Instructions::ALOAD(1))
+			.insn(Instructions::POP)
+			.branch(Opcodes::GOTO, second)
+			.unwrap()
+			.bind(second)
+			.unwrap()
+			.branch(Opcodes::GOTO, third)
+			.unwrap()
+			.bind(third)
+			.unwrap()
+			.insn(Instructions::ILOAD(0))
+			.insn(Instructions::ICONST_1)
+			.insn(Instructions::IMUL)
+			.insn(Instructions::IRETURN);
+		code.max_stack(2).max_locals(2);
+		let code = code.build().unwrap();
+		builder
+			.method(
+				MethodAccessFlags::STATIC,
+				"f",
+				"(ILjava/lang/Object;)I",
+				[IRAttribute::Code(code)],
+			)
+			.unwrap();
+		let mut class = builder.build();
+
+		let mut pass = Peephole::new(KnownClasses::new()).with_rule(TimesOne);
+		let mut log = ChangeLog::new();
+		pass.apply(&mut class, &mut log).unwrap();
+		let code = class.methods[0].code().unwrap();
+		// iload_0, ireturn
+		assert_eq!(code.code, [0x1A, 0xAC]);
+		assert_eq!((code.max_stack, code.max_locals), (1, 2));
+		assert!(code.attributes.stack_map_table().is_none());
+		// Of the 15 instructions only the first iload_0 and the last ireturn are left.
+		let removed = |pc, count| ChangeRecord::RemovedInstructions {
+			method: "f(ILjava/lang/Object;)I".to_string(),
+			pc,
+			count,
+		};
+		let changes = log.entries().iter().map(|entry| &entry.change).collect::<Vec<_>>();
+		assert_eq!(changes, [&removed(0, 5), &removed(8, 8)]);
+
+		// Nothing left to do the second time around.
+		let mut log = ChangeLog::new();
+		pass.apply(&mut class, &mut log).unwrap();
+		assert!(log.is_empty());
+	}
+}
@@ -0,0 +1,336 @@
+use crate::{
+	attribute::IRAttributeInfo,
+	class_pool::{CPClassRef, IRCpTag},
+	flags::ClassAccessFlags,
+	ClassFileVersion, IRClassFile, IRFieldInfo, IRMethodInfo, Shared,
+};
+
+/// An immutable class where every edit returns a new version.
+/// Versions share whatever an edit didn't touch, so keeping many of them around is cheap.
+#[derive(Debug, Clone)]
+pub struct PersistentClass {
+	header: Shared<Header>,
+	cp: Shared<[IRCpTag]>,
+	fields: Shared<[Shared<IRFieldInfo>]>,
+	methods: Shared<[Shared<IRMethodInfo>]>,
+	attributes: Shared<[IRAttributeInfo]>,
+}
+
+// Everything of the class except its members, attributes and constant pool.
+#[derive(Debug, Clone)]
+struct Header {
+	magic: u32,
+	version: ClassFileVersion,
+	access_flags: ClassAccessFlags,
+	this_class: CPClassRef,
+	super_class: Option<CPClassRef>,
+	interfaces: Vec<CPClassRef>,
+}
+
+impl From<IRClassFile> for PersistentClass {
+	fn from(class: IRClassFile) -> Self {
+		Self {
+			header: Shared::new(Header {
+				magic: class.magic,
+				version: class.version,
+				access_flags: class.access_flags,
+				this_class: class.this_class,
+				super_class: class.super_class,
+				interfaces: class.interfaces,
+			}),
+			cp: class.cp.into(),
+			fields: class.fields.into_iter().map(Shared::new).collect(),
+			methods: class.methods.into_iter().map(Shared::new).collect(),
+			attributes: class.attributes.into_inner().into(),
+		}
+	}
+}
+
+impl PersistentClass {
+	/// Copies this version out into a regular, mutable class.
+	pub fn to_class(&self) -> IRClassFile {
+		let header = &*self.header;
+		IRClassFile {
+			magic: header.magic,
+			version: header.version,
+			cp: self.cp.to_vec(),
+			access_flags: header.access_flags,
+			this_class: header.this_class.clone(),
+			super_class: header.super_class.clone(),
+			interfaces: header.interfaces.clone(),
+			fields: self.fields.iter().map(|field| (**field).clone()).collect(),
+			methods: self.methods.iter().map(|method| (**method).clone()).collect(),
+			attributes: self.attributes.to_vec().into(),
+		}
+	}
+
+	pub fn class_name(&self) -> &str {
+		&self.header.this_class.data.data
+	}
+
+	pub fn version(&self) -> ClassFileVersion {
+		self.header.version
+	}
+
+	pub fn access_flags(&self) -> ClassAccessFlags {
+		self.header.access_flags
+	}
+
+	pub fn cp(&self) -> &[IRCpTag] {
+		&self.cp
+	}
+
+	pub fn fields(&self) -> impl Iterator<Item = &IRFieldInfo> {
+		self.fields.iter().map(|field| &**field)
+	}
+
+	pub fn methods(&self) -> impl Iterator<Item = &IRMethodInfo> {
+		self.methods.iter().map(|method| &**method)
+	}
+
+	pub fn attributes(&self) -> &[IRAttributeInfo] {
+		&self.attributes
+	}
+
+	/// A new version with `edit` applied to the method at `index`, sharing everything else with `self`.
+	pub fn with_method(&self, index: usize, edit: impl FnOnce(&mut IRMethodInfo)) -> Self {
+		let mut method = (*self.methods[index]).clone();
+		edit(&mut method);
+
+		let mut methods = self.methods.to_vec();
+		methods[index] = Shared::new(method);
+		Self {
+			methods: methods.into(),
+			..self.clone()
+		}
+	}
+
+	pub fn with_field(&self, index: usize, edit: impl FnOnce(&mut IRFieldInfo)) -> Self {
+		let mut field = (*self.fields[index]).clone();
+		edit(&mut field);
+
+		let mut fields = self.fields.to_vec();
+		fields[index] = Shared::new(field);
+		Self {
+			fields: fields.into(),
+			..self.clone()
+		}
+	}
+
+	pub fn with_methods(&self, edit: impl FnOnce(&mut Vec<Shared<IRMethodInfo>>)) -> Self {
+		let mut methods = self.methods.to_vec();
+		edit(&mut methods);
+		Self {
+			methods: methods.into(),
+			..self.clone()
+		}
+	}
+
+	pub fn with_fields(&self, edit: impl FnOnce(&mut Vec<Shared<IRFieldInfo>>)) -> Self {
+		let mut fields = self.fields.to_vec();
+		edit(&mut fields);
+		Self {
+			fields: fields.into(),
+			..self.clone()
+		}
+	}
+
+	pub fn with_attributes(&self, edit: impl FnOnce(&mut Vec<IRAttributeInfo>)) -> Self {
+		let mut attributes = self.attributes.to_vec();
+		edit(&mut attributes);
+		Self {
+			attributes: attributes.into(),
+			..self.clone()
+		}
+	}
+
+	pub fn with_cp(&self, edit: impl FnOnce(&mut Vec<IRCpTag>)) -> Self {
+		let mut cp = self.cp.to_vec();
+		edit(&mut cp);
+		Self {
+			cp: cp.into(),
+			..self.clone()
+		}
+	}
+
+	pub fn with_access_flags(&self, access_flags: ClassAccessFlags) -> Self {
+		Self {
+			header: Shared::new(Header {
+				access_flags,
+				..(*self.header).clone()
+			}),
+			..self.clone()
+		}
+	}
+
+	/// What changed between two versions. Only meaningful when one version derives from the other,
+	/// since it compares by identity and not by value.
+	pub fn diff(&self, other: &Self) -> Vec<VersionChange> {
+		let mut changes = Vec::new();
+		if !Shared::ptr_eq(&self.header, &other.header) {
+			changes.push(VersionChange::Header);
+		}
+		if !Shared::ptr_eq(&self.cp, &other.cp) {
+			changes.push(VersionChange::ConstantPool);
+		}
+		if !Shared::ptr_eq(&self.attributes, &other.attributes) {
+			changes.push(VersionChange::Attributes);
+		}
+
+		diff_members(&self.fields, &other.fields, &mut changes, |field| {
+			VersionChange::Field(member_id(&field.name.data, &field.descriptor.data))
+		});
+		diff_members(&self.methods, &other.methods, &mut changes, |method| {
+			VersionChange::Method(member_id(&method.name.data, &method.descriptor.data))
+		});
+		changes
+	}
+}
+
+fn member_id(name: &str, descriptor: &str) -> String {
+	format!("{name}{descriptor}")
+}
+
+fn diff_members<T>(
+	before: &[Shared<T>],
+	after: &[Shared<T>],
+	changes: &mut Vec<VersionChange>,
+	change: impl Fn(&T) -> VersionChange,
+) {
+	let shared =
+		|member: &Shared<T>, other: &[Shared<T>]| other.iter().any(|candidate| Shared::ptr_eq(member, candidate));
+	for member in before.iter().filter(|member| !shared(member, after)) {
+		changes.push(change(member));
+	}
+	for member in after.iter().filter(|member| !shared(member, before)) {
+		let change = change(member);
+		if !changes.contains(&change) {
+			changes.push(change);
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionChange {
+	Header,
+	ConstantPool,
+	Attributes,
+	// name + descriptor of a field or method that was added, removed or edited
+	Field(String),
+	Method(String),
+}
+
+/// Every version a class went through, for undo and for comparing pipeline stages.
+#[derive(Debug, Clone)]
+pub struct History {
+	versions: Vec<(String, PersistentClass)>,
+}
+
+impl History {
+	pub fn new(initial: PersistentClass) -> Self {
+		Self {
+			versions: vec![("initial".to_string(), initial)],
+		}
+	}
+
+	pub fn current(&self) -> &PersistentClass {
+		&self.versions.last().expect("history is never empty").1
+	}
+
+	pub fn commit(&mut self, label: &str, version: PersistentClass) {
+		self.versions.push((label.to_string(), version));
+	}
+
+	/// Drops the latest version, returning it. The initial version can't be undone.
+	pub fn undo(&mut self) -> Option<PersistentClass> {
+		match self.versions.len() {
+			1 => None,
+			_ => self.versions.pop().map(|(_, version)| version),
+		}
+	}
+
+	pub fn get(&self, index: usize) -> Option<&PersistentClass> {
+		self.versions.get(index).map(|(_, version)| version)
+	}
+
+	pub fn label(&self, index: usize) -> Option<&str> {
+		self.versions.get(index).map(|(label, _)| label.as_str())
+	}
+
+	/// Every version with its label, oldest first.
+	pub fn versions(&self) -> impl Iterator<Item = (&str, &PersistentClass)> {
+		self.versions.iter().map(|(label, version)| (label.as_str(), version))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{attribute::Attributes, class_pool::CPUtf8Ref, flags::MethodAccessFlags};
+
+	fn utf8(data: &str) -> CPUtf8Ref {
+		CPUtf8Ref {
+			data: Shared::new(data.to_string()),
+			index: 0,
+		}
+	}
+
+	fn method(name: &str) -> IRMethodInfo {
+		IRMethodInfo {
+			access_flags: MethodAccessFlags::empty(),
+			name: utf8(name),
+			descriptor: utf8("()V"),
+			attributes: Attributes::new(),
+		}
+	}
+
+	#[test]
+	fn edits_share_untouched_members() {
+		let class = IRClassFile {
+			magic: 0xCAFEBABE,
+			version: ClassFileVersion { major: 61, minor: 0 },
+			cp: Vec::new(),
+			access_flags: ClassAccessFlags::SUPER,
+			this_class: CPClassRef {
+				data: utf8("A"),
+				index: 0,
+			},
+			super_class: None,
+			interfaces: Vec::new(),
+			fields: Vec::new(),
+			methods: vec![method("a"), method("b")],
+			attributes: Attributes::new(),
+		};
+
+		let mut history = History::new(class.into());
+		let edited = history
+			.current()
+			.with_method(1, |method| method.access_flags = MethodAccessFlags::PUBLIC);
+		assert!(Shared::ptr_eq(&history.current().methods[0], &edited.methods[0]));
+		assert_eq!(
+			history.current().diff(&edited),
+			vec![VersionChange::Method("b()V".into())]
+		);
+
+		history.commit("make b public", edited);
+		assert_eq!(
+			history.current().methods().nth(1).unwrap().access_flags,
+			MethodAccessFlags::PUBLIC
+		);
+		assert!(history.undo().is_some());
+		assert!(history.undo().is_none());
+		assert_eq!(
+			history.current().methods().nth(1).unwrap().access_flags,
+			MethodAccessFlags::empty()
+		);
+	}
+
+	// Versions can be handed to other threads, like the classes they come from.
+	#[cfg(feature = "sync")]
+	#[test]
+	fn thread_safe() {
+		fn thread_safe<T: Send + Sync>() {}
+		thread_safe::<PersistentClass>();
+		thread_safe::<History>();
+	}
+}
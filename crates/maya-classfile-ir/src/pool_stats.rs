@@ -0,0 +1,234 @@
+// What the constant pools of classes are made of, for working out where the bytes of a jar go. The pool is usually the
+// bulk of a class, and most of it Utf8: names, descriptors, signatures and string constants. Besides counting entries
+// by kind and their size as written, this finds entries a pool has twice, which a parser or shrinker left behind and
+// compacting the pool doesn't merge, and strings that many classes carry a copy of, e.g. a long message that could
+// live in one class instead.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.4
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::{self, Display},
+};
+
+use crate::{
+	class_pool::{IRClassfileError, IRCpTag},
+	IRClassFile,
+};
+
+/// How many of the largest entries are kept.
+const LARGEST: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LargeEntry {
+	pub class: String,
+	pub index: u16,
+	pub kind: &'static str,
+	// as written, tag byte included
+	pub size: usize,
+	pub value: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolStats {
+	pub classes: usize,
+	// Long and Double counted once
+	pub entries: usize,
+	// by kind name as javap prints them, e.g. Methodref
+	pub tags: BTreeMap<&'static str, usize>,
+	// the modified UTF-8 of the Utf8 entries
+	pub utf8_bytes: u64,
+	// the pools as written
+	pub size: u64,
+	// entries written the same as another of the same pool, by kind and value, to how many extra copies there are
+	pub duplicates: BTreeMap<String, usize>,
+	// Utf8 values to the number of classes having them
+	pub strings: BTreeMap<String, usize>,
+	// largest first
+	pub largest: Vec<LargeEntry>,
+}
+
+impl PoolStats {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn add(&mut self, class: &IRClassFile) -> Result<(), IRClassfileError> {
+		self.classes += 1;
+		let mut seen = BTreeSet::new();
+		let mut strings = BTreeSet::new();
+		for (i, tag) in class.cp.iter().enumerate() {
+			if matches!(tag, IRCpTag::Unusable) {
+				continue;
+			}
+			let kind = tag.kind_name();
+			let mut bytes = Vec::new();
+			tag.to_io()?.write(&mut bytes)?;
+			let value = describe(&class.cp, tag);
+
+			self.entries += 1;
+			*self.tags.entry(kind).or_default() += 1;
+			self.size += bytes.len() as u64;
+			if let IRCpTag::Utf8(data) = tag {
+				// less the tag byte and the length
+				self.utf8_bytes += bytes.len() as u64 - 3;
+				strings.insert(data.to_string());
+			}
+			let key = format!("{kind} {value}");
+			if seen.contains(&key) {
+				*self.duplicates.entry(key).or_default() += 1;
+			} else {
+				seen.insert(key);
+			}
+			self.largest.push(LargeEntry {
+				class: class.class_name().to_string(),
+				index: i as u16 + 1,
+				kind,
+				size: bytes.len(),
+				value,
+			});
+			if self.largest.len() > LARGEST * 2 {
+				self.trim_largest();
+			}
+		}
+		for string in strings {
+			*self.strings.entry(string).or_default() += 1;
+		}
+		self.trim_largest();
+		Ok(())
+	}
+
+	/// Adds up the statistics of other classes, e.g. those of another jar or worker thread.
+	pub fn merge(&mut self, other: &PoolStats) {
+		self.classes += other.classes;
+		self.entries += other.entries;
+		for (kind, count) in &other.tags {
+			*self.tags.entry(kind).or_default() += count;
+		}
+		self.utf8_bytes += other.utf8_bytes;
+		self.size += other.size;
+		for (entry, count) in &other.duplicates {
+			*self.duplicates.entry(entry.clone()).or_default() += count;
+		}
+		for (string, count) in &other.strings {
+			*self.strings.entry(string.clone()).or_default() += count;
+		}
+		self.largest.extend(other.largest.iter().cloned());
+		self.trim_largest();
+	}
+
+	/// The strings in at least `min_classes` classes, by the bytes their copies past the first take, most first.
+	pub fn repeated_strings(&self, min_classes: usize) -> Vec<(&str, usize)> {
+		let mut repeated = self
+			.strings
+			.iter()
+			.filter(|(_, classes)| **classes >= min_classes.max(2))
+			.map(|(string, classes)| (string.as_str(), *classes))
+			.collect::<Vec<_>>();
+		repeated.sort_by_key(|(string, classes)| std::cmp::Reverse(string.len() * (classes - 1)));
+		repeated
+	}
+
+	fn trim_largest(&mut self) {
+		self.largest.sort_by(|a, b| {
+			b.size
+				.cmp(&a.size)
+				.then_with(|| a.class.cmp(&b.class))
+				.then(a.index.cmp(&b.index))
+		});
+		self.largest.truncate(LARGEST);
+	}
+}
+
+impl IRClassFile {
+	pub fn pool_stats(&self) -> Result<PoolStats, IRClassfileError> {
+		let mut stats = PoolStats::new();
+		stats.add(self)?;
+		Ok(stats)
+	}
+}
+
+fn describe(cp: &[IRCpTag], tag: &IRCpTag) -> String {
+	match tag {
+		IRCpTag::Utf8(data) => data.to_string(),
+		IRCpTag::Integer(value) => value.to_string(),
+		IRCpTag::Float(value) => value.to_string(),
+		IRCpTag::Long(value) => value.to_string(),
+		IRCpTag::Double(value) => value.to_string(),
+		tag => tag.resolved(cp).unwrap_or_default(),
+	}
+}
+
+impl Display for PoolStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(
+			f,
+			"{} classes, {} constant pool entries, {} bytes, {} of them Utf8",
+			self.classes, self.entries, self.size, self.utf8_bytes
+		)?;
+		let mut tags = self.tags.iter().collect::<Vec<_>>();
+		tags.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+		for (kind, count) in tags {
+			writeln!(f, "  {kind}: {count}")?;
+		}
+		let duplicates = self.duplicates.values().sum::<usize>();
+		if duplicates != 0 {
+			writeln!(f, "{duplicates} duplicate entries")?;
+		}
+		if !self.largest.is_empty() {
+			writeln!(f, "largest entries:")?;
+		}
+		for entry in &self.largest {
+			writeln!(
+				f,
+				"  {} bytes, {} #{} {}: {}",
+				entry.size, entry.class, entry.index, entry.kind, entry.value
+			)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{asm::assemble, Shared};
+
+	#[test]
+	fn statistics() {
+		let message = "a message long enough to be worth sharing";
+		let class = |name: &str| {
+			assemble(&format!(
+				".class public super {name}\n.method public static run()V\nldc \"{message}\"\npop\nldc 100000\npop\n\
+				return\n.end method"
+			))
+			.unwrap()
+		};
+		let (first, mut second) = (class("p/First"), class("p/Second"));
+		second.cp.push(IRCpTag::Utf8(Shared::new("run".to_string())));
+
+		let mut stats = first.pool_stats().unwrap();
+		assert_eq!(stats.tags["Integer"], 1);
+		assert_eq!(stats.tags["String"], 1);
+		assert!(stats.duplicates.is_empty());
+		let utf8 = first
+			.cp
+			.iter()
+			.filter_map(|tag| match tag {
+				IRCpTag::Utf8(data) => Some(data.len() as u64),
+				_ => None,
+			})
+			.sum::<u64>();
+		assert_eq!(stats.utf8_bytes, utf8);
+
+		stats.merge(&second.pool_stats().unwrap());
+		assert_eq!(stats.classes, 2);
+		assert_eq!(stats.entries, first.cp.len() + second.cp.len());
+		assert_eq!(stats.duplicates, BTreeMap::from([("Utf8 run".to_string(), 1)]));
+		assert_eq!(stats.repeated_strings(2)[0], (message, 2));
+		assert!(stats.repeated_strings(2).iter().all(|(string, _)| *string != "p/First"));
+		assert_eq!(stats.largest[0].value, message);
+		assert_eq!(stats.largest[0].size, message.len() + 3);
+		assert_eq!(stats.largest[1].class, "p/Second");
+		assert!(stats.to_string().contains("2 classes"));
+	}
+}
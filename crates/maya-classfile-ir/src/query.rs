@@ -0,0 +1,378 @@
+// Facts extracted from classes as plain relations, so architectural queries can be written as joins and closures
+// over sets instead of as one-off traversals of the IR.
+
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap},
+	fmt::{self, Display},
+};
+
+use crate::{
+	attribute::{Attributes, RuntimeAnnotation},
+	class_pool::{CPClassRef, CPNameAndTypeRef},
+	code::Instructions,
+	names,
+	symbols::{ClassId, MemberId, SymbolTable},
+	IRClassFile,
+};
+
+/// A field or method, identified the way the JVM resolves it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MemberRef {
+	// internal name of the declaring (or referenced) class
+	pub owner: String,
+	pub name: String,
+	pub descriptor: String,
+}
+
+impl MemberRef {
+	pub fn new(owner: &str, name: &str, descriptor: &str) -> Self {
+		Self {
+			owner: owner.to_string(),
+			name: name.to_string(),
+			descriptor: descriptor.to_string(),
+		}
+	}
+}
+
+impl Display for MemberRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}.{}{}", self.owner, self.name, self.descriptor)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AnnotationTarget {
+	Class(ClassId),
+	Field(MemberId),
+	Method(MemberId),
+}
+
+/// A set of tuples. Kept ordered and deduplicated so query results are deterministic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relation<T: Ord> {
+	tuples: BTreeSet<T>,
+}
+
+impl<T: Ord> Default for Relation<T> {
+	fn default() -> Self {
+		Self {
+			tuples: BTreeSet::new(),
+		}
+	}
+}
+
+impl<T: Ord> FromIterator<T> for Relation<T> {
+	fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+		Self {
+			tuples: iter.into_iter().collect(),
+		}
+	}
+}
+
+impl<T: Ord> Relation<T> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, tuple: T) -> bool {
+		self.tuples.insert(tuple)
+	}
+
+	pub fn contains(&self, tuple: &T) -> bool {
+		self.tuples.contains(tuple)
+	}
+
+	pub fn len(&self) -> usize {
+		self.tuples.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.tuples.is_empty()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &T> {
+		self.tuples.iter()
+	}
+
+	pub fn select(&self, predicate: impl Fn(&T) -> bool) -> Relation<T>
+	where
+		T: Clone,
+	{
+		self.tuples.iter().filter(|tuple| predicate(tuple)).cloned().collect()
+	}
+
+	pub fn project<U: Ord>(&self, f: impl Fn(&T) -> U) -> Relation<U> {
+		self.tuples.iter().map(f).collect()
+	}
+
+	/// Pairs every tuple of `self` with every tuple of `other` that has the same key, then combines each pair.
+	pub fn join<U: Ord, K: Ord, R: Ord>(
+		&self,
+		other: &Relation<U>,
+		left_key: impl Fn(&T) -> K,
+		right_key: impl Fn(&U) -> K,
+		combine: impl Fn(&T, &U) -> R,
+	) -> Relation<R> {
+		let mut index: BTreeMap<K, Vec<&U>> = BTreeMap::new();
+		for tuple in &other.tuples {
+			index.entry(right_key(tuple)).or_default().push(tuple);
+		}
+
+		let mut out = Relation::new();
+		for left in &self.tuples {
+			for right in index.get(&left_key(left)).into_iter().flatten() {
+				out.insert(combine(left, right));
+			}
+		}
+		out
+	}
+
+	/// Tuples of `self` whose key doesn't appear in `other`.
+	pub fn antijoin<U: Ord, K: Ord>(
+		&self,
+		other: &Relation<U>,
+		left_key: impl Fn(&T) -> K,
+		right_key: impl Fn(&U) -> K,
+	) -> Relation<T>
+	where
+		T: Clone,
+	{
+		let keys: BTreeSet<K> = other.tuples.iter().map(right_key).collect();
+		self.select(|tuple| !keys.contains(&left_key(tuple)))
+	}
+
+	pub fn union(&self, other: &Relation<T>) -> Relation<T>
+	where
+		T: Clone,
+	{
+		self.tuples.union(&other.tuples).cloned().collect()
+	}
+}
+
+impl<A: Ord + Clone> Relation<(A, A)> {
+	/// The smallest relation containing `self` where `(a, b)` and `(b, c)` imply `(a, c)`.
+	pub fn transitive_closure(&self) -> Relation<(A, A)> {
+		let mut closure = self.clone();
+		let mut delta = self.clone();
+		while !delta.is_empty() {
+			let derived = delta.join(
+				self,
+				|(_, b)| b.clone(),
+				|(a, _)| a.clone(),
+				|(a, _), (_, c)| (a.clone(), c.clone()),
+			);
+			delta = derived.antijoin(&closure, |tuple| tuple.clone(), |tuple| tuple.clone());
+			closure = closure.union(&delta);
+		}
+		closure
+	}
+}
+
+impl<T: Ord> IntoIterator for Relation<T> {
+	type Item = T;
+	type IntoIter = std::collections::btree_set::IntoIter<T>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.tuples.into_iter()
+	}
+}
+
+impl<'a, T: Ord> IntoIterator for &'a Relation<T> {
+	type Item = &'a T;
+	type IntoIter = std::collections::btree_set::Iter<'a, T>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.tuples.iter()
+	}
+}
+
+/// Every relation extracted from a set of classes, over ids from `symbols`. Class names are internal names,
+/// annotation types are internal names too rather than descriptors.
+#[derive(Debug, Clone, Default)]
+pub struct Facts {
+	pub symbols: SymbolTable,
+	// every class that was added
+	pub classes: Relation<ClassId>,
+	// (class, superclass)
+	pub class_extends: Relation<(ClassId, ClassId)>,
+	// (class, interface)
+	pub class_implements: Relation<(ClassId, ClassId)>,
+	// (class, declared method)
+	pub class_declares_method: Relation<(ClassId, MemberId)>,
+	// (class, declared field)
+	pub class_declares_field: Relation<(ClassId, MemberId)>,
+	// (caller, callee), invokedynamic call sites aren't included since they have no static target
+	pub method_calls: Relation<(MemberId, MemberId)>,
+	// (method, field)
+	pub field_read: Relation<(MemberId, MemberId)>,
+	// (method, field)
+	pub field_write: Relation<(MemberId, MemberId)>,
+	// (target, annotation type), visible and invisible alike
+	pub annotation_on: Relation<(AnnotationTarget, ClassId)>,
+	// (array class, element class) for every array a member was referenced on, primitive arrays excluded
+	array_elements: Relation<(ClassId, ClassId)>,
+}
+
+impl Facts {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn from_classes<'a>(classes: impl IntoIterator<Item = &'a IRClassFile>) -> Self {
+		let mut facts = Self::new();
+		for class in classes {
+			facts.add(class);
+		}
+		facts
+	}
+
+	pub fn add(&mut self, class: &IRClassFile) {
+		let this_name = class.class_name();
+		let this = self.symbols.class(this_name);
+		self.classes.insert(this);
+		if let Some(super_class) = &class.super_class {
+			let super_class = self.symbols.class(&super_class.data.data);
+			self.class_extends.insert((this, super_class));
+		}
+		for interface in &class.interfaces {
+			let interface = self.symbols.class(&interface.data.data);
+			self.class_implements.insert((this, interface));
+		}
+		self.add_annotations(AnnotationTarget::Class(this), &class.attributes);
+
+		for field in &class.fields {
+			let field_id = self.symbols.member(this_name, field.name(), field.descriptor());
+			self.class_declares_field.insert((this, field_id));
+			self.add_annotations(AnnotationTarget::Field(field_id), &field.attributes);
+		}
+
+		for method in &class.methods {
+			let method_id = self.symbols.member(this_name, method.name(), method.descriptor());
+			self.class_declares_method.insert((this, method_id));
+			self.add_annotations(AnnotationTarget::Method(method_id), &method.attributes);
+
+			// Undecodable code contributes no facts, `lint` is the place to report it.
+			if let Some(code) = method.code() {
+				if let Ok(instructions) = Instructions::read_all(&class.cp, &code.code) {
+					self.add_code(method_id, &instructions);
+				}
+			}
+		}
+	}
+
+	fn add_annotations(&mut self, target: AnnotationTarget, attributes: &Attributes) {
+		for annotation in attributes.annotations() {
+			let ty = self.symbols.class(annotation_type(annotation));
+			self.annotation_on.insert((target, ty));
+		}
+	}
+
+	fn add_code(&mut self, method: MemberId, instructions: &[(u32, Instructions)]) {
+		for (_, insn) in instructions {
+			match insn {
+				Instructions::GETFIELD(field) | Instructions::GETSTATIC(field) => {
+					let field = self.referenced(&field.class, &field.name_and_ty);
+					self.field_read.insert((method, field));
+				}
+				Instructions::PUTFIELD(field) | Instructions::PUTSTATIC(field) => {
+					let field = self.referenced(&field.class, &field.name_and_ty);
+					self.field_write.insert((method, field));
+				}
+				Instructions::INVOKEVIRTUAL(callee)
+				| Instructions::INVOKESPECIAL(callee)
+				| Instructions::INVOKESTATIC(callee) => {
+					let callee = self.referenced(&callee.class, &callee.name_and_ty);
+					self.method_calls.insert((method, callee));
+				}
+				Instructions::INVOKEINTERFACE { method: callee, .. } => {
+					let callee = self.referenced(&callee.class, &callee.name_and_ty);
+					self.method_calls.insert((method, callee));
+				}
+				_ => {}
+			}
+		}
+	}
+
+	fn referenced(&mut self, class: &CPClassRef, name_and_ty: &CPNameAndTypeRef) -> MemberId {
+		let owner = class.data.data.as_str();
+		if names::is_array(owner) {
+			if let Some(element) = names::descriptor_to_internal(names::array_element(owner)) {
+				let pair = (self.symbols.class(owner), self.symbols.class(element));
+				self.array_elements.insert(pair);
+			}
+		}
+		self.symbols.member(owner, &name_and_ty.name.data, &name_and_ty.ty.data)
+	}
+
+	pub fn class_name(&self, class: ClassId) -> &str {
+		self.symbols.class_name(class)
+	}
+
+	/// `(class, ancestor)` for every superclass and interface reachable from a class, directly or not.
+	pub fn subtypes(&self) -> Relation<(ClassId, ClassId)> {
+		self.class_extends.union(&self.class_implements).transitive_closure()
+	}
+
+	/// `(class, dependency)` for every other class a class refers to through its hierarchy, calls or field accesses.
+	/// Calls on arrays count as a dependency on the element class, primitive arrays aren't dependencies.
+	pub fn class_depends_on(&self) -> Relation<(ClassId, ClassId)> {
+		let elements: HashMap<ClassId, ClassId> = self.array_elements.iter().copied().collect();
+		let owner_of_target = |(from, to): &(MemberId, MemberId)| {
+			let to = self.symbols.owner(*to);
+			let to = if names::is_array(self.class_name(to)) {
+				*elements.get(&to)?
+			} else {
+				to
+			};
+			Some((self.symbols.owner(*from), to))
+		};
+		let mut out = self.class_extends.union(&self.class_implements);
+		for edge in self
+			.method_calls
+			.iter()
+			.chain(&self.field_read)
+			.chain(&self.field_write)
+			.filter_map(owner_of_target)
+		{
+			out.insert(edge);
+		}
+		out.select(|(from, to)| from != to)
+	}
+}
+
+fn annotation_type(annotation: &RuntimeAnnotation) -> &str {
+	let descriptor = annotation.ty.data.as_str();
+	names::descriptor_to_internal(descriptor).unwrap_or(descriptor)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn pairs(tuples: &[(&str, &str)]) -> Relation<(String, String)> {
+		tuples.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect()
+	}
+
+	#[test]
+	fn join_and_closure() {
+		let extends = pairs(&[("C", "B"), ("B", "A"), ("A", "java/lang/Object")]);
+		let closure = extends.transitive_closure();
+		assert_eq!(closure.len(), 6);
+		assert!(closure.contains(&("C".to_string(), "java/lang/Object".to_string())));
+
+		let annotated = pairs(&[("A", "Entity")]);
+		let inherits_entity = closure.join(
+			&annotated,
+			|(_, sup)| sup.clone(),
+			|(class, _)| class.clone(),
+			|(sub, _), (_, ann)| (sub.clone(), ann.clone()),
+		);
+		assert_eq!(inherits_entity, pairs(&[("B", "Entity"), ("C", "Entity")]));
+		assert_eq!(
+			closure
+				.antijoin(&annotated, |(class, _)| class.clone(), |(class, _)| class.clone())
+				.len(),
+			5
+		);
+	}
+}
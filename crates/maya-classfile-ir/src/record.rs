@@ -0,0 +1,193 @@
+// Records, as declared by the Record attribute: a component per field, each read through an accessor of the same name
+// and all of them passed to the canonical constructor in order. javac copies a component's annotations to the field,
+// the accessor and the constructor parameter depending on the annotation's targets, and keeps on the component those
+// that apply to it.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.30
+// https://docs.oracle.com/javase/specs/jls/se22/html/jls-8.html#jls-8.10
+
+use crate::{
+	attribute::{RecordComponentInfo, RuntimeAnnotation},
+	class_pool::IRClassfileError,
+	signature::ReferenceTypeSignature,
+	IRClassFile, IRFieldInfo, IRMethodInfo,
+};
+
+/// The components of a record class, matched up with their fields and accessors.
+#[derive(Debug, Clone)]
+pub struct RecordView<'a> {
+	pub class: &'a IRClassFile,
+	pub components: Vec<RecordComponentView<'a>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RecordComponentView<'a> {
+	pub info: &'a RecordComponentInfo,
+	/// The private final field holding the value, `None` if the class doesn't declare it.
+	pub field: Option<&'a IRFieldInfo>,
+	/// The public method returning the value, `None` if the class doesn't declare it.
+	pub accessor: Option<&'a IRMethodInfo>,
+}
+
+impl<'a> RecordView<'a> {
+	/// Returns `None` if `class` has no Record attribute.
+	pub fn from_class(class: &'a IRClassFile) -> Option<Self> {
+		let components = class
+			.attributes
+			.record_components()?
+			.iter()
+			.map(|info| {
+				let (name, descriptor) = (info.name.data.as_str(), info.descriptor.data.as_str());
+				RecordComponentView {
+					info,
+					field: class
+						.find_field(name, descriptor)
+						.filter(|field| !field.access_flags.is_static()),
+					accessor: class
+						.find_method(name, &format!("(){descriptor}"))
+						.filter(|method| !method.access_flags.is_static()),
+				}
+			})
+			.collect();
+		Some(Self { class, components })
+	}
+
+	pub fn component(&self, name: &str) -> Option<&RecordComponentView<'a>> {
+		self.components.iter().find(|component| component.name() == name)
+	}
+
+	/// `(` the component descriptors in order `)V`.
+	pub fn canonical_constructor_descriptor(&self) -> String {
+		let params = self
+			.components
+			.iter()
+			.map(RecordComponentView::descriptor)
+			.collect::<String>();
+		format!("({params})V")
+	}
+
+	pub fn canonical_constructor(&self) -> Option<&'a IRMethodInfo> {
+		self.class
+			.find_method("<init>", &self.canonical_constructor_descriptor())
+	}
+}
+
+impl<'a> RecordComponentView<'a> {
+	pub fn name(&self) -> &'a str {
+		&self.info.name.data
+	}
+
+	pub fn descriptor(&self) -> &'a str {
+		&self.info.descriptor.data
+	}
+
+	/// The generic signature, `None` unless the component's type is generic, e.g. `Ljava/util/List<TT;>;`.
+	pub fn signature(&self) -> Option<&'a str> {
+		self.info.attributes.signature()
+	}
+
+	/// The parsed generic signature, `None` unless the component's type is generic.
+	pub fn generic_type(&self) -> Result<Option<ReferenceTypeSignature>, IRClassfileError> {
+		self.signature().map(ReferenceTypeSignature::parse).transpose()
+	}
+
+	/// The annotations on the component itself, visible and invisible.
+	pub fn annotations(&self) -> impl Iterator<Item = &'a RuntimeAnnotation> {
+		self.info.attributes.annotations()
+	}
+
+	pub fn annotation(&self, descriptor: &str) -> Option<&'a RuntimeAnnotation> {
+		self.info.attributes.annotation(descriptor)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use smallvec::SmallVec;
+
+	use super::*;
+	use crate::{
+		attribute::{Attributes, IRAttribute},
+		builder::ClassBuilder,
+		class_pool::CPUtf8Ref,
+		flags::{FieldAccessFlags, MethodAccessFlags},
+		signature::{ClassTypeSignature, TypeArgument},
+	};
+
+	#[test]
+	fn view_components() {
+		let mut builder = ClassBuilder::new("p/Pair").unwrap();
+		builder.super_class(Some("java/lang/Record")).unwrap();
+		let cp = builder.cp();
+		let nonnull = RuntimeAnnotation {
+			ty: CPUtf8Ref::find_or_add(cp, "Lp/NonNull;").unwrap(),
+			pairs: SmallVec::new(),
+		};
+		let signature = CPUtf8Ref::find_or_add(cp, "Ljava/util/List<TT;>;").unwrap();
+		let mut components = Vec::new();
+		for (name, descriptor, attributes) in [
+			(
+				"items",
+				"Ljava/util/List;",
+				vec![
+					IRAttribute::Signature(signature),
+					IRAttribute::RuntimeVisibleAnnotations {
+						annotations: vec![nonnull],
+					},
+				],
+			),
+			("count", "I", Vec::new()),
+		] {
+			let attributes = attributes
+				.into_iter()
+				.map(|attr| builder.attribute_info(attr))
+				.collect::<Result<Attributes, _>>()
+				.unwrap();
+			components.push(RecordComponentInfo {
+				name: CPUtf8Ref::find_or_add(builder.cp(), name).unwrap(),
+				descriptor: CPUtf8Ref::find_or_add(builder.cp(), descriptor).unwrap(),
+				attributes,
+			});
+		}
+		builder
+			.field(
+				FieldAccessFlags::PRIVATE | FieldAccessFlags::FINAL,
+				"items",
+				"Ljava/util/List;",
+				[],
+			)
+			.unwrap()
+			.field(FieldAccessFlags::PRIVATE | FieldAccessFlags::FINAL, "count", "I", [])
+			.unwrap()
+			.method(MethodAccessFlags::PUBLIC, "items", "()Ljava/util/List;", [])
+			.unwrap()
+			.method(MethodAccessFlags::PUBLIC, "<init>", "(Ljava/util/List;I)V", [])
+			.unwrap()
+			.attribute(IRAttribute::Record { components })
+			.unwrap();
+		let class = builder.build();
+
+		let record = class.record_view().unwrap();
+		assert_eq!(record.canonical_constructor_descriptor(), "(Ljava/util/List;I)V");
+		assert!(record.canonical_constructor().is_some());
+		let items = record.component("items").unwrap();
+		assert_eq!(items.field.unwrap().name(), "items");
+		assert_eq!(items.accessor.unwrap().descriptor(), "()Ljava/util/List;");
+		assert!(items.annotation("Lp/NonNull;").is_some());
+		assert_eq!(
+			items.generic_type().unwrap(),
+			Some(ReferenceTypeSignature::Class(ClassTypeSignature {
+				name: "java/util/List".to_string(),
+				args: vec![TypeArgument::Exact(ReferenceTypeSignature::TypeVariable(
+					"T".to_string()
+				))],
+				inner: Vec::new(),
+			}))
+		);
+		let count = &record.components[1];
+		assert_eq!((count.name(), count.descriptor()), ("count", "I"));
+		assert!(count.field.is_some() && count.accessor.is_none());
+		assert_eq!(count.generic_type().unwrap(), None);
+
+		assert!(ClassBuilder::new("p/C").unwrap().build().record_view().is_none());
+	}
+}
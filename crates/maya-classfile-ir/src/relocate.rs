@@ -0,0 +1,295 @@
+// Relocating packages, the way shading bundles a dependency under a package of its own so it can't clash with another
+// copy on the class path: `com.google.` becomes `shaded.com.google.`. Classes are renamed by a `Remapper`, which moves
+// the packages a module-info exports, opens and contains along with them.
+//
+// Strings naming relocated classes, e.g. for `Class.forName`, can be rewritten too. That's opt-in, as any string that
+// merely starts like a relocated package is rewritten just the same. In a jar, entries under the relocated packages
+// move with them, and service descriptors are renamed and their providers relocated.
+// https://maven.apache.org/plugins/maven-shade-plugin/examples/class-relocation.html
+// https://docs.oracle.com/en/java/javase/22/docs/api/java.base/java/util/ServiceLoader.html
+
+use crate::{
+	class_pool::{CPUtf8Ref, IRClassfileError, IRCpTag},
+	names,
+	remap::Remapper,
+	transform::{ChangeLog, ChangeRecord, Transform},
+	IRClassFile,
+};
+
+const SERVICES: &str = "META-INF/services/";
+const VERSIONS: &str = "META-INF/versions/";
+
+#[derive(Debug, Clone, Default)]
+pub struct Relocator {
+	remapper: Remapper,
+	strings: bool,
+}
+
+impl Relocator {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Moves package `from` and its subpackages to `to`, both binary (`com.google`) or internal (`com/google`) names.
+	pub fn relocate(mut self, from: &str, to: &str) -> Self {
+		let package = |name: &str| names::binary_to_internal(name.trim_end_matches(['.', '/']));
+		self.remapper.add_package(&package(from), &package(to));
+		self
+	}
+
+	/// Also rewrites string constants that start with a relocated package, in binary or internal form.
+	pub fn rewrite_strings(mut self, rewrite: bool) -> Self {
+		self.strings = rewrite;
+		self
+	}
+
+	pub fn remapper(&self) -> &Remapper {
+		&self.remapper
+	}
+
+	/// The relocated string, `None` if it doesn't start with a relocated package.
+	pub fn relocate_string(&self, string: &str) -> Option<String> {
+		// Descriptors and anything else starting with `[` aren't names, and could still contain a `/`.
+		if string.starts_with('[') {
+			return None;
+		}
+		let relocated = match string.contains('/') {
+			true => self.remapper.map_class(string),
+			false => names::internal_to_binary(&self.remapper.map_class(&names::binary_to_internal(string))),
+		};
+		(relocated != string).then_some(relocated)
+	}
+
+	/// The name and contents of a jar entry after relocating. Classes are found at their new names, and so are other
+	/// resources under the relocated packages, also in the versioned directories of a multi-release jar. Service
+	/// descriptors are named after a service and list providers, both relocated.
+	pub fn relocate_entry(&self, name: &str, data: Vec<u8>) -> (String, Vec<u8>) {
+		if let Some(service) = name.strip_prefix(SERVICES) {
+			let service = self.relocate_string(service).unwrap_or_else(|| service.to_string());
+			let data = match String::from_utf8(data) {
+				Ok(providers) => self.relocate_providers(&providers).into_bytes(),
+				Err(err) => err.into_bytes(),
+			};
+			return (format!("{SERVICES}{service}"), data);
+		}
+
+		let (version, path) = match name.strip_prefix(VERSIONS).and_then(|rest| rest.split_once('/')) {
+			Some((version, path)) => (format!("{VERSIONS}{version}/"), path),
+			None => (String::new(), name),
+		};
+		let path = match path.starts_with("META-INF/") {
+			true => path.to_string(),
+			false => self.remapper.map_class(path),
+		};
+		(format!("{version}{path}"), data)
+	}
+
+	// One provider per line, `#` starts a comment.
+	fn relocate_providers(&self, providers: &str) -> String {
+		let mut out = String::with_capacity(providers.len());
+		for line in providers.split_inclusive('\n') {
+			let provider = line.split('#').next().unwrap_or_default().trim();
+			match self.relocate_string(provider).filter(|_| !provider.is_empty()) {
+				Some(relocated) => out.push_str(&line.replacen(provider, &relocated, 1)),
+				None => out.push_str(line),
+			}
+		}
+		out
+	}
+}
+
+impl Transform for Relocator {
+	fn name(&self) -> &str {
+		"relocate"
+	}
+
+	fn apply(&mut self, class: &mut IRClassFile, log: &mut ChangeLog) -> Result<(), IRClassfileError> {
+		// Rewritten in place, so `ldc`s and constant values of fields keep referring to them. What the code and the
+		// attributes hold was resolved from the old pool, parsing the class again picks up the new strings.
+		if self.strings {
+			let mut rewritten = false;
+			for i in 0..class.cp.len() {
+				let IRCpTag::String(string) = &class.cp[i] else {
+					continue;
+				};
+				let Some(relocated) = self.relocate_string(&string.data) else {
+					continue;
+				};
+				log.record(ChangeRecord::Renamed {
+					from: string.data.to_string(),
+					to: relocated.clone(),
+				});
+				class.cp[i] = IRCpTag::String(CPUtf8Ref::find_or_add(&mut class.cp, &relocated)?);
+				rewritten = true;
+			}
+			if rewritten {
+				*class = IRClassFile::read(&class.to_bytes()?)?;
+			}
+		}
+		self.remapper.apply(class, log)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		attribute::{ConstantValueAttribute, IRAttribute},
+		builder::{ClassBuilder, CodeBuilder},
+		class_pool::{CPConstValueRefKind, CPPackageInfoRef},
+		code::Instructions,
+		flags::{FieldAccessFlags, MethodAccessFlags},
+	};
+
+	#[test]
+	fn relocate_classes_strings_and_resources() {
+		let mut relocator = Relocator::new()
+			.relocate("com.google.", "shaded.com.google.")
+			.rewrite_strings(true);
+
+		let mut builder = ClassBuilder::new("com/google/common/Cache").unwrap();
+		builder.super_class(Some("com/google/common/Base")).unwrap();
+		let name = CPUtf8Ref::find_or_add(builder.cp(), "com.google.common.Loader").unwrap();
+		let other = CPUtf8Ref::find_or_add(builder.cp(), "com.googlex.Other").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		code.max_stack(1)
+			.max_locals(0)
+			.insn(Instructions::LDC(IRCpTag::String(name)))
+			.insn(Instructions::LDC(IRCpTag::String(other)))
+			.insn(Instructions::ARETURN);
+		let code = code.build().unwrap();
+		builder
+			.method(
+				MethodAccessFlags::STATIC,
+				"loader",
+				"()Lcom/google/common/Base;",
+				[IRAttribute::Code(code)],
+			)
+			.unwrap();
+		let mut class = builder.build();
+
+		relocator.apply(&mut class, &mut ChangeLog::new()).unwrap();
+		assert_eq!(class.class_name(), "shaded/com/google/common/Cache");
+		assert_eq!(class.super_name(), Some("shaded/com/google/common/Base"));
+		assert_eq!(class.methods[0].descriptor(), "()Lshaded/com/google/common/Base;");
+		let code = class.methods[0].code().unwrap();
+		let strings = Instructions::read_all(&class.cp, &code.code)
+			.unwrap()
+			.into_iter()
+			.filter_map(|(_, insn)| match insn {
+				Instructions::LDC(IRCpTag::String(string)) => Some(string.data.to_string()),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(strings, ["shaded.com.google.common.Loader", "com.googlex.Other"]);
+
+		let mut builder = ClassBuilder::module_info().unwrap();
+		let package = CPPackageInfoRef::find_or_add(builder.cp(), "com/google/common").unwrap();
+		builder
+			.attribute(IRAttribute::ModulePackages {
+				packages: vec![package],
+			})
+			.unwrap();
+		let mut module = builder.build();
+		relocator.apply(&mut module, &mut ChangeLog::new()).unwrap();
+		match module.attributes().iter().map(|attr| &attr.attr).next() {
+			Some(IRAttribute::ModulePackages { packages }) => {
+				assert_eq!(packages[0].data.data.as_str(), "shaded/com/google/common")
+			}
+			attr => panic!("{attr:?}"),
+		}
+
+		assert_eq!(
+			relocator
+				.relocate_entry("META-INF/versions/11/com/google/common/Cache.class", Vec::new())
+				.0,
+			"META-INF/versions/11/shaded/com/google/common/Cache.class"
+		);
+		assert_eq!(
+			relocator
+				.relocate_entry("com/google/common/messages.properties", Vec::new())
+				.0,
+			"shaded/com/google/common/messages.properties"
+		);
+		assert_eq!(
+			relocator.relocate_entry("META-INF/MANIFEST.MF", Vec::new()).0,
+			"META-INF/MANIFEST.MF"
+		);
+		let (name, providers) = relocator.relocate_entry(
+			"META-INF/services/com.google.common.Codec",
+			b"# codecs\ncom.google.common.Utf8Codec # default\norg.example.Codec\n".to_vec(),
+		);
+		assert_eq!(name, "META-INF/services/shaded.com.google.common.Codec");
+		assert_eq!(
+			String::from_utf8(providers).unwrap(),
+			"# codecs\nshaded.com.google.common.Utf8Codec # default\norg.example.Codec\n"
+		);
+	}
+
+	#[test]
+	fn relocate_only_strings() {
+		let mut relocator = Relocator::new()
+			.relocate("com.google.", "shaded.com.google.")
+			.rewrite_strings(true);
+
+		// Nothing but the string refers to the relocated package.
+		let mut builder = ClassBuilder::new("p/Main").unwrap();
+		let name = CPUtf8Ref::find_or_add(builder.cp(), "com/google/common/Loader").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		code.max_stack(1)
+			.max_locals(0)
+			.insn(Instructions::LDC(IRCpTag::String(name)))
+			.insn(Instructions::ARETURN);
+		let code = code.build().unwrap();
+		let value = ConstantValueAttribute::find_or_add(
+			builder.cp(),
+			CPConstValueRefKind::String("com.google.common.Codec".to_string().into()),
+		)
+		.unwrap();
+		builder
+			.field(
+				FieldAccessFlags::STATIC | FieldAccessFlags::FINAL,
+				"CODEC",
+				"Ljava/lang/String;",
+				[IRAttribute::ConstantValue(value)],
+			)
+			.unwrap()
+			.method(
+				MethodAccessFlags::STATIC,
+				"loader",
+				"()Ljava/lang/Object;",
+				[IRAttribute::Code(code)],
+			)
+			.unwrap();
+		let mut class = builder.build();
+
+		let mut log = ChangeLog::new();
+		relocator.apply(&mut class, &mut log).unwrap();
+		assert_eq!(class.class_name(), "p/Main");
+		let code = class.methods[0].code().unwrap();
+		match &Instructions::read_all(&class.cp, &code.code).unwrap()[0].1 {
+			Instructions::LDC(IRCpTag::String(string)) => {
+				assert_eq!(string.data.as_str(), "shaded/com/google/common/Loader")
+			}
+			insn => panic!("{insn:?}"),
+		}
+		match class.fields[0].attributes.constant_value().unwrap().value() {
+			CPConstValueRefKind::String(value) => assert_eq!(value.as_str(), "shaded.com.google.common.Codec"),
+			value => panic!("{value:?}"),
+		}
+		let changes = log.entries().iter().map(|entry| &entry.change).collect::<Vec<_>>();
+		assert_eq!(
+			changes,
+			[
+				&ChangeRecord::Renamed {
+					from: "com/google/common/Loader".to_string(),
+					to: "shaded/com/google/common/Loader".to_string(),
+				},
+				&ChangeRecord::Renamed {
+					from: "com.google.common.Codec".to_string(),
+					to: "shaded.com.google.common.Codec".to_string(),
+				},
+			]
+		);
+	}
+}
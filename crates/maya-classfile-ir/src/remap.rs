@@ -0,0 +1,744 @@
+// Renaming classes, fields and methods throughout a class: its own name and members, every reference to other classes
+// and their members, descriptors, generic signatures, annotations and debug info. This is what deobfuscating with a
+// mapping file does, see the `mappings` module for reading the common formats into a `Remapper`. Whole packages can be
+// moved too, see `relocate`.
+//
+// Everything is keyed by the names before remapping. A member reference names the class it was resolved against,
+// which may be a subclass of the one declaring it, so with the supertypes of the classes known (`add_hierarchy`)
+// members are also found on their supertypes.
+//
+// Constant pool entries that refer to classes and members are rewritten in place, the names and descriptors they and
+// the attributes point to are added, so the old ones stay in the pool but nothing uses them anymore. The class is
+// parsed again afterwards, so all references in the IR read the new names. String constants are left alone, even
+// when they spell a class name.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::{
+	attribute::{Attributes, IRAttribute, RuntimeAnnotation, RuntimeAnnotationValue},
+	class_pool::{cp_get, CPNameAndTypeRef, CPUtf8Ref, IRClassfileError, IRCpTag},
+	names,
+	transform::{ChangeLog, ChangeRecord, Transform},
+	IRClassFile,
+};
+
+/// Names to rename, by internal name of the class and name and descriptor of the member before remapping.
+#[derive(Debug, Clone, Default)]
+pub struct Remapper {
+	classes: BTreeMap<String, String>,
+	// Fields are keyed with their descriptor when the mappings have one, formats like SRG only name them.
+	fields: BTreeMap<(String, String, Option<String>), String>,
+	methods: BTreeMap<(String, String, String), String>,
+	// Package prefixes with their trailing slash, covering subpackages too.
+	packages: BTreeMap<String, String>,
+	supertypes: BTreeMap<String, Vec<String>>,
+}
+
+impl Remapper {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn add_class(&mut self, from: &str, to: &str) -> &mut Self {
+		self.classes.insert(from.to_string(), to.to_string());
+		self
+	}
+
+	/// Moves the classes of package `from` and its subpackages to `to`, both internal names like `com/google`.
+	pub fn add_package(&mut self, from: &str, to: &str) -> &mut Self {
+		self.packages.insert(format!("{from}/"), format!("{to}/"));
+		self
+	}
+
+	pub fn add_field(&mut self, owner: &str, name: &str, descriptor: Option<&str>, to: &str) -> &mut Self {
+		self.fields.insert(
+			(owner.to_string(), name.to_string(), descriptor.map(str::to_string)),
+			to.to_string(),
+		);
+		self
+	}
+
+	pub fn add_method(&mut self, owner: &str, name: &str, descriptor: &str, to: &str) -> &mut Self {
+		self.methods.insert(
+			(owner.to_string(), name.to_string(), descriptor.to_string()),
+			to.to_string(),
+		);
+		self
+	}
+
+	/// Records the super class and interfaces of `class`, so members mapped on them are found through it.
+	pub fn add_hierarchy(&mut self, class: &IRClassFile) -> &mut Self {
+		let supertypes = class.super_name().into_iter().chain(class.interface_names());
+		self.supertypes
+			.insert(class.class_name().to_string(), supertypes.map(str::to_string).collect());
+		self
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.classes.is_empty() && self.fields.is_empty() && self.methods.is_empty() && self.packages.is_empty()
+	}
+
+	/// The same mappings the other way around, to undo a remapping.
+	pub fn reversed(&self) -> Self {
+		let mut reversed = Self::new();
+		for (from, to) in &self.classes {
+			reversed.add_class(to, from);
+		}
+		for (from, to) in &self.packages {
+			reversed.packages.insert(to.clone(), from.clone());
+		}
+		for ((owner, name, descriptor), to) in &self.fields {
+			let descriptor = descriptor.as_deref().map(|descriptor| self.map_descriptor(descriptor));
+			reversed.add_field(&self.map_class(owner), to, descriptor.as_deref(), name);
+		}
+		for ((owner, name, descriptor), to) in &self.methods {
+			reversed.add_method(&self.map_class(owner), to, &self.map_descriptor(descriptor), name);
+		}
+		for (class, supertypes) in &self.supertypes {
+			let supertypes = supertypes.iter().map(|supertype| self.map_class(supertype)).collect();
+			reversed.supertypes.insert(self.map_class(class), supertypes);
+		}
+		reversed
+	}
+
+	/// The new internal name of a class, or of an array class by its descriptor. Nested classes without a mapping of
+	/// their own move along with their outer class, and classes without one at all with their package.
+	pub fn map_class(&self, name: &str) -> String {
+		if names::is_array(name) {
+			return self.map_descriptor(name);
+		}
+		if let Some(to) = self.classes.get(name) {
+			return to.clone();
+		}
+		if let Some((outer, inner)) = name
+			.rsplit_once('$')
+			.filter(|(outer, inner)| !outer.is_empty() && !inner.is_empty())
+		{
+			let mapped = self.map_class(outer);
+			if mapped != outer {
+				return format!("{mapped}${inner}");
+			}
+		}
+		self.map_prefix(name).unwrap_or_else(|| name.to_string())
+	}
+
+	/// The new internal name of a package.
+	pub fn map_package(&self, package: &str) -> String {
+		match self.map_prefix(&format!("{package}/")) {
+			Some(mut mapped) => {
+				mapped.pop();
+				mapped
+			}
+			None => package.to_string(),
+		}
+	}
+
+	// The longest package prefix wins, so a subpackage can go somewhere else than its parent.
+	fn map_prefix(&self, name: &str) -> Option<String> {
+		self.packages
+			.iter()
+			.filter(|(from, _)| name.starts_with(from.as_str()))
+			.max_by_key(|(from, _)| from.len())
+			.map(|(from, to)| format!("{to}{}", &name[from.len()..]))
+	}
+
+	/// Renames the classes of a field or method descriptor.
+	pub fn map_descriptor(&self, descriptor: &str) -> String {
+		let mut out = String::with_capacity(descriptor.len());
+		let mut rest = descriptor;
+		while let Some(start) = rest.find('L') {
+			let Some(end) = rest[start..].find(';') else {
+				break;
+			};
+			out.push_str(&rest[..=start]);
+			out.push_str(&self.map_class(&rest[start + 1..start + end]));
+			out.push(';');
+			rest = &rest[start + end + 1..];
+		}
+		out.push_str(rest);
+		out
+	}
+
+	/// Renames the classes of a class, method or field signature. A malformed signature is returned as it is.
+	pub fn map_signature(&self, signature: &str) -> String {
+		let mut mapper = SignatureMapper {
+			remapper: self,
+			signature: signature.as_bytes(),
+			at: 0,
+			out: String::with_capacity(signature.len()),
+		};
+		match mapper.signature() {
+			Some(()) => mapper.out,
+			None => signature.to_string(),
+		}
+	}
+
+	/// The new name of a field, looked up on `owner` and then its supertypes.
+	pub fn map_field(&self, owner: &str, name: &str, descriptor: &str) -> Option<&str> {
+		self.lookup(owner, |class| {
+			let key = (class.to_string(), name.to_string(), Some(descriptor.to_string()));
+			self.fields.get(&key).or_else(|| self.fields.get(&(key.0, key.1, None)))
+		})
+	}
+
+	/// The new name of a method, looked up on `owner` and then its supertypes.
+	pub fn map_method(&self, owner: &str, name: &str, descriptor: &str) -> Option<&str> {
+		self.lookup(owner, |class| {
+			self.methods
+				.get(&(class.to_string(), name.to_string(), descriptor.to_string()))
+		})
+	}
+
+	fn lookup<'a>(&'a self, owner: &str, find: impl Fn(&str) -> Option<&'a String>) -> Option<&'a str> {
+		let mut queue = VecDeque::from([owner]);
+		let mut seen = BTreeSet::new();
+		while let Some(class) = queue.pop_front() {
+			if !seen.insert(class) {
+				continue;
+			}
+			if let Some(to) = find(class) {
+				return Some(to);
+			}
+			queue.extend(self.supertypes.get(class).into_iter().flatten().map(String::as_str));
+		}
+		None
+	}
+
+	// Annotation elements are methods of the annotation type without parameters, the value doesn't tell the return
+	// type.
+	fn map_element(&self, annotation: &str, name: &str) -> Option<&str> {
+		let owner = names::descriptor_to_internal(annotation)?;
+		let start = (owner.to_string(), name.to_string(), String::new());
+		self.methods
+			.range(start..)
+			.take_while(|((class, element, _), _)| class == owner && element == name)
+			.find(|((_, _, descriptor), _)| descriptor.starts_with("()"))
+			.map(|(_, to)| to.as_str())
+	}
+
+	fn remap_cp(&self, cp: &mut Vec<IRCpTag>) -> Result<(), IRClassfileError> {
+		let original = cp.clone();
+		let owner = |class_index: u16| match cp_get(&original, class_index) {
+			Ok(IRCpTag::Class(name)) => Ok(name.data.clone()),
+			_ => Err(IRClassfileError::UnexpectedCpTag {
+				index: class_index,
+				expected: "class",
+			}),
+		};
+
+		for (i, tag) in original.iter().enumerate() {
+			let remapped = match tag {
+				IRCpTag::Class(name) => {
+					let mapped = self.map_class(&name.data);
+					if mapped == *name.data {
+						continue;
+					}
+					IRCpTag::Class(CPUtf8Ref::find_or_add(cp, &mapped)?)
+				}
+				IRCpTag::FieldRef {
+					class_index,
+					name_and_ty,
+				} => {
+					let owner = owner(*class_index)?;
+					let name = self.map_field(&owner, &name_and_ty.name.data, &name_and_ty.ty.data);
+					IRCpTag::FieldRef {
+						class_index: *class_index,
+						name_and_ty: self.remap_name_and_type(cp, name_and_ty, name)?,
+					}
+				}
+				IRCpTag::MethodRef {
+					class_index,
+					name_and_ty,
+				} => {
+					let owner = owner(*class_index)?;
+					let name = self.map_method(&owner, &name_and_ty.name.data, &name_and_ty.ty.data);
+					IRCpTag::MethodRef {
+						class_index: *class_index,
+						name_and_ty: self.remap_name_and_type(cp, name_and_ty, name)?,
+					}
+				}
+				IRCpTag::InterfaceMethodRef {
+					class_index,
+					name_and_ty,
+				} => {
+					let owner = owner(*class_index)?;
+					let name = self.map_method(&owner, &name_and_ty.name.data, &name_and_ty.ty.data);
+					IRCpTag::InterfaceMethodRef {
+						class_index: *class_index,
+						name_and_ty: self.remap_name_and_type(cp, name_and_ty, name)?,
+					}
+				}
+				// The name is the one the bootstrap method gets, e.g. the interface method of a lambda, which the
+				// call site doesn't tell the owner of.
+				IRCpTag::InvokeDynamic {
+					bootstrap_method_attr_index,
+					name_and_ty,
+				} => IRCpTag::InvokeDynamic {
+					bootstrap_method_attr_index: *bootstrap_method_attr_index,
+					name_and_ty: self.remap_name_and_type(cp, name_and_ty, None)?,
+				},
+				IRCpTag::MethodType(descriptor) => {
+					IRCpTag::MethodType(CPUtf8Ref::find_or_add(cp, &self.map_descriptor(&descriptor.data))?)
+				}
+				// Packages a module exports, opens or contains.
+				IRCpTag::Package { name } => {
+					let mapped = self.map_package(&name.data);
+					if mapped == *name.data {
+						continue;
+					}
+					IRCpTag::Package {
+						name: CPUtf8Ref::find_or_add(cp, &mapped)?,
+					}
+				}
+				_ => continue,
+			};
+			cp[i] = remapped;
+		}
+		Ok(())
+	}
+
+	// Name and type entries are shared between references to members of different classes, so a renamed member gets
+	// an entry of its own.
+	fn remap_name_and_type(
+		&self,
+		cp: &mut Vec<IRCpTag>,
+		name_and_ty: &CPNameAndTypeRef,
+		name: Option<&str>,
+	) -> Result<CPNameAndTypeRef, IRClassfileError> {
+		let descriptor = self.map_descriptor(&name_and_ty.ty.data);
+		let name = name.unwrap_or(&name_and_ty.name.data);
+		if name == *name_and_ty.name.data && descriptor == *name_and_ty.ty.data {
+			return Ok(name_and_ty.clone());
+		}
+		CPNameAndTypeRef::find_or_add(cp, name, &descriptor)
+	}
+
+	fn remap_attributes(
+		&self,
+		cp: &mut Vec<IRCpTag>,
+		owner: &str,
+		attributes: &mut Attributes,
+	) -> Result<(), IRClassfileError> {
+		for info in attributes.iter_mut() {
+			match &mut info.attr {
+				IRAttribute::Code(code) => self.remap_attributes(cp, owner, &mut code.attributes)?,
+				IRAttribute::Signature(signature) => {
+					*signature = remap_utf8(cp, signature, |signature| self.map_signature(signature))?;
+				}
+				IRAttribute::InnerClasses(inner) => {
+					for class in &mut inner.classes {
+						let Some(inner_name) = &class.inner_name else {
+							continue;
+						};
+						let mapped = self.map_class(&class.inner_class_info.data.data);
+						if mapped == *class.inner_class_info.data.data {
+							continue;
+						}
+						let outer = class
+							.outer_class_info
+							.as_ref()
+							.map(|outer| format!("{}$", self.map_class(&outer.data.data)));
+						let simple = match outer.as_deref().and_then(|outer| mapped.strip_prefix(outer)) {
+							Some(simple) => simple,
+							None => mapped.rsplit(['$', '/']).next().unwrap_or(&mapped),
+						};
+						if simple != *inner_name.data {
+							class.inner_name = Some(CPUtf8Ref::find_or_add(cp, simple)?);
+						}
+					}
+				}
+				IRAttribute::EnclosingMethod {
+					class,
+					method: Some(method),
+				} => {
+					let name = self.map_method(&class.data.data, &method.name.data, &method.ty.data);
+					*method = self.remap_name_and_type(cp, method, name)?;
+				}
+				IRAttribute::LocalVariableTable { table } => {
+					for entry in table {
+						entry.descriptor =
+							remap_utf8(cp, &entry.descriptor, |descriptor| self.map_descriptor(descriptor))?;
+					}
+				}
+				IRAttribute::LocalVariableTypeTable { table } => {
+					for entry in table {
+						entry.signature = remap_utf8(cp, &entry.signature, |signature| self.map_signature(signature))?;
+					}
+				}
+				IRAttribute::RuntimeVisibleAnnotations { annotations }
+				| IRAttribute::RuntimeInvisibleAnnotations { annotations } => {
+					for annotation in annotations {
+						self.remap_annotation(cp, annotation)?;
+					}
+				}
+				IRAttribute::RuntimeVisibleParameterAnnotations { params }
+				| IRAttribute::RuntimeInvisibleParameterAnnotations { params } => {
+					for annotation in params.iter_mut().flatten() {
+						self.remap_annotation(cp, annotation)?;
+					}
+				}
+				IRAttribute::AnnotationDefault { default_value } => self.remap_value(cp, default_value)?,
+				IRAttribute::RuntimeVisibleTypeAnnotations { annotations }
+				| IRAttribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+					for annotation in annotations {
+						let ty = CPUtf8Ref::from_cp(cp, annotation.type_index)?;
+						let ty = remap_utf8(cp, &ty, |descriptor| self.map_descriptor(descriptor))?;
+						for pair in &mut annotation.pairs {
+							if let Some(name) = self.map_element(&ty.data, &pair.name.data) {
+								pair.name = CPUtf8Ref::find_or_add(cp, name)?;
+							}
+							self.remap_value(cp, &mut pair.value)?;
+						}
+						annotation.type_index = ty.index;
+					}
+				}
+				IRAttribute::Record { components } => {
+					for component in components {
+						if let Some(name) = self.map_field(owner, &component.name.data, &component.descriptor.data) {
+							component.name = CPUtf8Ref::find_or_add(cp, name)?;
+						}
+						component.descriptor =
+							remap_utf8(cp, &component.descriptor, |descriptor| self.map_descriptor(descriptor))?;
+						self.remap_attributes(cp, owner, &mut component.attributes)?;
+					}
+				}
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+
+	fn remap_annotation(
+		&self,
+		cp: &mut Vec<IRCpTag>,
+		annotation: &mut RuntimeAnnotation,
+	) -> Result<(), IRClassfileError> {
+		for pair in &mut annotation.pairs {
+			if let Some(name) = self.map_element(&annotation.ty.data, &pair.name.data) {
+				pair.name = CPUtf8Ref::find_or_add(cp, name)?;
+			}
+			self.remap_value(cp, &mut pair.value)?;
+		}
+		annotation.ty = remap_utf8(cp, &annotation.ty, |descriptor| self.map_descriptor(descriptor))?;
+		Ok(())
+	}
+
+	fn remap_value(&self, cp: &mut Vec<IRCpTag>, value: &mut RuntimeAnnotationValue) -> Result<(), IRClassfileError> {
+		match value {
+			RuntimeAnnotationValue::EnumConstValue { type_name, const_name } => {
+				if let Some(owner) = names::descriptor_to_internal(&type_name.data) {
+					if let Some(name) = self.map_field(owner, &const_name.data, &type_name.data) {
+						*const_name = CPUtf8Ref::find_or_add(cp, name)?;
+					}
+				}
+				*type_name = remap_utf8(cp, type_name, |descriptor| self.map_descriptor(descriptor))?;
+			}
+			RuntimeAnnotationValue::ClassInfoIndex(class) => {
+				*class = remap_utf8(cp, class, |descriptor| self.map_descriptor(descriptor))?;
+			}
+			RuntimeAnnotationValue::Annotation(annotation) => self.remap_annotation(cp, annotation)?,
+			RuntimeAnnotationValue::ArrayValue { values } => {
+				for value in values {
+					self.remap_value(cp, value)?;
+				}
+			}
+			RuntimeAnnotationValue::ConstValueIndex { .. } => {}
+		}
+		Ok(())
+	}
+}
+
+fn remap_utf8(
+	cp: &mut Vec<IRCpTag>,
+	utf8: &CPUtf8Ref,
+	map: impl FnOnce(&str) -> String,
+) -> Result<CPUtf8Ref, IRClassfileError> {
+	let mapped = map(&utf8.data);
+	match mapped == *utf8.data {
+		true => Ok(utf8.clone()),
+		false => CPUtf8Ref::find_or_add(cp, &mapped),
+	}
+}
+
+impl Transform for Remapper {
+	fn name(&self) -> &str {
+		"remap"
+	}
+
+	fn apply(&mut self, class: &mut IRClassFile, log: &mut ChangeLog) -> Result<(), IRClassfileError> {
+		if self.is_empty() {
+			return Ok(());
+		}
+		let owner = class.class_name().to_string();
+		let mut renamed = Vec::new();
+
+		self.remap_cp(&mut class.cp)?;
+		let cp = &mut class.cp;
+		for field in &mut class.fields {
+			let (name, descriptor) = (field.name.data.clone(), field.descriptor.data.clone());
+			if let Some(to) = self.map_field(&owner, &name, &descriptor) {
+				renamed.push((name.to_string(), to.to_string()));
+				field.name = CPUtf8Ref::find_or_add(cp, to)?;
+			}
+			field.descriptor = remap_utf8(cp, &field.descriptor, |descriptor| self.map_descriptor(descriptor))?;
+			self.remap_attributes(cp, &owner, &mut field.attributes)?;
+		}
+		for method in &mut class.methods {
+			let (name, descriptor) = (method.name.data.clone(), method.descriptor.data.clone());
+			if let Some(to) = self.map_method(&owner, &name, &descriptor) {
+				renamed.push((
+					format!("{name}{descriptor}"),
+					format!("{to}{}", self.map_descriptor(&descriptor)),
+				));
+				method.name = CPUtf8Ref::find_or_add(cp, to)?;
+			}
+			method.descriptor = remap_utf8(cp, &method.descriptor, |descriptor| self.map_descriptor(descriptor))?;
+			self.remap_attributes(cp, &owner, &mut method.attributes)?;
+		}
+		self.remap_attributes(cp, &owner, &mut class.attributes)?;
+
+		*class = IRClassFile::read(&class.to_bytes()?)?;
+		if class.class_name() != owner {
+			log.record(ChangeRecord::Renamed {
+				from: owner,
+				to: class.class_name().to_string(),
+			});
+		}
+		for (from, to) in renamed {
+			log.record(ChangeRecord::Renamed { from, to });
+		}
+		Ok(())
+	}
+}
+
+// Signatures are walked rather than scanned for `L...;` like descriptors, as type variable names can contain `L` and
+// nested classes are written as `Outer<T>.Inner`.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.9.1
+struct SignatureMapper<'a> {
+	remapper: &'a Remapper,
+	signature: &'a [u8],
+	at: usize,
+	out: String,
+}
+
+impl<'a> SignatureMapper<'a> {
+	fn peek(&self) -> Option<u8> {
+		self.signature.get(self.at).copied()
+	}
+
+	fn next(&mut self) -> Option<u8> {
+		let byte = self.peek()?;
+		self.at += 1;
+		self.out.push(byte as char);
+		Some(byte)
+	}
+
+	fn expect(&mut self, byte: u8) -> Option<()> {
+		(self.next()? == byte).then_some(())
+	}
+
+	// Identifiers may be any UTF-8, only the characters the grammar uses end them.
+	fn identifier(&mut self) -> Option<&'a str> {
+		let start = self.at;
+		while !matches!(self.peek()?, b'.' | b';' | b'[' | b'/' | b'<' | b'>' | b':') {
+			self.at += 1;
+		}
+		match self.at > start {
+			true => std::str::from_utf8(&self.signature[start..self.at]).ok(),
+			false => None,
+		}
+	}
+
+	fn signature(&mut self) -> Option<()> {
+		if self.peek() == Some(b'<') {
+			self.type_parameters()?;
+		}
+		while self.at < self.signature.len() {
+			match self.peek()? {
+				b'(' | b')' | b'^' => {
+					self.next();
+				}
+				b'V' => {
+					self.next();
+				}
+				_ => self.type_signature()?,
+			}
+		}
+		Some(())
+	}
+
+	fn type_parameters(&mut self) -> Option<()> {
+		self.expect(b'<')?;
+		while self.peek()? != b'>' {
+			let name = self.identifier()?;
+			self.out.push_str(name);
+			// The class bound may be empty, interface bounds follow with a colon each.
+			while self.peek()? == b':' {
+				self.next();
+				if !matches!(self.peek()?, b':' | b'>') {
+					self.type_signature()?;
+				}
+			}
+		}
+		self.expect(b'>')
+	}
+
+	fn type_signature(&mut self) -> Option<()> {
+		match self.peek()? {
+			b'L' => self.class_type(),
+			b'T' => {
+				self.next();
+				let name = self.identifier()?;
+				self.out.push_str(name);
+				self.expect(b';')
+			}
+			b'[' => {
+				self.next();
+				self.type_signature()
+			}
+			b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' => {
+				self.next();
+				Some(())
+			}
+			_ => None,
+		}
+	}
+
+	fn class_type(&mut self) -> Option<()> {
+		self.expect(b'L')?;
+		let start = self.at;
+		while !matches!(self.peek()?, b'<' | b'.' | b';') {
+			self.at += 1;
+		}
+		let mut name = std::str::from_utf8(&self.signature[start..self.at]).ok()?.to_string();
+		let mut mapped = self.remapper.map_class(&name);
+		self.out.push_str(&mapped);
+		loop {
+			match self.peek()? {
+				b'<' => self.type_arguments()?,
+				b'.' => {
+					self.next();
+					let simple = self.identifier()?;
+					name = format!("{name}${simple}");
+					let outer = format!("{mapped}$");
+					mapped = self.remapper.map_class(&name);
+					let simple = match mapped.strip_prefix(&outer) {
+						Some(simple) => simple,
+						None => mapped.rsplit(['$', '/']).next().unwrap_or(simple),
+					};
+					self.out.push_str(simple);
+				}
+				_ => return self.expect(b';'),
+			}
+		}
+	}
+
+	fn type_arguments(&mut self) -> Option<()> {
+		self.expect(b'<')?;
+		while self.peek()? != b'>' {
+			match self.peek()? {
+				b'*' => {
+					self.next();
+				}
+				b'+' | b'-' => {
+					self.next();
+					self.type_signature()?;
+				}
+				_ => self.type_signature()?,
+			}
+		}
+		self.expect(b'>')
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		builder::{ClassBuilder, CodeBuilder},
+		class_pool::CPMethodRef,
+		code::Instructions,
+		flags::{FieldAccessFlags, MethodAccessFlags},
+	};
+
+	#[test]
+	fn remap_class_members_and_references() {
+		let mut remapper = Remapper::new();
+		remapper
+			.add_class("a", "com/example/Widget")
+			.add_class("b", "com/example/Part")
+			.add_field("a", "c", Some("Lb;"), "part")
+			.add_method("b", "d", "(La;)Lb;", "attach");
+
+		assert_eq!(
+			remapper.map_descriptor("([La;ILb;)V"),
+			"([Lcom/example/Widget;ILcom/example/Part;)V"
+		);
+		assert_eq!(remapper.map_class("a$1"), "com/example/Widget$1");
+		assert_eq!(
+			remapper.map_signature("<TLa::La;>(Ljava/util/List<+La;>;TTLa;)La$e<Lb;>.f;"),
+			"<TLa::Lcom/example/Widget;>(Ljava/util/List<+Lcom/example/Widget;>;TTLa;)\
+			 Lcom/example/Widget$e<Lcom/example/Part;>.f;"
+		);
+
+		let mut builder = ClassBuilder::new("a").unwrap();
+		builder.field(FieldAccessFlags::PRIVATE, "c", "Lb;", []).unwrap();
+		let attach = CPMethodRef::find_or_add(builder.cp(), "b", "d", "(La;)Lb;").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		code.max_stack(2)
+			.max_locals(2)
+			.insn(Instructions::ALOAD(1))
+			.insn(Instructions::ALOAD(0))
+			.insn(Instructions::INVOKEVIRTUAL(attach))
+			.insn(Instructions::ARETURN);
+		let code = code.build().unwrap();
+		builder
+			.method(MethodAccessFlags::PUBLIC, "g", "(Lb;)Lb;", [IRAttribute::Code(code)])
+			.unwrap();
+		let mut class = builder.build();
+
+		let mut log = ChangeLog::new();
+		remapper.apply(&mut class, &mut log).unwrap();
+		assert_eq!(class.class_name(), "com/example/Widget");
+		assert_eq!(class.fields[0].name(), "part");
+		assert_eq!(class.fields[0].descriptor(), "Lcom/example/Part;");
+		assert_eq!(class.methods[0].descriptor(), "(Lcom/example/Part;)Lcom/example/Part;");
+		let code = class.methods[0].code().unwrap();
+		let called = Instructions::read_all(&class.cp, &code.code)
+			.unwrap()
+			.into_iter()
+			.find_map(|(_, insn)| match insn {
+				Instructions::INVOKEVIRTUAL(method) => Some(method),
+				_ => None,
+			})
+			.unwrap();
+		assert_eq!(called.class.data.data.as_str(), "com/example/Part");
+		assert_eq!(called.name_and_ty.name.data.as_str(), "attach");
+		assert_eq!(
+			called.name_and_ty.ty.data.as_str(),
+			"(Lcom/example/Widget;)Lcom/example/Part;"
+		);
+		assert_eq!(
+			log.entries()
+				.iter()
+				.map(|entry| entry.change.clone())
+				.collect::<Vec<_>>(),
+			[
+				ChangeRecord::Renamed {
+					from: "a".to_string(),
+					to: "com/example/Widget".to_string()
+				},
+				ChangeRecord::Renamed {
+					from: "c".to_string(),
+					to: "part".to_string()
+				},
+			]
+		);
+
+		// Undoing it gives the original names back.
+		remapper.reversed().apply(&mut class, &mut ChangeLog::new()).unwrap();
+		assert_eq!(class.class_name(), "a");
+		assert_eq!(class.fields[0].name(), "c");
+		assert_eq!(class.methods[0].descriptor(), "(Lb;)Lb;");
+	}
+}
@@ -0,0 +1,81 @@
+// Finding other classes by name. Computing frames needs the common super class of two types, verifying a class and
+// asking about its hierarchy need the classes above it, and none of them care where those come from. A
+// `ClassResolver` is anything that can hand one over: every `ClassProvider` is, so directories, jars, the JDK's
+// modules image and whole class paths resolve classes by parsing them each time, and a `ClassCache` resolves them
+// parsed once and shared.
+
+use std::{fmt, rc::Rc};
+
+use crate::{
+	classpath::{decode, ClassCache, ClassProvider, ClasspathError},
+	IRClassFile,
+};
+
+pub trait ClassResolver: fmt::Debug {
+	/// The class with this internal name, `None` if there's no such class.
+	fn try_resolve(&self, name: &str) -> Result<Option<Rc<IRClassFile>>, ClasspathError>;
+
+	/// Like `try_resolve`, a class that can't be read or parsed counting as missing.
+	fn resolve(&self, name: &str) -> Option<Rc<IRClassFile>> {
+		self.try_resolve(name).ok().flatten()
+	}
+}
+
+impl<P: ClassProvider + ?Sized> ClassResolver for P {
+	fn try_resolve(&self, name: &str) -> Result<Option<Rc<IRClassFile>>, ClasspathError> {
+		match self.read_class(name)? {
+			Some(bytes) => Ok(Some(Rc::new(decode(name, &bytes)?))),
+			None => Ok(None),
+		}
+	}
+}
+
+impl ClassResolver for ClassCache {
+	fn try_resolve(&self, name: &str) -> Result<Option<Rc<IRClassFile>>, ClasspathError> {
+		self.get(name)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+	use crate::{
+		builder::ClassBuilder,
+		classpath::{CachePolicy, Classpath, MemoryProvider},
+		scratch::ScratchDir,
+	};
+
+	#[test]
+	fn resolve_from_providers() {
+		let dir = ScratchDir::new("resolver");
+		fs::create_dir_all(dir.join("p")).unwrap();
+		let class = |name: &str| ClassBuilder::new(name).unwrap().to_bytes().unwrap();
+		fs::write(dir.join("p/A.class"), class("p/A")).unwrap();
+		let mut memory = MemoryProvider::new();
+		memory.insert("p/B", class("p/B"));
+		memory.insert("p/Broken", b"\xca\xfe".to_vec());
+
+		let mut classpath = Classpath::new(vec![dir.to_path_buf()]);
+		classpath.push_provider(Rc::new(memory));
+		let resolvers: [&dyn ClassResolver; 2] =
+			[&classpath, &ClassCache::new(classpath.clone(), CachePolicy::Unbounded)];
+		for resolver in resolvers {
+			assert_eq!(resolver.resolve("p/A").unwrap().class_name(), "p/A");
+			assert_eq!(resolver.resolve("p/B").unwrap().class_name(), "p/B");
+			assert!(resolver.resolve("p/Missing").is_none());
+			assert!(resolver.resolve("p/Broken").is_none());
+			assert!(matches!(
+				resolver.try_resolve("p/Broken"),
+				Err(ClasspathError::Class { name, .. }) if name == "p/Broken"
+			));
+		}
+
+		let cache = ClassCache::new(classpath, CachePolicy::Unbounded);
+		assert!(Rc::ptr_eq(
+			&cache.resolve("p/A").unwrap(),
+			&cache.resolve("p/A").unwrap()
+		));
+	}
+}
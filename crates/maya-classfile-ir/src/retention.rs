@@ -0,0 +1,440 @@
+// Annotation retention in compiled code: RUNTIME annotations go in the RuntimeVisible tables, CLASS ones in the
+// RuntimeInvisible tables, and SOURCE ones aren't in the class file at all. Changing an annotation type's retention,
+// or dropping runtime visibility to save space, means moving its annotations from one table to the other, for
+// declaration, parameter and type annotations alike. With the annotation types on a class path, the tables can also be
+// checked against the `@Retention` the types declare.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.16
+
+use crate::{
+	attribute::{Attributes, DecodedAnnotationValue, IRAttribute, IRAttributeInfo, RuntimeAnnotation},
+	class_pool::{CPUtf8Ref, IRClassfileError, IRCpTag},
+	classpath::{ClassCache, ClasspathError},
+	names, IRClassFile,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Retention {
+	Source,
+	Class,
+	Runtime,
+}
+
+impl Retention {
+	/// The retention an annotation type declares, CLASS if it has no `@Retention`.
+	pub fn declared_by(annotation_type: &IRClassFile) -> Result<Self, IRClassfileError> {
+		let Some(retention) = annotation_type
+			.attributes
+			.annotation("Ljava/lang/annotation/Retention;")
+		else {
+			return Ok(Self::Class);
+		};
+		Ok(match retention.resolve()?.get("value") {
+			Some(DecodedAnnotationValue::Enum { name, .. }) if name == "SOURCE" => Self::Source,
+			Some(DecodedAnnotationValue::Enum { name, .. }) if name == "RUNTIME" => Self::Runtime,
+			_ => Self::Class,
+		})
+	}
+
+	/// The retention of the annotations in a visible or invisible table.
+	fn of_table(visible: bool) -> Self {
+		match visible {
+			true => Self::Runtime,
+			false => Self::Class,
+		}
+	}
+}
+
+/// Moves the annotations of type `descriptor` (e.g. `Lcom/example/Foo;`) to the tables for `retention`, adding the
+/// tables if needed and removing the ones left empty. `Retention::Source` removes the annotations. Annotations keep
+/// their order, moved ones going after those already in the target table. Returns how many were moved or removed.
+pub fn set_retention(
+	cp: &mut Vec<IRCpTag>,
+	attributes: &mut Attributes,
+	descriptor: &str,
+	retention: Retention,
+) -> Result<usize, IRClassfileError> {
+	let mut annotations = Vec::new();
+	let mut params = Vec::<Vec<RuntimeAnnotation>>::new();
+	let mut type_annotations = Vec::new();
+	let mut emptied = Vec::new();
+
+	for (i, attr) in attributes.iter_mut().enumerate() {
+		if !is_visible_table(&attr.attr).is_some_and(|visible| Retention::of_table(visible) != retention) {
+			continue;
+		}
+		let empty = match &mut attr.attr {
+			IRAttribute::RuntimeVisibleAnnotations { annotations: from }
+			| IRAttribute::RuntimeInvisibleAnnotations { annotations: from } => {
+				annotations.extend(take_matching(from, |annotation| {
+					annotation.ty.data.as_str() == descriptor
+				}));
+				from.is_empty()
+			}
+			IRAttribute::RuntimeVisibleParameterAnnotations { params: from }
+			| IRAttribute::RuntimeInvisibleParameterAnnotations { params: from } => {
+				if params.len() < from.len() {
+					params.resize_with(from.len(), Vec::new);
+				}
+				for (param, taken) in from.iter_mut().zip(&mut params) {
+					taken.extend(take_matching(param, |annotation| {
+						annotation.ty.data.as_str() == descriptor
+					}));
+				}
+				from.iter().all(Vec::is_empty)
+			}
+			IRAttribute::RuntimeVisibleTypeAnnotations { annotations: from }
+			| IRAttribute::RuntimeInvisibleTypeAnnotations { annotations: from } => {
+				type_annotations.extend(take_matching(from, |annotation| {
+					CPUtf8Ref::from_cp(cp, annotation.type_index).is_ok_and(|ty| ty.data.as_str() == descriptor)
+				}));
+				from.is_empty()
+			}
+			_ => unreachable!("only annotation tables have a visibility"),
+		};
+		if empty {
+			emptied.push(i);
+		}
+	}
+
+	let moved = annotations.len() + params.iter().map(Vec::len).sum::<usize>() + type_annotations.len();
+	if moved == 0 {
+		return Ok(0);
+	}
+	let mut i = 0;
+	attributes.retain(|_| {
+		i += 1;
+		!emptied.contains(&(i - 1))
+	});
+	if retention == Retention::Source {
+		return Ok(moved);
+	}
+
+	let visible = retention == Retention::Runtime;
+	if !annotations.is_empty() {
+		match attributes.iter_mut().find_map(|attr| match &mut attr.attr {
+			IRAttribute::RuntimeVisibleAnnotations { annotations } if visible => Some(annotations),
+			IRAttribute::RuntimeInvisibleAnnotations { annotations } if !visible => Some(annotations),
+			_ => None,
+		}) {
+			Some(table) => table.extend(annotations),
+			None => push_table(
+				cp,
+				attributes,
+				match visible {
+					true => IRAttribute::RuntimeVisibleAnnotations { annotations },
+					false => IRAttribute::RuntimeInvisibleAnnotations { annotations },
+				},
+			)?,
+		}
+	}
+	if params.iter().any(|param| !param.is_empty()) {
+		match attributes.iter_mut().find_map(|attr| match &mut attr.attr {
+			IRAttribute::RuntimeVisibleParameterAnnotations { params } if visible => Some(params),
+			IRAttribute::RuntimeInvisibleParameterAnnotations { params } if !visible => Some(params),
+			_ => None,
+		}) {
+			Some(table) => {
+				if table.len() < params.len() {
+					table.resize_with(params.len(), Vec::new);
+				}
+				for (param, moved) in table.iter_mut().zip(params) {
+					param.extend(moved);
+				}
+			}
+			None => push_table(
+				cp,
+				attributes,
+				match visible {
+					true => IRAttribute::RuntimeVisibleParameterAnnotations { params },
+					false => IRAttribute::RuntimeInvisibleParameterAnnotations { params },
+				},
+			)?,
+		}
+	}
+	if !type_annotations.is_empty() {
+		match attributes.iter_mut().find_map(|attr| match &mut attr.attr {
+			IRAttribute::RuntimeVisibleTypeAnnotations { annotations } if visible => Some(annotations),
+			IRAttribute::RuntimeInvisibleTypeAnnotations { annotations } if !visible => Some(annotations),
+			_ => None,
+		}) {
+			Some(table) => table.extend(type_annotations),
+			None => push_table(
+				cp,
+				attributes,
+				match visible {
+					true => IRAttribute::RuntimeVisibleTypeAnnotations {
+						annotations: type_annotations,
+					},
+					false => IRAttribute::RuntimeInvisibleTypeAnnotations {
+						annotations: type_annotations,
+					},
+				},
+			)?,
+		}
+	}
+	Ok(moved)
+}
+
+/// `set_retention` on everything in the class that can be annotated: the class, its fields, methods, record components
+/// and the Code attributes of its methods.
+pub fn set_class_retention(
+	class: &mut IRClassFile,
+	descriptor: &str,
+	retention: Retention,
+) -> Result<usize, IRClassfileError> {
+	let cp = &mut class.cp;
+	let mut moved = set_retention(cp, &mut class.attributes, descriptor, retention)?;
+	for attr in class.attributes.iter_mut() {
+		if let IRAttribute::Record { components } = &mut attr.attr {
+			for component in components {
+				moved += set_retention(cp, &mut component.attributes, descriptor, retention)?;
+			}
+		}
+	}
+	for field in &mut class.fields {
+		moved += set_retention(cp, &mut field.attributes, descriptor, retention)?;
+	}
+	for method in &mut class.methods {
+		moved += set_retention(cp, &mut method.attributes, descriptor, retention)?;
+		if let Some(code) = method.attributes.code_mut() {
+			moved += set_retention(cp, &mut code.attributes, descriptor, retention)?;
+		}
+	}
+	Ok(moved)
+}
+
+/// An annotation in the table for one retention whose type declares another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionMismatch {
+	// what's annotated, e.g. `method run()V`
+	pub location: String,
+	// descriptor of the annotation type
+	pub annotation: String,
+	pub found: Retention,
+	pub declared: Retention,
+}
+
+/// Checks every annotation in `class` against the `@Retention` of its type. Annotation types that aren't on the class
+/// path are skipped.
+pub fn check_retention(class: &IRClassFile, classes: &ClassCache) -> Result<Vec<RetentionMismatch>, ClasspathError> {
+	let mut mismatches = Vec::new();
+	check_attributes(class, classes, "class", &class.attributes, &mut mismatches)?;
+	for attr in class.attributes.iter() {
+		if let IRAttribute::Record { components } = &attr.attr {
+			for component in components {
+				let location = format!("record component {}", component.name.data);
+				check_attributes(class, classes, &location, &component.attributes, &mut mismatches)?;
+			}
+		}
+	}
+	for field in &class.fields {
+		let location = format!("field {}", field.name());
+		check_attributes(class, classes, &location, &field.attributes, &mut mismatches)?;
+	}
+	for method in &class.methods {
+		let location = format!("method {}{}", method.name(), method.descriptor());
+		check_attributes(class, classes, &location, &method.attributes, &mut mismatches)?;
+		if let Some(code) = method.code() {
+			check_attributes(class, classes, &location, &code.attributes, &mut mismatches)?;
+		}
+	}
+	Ok(mismatches)
+}
+
+fn check_attributes(
+	class: &IRClassFile,
+	classes: &ClassCache,
+	location: &str,
+	attributes: &Attributes,
+	mismatches: &mut Vec<RetentionMismatch>,
+) -> Result<(), ClasspathError> {
+	for attr in attributes.iter() {
+		let Some(visible) = is_visible_table(&attr.attr) else {
+			continue;
+		};
+		let descriptors = match &attr.attr {
+			IRAttribute::RuntimeVisibleAnnotations { annotations }
+			| IRAttribute::RuntimeInvisibleAnnotations { annotations } => annotations
+				.iter()
+				.map(|annotation| annotation.ty.data.to_string())
+				.collect::<Vec<_>>(),
+			IRAttribute::RuntimeVisibleParameterAnnotations { params }
+			| IRAttribute::RuntimeInvisibleParameterAnnotations { params } => params
+				.iter()
+				.flatten()
+				.map(|annotation| annotation.ty.data.to_string())
+				.collect(),
+			IRAttribute::RuntimeVisibleTypeAnnotations { annotations }
+			| IRAttribute::RuntimeInvisibleTypeAnnotations { annotations } => annotations
+				.iter()
+				.map(|annotation| Ok(CPUtf8Ref::from_cp(&class.cp, annotation.type_index)?.data.to_string()))
+				.collect::<Result<_, IRClassfileError>>()
+				.map_err(|source| ClasspathError::Class {
+					name: class.class_name().to_string(),
+					source,
+				})?,
+			_ => unreachable!("only annotation tables have a visibility"),
+		};
+
+		let found = Retention::of_table(visible);
+		for descriptor in descriptors {
+			let Some(ty) = names::descriptor_to_internal(&descriptor) else {
+				continue;
+			};
+			let Some(annotation_type) = classes.get(ty)? else {
+				continue;
+			};
+			let declared = Retention::declared_by(&annotation_type).map_err(|source| ClasspathError::Class {
+				name: ty.to_string(),
+				source,
+			})?;
+			if declared != found {
+				mismatches.push(RetentionMismatch {
+					location: location.to_string(),
+					annotation: descriptor,
+					found,
+					declared,
+				});
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Whether `attr` is a visible or an invisible annotation table, `None` if it's something else.
+fn is_visible_table(attr: &IRAttribute) -> Option<bool> {
+	match attr {
+		IRAttribute::RuntimeVisibleAnnotations { .. }
+		| IRAttribute::RuntimeVisibleParameterAnnotations { .. }
+		| IRAttribute::RuntimeVisibleTypeAnnotations { .. } => Some(true),
+		IRAttribute::RuntimeInvisibleAnnotations { .. }
+		| IRAttribute::RuntimeInvisibleParameterAnnotations { .. }
+		| IRAttribute::RuntimeInvisibleTypeAnnotations { .. } => Some(false),
+		_ => None,
+	}
+}
+
+// Takes what `is_match` accepts out of `from`, both parts keeping their order.
+fn take_matching<T>(from: &mut Vec<T>, is_match: impl FnMut(&T) -> bool) -> Vec<T> {
+	let (taken, kept) = std::mem::take(from).into_iter().partition(is_match);
+	*from = kept;
+	taken
+}
+
+fn push_table(cp: &mut Vec<IRCpTag>, attributes: &mut Attributes, attr: IRAttribute) -> Result<(), IRClassfileError> {
+	let name = CPUtf8Ref::find_or_add(cp, attr.name())?;
+	attributes.push(IRAttributeInfo { name, length: 0, attr });
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use smallvec::{smallvec, SmallVec};
+
+	use super::*;
+	use crate::{
+		attribute::{RuntimeAnnotationEVPair, RuntimeAnnotationValue},
+		builder::ClassBuilder,
+		classpath::{CachePolicy, Classpath},
+		flags::MethodAccessFlags,
+		scratch::ScratchDir,
+	};
+
+	fn annotation(cp: &mut Vec<IRCpTag>, descriptor: &str) -> RuntimeAnnotation {
+		RuntimeAnnotation {
+			ty: CPUtf8Ref::find_or_add(cp, descriptor).unwrap(),
+			pairs: SmallVec::new(),
+		}
+	}
+
+	#[test]
+	fn move_and_check() {
+		// @Retention(RUNTIME) @interface Kept
+		let mut kept = ClassBuilder::new("p/Kept").unwrap();
+		let retention = RuntimeAnnotation {
+			pairs: smallvec![RuntimeAnnotationEVPair {
+				name: CPUtf8Ref::find_or_add(kept.cp(), "value").unwrap(),
+				value: RuntimeAnnotationValue::EnumConstValue {
+					type_name: CPUtf8Ref::find_or_add(kept.cp(), "Ljava/lang/annotation/RetentionPolicy;").unwrap(),
+					const_name: CPUtf8Ref::find_or_add(kept.cp(), "RUNTIME").unwrap(),
+				},
+			}],
+			..annotation(kept.cp(), "Ljava/lang/annotation/Retention;")
+		};
+		kept.attribute(IRAttribute::RuntimeVisibleAnnotations {
+			annotations: vec![retention],
+		})
+		.unwrap();
+		let kept = kept.to_bytes().unwrap();
+		assert_eq!(
+			Retention::declared_by(&IRClassFile::read(&kept).unwrap()).unwrap(),
+			Retention::Runtime
+		);
+
+		// abstract void m(@Kept int x), annotated @Kept @Other, all in the invisible tables
+		let mut builder = ClassBuilder::new("p/C").unwrap();
+		let annotations = vec![
+			annotation(builder.cp(), "Lp/Kept;"),
+			annotation(builder.cp(), "Lp/Other;"),
+		];
+		let params = vec![vec![annotation(builder.cp(), "Lp/Kept;")]];
+		builder
+			.method(
+				MethodAccessFlags::ABSTRACT,
+				"m",
+				"(I)V",
+				[
+					IRAttribute::RuntimeInvisibleAnnotations { annotations },
+					IRAttribute::RuntimeInvisibleParameterAnnotations { params },
+				],
+			)
+			.unwrap();
+		let mut class = builder.build();
+
+		let dir = ScratchDir::new("retention");
+		fs::create_dir_all(dir.join("p")).unwrap();
+		fs::write(dir.join("p/Kept.class"), kept).unwrap();
+		let classes = ClassCache::new(Classpath::new(vec![dir.to_path_buf()]), CachePolicy::Unbounded);
+		let mismatches = check_retention(&class, &classes).unwrap();
+		assert_eq!(mismatches.len(), 2);
+		assert_eq!(
+			mismatches[0],
+			RetentionMismatch {
+				location: "method m(I)V".to_string(),
+				annotation: "Lp/Kept;".to_string(),
+				found: Retention::Class,
+				declared: Retention::Runtime,
+			}
+		);
+
+		assert_eq!(
+			set_class_retention(&mut class, "Lp/Kept;", Retention::Runtime).unwrap(),
+			2
+		);
+		let class = IRClassFile::read(&class.to_bytes().unwrap()).unwrap();
+		let attributes = &class.methods[0].attributes;
+		let types = |annotations: &[RuntimeAnnotation]| {
+			annotations
+				.iter()
+				.map(|annotation| annotation.ty.data.to_string())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(types(attributes.runtime_visible_annotations().unwrap()), ["Lp/Kept;"]);
+		assert_eq!(
+			types(attributes.runtime_invisible_annotations().unwrap()),
+			["Lp/Other;"]
+		);
+		let tables = attributes.iter().map(|attr| attr.attr.name()).collect::<Vec<_>>();
+		assert_eq!(
+			tables,
+			[
+				"RuntimeInvisibleAnnotations",
+				"RuntimeVisibleAnnotations",
+				"RuntimeVisibleParameterAnnotations"
+			]
+		);
+		assert!(check_retention(&class, &classes).unwrap().is_empty());
+	}
+}
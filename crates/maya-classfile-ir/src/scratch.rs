@@ -0,0 +1,40 @@
+// Scratch directories for the tests that go through the filesystem. Each is named after its test and the process, so
+// tests running at the same time don't share one, and removed when dropped, also when the test fails.
+
+use std::{
+	fs,
+	ops::Deref,
+	path::{Path, PathBuf},
+};
+
+pub(crate) struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+	/// An empty directory, whatever a run before left behind.
+	pub(crate) fn new(name: &str) -> Self {
+		let dir = std::env::temp_dir().join(format!("maya-{name}-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		Self(dir)
+	}
+}
+
+impl Deref for ScratchDir {
+	type Target = Path;
+
+	fn deref(&self) -> &Path {
+		&self.0
+	}
+}
+
+impl AsRef<Path> for ScratchDir {
+	fn as_ref(&self) -> &Path {
+		&self.0
+	}
+}
+
+impl Drop for ScratchDir {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.0);
+	}
+}
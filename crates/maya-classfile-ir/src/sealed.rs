@@ -0,0 +1,114 @@
+// Sealed classes and interfaces, since Java 17: PermittedSubclasses lists the only classes allowed to extend or
+// implement them directly. The JVM checks it from the other end, refusing to load a subclass the sealed class doesn't
+// permit, so a permitted subclass that doesn't actually extend the class goes unnoticed until something is compiled
+// against the hierarchy.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.31
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-5.html#jvms-5.3.5
+
+use std::rc::Rc;
+
+use crate::{classpath::ClasspathError, IRClassFile};
+
+impl IRClassFile {
+	/// Whether the class has a PermittedSubclasses attribute.
+	pub fn is_sealed(&self) -> bool {
+		self.attributes.permitted_subclasses().is_some()
+	}
+
+	/// The classes allowed to extend or implement this one, empty unless it's sealed.
+	pub fn permitted_subclasses(&self) -> impl Iterator<Item = &str> {
+		self.attributes
+			.permitted_subclasses()
+			.unwrap_or_default()
+			.iter()
+			.map(|class| class.data.data.as_str())
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SealedViolation {
+	/// The sealed class is also final, which the JVM rejects.
+	Final,
+	/// A permitted subclass the resolver can't find.
+	Missing { subclass: String },
+	/// A permitted subclass that neither extends nor implements the sealed class directly.
+	NotDirectSubclass { subclass: String },
+}
+
+/// Checks that each permitted subclass of `class` extends or implements it. Empty if `class` isn't sealed.
+pub fn check_sealed<R>(class: &IRClassFile, resolve: R) -> Result<Vec<SealedViolation>, ClasspathError>
+where
+	R: Fn(&str) -> Result<Option<Rc<IRClassFile>>, ClasspathError>,
+{
+	let mut violations = Vec::new();
+	if class.is_sealed() && class.access_flags.is_final() {
+		violations.push(SealedViolation::Final);
+	}
+	let name = class.class_name();
+	for subclass in class.permitted_subclasses() {
+		let Some(resolved) = resolve(subclass)? else {
+			violations.push(SealedViolation::Missing {
+				subclass: subclass.to_string(),
+			});
+			continue;
+		};
+		let direct = match class.access_flags.is_interface() {
+			true => resolved.interface_names().any(|interface| interface == name),
+			false => resolved.super_name() == Some(name),
+		};
+		if !direct {
+			violations.push(SealedViolation::NotDirectSubclass {
+				subclass: subclass.to_string(),
+			});
+		}
+	}
+	Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+
+	use super::*;
+	use crate::{attribute::IRAttribute, builder::ClassBuilder, class_pool::CPClassRef, flags::ClassAccessFlags};
+
+	#[test]
+	fn check_permitted_subclasses() {
+		let mut builder = ClassBuilder::new("p/Shape").unwrap();
+		builder.access_flags(ClassAccessFlags::PUBLIC | ClassAccessFlags::ABSTRACT | ClassAccessFlags::SUPER);
+		let classes = ["p/Circle", "p/Square", "p/Triangle"]
+			.into_iter()
+			.map(|name| CPClassRef::find_or_add(builder.cp(), name))
+			.collect::<Result<_, _>>()
+			.unwrap();
+		builder.attribute(IRAttribute::PermittedSubclasses { classes }).unwrap();
+		let shape = builder.build();
+
+		let mut circle = ClassBuilder::new("p/Circle").unwrap();
+		circle.super_class(Some("p/Shape")).unwrap();
+		// Permitted, but extends Object.
+		let square = ClassBuilder::new("p/Square").unwrap();
+		let classes = BTreeMap::from([
+			("p/Circle", Rc::new(circle.build())),
+			("p/Square", Rc::new(square.build())),
+		]);
+		let resolve = |name: &str| Ok(classes.get(name).cloned());
+
+		assert!(shape.is_sealed());
+		assert_eq!(shape.permitted_subclasses().count(), 3);
+		assert_eq!(
+			check_sealed(&shape, resolve).unwrap(),
+			[
+				SealedViolation::NotDirectSubclass {
+					subclass: "p/Square".to_string()
+				},
+				SealedViolation::Missing {
+					subclass: "p/Triangle".to_string()
+				},
+			]
+		);
+		let circle = &classes["p/Circle"];
+		assert!(!circle.is_sealed());
+		assert!(check_sealed(circle, resolve).unwrap().is_empty());
+	}
+}
@@ -0,0 +1,196 @@
+// Finding instructions by what they refer to, the way an IDE's "find usages" does it over bytecode: calls of a method,
+// reads and writes of a field, `new` of a class and string constants. Members are matched by the class the
+// instruction names, which for an inherited member may be a subclass of the one declaring it. Code that can't be
+// decoded has no hits.
+
+use std::fmt::{self, Display};
+
+use crate::{
+	class_pool::{CPClassRef, CPNameAndTypeRef, IRCpTag},
+	code::Instructions,
+	jar::{Jar, JarError},
+	query::MemberRef,
+	IRClassFile,
+};
+
+/// A field or method by owner and name, of any descriptor unless one is given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberPattern {
+	pub owner: String,
+	pub name: String,
+	pub descriptor: Option<String>,
+}
+
+impl MemberPattern {
+	pub fn new(owner: &str, name: &str, descriptor: Option<&str>) -> Self {
+		Self {
+			owner: owner.to_string(),
+			name: name.to_string(),
+			descriptor: descriptor.map(str::to_string),
+		}
+	}
+
+	fn matches(&self, class: &CPClassRef, name_and_ty: &CPNameAndTypeRef) -> bool {
+		*class.data.data == self.owner
+			&& *name_and_ty.name.data == self.name
+			&& self
+				.descriptor
+				.as_ref()
+				.is_none_or(|descriptor| *name_and_ty.ty.data == *descriptor)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+	/// Any invoke instruction but invokedynamic.
+	Calls(MemberPattern),
+	/// getfield and getstatic.
+	Reads(MemberPattern),
+	/// putfield and putstatic.
+	Writes(MemberPattern),
+	/// `new` of the class with this internal name.
+	New(String),
+	/// String constants loaded by ldc, matched against a pattern where `*` stands for any run of characters and `?`
+	/// for any one.
+	Strings(String),
+}
+
+impl Query {
+	fn matches(&self, instruction: &Instructions) -> bool {
+		match (self, instruction) {
+			(
+				Self::Calls(pattern),
+				Instructions::INVOKEVIRTUAL(method)
+				| Instructions::INVOKESPECIAL(method)
+				| Instructions::INVOKESTATIC(method),
+			) => pattern.matches(&method.class, &method.name_and_ty),
+			(Self::Calls(pattern), Instructions::INVOKEINTERFACE { method, .. }) => {
+				pattern.matches(&method.class, &method.name_and_ty)
+			}
+			(Self::Reads(pattern), Instructions::GETFIELD(field) | Instructions::GETSTATIC(field))
+			| (Self::Writes(pattern), Instructions::PUTFIELD(field) | Instructions::PUTSTATIC(field)) => {
+				pattern.matches(&field.class, &field.name_and_ty)
+			}
+			(Self::New(class), Instructions::NEW(new)) => *new.data.data == *class,
+			(Self::Strings(pattern), Instructions::LDC(IRCpTag::String(string))) => {
+				glob_matches(pattern.as_bytes(), string.data.as_bytes())
+			}
+			_ => false,
+		}
+	}
+}
+
+/// An instruction found, by the method it's in and its offset in the method's code.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hit {
+	pub method: MemberRef,
+	pub offset: u32,
+}
+
+impl Display for Hit {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} @ {}", self.method, self.offset)
+	}
+}
+
+impl IRClassFile {
+	/// The instructions matching the query, in method and code order.
+	pub fn search(&self, query: &Query) -> Vec<Hit> {
+		let mut hits = Vec::new();
+		for method in &self.methods {
+			let Some(Ok(instructions)) = method.code().map(|code| Instructions::read_all(&self.cp, &code.code)) else {
+				continue;
+			};
+			let found = instructions
+				.iter()
+				.filter(|(_, instruction)| query.matches(instruction));
+			hits.extend(found.map(|(offset, _)| Hit {
+				method: MemberRef::new(self.class_name(), method.name(), method.descriptor()),
+				offset: *offset,
+			}));
+		}
+		hits
+	}
+}
+
+impl Jar {
+	/// The instructions matching the query in every class of the jar, in archive order.
+	pub fn search(&self, query: &Query) -> Result<Vec<Hit>, JarError> {
+		let mut hits = Vec::new();
+		for class in self.classes() {
+			hits.extend(class?.search(query));
+		}
+		Ok(hits)
+	}
+}
+
+pub(crate) fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+	match pattern.split_first() {
+		None => text.is_empty(),
+		Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_matches(rest, &text[skip..])),
+		Some((b'?', rest)) => !text.is_empty() && glob_matches(rest, &text[1..]),
+		Some((c, rest)) => text.first() == Some(c) && glob_matches(rest, &text[1..]),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn find_usages() {
+		let class = assemble(
+			r#"
+.class public super p/Main
+.method public run()V
+	new p/Box
+	dup
+	ldc "jdbc:postgres://localhost"
+	invokespecial p/Box/<init>(Ljava/lang/String;)V
+	getstatic p/Config/debug Z
+	pop
+	aload_0
+	iconst_1
+	putfield p/Main/count I
+	ldc "hello"
+	invokestatic p/Log/info(Ljava/lang/String;)V
+	return
+.end method
+.method public stop()V
+	ldc "jdbc:h2:mem"
+	invokestatic p/Log/info(Ljava/lang/String;)V
+	return
+.end method
+"#,
+		)
+		.unwrap();
+		let offsets = |query: Query| {
+			let hits = class.search(&query);
+			hits.iter()
+				.map(|hit| format!("{}.{}", hit.method.name, hit.offset))
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(
+			offsets(Query::Calls(MemberPattern::new("p/Log", "info", None))),
+			["run.20", "stop.2"]
+		);
+		assert!(offsets(Query::Calls(MemberPattern::new("p/Log", "info", Some("()V")))).is_empty());
+		assert_eq!(
+			offsets(Query::Reads(MemberPattern::new("p/Config", "debug", None))),
+			["run.9"]
+		);
+		assert_eq!(
+			offsets(Query::Writes(MemberPattern::new("p/Main", "count", Some("I")))),
+			["run.15"]
+		);
+		assert!(offsets(Query::Reads(MemberPattern::new("p/Main", "count", None))).is_empty());
+		assert_eq!(offsets(Query::New("p/Box".to_string())), ["run.0"]);
+		assert_eq!(offsets(Query::Strings("jdbc:*".to_string())), ["run.4", "stop.0"]);
+		assert_eq!(
+			class.search(&Query::Strings("h?llo".to_string()))[0].to_string(),
+			"p/Main.run()V @ 18"
+		);
+	}
+}
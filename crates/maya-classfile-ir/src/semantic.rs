@@ -0,0 +1,484 @@
+// Classes reduced to what they mean, to tell whether two class files are the same class however they were written.
+// Pool indices are resolved to what they stand for, and instructions read back the same whichever encoding they had
+// (`ldc` or `ldc_w`, `iload_1` or `iload 1`, with or without `wide`). Pcs become positions in the instruction list,
+// so the tables pointing into the code don't change with the encoding either, and stack map frames are compared as
+// the states they describe rather than how they were compressed.
+
+use std::collections::BTreeMap;
+
+use crate::{
+	attribute::{Attributes, CodeAttribute, IRAttribute, VerificationType},
+	class_pool::{CPClassRef, IRClassfileError},
+	code::{Instructions, Opcodes},
+	disasm::{array_type, constant},
+	flags::ClassAccessFlags,
+	text::AttributeResolver,
+	ClassFileVersion, IRClassFile, IRMethodInfo,
+};
+
+// The resolved text of every attribute by name, the ones of a name in class file order.
+pub(crate) type AttributeTexts = BTreeMap<String, Vec<String>>;
+
+/// A class in a form that's the same for any two class files meaning the same class.
+#[derive(Debug, Clone)]
+pub struct CanonicalClass {
+	pub(crate) version: ClassFileVersion,
+	pub(crate) access_flags: ClassAccessFlags,
+	pub(crate) name: String,
+	pub(crate) super_class: Option<String>,
+	// in class file order, which is as far as their order matters
+	pub(crate) interfaces: Vec<String>,
+	pub(crate) attributes: AttributeTexts,
+	pub(crate) fields: Vec<CanonicalMember>,
+	pub(crate) methods: Vec<CanonicalMember>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanonicalMember {
+	/// The name and descriptor, e.g. `run()V`.
+	pub id: String,
+	pub(crate) access_flags: u16,
+	// without `Code`, which is in `code`
+	pub(crate) attributes: AttributeTexts,
+	pub(crate) code: Option<CanonicalCode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CanonicalCode {
+	pub(crate) max_stack: u16,
+	pub(crate) max_locals: u16,
+	pub(crate) instructions: Vec<String>,
+	pub(crate) exception_table: Vec<String>,
+	pub(crate) attributes: AttributeTexts,
+}
+
+impl CanonicalClass {
+	pub fn new(class: &IRClassFile) -> Result<Self, IRClassfileError> {
+		let mut canonicalizer = Canonicalizer {
+			class,
+			resolver: AttributeResolver::new(&class.to_bytes()?)?,
+		};
+		let fields = class.fields.iter().map(|field| {
+			canonicalizer.member(
+				field.name(),
+				field.descriptor(),
+				field.access_flags.bits(),
+				&field.attributes,
+				None,
+			)
+		});
+		let fields = fields.collect::<Result<_, _>>()?;
+		let methods = class.methods.iter().map(|method| {
+			let flags = method.access_flags.bits();
+			canonicalizer.member(
+				method.name(),
+				method.descriptor(),
+				flags,
+				&method.attributes,
+				Some(method),
+			)
+		});
+		let methods = methods.collect::<Result<_, _>>()?;
+		Ok(Self {
+			version: class.version,
+			access_flags: class.access_flags,
+			name: class.this_class.data.data.to_string(),
+			super_class: class.super_class.as_ref().map(|class| class.data.data.to_string()),
+			interfaces: class
+				.interfaces
+				.iter()
+				.map(|class| class.data.data.to_string())
+				.collect(),
+			attributes: canonicalizer.attributes(&class.attributes)?,
+			fields,
+			methods,
+		})
+	}
+
+	pub fn field(&self, id: &str) -> Option<&CanonicalMember> {
+		self.fields.iter().find(|field| field.id == id)
+	}
+
+	pub fn method(&self, id: &str) -> Option<&CanonicalMember> {
+		self.methods.iter().find(|method| method.id == id)
+	}
+
+	/// A hash of the class that stays the same across runs, platforms and versions of this crate as long as the
+	/// canonical form does. The order of members and interfaces doesn't go into it.
+	pub fn content_hash(&self) -> u64 {
+		let mut hasher = StableHasher::default();
+		hasher.text(&self.version.to_string());
+		hasher.number(self.access_flags.bits() as u64);
+		hasher.text(&self.name);
+		hasher.text(self.super_class.as_deref().unwrap_or_default());
+		let mut interfaces = self.interfaces.iter().collect::<Vec<_>>();
+		interfaces.sort();
+		hasher.texts(interfaces);
+		hasher.attributes(&self.attributes);
+		for members in [&self.fields, &self.methods] {
+			let mut hashes = members.iter().map(CanonicalMember::content_hash).collect::<Vec<_>>();
+			hashes.sort_unstable();
+			hasher.number(hashes.len() as u64);
+			hashes.into_iter().for_each(|hash| hasher.number(hash));
+		}
+		hasher.finish()
+	}
+}
+
+impl CanonicalMember {
+	/// A hash of the member, see `CanonicalClass::content_hash`.
+	pub fn content_hash(&self) -> u64 {
+		let mut hasher = StableHasher::default();
+		hasher.text(&self.id);
+		hasher.number(self.access_flags as u64);
+		hasher.attributes(&self.attributes);
+		match &self.code {
+			None => hasher.number(0),
+			Some(code) => {
+				hasher.number(1);
+				hasher.number(code.max_stack as u64);
+				hasher.number(code.max_locals as u64);
+				hasher.texts(&code.instructions);
+				hasher.texts(&code.exception_table);
+				hasher.attributes(&code.attributes);
+			}
+		}
+		hasher.finish()
+	}
+}
+
+impl IRClassFile {
+	/// Whether `other` is the same class, however differently the two were written. See `diff::diff` for what differs
+	/// when they aren't.
+	pub fn semantic_eq(&self, other: &IRClassFile) -> Result<bool, IRClassfileError> {
+		Ok(self.diff(other)?.is_empty())
+	}
+
+	/// See `CanonicalClass::content_hash`.
+	pub fn content_hash(&self) -> Result<u64, IRClassfileError> {
+		Ok(CanonicalClass::new(self)?.content_hash())
+	}
+}
+
+// 64-bit FNV-1a, fed with length-prefixed values so that no two sequences of them run together the same way.
+struct StableHasher(u64);
+
+impl Default for StableHasher {
+	fn default() -> Self {
+		Self(0xcbf2_9ce4_8422_2325)
+	}
+}
+
+impl StableHasher {
+	fn bytes(&mut self, bytes: &[u8]) {
+		for byte in bytes {
+			self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+		}
+	}
+
+	fn number(&mut self, value: u64) {
+		self.bytes(&value.to_le_bytes());
+	}
+
+	fn text(&mut self, text: &str) {
+		self.number(text.len() as u64);
+		self.bytes(text.as_bytes());
+	}
+
+	fn texts<S: AsRef<str>>(&mut self, texts: impl IntoIterator<Item = S, IntoIter: ExactSizeIterator>) {
+		let texts = texts.into_iter();
+		self.number(texts.len() as u64);
+		texts.for_each(|text| self.text(text.as_ref()));
+	}
+
+	fn attributes(&mut self, attributes: &AttributeTexts) {
+		self.number(attributes.len() as u64);
+		for (name, texts) in attributes {
+			self.text(name);
+			self.texts(texts);
+		}
+	}
+
+	fn finish(&self) -> u64 {
+		self.0
+	}
+}
+
+struct Canonicalizer<'a> {
+	class: &'a IRClassFile,
+	resolver: AttributeResolver,
+}
+
+impl Canonicalizer<'_> {
+	fn member(
+		&mut self,
+		name: &str,
+		descriptor: &str,
+		access_flags: u16,
+		attributes: &Attributes,
+		method: Option<&IRMethodInfo>,
+	) -> Result<CanonicalMember, IRClassfileError> {
+		let code = match method.and_then(IRMethodInfo::code) {
+			Some(code) => Some(self.code(method.expect("has code"), code)?),
+			None => None,
+		};
+		let attributes = attributes
+			.iter()
+			.filter(|attr| !matches!(attr.attr, IRAttribute::Code(_)))
+			.cloned();
+		Ok(CanonicalMember {
+			id: format!("{name}{descriptor}"),
+			access_flags,
+			attributes: self.attributes(&attributes.collect())?,
+			code,
+		})
+	}
+
+	fn attributes(&mut self, attributes: &Attributes) -> Result<AttributeTexts, IRClassfileError> {
+		let mut texts = AttributeTexts::new();
+		for attr in attributes.iter() {
+			let name = attr.name.data.to_string();
+			let text = self.resolver.text(&name, &attr.to_io()?.info);
+			texts.entry(name).or_default().push(text);
+		}
+		Ok(texts)
+	}
+
+	fn code(&mut self, method: &IRMethodInfo, code: &CodeAttribute) -> Result<CanonicalCode, IRClassfileError> {
+		let cp = &self.class.cp;
+		let instructions = Instructions::read_all(cp, &code.code)?;
+		let positions = Positions {
+			pcs: instructions
+				.iter()
+				.enumerate()
+				.map(|(index, (pc, _))| (*pc, index))
+				.collect(),
+			end: code.code.len() as u32,
+		};
+		let mut listing = Vec::with_capacity(instructions.len());
+		for (index, (pc, instruction)) in instructions.iter().enumerate() {
+			let mut text = Opcodes::mnemonic(instruction.opcode()).unwrap_or_default().to_string();
+			if let Some(operand) = self.operand(instruction)? {
+				text = format!("{text} {operand}");
+			}
+			for target in instruction.branch_targets(*pc) {
+				match positions.pcs.get(&target) {
+					Some(target) => text = format!("{text} {:+}", *target as i64 - index as i64),
+					None => text = format!("{text} pc {target}"),
+				}
+			}
+			listing.push(text);
+		}
+		let handlers = code.exception_table.iter().map(|handler| {
+			let catch_type = match handler.catch_type {
+				0 => "any".to_string(),
+				index => CPClassRef::from_cp(cp, index)?.data.to_string(),
+			};
+			Ok(format!(
+				"{} {} {} {catch_type}",
+				positions.of(handler.start_pc as u32),
+				positions.of(handler.end_pc as u32),
+				positions.of(handler.handler_pc as u32),
+			))
+		});
+		let exception_table = handlers.collect::<Result<_, IRClassfileError>>()?;
+
+		let mut attributes = AttributeTexts::new();
+		for attr in code.attributes.iter() {
+			let name = attr.name.data.to_string();
+			let text = match self.code_attribute(method, &attr.attr, &positions) {
+				Some(text) => text,
+				None => self.resolver.text(&name, &attr.to_io()?.info),
+			};
+			attributes.entry(name).or_default().push(text);
+		}
+		Ok(CanonicalCode {
+			max_stack: code.max_stack,
+			max_locals: code.max_locals,
+			instructions: listing,
+			exception_table,
+			attributes,
+		})
+	}
+
+	// The tables that point into the code, by instruction position. `None` for the rest.
+	fn code_attribute(&self, method: &IRMethodInfo, attr: &IRAttribute, positions: &Positions) -> Option<String> {
+		let lines: Vec<String> = match attr {
+			IRAttribute::LineNumberTable(table) => table
+				.line_number_table
+				.iter()
+				.map(|entry| format!("{} {}", positions.of(entry.start_pc as u32), entry.line_number))
+				.collect(),
+			IRAttribute::LocalVariableTable { table } => table
+				.iter()
+				.map(|entry| {
+					let (start, end) = positions.range(entry.start_pc, entry.length);
+					format!("{start} {end} {} {} {}", entry.index, entry.name, entry.descriptor)
+				})
+				.collect(),
+			IRAttribute::LocalVariableTypeTable { table } => table
+				.iter()
+				.map(|entry| {
+					let (start, end) = positions.range(entry.start_pc, entry.length);
+					format!("{start} {end} {} {} {}", entry.index, entry.name, entry.signature)
+				})
+				.collect(),
+			IRAttribute::StackMapTable(table) => {
+				let frames = table
+					.resolve(&self.class.cp, &self.class.this_class.data.data, method)
+					.ok()?;
+				let types = |types: &[VerificationType]| {
+					let types = types.iter().map(|ty| match ty {
+						VerificationType::Object(name) => name.clone(),
+						VerificationType::Uninitialized(pc) => format!("uninitialized {}", positions.of(*pc)),
+						ty => format!("{ty:?}"),
+					});
+					types.collect::<Vec<_>>().join(", ")
+				};
+				let frames = frames.iter().map(|frame| {
+					let (locals, stack) = (types(&frame.locals), types(&frame.stack));
+					format!("{} locals [{locals}] stack [{stack}]", positions.of(frame.pc))
+				});
+				frames.collect()
+			}
+			_ => return None,
+		};
+		Some(lines.join("\n"))
+	}
+
+	// Everything but branch targets, with pool references resolved.
+	fn operand(&self, instruction: &Instructions) -> Result<Option<String>, IRClassfileError> {
+		let cp = &self.class.cp;
+		Ok(Some(match instruction {
+			Instructions::BIPUSH(value) => value.to_string(),
+			Instructions::SIPUSH(value) => value.to_string(),
+			Instructions::LDC(tag) => constant(cp, tag).unwrap_or_else(|| tag.kind_name().to_string()),
+			Instructions::ILOAD(index)
+			| Instructions::LLOAD(index)
+			| Instructions::FLOAD(index)
+			| Instructions::DLOAD(index)
+			| Instructions::ALOAD(index)
+			| Instructions::ISTORE(index)
+			| Instructions::LSTORE(index)
+			| Instructions::FSTORE(index)
+			| Instructions::DSTORE(index)
+			| Instructions::ASTORE(index)
+			| Instructions::RET(index) => index.to_string(),
+			Instructions::IINC { index, r#const } => format!("{index} {const}", r#const = r#const),
+			Instructions::TABLESWITCH { low, .. } => format!("low {low}"),
+			Instructions::LOOKUPSWITCH { pairs, .. } => {
+				let keys = pairs.iter().map(|(key, _)| key.to_string()).collect::<Vec<_>>();
+				format!("keys {}", keys.join(" "))
+			}
+			Instructions::GETSTATIC(field)
+			| Instructions::PUTSTATIC(field)
+			| Instructions::GETFIELD(field)
+			| Instructions::PUTFIELD(field) => field.to_string(),
+			Instructions::INVOKEVIRTUAL(method)
+			| Instructions::INVOKESPECIAL(method)
+			| Instructions::INVOKESTATIC(method) => method.to_string(),
+			Instructions::INVOKEINTERFACE { method, count } => format!("{method} {count}"),
+			Instructions::INVOKEDYNAMIC(indy) => {
+				let bootstrap = self.class.bootstrap_method(indy)?;
+				let handle = &bootstrap.method;
+				let handle = format!(
+					"{} {}",
+					handle.ref_kind,
+					handle.ref_tag.resolved(cp).unwrap_or_default()
+				);
+				let mut text = format!("{} {handle}", indy.name_and_ty);
+				for argument in &bootstrap.arguments {
+					let argument = constant(cp, &argument.tag).unwrap_or_else(|| argument.tag.kind_name().to_string());
+					text = format!("{text}, {argument}");
+				}
+				text
+			}
+			Instructions::NEW(class)
+			| Instructions::ANEWARRAY(class)
+			| Instructions::CHECKCAST(class)
+			| Instructions::INSTANCEOF(class) => class.to_string(),
+			Instructions::NEWARRAY(atype) => array_type(*atype).to_string(),
+			Instructions::MULTIANEWARRAY { class, dimensions } => format!("{class} {dimensions}"),
+			_ => return Ok(None),
+		}))
+	}
+}
+
+// Instruction positions by pc.
+struct Positions {
+	pcs: BTreeMap<u32, usize>,
+	// the end of the code, which ranges can end at
+	end: u32,
+}
+
+impl Positions {
+	fn of(&self, pc: u32) -> String {
+		match self.pcs.get(&pc) {
+			Some(index) => index.to_string(),
+			None if pc == self.end => self.pcs.len().to_string(),
+			None => format!("pc {pc}"),
+		}
+	}
+
+	fn range(&self, start: u16, length: u16) -> (String, String) {
+		(self.of(start as u32), self.of(start as u32 + length as u32))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{asm::assemble, flags::MethodAccessFlags, text};
+
+	#[test]
+	fn equal_across_encodings() {
+		let class = assemble(
+			r#"
+			.class public super p/Greeter
+			.field private name Ljava/lang/String;
+			.method public greet(I)Ljava/lang/String;
+				iload_1
+				ifeq Anonymous
+				ldc "hello"
+				areturn
+			Anonymous:
+				aconst_null
+				areturn
+			.end method
+			"#,
+		)
+		.unwrap();
+		// the same with the long form of each instruction, which moves every pc after them
+		let short_forms = "code_length 9
+          0: iload_1
+          1: ifeq 7
+          4: ldc #10  ; \"hello\"
+          6: areturn
+          7: aconst_null
+          8: areturn";
+		let long_forms = "code_length 13
+          0: wide iload 1
+          4: ifeq 11
+          7: ldc_w #10
+          10: areturn
+          11: aconst_null
+          12: areturn";
+		let text = text::to_text(&class).unwrap();
+		assert!(text.contains(short_forms));
+		let long = text::from_text(&text.replace(short_forms, long_forms)).unwrap();
+
+		assert!(class.semantic_eq(&long).unwrap());
+		assert_eq!(class.content_hash().unwrap(), long.content_hash().unwrap());
+		let (canonical, long_canonical) = (
+			CanonicalClass::new(&class).unwrap(),
+			CanonicalClass::new(&long).unwrap(),
+		);
+		let greet = canonical.method("greet(I)Ljava/lang/String;").unwrap();
+		assert_eq!(Some(greet), long_canonical.method("greet(I)Ljava/lang/String;"));
+
+		let mut changed = class.clone();
+		changed.methods[0].access_flags |= MethodAccessFlags::FINAL;
+		assert!(!class.semantic_eq(&changed).unwrap());
+		assert_ne!(class.content_hash().unwrap(), changed.content_hash().unwrap());
+	}
+}
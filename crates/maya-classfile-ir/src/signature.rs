@@ -0,0 +1,530 @@
+// Generic signatures, the grammar is at
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.9.1
+
+use std::fmt::{self, Display};
+
+use crate::{class_pool::IRClassfileError, descriptor::BaseType, names};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum JavaTypeSignature {
+	Base(BaseType),
+	Reference(ReferenceTypeSignature),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReferenceTypeSignature {
+	Class(ClassTypeSignature),
+	TypeVariable(String),
+	Array(Box<JavaTypeSignature>),
+}
+
+/// `Ljava/util/Map<TK;TV;>.Entry<TK;TV;>;` is `name: java/util/Map` with one inner `Entry`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassTypeSignature {
+	// internal name of the outermost class
+	pub name: String,
+	pub args: Vec<TypeArgument>,
+	pub inner: Vec<SimpleClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimpleClassTypeSignature {
+	pub name: String,
+	pub args: Vec<TypeArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeArgument {
+	// *
+	Any,
+	Exact(ReferenceTypeSignature),
+	// ? extends T
+	Extends(ReferenceTypeSignature),
+	// ? super T
+	Super(ReferenceTypeSignature),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeParameter {
+	pub name: String,
+	// Empty for `T::Ljava/lang/Comparable;`, where the bound is an interface.
+	pub class_bound: Option<ReferenceTypeSignature>,
+	pub interface_bounds: Vec<ReferenceTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassSignature {
+	pub type_params: Vec<TypeParameter>,
+	pub superclass: ClassTypeSignature,
+	pub interfaces: Vec<ClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodSignature {
+	pub type_params: Vec<TypeParameter>,
+	pub params: Vec<JavaTypeSignature>,
+	// None for void
+	pub ret: Option<JavaTypeSignature>,
+	// Only class types and type variables.
+	pub throws: Vec<ReferenceTypeSignature>,
+}
+
+pub type FieldSignature = ReferenceTypeSignature;
+
+struct Parser<'a> {
+	input: &'a str,
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn new(input: &'a str) -> Self {
+		Self { input, pos: 0 }
+	}
+
+	fn error(&self) -> IRClassfileError {
+		IRClassfileError::InvalidSignature(self.input.to_string())
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.input.as_bytes().get(self.pos).copied()
+	}
+
+	fn eat(&mut self, c: u8) -> bool {
+		let matched = self.peek() == Some(c);
+		if matched {
+			self.pos += 1;
+		}
+		matched
+	}
+
+	fn expect(&mut self, c: u8) -> Result<(), IRClassfileError> {
+		match self.eat(c) {
+			true => Ok(()),
+			false => Err(self.error()),
+		}
+	}
+
+	fn finish<T>(self, value: T) -> Result<T, IRClassfileError> {
+		match self.pos == self.input.len() {
+			true => Ok(value),
+			false => Err(self.error()),
+		}
+	}
+
+	fn identifier(&mut self) -> Result<String, IRClassfileError> {
+		let start = self.pos;
+		while let Some(c) = self.peek() {
+			if matches!(c, b'.' | b';' | b'[' | b'/' | b'<' | b'>' | b':') {
+				break;
+			}
+			self.pos += 1;
+		}
+
+		match start == self.pos {
+			true => Err(self.error()),
+			false => Ok(self.input[start..self.pos].to_string()),
+		}
+	}
+
+	fn type_params(&mut self) -> Result<Vec<TypeParameter>, IRClassfileError> {
+		let mut params = Vec::new();
+		if !self.eat(b'<') {
+			return Ok(params);
+		}
+
+		while !self.eat(b'>') {
+			let name = self.identifier()?;
+			self.expect(b':')?;
+			let class_bound = match self.peek() {
+				Some(b'L' | b'T' | b'[') => Some(self.reference()?),
+				_ => None,
+			};
+
+			let mut interface_bounds = Vec::new();
+			while self.eat(b':') {
+				interface_bounds.push(self.reference()?);
+			}
+
+			params.push(TypeParameter {
+				name,
+				class_bound,
+				interface_bounds,
+			});
+		}
+
+		match params.is_empty() {
+			true => Err(self.error()),
+			false => Ok(params),
+		}
+	}
+
+	fn type_args(&mut self) -> Result<Vec<TypeArgument>, IRClassfileError> {
+		let mut args = Vec::new();
+		if !self.eat(b'<') {
+			return Ok(args);
+		}
+
+		while !self.eat(b'>') {
+			args.push(match self.peek() {
+				Some(b'*') => {
+					self.pos += 1;
+					TypeArgument::Any
+				}
+				Some(b'+') => {
+					self.pos += 1;
+					TypeArgument::Extends(self.reference()?)
+				}
+				Some(b'-') => {
+					self.pos += 1;
+					TypeArgument::Super(self.reference()?)
+				}
+				_ => TypeArgument::Exact(self.reference()?),
+			});
+		}
+
+		match args.is_empty() {
+			true => Err(self.error()),
+			false => Ok(args),
+		}
+	}
+
+	fn class_type(&mut self) -> Result<ClassTypeSignature, IRClassfileError> {
+		self.expect(b'L')?;
+		let mut name = self.identifier()?;
+		while self.eat(b'/') {
+			name.push('/');
+			name.push_str(&self.identifier()?);
+		}
+
+		let args = self.type_args()?;
+		let mut inner = Vec::new();
+		while self.eat(b'.') {
+			inner.push(SimpleClassTypeSignature {
+				name: self.identifier()?,
+				args: self.type_args()?,
+			});
+		}
+		self.expect(b';')?;
+
+		Ok(ClassTypeSignature { name, args, inner })
+	}
+
+	fn reference(&mut self) -> Result<ReferenceTypeSignature, IRClassfileError> {
+		Ok(match self.peek() {
+			Some(b'L') => ReferenceTypeSignature::Class(self.class_type()?),
+			Some(b'T') => {
+				self.pos += 1;
+				let name = self.identifier()?;
+				self.expect(b';')?;
+				ReferenceTypeSignature::TypeVariable(name)
+			}
+			Some(b'[') => {
+				self.pos += 1;
+				ReferenceTypeSignature::Array(Box::new(self.java_type()?))
+			}
+			_ => return Err(self.error()),
+		})
+	}
+
+	fn java_type(&mut self) -> Result<JavaTypeSignature, IRClassfileError> {
+		match self.peek().and_then(|c| BaseType::from_char(c as char)) {
+			Some(base) => {
+				self.pos += 1;
+				Ok(JavaTypeSignature::Base(base))
+			}
+			None => Ok(JavaTypeSignature::Reference(self.reference()?)),
+		}
+	}
+}
+
+impl ClassSignature {
+	pub fn parse(signature: &str) -> Result<Self, IRClassfileError> {
+		let mut parser = Parser::new(signature);
+		let type_params = parser.type_params()?;
+		let superclass = parser.class_type()?;
+		let mut interfaces = Vec::new();
+		while parser.peek().is_some() {
+			interfaces.push(parser.class_type()?);
+		}
+
+		parser.finish(Self {
+			type_params,
+			superclass,
+			interfaces,
+		})
+	}
+}
+
+impl MethodSignature {
+	pub fn parse(signature: &str) -> Result<Self, IRClassfileError> {
+		let mut parser = Parser::new(signature);
+		let type_params = parser.type_params()?;
+
+		parser.expect(b'(')?;
+		let mut params = Vec::new();
+		while !parser.eat(b')') {
+			params.push(parser.java_type()?);
+		}
+
+		let ret = match parser.eat(b'V') {
+			true => None,
+			false => Some(parser.java_type()?),
+		};
+
+		let mut throws = Vec::new();
+		while parser.eat(b'^') {
+			match parser.reference()? {
+				ReferenceTypeSignature::Array(_) => return Err(parser.error()),
+				thrown => throws.push(thrown),
+			}
+		}
+
+		parser.finish(Self {
+			type_params,
+			params,
+			ret,
+			throws,
+		})
+	}
+}
+
+impl ReferenceTypeSignature {
+	/// Parses a FieldSignature.
+	pub fn parse(signature: &str) -> Result<Self, IRClassfileError> {
+		let mut parser = Parser::new(signature);
+		let reference = parser.reference()?;
+		parser.finish(reference)
+	}
+}
+
+fn write_type_params(f: &mut fmt::Formatter<'_>, params: &[TypeParameter]) -> fmt::Result {
+	if params.is_empty() {
+		return Ok(());
+	}
+
+	write!(f, "<")?;
+	for param in params {
+		write!(f, "{}:", param.name)?;
+		if let Some(bound) = &param.class_bound {
+			write!(f, "{bound}")?;
+		}
+		for bound in &param.interface_bounds {
+			write!(f, ":{bound}")?;
+		}
+	}
+	write!(f, ">")
+}
+
+fn write_type_args(f: &mut fmt::Formatter<'_>, args: &[TypeArgument]) -> fmt::Result {
+	if args.is_empty() {
+		return Ok(());
+	}
+
+	write!(f, "<")?;
+	for arg in args {
+		match arg {
+			TypeArgument::Any => write!(f, "*")?,
+			TypeArgument::Exact(ty) => write!(f, "{ty}")?,
+			TypeArgument::Extends(ty) => write!(f, "+{ty}")?,
+			TypeArgument::Super(ty) => write!(f, "-{ty}")?,
+		}
+	}
+	write!(f, ">")
+}
+
+impl Display for JavaTypeSignature {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Base(base) => write!(f, "{}", base.as_char()),
+			Self::Reference(reference) => write!(f, "{reference}"),
+		}
+	}
+}
+
+impl Display for ReferenceTypeSignature {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Class(class) => write!(f, "{class}"),
+			Self::TypeVariable(name) => write!(f, "T{name};"),
+			Self::Array(component) => write!(f, "[{component}"),
+		}
+	}
+}
+
+impl Display for ClassTypeSignature {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "L{}", self.name)?;
+		write_type_args(f, &self.args)?;
+		for inner in &self.inner {
+			write!(f, ".{}", inner.name)?;
+			write_type_args(f, &inner.args)?;
+		}
+		write!(f, ";")
+	}
+}
+
+impl Display for ClassSignature {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write_type_params(f, &self.type_params)?;
+		write!(f, "{}", self.superclass)?;
+		for interface in &self.interfaces {
+			write!(f, "{interface}")?;
+		}
+		Ok(())
+	}
+}
+
+impl Display for MethodSignature {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write_type_params(f, &self.type_params)?;
+		write!(f, "(")?;
+		for param in &self.params {
+			write!(f, "{param}")?;
+		}
+		write!(f, ")")?;
+		match &self.ret {
+			Some(ret) => write!(f, "{ret}")?,
+			None => write!(f, "V")?,
+		}
+		for thrown in &self.throws {
+			write!(f, "^{thrown}")?;
+		}
+		Ok(())
+	}
+}
+
+// The same types spelled the way they appear in Java source, e.g. `java.util.List<? extends T>`.
+
+impl JavaTypeSignature {
+	pub fn to_java(&self) -> String {
+		match self {
+			Self::Base(base) => names::primitive_name(base.as_char()).unwrap_or_default().to_string(),
+			Self::Reference(reference) => reference.to_java(),
+		}
+	}
+}
+
+impl ReferenceTypeSignature {
+	pub fn to_java(&self) -> String {
+		match self {
+			Self::Class(class) => class.to_java(),
+			Self::TypeVariable(name) => name.clone(),
+			Self::Array(component) => format!("{}[]", component.to_java()),
+		}
+	}
+}
+
+impl ClassTypeSignature {
+	pub fn to_java(&self) -> String {
+		let mut out = names::internal_to_binary(&self.name);
+		out.push_str(&type_args_to_java(&self.args));
+		for inner in &self.inner {
+			out.push('.');
+			out.push_str(&inner.name);
+			out.push_str(&type_args_to_java(&inner.args));
+		}
+		out
+	}
+}
+
+impl TypeArgument {
+	pub fn to_java(&self) -> String {
+		match self {
+			Self::Any => "?".to_string(),
+			Self::Exact(ty) => ty.to_java(),
+			Self::Extends(ty) => format!("? extends {}", ty.to_java()),
+			Self::Super(ty) => format!("? super {}", ty.to_java()),
+		}
+	}
+}
+
+impl TypeParameter {
+	/// Leaves out a lone `extends java.lang.Object` like javac does.
+	pub fn to_java(&self) -> String {
+		let bounds: Vec<String> = self
+			.class_bound
+			.iter()
+			.chain(&self.interface_bounds)
+			.map(ReferenceTypeSignature::to_java)
+			.collect();
+		match bounds.as_slice() {
+			[] => self.name.clone(),
+			[only] if only == "java.lang.Object" => self.name.clone(),
+			_ => format!("{} extends {}", self.name, bounds.join(" & ")),
+		}
+	}
+}
+
+/// `<K, V extends Comparable<V>>`, or an empty string when there are no parameters.
+pub fn type_params_to_java(params: &[TypeParameter]) -> String {
+	if params.is_empty() {
+		return String::new();
+	}
+
+	let params: Vec<String> = params.iter().map(TypeParameter::to_java).collect();
+	format!("<{}>", params.join(", "))
+}
+
+fn type_args_to_java(args: &[TypeArgument]) -> String {
+	if args.is_empty() {
+		return String::new();
+	}
+
+	let args: Vec<String> = args.iter().map(TypeArgument::to_java).collect();
+	format!("<{}>", args.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		for sig in [
+			"<K:Ljava/lang/Object;V:Ljava/lang/Object;>Ljava/util/AbstractMap<TK;TV;>;Ljava/util/Map<TK;TV;>;",
+			"<T::Ljava/lang/Comparable<-TT;>;>Ljava/lang/Object;",
+			"Ljava/lang/Enum<Ljava/lang/Thread$State;>;",
+		] {
+			assert_eq!(ClassSignature::parse(sig).unwrap().to_string(), sig);
+		}
+
+		for sig in [
+			"<T:Ljava/lang/Object;>([TT;I)Ljava/util/List<+TT;>;",
+			"(Ljava/util/Map<**>;)V^Ljava/io/IOException;^TE;",
+		] {
+			assert_eq!(MethodSignature::parse(sig).unwrap().to_string(), sig);
+		}
+
+		let sig = "Ljava/util/Map<TK;TV;>.Entry<TK;TV;>;";
+		let ReferenceTypeSignature::Class(class) = ReferenceTypeSignature::parse(sig).unwrap() else {
+			panic!("expected a class type");
+		};
+		assert_eq!(class.name, "java/util/Map");
+		assert_eq!(class.inner[0].name, "Entry");
+		assert_eq!(class.to_string(), sig);
+	}
+
+	#[test]
+	fn invalid() {
+		assert!(ClassSignature::parse("<>Ljava/lang/Object;").is_err());
+		assert!(MethodSignature::parse("()V^[I").is_err());
+		assert!(ReferenceTypeSignature::parse("I").is_err());
+		assert!(ReferenceTypeSignature::parse("Ljava/util/List<>;").is_err());
+		assert!(ReferenceTypeSignature::parse("TT;X").is_err());
+	}
+
+	#[test]
+	fn java_spelling() {
+		let class =
+			ClassSignature::parse("<T::Ljava/lang/Comparable<-TT;>;U:Ljava/lang/Object;>Ljava/lang/Object;").unwrap();
+		assert_eq!(
+			type_params_to_java(&class.type_params),
+			"<T extends java.lang.Comparable<? super T>, U>"
+		);
+
+		let method = MethodSignature::parse("([TT;Ljava/util/Map<**>.Entry<TK;+TV;>;)I").unwrap();
+		let params: Vec<String> = method.params.iter().map(JavaTypeSignature::to_java).collect();
+		assert_eq!(params, ["T[]", "java.util.Map<?, ?>.Entry<K, ? extends V>"]);
+		assert_eq!(method.ret.unwrap().to_java(), "int");
+	}
+}
@@ -0,0 +1,132 @@
+// Rewriting artifacts in place without risking the input. Output is staged in a temp file next to the target and only
+// renamed over it once everything was written, so an error or an interrupted run leaves the original untouched.
+// The temp file lives in the same directory because a rename is only atomic within one file system, and it takes over
+// the permissions of the file it replaces.
+
+use std::{
+	fs::{self, File},
+	io::{self, Write},
+	path::{Path, PathBuf},
+};
+
+/// A file being written in place of `target`. Dropping it without calling [`StagedFile::commit`] discards the output.
+#[derive(Debug)]
+pub struct StagedFile {
+	target: PathBuf,
+	temp: PathBuf,
+	file: Option<File>,
+}
+
+impl StagedFile {
+	pub fn create(target: &Path) -> io::Result<Self> {
+		let name = target
+			.file_name()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "target has no file name"))?;
+		let mut temp_name = std::ffi::OsString::from(".");
+		temp_name.push(name);
+		temp_name.push(format!(".{}.staged", std::process::id()));
+		let temp = target.with_file_name(temp_name);
+
+		let file = File::options().write(true).create_new(true).open(&temp)?;
+		Ok(Self {
+			target: target.to_path_buf(),
+			temp,
+			file: Some(file),
+		})
+	}
+
+	pub fn target(&self) -> &Path {
+		&self.target
+	}
+
+	/// Flushes the output to disk and swaps it in for the target.
+	pub fn commit(mut self) -> io::Result<()> {
+		let file = self.file.take().expect("file is only taken on commit");
+		match fs::metadata(&self.target) {
+			Ok(metadata) => file.set_permissions(metadata.permissions())?,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+			Err(err) => return Err(err),
+		}
+		file.sync_all()?;
+		drop(file);
+		fs::rename(&self.temp, &self.target)
+	}
+
+	fn file(&mut self) -> &mut File {
+		self.file.as_mut().expect("file is only taken on commit")
+	}
+}
+
+impl Write for StagedFile {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.file().write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.file().flush()
+	}
+}
+
+impl Drop for StagedFile {
+	fn drop(&mut self) {
+		// Also runs after a failed rename, in which case the temp file is still there.
+		let _ = fs::remove_file(&self.temp);
+	}
+}
+
+/// Replaces the contents of `target` with what `rewrite` makes of them. If `rewrite` fails for any entry of the
+/// artifact, the target is left exactly as it was.
+pub fn rewrite_in_place<E: From<io::Error>>(
+	target: &Path,
+	rewrite: impl FnOnce(&[u8], &mut StagedFile) -> Result<(), E>,
+) -> Result<(), E> {
+	rewrite_into(target, target, rewrite)
+}
+
+/// Writes what `rewrite` makes of `source` to `target`, which may be the same file. Nothing is written to `target`
+/// unless `rewrite` succeeds.
+pub fn rewrite_into<E: From<io::Error>>(
+	source: &Path,
+	target: &Path,
+	rewrite: impl FnOnce(&[u8], &mut StagedFile) -> Result<(), E>,
+) -> Result<(), E> {
+	let input = fs::read(source)?;
+	let mut staged = StagedFile::create(target)?;
+	rewrite(&input, &mut staged)?;
+	staged.commit()?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::scratch::ScratchDir;
+
+	#[test]
+	fn failed_rewrite_keeps_the_input() {
+		let dir = ScratchDir::new("staging");
+		let target = dir.join("classes.jar");
+		fs::write(&target, b"original").unwrap();
+
+		let failed: Result<(), io::Error> = rewrite_in_place(&target, |_, out| {
+			out.write_all(b"half written")?;
+			Err(io::Error::other("entry 3 failed"))
+		});
+		assert!(failed.is_err());
+		assert_eq!(fs::read(&target).unwrap(), b"original");
+		assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+		rewrite_in_place::<io::Error>(&target, |input, out| out.write_all(&input.to_ascii_uppercase())).unwrap();
+		assert_eq!(fs::read(&target).unwrap(), b"ORIGINAL");
+		assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+
+			fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+			rewrite_in_place::<io::Error>(&target, |input, out| out.write_all(input)).unwrap();
+			assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o640);
+		}
+	}
+}
@@ -0,0 +1,81 @@
+// Parsing classes from a `Read`, see `maya_classfile_io::stream` for how the structure is read without seeking. The
+// attributes are parsed once the whole class is read, like the other readers do.
+
+use std::io::Read;
+
+use maya_classfile_io::stream::ClassStream;
+
+use crate::{
+	class_pool::IRClassfileError,
+	parse::{ParseOptions, ParseWarning},
+	IRClassFile,
+};
+
+impl IRClassFile {
+	/// Reads one class from `reader`, strictly and with the default limits, leaving anything after it unread.
+	pub fn read_from<R: Read>(reader: R) -> Result<Self, IRClassfileError> {
+		Self::read_from_with(reader, &ParseOptions::default()).map(|(class, _)| class)
+	}
+
+	pub fn read_from_with<R: Read>(
+		reader: R,
+		options: &ParseOptions,
+	) -> Result<(Self, Vec<ParseWarning>), IRClassfileError> {
+		let raw = ClassStream::new(reader).read_class_with(&options.limits)?;
+		Self::from_io_with(raw, options)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Cursor, Read};
+
+	use maya_bytes::BytesError;
+	use maya_classfile_io::IOClassfileError;
+
+	use super::*;
+	use crate::asm::assemble;
+
+	/// Hands out a few bytes at a time, like a socket.
+	struct Trickle<'a>(&'a [u8]);
+
+	impl Read for Trickle<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+			let n = buf.len().min(self.0.len()).min(3);
+			buf[..n].copy_from_slice(&self.0[..n]);
+			self.0 = &self.0[n..];
+			Ok(n)
+		}
+	}
+
+	#[test]
+	fn read_without_seeking() {
+		let class = |name: &str| {
+			assemble(&format!(
+				".class public super {name}\n.field static final RATE D = 0.5\n.method public static \
+				 run()I\n.line 3\nldc \"stream\"\ninvokevirtual java/lang/String/length()I\nireturn\n.end method"
+			))
+			.unwrap()
+			.to_bytes()
+			.unwrap()
+		};
+		let (first, second) = (class("p/First"), class("p/Second"));
+		let joined = [first.clone(), second.clone()].concat();
+
+		let mut stream = ClassStream::new(Trickle(&joined));
+		let read = IRClassFile::from_io(stream.read_class().unwrap()).unwrap();
+		assert_eq!(read.to_bytes().unwrap(), first);
+		assert_eq!(stream.position(), first.len() as u64);
+		let read = IRClassFile::read_from(stream.into_inner()).unwrap();
+		assert_eq!(read.to_bytes().unwrap(), second);
+
+		// A class cut short, with an attribute length claiming far more than follows.
+		let mut cut = first.clone();
+		let at = cut.len() - 4;
+		cut.truncate(at);
+		assert!(matches!(
+			IRClassFile::read_from(Cursor::new(&cut)),
+			Err(IRClassfileError::IO(IOClassfileError::Bytes(BytesError::NotEnoughData)))
+		));
+	}
+}
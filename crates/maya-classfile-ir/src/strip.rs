@@ -0,0 +1,200 @@
+// Dropping debug information: line numbers, local variable names and types, and where the source came from. The JVM
+// doesn't need any of it to run the code, but stack traces lose their line numbers and debuggers their variables.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.10
+
+use crate::{
+	archive::{Archive, ArchiveEntry},
+	attribute::{Attributes, IRAttribute},
+	class_pool::IRClassfileError,
+	transform::{ChangeLog, ChangeRecord, Transform},
+	IRClassFile,
+};
+
+/// Which debug information to drop, all of it by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StripOptions {
+	/// `LineNumberTable`
+	pub line_numbers: bool,
+	/// `LocalVariableTable`
+	pub local_variables: bool,
+	/// `LocalVariableTypeTable`, the generic types of local variables.
+	pub local_variable_types: bool,
+	/// `SourceFile`
+	pub source_file: bool,
+	/// `SourceDebugExtension`, e.g. the SMAP of a JSP or Kotlin inline functions.
+	pub source_debug_extension: bool,
+}
+
+impl Default for StripOptions {
+	fn default() -> Self {
+		Self {
+			line_numbers: true,
+			local_variables: true,
+			local_variable_types: true,
+			source_file: true,
+			source_debug_extension: true,
+		}
+	}
+}
+
+impl StripOptions {
+	pub fn none() -> Self {
+		Self {
+			line_numbers: false,
+			local_variables: false,
+			local_variable_types: false,
+			source_file: false,
+			source_debug_extension: false,
+		}
+	}
+
+	fn drops(&self, attr: &IRAttribute) -> bool {
+		match attr {
+			IRAttribute::LineNumberTable(_) => self.line_numbers,
+			IRAttribute::LocalVariableTable { .. } => self.local_variables,
+			IRAttribute::LocalVariableTypeTable { .. } => self.local_variable_types,
+			IRAttribute::SourceFile(_) => self.source_file,
+			IRAttribute::SourceDebugExtension(_) => self.source_debug_extension,
+			_ => false,
+		}
+	}
+
+	fn strip(&self, owner: &str, attributes: &mut Attributes, log: &mut ChangeLog) {
+		attributes.retain(|info| {
+			let drop = self.drops(&info.attr);
+			if drop {
+				log.record(ChangeRecord::RemovedAttribute {
+					owner: owner.to_string(),
+					name: info.name.data.to_string(),
+				});
+			}
+			!drop
+		});
+		if let Some(code) = attributes.code_mut() {
+			self.strip(owner, &mut code.attributes, log);
+		}
+	}
+}
+
+pub struct DebugStripper {
+	pub options: StripOptions,
+}
+
+impl DebugStripper {
+	pub fn new(options: StripOptions) -> Self {
+		Self { options }
+	}
+}
+
+impl Transform for DebugStripper {
+	fn name(&self) -> &str {
+		"strip_debug"
+	}
+
+	fn apply(&mut self, class: &mut IRClassFile, log: &mut ChangeLog) -> Result<(), IRClassfileError> {
+		let name = class.class_name().to_string();
+		self.options.strip(&name, &mut class.attributes, log);
+		for method in &mut class.methods {
+			let owner = format!("{}{}", method.name(), method.descriptor());
+			self.options.strip(&owner, &mut method.attributes, log);
+		}
+		Ok(())
+	}
+}
+
+/// Reads a class, drops its debug information and writes it again.
+pub fn strip_class(bytes: &[u8], options: StripOptions) -> Result<Vec<u8>, IRClassfileError> {
+	let mut class = IRClassFile::read(bytes)?;
+	DebugStripper::new(options).apply(&mut class, &mut ChangeLog::disabled())?;
+	class.to_bytes()
+}
+
+/// The entries of a jar with the debug information of its classes dropped, everything else as it was.
+pub fn strip_archive(archive: &Archive, options: StripOptions) -> Result<Vec<ArchiveEntry>, IRClassfileError> {
+	archive
+		.entries()
+		.iter()
+		.map(|entry| match entry.is_class() {
+			true => Ok(ArchiveEntry {
+				name: entry.name.clone(),
+				data: strip_class(&entry.data, options)?,
+			}),
+			false => Ok(entry.clone()),
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		attribute::{LineNumberTableAttribute, LineNumberTableAttributeEntry, LocalVariableTableEntry},
+		builder::{ClassBuilder, CodeBuilder},
+		class_pool::CPUtf8Ref,
+		code::Instructions,
+		flags::MethodAccessFlags,
+	};
+
+	#[test]
+	fn strip_selected_categories() {
+		let mut builder = ClassBuilder::new("p/C").unwrap();
+		let source_file = CPUtf8Ref::find_or_add(builder.cp(), "C.java").unwrap();
+		let this = CPUtf8Ref::find_or_add(builder.cp(), "this").unwrap();
+		let descriptor = CPUtf8Ref::find_or_add(builder.cp(), "Lp/C;").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		code.max_stack(0).max_locals(1).insn(Instructions::RETURN);
+		let mut code = code.build().unwrap();
+		code.attributes = [
+			IRAttribute::LineNumberTable(LineNumberTableAttribute {
+				line_number_table: vec![LineNumberTableAttributeEntry {
+					start_pc: 0,
+					line_number: 3,
+				}],
+			}),
+			IRAttribute::LocalVariableTable {
+				table: vec![LocalVariableTableEntry {
+					start_pc: 0,
+					length: 1,
+					name: this,
+					descriptor,
+					index: 0,
+				}],
+			},
+		]
+		.into_iter()
+		.map(|attr| builder.attribute_info(attr))
+		.collect::<Result<_, _>>()
+		.unwrap();
+		builder
+			.method(MethodAccessFlags::PUBLIC, "run", "()V", [IRAttribute::Code(code)])
+			.unwrap()
+			.attribute(IRAttribute::SourceFile(source_file))
+			.unwrap();
+		let bytes = builder.to_bytes().unwrap();
+
+		let keep_lines = StripOptions {
+			line_numbers: false,
+			..StripOptions::default()
+		};
+		let mut class = IRClassFile::read(&bytes).unwrap();
+		let mut log = ChangeLog::new();
+		DebugStripper::new(keep_lines).apply(&mut class, &mut log).unwrap();
+		assert!(class.attributes().source_file().is_none());
+		let code = class.methods[0].code().unwrap();
+		assert!(code.attributes.line_number_table().is_some());
+		assert_eq!(code.attributes.len(), 1);
+		let removed = log
+			.entries()
+			.iter()
+			.map(|entry| match &entry.change {
+				ChangeRecord::RemovedAttribute { owner, name } => format!("{owner} {name}"),
+				change => panic!("{change:?}"),
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(removed, ["p/C SourceFile", "run()V LocalVariableTable"]);
+
+		let stripped = IRClassFile::read(&strip_class(&bytes, StripOptions::default()).unwrap()).unwrap();
+		assert!(stripped.methods[0].code().unwrap().attributes.is_empty());
+		assert_eq!(strip_class(&bytes, StripOptions::none()).unwrap(), bytes);
+	}
+}
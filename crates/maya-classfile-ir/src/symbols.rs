@@ -0,0 +1,160 @@
+// Interned names for whole-program analyses. Classes and members are handed out as small copyable ids, so relations and
+// maps over them compare and hash integers instead of strings. Ids are only meaningful for the table that made them.
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::query::MemberRef;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+/// A class, by internal name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClassId(u32);
+
+/// A field or method, by owner, name and descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MemberId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct MemberKey {
+	owner: ClassId,
+	name: Symbol,
+	descriptor: Symbol,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+	strings: Vec<Rc<str>>,
+	string_ids: HashMap<Rc<str>, Symbol>,
+	classes: Vec<Symbol>,
+	class_ids: HashMap<Symbol, ClassId>,
+	members: Vec<MemberKey>,
+	member_ids: HashMap<MemberKey, MemberId>,
+}
+
+impl SymbolTable {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn intern(&mut self, string: &str) -> Symbol {
+		if let Some(symbol) = self.string_ids.get(string) {
+			return *symbol;
+		}
+
+		let symbol = Symbol(self.strings.len() as u32);
+		let string: Rc<str> = string.into();
+		self.strings.push(string.clone());
+		self.string_ids.insert(string, symbol);
+		symbol
+	}
+
+	pub fn lookup(&self, string: &str) -> Option<Symbol> {
+		self.string_ids.get(string).copied()
+	}
+
+	pub fn resolve(&self, symbol: Symbol) -> &str {
+		&self.strings[symbol.0 as usize]
+	}
+
+	/// `name` is an internal name.
+	pub fn class(&mut self, name: &str) -> ClassId {
+		let name = self.intern(name);
+		let next = ClassId(self.classes.len() as u32);
+		let id = *self.class_ids.entry(name).or_insert(next);
+		if id == next {
+			self.classes.push(name);
+		}
+		id
+	}
+
+	pub fn lookup_class(&self, name: &str) -> Option<ClassId> {
+		self.class_ids.get(&self.lookup(name)?).copied()
+	}
+
+	pub fn class_name(&self, class: ClassId) -> &str {
+		self.resolve(self.classes[class.0 as usize])
+	}
+
+	pub fn member(&mut self, owner: &str, name: &str, descriptor: &str) -> MemberId {
+		let owner = self.class(owner);
+		let name = self.intern(name);
+		let descriptor = self.intern(descriptor);
+		self.member_of(owner, name, descriptor)
+	}
+
+	/// `member` with the parts interned already.
+	pub fn member_of(&mut self, owner: ClassId, name: Symbol, descriptor: Symbol) -> MemberId {
+		let key = MemberKey {
+			owner,
+			name,
+			descriptor,
+		};
+		let next = MemberId(self.members.len() as u32);
+		let id = *self.member_ids.entry(key).or_insert(next);
+		if id == next {
+			self.members.push(key);
+		}
+		id
+	}
+
+	pub fn lookup_member(&self, owner: &str, name: &str, descriptor: &str) -> Option<MemberId> {
+		let key = MemberKey {
+			owner: self.lookup_class(owner)?,
+			name: self.lookup(name)?,
+			descriptor: self.lookup(descriptor)?,
+		};
+		self.member_ids.get(&key).copied()
+	}
+
+	pub fn owner(&self, member: MemberId) -> ClassId {
+		self.members[member.0 as usize].owner
+	}
+
+	/// The name and descriptor of `member`.
+	pub fn signature(&self, member: MemberId) -> (Symbol, Symbol) {
+		let key = self.members[member.0 as usize];
+		(key.name, key.descriptor)
+	}
+
+	pub fn member_name(&self, member: MemberId) -> &str {
+		self.resolve(self.members[member.0 as usize].name)
+	}
+
+	pub fn member_descriptor(&self, member: MemberId) -> &str {
+		self.resolve(self.members[member.0 as usize].descriptor)
+	}
+
+	/// The member spelled out, e.g. for printing.
+	pub fn member_ref(&self, member: MemberId) -> MemberRef {
+		MemberRef::new(
+			self.class_name(self.owner(member)),
+			self.member_name(member),
+			self.member_descriptor(member),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ids_are_stable() {
+		let mut symbols = SymbolTable::new();
+		let object = symbols.class("java/lang/Object");
+		let to_string = symbols.member("java/lang/Object", "toString", "()Ljava/lang/String;");
+		assert_eq!(symbols.class("java/lang/Object"), object);
+		assert_eq!(
+			symbols.lookup_member("java/lang/Object", "toString", "()Ljava/lang/String;"),
+			Some(to_string)
+		);
+		assert_eq!(symbols.owner(to_string), object);
+		assert_eq!(
+			symbols.member_ref(to_string).to_string(),
+			"java/lang/Object.toString()Ljava/lang/String;"
+		);
+		assert_eq!(symbols.lookup_class("toString"), None);
+	}
+}
@@ -0,0 +1,1732 @@
+// A canonical text form of class files, for keeping them in git and reviewing changes to them as diffs. It follows the
+// class file byte for byte: every constant pool entry in pool order, every field of every structure labelled with its
+// name in the JVMS, and counts and pool indices as the class has them, so reading it back gives exactly the bytes it
+// came from. `;` starts a comment, which the printer uses for what pool indices and flags stand for.
+//
+//   magic 0xcafebabe
+//   minor_version 0
+//   major_version 52
+//   constant_pool_count 15
+//     #1 Methodref #2 #3  ; java/lang/Object.<init>:()V
+//     #2 Class #4  ; java/lang/Object
+//     ...
+//   methods_count 1
+//     access_flags 0x0001 name_index #5 descriptor_index #6  ; public, <init>, ()V
+//       attributes_count 1
+//         attribute_name_index #7  ; Code
+//           max_stack 1
+//           max_locals 1
+//           code_length 5
+//             0: aload_0
+//             1: invokespecial #1  ; java/lang/Object.<init>:()V
+//             4: return
+//
+// Instructions are `pc: mnemonic operands` with branches as the pc they go to. Attribute lengths are left out since the
+// content gives them. Attributes the JVMS doesn't define, and anything that doesn't decode as its layout says, e.g. a
+// Code attribute whose switch padding isn't zero, are written as `bytes n` and their hex instead.
+
+use std::fmt::Write;
+
+use bitflags::Flags;
+use maya_bytes::BytesError;
+
+use crate::{
+	asm::{error, parse_int, tokenize, Token},
+	class_pool::{escape, java_number, IRClassfileError},
+	code::Opcodes,
+	disasm::array_type,
+	flags::{
+		CharacterRangeFlags, ClassAccessFlags, FieldAccessFlags, MethodAccessFlags, ModuleFlags, ParameterAccessFlags,
+		RequiresFlags,
+	},
+	IRClassFile,
+};
+
+/// The text form of `class` as written.
+pub fn to_text(class: &IRClassFile) -> Result<String, IRClassfileError> {
+	bytes_to_text(&class.to_bytes()?)
+}
+
+/// Reads a class back from its text form.
+pub fn from_text(text: &str) -> Result<IRClassFile, IRClassfileError> {
+	IRClassFile::read(&text_to_bytes(text)?)
+}
+
+/// The text form of the class file in `bytes`, which only has to be laid out as the JVMS says, not make sense.
+pub fn bytes_to_text(bytes: &[u8]) -> Result<String, IRClassfileError> {
+	let mut printer = Printer::new(bytes)?;
+	class(&mut printer)?;
+	match bytes.get(printer.pos) {
+		Some(&value) => Err(IRClassfileError::InvalidTag {
+			kind: "byte after the end of the class",
+			value,
+		}),
+		None => Ok(printer.out),
+	}
+}
+
+/// The class file bytes `text` stands for.
+pub fn text_to_bytes(text: &str) -> Result<Vec<u8>, IRClassfileError> {
+	let lines = tokenize(text)?;
+	let mut parser = Parser {
+		tokens: lines
+			.into_iter()
+			.flat_map(|line| line.tokens.into_iter().map(move |token| (line.number, token)))
+			.collect(),
+		last_line: text.lines().count(),
+		pos: 0,
+		out: Vec::new(),
+		utf8: Vec::new(),
+	};
+	class(&mut parser)?;
+	match parser.tokens.get(parser.pos) {
+		Some(_) => Err(parser.error(format!("unexpected {}", parser.found()))),
+		None => Ok(parser.out),
+	}
+}
+
+// Flag names for a comment, e.g. `public static`.
+type FlagNames = fn(u16) -> String;
+
+// The layout of the class and its attributes, shared by printing and parsing so the two can't disagree on it.
+trait Codec: Sized {
+	/// An unsigned number `width` bytes wide, written in decimal.
+	fn number(&mut self, label: &'static str, width: usize) -> Result<u32, IRClassfileError>;
+	/// The same in hexadecimal.
+	fn hex(&mut self, label: &'static str, width: usize) -> Result<u32, IRClassfileError>;
+	/// A u2 constant pool index, `#0` for none.
+	fn index(&mut self, label: &'static str) -> Result<u16, IRClassfileError>;
+	fn flags(&mut self, label: &'static str, names: FlagNames) -> Result<u16, IRClassfileError>;
+	/// A u1 written as its name in `names`, or as a number if it has none.
+	fn named(&mut self, label: &'static str, names: &[(u8, &'static str)]) -> Result<u8, IRClassfileError>;
+	/// Whatever `f` writes on one line.
+	fn row<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, IRClassfileError>) -> Result<T, IRClassfileError>;
+	/// Whatever `f` writes one level further in.
+	fn nested(&mut self, f: impl FnOnce(&mut Self) -> Result<(), IRClassfileError>) -> Result<(), IRClassfileError>;
+	/// A u4 length and a payload of that many bytes that `f` lays out, or that are given as `bytes`.
+	fn sized(&mut self, f: impl FnOnce(&mut Self) -> Result<(), IRClassfileError>) -> Result<(), IRClassfileError>;
+	/// The rest of the payload as a modified UTF-8 string.
+	fn string(&mut self, label: &'static str) -> Result<(), IRClassfileError>;
+	fn constant_pool(&mut self) -> Result<(), IRClassfileError>;
+	/// code_length and the instructions.
+	fn code(&mut self) -> Result<(), IRClassfileError>;
+	/// The Utf8 entry at `index`, for attribute names.
+	fn utf8(&self, index: u16) -> Option<String>;
+	fn unknown_attribute(&self, name: Option<String>) -> IRClassfileError;
+}
+
+fn class<C: Codec>(c: &mut C) -> Result<(), IRClassfileError> {
+	c.hex("magic", 4)?;
+	c.number("minor_version", 2)?;
+	c.number("major_version", 2)?;
+	c.constant_pool()?;
+	c.flags("access_flags", flag_names::<ClassAccessFlags>)?;
+	c.index("this_class")?;
+	c.index("super_class")?;
+	let interfaces = c.number("interfaces_count", 2)?;
+	list(c, interfaces, |c| c.index("interface").map(drop))?;
+	let members: [(_, FlagNames); 2] = [
+		("fields_count", flag_names::<FieldAccessFlags>),
+		("methods_count", flag_names::<MethodAccessFlags>),
+	];
+	for (label, flags) in members {
+		let count = c.number(label, 2)?;
+		list(c, count, |c| {
+			c.row(|c| {
+				c.flags("access_flags", flags)?;
+				c.index("name_index")?;
+				c.index("descriptor_index").map(drop)
+			})?;
+			c.nested(attributes)
+		})?;
+	}
+	attributes(c)
+}
+
+fn list<C: Codec>(
+	c: &mut C,
+	count: u32,
+	mut f: impl FnMut(&mut C) -> Result<(), IRClassfileError>,
+) -> Result<(), IRClassfileError> {
+	c.nested(|c| (0..count).try_for_each(|_| f(c)))
+}
+
+fn attributes<C: Codec>(c: &mut C) -> Result<(), IRClassfileError> {
+	let count = c.number("attributes_count", 2)?;
+	list(c, count, |c| {
+		let name = c.index("attribute_name_index")?;
+		let name = c.utf8(name);
+		c.nested(|c| c.sized(|c| attribute(c, name)))
+	})
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7
+fn attribute<C: Codec>(c: &mut C, name: Option<String>) -> Result<(), IRClassfileError> {
+	match name.as_deref().unwrap_or_default() {
+		"ConstantValue" => c.index("constantvalue_index").map(drop),
+		"Code" => {
+			c.number("max_stack", 2)?;
+			c.number("max_locals", 2)?;
+			c.code()?;
+			let handlers = c.number("exception_table_length", 2)?;
+			list(c, handlers, |c| {
+				c.row(|c| {
+					c.number("start_pc", 2)?;
+					c.number("end_pc", 2)?;
+					c.number("handler_pc", 2)?;
+					c.index("catch_type").map(drop)
+				})
+			})?;
+			attributes(c)
+		}
+		"StackMapTable" => {
+			let entries = c.number("number_of_entries", 2)?;
+			list(c, entries, |c| c.row(frame))
+		}
+		"Exceptions" => {
+			let exceptions = c.number("number_of_exceptions", 2)?;
+			list(c, exceptions, |c| c.index("exception_index").map(drop))
+		}
+		"InnerClasses" => {
+			let classes = c.number("number_of_classes", 2)?;
+			list(c, classes, |c| {
+				c.row(|c| {
+					c.index("inner_class_info_index")?;
+					c.index("outer_class_info_index")?;
+					c.index("inner_name_index")?;
+					c.flags("inner_class_access_flags", flag_names::<ClassAccessFlags>)
+						.map(drop)
+				})
+			})
+		}
+		"EnclosingMethod" => c.row(|c| {
+			c.index("class_index")?;
+			c.index("method_index").map(drop)
+		}),
+		"Synthetic" | "Deprecated" => Ok(()),
+		"Signature" => c.index("signature_index").map(drop),
+		"SourceFile" => c.index("sourcefile_index").map(drop),
+		"SourceDebugExtension" => c.string("debug_extension"),
+		"LineNumberTable" => {
+			let lines = c.number("line_number_table_length", 2)?;
+			list(c, lines, |c| {
+				c.row(|c| {
+					c.number("start_pc", 2)?;
+					c.number("line_number", 2).map(drop)
+				})
+			})
+		}
+		name @ ("LocalVariableTable" | "LocalVariableTypeTable") => {
+			let (length, descriptor) = match name {
+				"LocalVariableTable" => ("local_variable_table_length", "descriptor_index"),
+				_ => ("local_variable_type_table_length", "signature_index"),
+			};
+			let locals = c.number(length, 2)?;
+			list(c, locals, |c| {
+				c.row(|c| {
+					c.number("start_pc", 2)?;
+					c.number("length", 2)?;
+					c.index("name_index")?;
+					c.index(descriptor)?;
+					c.number("index", 2).map(drop)
+				})
+			})
+		}
+		"RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => {
+			let annotations = c.number("num_annotations", 2)?;
+			list(c, annotations, annotation)
+		}
+		"RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+			let parameters = c.number("num_parameters", 1)?;
+			list(c, parameters, |c| {
+				let annotations = c.number("num_annotations", 2)?;
+				list(c, annotations, annotation)
+			})
+		}
+		"RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => {
+			let annotations = c.number("num_annotations", 2)?;
+			list(c, annotations, type_annotation)
+		}
+		"AnnotationDefault" => element_value(c),
+		"BootstrapMethods" => {
+			let methods = c.number("num_bootstrap_methods", 2)?;
+			list(c, methods, |c| {
+				let arguments = c.row(|c| {
+					c.index("bootstrap_method_ref")?;
+					c.number("num_bootstrap_arguments", 2)
+				})?;
+				list(c, arguments, |c| c.index("bootstrap_argument").map(drop))
+			})
+		}
+		"MethodParameters" => {
+			let parameters = c.number("parameters_count", 1)?;
+			list(c, parameters, |c| {
+				c.row(|c| {
+					c.index("name_index")?;
+					c.flags("access_flags", flag_names::<ParameterAccessFlags>).map(drop)
+				})
+			})
+		}
+		"Module" => module(c),
+		"ModulePackages" => {
+			let packages = c.number("package_count", 2)?;
+			list(c, packages, |c| c.index("package_index").map(drop))
+		}
+		"ModuleMainClass" => c.index("main_class_index").map(drop),
+		"NestHost" => c.index("host_class_index").map(drop),
+		"NestMembers" | "PermittedSubclasses" => {
+			let classes = c.number("number_of_classes", 2)?;
+			list(c, classes, |c| c.index("class").map(drop))
+		}
+		"Record" => {
+			let components = c.number("components_count", 2)?;
+			list(c, components, |c| {
+				c.row(|c| {
+					c.index("name_index")?;
+					c.index("descriptor_index").map(drop)
+				})?;
+				c.nested(attributes)
+			})
+		}
+		"CharacterRangeTable" => {
+			let ranges = c.number("character_range_table_length", 2)?;
+			list(c, ranges, |c| {
+				c.row(|c| {
+					c.number("start_pc", 2)?;
+					c.number("end_pc", 2)?;
+					c.hex("character_range_start", 4)?;
+					c.hex("character_range_end", 4)?;
+					c.flags("flags", flag_names::<CharacterRangeFlags>).map(drop)
+				})
+			})
+		}
+		"CompilationID" => c.index("compilation_id_index").map(drop),
+		"SourceID" => c.index("sourceid_index").map(drop),
+		_ => Err(c.unknown_attribute(name)),
+	}
+}
+
+const VERIFICATION_TYPES: &[(u8, &str)] = &[
+	(0, "Top"),
+	(1, "Integer"),
+	(2, "Float"),
+	(3, "Double"),
+	(4, "Long"),
+	(5, "Null"),
+	(6, "UninitializedThis"),
+	(7, "Object"),
+	(8, "Uninitialized"),
+];
+
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.4
+fn frame<C: Codec>(c: &mut C) -> Result<(), IRClassfileError> {
+	let frame_type = c.number("frame_type", 1)?;
+	let types = |c: &mut C, count: u32| (0..count).try_for_each(|_| verification_type(c));
+	match frame_type {
+		0..=63 => Ok(()),
+		64..=127 => verification_type(c),
+		128..=246 => Err(IRClassfileError::InvalidTag {
+			kind: "stack map frame type",
+			value: frame_type as u8,
+		}),
+		247 => {
+			c.number("offset_delta", 2)?;
+			verification_type(c)
+		}
+		248..=251 => c.number("offset_delta", 2).map(drop),
+		252..=254 => {
+			c.number("offset_delta", 2)?;
+			types(c, frame_type - 251)
+		}
+		_ => {
+			c.number("offset_delta", 2)?;
+			let locals = c.number("number_of_locals", 2)?;
+			types(c, locals)?;
+			let stack = c.number("number_of_stack_items", 2)?;
+			types(c, stack)
+		}
+	}
+}
+
+fn verification_type<C: Codec>(c: &mut C) -> Result<(), IRClassfileError> {
+	match c.named("", VERIFICATION_TYPES)? {
+		0..=6 => Ok(()),
+		7 => c.index("").map(drop),
+		8 => c.number("", 2).map(drop),
+		value => Err(IRClassfileError::InvalidTag {
+			kind: "verification type",
+			value,
+		}),
+	}
+}
+
+const ELEMENT_VALUE_TAGS: &[(u8, &str)] = &[
+	(b'B', "B"),
+	(b'C', "C"),
+	(b'D', "D"),
+	(b'F', "F"),
+	(b'I', "I"),
+	(b'J', "J"),
+	(b'S', "S"),
+	(b'Z', "Z"),
+	(b's', "s"),
+	(b'e', "e"),
+	(b'c', "c"),
+	(b'@', "@"),
+	(b'[', "["),
+];
+
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.16
+fn annotation<C: Codec>(c: &mut C) -> Result<(), IRClassfileError> {
+	let pairs = c.row(|c| {
+		c.index("type_index")?;
+		c.number("num_element_value_pairs", 2)
+	})?;
+	list(c, pairs, |c| {
+		c.index("element_name_index")?;
+		c.nested(element_value)
+	})
+}
+
+fn element_value<C: Codec>(c: &mut C) -> Result<(), IRClassfileError> {
+	let (tag, values) = c.row(|c| {
+		let tag = c.named("tag", ELEMENT_VALUE_TAGS)?;
+		match tag {
+			b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => c.index("const_value_index").map(drop)?,
+			b'e' => {
+				c.index("type_name_index")?;
+				c.index("const_name_index")?;
+			}
+			b'c' => c.index("class_info_index").map(drop)?,
+			b'@' => {}
+			b'[' => return Ok((tag, c.number("num_values", 2)?)),
+			value => {
+				return Err(IRClassfileError::InvalidTag {
+					kind: "element value tag",
+					value,
+				})
+			}
+		}
+		Ok((tag, 0))
+	})?;
+	match tag {
+		b'@' => c.nested(annotation),
+		b'[' => list(c, values, element_value),
+		_ => Ok(()),
+	}
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.20
+fn type_annotation<C: Codec>(c: &mut C) -> Result<(), IRClassfileError> {
+	let locals = c.row(|c| {
+		let target_type = c.hex("target_type", 1)?;
+		match target_type {
+			0x00 | 0x01 => c.number("type_parameter_index", 1).map(drop)?,
+			0x10 => c.number("supertype_index", 2).map(drop)?,
+			0x11 | 0x12 => {
+				c.number("type_parameter_index", 1)?;
+				c.number("bound_index", 1)?;
+			}
+			0x13..=0x15 => {}
+			0x16 => c.number("formal_parameter_index", 1).map(drop)?,
+			0x17 => c.number("throws_type_index", 2).map(drop)?,
+			0x40 | 0x41 => return c.number("table_length", 2),
+			0x42 => c.number("exception_table_index", 2).map(drop)?,
+			0x43..=0x46 => c.number("offset", 2).map(drop)?,
+			0x47..=0x4B => {
+				c.number("offset", 2)?;
+				c.number("type_argument_index", 1)?;
+			}
+			_ => {
+				return Err(IRClassfileError::InvalidTag {
+					kind: "type annotation target",
+					value: target_type as u8,
+				})
+			}
+		}
+		Ok(0)
+	})?;
+	list(c, locals, |c| {
+		c.row(|c| {
+			c.number("start_pc", 2)?;
+			c.number("length", 2)?;
+			c.number("index", 2).map(drop)
+		})
+	})?;
+	let path = c.number("path_length", 1)?;
+	list(c, path, |c| {
+		c.row(|c| {
+			c.number("type_path_kind", 1)?;
+			c.number("type_argument_index", 1).map(drop)
+		})
+	})?;
+	annotation(c)
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.25
+fn module<C: Codec>(c: &mut C) -> Result<(), IRClassfileError> {
+	c.row(|c| {
+		c.index("module_name_index")?;
+		c.flags("module_flags", flag_names::<ModuleFlags>)?;
+		c.index("module_version_index").map(drop)
+	})?;
+	let requires = c.number("requires_count", 2)?;
+	list(c, requires, |c| {
+		c.row(|c| {
+			c.index("requires_index")?;
+			c.flags("requires_flags", flag_names::<RequiresFlags>)?;
+			c.index("requires_version_index").map(drop)
+		})
+	})?;
+	for (count, index, flags, to_count, to_index) in [
+		(
+			"exports_count",
+			"exports_index",
+			"exports_flags",
+			"exports_to_count",
+			"exports_to_index",
+		),
+		(
+			"opens_count",
+			"opens_index",
+			"opens_flags",
+			"opens_to_count",
+			"opens_to_index",
+		),
+	] {
+		let count = c.number(count, 2)?;
+		list(c, count, |c| {
+			let targets = c.row(|c| {
+				c.index(index)?;
+				c.flags(flags, flag_names::<ModuleFlags>)?;
+				c.number(to_count, 2)
+			})?;
+			list(c, targets, |c| c.index(to_index).map(drop))
+		})?;
+	}
+	let uses = c.number("uses_count", 2)?;
+	list(c, uses, |c| c.index("uses_index").map(drop))?;
+	let provides = c.number("provides_count", 2)?;
+	list(c, provides, |c| {
+		let with = c.row(|c| {
+			c.index("provides_index")?;
+			c.number("provides_with_count", 2)
+		})?;
+		list(c, with, |c| c.index("provides_with_index").map(drop))
+	})
+}
+
+fn flag_names<F: Flags<Bits = u16>>(bits: u16) -> String {
+	let names = F::from_bits_retain(bits)
+		.iter_names()
+		.map(|(name, _)| name.to_lowercase());
+	names.collect::<Vec<_>>().join(" ")
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.4
+const POOL_TAGS: &[(u8, &str)] = &[
+	(1, "Utf8"),
+	(3, "Integer"),
+	(4, "Float"),
+	(5, "Long"),
+	(6, "Double"),
+	(7, "Class"),
+	(8, "String"),
+	(9, "Fieldref"),
+	(10, "Methodref"),
+	(11, "InterfaceMethodref"),
+	(12, "NameAndType"),
+	(15, "MethodHandle"),
+	(16, "MethodType"),
+	(17, "Dynamic"),
+	(18, "InvokeDynamic"),
+	(19, "Module"),
+	(20, "Package"),
+];
+
+const REFERENCE_KINDS: &[(u8, &str)] = &[
+	(1, "REF_getField"),
+	(2, "REF_getStatic"),
+	(3, "REF_putField"),
+	(4, "REF_putStatic"),
+	(5, "REF_invokeVirtual"),
+	(6, "REF_invokeStatic"),
+	(7, "REF_invokeSpecial"),
+	(8, "REF_newInvokeSpecial"),
+	(9, "REF_invokeInterface"),
+];
+
+fn name_of(names: &[(u8, &'static str)], value: u8) -> Option<&'static str> {
+	names.iter().find(|(v, _)| *v == value).map(|(_, name)| *name)
+}
+
+fn value_of(names: &[(u8, &str)], name: &str) -> Option<u8> {
+	names.iter().find(|(_, n)| *n == name).map(|(value, _)| *value)
+}
+
+// A constant pool entry as it's laid out, pool indices and all.
+#[derive(Debug, Clone)]
+enum PoolEntry {
+	Utf8(Vec<u8>),
+	Integer(i32),
+	// the bits, NaNs aren't all the same
+	Float(u32),
+	Long(i64),
+	Double(u64),
+	// Class, String, MethodType, Module and Package
+	Index(u8, u16),
+	// Fieldref, Methodref, InterfaceMethodref and NameAndType, or Dynamic and InvokeDynamic whose first half is a
+	// bootstrap method rather than a pool index
+	Pair(u8, u16, u16),
+	MethodHandle(u8, u16),
+}
+
+impl PoolEntry {
+	fn tag(&self) -> u8 {
+		match self {
+			Self::Utf8(_) => 1,
+			Self::Integer(_) => 3,
+			Self::Float(_) => 4,
+			Self::Long(_) => 5,
+			Self::Double(_) => 6,
+			Self::Index(tag, _) | Self::Pair(tag, _, _) => *tag,
+			Self::MethodHandle(..) => 15,
+		}
+	}
+
+	// Longs and doubles take up two indices.
+	fn slots(&self) -> u16 {
+		match self {
+			Self::Long(_) | Self::Double(_) => 2,
+			_ => 1,
+		}
+	}
+
+	fn write(&self, out: &mut Vec<u8>) {
+		out.push(self.tag());
+		match self {
+			Self::Utf8(bytes) => {
+				out.extend((bytes.len() as u16).to_be_bytes());
+				out.extend(bytes);
+			}
+			Self::Integer(value) => out.extend(value.to_be_bytes()),
+			Self::Float(bits) => out.extend(bits.to_be_bytes()),
+			Self::Long(value) => out.extend(value.to_be_bytes()),
+			Self::Double(bits) => out.extend(bits.to_be_bytes()),
+			Self::Index(_, index) => out.extend(index.to_be_bytes()),
+			Self::Pair(_, first, second) => {
+				out.extend(first.to_be_bytes());
+				out.extend(second.to_be_bytes());
+			}
+			Self::MethodHandle(kind, index) => {
+				out.push(*kind);
+				out.extend(index.to_be_bytes());
+			}
+		}
+	}
+}
+
+// The Utf8 text if it re-encodes to the same bytes, which for the odd class using the unusual encodings modified UTF-8
+// allows, or invalid ones, it doesn't.
+fn utf8_text(bytes: &[u8]) -> Option<String> {
+	maya_mutf8::decode(bytes)
+		.ok()
+		.filter(|text| maya_mutf8::encode(text) == bytes)
+}
+
+/// Prints single attributes of a class with their pool indices resolved, so the same attribute comes out the same
+/// in classes whose pools are laid out differently.
+pub(crate) struct AttributeResolver {
+	pool: Vec<Option<PoolEntry>>,
+}
+
+impl AttributeResolver {
+	/// Takes the pool of the class file in `bytes`.
+	pub(crate) fn new(bytes: &[u8]) -> Result<Self, IRClassfileError> {
+		Ok(Self {
+			pool: Printer::new(bytes)?.pool,
+		})
+	}
+
+	/// The text of the attribute called `name`, given its `info`. Anything it can't lay out goes out as bytes.
+	pub(crate) fn text(&mut self, name: &str, info: &[u8]) -> String {
+		let mut bytes = (info.len() as u32).to_be_bytes().to_vec();
+		bytes.extend_from_slice(info);
+		let mut printer = Printer {
+			bytes: &bytes,
+			pos: 0,
+			limit: bytes.len(),
+			pool: std::mem::take(&mut self.pool),
+			pool_end: 0,
+			out: String::new(),
+			indent: 0,
+			row: None,
+			resolved: true,
+		};
+		// the length is right by construction, so it can't fail
+		let _ = printer.sized(|c| attribute(c, Some(name.to_string())));
+		self.pool = printer.pool;
+		printer.out
+	}
+}
+
+#[derive(Default)]
+struct Row {
+	parts: Vec<String>,
+	comments: Vec<String>,
+}
+
+struct Printer<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+	// the end of what's being printed, the current attribute or code
+	limit: usize,
+	// indexed by pool index, `None` for 0 and the second half of longs and doubles
+	pool: Vec<Option<PoolEntry>>,
+	pool_end: usize,
+	out: String,
+	indent: usize,
+	row: Option<Row>,
+	// pool indices printed as what they stand for instead, which doesn't read back
+	resolved: bool,
+}
+
+impl<'a> Printer<'a> {
+	fn new(bytes: &'a [u8]) -> Result<Self, IRClassfileError> {
+		let mut printer = Self {
+			bytes,
+			pos: 8,
+			limit: bytes.len(),
+			pool: vec![None],
+			pool_end: 0,
+			out: String::new(),
+			indent: 0,
+			row: None,
+			resolved: false,
+		};
+		let count = printer.read(2)? as usize;
+		while printer.pool.len() < count {
+			let entry = printer.entry()?;
+			let slots = entry.slots();
+			printer.pool.push(Some(entry));
+			if slots == 2 {
+				printer.pool.push(None);
+			}
+		}
+		printer.pool_end = printer.pos;
+		printer.pos = 0;
+		Ok(printer)
+	}
+
+	fn read(&mut self, width: usize) -> Result<u64, IRClassfileError> {
+		let bytes = self.take(width)?;
+		Ok(bytes.iter().fold(0, |value, byte| value << 8 | *byte as u64))
+	}
+
+	fn take(&mut self, length: usize) -> Result<&'a [u8], IRClassfileError> {
+		let end = self.pos.checked_add(length).filter(|end| *end <= self.limit);
+		let end = end.ok_or(BytesError::NotEnoughData)?;
+		let bytes = &self.bytes[self.pos..end];
+		self.pos = end;
+		Ok(bytes)
+	}
+
+	fn entry(&mut self) -> Result<PoolEntry, IRClassfileError> {
+		let tag = self.read(1)? as u8;
+		Ok(match tag {
+			1 => {
+				let length = self.read(2)? as usize;
+				PoolEntry::Utf8(self.take(length)?.to_vec())
+			}
+			3 => PoolEntry::Integer(self.read(4)? as i32),
+			4 => PoolEntry::Float(self.read(4)? as u32),
+			5 => PoolEntry::Long(self.read(8)? as i64),
+			6 => PoolEntry::Double(self.read(8)?),
+			7 | 8 | 16 | 19 | 20 => PoolEntry::Index(tag, self.read(2)? as u16),
+			9..=12 | 17 | 18 => PoolEntry::Pair(tag, self.read(2)? as u16, self.read(2)? as u16),
+			15 => PoolEntry::MethodHandle(self.read(1)? as u8, self.read(2)? as u16),
+			value => {
+				return Err(IRClassfileError::InvalidTag {
+					kind: "constant pool tag",
+					value,
+				})
+			}
+		})
+	}
+
+	fn field(&mut self, label: &str, value: String, comment: Option<String>) {
+		let text = match label.is_empty() {
+			true => value,
+			false => format!("{label} {value}"),
+		};
+		let comment = comment.filter(|comment| !comment.is_empty());
+		match &mut self.row {
+			Some(row) => {
+				row.parts.push(text);
+				row.comments.extend(comment);
+			}
+			None => self.line(&text, comment.as_slice()),
+		}
+	}
+
+	fn line(&mut self, text: &str, comments: &[String]) {
+		for _ in 0..self.indent {
+			self.out.push_str("  ");
+		}
+		self.out.push_str(text);
+		if !comments.is_empty() {
+			let _ = write!(self.out, "  ; {}", comments.join(", "));
+		}
+		self.out.push('\n');
+	}
+
+	// `#index`, and what it stands for as a comment, or only the latter when printing resolved.
+	fn pool_index(&self, index: u16) -> (String, Option<String>) {
+		match (self.resolved, self.describe(index)) {
+			(true, Some(description)) => (description, None),
+			(_, description) => (format!("#{index}"), description),
+		}
+	}
+
+	// What the entry at `index` stands for, with everything it refers to resolved.
+	fn describe(&self, index: u16) -> Option<String> {
+		self.describe_at(index, 0)
+	}
+
+	fn describe_at(&self, index: u16, depth: usize) -> Option<String> {
+		// entries referring to each other in a cycle are invalid, but the class gets printed all the same
+		if depth > 8 {
+			return None;
+		}
+		let describe = |index| self.describe_at(index, depth + 1);
+		Some(match self.pool.get(index as usize)?.as_ref()? {
+			PoolEntry::Utf8(bytes) => escape(&maya_mutf8::decode(bytes).ok()?),
+			PoolEntry::Integer(value) => value.to_string(),
+			PoolEntry::Float(bits) => format!("{}f", java_number(f32::from_bits(*bits))),
+			PoolEntry::Long(value) => format!("{value}l"),
+			PoolEntry::Double(bits) => format!("{}d", java_number(f64::from_bits(*bits))),
+			PoolEntry::Index(8, index) => format!("\"{}\"", describe(*index)?),
+			PoolEntry::Index(_, index) => describe(*index)?,
+			PoolEntry::Pair(12, name, descriptor) => format!("{}:{}", describe(*name)?, describe(*descriptor)?),
+			PoolEntry::Pair(17 | 18, bootstrap, name_and_type) => format!("#{bootstrap}:{}", describe(*name_and_type)?),
+			PoolEntry::Pair(_, class, name_and_type) => format!("{}.{}", describe(*class)?, describe(*name_and_type)?),
+			PoolEntry::MethodHandle(kind, reference) => {
+				format!("{} {}", name_of(REFERENCE_KINDS, *kind)?, describe(*reference)?)
+			}
+		})
+	}
+
+	fn entry_text(entry: &PoolEntry) -> String {
+		match entry {
+			PoolEntry::Utf8(bytes) => match utf8_text(bytes) {
+				Some(text) => format!("\"{}\"", escape(&text)),
+				None => format!("bytes {} {}", bytes.len(), hex(bytes)),
+			},
+			PoolEntry::Integer(value) => value.to_string(),
+			PoolEntry::Float(bits) => {
+				let text = format!("{:?}", f32::from_bits(*bits));
+				match text.parse::<f32>().is_ok_and(|value| value.to_bits() == *bits) {
+					true => text,
+					false => format!("bits {bits:#010x}"),
+				}
+			}
+			PoolEntry::Long(value) => value.to_string(),
+			PoolEntry::Double(bits) => {
+				let text = format!("{:?}", f64::from_bits(*bits));
+				match text.parse::<f64>().is_ok_and(|value| value.to_bits() == *bits) {
+					true => text,
+					false => format!("bits {bits:#018x}"),
+				}
+			}
+			PoolEntry::Index(_, index) => format!("#{index}"),
+			PoolEntry::Pair(17 | 18, bootstrap, name_and_type) => format!("{bootstrap} #{name_and_type}"),
+			PoolEntry::Pair(_, first, second) => format!("#{first} #{second}"),
+			PoolEntry::MethodHandle(kind, index) => match name_of(REFERENCE_KINDS, *kind) {
+				Some(kind) => format!("{kind} #{index}"),
+				None => format!("{kind} #{index}"),
+			},
+		}
+	}
+
+	// `bytes n` and the bytes up to `end` in hex, 32 to a line.
+	fn dump(&mut self, end: usize) {
+		let bytes = &self.bytes[self.pos..end];
+		self.line(&format!("bytes {}", bytes.len()), &[]);
+		self.indent += 1;
+		for chunk in bytes.chunks(32) {
+			self.line(&hex(chunk), &[]);
+		}
+		self.indent -= 1;
+		self.pos = end;
+	}
+
+	fn instructions(&mut self, start: usize) -> Result<(), IRClassfileError> {
+		while self.pos < self.limit {
+			let pc = self.pos - start;
+			let opcode = self.read(1)? as u8;
+			let mnemonic = Opcodes::mnemonic(opcode).ok_or(IRClassfileError::InvalidTag {
+				kind: "opcode",
+				value: opcode,
+			})?;
+			let mut parts = vec![format!("{pc}:"), mnemonic.to_string()];
+			let mut comments = Vec::new();
+			let mut cases = Vec::new();
+			match opcode {
+				Opcodes::WIDE => {
+					let opcode = self.read(1)? as u8;
+					if !is_wide(opcode) {
+						return Err(IRClassfileError::InvalidTag {
+							kind: "opcode after wide",
+							value: opcode,
+						});
+					}
+					parts.push(Opcodes::mnemonic(opcode).unwrap_or_default().to_string());
+					parts.push(self.read(2)?.to_string());
+					if opcode == Opcodes::IINC {
+						parts.push((self.read(2)? as i16).to_string());
+					}
+				}
+				Opcodes::TABLESWITCH | Opcodes::LOOKUPSWITCH => {
+					while !(self.pos - start).is_multiple_of(4) {
+						if self.read(1)? != 0 {
+							return Err(IRClassfileError::InvalidTag {
+								kind: "switch padding",
+								value: self.bytes[self.pos - 1],
+							});
+						}
+					}
+					let target = |offset: u64| pc as i64 + offset as u32 as i32 as i64;
+					parts.push(format!("default {}", target(self.read(4)?)));
+					if opcode == Opcodes::TABLESWITCH {
+						let low = self.read(4)? as i32;
+						let high = self.read(4)? as i32;
+						if high < low {
+							return Err(IRClassfileError::InvalidTableSwitch { low, high });
+						}
+						parts.push(format!("low {low} high {high}"));
+						for key in low..=high {
+							cases.push(format!("{key}: {}", target(self.read(4)?)));
+						}
+					} else {
+						let pairs = self.read(4)? as i32;
+						if pairs < 0 {
+							return Err(BytesError::NotEnoughData.into());
+						}
+						parts.push(format!("npairs {pairs}"));
+						for _ in 0..pairs {
+							let key = self.read(4)? as i32;
+							cases.push(format!("{key}: {}", target(self.read(4)?)));
+						}
+					}
+				}
+				_ => {
+					for operand in operands(opcode) {
+						let text = match operand {
+							Operand::Local | Operand::Count => self.read(1)?.to_string(),
+							Operand::Byte => (self.read(1)? as i8).to_string(),
+							Operand::Short => (self.read(2)? as i16).to_string(),
+							Operand::Index1 | Operand::Index2 => {
+								let index = self.read(if *operand == Operand::Index1 { 1 } else { 2 })? as u16;
+								let (text, comment) = self.pool_index(index);
+								comments.extend(comment);
+								text
+							}
+							Operand::Branch => (pc as i64 + self.read(2)? as i16 as i64).to_string(),
+							Operand::WideBranch => (pc as i64 + self.read(4)? as u32 as i32 as i64).to_string(),
+							Operand::ArrayType => {
+								let atype = self.read(1)?;
+								comments.push(array_type(atype as u8).to_string());
+								atype.to_string()
+							}
+							Operand::Zero => match self.read(1)? {
+								0 => continue,
+								value => {
+									return Err(IRClassfileError::InvalidTag {
+										kind: "reserved operand",
+										value: value as u8,
+									})
+								}
+							},
+						};
+						parts.push(text);
+					}
+				}
+			}
+			self.line(&parts.join(" "), &comments);
+			self.indent += 1;
+			for case in cases {
+				self.line(&case, &[]);
+			}
+			self.indent -= 1;
+		}
+		Ok(())
+	}
+}
+
+impl Codec for Printer<'_> {
+	fn number(&mut self, label: &'static str, width: usize) -> Result<u32, IRClassfileError> {
+		let value = self.read(width)? as u32;
+		self.field(label, value.to_string(), None);
+		Ok(value)
+	}
+
+	fn hex(&mut self, label: &'static str, width: usize) -> Result<u32, IRClassfileError> {
+		let value = self.read(width)? as u32;
+		self.field(label, format!("{value:#0width$x}", width = width * 2 + 2), None);
+		Ok(value)
+	}
+
+	fn index(&mut self, label: &'static str) -> Result<u16, IRClassfileError> {
+		let index = self.read(2)? as u16;
+		let (text, comment) = self.pool_index(index);
+		self.field(label, text, comment);
+		Ok(index)
+	}
+
+	fn flags(&mut self, label: &'static str, names: FlagNames) -> Result<u16, IRClassfileError> {
+		let flags = self.read(2)? as u16;
+		self.field(label, format!("{flags:#06x}"), Some(names(flags)));
+		Ok(flags)
+	}
+
+	fn named(&mut self, label: &'static str, names: &[(u8, &'static str)]) -> Result<u8, IRClassfileError> {
+		let value = self.read(1)? as u8;
+		let text = name_of(names, value).map_or_else(|| value.to_string(), str::to_string);
+		self.field(label, text, None);
+		Ok(value)
+	}
+
+	fn row<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, IRClassfileError>) -> Result<T, IRClassfileError> {
+		self.row = Some(Row::default());
+		let value = f(self)?;
+		if let Some(row) = self.row.take() {
+			self.line(&row.parts.join(" "), &row.comments);
+		}
+		Ok(value)
+	}
+
+	fn nested(&mut self, f: impl FnOnce(&mut Self) -> Result<(), IRClassfileError>) -> Result<(), IRClassfileError> {
+		self.indent += 1;
+		let result = f(self);
+		self.indent -= 1;
+		result
+	}
+
+	fn sized(&mut self, f: impl FnOnce(&mut Self) -> Result<(), IRClassfileError>) -> Result<(), IRClassfileError> {
+		let length = self.read(4)? as usize;
+		let (start, limit, out, indent) = (self.pos, self.limit, self.out.len(), self.indent);
+		let end = start.checked_add(length).filter(|end| *end <= limit);
+		let end = end.ok_or(BytesError::NotEnoughData)?;
+		self.limit = end;
+		let laid_out = f(self).is_ok() && self.pos == end;
+		self.limit = limit;
+		if !laid_out {
+			self.out.truncate(out);
+			self.indent = indent;
+			self.row = None;
+			self.pos = start;
+			self.dump(end);
+		}
+		Ok(())
+	}
+
+	fn string(&mut self, label: &'static str) -> Result<(), IRClassfileError> {
+		let bytes = self.take(self.limit - self.pos)?;
+		let text = utf8_text(bytes).ok_or(IRClassfileError::InvalidTag {
+			kind: "modified UTF-8",
+			value: bytes.first().copied().unwrap_or_default(),
+		})?;
+		self.field(label, format!("\"{}\"", escape(&text)), None);
+		Ok(())
+	}
+
+	fn constant_pool(&mut self) -> Result<(), IRClassfileError> {
+		let count = self.read(2)?;
+		self.line(&format!("constant_pool_count {count}"), &[]);
+		self.indent += 1;
+		let entries = self.pool.iter().enumerate().filter_map(|(index, entry)| {
+			let entry = entry.as_ref()?;
+			let tag = name_of(POOL_TAGS, entry.tag()).unwrap_or_default();
+			let comment = match entry {
+				PoolEntry::Index(..) | PoolEntry::Pair(..) | PoolEntry::MethodHandle(..) => self.describe(index as u16),
+				_ => None,
+			};
+			Some((format!("#{index} {tag} {}", Self::entry_text(entry)), comment))
+		});
+		for (text, comment) in entries.collect::<Vec<_>>() {
+			self.line(&text, comment.as_slice());
+		}
+		self.indent -= 1;
+		self.pos = self.pool_end;
+		Ok(())
+	}
+
+	fn code(&mut self) -> Result<(), IRClassfileError> {
+		let length = self.number("code_length", 4)? as usize;
+		let (start, limit, out) = (self.pos, self.limit, self.out.len());
+		let end = start.checked_add(length).filter(|end| *end <= limit);
+		self.limit = end.ok_or(BytesError::NotEnoughData)?;
+		self.indent += 1;
+		if self.instructions(start).is_err() {
+			self.out.truncate(out);
+			self.pos = start;
+			self.dump(self.limit);
+		}
+		self.indent -= 1;
+		self.limit = limit;
+		Ok(())
+	}
+
+	fn utf8(&self, index: u16) -> Option<String> {
+		match self.pool.get(index as usize)? {
+			Some(PoolEntry::Utf8(bytes)) => utf8_text(bytes),
+			_ => None,
+		}
+	}
+
+	fn unknown_attribute(&self, _name: Option<String>) -> IRClassfileError {
+		// only ever makes the attribute go out as bytes
+		BytesError::NotEnoughData.into()
+	}
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes
+		.iter()
+		.fold(String::with_capacity(bytes.len() * 2), |mut out, byte| {
+			let _ = write!(out, "{byte:02x}");
+			out
+		})
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+	// a u1 local variable index
+	Local,
+	Byte,
+	Short,
+	// a pool index, u1 for ldc and u2 for the rest
+	Index1,
+	Index2,
+	Branch,
+	WideBranch,
+	// invokeinterface's count and multianewarray's dimensions
+	Count,
+	ArrayType,
+	// the zero bytes of invokeinterface and invokedynamic, left out of the text
+	Zero,
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-6.html#jvms-6.5
+fn operands(opcode: u8) -> &'static [Operand] {
+	use Operand::*;
+	match opcode {
+		Opcodes::BIPUSH => &[Byte],
+		Opcodes::SIPUSH => &[Short],
+		Opcodes::LDC => &[Index1],
+		Opcodes::LDC_W | Opcodes::LDC2_W => &[Index2],
+		Opcodes::ILOAD..=Opcodes::ALOAD | Opcodes::ISTORE..=Opcodes::ASTORE | Opcodes::RET => &[Local],
+		Opcodes::IINC => &[Local, Byte],
+		Opcodes::IFEQ..=Opcodes::JSR | Opcodes::IFNULL | Opcodes::IFNONNULL => &[Branch],
+		Opcodes::GOTO_W | Opcodes::JSR_W => &[WideBranch],
+		Opcodes::GETSTATIC..=Opcodes::INVOKESTATIC
+		| Opcodes::NEW
+		| Opcodes::ANEWARRAY
+		| Opcodes::CHECKCAST
+		| Opcodes::INSTANCEOF => &[Index2],
+		Opcodes::INVOKEINTERFACE => &[Index2, Count, Zero],
+		Opcodes::INVOKEDYNAMIC => &[Index2, Zero, Zero],
+		Opcodes::NEWARRAY => &[ArrayType],
+		Opcodes::MULTIANEWARRAY => &[Index2, Count],
+		_ => &[],
+	}
+}
+
+// The instructions `wide` can modify.
+fn is_wide(opcode: u8) -> bool {
+	matches!(
+		opcode,
+		Opcodes::ILOAD..=Opcodes::ALOAD | Opcodes::ISTORE..=Opcodes::ASTORE | Opcodes::RET | Opcodes::IINC
+	)
+}
+
+struct Parser {
+	// the tokens of all lines, with the line they're on
+	tokens: Vec<(usize, Token)>,
+	// for errors at the end
+	last_line: usize,
+	pos: usize,
+	out: Vec<u8>,
+	// the Utf8 entries by pool index, for attribute names
+	utf8: Vec<Option<String>>,
+}
+
+impl Parser {
+	fn error(&self, message: impl Into<String>) -> IRClassfileError {
+		let line = self.tokens.get(self.pos).map_or(self.last_line, |(line, _)| *line);
+		error(line, message)
+	}
+
+	fn found(&self) -> String {
+		match self.tokens.get(self.pos) {
+			Some((_, Token::Word(word))) => word.clone(),
+			Some((_, Token::Str(value))) => format!("\"{}\"", escape(value)),
+			None => "the end".to_string(),
+		}
+	}
+
+	fn peek_word(&self) -> Option<&str> {
+		match self.tokens.get(self.pos) {
+			Some((_, Token::Word(word))) => Some(word),
+			_ => None,
+		}
+	}
+
+	fn word(&mut self, what: &str) -> Result<String, IRClassfileError> {
+		match self.peek_word() {
+			Some(word) => {
+				let word = word.to_string();
+				self.pos += 1;
+				Ok(word)
+			}
+			None => Err(self.error(format!("expected {what}, found {}", self.found()))),
+		}
+	}
+
+	fn label(&mut self, label: &str) -> Result<(), IRClassfileError> {
+		match label.is_empty() || self.peek_word() == Some(label) {
+			true => {
+				self.pos += !label.is_empty() as usize;
+				Ok(())
+			}
+			false => Err(self.error(format!("expected {label}, found {}", self.found()))),
+		}
+	}
+
+	// An integer in the range of `T`, which for the operands written as bytes is that of their width.
+	fn integer<T: TryFrom<i64>>(&mut self, what: &str) -> Result<T, IRClassfileError> {
+		let word = self.word(what)?;
+		parse_int(&word)
+			.and_then(|value| T::try_from(value).ok())
+			.ok_or_else(|| error(self.tokens[self.pos - 1].0, format!("expected {what}, found {word}")))
+	}
+
+	fn unsigned(&mut self, what: &str, width: usize) -> Result<u32, IRClassfileError> {
+		let value = self.integer::<u32>(what)?;
+		if width < 4 && value >> (width * 8) != 0 {
+			return Err(error(
+				self.tokens[self.pos - 1].0,
+				format!("{what} {value} doesn't fit in {width} bytes"),
+			));
+		}
+		self.out.extend(&value.to_be_bytes()[4 - width..]);
+		Ok(value)
+	}
+
+	fn pool_index(&mut self, what: &str) -> Result<u16, IRClassfileError> {
+		let word = self.word(what)?;
+		word.strip_prefix('#')
+			.and_then(|index| index.parse().ok())
+			.ok_or_else(|| error(self.tokens[self.pos - 1].0, format!("expected {what}, found {word}")))
+	}
+
+	// The bytes after a `bytes` keyword: their count and their hex, in as many words as it takes.
+	fn payload(&mut self) -> Result<Vec<u8>, IRClassfileError> {
+		let length = self.integer::<usize>("byte count")?;
+		let mut bytes = Vec::with_capacity(length);
+		while bytes.len() < length {
+			let word = self.word("hex bytes")?;
+			let digits = word.as_bytes();
+			if !digits.len().is_multiple_of(2) || bytes.len() + digits.len() / 2 > length {
+				return Err(error(
+					self.tokens[self.pos - 1].0,
+					format!("expected {length} bytes of hex"),
+				));
+			}
+			for pair in digits.chunks(2) {
+				let byte = std::str::from_utf8(pair)
+					.ok()
+					.and_then(|pair| u8::from_str_radix(pair, 16).ok());
+				bytes.push(byte.ok_or_else(|| error(self.tokens[self.pos - 1].0, format!("invalid hex {word}")))?);
+			}
+		}
+		Ok(bytes)
+	}
+
+	fn entry(&mut self, tag: u8) -> Result<PoolEntry, IRClassfileError> {
+		Ok(match tag {
+			1 => match self.tokens.get(self.pos) {
+				Some((_, Token::Str(text))) => {
+					let bytes = maya_mutf8::encode(text);
+					self.pos += 1;
+					if bytes.len() > u16::MAX as usize {
+						return Err(self.error("string too long for a Utf8 entry"));
+					}
+					PoolEntry::Utf8(bytes)
+				}
+				_ => {
+					self.label("bytes")?;
+					PoolEntry::Utf8(self.payload()?)
+				}
+			},
+			3 => PoolEntry::Integer(self.integer("int")?),
+			4 => PoolEntry::Float(match self.peek_word() == Some("bits") {
+				true => self.bits()?,
+				false => {
+					let word = self.word("float")?;
+					let value = word.parse::<f32>();
+					value
+						.map_err(|_| self.error(format!("expected float, found {word}")))?
+						.to_bits()
+				}
+			}),
+			5 => PoolEntry::Long(self.integer("long")?),
+			6 => PoolEntry::Double(match self.peek_word() == Some("bits") {
+				true => self.bits()?,
+				false => {
+					let word = self.word("double")?;
+					let value = word.parse::<f64>();
+					value
+						.map_err(|_| self.error(format!("expected double, found {word}")))?
+						.to_bits()
+				}
+			}),
+			7 | 8 | 16 | 19 | 20 => PoolEntry::Index(tag, self.pool_index("constant pool index")?),
+			9..=12 => PoolEntry::Pair(
+				tag,
+				self.pool_index("constant pool index")?,
+				self.pool_index("constant pool index")?,
+			),
+			17 | 18 => PoolEntry::Pair(
+				tag,
+				self.integer("bootstrap method index")?,
+				self.pool_index("constant pool index")?,
+			),
+			_ => {
+				let kind = self.word("reference kind")?;
+				let kind = value_of(REFERENCE_KINDS, &kind).or_else(|| kind.parse().ok());
+				let kind = kind.ok_or_else(|| error(self.tokens[self.pos - 1].0, "expected reference kind"))?;
+				PoolEntry::MethodHandle(kind, self.pool_index("constant pool index")?)
+			}
+		})
+	}
+
+	// `bits` and a float's or double's bits.
+	fn bits<T: TryFrom<i64>>(&mut self) -> Result<T, IRClassfileError> {
+		self.pos += 1;
+		let word = self.word("bits")?;
+		let bits = match word.strip_prefix("0x") {
+			Some(hex) => u64::from_str_radix(hex, 16).ok(),
+			None => word.parse().ok(),
+		};
+		bits.and_then(|bits| T::try_from(bits as i64).ok())
+			.ok_or_else(|| error(self.tokens[self.pos - 1].0, format!("expected bits, found {word}")))
+	}
+
+	fn instruction(&mut self, start: usize) -> Result<(), IRClassfileError> {
+		let pc_label = self.word("pc")?;
+		if !pc_label.ends_with(':') {
+			return Err(error(
+				self.tokens[self.pos - 1].0,
+				format!("expected pc, found {pc_label}"),
+			));
+		}
+		let opcode = self.opcode()?;
+		let pc = (self.out.len() - start) as i64;
+		self.out.push(opcode);
+		match opcode {
+			Opcodes::WIDE => {
+				let opcode = self.opcode()?;
+				if !is_wide(opcode) {
+					return Err(error(self.tokens[self.pos - 1].0, "wide can't modify this instruction"));
+				}
+				self.out.push(opcode);
+				self.unsigned("local", 2)?;
+				if opcode == Opcodes::IINC {
+					let value = self.integer::<i16>("increment")?;
+					self.out.extend(value.to_be_bytes());
+				}
+			}
+			Opcodes::TABLESWITCH | Opcodes::LOOKUPSWITCH => {
+				while !(self.out.len() - start).is_multiple_of(4) {
+					self.out.push(0);
+				}
+				self.label("default")?;
+				self.target::<i32>(pc)?;
+				if opcode == Opcodes::TABLESWITCH {
+					self.label("low")?;
+					let low = self.integer::<i32>("low")?;
+					self.label("high")?;
+					let high = self.integer::<i32>("high")?;
+					if high < low {
+						return Err(self.error(IRClassfileError::InvalidTableSwitch { low, high }.to_string()));
+					}
+					self.out.extend(low.to_be_bytes());
+					self.out.extend(high.to_be_bytes());
+					for _ in low..=high {
+						self.word("key")?;
+						self.target::<i32>(pc)?;
+					}
+				} else {
+					self.label("npairs")?;
+					let pairs = self.integer::<i32>("npairs")?;
+					self.out.extend(pairs.to_be_bytes());
+					for _ in 0..pairs {
+						let key = self.word("key")?;
+						let key = key
+							.strip_suffix(':')
+							.and_then(parse_int)
+							.and_then(|key| i32::try_from(key).ok());
+						let key = key.ok_or_else(|| error(self.tokens[self.pos - 1].0, "expected key"))?;
+						self.out.extend(key.to_be_bytes());
+						self.target::<i32>(pc)?;
+					}
+				}
+			}
+			_ => {
+				for operand in operands(opcode) {
+					match operand {
+						Operand::Local | Operand::Count | Operand::ArrayType => {
+							self.unsigned("operand", 1).map(drop)?
+						}
+						Operand::Byte => {
+							let value = self.integer::<i8>("byte")?;
+							self.out.push(value as u8);
+						}
+						Operand::Short => {
+							let value = self.integer::<i16>("short")?;
+							self.out.extend(value.to_be_bytes());
+						}
+						Operand::Index1 => {
+							let index = self.pool_index("constant pool index")?;
+							let index = u8::try_from(index).map_err(|_| self.error("ldc takes an index below 256"))?;
+							self.out.push(index);
+						}
+						Operand::Index2 => {
+							let index = self.pool_index("constant pool index")?;
+							self.out.extend(index.to_be_bytes());
+						}
+						Operand::Branch => self.target::<i16>(pc)?,
+						Operand::WideBranch => self.target::<i32>(pc)?,
+						Operand::Zero => self.out.push(0),
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn opcode(&mut self) -> Result<u8, IRClassfileError> {
+		let mnemonic = self.word("instruction")?;
+		(0..=u8::MAX)
+			.find(|opcode| Opcodes::mnemonic(*opcode) == Some(&mnemonic))
+			.ok_or_else(|| error(self.tokens[self.pos - 1].0, format!("unknown instruction {mnemonic}")))
+	}
+
+	// A branch target, written as the offset from `pc` in the range of `T`.
+	fn target<T: TryFrom<i64> + Into<i64>>(&mut self, pc: i64) -> Result<(), IRClassfileError> {
+		let target = self.integer::<i64>("branch target")?;
+		let offset = T::try_from(target - pc).map_err(|_| {
+			error(
+				self.tokens[self.pos - 1].0,
+				format!("branch target {target} is too far away"),
+			)
+		})?;
+		let offset: i64 = offset.into();
+		let width = std::mem::size_of::<T>();
+		self.out.extend(&offset.to_be_bytes()[8 - width..]);
+		Ok(())
+	}
+}
+
+impl Codec for Parser {
+	fn number(&mut self, label: &'static str, width: usize) -> Result<u32, IRClassfileError> {
+		self.label(label)?;
+		self.unsigned(if label.is_empty() { "number" } else { label }, width)
+	}
+
+	fn hex(&mut self, label: &'static str, width: usize) -> Result<u32, IRClassfileError> {
+		self.number(label, width)
+	}
+
+	fn index(&mut self, label: &'static str) -> Result<u16, IRClassfileError> {
+		self.label(label)?;
+		let index = self.pool_index("constant pool index")?;
+		self.out.extend(index.to_be_bytes());
+		Ok(index)
+	}
+
+	fn flags(&mut self, label: &'static str, _names: FlagNames) -> Result<u16, IRClassfileError> {
+		self.number(label, 2).map(|flags| flags as u16)
+	}
+
+	fn named(&mut self, label: &'static str, names: &[(u8, &'static str)]) -> Result<u8, IRClassfileError> {
+		self.label(label)?;
+		let word = self.word("name")?;
+		let value = value_of(names, &word).or_else(|| word.parse().ok());
+		let value = value.ok_or_else(|| error(self.tokens[self.pos - 1].0, format!("unexpected {word}")))?;
+		self.out.push(value);
+		Ok(value)
+	}
+
+	fn row<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, IRClassfileError>) -> Result<T, IRClassfileError> {
+		f(self)
+	}
+
+	fn nested(&mut self, f: impl FnOnce(&mut Self) -> Result<(), IRClassfileError>) -> Result<(), IRClassfileError> {
+		f(self)
+	}
+
+	fn sized(&mut self, f: impl FnOnce(&mut Self) -> Result<(), IRClassfileError>) -> Result<(), IRClassfileError> {
+		if self.peek_word() == Some("bytes") {
+			self.pos += 1;
+			let bytes = self.payload()?;
+			self.out.extend((bytes.len() as u32).to_be_bytes());
+			self.out.extend(bytes);
+			return Ok(());
+		}
+		let start = self.out.len();
+		self.out.extend([0; 4]);
+		f(self)?;
+		let length = (self.out.len() - start - 4) as u32;
+		self.out[start..start + 4].copy_from_slice(&length.to_be_bytes());
+		Ok(())
+	}
+
+	fn string(&mut self, label: &'static str) -> Result<(), IRClassfileError> {
+		self.label(label)?;
+		match self.tokens.get(self.pos) {
+			Some((_, Token::Str(text))) => {
+				self.out.extend(maya_mutf8::encode(text));
+				self.pos += 1;
+				Ok(())
+			}
+			_ => Err(self.error(format!("expected string, found {}", self.found()))),
+		}
+	}
+
+	fn constant_pool(&mut self) -> Result<(), IRClassfileError> {
+		let count = self.number("constant_pool_count", 2)? as usize;
+		self.utf8 = vec![None];
+		while self.utf8.len() < count {
+			let index = format!("#{}", self.utf8.len());
+			self.label(&index)?;
+			let tag = self.word("constant pool tag")?;
+			let tag = value_of(POOL_TAGS, &tag)
+				.ok_or_else(|| error(self.tokens[self.pos - 1].0, format!("unknown constant pool tag {tag}")))?;
+			let entry = self.entry(tag)?;
+			entry.write(&mut self.out);
+			self.utf8.push(match &entry {
+				PoolEntry::Utf8(bytes) => utf8_text(bytes),
+				_ => None,
+			});
+			if entry.slots() == 2 {
+				self.utf8.push(None);
+			}
+		}
+		Ok(())
+	}
+
+	fn code(&mut self) -> Result<(), IRClassfileError> {
+		let length = self.number("code_length", 4)? as usize;
+		if self.peek_word() == Some("bytes") {
+			self.pos += 1;
+			let bytes = self.payload()?;
+			if bytes.len() != length {
+				return Err(self.error(format!(
+					"code_length is {length}, but the code is {} bytes",
+					bytes.len()
+				)));
+			}
+			self.out.extend(bytes);
+			return Ok(());
+		}
+		let start = self.out.len();
+		while self.out.len() - start < length {
+			self.instruction(start)?;
+		}
+		match self.out.len() - start {
+			written if written == length => Ok(()),
+			written => Err(self.error(format!(
+				"code_length is {length}, but the instructions take {written} bytes"
+			))),
+		}
+	}
+
+	fn utf8(&self, index: u16) -> Option<String> {
+		self.utf8.get(index as usize)?.clone()
+	}
+
+	fn unknown_attribute(&self, name: Option<String>) -> IRClassfileError {
+		let name = name.map_or_else(
+			|| "without a name".to_string(),
+			|name| format!("{name} isn't one the JVMS defines"),
+		);
+		self.error(format!("attribute {name}, its payload has to be given as bytes"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::assemble;
+
+	#[test]
+	fn round_trip_text() {
+		let class = assemble(
+			r#"
+			.version 52
+			.class public super p/Text
+			.super java/lang/Object
+			.source "Text.java"
+			.attribute Custom 00ff
+
+			.field private static final LIMIT J = 10000000000L
+
+			.method public static run(I)I
+				.parameter final count
+				.annotation visible Lp/Marker;
+					value [ I 1 s "two" ]
+				.end annotation
+				.line 4
+				iload_0
+				tableswitch 0
+					Zero
+					default: Other
+			Zero:
+				ldc 1.5f
+				f2i
+				ireturn
+			Other:
+				ldc "café"
+				invokevirtual java/lang/String/length()I
+				ireturn
+			.end method
+			"#,
+		)
+		.unwrap();
+		let bytes = class.to_bytes().unwrap();
+
+		let text = to_text(&class).unwrap();
+		assert!(text.contains("  #1 Utf8 \"p/Text\"\n"));
+		assert!(text.contains("attribute_name_index #"));
+		assert!(text.contains("  ; Custom\n") && text.contains("bytes 2\n") && text.contains("00ff\n"));
+		assert!(text.contains("1: tableswitch default 24 low 0 high 0\n") && text.contains("  0: 20\n"));
+		assert!(text.contains("tag [ num_values 2"));
+		assert_eq!(text_to_bytes(&text).unwrap(), bytes);
+		assert_eq!(from_text(&text).unwrap().to_bytes().unwrap(), bytes);
+
+		// The same goes for a class whose code can't be decoded, down to its broken switch padding.
+		let mut broken = bytes.clone();
+		let code = broken
+			.windows(2)
+			.position(|pair| pair == [26, Opcodes::TABLESWITCH])
+			.unwrap();
+		broken[code + 2] = 0xff;
+		let text = bytes_to_text(&broken).unwrap();
+		assert!(text.contains("code_length 30\n") && text.contains("bytes 30\n"));
+		assert_eq!(text_to_bytes(&text).unwrap(), broken);
+
+		let err = text_to_bytes(&text.replacen("constant_pool_count", "constant_pool_size", 1)).unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"Line 4: expected constant_pool_count, found constant_pool_size"
+		);
+	}
+
+	fn wide_class() -> IRClassFile {
+		assemble(
+			r#"
+			.class public super p/Wide
+			.super java/lang/Object
+			.field static final BIG J = 10000000000L
+			.method static run()V
+				ldc2_w 2.5d
+				pop2
+				ldc "a\u0000b😀"
+				pop
+				ldc 7
+				pop
+				return
+			.end method
+			"#,
+		)
+		.unwrap()
+	}
+
+	// Longs and doubles take two pool slots, the second isn't written and the numbering skips it.
+	#[test]
+	fn wide_pool_entries() {
+		let class = wide_class();
+		let bytes = class.to_bytes().unwrap();
+		let text = to_text(&class).unwrap();
+		assert!(text.contains("  #7 Long 10000000000\n  #9 Utf8 \"ConstantValue\"\n"));
+		assert!(text.contains("  #13 Double 2.5\n  #15 String #10"));
+		assert!(text.contains("  #16 Integer 7\n"));
+		assert!(text.contains("0: ldc2_w #13  ; 2.5d\n"));
+		assert_eq!(text_to_bytes(&text).unwrap(), bytes);
+
+		// Giving the second slot a number of its own is an error rather than a shifted pool.
+		let err = text_to_bytes(&text.replace("#9 Utf8", "#8 Utf8")).unwrap_err();
+		assert_eq!(err.to_string(), "Line 12: expected #9, found #8");
+		let err = text_to_bytes(&text.replace("#7 Long 10000000000", "#7 Long 1.5")).unwrap_err();
+		assert_eq!(err.to_string(), "Line 11: expected long, found 1.5");
+		let err = text_to_bytes(&text.replace("#13 Double 2.5", "#13 Double two")).unwrap_err();
+		assert_eq!(err.to_string(), "Line 17: expected double, found two");
+	}
+
+	// NUL and supplementary characters are written the modified UTF-8 way, as 0xc0 0x80 and as a surrogate pair.
+	#[test]
+	fn modified_utf8_strings() {
+		let bytes = wide_class().to_bytes().unwrap();
+		let text = bytes_to_text(&bytes).unwrap();
+		assert!(text.contains("  #10 Utf8 \"a\\u0000b😀\"\n"));
+		assert!(text.contains("4: ldc #15  ; \"a\\u0000b😀\"\n"));
+		let encoded = [b'a', 0xc0, 0x80, b'b', 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80];
+		let written = text_to_bytes(&text).unwrap();
+		assert!(written.windows(encoded.len()).any(|window| window == encoded));
+		assert_eq!(written, bytes);
+
+		// The same text in standard UTF-8 isn't valid in a class file, so it's kept as bytes.
+		let entry = [&[1, 0, 10][..], &encoded].concat();
+		let start = bytes.windows(entry.len()).position(|window| window == entry).unwrap();
+		let mut standard = bytes[..start].to_vec();
+		standard.extend([1, 0, 7, b'a', 0, b'b', 0xf0, 0x9f, 0x98, 0x80]);
+		standard.extend(&bytes[start + entry.len()..]);
+		let text = bytes_to_text(&standard).unwrap();
+		assert!(text.contains("  #10 Utf8 bytes 7 610062f09f9880\n"));
+		assert_eq!(text_to_bytes(&text).unwrap(), standard);
+	}
+
+	#[test]
+	fn malformed_text() {
+		let text = to_text(&wide_class()).unwrap();
+		let err = |from: &str, to: &str| {
+			assert!(text.contains(from), "{from}");
+			text_to_bytes(&text.replacen(from, to, 1)).unwrap_err().to_string()
+		};
+
+		assert_eq!(err("\"p/Wide\"", "\"p/\\qWide\""), "Line 5: invalid escape \\q");
+		assert_eq!(err("\"p/Wide\"", "\"p/\\u00zzWide\""), "Line 5: invalid escape \\u00zz");
+		assert_eq!(err("\"p/Wide\"", "\"p/Wide"), "Line 5: unterminated string");
+		assert_eq!(err("#7 Long", "#7 Quad"), "Line 11: unknown constant pool tag Quad");
+		assert_eq!(err("ldc2_w", "ldc3_w"), "Line 36: unknown instruction ldc3_w");
+		assert_eq!(
+			err("fields_count", "field_count"),
+			"Line 24: expected fields_count, found field_count"
+		);
+		assert_eq!(
+			err("Class #1", "Class #70000"),
+			"Line 6: expected constant pool index, found #70000"
+		);
+		assert_eq!(
+			err("Class #1", "Class 1"),
+			"Line 6: expected constant pool index, found 1"
+		);
+
+		// An index past the end of the pool lays out fine, but the class it makes doesn't read.
+		let past = text.replacen("this_class #2", "this_class #200", 1);
+		assert!(text_to_bytes(&past).is_ok());
+		assert!(from_text(&past).is_err());
+	}
+}
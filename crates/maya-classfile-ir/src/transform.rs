@@ -0,0 +1,385 @@
+use std::time::Duration;
+
+use crate::{
+	class_pool::IRClassfileError,
+	code::Instructions,
+	json::{self, JsonObject},
+	metrics::{self, PassMetrics, PipelineMetrics},
+	IRClassFile,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeRecord {
+	Renamed { from: String, to: String },
+	RemovedMethod { name: String, descriptor: String },
+	RemovedField { name: String, descriptor: String },
+	// `owner` is the member the attribute was attached to, or the class itself.
+	RemovedAttribute { owner: String, name: String },
+	InsertedInstructions { method: String, pc: u32, count: usize },
+	RemovedInstructions { method: String, pc: u32, count: usize },
+	// For changes a user transform can't express with the variants above.
+	Custom { kind: String, detail: String },
+}
+
+impl ChangeRecord {
+	pub fn kind(&self) -> &str {
+		match self {
+			Self::Renamed { .. } => "renamed",
+			Self::RemovedMethod { .. } => "removed_method",
+			Self::RemovedField { .. } => "removed_field",
+			Self::RemovedAttribute { .. } => "removed_attribute",
+			Self::InsertedInstructions { .. } => "inserted_instructions",
+			Self::RemovedInstructions { .. } => "removed_instructions",
+			Self::Custom { kind, .. } => kind,
+		}
+	}
+
+	fn write_json(&self, obj: &mut JsonObject) {
+		obj.str("kind", self.kind());
+		match self {
+			Self::Renamed { from, to } => {
+				obj.str("from", from).str("to", to);
+			}
+			Self::RemovedMethod { name, descriptor } | Self::RemovedField { name, descriptor } => {
+				obj.str("name", name).str("descriptor", descriptor);
+			}
+			Self::RemovedAttribute { owner, name } => {
+				obj.str("owner", owner).str("name", name);
+			}
+			Self::InsertedInstructions { method, pc, count } | Self::RemovedInstructions { method, pc, count } => {
+				obj.str("method", method).num("pc", pc).num("count", count);
+			}
+			Self::Custom { kind: _, detail } => {
+				obj.str("detail", detail);
+			}
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEntry {
+	pub pass: String,
+	pub class: String,
+	pub change: ChangeRecord,
+}
+
+/// Audit trail of everything the transforms of a pipeline changed.
+/// A disabled log drops records, so transforms can record unconditionally.
+#[derive(Debug, Clone)]
+pub struct ChangeLog {
+	enabled: bool,
+	pass: String,
+	class: String,
+	entries: Vec<ChangeEntry>,
+}
+
+impl Default for ChangeLog {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ChangeLog {
+	pub fn new() -> Self {
+		Self {
+			enabled: true,
+			pass: String::new(),
+			class: String::new(),
+			entries: Vec::new(),
+		}
+	}
+
+	pub fn disabled() -> Self {
+		Self {
+			enabled: false,
+			..Self::new()
+		}
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		self.enabled
+	}
+
+	/// Sets the pass and class subsequent records are attributed to.
+	pub fn begin(&mut self, pass: &str, class: &str) {
+		pass.clone_into(&mut self.pass);
+		class.clone_into(&mut self.class);
+	}
+
+	pub fn record(&mut self, change: ChangeRecord) {
+		if !self.enabled {
+			return;
+		}
+
+		self.entries.push(ChangeEntry {
+			pass: self.pass.clone(),
+			class: self.class.clone(),
+			change,
+		});
+	}
+
+	/// Records how the code of `method` changed, as runs of removed instructions at their pc in `before` and runs of
+	/// inserted ones at their pc in `after`. Instructions are matched up by opcode, one that only got a different
+	/// operand is neither.
+	pub fn record_code_changes(&mut self, method: &str, before: &[(u32, Instructions)], after: &[(u32, Instructions)]) {
+		if !self.enabled {
+			return;
+		}
+
+		let mut removed = (0, 0);
+		let mut inserted = (0, 0);
+		let flush = |log: &mut Self, removed: &mut (usize, usize), inserted: &mut (usize, usize)| {
+			if removed.1 > 0 {
+				log.record(ChangeRecord::RemovedInstructions {
+					method: method.to_string(),
+					pc: before[removed.0].0,
+					count: removed.1,
+				});
+			}
+			if inserted.1 > 0 {
+				log.record(ChangeRecord::InsertedInstructions {
+					method: method.to_string(),
+					pc: after[inserted.0].0,
+					count: inserted.1,
+				});
+			}
+			*removed = (0, 0);
+			*inserted = (0, 0);
+		};
+		for step in diff_opcodes(before, after) {
+			match step {
+				DiffStep::Keep => flush(self, &mut removed, &mut inserted),
+				DiffStep::Remove(i) => {
+					if removed.1 == 0 {
+						removed.0 = i;
+					}
+					removed.1 += 1;
+				}
+				DiffStep::Insert(j) => {
+					if inserted.1 == 0 {
+						inserted.0 = j;
+					}
+					inserted.1 += 1;
+				}
+			}
+		}
+		flush(self, &mut removed, &mut inserted);
+	}
+
+	pub fn entries(&self) -> &[ChangeEntry] {
+		&self.entries
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	pub fn extend(&mut self, other: ChangeLog) {
+		if self.enabled {
+			self.entries.extend(other.entries);
+		}
+	}
+
+	pub fn to_json(&self) -> String {
+		json::array(&self.entries, |entry, out| {
+			let mut obj = JsonObject::new(out);
+			obj.str("pass", &entry.pass).str("class", &entry.class);
+			entry.change.write_json(&mut obj);
+			obj.end();
+		})
+	}
+}
+
+enum DiffStep {
+	Keep,
+	Remove(usize),
+	Insert(usize),
+}
+
+/// Above this many cells the table of the longest common subsequence isn't built, and whatever differs after the
+/// common start and end counts as removed and inserted whole.
+const MAX_DIFF_CELLS: usize = 1 << 22;
+
+// The steps turning `before` into `after`, keeping their longest common subsequence of opcodes.
+fn diff_opcodes(before: &[(u32, Instructions)], after: &[(u32, Instructions)]) -> Vec<DiffStep> {
+	let same = |i: usize, j: usize| before[i].1.opcode() == after[j].1.opcode();
+	let prefix = (0..before.len().min(after.len())).take_while(|&i| same(i, i)).count();
+	let suffix = (0..before.len().min(after.len()) - prefix)
+		.take_while(|&k| same(before.len() - 1 - k, after.len() - 1 - k))
+		.count();
+	let (n, m) = (before.len() - prefix - suffix, after.len() - prefix - suffix);
+
+	let mut steps = Vec::with_capacity(before.len() + after.len());
+	steps.extend((0..prefix).map(|_| DiffStep::Keep));
+	if n.saturating_mul(m) > MAX_DIFF_CELLS {
+		steps.extend((prefix..prefix + n).map(DiffStep::Remove));
+		steps.extend((prefix..prefix + m).map(DiffStep::Insert));
+	} else {
+		// lengths[i][j] is the longest common subsequence of the middles from i and j on
+		let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+		let at = |i: usize, j: usize| i * (m + 1) + j;
+		for i in (0..n).rev() {
+			for j in (0..m).rev() {
+				lengths[at(i, j)] = match same(prefix + i, prefix + j) {
+					true => lengths[at(i + 1, j + 1)] + 1,
+					false => lengths[at(i + 1, j)].max(lengths[at(i, j + 1)]),
+				};
+			}
+		}
+		let (mut i, mut j) = (0, 0);
+		while i < n || j < m {
+			if i < n && j < m && same(prefix + i, prefix + j) {
+				steps.push(DiffStep::Keep);
+				(i, j) = (i + 1, j + 1);
+			} else if j == m || (i < n && lengths[at(i + 1, j)] >= lengths[at(i, j + 1)]) {
+				steps.push(DiffStep::Remove(prefix + i));
+				i += 1;
+			} else {
+				steps.push(DiffStep::Insert(prefix + j));
+				j += 1;
+			}
+		}
+	}
+	steps.extend((0..suffix).map(|_| DiffStep::Keep));
+	steps
+}
+
+pub trait Transform {
+	fn name(&self) -> &str;
+
+	fn apply(&mut self, class: &mut IRClassFile, log: &mut ChangeLog) -> Result<(), IRClassfileError>;
+}
+
+/// Runs transforms in order over every class handed to it, aggregating their change records.
+pub struct Pipeline {
+	passes: Vec<Box<dyn Transform>>,
+	log: ChangeLog,
+	metrics: PipelineMetrics,
+}
+
+impl Default for Pipeline {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Pipeline {
+	pub fn new() -> Self {
+		Self {
+			passes: Vec::new(),
+			log: ChangeLog::disabled(),
+			metrics: PipelineMetrics::new(),
+		}
+	}
+
+	pub fn with<T: Transform + 'static>(mut self, pass: T) -> Self {
+		self.metrics.passes.push(PassMetrics {
+			name: pass.name().to_string(),
+			..PassMetrics::default()
+		});
+		self.passes.push(Box::new(pass));
+		self
+	}
+
+	pub fn record_changes(mut self, record: bool) -> Self {
+		self.log = if record {
+			ChangeLog::new()
+		} else {
+			ChangeLog::disabled()
+		};
+		self
+	}
+
+	pub fn run(&mut self, class: &mut IRClassFile) -> Result<(), IRClassfileError> {
+		let initial = metrics::method_fingerprints(class);
+		let mut before = initial.clone();
+		for (pass, pass_metrics) in self.passes.iter_mut().zip(&mut self.metrics.passes) {
+			self.log.begin(pass.name(), &class.this_class.data.data);
+			let (applied, elapsed) = timed(|| pass.apply(class, &mut self.log));
+			applied?;
+			pass_metrics.wall_time += elapsed;
+
+			let after = metrics::method_fingerprints(class);
+			let changed = metrics::changed_methods(&before, &after);
+			if changed != 0 {
+				pass_metrics.classes_changed += 1;
+				pass_metrics.methods_changed += changed;
+			}
+			before = after;
+		}
+
+		self.metrics.classes_processed += 1;
+		self.metrics.methods_transformed += metrics::changed_methods(&initial, &before);
+		Ok(())
+	}
+
+	pub fn metrics(&self) -> &PipelineMetrics {
+		&self.metrics
+	}
+
+	/// For the parts of the metrics only the caller knows, like byte sizes and verifier warnings.
+	pub fn metrics_mut(&mut self) -> &mut PipelineMetrics {
+		&mut self.metrics
+	}
+
+	pub fn changes(&self) -> &ChangeLog {
+		&self.log
+	}
+
+	pub fn take_changes(&mut self) -> ChangeLog {
+		let fresh = if self.log.is_enabled() {
+			ChangeLog::new()
+		} else {
+			ChangeLog::disabled()
+		};
+		std::mem::replace(&mut self.log, fresh)
+	}
+}
+
+// wasm32-unknown-unknown has no clock, `Instant::now` panics there, so passes go untimed.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+	let start = std::time::Instant::now();
+	let out = f();
+	(out, start.elapsed())
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+	(f(), Duration::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn change_log_json() {
+		let mut log = ChangeLog::new();
+		log.begin("rename", "a/B");
+		log.record(ChangeRecord::Renamed {
+			from: "a/B".into(),
+			to: "c/\"D\"".into(),
+		});
+		log.record(ChangeRecord::InsertedInstructions {
+			method: "run()V".into(),
+			pc: 4,
+			count: 2,
+		});
+
+		assert_eq!(
+			log.to_json(),
+			r#"[{"pass":"rename","class":"a/B","kind":"renamed","from":"a/B","to":"c/\"D\""},{"pass":"rename","class":"a/B","kind":"inserted_instructions","method":"run()V","pc":4,"count":2}]"#
+		);
+	}
+
+	#[test]
+	fn disabled_log_drops_records() {
+		let mut log = ChangeLog::disabled();
+		log.record(ChangeRecord::RemovedField {
+			name: "x".into(),
+			descriptor: "I".into(),
+		});
+		assert!(log.is_empty());
+	}
+}
@@ -0,0 +1,195 @@
+// What a class file version means: the Java release that introduced it and what the JVM allows in classes of that
+// version. The minor version has been 0 since Java 1.2, except 0xFFFF marking a class that uses the preview features of
+// exactly its release, which the JVM only loads with --enable-preview.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.1-200-B.2
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7-310
+
+use std::fmt::{self, Display};
+
+use crate::{class_pool::IRCpTag, ClassFileVersion};
+
+macro_rules! since {
+	($($(#[$meta:meta])* $query:ident => $major:literal),* $(,)?) => {
+		$(
+			$(#[$meta])*
+			pub const fn $query(&self) -> bool {
+				self.major >= $major
+			}
+		)*
+	};
+}
+
+impl ClassFileVersion {
+	pub const PREVIEW_MINOR: u16 = 0xFFFF;
+
+	pub const JAVA_5: Self = Self::release(5);
+	pub const JAVA_6: Self = Self::release(6);
+	pub const JAVA_7: Self = Self::release(7);
+	pub const JAVA_8: Self = Self::release(8);
+	pub const JAVA_11: Self = Self::release(11);
+	pub const JAVA_17: Self = Self::release(17);
+	pub const JAVA_21: Self = Self::release(21);
+
+	/// The version javac targets for `--release release`, which starts at 5.
+	pub const fn release(release: u16) -> Self {
+		Self {
+			major: release + 44,
+			minor: 0,
+		}
+	}
+
+	/// The Java release this version came with, `1.1` to `1.4` and then `5` onwards. `None` before 45, which no
+	/// release ever used.
+	pub fn release_name(&self) -> Option<String> {
+		match self.major {
+			45..=48 => Some(format!("1.{}", self.major - 44)),
+			49.. => Some((self.major - 44).to_string()),
+			_ => None,
+		}
+	}
+
+	/// Whether the class needs `--enable-preview`, only possible from Java 12 on.
+	pub const fn is_preview(&self) -> bool {
+		self.minor == Self::PREVIEW_MINOR && self.major >= 56
+	}
+
+	since! {
+		/// Classes may carry StackMapTable frames and are verified by type checking when they do.
+		supports_stack_map_table => 50,
+		/// Methods with code must have frames, inference is only a fallback before.
+		requires_stack_map_table => 51,
+		/// `invokedynamic`, and MethodHandle and MethodType constants.
+		supports_invokedynamic => 51,
+		/// Interfaces may have default and static methods, which `invokestatic`, `invokespecial` and method handles
+		/// can refer to.
+		supports_default_methods => 52,
+		supports_modules => 53,
+		/// Dynamic constants, `ldc` of a value computed by a bootstrap method.
+		supports_constant_dynamic => 55,
+		supports_nestmates => 55,
+		supports_records => 60,
+		supports_sealed_classes => 61,
+	}
+
+	/// `jsr` and `ret`, which can't be verified by type checking and were dropped with it.
+	pub const fn supports_jsr(&self) -> bool {
+		self.major < 51
+	}
+
+	/// The first version a predefined attribute is recognized in, `None` for the ones the JVMS doesn't define. Before
+	/// it, the JVM ignores the attribute as it does any it doesn't know.
+	pub fn attribute_since(name: &str) -> Option<Self> {
+		let major = match name {
+			"ConstantValue" | "Code" | "Exceptions" | "SourceFile" | "LineNumberTable" | "LocalVariableTable"
+			| "InnerClasses" | "Synthetic" | "Deprecated" => 45,
+			"EnclosingMethod"
+			| "Signature"
+			| "SourceDebugExtension"
+			| "LocalVariableTypeTable"
+			| "RuntimeVisibleAnnotations"
+			| "RuntimeInvisibleAnnotations"
+			| "RuntimeVisibleParameterAnnotations"
+			| "RuntimeInvisibleParameterAnnotations"
+			| "AnnotationDefault" => 49,
+			"StackMapTable" => 50,
+			"BootstrapMethods" => 51,
+			"RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" | "MethodParameters" => 52,
+			"Module" | "ModulePackages" | "ModuleMainClass" => 53,
+			"NestHost" | "NestMembers" => 55,
+			"Record" => 60,
+			"PermittedSubclasses" => 61,
+			_ => return None,
+		};
+		// 45.3 for the oldest ones strictly speaking, but nothing older than 45.3 was ever released.
+		Some(Self { major, minor: 0 })
+	}
+
+	/// Whether an attribute called `name` means anything in a class of this version, always for non-standard ones.
+	pub fn supports_attribute(&self, name: &str) -> bool {
+		Self::attribute_since(name).is_none_or(|since| self.major >= since.major)
+	}
+
+	/// The first version a constant pool entry like `tag` is allowed in, `None` for the ones every version allows.
+	pub fn tag_since(tag: &IRCpTag) -> Option<Self> {
+		match tag {
+			IRCpTag::MethodHandle { .. } | IRCpTag::MethodType(_) | IRCpTag::InvokeDynamic { .. } => Some(Self::JAVA_7),
+			IRCpTag::Module { .. } | IRCpTag::Package { .. } => Some(Self::release(9)),
+			_ => None,
+		}
+	}
+}
+
+impl Display for ClassFileVersion {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}.{}", self.major, self.minor)?;
+		if let Some(release) = self.release_name() {
+			write!(f, " (Java {release}")?;
+			if self.is_preview() {
+				write!(f, " preview")?;
+			}
+			write!(f, ")")?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		attribute::IRAttribute,
+		builder::ClassBuilder,
+		class_pool::CPClassRef,
+		parse::{ParseOptions, ParseWarning},
+		IRClassFile,
+	};
+
+	#[test]
+	fn releases_and_capabilities() {
+		let java_1_4 = ClassFileVersion { major: 48, minor: 0 };
+		let preview = ClassFileVersion {
+			major: 65,
+			minor: ClassFileVersion::PREVIEW_MINOR,
+		};
+		assert_eq!(java_1_4.release_name().as_deref(), Some("1.4"));
+		assert_eq!(ClassFileVersion::JAVA_17, ClassFileVersion { major: 61, minor: 0 });
+		assert_eq!(ClassFileVersion::JAVA_8.to_string(), "52.0 (Java 8)");
+		assert_eq!(preview.to_string(), "65.65535 (Java 21 preview)");
+		assert_eq!(ClassFileVersion { major: 40, minor: 0 }.to_string(), "40.0");
+		assert!(
+			preview.is_preview()
+				&& !ClassFileVersion {
+					major: 50,
+					minor: 0xFFFF
+				}
+				.is_preview()
+		);
+		assert!(ClassFileVersion::JAVA_21 < preview && preview < ClassFileVersion::release(22));
+
+		assert!(java_1_4.supports_jsr() && !java_1_4.supports_stack_map_table());
+		assert!(ClassFileVersion::JAVA_6.supports_stack_map_table());
+		assert!(!ClassFileVersion::JAVA_6.requires_stack_map_table());
+		assert!(ClassFileVersion::JAVA_7.supports_invokedynamic() && !ClassFileVersion::JAVA_7.supports_jsr());
+		assert!(ClassFileVersion::JAVA_11.supports_nestmates() && !ClassFileVersion::JAVA_11.supports_records());
+		assert!(ClassFileVersion::JAVA_17.supports_sealed_classes());
+
+		assert!(!ClassFileVersion::JAVA_5.supports_attribute("StackMapTable"));
+		assert!(ClassFileVersion::JAVA_5.supports_attribute("Signature"));
+		assert!(!ClassFileVersion::JAVA_8.supports_attribute("Module"));
+		assert!(java_1_4.supports_attribute("CompilationID"));
+
+		// A NestHost in a Java 8 class means nothing to the JVM.
+		let mut builder = ClassBuilder::new("p/Old$Inner").unwrap();
+		let host = CPClassRef::find_or_add(builder.cp(), "p/Old").unwrap();
+		builder.version(ClassFileVersion::JAVA_8);
+		builder.attribute(IRAttribute::NestHost(host)).unwrap();
+		let bytes = builder.build().to_bytes().unwrap();
+		let (class, warnings) = IRClassFile::read_with(&bytes, &ParseOptions::default()).unwrap();
+		assert!(matches!(class.attributes[0].attr, IRAttribute::Unknown(_)));
+		assert!(matches!(
+			&warnings[..],
+			[ParseWarning::UnsupportedAttribute { name, version }] if name == "NestHost" && *version == ClassFileVersion::JAVA_8
+		));
+		assert_eq!(class.to_bytes().unwrap(), bytes);
+	}
+}
@@ -0,0 +1,146 @@
+// A JavaScript facade over the parser, for browser tools built with wasm-bindgen for wasm32-unknown-unknown. The IR's
+// types don't cross into JavaScript, so a class is an opaque `ClassFile` handle with getters for its names and members
+// and the text forms the crate already prints. Errors become `Error`s with the message the Rust error would print.
+//
+// Nothing here touches the filesystem: classes come in and go out as bytes or text, e.g. from a `File` the page read.
+//
+//   const class = new ClassFile(new Uint8Array(await file.arrayBuffer()));
+//   console.log(class.className, class.methods, class.disassemble());
+
+use wasm_bindgen::prelude::*;
+
+use crate::{asm, class_pool::IRClassfileError, disasm, text, IRClassFile};
+
+fn js_error(err: IRClassfileError) -> JsError {
+	JsError::new(&err.to_string())
+}
+
+#[wasm_bindgen(js_name = ClassFile)]
+pub struct JsClassFile {
+	class: IRClassFile,
+}
+
+#[wasm_bindgen(js_class = ClassFile)]
+impl JsClassFile {
+	/// Parses a class file, strictly and with the default limits.
+	#[wasm_bindgen(constructor)]
+	pub fn new(bytes: &[u8]) -> Result<JsClassFile, JsError> {
+		IRClassFile::read(bytes).map(Self::from).map_err(js_error)
+	}
+
+	/// Assembles a class from the `asm` syntax.
+	pub fn assemble(source: &str) -> Result<JsClassFile, JsError> {
+		asm::assemble(source).map(Self::from).map_err(js_error)
+	}
+
+	/// Reads a class back from the text form `toText` gives.
+	#[wasm_bindgen(js_name = fromText)]
+	pub fn from_text(source: &str) -> Result<JsClassFile, JsError> {
+		text::from_text(source).map(Self::from).map_err(js_error)
+	}
+
+	#[wasm_bindgen(getter, js_name = className)]
+	pub fn class_name(&self) -> String {
+		self.class.class_name().to_string()
+	}
+
+	/// `undefined` for java/lang/Object and module-info.
+	#[wasm_bindgen(getter, js_name = superName)]
+	pub fn super_name(&self) -> Option<String> {
+		self.class.super_name().map(str::to_string)
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn interfaces(&self) -> Vec<String> {
+		self.class.interface_names().map(str::to_string).collect()
+	}
+
+	/// e.g. `61.0 (Java 17)`.
+	#[wasm_bindgen(getter)]
+	pub fn version(&self) -> String {
+		self.class.version.to_string()
+	}
+
+	/// Each as `name:descriptor`.
+	#[wasm_bindgen(getter)]
+	pub fn fields(&self) -> Vec<String> {
+		self.class
+			.fields
+			.iter()
+			.map(|field| format!("{}:{}", field.name(), field.descriptor()))
+			.collect()
+	}
+
+	/// Each as its name followed by its descriptor, e.g. `run()I`.
+	#[wasm_bindgen(getter)]
+	pub fn methods(&self) -> Vec<String> {
+		self.class
+			.methods
+			.iter()
+			.map(|method| format!("{}{}", method.name(), method.descriptor()))
+			.collect()
+	}
+
+	/// The javap-style listing.
+	pub fn disassemble(&self) -> Result<String, JsError> {
+		disasm::disassemble(&self.class).map_err(js_error)
+	}
+
+	#[wasm_bindgen(js_name = toText)]
+	pub fn to_text(&self) -> Result<String, JsError> {
+		text::to_text(&self.class).map_err(js_error)
+	}
+
+	/// The resolved JSON view of `export`.
+	#[cfg(feature = "serde")]
+	#[wasm_bindgen(js_name = toJson)]
+	pub fn to_json(&self) -> Result<String, JsError> {
+		self.class.to_json().map_err(js_error)
+	}
+
+	#[wasm_bindgen(js_name = toBytes)]
+	pub fn to_bytes(&self) -> Result<Vec<u8>, JsError> {
+		self.class.to_bytes().map_err(js_error)
+	}
+}
+
+impl From<IRClassFile> for JsClassFile {
+	fn from(class: IRClassFile) -> Self {
+		Self { class }
+	}
+}
+
+impl JsClassFile {
+	pub fn class(&self) -> &IRClassFile {
+		&self.class
+	}
+
+	pub fn into_class(self) -> IRClassFile {
+		self.class
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Only the success paths, building a `JsError` needs a JavaScript host.
+	#[test]
+	fn wrap_a_class() {
+		let class = JsClassFile::assemble(
+			".class public super p/Main\n.implements java/lang/Runnable\n.field static count I\n.method public \
+			 run()V\nreturn\n.end method",
+		)
+		.unwrap();
+		let bytes = class.to_bytes().unwrap();
+		let class = JsClassFile::new(&bytes).unwrap();
+		assert_eq!(class.class_name(), "p/Main");
+		assert_eq!(class.super_name().as_deref(), Some("java/lang/Object"));
+		assert_eq!(class.interfaces(), ["java/lang/Runnable"]);
+		assert_eq!(class.fields(), ["count:I"]);
+		assert_eq!(class.methods(), ["run()V"]);
+		assert!(class.disassemble().unwrap().contains("public void run();"));
+		let text = class.to_text().unwrap();
+		assert_eq!(JsClassFile::from_text(&text).unwrap().to_bytes().unwrap(), bytes);
+	}
+}
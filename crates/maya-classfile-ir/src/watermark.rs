@@ -0,0 +1,161 @@
+// Provenance markers: build metadata embedded into classes as a custom attribute, so a class found in the wild can be
+// traced back to the build that produced it. The JVM ignores attributes it doesn't know, so the marker has no runtime effect.
+//
+// Payload layout, all integers big endian:
+//   u8 version (1)
+//   u16 entry count
+//   entries: u16 key length, key as UTF-8, u16 value length, value as UTF-8
+
+use std::{collections::BTreeMap, io::Cursor};
+
+use maya_bytes::BytesReadExt;
+use maya_classfile_io::limits::Limits;
+
+use crate::{
+	attribute::{IRAttribute, IRAttributeInfo},
+	class_pool::{CPUtf8Ref, IRClassfileError},
+	transform::{ChangeLog, ChangeRecord, Transform},
+	IRClassFile,
+};
+
+pub const ATTRIBUTE_NAME: &str = "MayaProvenance";
+const VERSION: u8 = 1;
+
+/// Key/value build metadata, e.g. `commit`, `builder` or `timestamp`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+	pub entries: BTreeMap<String, String>,
+}
+
+impl Provenance {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn with(mut self, key: &str, value: &str) -> Self {
+		self.entries.insert(key.to_string(), value.to_string());
+		self
+	}
+
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.entries.get(key).map(String::as_str)
+	}
+
+	pub fn encode(&self) -> Result<Vec<u8>, IRClassfileError> {
+		let mut out = vec![VERSION];
+		push_len(&mut out, "provenance entries", self.entries.len())?;
+		for (key, value) in &self.entries {
+			for text in [key, value] {
+				push_len(&mut out, "provenance entry length", text.len())?;
+				out.extend_from_slice(text.as_bytes());
+			}
+		}
+		Ok(out)
+	}
+
+	pub fn decode(payload: &[u8]) -> Result<Self, IRClassfileError> {
+		let mut buffer = Cursor::new(payload);
+		let version = buffer.read_u8()?;
+		if version != VERSION {
+			return Err(IRClassfileError::InvalidTag {
+				kind: "provenance version",
+				value: version,
+			});
+		}
+
+		let mut entries = BTreeMap::new();
+		for _ in 0..buffer.read_u16()? {
+			let key = read_string(&mut buffer)?;
+			let value = read_string(&mut buffer)?;
+			entries.insert(key, value);
+		}
+		Ok(Self { entries })
+	}
+
+	/// Adds the marker to `class`, replacing any marker it already had.
+	pub fn embed(&self, class: &mut IRClassFile) -> Result<(), IRClassfileError> {
+		let payload = self.encode()?;
+		let name = CPUtf8Ref::find_or_add(&mut class.cp, ATTRIBUTE_NAME)?;
+		class
+			.attributes
+			.retain(|attr| attr.name.data.as_str() != ATTRIBUTE_NAME);
+		class.attributes.push(IRAttributeInfo {
+			name,
+			length: payload.len() as u32,
+			attr: IRAttribute::Unknown(payload),
+		});
+		Ok(())
+	}
+
+	/// The marker of `class`, `None` if it has none.
+	pub fn extract(class: &IRClassFile) -> Option<Result<Self, IRClassfileError>> {
+		class.attributes.iter().find_map(|attr| match &attr.attr {
+			IRAttribute::Unknown(payload) if attr.name.data.as_str() == ATTRIBUTE_NAME => Some(Self::decode(payload)),
+			_ => None,
+		})
+	}
+}
+
+fn push_len(out: &mut Vec<u8>, what: &'static str, len: usize) -> Result<(), IRClassfileError> {
+	Limits::check(what, u64::from(u16::MAX), len as u64)?;
+	out.extend_from_slice(&(len as u16).to_be_bytes());
+	Ok(())
+}
+
+fn read_string(buffer: &mut Cursor<&[u8]>) -> Result<String, IRClassfileError> {
+	let len = buffer.read_u16()? as usize;
+	Ok(String::from_utf8(buffer.read_vec(len)?)?)
+}
+
+/// Embeds the same provenance marker into every class it runs over.
+pub struct Watermark {
+	pub provenance: Provenance,
+}
+
+impl Transform for Watermark {
+	fn name(&self) -> &str {
+		"watermark"
+	}
+
+	fn apply(&mut self, class: &mut IRClassFile, log: &mut ChangeLog) -> Result<(), IRClassfileError> {
+		self.provenance.embed(class)?;
+		log.record(ChangeRecord::Custom {
+			kind: "watermarked".to_string(),
+			detail: format!("{} entries", self.provenance.entries.len()),
+		});
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use maya_classfile_io::IOAttributeInfo;
+
+	use super::*;
+	use crate::parse::{ParseContext, ParseOptions};
+
+	#[test]
+	fn round_trip() {
+		let provenance = Provenance::new().with("commit", "4a0e92d").with("builder", "ci-7");
+		let payload = provenance.encode().unwrap();
+		assert_eq!(Provenance::decode(&payload).unwrap(), provenance);
+		assert!(Provenance::decode(&payload[..payload.len() - 1]).is_err());
+		assert!(Provenance::decode(&[2, 0, 0]).is_err());
+	}
+
+	#[test]
+	fn strict_parsing_keeps_the_marker() {
+		let payload = Provenance::new().with("commit", "4a0e92d").encode().unwrap();
+		let mut cp = Vec::new();
+		let name = CPUtf8Ref::find_or_add(&mut cp, ATTRIBUTE_NAME).unwrap();
+		let raw = IOAttributeInfo {
+			attribute_name_index: name.index,
+			attribute_length: payload.len() as u32,
+			info: payload.clone(),
+		};
+
+		let options = ParseOptions::default();
+		let attr = IRAttributeInfo::from_io(&mut ParseContext::new(&cp, &options), raw).unwrap();
+		assert!(matches!(&attr.attr, IRAttribute::Unknown(bytes) if *bytes == payload));
+	}
+}
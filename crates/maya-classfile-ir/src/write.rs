@@ -0,0 +1,860 @@
+// Serializing the IR back into a class file, the counterpart of `IRClassFile::from_io`. Every reference carries its
+// constant pool index, so the pool is written exactly as it is, entries added with the `find_or_add` helpers included.
+// Attribute lengths are recomputed from the payload, the `length` kept from parsing is ignored.
+
+use maya_bytes::BytesWriteExt;
+use maya_classfile_io::{limits::Limits, IOAttributeInfo, IOClassFile, IOFieldInfo, IOMethodInfo};
+
+use crate::{
+	attribute::{
+		Attributes, BootstrapMethodsMethod, CodeAttribute, ConstantValueAttribute, IRAttribute, IRAttributeInfo,
+		RuntimeAnnotation, RuntimeAnnotationValue, RuntimeTypeAnnotation, RuntimeTypeAnnotationTargetInfo,
+		StackMapFrame, VerificationTypeInfo,
+	},
+	class_pool::{CPClassRef, IRClassfileError, IRCpTag, PoolIndex},
+	code::{Instructions, Opcodes},
+	IRClassFile, IRFieldInfo, IRMethodInfo,
+};
+
+impl IRClassFile {
+	pub fn to_io(&self) -> Result<IOClassFile, IRClassfileError> {
+		Limits::check("constant pool entries", u64::from(u16::MAX - 1), self.cp.len() as u64)?;
+		let cp = self.cp.iter().map(|tag| tag.to_io()).collect::<Result<Vec<_>, _>>()?;
+		let interfaces = self.interfaces.iter().map(|class| class.index).collect::<Vec<_>>();
+		let fields = self
+			.fields
+			.iter()
+			.map(IRFieldInfo::to_io)
+			.collect::<Result<Vec<_>, _>>()?;
+		let methods = self
+			.methods
+			.iter()
+			.map(IRMethodInfo::to_io)
+			.collect::<Result<Vec<_>, _>>()?;
+		let attributes = attributes_to_io(&self.attributes)?;
+
+		Ok(IOClassFile {
+			magic: self.magic,
+			minor_version: self.version.minor,
+			major_version: self.version.major,
+			cp_count: cp.len() as u16 + 1,
+			cp,
+			access_flags: self.access_flags.bits(),
+			this_class: self.this_class.index,
+			super_class: self.super_class.as_ref().map_or(0, |class| class.index),
+			interface_count: count("interfaces", interfaces.len())?,
+			interfaces,
+			field_count: count("fields", fields.len())?,
+			fields,
+			method_count: count("methods", methods.len())?,
+			methods,
+			attribute_count: count("attributes", attributes.len())?,
+			attributes,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, IRClassfileError> {
+		let mut out = Vec::new();
+		self.to_io()?.write(&mut out)?;
+		Ok(out)
+	}
+}
+
+impl IRFieldInfo {
+	pub fn to_io(&self) -> Result<IOFieldInfo, IRClassfileError> {
+		let attributes = attributes_to_io(&self.attributes)?;
+		Ok(IOFieldInfo {
+			access_flags: self.access_flags.bits(),
+			name_index: self.name.index,
+			descriptor_index: self.descriptor.index,
+			attributes_count: count("attributes", attributes.len())?,
+			attributes,
+		})
+	}
+}
+
+impl IRMethodInfo {
+	pub fn to_io(&self) -> Result<IOMethodInfo, IRClassfileError> {
+		let attributes = attributes_to_io(&self.attributes)?;
+		Ok(IOMethodInfo {
+			access_flags: self.access_flags.bits(),
+			name_index: self.name.index,
+			descriptor_index: self.descriptor.index,
+			attributes_count: count("attributes", attributes.len())?,
+			attributes,
+		})
+	}
+}
+
+impl IRAttributeInfo {
+	pub fn to_io(&self) -> Result<IOAttributeInfo, IRClassfileError> {
+		let mut info = Vec::new();
+		self.attr.write(&mut info)?;
+		Limits::check("attribute length", u64::from(u32::MAX), info.len() as u64)?;
+		Ok(IOAttributeInfo {
+			attribute_name_index: self.name.index,
+			attribute_length: info.len() as u32,
+			info,
+		})
+	}
+}
+
+fn attributes_to_io(attributes: &Attributes) -> Result<Vec<IOAttributeInfo>, IRClassfileError> {
+	attributes.iter().map(IRAttributeInfo::to_io).collect()
+}
+
+fn count(what: &'static str, len: usize) -> Result<u16, IRClassfileError> {
+	Limits::check(what, u64::from(u16::MAX), len as u64)?;
+	Ok(len as u16)
+}
+
+fn write_count(out: &mut Vec<u8>, what: &'static str, len: usize) -> Result<(), IRClassfileError> {
+	out.write_u16(count(what, len)?)?;
+	Ok(())
+}
+
+fn write_u8_count(out: &mut Vec<u8>, what: &'static str, len: usize) -> Result<(), IRClassfileError> {
+	Limits::check(what, u64::from(u8::MAX), len as u64)?;
+	out.write_u8(len as u8)?;
+	Ok(())
+}
+
+fn write_classes(out: &mut Vec<u8>, classes: &[CPClassRef]) -> Result<(), IRClassfileError> {
+	write_count(out, "classes", classes.len())?;
+	for class in classes {
+		out.write_u16(class.index)?;
+	}
+	Ok(())
+}
+
+fn write_attributes(out: &mut Vec<u8>, attributes: &Attributes) -> Result<(), IRClassfileError> {
+	write_count(out, "attributes", attributes.len())?;
+	for attr in attributes {
+		let io = attr.to_io()?;
+		out.write_u16(io.attribute_name_index)?;
+		out.write_u32(io.attribute_length)?;
+		out.extend_from_slice(&io.info);
+	}
+	Ok(())
+}
+
+fn write_annotations(out: &mut Vec<u8>, annotations: &[RuntimeAnnotation]) -> Result<(), IRClassfileError> {
+	write_count(out, "annotations", annotations.len())?;
+	for annotation in annotations {
+		annotation.write(out)?;
+	}
+	Ok(())
+}
+
+fn write_type_annotations(out: &mut Vec<u8>, annotations: &[RuntimeTypeAnnotation]) -> Result<(), IRClassfileError> {
+	write_count(out, "type annotations", annotations.len())?;
+	for annotation in annotations {
+		annotation.write(out)?;
+	}
+	Ok(())
+}
+
+fn write_parameter_annotations(out: &mut Vec<u8>, params: &[Vec<RuntimeAnnotation>]) -> Result<(), IRClassfileError> {
+	write_u8_count(out, "annotated parameters", params.len())?;
+	for annotations in params {
+		write_annotations(out, annotations)?;
+	}
+	Ok(())
+}
+
+impl IRAttribute {
+	/// Writes the attribute's payload, without the name and length header.
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7
+	pub fn write(&self, out: &mut Vec<u8>) -> Result<(), IRClassfileError> {
+		match self {
+			Self::ConstantValue(value) => out.write_u16(match value {
+				ConstantValueAttribute::Long { cp_idx, .. }
+				| ConstantValueAttribute::Float { cp_idx, .. }
+				| ConstantValueAttribute::Double { cp_idx, .. }
+				| ConstantValueAttribute::Int { cp_idx, .. }
+				| ConstantValueAttribute::String { cp_idx, .. } => *cp_idx,
+			})?,
+			Self::Code(code) => code.write(out)?,
+			Self::StackMapTable(table) => {
+				write_count(out, "stack map frames", table.entries.len())?;
+				for frame in &table.entries {
+					frame.write(out)?;
+				}
+			}
+			Self::Exceptions { exception_index_table } => write_classes(out, exception_index_table)?,
+			Self::InnerClasses(inner) => {
+				write_count(out, "inner classes", inner.classes.len())?;
+				for class in &inner.classes {
+					out.write_u16(class.inner_class_info.index)?;
+					out.write_u16(class.outer_class_info.as_ref().map_or(0, |outer| outer.index))?;
+					out.write_u16(class.inner_name.as_ref().map_or(0, |name| name.index))?;
+					out.write_u16(class.inner_class_access_flags.bits())?;
+				}
+			}
+			Self::EnclosingMethod { class, method } => {
+				out.write_u16(class.index)?;
+				out.write_u16(method.as_ref().map_or(0, |method| method.index))?;
+			}
+			Self::Synthetic | Self::Deprecated => {}
+			Self::Signature(utf8) | Self::SourceFile(utf8) | Self::CompilationID(utf8) | Self::SourceID(utf8) => {
+				out.write_u16(utf8.index)?
+			}
+			Self::SourceDebugExtension(debug) => out.extend_from_slice(debug.as_bytes()),
+			Self::LineNumberTable(table) => {
+				write_count(out, "line numbers", table.line_number_table.len())?;
+				for entry in &table.line_number_table {
+					out.write_u16(entry.start_pc)?;
+					out.write_u16(entry.line_number)?;
+				}
+			}
+			Self::LocalVariableTable { table } => {
+				write_count(out, "local variables", table.len())?;
+				for entry in table {
+					out.write_u16(entry.start_pc)?;
+					out.write_u16(entry.length)?;
+					out.write_u16(entry.name.index)?;
+					out.write_u16(entry.descriptor.index)?;
+					out.write_u16(entry.index)?;
+				}
+			}
+			Self::LocalVariableTypeTable { table } => {
+				write_count(out, "local variable types", table.len())?;
+				for entry in table {
+					out.write_u16(entry.start_pc)?;
+					out.write_u16(entry.length)?;
+					out.write_u16(entry.name.index)?;
+					out.write_u16(entry.signature.index)?;
+					out.write_u16(entry.index)?;
+				}
+			}
+			Self::RuntimeVisibleAnnotations { annotations } | Self::RuntimeInvisibleAnnotations { annotations } => {
+				write_annotations(out, annotations)?
+			}
+			Self::RuntimeVisibleParameterAnnotations { params }
+			| Self::RuntimeInvisibleParameterAnnotations { params } => write_parameter_annotations(out, params)?,
+			Self::AnnotationDefault { default_value } => default_value.write(out)?,
+			Self::BootstrapMethods { methods } => {
+				write_count(out, "bootstrap methods", methods.len())?;
+				for method in methods {
+					method.write(out)?;
+				}
+			}
+			Self::NestMembers { classes } | Self::PermittedSubclasses { classes } => write_classes(out, classes)?,
+			Self::NestHost(class) => out.write_u16(class.index)?,
+			Self::MethodParameters { parameters } => {
+				write_u8_count(out, "method parameters", parameters.len())?;
+				for param in parameters {
+					out.write_u16(param.name.as_ref().map_or(0, |name| name.index))?;
+					out.write_u16(param.access_flags.bits())?;
+				}
+			}
+			Self::Record { components } => {
+				write_count(out, "record components", components.len())?;
+				for component in components {
+					out.write_u16(component.name.index)?;
+					out.write_u16(component.descriptor.index)?;
+					write_attributes(out, &component.attributes)?;
+				}
+			}
+			Self::RuntimeVisibleTypeAnnotations { annotations }
+			| Self::RuntimeInvisibleTypeAnnotations { annotations } => write_type_annotations(out, annotations)?,
+			// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.25
+			Self::Module {
+				module_name,
+				module_flags,
+				module_version,
+				requires,
+				exports,
+				opens,
+				uses,
+				provides,
+			} => {
+				out.write_u16(module_name.index)?;
+				out.write_u16(module_flags.bits())?;
+				out.write_u16(module_version.as_ref().map_or(0, |version| version.index))?;
+
+				write_count(out, "module requires", requires.len())?;
+				for entry in requires {
+					out.write_u16(entry.module.index)?;
+					out.write_u16(entry.flags.bits())?;
+					out.write_u16(entry.version.as_ref().map_or(0, |version| version.index))?;
+				}
+
+				write_count(out, "module exports", exports.len())?;
+				for entry in exports {
+					out.write_u16(entry.package.index)?;
+					out.write_u16(entry.flags.bits())?;
+					write_count(out, "module exports", entry.exports.len())?;
+					for module in &entry.exports {
+						out.write_u16(module.index)?;
+					}
+				}
+
+				write_count(out, "module opens", opens.len())?;
+				for entry in opens {
+					out.write_u16(entry.package.index)?;
+					out.write_u16(entry.flags.bits())?;
+					write_count(out, "module opens", entry.opens.len())?;
+					for module in &entry.opens {
+						out.write_u16(module.index)?;
+					}
+				}
+
+				write_classes(out, uses)?;
+
+				write_count(out, "module provides", provides.len())?;
+				for entry in provides {
+					out.write_u16(entry.class.index)?;
+					write_classes(out, &entry.provides)?;
+				}
+			}
+			Self::ModulePackages { packages } => {
+				write_count(out, "module packages", packages.len())?;
+				for package in packages {
+					out.write_u16(package.index)?;
+				}
+			}
+			Self::ModuleMainClass { class } => out.write_u16(class.index)?,
+			Self::CharacterRangeTable { table } => {
+				write_count(out, "character ranges", table.len())?;
+				for entry in table {
+					out.write_u16(entry.start_pc)?;
+					out.write_u16(entry.end_pc)?;
+					out.write_u32(entry.character_range_start.0)?;
+					out.write_u32(entry.character_range_end.0)?;
+					out.write_u16(entry.flags.bits())?;
+				}
+			}
+			Self::Unknown(payload) => out.extend_from_slice(payload),
+		}
+		Ok(())
+	}
+}
+
+impl CodeAttribute {
+	pub fn write(&self, out: &mut Vec<u8>) -> Result<(), IRClassfileError> {
+		out.write_u16(self.max_stack)?;
+		out.write_u16(self.max_locals)?;
+		Limits::check("code length", u64::from(u32::MAX), self.code.len() as u64)?;
+		out.write_u32(self.code.len() as u32)?;
+		out.extend_from_slice(&self.code);
+
+		write_count(out, "exception table entries", self.exception_table.len())?;
+		for entry in &self.exception_table {
+			out.write_u16(entry.start_pc)?;
+			out.write_u16(entry.end_pc)?;
+			out.write_u16(entry.handler_pc)?;
+			out.write_u16(entry.catch_type)?;
+		}
+
+		write_attributes(out, &self.attributes)
+	}
+}
+
+impl Instructions {
+	/// Encodes the instruction at `pc` in its shortest form: `aload_0` for `ALOAD(0)`, `wide` only when the operands need
+	/// it, `ldc` unless the pool index doesn't fit a byte. `ldc` operands are looked up in the pool and added if missing.
+	/// Branch offsets are written as they are.
+	pub fn write(&self, cp: &mut Vec<IRCpTag>, pc: u32, out: &mut Vec<u8>) -> Result<(), IRClassfileError> {
+		self.write_with(&mut PoolIndex::new(), cp, pc, out)
+	}
+
+	/// Like `write`, looking `ldc` operands up through `pool`, for writing many instructions on the same pool.
+	pub fn write_with(
+		&self,
+		pool: &mut PoolIndex,
+		cp: &mut Vec<IRCpTag>,
+		pc: u32,
+		out: &mut Vec<u8>,
+	) -> Result<(), IRClassfileError> {
+		match self {
+			Self::BIPUSH(value) => {
+				out.write_u8(Opcodes::BIPUSH)?;
+				out.write_i8(*value)?;
+			}
+			Self::SIPUSH(value) => {
+				out.write_u8(Opcodes::SIPUSH)?;
+				out.write_i16(*value)?;
+			}
+			Self::LDC(tag) => {
+				let wide = matches!(tag, IRCpTag::Long(_) | IRCpTag::Double(_));
+				let index = pool.find_or_add(cp, tag.clone())?;
+				if wide {
+					out.write_u8(Opcodes::LDC2_W)?;
+					out.write_u16(index)?;
+				} else if let Ok(index) = u8::try_from(index) {
+					out.write_u8(Opcodes::LDC)?;
+					out.write_u8(index)?;
+				} else {
+					out.write_u8(Opcodes::LDC_W)?;
+					out.write_u16(index)?;
+				}
+			}
+
+			Self::ILOAD(index) => write_local(out, Opcodes::ILOAD, Some(0x1A), *index)?,
+			Self::LLOAD(index) => write_local(out, Opcodes::LLOAD, Some(0x1E), *index)?,
+			Self::FLOAD(index) => write_local(out, Opcodes::FLOAD, Some(0x22), *index)?,
+			Self::DLOAD(index) => write_local(out, Opcodes::DLOAD, Some(0x26), *index)?,
+			Self::ALOAD(index) => write_local(out, Opcodes::ALOAD, Some(0x2A), *index)?,
+			Self::ISTORE(index) => write_local(out, Opcodes::ISTORE, Some(0x3B), *index)?,
+			Self::LSTORE(index) => write_local(out, Opcodes::LSTORE, Some(0x3F), *index)?,
+			Self::FSTORE(index) => write_local(out, Opcodes::FSTORE, Some(0x43), *index)?,
+			Self::DSTORE(index) => write_local(out, Opcodes::DSTORE, Some(0x47), *index)?,
+			Self::ASTORE(index) => write_local(out, Opcodes::ASTORE, Some(0x4B), *index)?,
+			Self::RET(index) => write_local(out, Opcodes::RET, None, *index)?,
+			Self::IINC { index, r#const } => match (u8::try_from(*index), i8::try_from(*r#const)) {
+				(Ok(index), Ok(r#const)) => {
+					out.write_u8(Opcodes::IINC)?;
+					out.write_u8(index)?;
+					out.write_i8(r#const)?;
+				}
+				_ => {
+					out.write_u8(Opcodes::WIDE)?;
+					out.write_u8(Opcodes::IINC)?;
+					out.write_u16(*index)?;
+					out.write_i16(*r#const)?;
+				}
+			},
+
+			Self::IFEQ(offset)
+			| Self::IFNE(offset)
+			| Self::IFLT(offset)
+			| Self::IFGE(offset)
+			| Self::IFGT(offset)
+			| Self::IFLE(offset)
+			| Self::IF_ICMPEQ(offset)
+			| Self::IF_ICMPNE(offset)
+			| Self::IF_ICMPLT(offset)
+			| Self::IF_ICMPGE(offset)
+			| Self::IF_ICMPGT(offset)
+			| Self::IF_ICMPLE(offset)
+			| Self::IF_ACMPEQ(offset)
+			| Self::IF_ACMPNE(offset)
+			| Self::IFNULL(offset)
+			| Self::IFNONNULL(offset)
+			| Self::GOTO(offset)
+			| Self::JSR(offset) => {
+				out.write_u8(self.opcode())?;
+				out.write_i16(*offset)?;
+			}
+			Self::GOTO_W(offset) | Self::JSR_W(offset) => {
+				out.write_u8(self.opcode())?;
+				out.write_i32(*offset)?;
+			}
+			Self::TABLESWITCH {
+				default,
+				low,
+				high,
+				offsets,
+			} => {
+				if high < low || offsets.len() as i64 != *high as i64 - *low as i64 + 1 {
+					return Err(IRClassfileError::InvalidTableSwitch { low: *low, high: *high });
+				}
+				out.write_u8(Opcodes::TABLESWITCH)?;
+				write_switch_padding(out, pc)?;
+				out.write_i32(*default)?;
+				out.write_i32(*low)?;
+				out.write_i32(*high)?;
+				for offset in offsets {
+					out.write_i32(*offset)?;
+				}
+			}
+			Self::LOOKUPSWITCH { default, pairs } => {
+				out.write_u8(Opcodes::LOOKUPSWITCH)?;
+				write_switch_padding(out, pc)?;
+				out.write_i32(*default)?;
+				out.write_i32(pairs.len() as i32)?;
+				for (key, offset) in pairs {
+					out.write_i32(*key)?;
+					out.write_i32(*offset)?;
+				}
+			}
+
+			Self::GETSTATIC(field) | Self::PUTSTATIC(field) | Self::GETFIELD(field) | Self::PUTFIELD(field) => {
+				out.write_u8(self.opcode())?;
+				out.write_u16(field.index)?;
+			}
+			Self::INVOKEVIRTUAL(method) | Self::INVOKESPECIAL(method) | Self::INVOKESTATIC(method) => {
+				out.write_u8(self.opcode())?;
+				out.write_u16(method.index)?;
+			}
+			Self::INVOKEINTERFACE { method, count } => {
+				out.write_u8(Opcodes::INVOKEINTERFACE)?;
+				out.write_u16(method.index)?;
+				out.write_u8(*count)?;
+				out.write_u8(0)?;
+			}
+			Self::INVOKEDYNAMIC(call_site) => {
+				out.write_u8(Opcodes::INVOKEDYNAMIC)?;
+				out.write_u16(call_site.index)?;
+				out.write_u16(0)?;
+			}
+			Self::NEW(class) | Self::ANEWARRAY(class) | Self::CHECKCAST(class) | Self::INSTANCEOF(class) => {
+				out.write_u8(self.opcode())?;
+				out.write_u16(class.index)?;
+			}
+			Self::NEWARRAY(ty) => {
+				out.write_u8(Opcodes::NEWARRAY)?;
+				out.write_u8(*ty)?;
+			}
+			Self::MULTIANEWARRAY { class, dimensions } => {
+				out.write_u8(Opcodes::MULTIANEWARRAY)?;
+				out.write_u16(class.index)?;
+				out.write_u8(*dimensions)?;
+			}
+
+			// Everything else is just the opcode.
+			_ => out.write_u8(self.opcode())?,
+		}
+		Ok(())
+	}
+}
+
+/// `short` is the opcode of the `<op>_0` form, if the instruction has short forms.
+fn write_local(out: &mut Vec<u8>, opcode: u8, short: Option<u8>, index: u16) -> Result<(), IRClassfileError> {
+	match (short, u8::try_from(index)) {
+		(Some(short), _) if index <= 3 => out.write_u8(short + index as u8)?,
+		(_, Ok(index)) => {
+			out.write_u8(opcode)?;
+			out.write_u8(index)?;
+		}
+		_ => {
+			out.write_u8(Opcodes::WIDE)?;
+			out.write_u8(opcode)?;
+			out.write_u16(index)?;
+		}
+	}
+	Ok(())
+}
+
+// The switch operands start at the next multiple of 4 from the start of the code array, `pc` is the opcode's.
+fn write_switch_padding(out: &mut Vec<u8>, pc: u32) -> Result<(), IRClassfileError> {
+	for _ in 0..(4 - (pc + 1) % 4) % 4 {
+		out.write_u8(0)?;
+	}
+	Ok(())
+}
+
+impl StackMapFrame {
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.4
+	pub fn write(&self, out: &mut Vec<u8>) -> Result<(), IRClassfileError> {
+		match self {
+			Self::SameFrame { frame_type, .. } => out.write_u8(*frame_type)?,
+			Self::SameLocals1StackItemFrame { frame_type, stack, .. } => {
+				out.write_u8(*frame_type)?;
+				stack.write(out)?;
+			}
+			Self::SameLocals1StackItemFrameExtended {
+				frame_type,
+				offset_delta,
+				stack,
+			} => {
+				out.write_u8(*frame_type)?;
+				out.write_u16(*offset_delta)?;
+				stack.write(out)?;
+			}
+			Self::ChopFrame {
+				frame_type,
+				offset_delta,
+			}
+			| Self::SameFrameExtended {
+				frame_type,
+				offset_delta,
+			} => {
+				out.write_u8(*frame_type)?;
+				out.write_u16(*offset_delta)?;
+			}
+			Self::AppendFrame {
+				frame_type,
+				offset_delta,
+				locals,
+			} => {
+				out.write_u8(*frame_type)?;
+				out.write_u16(*offset_delta)?;
+				for local in locals {
+					local.write(out)?;
+				}
+			}
+			Self::FullFrame {
+				frame_type,
+				offset_delta,
+				locals,
+				stack,
+			} => {
+				out.write_u8(*frame_type)?;
+				out.write_u16(*offset_delta)?;
+				write_count(out, "frame locals", locals.len())?;
+				for local in locals {
+					local.write(out)?;
+				}
+				write_count(out, "frame stack items", stack.len())?;
+				for item in stack {
+					item.write(out)?;
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+impl VerificationTypeInfo {
+	pub fn write(&self, out: &mut Vec<u8>) -> Result<(), IRClassfileError> {
+		match self {
+			Self::TopVariableInfo => out.write_u8(0)?,
+			Self::IntegerVariableInfo => out.write_u8(1)?,
+			Self::FloatVariableInfo => out.write_u8(2)?,
+			Self::DoubleVariableInfo => out.write_u8(3)?,
+			Self::LongVariableInfo => out.write_u8(4)?,
+			Self::NullVariableInfo => out.write_u8(5)?,
+			Self::UninitializedThisVariableInfo => out.write_u8(6)?,
+			Self::ObjectVariableInfo { cpool_idx } => {
+				out.write_u8(7)?;
+				out.write_u16(*cpool_idx)?;
+			}
+			Self::UninitializedVariableInfo { offset } => {
+				out.write_u8(8)?;
+				out.write_u16(*offset)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl BootstrapMethodsMethod {
+	pub fn write(&self, out: &mut Vec<u8>) -> Result<(), IRClassfileError> {
+		out.write_u16(self.method.index)?;
+		write_count(out, "bootstrap arguments", self.arguments.len())?;
+		for argument in &self.arguments {
+			out.write_u16(argument.index)?;
+		}
+		Ok(())
+	}
+}
+
+impl RuntimeAnnotation {
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.16
+	pub fn write(&self, out: &mut Vec<u8>) -> Result<(), IRClassfileError> {
+		out.write_u16(self.ty.index)?;
+		write_count(out, "annotation elements", self.pairs.len())?;
+		for pair in &self.pairs {
+			out.write_u16(pair.name.index)?;
+			pair.value.write(out)?;
+		}
+		Ok(())
+	}
+}
+
+impl RuntimeAnnotationValue {
+	pub fn write(&self, out: &mut Vec<u8>) -> Result<(), IRClassfileError> {
+		match self {
+			Self::ConstValueIndex { tag, value } => {
+				out.write_u8(*tag)?;
+				out.write_u16(value.index)?;
+			}
+			Self::EnumConstValue { type_name, const_name } => {
+				out.write_u8(b'e')?;
+				out.write_u16(type_name.index)?;
+				out.write_u16(const_name.index)?;
+			}
+			Self::ClassInfoIndex(class) => {
+				out.write_u8(b'c')?;
+				out.write_u16(class.index)?;
+			}
+			Self::Annotation(annotation) => {
+				out.write_u8(b'@')?;
+				annotation.write(out)?;
+			}
+			Self::ArrayValue { values } => {
+				out.write_u8(b'[')?;
+				write_count(out, "array element values", values.len())?;
+				for value in values {
+					value.write(out)?;
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+impl RuntimeTypeAnnotation {
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.20
+	pub fn write(&self, out: &mut Vec<u8>) -> Result<(), IRClassfileError> {
+		out.write_u8(self.target_type)?;
+		match &self.target_info {
+			RuntimeTypeAnnotationTargetInfo::TypeParameterTarget { type_param_index } => {
+				out.write_u8(*type_param_index)?
+			}
+			RuntimeTypeAnnotationTargetInfo::SupertypeTarget { supertype_index } => out.write_u16(*supertype_index)?,
+			RuntimeTypeAnnotationTargetInfo::TypeParameterBoundTarget {
+				type_param_index,
+				bound_index,
+			} => {
+				out.write_u8(*type_param_index)?;
+				out.write_u8(*bound_index)?;
+			}
+			RuntimeTypeAnnotationTargetInfo::EmptyTarget => {}
+			RuntimeTypeAnnotationTargetInfo::FormalParameterTarget { formal_param_index } => {
+				out.write_u8(*formal_param_index)?
+			}
+			RuntimeTypeAnnotationTargetInfo::ThrowsTarget { throws_type_index } => out.write_u16(*throws_type_index)?,
+			RuntimeTypeAnnotationTargetInfo::LocalvarTarget { table } => {
+				write_count(out, "local variable targets", table.len())?;
+				for entry in table {
+					out.write_u16(entry.start_pc)?;
+					out.write_u16(entry.length)?;
+					out.write_u16(entry.index)?;
+				}
+			}
+			RuntimeTypeAnnotationTargetInfo::CatchTarget { exception_table_index } => {
+				out.write_u16(*exception_table_index)?
+			}
+			RuntimeTypeAnnotationTargetInfo::OffsetTarget { offset } => out.write_u16(*offset)?,
+			RuntimeTypeAnnotationTargetInfo::TypeArgumentTarget {
+				offset,
+				type_argument_index,
+			} => {
+				out.write_u16(*offset)?;
+				out.write_u8(*type_argument_index)?;
+			}
+		}
+
+		write_u8_count(out, "type path entries", self.target_path.len())?;
+		for part in &self.target_path {
+			out.write_u8(part.type_path_kind)?;
+			out.write_u8(part.type_argument_kind)?;
+		}
+
+		out.write_u16(self.type_index)?;
+		write_count(out, "annotation elements", self.pairs.len())?;
+		for pair in &self.pairs {
+			out.write_u16(pair.name.index)?;
+			pair.value.write(out)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Shared;
+
+	fn write(insn: Instructions, cp: &mut Vec<IRCpTag>, pc: u32) -> Vec<u8> {
+		let mut out = Vec::new();
+		insn.write(cp, pc, &mut out).unwrap();
+		out
+	}
+
+	#[test]
+	fn shortest_local_encoding() {
+		let mut cp = Vec::new();
+		assert_eq!(write(Instructions::ALOAD(0), &mut cp, 0), [0x2A]);
+		assert_eq!(write(Instructions::ILOAD(3), &mut cp, 0), [0x1D]);
+		assert_eq!(write(Instructions::DSTORE(2), &mut cp, 0), [0x49]);
+		assert_eq!(write(Instructions::ASTORE(4), &mut cp, 0), [Opcodes::ASTORE, 4]);
+		assert_eq!(write(Instructions::LLOAD(255), &mut cp, 0), [Opcodes::LLOAD, 255]);
+		// `ret` has no short forms.
+		assert_eq!(write(Instructions::RET(0), &mut cp, 0), [Opcodes::RET, 0]);
+	}
+
+	#[test]
+	fn wide_locals() {
+		let mut cp = Vec::new();
+		assert_eq!(
+			write(Instructions::ILOAD(256), &mut cp, 0),
+			[Opcodes::WIDE, Opcodes::ILOAD, 1, 0]
+		);
+		assert_eq!(
+			write(Instructions::RET(0x1234), &mut cp, 0),
+			[Opcodes::WIDE, Opcodes::RET, 0x12, 0x34]
+		);
+		assert_eq!(
+			write(Instructions::IINC { index: 5, r#const: -1 }, &mut cp, 0),
+			[Opcodes::IINC, 5, 0xFF]
+		);
+		// Either operand not fitting a byte widens both.
+		assert_eq!(
+			write(Instructions::IINC { index: 300, r#const: 1 }, &mut cp, 0),
+			[Opcodes::WIDE, Opcodes::IINC, 1, 44, 0, 1]
+		);
+		assert_eq!(
+			write(Instructions::IINC { index: 1, r#const: 128 }, &mut cp, 0),
+			[Opcodes::WIDE, Opcodes::IINC, 0, 1, 0, 128]
+		);
+	}
+
+	#[test]
+	fn switch_padding() {
+		let mut cp = Vec::new();
+		for pc in 0..8 {
+			let table = Instructions::TABLESWITCH {
+				default: 1,
+				low: 0,
+				high: 0,
+				offsets: vec![2],
+			};
+			let out = write(table, &mut cp, pc);
+			let padding = (3 - pc as usize % 4) % 4;
+			assert_eq!(out[0], Opcodes::TABLESWITCH);
+			assert!(out[1..=padding].iter().all(|&b| b == 0));
+			// The operands start 4 byte aligned from the start of the code array.
+			assert_eq!((pc as usize + 1 + padding) % 4, 0);
+			assert_eq!(out.len(), 1 + padding + 16);
+			assert_eq!(out[1 + padding..][..4], 1i32.to_be_bytes());
+
+			let lookup = Instructions::LOOKUPSWITCH {
+				default: 1,
+				pairs: vec![(7, 2)],
+			};
+			let out = write(lookup, &mut cp, pc);
+			assert_eq!(out.len(), 1 + padding + 16);
+			assert_eq!(out[1 + padding + 4..][..4], 1i32.to_be_bytes());
+		}
+
+		let inverted = Instructions::TABLESWITCH {
+			default: 0,
+			low: 1,
+			high: 0,
+			offsets: vec![],
+		};
+		assert!(matches!(
+			inverted.write(&mut cp, 0, &mut Vec::new()),
+			Err(IRClassfileError::InvalidTableSwitch { low: 1, high: 0 })
+		));
+	}
+
+	#[test]
+	fn ldc_width() {
+		let mut cp = Vec::new();
+		assert_eq!(
+			write(Instructions::LDC(IRCpTag::Integer(7)), &mut cp, 0),
+			[Opcodes::LDC, 1]
+		);
+		// Reuses the existing entry.
+		assert_eq!(
+			write(Instructions::LDC(IRCpTag::Integer(7)), &mut cp, 0),
+			[Opcodes::LDC, 1]
+		);
+		assert_eq!(
+			write(Instructions::LDC(IRCpTag::Long(7)), &mut cp, 0),
+			[Opcodes::LDC2_W, 0, 2]
+		);
+		assert!(matches!(cp[2], IRCpTag::Unusable));
+
+		while cp.len() < 254 {
+			cp.push(IRCpTag::Utf8(Shared::new(cp.len().to_string())));
+		}
+		assert_eq!(
+			write(Instructions::LDC(IRCpTag::Float(1.0)), &mut cp, 0),
+			[Opcodes::LDC, 255]
+		);
+		assert_eq!(
+			write(Instructions::LDC(IRCpTag::Float(2.0)), &mut cp, 0),
+			[Opcodes::LDC_W, 1, 0]
+		);
+		assert_eq!(
+			write(Instructions::LDC(IRCpTag::Double(1.0)), &mut cp, 0),
+			[Opcodes::LDC2_W, 1, 1]
+		);
+	}
+}
@@ -0,0 +1,410 @@
+// Architecture rules in the spirit of ArchUnit, checked against the facts of a set of classes, e.g.
+// `ArchRule::classes(reside_in("..service..")).should_not_depend_on(reside_in("..web.."))`.
+// Class names in patterns use the binary form (dots), like ArchUnit does.
+
+use std::{
+	collections::BTreeSet,
+	fmt::{self, Display, Write},
+	ops::Not,
+};
+
+use maya_classfile_ir::{
+	names,
+	query::{AnnotationTarget, Facts},
+	symbols::ClassId,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClassPredicate {
+	Any,
+	// An ArchUnit package pattern, `..` matches any number of packages and `*` any part of one.
+	ResideIn(String),
+	// A glob over the binary name of the class, `*` matches anything and `?` a single character.
+	NameMatches(String),
+	// Internal name of the annotation type. Only known for the classes the facts were built from.
+	AnnotatedWith(String),
+	Not(Box<ClassPredicate>),
+	And(Vec<ClassPredicate>),
+	Or(Vec<ClassPredicate>),
+}
+
+pub fn reside_in(pattern: &str) -> ClassPredicate {
+	ClassPredicate::ResideIn(pattern.to_string())
+}
+
+pub fn name_matches(glob: &str) -> ClassPredicate {
+	ClassPredicate::NameMatches(glob.to_string())
+}
+
+/// Takes either the binary or the internal name of the annotation.
+pub fn annotated_with(annotation: &str) -> ClassPredicate {
+	ClassPredicate::AnnotatedWith(names::binary_to_internal(annotation))
+}
+
+impl ClassPredicate {
+	pub fn and(self, other: ClassPredicate) -> Self {
+		match self {
+			Self::And(mut all) => {
+				all.push(other);
+				Self::And(all)
+			}
+			_ => Self::And(vec![self, other]),
+		}
+	}
+
+	pub fn or(self, other: ClassPredicate) -> Self {
+		match self {
+			Self::Or(mut any) => {
+				any.push(other);
+				Self::Or(any)
+			}
+			_ => Self::Or(vec![self, other]),
+		}
+	}
+
+	pub fn matches(&self, class: ClassId, facts: &Facts) -> bool {
+		let name = facts.class_name(class);
+		match self {
+			Self::Any => true,
+			Self::ResideIn(pattern) => {
+				let package = names::package_name(name);
+				let segments: Vec<&str> = if package.is_empty() {
+					Vec::new()
+				} else {
+					package.split('/').collect()
+				};
+				package_matches(&parse_package_pattern(pattern), &segments)
+			}
+			Self::NameMatches(pattern) => glob_matches(pattern.as_bytes(), names::internal_to_binary(name).as_bytes()),
+			Self::AnnotatedWith(annotation) => facts.symbols.lookup_class(annotation).is_some_and(|annotation| {
+				facts
+					.annotation_on
+					.contains(&(AnnotationTarget::Class(class), annotation))
+			}),
+			Self::Not(inner) => !inner.matches(class, facts),
+			Self::And(all) => all.iter().all(|predicate| predicate.matches(class, facts)),
+			Self::Or(any) => any.iter().any(|predicate| predicate.matches(class, facts)),
+		}
+	}
+}
+
+impl Not for ClassPredicate {
+	type Output = Self;
+
+	fn not(self) -> Self {
+		Self::Not(Box::new(self))
+	}
+}
+
+impl Display for ClassPredicate {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let join = |f: &mut fmt::Formatter<'_>, all: &[ClassPredicate], sep: &str| {
+			for (i, predicate) in all.iter().enumerate() {
+				if i > 0 {
+					f.write_str(sep)?;
+				}
+				write!(f, "({predicate})")?;
+			}
+			Ok(())
+		};
+		match self {
+			Self::Any => f.write_str("any class"),
+			Self::ResideIn(pattern) => write!(f, "reside in {pattern}"),
+			Self::NameMatches(pattern) => write!(f, "have a name matching {pattern}"),
+			Self::AnnotatedWith(annotation) => {
+				write!(f, "are annotated with @{}", names::internal_to_binary(annotation))
+			}
+			Self::Not(inner) => write!(f, "not ({inner})"),
+			Self::And(all) => join(f, all, " and "),
+			Self::Or(any) => join(f, any, " or "),
+		}
+	}
+}
+
+enum PackageToken<'a> {
+	// `..`, any number of packages
+	Any,
+	Segment(&'a str),
+}
+
+fn parse_package_pattern(pattern: &str) -> Vec<PackageToken<'_>> {
+	let mut tokens = Vec::new();
+	let mut rest = pattern;
+	while !rest.is_empty() {
+		if let Some(after) = rest.strip_prefix("..") {
+			tokens.push(PackageToken::Any);
+			rest = after;
+		} else if let Some(after) = rest.strip_prefix('.') {
+			rest = after;
+		} else {
+			let end = rest.find('.').unwrap_or(rest.len());
+			tokens.push(PackageToken::Segment(&rest[..end]));
+			rest = &rest[end..];
+		}
+	}
+	tokens
+}
+
+fn package_matches(tokens: &[PackageToken], segments: &[&str]) -> bool {
+	match tokens.split_first() {
+		None => segments.is_empty(),
+		Some((PackageToken::Any, rest)) => (0..=segments.len()).any(|skip| package_matches(rest, &segments[skip..])),
+		Some((PackageToken::Segment(pattern), rest)) => segments.split_first().is_some_and(|(segment, others)| {
+			glob_matches(pattern.as_bytes(), segment.as_bytes()) && package_matches(rest, others)
+		}),
+	}
+}
+
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+	match pattern.split_first() {
+		None => text.is_empty(),
+		Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_matches(rest, &text[skip..])),
+		Some((b'?', rest)) => !text.is_empty() && glob_matches(rest, &text[1..]),
+		Some((c, rest)) => text.first() == Some(c) && glob_matches(rest, &text[1..]),
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+	NotDependOn(ClassPredicate),
+	OnlyDependOn(ClassPredicate),
+	Satisfy(ClassPredicate),
+}
+
+impl Display for Condition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NotDependOn(predicate) => write!(f, "should not depend on classes that {predicate}"),
+			Self::OnlyDependOn(predicate) => write!(f, "should only depend on classes that {predicate}"),
+			Self::Satisfy(predicate) => write!(f, "should {predicate}"),
+		}
+	}
+}
+
+/// Selects classes with a predicate and states a condition every one of them must meet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchRule {
+	pub name: Option<String>,
+	pub subjects: ClassPredicate,
+	pub condition: Condition,
+}
+
+/// The first half of a rule, see [`ArchRule::classes`].
+#[derive(Debug, Clone)]
+pub struct RuleSubjects(ClassPredicate);
+
+impl RuleSubjects {
+	fn rule(self, condition: Condition) -> ArchRule {
+		ArchRule {
+			name: None,
+			subjects: self.0,
+			condition,
+		}
+	}
+
+	pub fn should_not_depend_on(self, predicate: ClassPredicate) -> ArchRule {
+		self.rule(Condition::NotDependOn(predicate))
+	}
+
+	pub fn should_only_depend_on(self, predicate: ClassPredicate) -> ArchRule {
+		self.rule(Condition::OnlyDependOn(predicate))
+	}
+
+	pub fn should(self, predicate: ClassPredicate) -> ArchRule {
+		self.rule(Condition::Satisfy(predicate))
+	}
+}
+
+impl ArchRule {
+	pub fn classes(subjects: ClassPredicate) -> RuleSubjects {
+		RuleSubjects(subjects)
+	}
+
+	pub fn named(mut self, name: &str) -> Self {
+		self.name = Some(name.to_string());
+		self
+	}
+
+	/// The name if the rule has one, otherwise the rule spelled out.
+	pub fn description(&self) -> String {
+		match &self.name {
+			Some(name) => name.clone(),
+			None => self.to_string(),
+		}
+	}
+
+	pub fn check(&self, facts: &Facts) -> Vec<ArchViolation> {
+		let subjects: BTreeSet<ClassId> = facts
+			.classes
+			.iter()
+			.copied()
+			.filter(|class| self.subjects.matches(*class, facts))
+			.collect();
+
+		let mut violations = Vec::new();
+		match &self.condition {
+			Condition::NotDependOn(predicate) | Condition::OnlyDependOn(predicate) => {
+				let forbid_matching = matches!(self.condition, Condition::NotDependOn(_));
+				let dependencies = facts.class_depends_on();
+				for &(class, dependency) in dependencies.iter() {
+					// Relations are sorted, so this keeps violations grouped by class.
+					if !subjects.contains(&class) || predicate.matches(dependency, facts) != forbid_matching {
+						continue;
+					}
+					violations.push(ArchViolation {
+						rule: self.description(),
+						class: names::internal_to_binary(facts.class_name(class)),
+						message: format!("depends on {}", names::internal_to_binary(facts.class_name(dependency))),
+					});
+				}
+			}
+			Condition::Satisfy(predicate) => {
+				for class in subjects.into_iter().filter(|class| !predicate.matches(*class, facts)) {
+					violations.push(ArchViolation {
+						rule: self.description(),
+						class: names::internal_to_binary(facts.class_name(class)),
+						message: format!("doesn't {predicate}"),
+					});
+				}
+			}
+		}
+		violations
+	}
+}
+
+impl Display for ArchRule {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "classes that {} {}", self.subjects, self.condition)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchViolation {
+	pub rule: String,
+	// binary name
+	pub class: String,
+	pub message: String,
+}
+
+impl Display for ArchViolation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} {} ({})", self.class, self.message, self.rule)
+	}
+}
+
+/// The result of checking several rules, printable as plain text or as JUnit XML for CI.
+#[derive(Debug, Clone, Default)]
+pub struct ArchReport {
+	// every rule with the violations it found, in the order the rules were given
+	pub results: Vec<(String, Vec<ArchViolation>)>,
+}
+
+impl ArchReport {
+	pub fn check(rules: &[ArchRule], facts: &Facts) -> Self {
+		Self {
+			results: rules
+				.iter()
+				.map(|rule| (rule.description(), rule.check(facts)))
+				.collect(),
+		}
+	}
+
+	pub fn is_ok(&self) -> bool {
+		self.results.iter().all(|(_, violations)| violations.is_empty())
+	}
+
+	pub fn violations(&self) -> impl Iterator<Item = &ArchViolation> {
+		self.results.iter().flat_map(|(_, violations)| violations)
+	}
+
+	pub fn to_junit(&self) -> String {
+		let failures = self
+			.results
+			.iter()
+			.filter(|(_, violations)| !violations.is_empty())
+			.count();
+		let mut out = String::new();
+		let _ = writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+		let _ = writeln!(
+			out,
+			r#"<testsuite name="architecture" tests="{}" failures="{failures}">"#,
+			self.results.len()
+		);
+		for (rule, violations) in &self.results {
+			let _ = write!(out, r#"  <testcase name="{}""#, xml_escape(rule));
+			if violations.is_empty() {
+				let _ = writeln!(out, "/>");
+				continue;
+			}
+
+			let _ = writeln!(out, ">");
+			let _ = writeln!(out, r#"    <failure message="{} violations">"#, violations.len());
+			for violation in violations {
+				let _ = writeln!(
+					out,
+					"{}",
+					xml_escape(&format!("{} {}", violation.class, violation.message))
+				);
+			}
+			let _ = writeln!(out, "    </failure>");
+			let _ = writeln!(out, "  </testcase>");
+		}
+		let _ = writeln!(out, "</testsuite>");
+		out
+	}
+}
+
+impl Display for ArchReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (rule, violations) in &self.results {
+			let status = if violations.is_empty() { "ok" } else { "FAILED" };
+			writeln!(f, "{status}: {rule}")?;
+			for violation in violations {
+				writeln!(f, "  {} {}", violation.class, violation.message)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+fn xml_escape(text: &str) -> String {
+	let mut out = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => out.push_str("&amp;"),
+			'<' => out.push_str("&lt;"),
+			'>' => out.push_str("&gt;"),
+			'"' => out.push_str("&quot;"),
+			_ => out.push(c),
+		}
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn matches(predicate: ClassPredicate, class: &str) -> bool {
+		let mut facts = Facts::new();
+		let class = facts.symbols.class(class);
+		predicate.matches(class, &facts)
+	}
+
+	fn resides(pattern: &str, class: &str) -> bool {
+		matches(reside_in(pattern), class)
+	}
+
+	#[test]
+	fn package_patterns() {
+		assert!(resides("..service..", "com/acme/service/UserService"));
+		assert!(resides("..service..", "service/impl/UserService"));
+		assert!(!resides("..service..", "com/acme/services/UserService"));
+		assert!(resides("com.acme..", "com/acme/Main"));
+		assert!(resides("com.*.web", "com/acme/web/Controller"));
+		assert!(!resides("com.*.web", "com/acme/web/api/Controller"));
+		assert!(resides("..", "Main"));
+		assert!(matches(name_matches("*Service"), "com/acme/UserService"));
+		assert!(matches(!name_matches("*Impl"), "com/acme/UserService"));
+	}
+}
@@ -1 +1,3 @@
-
+pub mod arch;
+pub mod lint;
+pub mod validate;
@@ -0,0 +1,472 @@
+// Checks for mistakes that are easy to make when generating classes by hand but that javac never makes.
+// Unlike `validate`, most of these produce classes the JVM still loads, they just behave or verify differently than intended.
+
+use std::{
+	collections::{BTreeSet, HashMap},
+	fmt::{self, Display},
+};
+
+use maya_classfile_ir::{
+	attribute::CodeAttribute, class_pool::IRCpTag, code::Instructions, descriptor::MethodDescriptor, IRClassFile,
+	IRMethodInfo,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintKind {
+	UndecodableCode,
+	MissingSuperCall,
+	MaxLocalsTooSmall,
+	MaxLocalsTooLarge,
+	MissingStackMapTable,
+	StaleStackMapTable,
+	UnreachableCode,
+	DuplicatePoolEntry,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+	pub kind: LintKind,
+	pub location: String,
+	pub message: String,
+	// What to change to fix it, if there's a single obvious fix.
+	pub suggestion: Option<String>,
+}
+
+impl Display for Lint {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.location, self.message)?;
+		if let Some(suggestion) = &self.suggestion {
+			write!(f, " (help: {suggestion})")?;
+		}
+		Ok(())
+	}
+}
+
+pub fn lint(class: &IRClassFile) -> Vec<Lint> {
+	let mut lints = Vec::new();
+	lint_pool(class, &mut lints);
+
+	for method in &class.methods {
+		if let Some(code) = method.code() {
+			lint_method(class, method, code, &mut lints);
+		}
+	}
+
+	lints
+}
+
+fn push(lints: &mut Vec<Lint>, kind: LintKind, location: &str, message: String, suggestion: Option<String>) {
+	lints.push(Lint {
+		kind,
+		location: location.to_string(),
+		message,
+		suggestion,
+	});
+}
+
+fn lint_method(class: &IRClassFile, method: &IRMethodInfo, code: &CodeAttribute, lints: &mut Vec<Lint>) {
+	let location = format!("method {}{}", method.name.data, method.descriptor.data);
+	let instructions = match Instructions::read_all(&class.cp, &code.code) {
+		Ok(instructions) => instructions,
+		Err(err) => {
+			push(
+				lints,
+				LintKind::UndecodableCode,
+				&location,
+				format!("code can't be decoded: {err}"),
+				None,
+			);
+			return;
+		}
+	};
+
+	if method.name.data.as_str() == "<init>" && class.super_class.is_some() {
+		lint_super_call(class, &location, &instructions, lints);
+	}
+	lint_max_locals(method, &location, code, &instructions, lints);
+	lint_reachability(&location, code, &instructions, lints);
+	if class.version.supports_stack_map_table() {
+		lint_stack_map(&location, code, &instructions, lints);
+	}
+}
+
+fn lint_super_call(class: &IRClassFile, location: &str, instructions: &[(u32, Instructions)], lints: &mut Vec<Lint>) {
+	let this = class.class_name();
+	let Some(super_name) = class.super_class.as_ref().map(|sup| sup.data.data.as_str()) else {
+		return;
+	};
+
+	let calls_init = instructions.iter().any(|(_, insn)| match insn {
+		Instructions::INVOKESPECIAL(method) => {
+			let owner = method.class.data.data.as_str();
+			method.name_and_ty.name.data.as_str() == "<init>" && (owner == this || owner == super_name)
+		}
+		_ => false,
+	});
+
+	if !calls_init {
+		push(
+			lints,
+			LintKind::MissingSuperCall,
+			location,
+			"constructor never calls super() or this()".to_string(),
+			Some(format!(
+				"start the constructor with `aload_0; invokespecial {super_name}.<init>`"
+			)),
+		);
+	}
+}
+
+fn lint_max_locals(
+	method: &IRMethodInfo,
+	location: &str,
+	code: &CodeAttribute,
+	instructions: &[(u32, Instructions)],
+	lints: &mut Vec<Lint>,
+) {
+	let Ok(descriptor) = MethodDescriptor::parse(&method.descriptor.data) else {
+		return;
+	};
+
+	let this_slot = u32::from(!method.access_flags.is_static());
+	let mut needed = u32::from(descriptor.param_slots()) + this_slot;
+	for (_, insn) in instructions {
+		let used = match insn {
+			Instructions::LLOAD(index)
+			| Instructions::DLOAD(index)
+			| Instructions::LSTORE(index)
+			| Instructions::DSTORE(index) => u32::from(*index) + 2,
+			Instructions::ILOAD(index)
+			| Instructions::FLOAD(index)
+			| Instructions::ALOAD(index)
+			| Instructions::ISTORE(index)
+			| Instructions::FSTORE(index)
+			| Instructions::ASTORE(index)
+			| Instructions::RET(index)
+			| Instructions::IINC { index, .. } => u32::from(*index) + 1,
+			_ => 0,
+		};
+		needed = needed.max(used);
+	}
+
+	let max_locals = u32::from(code.max_locals);
+	if max_locals < needed {
+		push(
+			lints,
+			LintKind::MaxLocalsTooSmall,
+			location,
+			format!("max_locals is {max_locals} but the code uses {needed} local slots"),
+			Some(format!("set max_locals to {needed}")),
+		);
+	} else if max_locals > needed {
+		push(
+			lints,
+			LintKind::MaxLocalsTooLarge,
+			location,
+			format!("max_locals is {max_locals} but only {needed} local slots are used"),
+			Some(format!("set max_locals to {needed}")),
+		);
+	}
+}
+
+fn lint_reachability(
+	location: &str,
+	code: &CodeAttribute,
+	instructions: &[(u32, Instructions)],
+	lints: &mut Vec<Lint>,
+) {
+	let index_of: HashMap<u32, usize> = instructions.iter().enumerate().map(|(i, (pc, _))| (*pc, i)).collect();
+
+	let mut reachable = vec![false; instructions.len()];
+	let mut pending = vec![0];
+	pending.extend(
+		code.exception_table
+			.iter()
+			.filter_map(|entry| index_of.get(&(entry.handler_pc as u32)).copied()),
+	);
+
+	while let Some(i) = pending.pop() {
+		if i >= instructions.len() || reachable[i] {
+			continue;
+		}
+		reachable[i] = true;
+
+		let (pc, insn) = &instructions[i];
+		if insn.falls_through() {
+			pending.push(i + 1);
+		}
+		pending.extend(
+			insn.branch_targets(*pc)
+				.iter()
+				.filter_map(|target| index_of.get(target).copied()),
+		);
+	}
+
+	let mut i = 0;
+	while i < instructions.len() {
+		if reachable[i] {
+			i += 1;
+			continue;
+		}
+
+		let start = i;
+		while i < instructions.len() && !reachable[i] {
+			i += 1;
+		}
+
+		let dead = &instructions[start..i];
+		let is_return = |insn: &Instructions| {
+			matches!(
+				insn,
+				Instructions::RETURN
+					| Instructions::IRETURN
+					| Instructions::LRETURN
+					| Instructions::FRETURN
+					| Instructions::DRETURN
+					| Instructions::ARETURN
+			)
+		};
+		let what = if dead.len() == 1 && is_return(&dead[0].1) {
+			"unreachable return".to_string()
+		} else {
+			format!("{} unreachable instructions", dead.len())
+		};
+
+		push(
+			lints,
+			LintKind::UnreachableCode,
+			location,
+			format!("{what} at pc {}", dead[0].0),
+			Some("remove the dead code, it still has to pass verification".to_string()),
+		);
+	}
+}
+
+fn lint_stack_map(location: &str, code: &CodeAttribute, instructions: &[(u32, Instructions)], lints: &mut Vec<Lint>) {
+	let table = code.attributes.stack_map_table();
+	let frame_pcs: BTreeSet<u32> = table
+		.map(|table| table.frame_pcs().into_iter().collect())
+		.unwrap_or_default();
+	let boundaries: BTreeSet<u32> = instructions.iter().map(|(pc, _)| *pc).collect();
+
+	// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.10.1
+	// Every branch target, exception handler and instruction following an unconditional jump needs a frame.
+	let mut required = BTreeSet::new();
+	for (i, (pc, insn)) in instructions.iter().enumerate() {
+		required.extend(insn.branch_targets(*pc));
+		if !insn.falls_through() {
+			if let Some((next, _)) = instructions.get(i + 1) {
+				required.insert(*next);
+			}
+		}
+	}
+	required.extend(code.exception_table.iter().map(|entry| entry.handler_pc as u32));
+
+	let misplaced: Vec<_> = frame_pcs.difference(&boundaries).collect();
+	let missing: Vec<_> = required.difference(&frame_pcs).collect();
+	let suggestion = Some("recompute the StackMapTable after editing the code".to_string());
+
+	if table.is_none() {
+		if !missing.is_empty() {
+			push(
+				lints,
+				LintKind::MissingStackMapTable,
+				location,
+				format!("no StackMapTable, but {} pcs need a frame", missing.len()),
+				suggestion,
+			);
+		}
+		return;
+	}
+
+	if !misplaced.is_empty() {
+		push(
+			lints,
+			LintKind::StaleStackMapTable,
+			location,
+			format!("frames at pcs {misplaced:?} aren't on an instruction boundary"),
+			suggestion.clone(),
+		);
+	}
+	if !missing.is_empty() {
+		push(
+			lints,
+			LintKind::StaleStackMapTable,
+			location,
+			format!("pcs {missing:?} need a frame but have none"),
+			suggestion,
+		);
+	}
+}
+
+fn lint_pool(class: &IRClassFile, lints: &mut Vec<Lint>) {
+	let mut seen = HashMap::new();
+	for (i, tag) in class.cp.iter().enumerate() {
+		let Some(key) = pool_key(tag) else {
+			continue;
+		};
+
+		let index = i + 1;
+		match seen.get(&key) {
+			Some(first) => push(
+				lints,
+				LintKind::DuplicatePoolEntry,
+				&format!("constant pool #{index}"),
+				format!("duplicate of #{first}"),
+				Some(format!("reuse #{first} instead of adding a new entry")),
+			),
+			None => {
+				seen.insert(key, index);
+			}
+		}
+	}
+}
+
+// Identifies an entry by what it resolves to, so two entries are duplicates exactly when their keys match.
+fn pool_key(tag: &IRCpTag) -> Option<String> {
+	let nat = |name: &str, ty: &str| format!("{name}:{ty}");
+	Some(match tag {
+		IRCpTag::Unusable => return None,
+		IRCpTag::Utf8(data) => format!("Utf8 {data}"),
+		IRCpTag::Integer(value) => format!("Integer {value}"),
+		IRCpTag::Float(value) => format!("Float {}", value.to_bits()),
+		IRCpTag::Long(value) => format!("Long {value}"),
+		IRCpTag::Double(value) => format!("Double {}", value.to_bits()),
+		IRCpTag::Class(name) => format!("Class {}", name.data),
+		IRCpTag::String(value) => format!("String {}", value.data),
+		IRCpTag::FieldRef {
+			class_index,
+			name_and_ty,
+		} => format!(
+			"Field {class_index} {}",
+			nat(&name_and_ty.name.data, &name_and_ty.ty.data)
+		),
+		IRCpTag::MethodRef {
+			class_index,
+			name_and_ty,
+		} => format!(
+			"Method {class_index} {}",
+			nat(&name_and_ty.name.data, &name_and_ty.ty.data)
+		),
+		IRCpTag::InterfaceMethodRef {
+			class_index,
+			name_and_ty,
+		} => format!(
+			"InterfaceMethod {class_index} {}",
+			nat(&name_and_ty.name.data, &name_and_ty.ty.data)
+		),
+		IRCpTag::NameAndType { name, descriptor } => format!("NameAndType {}", nat(&name.data, &descriptor.data)),
+		IRCpTag::MethodHandle {
+			ref_kind, ref_index, ..
+		} => format!("MethodHandle {ref_kind:?} {ref_index}"),
+		IRCpTag::MethodType(descriptor) => format!("MethodType {}", descriptor.data),
+		IRCpTag::InvokeDynamic {
+			bootstrap_method_attr_index,
+			name_and_ty,
+		} => format!(
+			"InvokeDynamic {bootstrap_method_attr_index} {}",
+			nat(&name_and_ty.name.data, &name_and_ty.ty.data)
+		),
+		IRCpTag::Module { name } => format!("Module {}", name.data),
+		IRCpTag::Package { name } => format!("Package {}", name.data),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use maya_classfile_ir::{
+		analysis::frames::{recompute_frames, KnownClasses},
+		asm::assemble,
+	};
+
+	use super::*;
+
+	const CONSTRUCTOR: &str = "
+		.method public <init>()V
+			aload_0
+			invokespecial java/lang/Object/<init>()V
+			return
+		.end method
+	";
+
+	fn class(methods: &str) -> IRClassFile {
+		assemble(&format!(".class public super p/Main\n{CONSTRUCTOR}{methods}")).unwrap()
+	}
+
+	fn kinds(class: &IRClassFile) -> Vec<LintKind> {
+		lint(class).into_iter().map(|lint| lint.kind).collect()
+	}
+
+	fn code(class: &mut IRClassFile, method: usize) -> &mut CodeAttribute {
+		class.methods[method].attributes.code_mut().unwrap()
+	}
+
+	#[test]
+	fn clean_class() {
+		assert_eq!(kinds(&class("")), []);
+	}
+
+	#[test]
+	fn missing_super_call() {
+		let class = assemble(".class public super p/Main\n.method public <init>()V\nreturn\n.end method").unwrap();
+		let lints = lint(&class);
+		assert_eq!(lints.len(), 1);
+		assert_eq!(lints[0].kind, LintKind::MissingSuperCall);
+		assert_eq!(lints[0].location, "method <init>()V");
+	}
+
+	#[test]
+	fn max_locals() {
+		let source = |locals: u16| format!(".method static f(IJ)V\n.limit locals {locals}\nreturn\n.end method");
+		assert_eq!(kinds(&class(&source(3))), []);
+		assert_eq!(kinds(&class(&source(2))), [LintKind::MaxLocalsTooSmall]);
+		let lints = lint(&class(&source(5)));
+		assert_eq!(lints[0].kind, LintKind::MaxLocalsTooLarge);
+		assert_eq!(lints[0].suggestion.as_deref(), Some("set max_locals to 3"));
+	}
+
+	#[test]
+	fn unreachable_code() {
+		let lints = lint(&class(".method static f()V\nreturn\nreturn\n.end method"));
+		let unreachable = lints
+			.iter()
+			.find(|lint| lint.kind == LintKind::UnreachableCode)
+			.unwrap();
+		assert_eq!(unreachable.message, "unreachable return at pc 1");
+	}
+
+	#[test]
+	fn stack_map() {
+		let branchy =
+			".method static f(I)I\niload_0\nifeq Zero\niconst_1\nireturn\nZero: iconst_0\nireturn\n.end method";
+		let mut class = class(branchy);
+		assert_eq!(kinds(&class), [LintKind::MissingStackMapTable]);
+
+		recompute_frames(&mut class, &KnownClasses::new()).unwrap();
+		assert_eq!(kinds(&class), []);
+
+		// A nop in front moves every instruction, but not the frames.
+		code(&mut class, 1).code.insert(0, 0);
+		assert!(kinds(&class).contains(&LintKind::StaleStackMapTable));
+	}
+
+	#[test]
+	fn undecodable_code() {
+		let mut class = class("");
+		// invokevirtual without its operand
+		code(&mut class, 0).code = vec![0xB6];
+		assert_eq!(kinds(&class), [LintKind::UndecodableCode]);
+	}
+
+	#[test]
+	fn duplicate_pool_entry() {
+		let mut class = class("");
+		let duplicate = class.cp.iter().position(|tag| matches!(tag, IRCpTag::Utf8(_))).unwrap();
+		class.cp.push(class.cp[duplicate].clone());
+		let lints = lint(&class);
+		assert_eq!(lints.len(), 1);
+		assert_eq!(lints[0].kind, LintKind::DuplicatePoolEntry);
+		assert_eq!(lints[0].location, format!("constant pool #{}", class.cp.len()));
+		assert_eq!(lints[0].message, format!("duplicate of #{}", duplicate + 1));
+	}
+}
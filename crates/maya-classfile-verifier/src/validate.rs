@@ -0,0 +1,870 @@
+// Structural checks from JVMS §4.1-§4.8 that a class has to pass before the JVM even starts verifying bytecode.
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html
+
+use std::{
+	collections::HashSet,
+	fmt::{self, Display},
+};
+
+use maya_classfile_ir::{
+	attribute::{CodeAttribute, IRAttribute, IRAttributeInfo},
+	class_pool::{IRCpTag, IRMethodRefKind},
+	descriptor::{FieldType, MethodDescriptor},
+	flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+	ClassFileVersion, IRClassFile,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+	// e.g. "method foo(I)V" or "constant pool #12"
+	pub location: String,
+	// The JVMS section the rule comes from.
+	pub section: &'static str,
+	pub message: String,
+}
+
+impl Display for Violation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {} (JVMS §{})", self.location, self.message, self.section)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttributeOwner {
+	Class,
+	Field,
+	Method,
+	Code,
+	RecordComponent,
+}
+
+/// Validates `class` against the structural constraints of the class file format and returns every violation found.
+pub fn validate(class: &IRClassFile) -> Vec<Violation> {
+	let mut validator = Validator {
+		class,
+		violations: Vec::new(),
+	};
+	validator.validate();
+	validator.violations
+}
+
+struct Validator<'a> {
+	class: &'a IRClassFile,
+	violations: Vec<Violation>,
+}
+
+impl Validator<'_> {
+	fn report(&mut self, location: impl Into<String>, section: &'static str, message: impl Into<String>) {
+		self.violations.push(Violation {
+			location: location.into(),
+			section,
+			message: message.into(),
+		});
+	}
+
+	fn validate(&mut self) {
+		self.check_version();
+		self.check_constant_pool();
+		self.check_class();
+
+		let class = self.class;
+		let mut seen = HashSet::new();
+		for field in &class.fields {
+			let location = format!("field {}", field.name.data);
+			if !seen.insert((field.name.data.as_str(), field.descriptor.data.as_str())) {
+				self.report(&location, "4.6", "duplicate field");
+			}
+			self.check_field(&location, field.access_flags, &field.name.data, &field.descriptor.data);
+			self.check_attributes(&location, AttributeOwner::Field, &field.attributes);
+		}
+
+		let mut seen = HashSet::new();
+		for method in &class.methods {
+			let location = format!("method {}{}", method.name.data, method.descriptor.data);
+			if !seen.insert((method.name.data.as_str(), method.descriptor.data.as_str())) {
+				self.report(&location, "4.6", "duplicate method");
+			}
+			self.check_method(&location, method);
+			self.check_attributes(&location, AttributeOwner::Method, &method.attributes);
+		}
+
+		self.check_attributes("class", AttributeOwner::Class, &class.attributes);
+	}
+
+	fn check_version(&mut self) {
+		let version = &self.class.version;
+		if version.major < 45 {
+			self.report("class", "4.1", format!("unsupported major version {}", version.major));
+		}
+		if version.minor == ClassFileVersion::PREVIEW_MINOR && !version.is_preview() {
+			self.report("class", "4.1", "preview features require major version 56 or above");
+		}
+	}
+
+	fn is_module(&self) -> bool {
+		self.class.access_flags.is_module()
+	}
+
+	fn check_constant_pool(&mut self) {
+		let cp = &self.class.cp;
+		for (i, tag) in cp.iter().enumerate() {
+			let location = format!("constant pool #{}", i + 1);
+			match tag {
+				IRCpTag::Long(_) | IRCpTag::Double(_) => {
+					if !matches!(cp.get(i + 1), Some(IRCpTag::Unusable)) {
+						self.report(
+							&location,
+							"4.4.5",
+							"8-byte constant must be followed by an unusable entry",
+						);
+					}
+				}
+				IRCpTag::Unusable => {
+					if !matches!(
+						i.checked_sub(1).and_then(|prev| cp.get(prev)),
+						Some(IRCpTag::Long(_) | IRCpTag::Double(_))
+					) {
+						self.report(&location, "4.4.5", "unusable entry does not follow an 8-byte constant");
+					}
+				}
+				IRCpTag::Class(name) => {
+					let valid = if name.data.starts_with('[') {
+						FieldType::parse(&name.data).is_ok_and(|ty| ty.array_dimensions() <= 255)
+					} else {
+						is_valid_class_name(&name.data)
+					};
+					if !valid {
+						self.report(&location, "4.4.1", format!("invalid class name {:?}", name.data));
+					}
+				}
+				IRCpTag::NameAndType { name, descriptor } => {
+					if !is_valid_member_name(&name.data) {
+						self.report(&location, "4.2.2", format!("invalid member name {:?}", name.data));
+					}
+					if FieldType::parse(&descriptor.data).is_err() && MethodDescriptor::parse(&descriptor.data).is_err()
+					{
+						self.report(&location, "4.3", format!("invalid descriptor {:?}", descriptor.data));
+					}
+				}
+				IRCpTag::FieldRef {
+					class_index,
+					name_and_ty,
+				} => {
+					self.check_class_index(&location, *class_index);
+					if FieldType::parse(&name_and_ty.ty.data).is_err() {
+						self.report(&location, "4.4.2", "FieldRef must have a field descriptor");
+					}
+				}
+				IRCpTag::MethodRef {
+					class_index,
+					name_and_ty,
+				}
+				| IRCpTag::InterfaceMethodRef {
+					class_index,
+					name_and_ty,
+				} => {
+					self.check_class_index(&location, *class_index);
+					match MethodDescriptor::parse(&name_and_ty.ty.data) {
+						Err(_) => self.report(&location, "4.4.2", "method reference must have a method descriptor"),
+						Ok(desc) => {
+							if name_and_ty.name.data.starts_with('<')
+								&& (name_and_ty.name.data.as_str() != "<init>" || desc.ret.is_some())
+							{
+								self.report(&location, "4.4.2", "only <init> may be referenced by a special name");
+							}
+						}
+					}
+				}
+				IRCpTag::MethodHandle { ref_kind, ref_tag, .. } => {
+					self.check_method_handle(&location, ref_kind, ref_tag)
+				}
+				IRCpTag::MethodType(descriptor) => {
+					if MethodDescriptor::parse(&descriptor.data).is_err() {
+						self.report(&location, "4.4.9", "MethodType must have a method descriptor");
+					}
+				}
+				IRCpTag::InvokeDynamic { name_and_ty, .. } => {
+					if MethodDescriptor::parse(&name_and_ty.ty.data).is_err() {
+						self.report(&location, "4.4.10", "InvokeDynamic must have a method descriptor");
+					}
+				}
+				IRCpTag::Module { .. } | IRCpTag::Package { .. } if !self.is_module() => {
+					self.report(
+						&location,
+						"4.4.11",
+						"Module and Package entries are only allowed in module-info",
+					);
+				}
+				_ => {}
+			}
+		}
+	}
+
+	fn check_class_index(&mut self, location: &str, index: u16) {
+		let is_class = index != 0 && matches!(self.class.cp.get(index as usize - 1), Some(IRCpTag::Class(_)));
+		if !is_class {
+			self.report(location, "4.4.2", format!("class_index #{index} is not a Class entry"));
+		}
+	}
+
+	fn check_method_handle(&mut self, location: &str, kind: &IRMethodRefKind, tag: &IRCpTag) {
+		use IRMethodRefKind::*;
+
+		let (valid, name) = match (kind, tag) {
+			(GetField | GetStatic | PutField | PutStatic, IRCpTag::FieldRef { .. }) => (true, None),
+			(InvokeVirtual | NewInvokeSpecial, IRCpTag::MethodRef { name_and_ty, .. }) => (true, Some(name_and_ty)),
+			(InvokeStatic | InvokeSpecial, IRCpTag::MethodRef { name_and_ty, .. }) => (true, Some(name_and_ty)),
+			(InvokeStatic | InvokeSpecial, IRCpTag::InterfaceMethodRef { name_and_ty, .. }) => {
+				(self.class.version.supports_default_methods(), Some(name_and_ty))
+			}
+			(InvokeInterface, IRCpTag::InterfaceMethodRef { name_and_ty, .. }) => (true, Some(name_and_ty)),
+			_ => (false, None),
+		};
+
+		if !valid {
+			self.report(
+				location,
+				"4.4.8",
+				format!("reference kind {kind:?} can't refer to this entry"),
+			);
+			return;
+		}
+
+		if let Some(name) = name {
+			let is_init = name.name.data.as_str() == "<init>";
+			let special = is_init || name.name.data.as_str() == "<clinit>";
+			match kind {
+				NewInvokeSpecial if !is_init => self.report(location, "4.4.8", "newInvokeSpecial must refer to <init>"),
+				NewInvokeSpecial => {}
+				_ if special => self.report(location, "4.4.8", "method handle can't refer to <init> or <clinit>"),
+				_ => {}
+			}
+		}
+	}
+
+	fn check_class(&mut self) {
+		let class = self.class;
+		let flags = class.access_flags;
+
+		if flags.is_module() {
+			if flags != ClassAccessFlags::MODULE {
+				self.report("class", "4.1", "ACC_MODULE can't be combined with other flags");
+			}
+			if class.this_class.data.data.as_str() != "module-info" {
+				self.report("class", "4.1", "a module's this_class must be module-info");
+			}
+		} else if flags.is_interface() {
+			if !flags.is_abstract() {
+				self.report("class", "4.1", "interfaces must be ACC_ABSTRACT");
+			}
+			if flags.intersects(ClassAccessFlags::FINAL | ClassAccessFlags::SUPER | ClassAccessFlags::ENUM) {
+				self.report("class", "4.1", "interfaces can't be ACC_FINAL, ACC_SUPER or ACC_ENUM");
+			}
+		} else {
+			if flags.is_annotation() {
+				self.report("class", "4.1", "ACC_ANNOTATION requires ACC_INTERFACE");
+			}
+			if flags.is_final() && flags.is_abstract() {
+				self.report("class", "4.1", "a class can't be both ACC_FINAL and ACC_ABSTRACT");
+			}
+		}
+
+		let name = class.this_class.data.data.as_str();
+		if class.super_class.is_none() && name != "java/lang/Object" && !self.is_module() {
+			self.report("class", "4.1", "only java/lang/Object and modules may omit super_class");
+		}
+		if flags.is_interface()
+			&& class
+				.super_class
+				.as_ref()
+				.is_some_and(|sup| sup.data.data.as_str() != "java/lang/Object")
+		{
+			self.report(
+				"class",
+				"4.1",
+				"the super class of an interface must be java/lang/Object",
+			);
+		}
+	}
+
+	// Fields and methods share the visibility bits, so this takes them raw.
+	fn check_visibility(&mut self, location: &str, section: &'static str, flags: u16) {
+		let visibility =
+			flags & (MethodAccessFlags::PUBLIC | MethodAccessFlags::PRIVATE | MethodAccessFlags::PROTECTED).bits();
+		if visibility.count_ones() > 1 {
+			self.report(
+				location,
+				section,
+				"at most one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED may be set",
+			);
+		}
+	}
+
+	fn check_field(&mut self, location: &str, flags: FieldAccessFlags, name: &str, descriptor: &str) {
+		if !is_valid_unqualified_name(name) {
+			self.report(location, "4.2.2", format!("invalid field name {name:?}"));
+		}
+		if FieldType::parse(descriptor).is_err() {
+			self.report(location, "4.3.2", format!("invalid field descriptor {descriptor:?}"));
+		}
+
+		self.check_visibility(location, "4.5", flags.bits());
+		if flags.is_final() && flags.is_volatile() {
+			self.report(location, "4.5", "a field can't be both ACC_FINAL and ACC_VOLATILE");
+		}
+
+		let required = FieldAccessFlags::PUBLIC | FieldAccessFlags::STATIC | FieldAccessFlags::FINAL;
+		if self.class.access_flags.is_interface() && !flags.contains(required) {
+			self.report(location, "4.5", "interface fields must be public static final");
+		}
+	}
+
+	fn check_method(&mut self, location: &str, method: &maya_classfile_ir::IRMethodInfo) {
+		let flags = method.access_flags;
+		let name = method.name.data.as_str();
+		let major = self.class.version.major;
+		let is_interface = self.class.access_flags.is_interface();
+
+		if name.starts_with('<') && name != "<init>" && name != "<clinit>" || !is_valid_member_name(name) {
+			self.report(location, "4.2.2", format!("invalid method name {name:?}"));
+		}
+
+		let descriptor = match MethodDescriptor::parse(&method.descriptor.data) {
+			Ok(descriptor) => Some(descriptor),
+			Err(_) => {
+				self.report(location, "4.3.3", "invalid method descriptor");
+				None
+			}
+		};
+
+		if let Some(descriptor) = &descriptor {
+			let this_slot = u16::from(!flags.is_static());
+			if descriptor.param_slots() + this_slot > 255 {
+				self.report(location, "4.3.3", "method parameters take more than 255 slots");
+			}
+			if (name == "<init>" || name == "<clinit>") && descriptor.ret.is_some() {
+				self.report(location, "4.2.2", "initialization methods must return void");
+			}
+		}
+
+		if name == "<clinit>" {
+			if major >= 51 && !flags.is_static() {
+				self.report(location, "4.6", "<clinit> must be ACC_STATIC");
+			}
+		} else {
+			self.check_visibility(location, "4.6", flags.bits());
+		}
+
+		if name == "<init>" {
+			let allowed = MethodAccessFlags::PUBLIC
+				| MethodAccessFlags::PRIVATE
+				| MethodAccessFlags::PROTECTED
+				| MethodAccessFlags::VARARGS
+				| MethodAccessFlags::STRICT
+				| MethodAccessFlags::SYNTHETIC;
+			if !allowed.contains(flags) {
+				self.report(
+					location,
+					"4.6",
+					"<init> may only be a visibility, varargs, strict or synthetic method",
+				);
+			}
+			if is_interface {
+				self.report(location, "4.6", "interfaces can't declare <init>");
+			}
+		}
+
+		if flags.is_abstract() {
+			let mut forbidden = MethodAccessFlags::PRIVATE
+				| MethodAccessFlags::STATIC
+				| MethodAccessFlags::FINAL
+				| MethodAccessFlags::SYNCHRONIZED
+				| MethodAccessFlags::NATIVE;
+			if (46..61).contains(&major) {
+				forbidden |= MethodAccessFlags::STRICT;
+			}
+			if flags.intersects(forbidden) {
+				self.report(
+					location,
+					"4.6",
+					"abstract methods can't be private, static, final, synchronized, native or strict",
+				);
+			}
+		}
+
+		if is_interface && name != "<clinit>" {
+			if !self.class.version.supports_default_methods() {
+				if !flags.contains(MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT) {
+					self.report(
+						location,
+						"4.6",
+						"interface methods must be public abstract before Java 8",
+					);
+				}
+			} else if flags.intersects(
+				MethodAccessFlags::PROTECTED
+					| MethodAccessFlags::FINAL
+					| MethodAccessFlags::SYNCHRONIZED
+					| MethodAccessFlags::NATIVE,
+			) || flags.is_public() == flags.is_private()
+			{
+				self.report(location, "4.6", "interface methods must be exactly one of public or private and can't be protected, final, synchronized or native");
+			}
+		}
+
+		let code = method.code();
+		let bodiless = flags.intersects(MethodAccessFlags::ABSTRACT | MethodAccessFlags::NATIVE);
+		match (code, bodiless) {
+			(Some(_), true) => self.report(
+				location,
+				"4.7.3",
+				"abstract and native methods must not have a Code attribute",
+			),
+			(None, false) => self.report(location, "4.7.3", "missing Code attribute"),
+			(Some(code), false) => {
+				let min_locals = descriptor.map(|desc| desc.param_slots() + u16::from(!flags.is_static()));
+				self.check_code(location, code, min_locals);
+			}
+			(None, true) => {}
+		}
+	}
+
+	fn check_code(&mut self, location: &str, code: &CodeAttribute, min_locals: Option<u16>) {
+		let len = code.code.len();
+		if len == 0 || len >= 65536 {
+			self.report(location, "4.7.3", format!("code_length {len} is outside 1..65536"));
+		}
+		if let Some(min_locals) = min_locals {
+			if code.max_locals < min_locals {
+				self.report(
+					location,
+					"4.7.3",
+					format!(
+						"max_locals {} is smaller than the {min_locals} slots the parameters need",
+						code.max_locals
+					),
+				);
+			}
+		}
+
+		for (i, entry) in code.exception_table.iter().enumerate() {
+			let entry_location = format!("{location} exception_table[{i}]");
+			if entry.start_pc >= entry.end_pc || entry.end_pc as usize > len {
+				self.report(&entry_location, "4.7.3", "invalid start_pc/end_pc range");
+			}
+			if entry.handler_pc as usize >= len {
+				self.report(&entry_location, "4.7.3", "handler_pc is outside the code array");
+			}
+			if entry.catch_type != 0 {
+				self.check_class_index(&entry_location, entry.catch_type);
+			}
+		}
+
+		self.check_attributes(&format!("{location} Code"), AttributeOwner::Code, &code.attributes);
+	}
+
+	fn check_attributes<'b>(
+		&mut self,
+		location: &str,
+		owner: AttributeOwner,
+		attributes: impl IntoIterator<Item = &'b IRAttributeInfo>,
+	) {
+		let mut seen = HashSet::new();
+		for attr in attributes {
+			let name = attr.name.data.as_str();
+			if matches!(attr.attr, IRAttribute::Unknown(_)) {
+				continue;
+			}
+
+			if !attribute_allowed(name, owner) {
+				self.report(location, "4.7", format!("{name} is not allowed on {owner:?}"));
+			}
+
+			let repeatable = matches!(
+				name,
+				"LineNumberTable" | "LocalVariableTable" | "LocalVariableTypeTable"
+			);
+			if !repeatable && !seen.insert(name) {
+				self.report(location, "4.7", format!("more than one {name} attribute"));
+			}
+
+			if let IRAttribute::Record { components } = &attr.attr {
+				for component in components {
+					let component_location = format!("record component {}", component.name.data);
+					if FieldType::parse(&component.descriptor.data).is_err() {
+						self.report(&component_location, "4.7.30", "invalid component descriptor");
+					}
+					self.check_attributes(
+						&component_location,
+						AttributeOwner::RecordComponent,
+						&component.attributes,
+					);
+				}
+			}
+		}
+	}
+}
+
+// JVMS Table 4.7-C
+fn attribute_allowed(name: &str, owner: AttributeOwner) -> bool {
+	use AttributeOwner::*;
+
+	match name {
+		"Synthetic" | "Deprecated" => matches!(owner, Class | Field | Method),
+		"Signature" | "RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => {
+			matches!(owner, Class | Field | Method | RecordComponent)
+		}
+		"RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => true,
+		"ConstantValue" => owner == Field,
+		"Code"
+		| "Exceptions"
+		| "RuntimeVisibleParameterAnnotations"
+		| "RuntimeInvisibleParameterAnnotations"
+		| "AnnotationDefault"
+		| "MethodParameters" => owner == Method,
+		"LineNumberTable" | "LocalVariableTable" | "LocalVariableTypeTable" | "StackMapTable" => owner == Code,
+		"SourceFile"
+		| "InnerClasses"
+		| "EnclosingMethod"
+		| "SourceDebugExtension"
+		| "BootstrapMethods"
+		| "Module"
+		| "ModulePackages"
+		| "ModuleMainClass"
+		| "NestHost"
+		| "NestMembers"
+		| "Record"
+		| "PermittedSubclasses" => owner == Class,
+		_ => true,
+	}
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.2.2
+fn is_valid_unqualified_name(name: &str) -> bool {
+	!name.is_empty() && !name.contains(['.', ';', '[', '/'])
+}
+
+fn is_valid_member_name(name: &str) -> bool {
+	name == "<init>" || name == "<clinit>" || (is_valid_unqualified_name(name) && !name.contains(['<', '>']))
+}
+
+fn is_valid_class_name(name: &str) -> bool {
+	!name.is_empty() && name.split('/').all(is_valid_unqualified_name)
+}
+
+#[cfg(test)]
+mod tests {
+	use maya_classfile_ir::{
+		asm::assemble,
+		attribute::CodeAttributeException,
+		class_pool::{CPClassRef, CPFieldRef, CPMethodRef, CPNameAndTypeRef, CPUtf8Ref},
+	};
+
+	use super::*;
+
+	const CONSTRUCTOR: &str = "
+		.method public <init>()V
+			aload_0
+			invokespecial java/lang/Object/<init>()V
+			return
+		.end method
+	";
+
+	fn class(body: &str) -> IRClassFile {
+		assemble(&format!(".class public super p/Main\n.source \"Main.java\"\n{body}")).unwrap()
+	}
+
+	fn messages(class: &IRClassFile) -> Vec<String> {
+		validate(class).iter().map(Violation::to_string).collect()
+	}
+
+	#[track_caller]
+	fn assert_reports(class: &IRClassFile, expected: &[&str]) {
+		let messages = messages(class);
+		for message in expected {
+			assert!(
+				messages.iter().any(|found| found == message),
+				"{message:?} not in {messages:#?}"
+			);
+		}
+	}
+
+	#[test]
+	fn well_formed_class() {
+		let class = class(&format!(
+			"{CONSTRUCTOR}
+			.field private static final LIMIT I = 5
+			.method public static max(II)I
+				iload_0
+				iload_1
+				if_icmpge First
+				iload_1
+				ireturn
+			First: iload_0
+				ireturn
+			.end method"
+		));
+		assert_eq!(messages(&class), Vec::<String>::new());
+
+		let interface = assemble(
+			".class public interface abstract p/Shape\n.field public static final SIDES I = 4\n.method public \
+			 abstract area()D\n.end method",
+		)
+		.unwrap();
+		assert_eq!(messages(&interface), Vec::<String>::new());
+	}
+
+	#[test]
+	fn version() {
+		let mut class = class(CONSTRUCTOR);
+		class.version.major = 44;
+		assert_reports(&class, &["class: unsupported major version 44 (JVMS §4.1)"]);
+		class.version = ClassFileVersion {
+			major: 52,
+			minor: ClassFileVersion::PREVIEW_MINOR,
+		};
+		assert_reports(
+			&class,
+			&["class: preview features require major version 56 or above (JVMS §4.1)"],
+		);
+	}
+
+	#[test]
+	fn constant_pool() {
+		let mut class = class(CONSTRUCTOR);
+		let cp = &mut class.cp;
+		CPClassRef::find_or_add(cp, "p.Main").unwrap();
+		CPNameAndTypeRef::find_or_add(cp, "a;b", "Q").unwrap();
+		CPFieldRef::find_or_add(cp, "p/Main", "count", "()V").unwrap();
+		CPMethodRef::find_or_add(cp, "p/Main", "run", "I").unwrap();
+		CPMethodRef::find_or_add(cp, "p/Main", "<clinit>", "()V").unwrap();
+		let count = CPNameAndTypeRef::find_or_add(cp, "count", "I").unwrap();
+		cp.push(IRCpTag::FieldRef {
+			class_index: count.name.index,
+			name_and_ty: count,
+		});
+		let handle = |cp: &mut Vec<IRCpTag>, ref_kind, name: &str| {
+			let method = CPMethodRef::find_or_add(cp, "p/Main", name, "()V").unwrap();
+			cp.push(IRCpTag::MethodHandle {
+				ref_kind,
+				ref_index: method.index,
+				ref_tag: Box::new(cp[method.index as usize - 1].clone()),
+			});
+		};
+		handle(cp, IRMethodRefKind::GetField, "run");
+		handle(cp, IRMethodRefKind::NewInvokeSpecial, "run");
+		handle(cp, IRMethodRefKind::InvokeStatic, "<init>");
+		let name = CPUtf8Ref::find_or_add(cp, "java.base").unwrap();
+		cp.push(IRCpTag::Module { name });
+		cp.push(IRCpTag::Unusable);
+		cp.push(IRCpTag::Long(1));
+		let long = cp.len();
+		let messages = messages(&class);
+		for expected in [
+			"invalid class name \"p.Main\" (JVMS §4.4.1)".to_string(),
+			"invalid member name \"a;b\" (JVMS §4.2.2)".to_string(),
+			"invalid descriptor \"Q\" (JVMS §4.3)".to_string(),
+			"FieldRef must have a field descriptor (JVMS §4.4.2)".to_string(),
+			"method reference must have a method descriptor (JVMS §4.4.2)".to_string(),
+			"only <init> may be referenced by a special name (JVMS §4.4.2)".to_string(),
+			"is not a Class entry (JVMS §4.4.2)".to_string(),
+			"reference kind GetField can't refer to this entry (JVMS §4.4.8)".to_string(),
+			"newInvokeSpecial must refer to <init> (JVMS §4.4.8)".to_string(),
+			"method handle can't refer to <init> or <clinit> (JVMS §4.4.8)".to_string(),
+			format!(
+				"#{}: Module and Package entries are only allowed in module-info (JVMS §4.4.11)",
+				long - 2
+			),
+			format!(
+				"#{}: unusable entry does not follow an 8-byte constant (JVMS §4.4.5)",
+				long - 1
+			),
+			format!("#{long}: 8-byte constant must be followed by an unusable entry (JVMS §4.4.5)"),
+		] {
+			assert!(
+				messages.iter().any(|message| message.ends_with(&expected)),
+				"{expected:?} not in {messages:#?}"
+			);
+		}
+	}
+
+	#[test]
+	fn class_flags() {
+		let mut class = class(CONSTRUCTOR);
+		class.access_flags = ClassAccessFlags::PUBLIC
+			| ClassAccessFlags::FINAL
+			| ClassAccessFlags::ABSTRACT
+			| ClassAccessFlags::ANNOTATION;
+		class.super_class = None;
+		assert_reports(
+			&class,
+			&[
+				"class: ACC_ANNOTATION requires ACC_INTERFACE (JVMS §4.1)",
+				"class: a class can't be both ACC_FINAL and ACC_ABSTRACT (JVMS §4.1)",
+				"class: only java/lang/Object and modules may omit super_class (JVMS §4.1)",
+			],
+		);
+
+		let mut interface = assemble(".class public final interface p/Shape\n.super p/Base").unwrap();
+		assert_reports(
+			&interface,
+			&[
+				"class: interfaces must be ACC_ABSTRACT (JVMS §4.1)",
+				"class: interfaces can't be ACC_FINAL, ACC_SUPER or ACC_ENUM (JVMS §4.1)",
+				"class: the super class of an interface must be java/lang/Object (JVMS §4.1)",
+			],
+		);
+		interface.access_flags = ClassAccessFlags::MODULE | ClassAccessFlags::PUBLIC;
+		assert_reports(
+			&interface,
+			&[
+				"class: ACC_MODULE can't be combined with other flags (JVMS §4.1)",
+				"class: a module's this_class must be module-info (JVMS §4.1)",
+			],
+		);
+	}
+
+	#[test]
+	fn fields() {
+		let class = class(&format!(
+			"{CONSTRUCTOR}
+			.field public private final volatile count I
+			.field static count I
+			.field static count I
+			.field static a.b Q"
+		));
+		assert_reports(
+			&class,
+			&[
+				"field count: at most one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED may be set (JVMS §4.5)",
+				"field count: a field can't be both ACC_FINAL and ACC_VOLATILE (JVMS §4.5)",
+				"field count: duplicate field (JVMS §4.6)",
+				"field a.b: invalid field name \"a.b\" (JVMS §4.2.2)",
+				"field a.b: invalid field descriptor \"Q\" (JVMS §4.3.2)",
+			],
+		);
+
+		let interface = assemble(".class public interface abstract p/Shape\n.field static SIDES I").unwrap();
+		assert_reports(
+			&interface,
+			&["field SIDES: interface fields must be public static final (JVMS §4.5)"],
+		);
+	}
+
+	#[test]
+	fn methods() {
+		let class = class(&format!(
+			"{CONSTRUCTOR}
+			.method public <init>(I)I
+				iconst_0
+				ireturn
+			.end method
+			.method <clinit>()V
+				return
+			.end method
+			.method public abstract static run()V
+				return
+			.end method
+			.method public native ping()V
+			.end method
+			.method public ping()V
+			.end method
+			.method public static <init>()V
+				return
+			.end method
+			.method a.b()V
+				return
+			.end method
+			.method static wide({})V
+				return
+			.end method",
+			"J".repeat(128)
+		));
+		assert_reports(
+			&class,
+			&[
+				"method <init>(I)I: initialization methods must return void (JVMS §4.2.2)",
+				"method <clinit>()V: <clinit> must be ACC_STATIC (JVMS §4.6)",
+				"method run()V: abstract methods can't be private, static, final, synchronized, native or strict (JVMS \
+				 §4.6)",
+				"method run()V: abstract and native methods must not have a Code attribute (JVMS §4.7.3)",
+				"method ping()V: duplicate method (JVMS §4.6)",
+				"method ping()V: missing Code attribute (JVMS §4.7.3)",
+				"method <init>()V: <init> may only be a visibility, varargs, strict or synthetic method (JVMS §4.6)",
+				"method a.b()V: invalid method name \"a.b\" (JVMS §4.2.2)",
+				&format!(
+					"method wide({})V: method parameters take more than 255 slots (JVMS §4.3.3)",
+					"J".repeat(128)
+				),
+			],
+		);
+
+		let interface = assemble(
+			".class public interface abstract p/Shape\n.method protected abstract area()D\n.end method\n.method \
+			 public <init>()V\nreturn\n.end method",
+		)
+		.unwrap();
+		assert_reports(
+			&interface,
+			&[
+				"method area()D: interface methods must be exactly one of public or private and can't be protected, \
+				 final, synchronized or native (JVMS §4.6)",
+				"method <init>()V: interfaces can't declare <init> (JVMS §4.6)",
+			],
+		);
+
+		let mut old_interface = assemble(
+			".version 51\n.class public interface abstract p/Shape\n.method public static area()D\n.end method",
+		)
+		.unwrap();
+		assert_reports(
+			&old_interface,
+			&["method area()D: interface methods must be public abstract before Java 8 (JVMS §4.6)"],
+		);
+		old_interface.methods[0].descriptor = CPUtf8Ref::find_or_add(&mut old_interface.cp, "(Q)D").unwrap();
+		assert_reports(
+			&old_interface,
+			&["method area(Q)D: invalid method descriptor (JVMS §4.3.3)"],
+		);
+	}
+
+	#[test]
+	fn code_and_attributes() {
+		let mut class = class(&format!(
+			"{CONSTRUCTOR}
+			.method static f(J)V
+				.limit locals 1
+				return
+			.end method"
+		));
+		let source_file = class.attributes[0].clone();
+		class.attributes.push(source_file.clone());
+		let method = &mut class.methods[1];
+		method.attributes.push(source_file);
+		let code = method.attributes.code_mut().unwrap();
+		code.code.clear();
+		code.exception_table.push(CodeAttributeException {
+			start_pc: 1,
+			end_pc: 1,
+			handler_pc: 4,
+			catch_type: 0,
+		});
+		assert_reports(
+			&class,
+			&[
+				"method f(J)V: code_length 0 is outside 1..65536 (JVMS §4.7.3)",
+				"method f(J)V: max_locals 1 is smaller than the 2 slots the parameters need (JVMS §4.7.3)",
+				"method f(J)V exception_table[0]: invalid start_pc/end_pc range (JVMS §4.7.3)",
+				"method f(J)V exception_table[0]: handler_pc is outside the code array (JVMS §4.7.3)",
+				"method f(J)V: SourceFile is not allowed on Method (JVMS §4.7)",
+				"class: more than one SourceFile attribute (JVMS §4.7)",
+			],
+		);
+
+		let record = assemble(".class public final super p/Point\n.super java/lang/Record\n.component x Q").unwrap();
+		assert_reports(
+			&record,
+			&["record component x: invalid component descriptor (JVMS §4.7.30)"],
+		);
+	}
+}
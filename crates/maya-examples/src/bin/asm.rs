@@ -0,0 +1,20 @@
+use std::{env, fs};
+
+use eyre::bail;
+use maya_classfile_ir::{
+	analysis::frames::{recompute_frames, KnownClasses},
+	asm::assemble,
+};
+
+fn main() -> eyre::Result<()> {
+	let args = env::args().skip(1).collect::<Vec<_>>();
+	let [source, output] = &args[..] else {
+		bail!("usage: asm <source.j> <output.class>");
+	};
+
+	let mut class = assemble(&fs::read_to_string(source)?)?;
+	let hierarchy = KnownClasses::from_classes([&class]);
+	recompute_frames(&mut class, &hierarchy)?;
+	fs::write(output, class.to_bytes()?)?;
+	Ok(())
+}
@@ -0,0 +1,18 @@
+use std::{env, fs};
+
+use eyre::bail;
+use maya_classfile_ir::text::{bytes_to_text, text_to_bytes};
+
+// `classtext Foo.class Foo.txt` writes the text form, `classtext Foo.txt Foo.class` the class back.
+fn main() -> eyre::Result<()> {
+	let args = env::args().skip(1).collect::<Vec<_>>();
+	let [input, output] = &args[..] else {
+		bail!("usage: classtext <input.class|input.txt> <output.txt|output.class>");
+	};
+
+	match input.ends_with(".class") {
+		true => fs::write(output, bytes_to_text(&fs::read(input)?)?)?,
+		false => fs::write(output, text_to_bytes(&fs::read_to_string(input)?)?)?,
+	}
+	Ok(())
+}
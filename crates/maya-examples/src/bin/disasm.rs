@@ -0,0 +1,17 @@
+use std::{env, fs};
+
+use eyre::bail;
+use maya_classfile_ir::{disasm::disassemble, IRClassFile};
+
+fn main() -> eyre::Result<()> {
+	let args = env::args().skip(1).collect::<Vec<_>>();
+	if args.is_empty() {
+		bail!("usage: disasm <file.class>...");
+	}
+
+	for path in &args {
+		let class = IRClassFile::read(&fs::read(path)?)?;
+		print!("{}", disassemble(&class)?);
+	}
+	Ok(())
+}
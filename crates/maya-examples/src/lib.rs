@@ -0,0 +1,183 @@
+// What a simple java agent does at load time, done ahead of time on a whole jar instead: read the classes, add timing
+// to the methods asked for, recompute their frames, check the result still holds together and write a jar that is
+// byte for byte the same each time it's built from the same input.
+
+pub mod timing;
+
+use std::io::{Cursor, Write};
+
+use eyre::{bail, eyre};
+use maya_classfile_ir::{
+	analysis::{
+		frames::{recompute_frames, KnownClasses},
+		stack,
+	},
+	archive::{Archive, DuplicatePolicy},
+	metrics::PipelineMetrics,
+	transform::Pipeline,
+	IRClassFile,
+};
+use maya_classfile_verifier::validate::validate;
+use zip::{write::FileOptions, CompressionMethod, DateTime, ZipWriter};
+
+use crate::timing::{timings_class, MethodMatcher, TimingTransform, TIMINGS_CLASS};
+
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+
+/// Instruments the methods `matcher` picks in the jar `input` and returns the new jar with what the pipeline did.
+pub fn instrument_jar(input: &[u8], matcher: MethodMatcher) -> eyre::Result<(Vec<u8>, PipelineMetrics)> {
+	let archive = Archive::read(Cursor::new(input), DuplicatePolicy::FirstWins)?;
+	let mut classes = archive
+		.classes()
+		.map(|entry| Ok((entry.name.clone(), IRClassFile::read(&entry.data)?)))
+		.collect::<eyre::Result<Vec<_>>>()?;
+	let hierarchy = KnownClasses::from_classes(classes.iter().map(|(_, class)| class));
+
+	let mut pipeline = Pipeline::new().with(TimingTransform::new(matcher)).record_changes(true);
+	let mut instrumented = false;
+	for (name, class) in &mut classes {
+		let methods = class.methods.len();
+		pipeline.run(class)?;
+		if class.methods.len() == methods {
+			continue;
+		}
+		instrumented = true;
+		recompute_frames(class, &hierarchy).map_err(|err| eyre!("{name}: {err}"))?;
+		check(class).map_err(|err| eyre!("{name}: {err}"))?;
+	}
+	if instrumented {
+		let timings = timings_class()?;
+		check(&timings)?;
+		classes.push((format!("{TIMINGS_CLASS}.class"), timings));
+	}
+
+	let mut entries = archive
+		.entries()
+		.iter()
+		.filter(|entry| !entry.is_class() && !is_signature(&entry.name))
+		.map(|entry| Ok((entry.name.clone(), entry.data.clone())))
+		.chain(
+			classes
+				.iter()
+				.map(|(name, class)| Ok((name.clone(), class.to_bytes()?))),
+		)
+		.collect::<eyre::Result<Vec<_>>>()?;
+	// The manifest goes first so `java.util.jar.JarInputStream` finds it.
+	entries.sort_by(|(a, _), (b, _)| (a != MANIFEST, a).cmp(&(b != MANIFEST, b)));
+	let output = write_jar(&entries)?;
+
+	pipeline.metrics_mut().add_bytes(input.len(), output.len());
+	Ok((output, pipeline.metrics().clone()))
+}
+
+/// Fails on anything that would keep the class from loading: structural violations, or code whose stack doesn't add
+/// up.
+fn check(class: &IRClassFile) -> eyre::Result<()> {
+	if let Some(violation) = validate(class).first() {
+		bail!("{violation}");
+	}
+	let class_name = class.class_name();
+	for method in &class.methods {
+		stack::simulate(&class.cp, class_name, method)
+			.map_err(|err| eyre!("{}{}: {err}", method.name(), method.descriptor()))?;
+	}
+	Ok(())
+}
+
+// Signatures of the original jar no longer match the classes, and a jar with stale ones fails to load.
+fn is_signature(name: &str) -> bool {
+	name.strip_prefix("META-INF/")
+		.is_some_and(|file| !file.contains('/') && [".SF", ".RSA", ".DSA", ".EC"].iter().any(|ext| file.ends_with(ext)))
+}
+
+// Fixed timestamps and permissions, so only the entries themselves decide the bytes.
+fn write_jar(entries: &[(String, Vec<u8>)]) -> eyre::Result<Vec<u8>> {
+	let options = FileOptions::default()
+		.compression_method(CompressionMethod::Deflated)
+		.last_modified_time(DateTime::default())
+		.unix_permissions(0o644);
+	let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+	for (name, data) in entries {
+		zip.start_file(name, options)?;
+		zip.write_all(data)?;
+	}
+	Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+	use maya_classfile_ir::{
+		attribute::IRAttribute,
+		builder::{ClassBuilder, CodeBuilder},
+		code::Instructions,
+		flags::MethodAccessFlags,
+	};
+
+	use super::*;
+
+	fn input_jar() -> Vec<u8> {
+		let mut builder = ClassBuilder::new("com/example/Calc").unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		code.max_stack(2)
+			.max_locals(2)
+			.insn(Instructions::ILOAD(0))
+			.insn(Instructions::ILOAD(1))
+			.insn(Instructions::IADD)
+			.insn(Instructions::IRETURN);
+		let add = code.build().unwrap();
+		let mut code = CodeBuilder::new(builder.cp());
+		code.max_stack(1).max_locals(1).insn(Instructions::RETURN);
+		let reset = code.build().unwrap();
+		builder
+			.method(
+				MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+				"add",
+				"(II)I",
+				[IRAttribute::Code(add)],
+			)
+			.unwrap()
+			.method(MethodAccessFlags::PUBLIC, "reset", "()V", [IRAttribute::Code(reset)])
+			.unwrap();
+		let class = builder.to_bytes().unwrap();
+
+		let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+		for (name, data) in [
+			("com/example/Calc.class", &class[..]),
+			("META-INF/CALC.SF", b"signature"),
+			(MANIFEST, b"Manifest-Version: 1.0\n"),
+		] {
+			zip.start_file(name, FileOptions::default()).unwrap();
+			zip.write_all(data).unwrap();
+		}
+		zip.finish().unwrap().into_inner()
+	}
+
+	#[test]
+	fn instrument_and_rebuild() {
+		let input = input_jar();
+		let matcher = MethodMatcher {
+			class_prefix: "com/example/".to_string(),
+			method: None,
+		};
+		let (output, metrics) = instrument_jar(&input, matcher.clone()).unwrap();
+		assert_eq!(instrument_jar(&input, matcher).unwrap().0, output);
+		assert_eq!(metrics.classes_processed, 1);
+
+		let archive = Archive::read(Cursor::new(&output), DuplicatePolicy::Error).unwrap();
+		let names = archive
+			.entries()
+			.iter()
+			.map(|entry| entry.name.as_str())
+			.collect::<Vec<_>>();
+		assert_eq!(names, [MANIFEST, "com/example/Calc.class", "maya/agent/Timings.class"]);
+
+		let calc = IRClassFile::read(&archive.get("com/example/Calc.class").unwrap().data).unwrap();
+		let methods = calc.methods.iter().map(|method| method.name()).collect::<Vec<_>>();
+		assert_eq!(methods, ["add", "add$timed", "reset", "reset$timed"]);
+		assert!(calc.methods[1]
+			.access_flags
+			.contains(MethodAccessFlags::PRIVATE | MethodAccessFlags::STATIC));
+		assert!(validate(&calc).is_empty());
+		check(&calc).unwrap();
+	}
+}
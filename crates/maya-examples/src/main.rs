@@ -0,0 +1,27 @@
+use std::{env, io::Write, path::Path};
+
+use eyre::bail;
+use maya_classfile_ir::staging;
+use maya_examples::{instrument_jar, timing::MethodMatcher};
+
+fn main() -> eyre::Result<()> {
+	let args = env::args().skip(1).collect::<Vec<_>>();
+	let [input, output, class_prefix, rest @ ..] = &args[..] else {
+		bail!("usage: maya-examples <input.jar> <output.jar> <class-prefix> [method]");
+	};
+	let matcher = MethodMatcher {
+		class_prefix: class_prefix.replace('.', "/"),
+		method: rest.first().cloned(),
+	};
+
+	// The output may be the input itself, which is only replaced once the whole jar was instrumented.
+	let mut metrics = None;
+	staging::rewrite_into(Path::new(input), Path::new(output), |input, out| {
+		let (jar, jar_metrics) = instrument_jar(input, matcher)?;
+		out.write_all(&jar)?;
+		metrics = Some(jar_metrics);
+		eyre::Ok(())
+	})?;
+	println!("{}", metrics.expect("set by a successful rewrite"));
+	Ok(())
+}
@@ -0,0 +1,275 @@
+// Timing instrumentation the way a java agent would add it. Each matching method is renamed to `<name>$timed` and made
+// private, and a new method with the original name, flags and attributes calls it and reports how long the call took
+// to `maya/agent/Timings.record`, whether it returned or threw. Wrapping leaves the original code with its frames and
+// debug info alone, only the wrapper is new code.
+
+use maya_classfile_ir::{
+	analysis::stack,
+	attribute::{Attributes, CodeAttribute, IRAttribute, IRAttributeInfo},
+	builder::{ClassBuilder, CodeBuilder},
+	class_pool::{CPClassRef, CPFieldRef, CPMethodRef, CPUtf8Ref, IRClassfileError, IRCpTag},
+	code::Instructions,
+	descriptor::{BaseType, FieldType, MethodDescriptor},
+	flags::{ClassAccessFlags, MethodAccessFlags},
+	transform::{ChangeLog, ChangeRecord, Transform},
+	IRClassFile, IRMethodInfo,
+};
+
+/// The class the wrappers report to, added to the jar by the pipeline.
+pub const TIMINGS_CLASS: &str = "maya/agent/Timings";
+
+/// Which methods to time: the ones in classes whose internal name starts with `class_prefix`, only those called
+/// `method` if given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodMatcher {
+	pub class_prefix: String,
+	pub method: Option<String>,
+}
+
+impl MethodMatcher {
+	fn matches(&self, class: &IRClassFile, method: &IRMethodInfo) -> bool {
+		let name = method.name();
+		// Constructors can't be wrapped, the object has to be initialized by the method called with `new`.
+		class.class_name().starts_with(&self.class_prefix)
+			&& self.method.as_deref().is_none_or(|wanted| wanted == name)
+			&& method.code().is_some()
+			&& !name.starts_with('<')
+			&& !name.ends_with("$timed")
+			&& !method.access_flags.is_bridge()
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct TimingTransform {
+	matcher: MethodMatcher,
+}
+
+impl TimingTransform {
+	pub fn new(matcher: MethodMatcher) -> Self {
+		Self { matcher }
+	}
+}
+
+impl Transform for TimingTransform {
+	fn name(&self) -> &str {
+		"timing"
+	}
+
+	fn apply(&mut self, class: &mut IRClassFile, log: &mut ChangeLog) -> Result<(), IRClassfileError> {
+		// Private interface methods need interface method refs, which this example leaves out.
+		if class.access_flags.is_interface() {
+			return Ok(());
+		}
+		let targets = (0..class.methods.len())
+			.filter(|i| self.matcher.matches(class, &class.methods[*i]))
+			.collect::<Vec<_>>();
+		// Back to front, so inserting wrappers doesn't move the methods still to do.
+		for i in targets.into_iter().rev() {
+			let (name, descriptor) = {
+				let method = &class.methods[i];
+				(method.name().to_string(), method.descriptor().to_string())
+			};
+			wrap(class, i)?;
+			log.record(ChangeRecord::Renamed {
+				from: format!("{name}{descriptor}"),
+				to: format!("{name}$timed{descriptor}"),
+			});
+		}
+		Ok(())
+	}
+}
+
+fn wrap(class: &mut IRClassFile, i: usize) -> Result<(), IRClassfileError> {
+	let class_name = class.class_name().to_string();
+	let cp = &mut class.cp;
+	let method = &mut class.methods[i];
+	let name = method.name().to_string();
+	let descriptor = method.descriptor().to_string();
+	let flags = method.access_flags;
+	let timed = format!("{name}$timed");
+
+	// The wrapper takes over everything callers and reflection see, annotations and signatures included.
+	let (code, declaration) = std::mem::take(&mut *method.attributes)
+		.into_iter()
+		.partition::<Vec<_>, _>(|attr| matches!(attr.attr, IRAttribute::Code(_)));
+	method.attributes = Attributes::from(code);
+	method.name = CPUtf8Ref::find_or_add(cp, &timed)?;
+	method.access_flags = MethodAccessFlags::PRIVATE
+		| MethodAccessFlags::SYNTHETIC
+		| (flags & (MethodAccessFlags::STATIC | MethodAccessFlags::SYNCHRONIZED | MethodAccessFlags::STRICT));
+
+	let code = wrapper_code(cp, &class_name, &name, &descriptor, flags.is_static())?;
+	let mut attributes = Attributes::from(declaration);
+	attributes.push(IRAttributeInfo {
+		name: CPUtf8Ref::find_or_add(cp, "Code")?,
+		length: 0,
+		attr: IRAttribute::Code(code),
+	});
+	let mut wrapper = IRMethodInfo {
+		access_flags: flags - MethodAccessFlags::SYNCHRONIZED,
+		name: CPUtf8Ref::find_or_add(cp, &name)?,
+		descriptor: CPUtf8Ref::find_or_add(cp, &descriptor)?,
+		attributes,
+	};
+	set_maxs(cp, &class_name, &mut wrapper)?;
+	class.methods.insert(i, wrapper);
+	Ok(())
+}
+
+/// Sets max_stack and max_locals of generated code to what it needs.
+pub fn set_maxs(cp: &[IRCpTag], class_name: &str, method: &mut IRMethodInfo) -> Result<(), IRClassfileError> {
+	if let Some((max_stack, max_locals)) = stack::compute_maxs(cp, class_name, method)? {
+		let code = method.attributes.code_mut().expect("compute_maxs found code");
+		code.max_stack = max_stack;
+		code.max_locals = max_locals;
+	}
+	Ok(())
+}
+
+// long start = System.nanoTime();
+// try { result = name$timed(args); } catch (Throwable t) { record(start); throw t; }
+// record(start); return result;
+fn wrapper_code(
+	cp: &mut Vec<IRCpTag>,
+	class_name: &str,
+	name: &str,
+	descriptor: &str,
+	is_static: bool,
+) -> Result<CodeAttribute, IRClassfileError> {
+	let parsed = MethodDescriptor::parse(descriptor)?;
+	let mut code = CodeBuilder::new(cp);
+	let nano_time = CPMethodRef::find_or_add(code.cp(), "java/lang/System", "nanoTime", "()J")?;
+	let record = CPMethodRef::find_or_add(code.cp(), TIMINGS_CLASS, "record", "(Ljava/lang/String;J)V")?;
+	let timed = CPMethodRef::find_or_add(code.cp(), class_name, &format!("{name}$timed"), descriptor)?;
+	let label = IRCpTag::String(CPUtf8Ref::find_or_add(
+		code.cp(),
+		&format!("{class_name}.{name}{descriptor}"),
+	)?);
+
+	let first_param = if is_static { 0 } else { 1 };
+	let start = first_param + parsed.param_slots();
+	// the return value, or the exception on the way out
+	let result = start + 2;
+	let report = |code: &mut CodeBuilder| {
+		code.insn(Instructions::LDC(label.clone()))
+			.insn(Instructions::INVOKESTATIC(nano_time.clone()))
+			.insn(Instructions::LLOAD(start))
+			.insn(Instructions::LSUB)
+			.insn(Instructions::INVOKESTATIC(record.clone()));
+	};
+
+	code.insn(Instructions::INVOKESTATIC(nano_time.clone()))
+		.insn(Instructions::LSTORE(start));
+	let (body, end, handler) = (code.mark(), code.new_label(), code.new_label());
+	if !is_static {
+		code.insn(Instructions::ALOAD(0));
+	}
+	let mut slot = first_param;
+	for param in &parsed.params {
+		code.insn(load(param, slot));
+		slot += param.slots();
+	}
+	code.insn(match is_static {
+		true => Instructions::INVOKESTATIC(timed),
+		false => Instructions::INVOKESPECIAL(timed),
+	});
+	if let Some(ret) = &parsed.ret {
+		code.insn(store(ret, result));
+	}
+	code.bind(end)?;
+	report(&mut code);
+	match &parsed.ret {
+		Some(ret) => code.insn(load(ret, result)).insn(return_insn(ret)),
+		None => code.insn(Instructions::RETURN),
+	};
+
+	code.bind(handler)?.insn(Instructions::ASTORE(result));
+	report(&mut code);
+	code.insn(Instructions::ALOAD(result))
+		.insn(Instructions::ATHROW)
+		.try_catch(body, end, handler, None)?;
+	code.build()
+}
+
+fn load(ty: &FieldType, slot: u16) -> Instructions {
+	match ty {
+		FieldType::Base(BaseType::Long) => Instructions::LLOAD(slot),
+		FieldType::Base(BaseType::Float) => Instructions::FLOAD(slot),
+		FieldType::Base(BaseType::Double) => Instructions::DLOAD(slot),
+		FieldType::Base(_) => Instructions::ILOAD(slot),
+		FieldType::Object(_) | FieldType::Array(_) => Instructions::ALOAD(slot),
+	}
+}
+
+fn store(ty: &FieldType, slot: u16) -> Instructions {
+	match ty {
+		FieldType::Base(BaseType::Long) => Instructions::LSTORE(slot),
+		FieldType::Base(BaseType::Float) => Instructions::FSTORE(slot),
+		FieldType::Base(BaseType::Double) => Instructions::DSTORE(slot),
+		FieldType::Base(_) => Instructions::ISTORE(slot),
+		FieldType::Object(_) | FieldType::Array(_) => Instructions::ASTORE(slot),
+	}
+}
+
+fn return_insn(ty: &FieldType) -> Instructions {
+	match ty {
+		FieldType::Base(BaseType::Long) => Instructions::LRETURN,
+		FieldType::Base(BaseType::Float) => Instructions::FRETURN,
+		FieldType::Base(BaseType::Double) => Instructions::DRETURN,
+		FieldType::Base(_) => Instructions::IRETURN,
+		FieldType::Object(_) | FieldType::Array(_) => Instructions::ARETURN,
+	}
+}
+
+/// `public final class Timings { public static void record(String method, long nanos) }`, printing each call to
+/// System.err. A real agent would aggregate them.
+pub fn timings_class() -> Result<IRClassFile, IRClassfileError> {
+	let mut builder = ClassBuilder::new(TIMINGS_CLASS)?;
+	builder.access_flags(ClassAccessFlags::PUBLIC | ClassAccessFlags::FINAL | ClassAccessFlags::SUPER);
+
+	let string_builder = "java/lang/StringBuilder";
+	let mut code = CodeBuilder::new(builder.cp());
+	let err = CPFieldRef::find_or_add(code.cp(), "java/lang/System", "err", "Ljava/io/PrintStream;")?;
+	let new = CPClassRef::find_or_add(code.cp(), string_builder)?;
+	let init = CPMethodRef::find_or_add(code.cp(), string_builder, "<init>", "()V")?;
+	let append_string = CPMethodRef::find_or_add(
+		code.cp(),
+		string_builder,
+		"append",
+		"(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+	)?;
+	let append_long = CPMethodRef::find_or_add(code.cp(), string_builder, "append", "(J)Ljava/lang/StringBuilder;")?;
+	let to_string = CPMethodRef::find_or_add(code.cp(), string_builder, "toString", "()Ljava/lang/String;")?;
+	let println = CPMethodRef::find_or_add(code.cp(), "java/io/PrintStream", "println", "(Ljava/lang/String;)V")?;
+	let took = IRCpTag::String(CPUtf8Ref::find_or_add(code.cp(), " took ")?);
+	let ns = IRCpTag::String(CPUtf8Ref::find_or_add(code.cp(), " ns")?);
+	code.insn(Instructions::GETSTATIC(err))
+		.insn(Instructions::NEW(new))
+		.insn(Instructions::DUP)
+		.insn(Instructions::INVOKESPECIAL(init))
+		.insn(Instructions::ALOAD(0))
+		.insn(Instructions::INVOKEVIRTUAL(append_string.clone()))
+		.insn(Instructions::LDC(took))
+		.insn(Instructions::INVOKEVIRTUAL(append_string.clone()))
+		.insn(Instructions::LLOAD(1))
+		.insn(Instructions::INVOKEVIRTUAL(append_long))
+		.insn(Instructions::LDC(ns))
+		.insn(Instructions::INVOKEVIRTUAL(append_string))
+		.insn(Instructions::INVOKEVIRTUAL(to_string))
+		.insn(Instructions::INVOKEVIRTUAL(println))
+		.insn(Instructions::RETURN);
+	let code = code.build()?;
+	builder.method(
+		MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+		"record",
+		"(Ljava/lang/String;J)V",
+		[IRAttribute::Code(code)],
+	)?;
+
+	let mut class = builder.build();
+	let class_name = class.class_name().to_string();
+	for method in &mut class.methods {
+		set_maxs(&class.cp, &class_name, method)?;
+	}
+	Ok(class)
+}